@@ -83,6 +83,8 @@ impl From<jsonwebtoken::errors::Error> for AuthError {
     fn from(value: jsonwebtoken::errors::Error) -> Self {
         use jsonwebtoken::errors::ErrorKind::*;
 
+        // ErrorKind 是 non_exhaustive 的，新增的种类统一视为内部错误
+        let unknown = value.to_string();
         match value.into_kind() {
             ExpiredSignature => AuthError::TokenExpired,
             InvalidSignature => AuthError::InvalidSignature,
@@ -104,14 +106,15 @@ impl From<jsonwebtoken::errors::Error> for AuthError {
             InvalidAlgorithm => AuthError::InternalError("the algorithm in the header doesn't match the one passed to decode or the encoding/decoding key used doesn't match the alg requested".to_string()),
             MissingAlgorithm => AuthError::InternalError("the Validation struct does not contain at least 1 algorithm".into()),
             Crypto(e) => AuthError::InternalError(format!("Something unspecified went wrong with crypto: {e}")),
-            _ => todo!()
+            _ => AuthError::InternalError(unknown),
         }
     }
 }
 
-impl IntoResponse for AuthError {
-    fn into_response(self) -> Response {
-        let status_code = match self {
+impl AuthError {
+    /// 这个错误对应的 HTTP 状态码，除了权限不足之外都是 401
+    pub fn status_code(&self) -> StatusCode {
+        match self {
             AuthError::MissingAuthHeader
             | AuthError::InvalidKeyId
             | AuthError::InvalidAuthFormat
@@ -132,9 +135,39 @@ impl IntoResponse for AuthError {
             AuthError::InsufficientPermissions => StatusCode::FORBIDDEN,
 
             AuthError::InternalError(_) => StatusCode::UNAUTHORIZED,
-        };
+        }
+    }
+
+    /// ## 这个错误稳定的代码
+    ///
+    /// 服务端只把它写入日志，响应中不透露鉴权失败的细节；与变体的名称分开写，重命名变体不会改变它
+    pub const fn error_code(&self) -> &'static str {
+        match self {
+            AuthError::MissingAuthHeader => "missingAuthHeader",
+            AuthError::InvalidAlgorithm(_) => "invalidAlgorithm",
+            AuthError::InvalidAuthFormat => "invalidAuthFormat",
+            AuthError::InvalidKeyId => "invalidKeyId",
+            AuthError::InvalidUtf8(_) => "invalidUtf8",
+            AuthError::InvalidJson(_) => "invalidJson",
+            AuthError::InvalidBase64(_) => "invalidBase64",
+            AuthError::InvalidToken => "invalidToken",
+            AuthError::TokenExpired => "tokenExpired",
+            AuthError::TokenNotYetValid => "tokenNotYetValid",
+            AuthError::InvalidSignature => "invalidSignature",
+            AuthError::InvalidIssuer => "invalidIssuer",
+            AuthError::InvalidAudience => "invalidAudience",
+            AuthError::InvalidSubject => "invalidSubject",
+            AuthError::MissingClaim(_) => "missingClaim",
+            AuthError::InsufficientPermissions => "insufficientPermissions",
+            AuthError::TokenRevoked => "tokenRevoked",
+            AuthError::InternalError(_) => "internalError",
+        }
+    }
+}
 
-        status_code.into_response()
+impl IntoResponse for AuthError {
+    fn into_response(self) -> Response {
+        self.status_code().into_response()
     }
 }
 
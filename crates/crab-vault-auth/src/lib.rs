@@ -1,9 +1,13 @@
 pub mod error;
+pub mod pattern;
+pub mod policy;
+#[cfg(feature = "server-side")]
+pub mod revocation;
 
 use clap::ValueEnum;
 use jsonwebtoken::{Algorithm, EncodingKey, Header};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::vec;
 use uuid::Uuid;
 use validator::{Validate, ValidationError};
@@ -14,8 +18,16 @@ use base64::Engine;
 use glob::Pattern;
 #[cfg(feature = "server-side")]
 use jsonwebtoken::{DecodingKey, Validation};
+#[cfg(feature = "server-side")]
+use std::sync::Arc;
+
+#[cfg(feature = "server-side")]
+use crate::revocation::RevocationStore;
 
-use crate::error::AuthError;
+use crate::{error::AuthError, pattern::GlobLimits};
+
+/// 预签名 URL 中携带令牌的查询参数名
+pub const PRESIGN_QUERY_KEY: &str = "token";
 
 #[derive(Clone)]
 pub struct JwtEncoder {
@@ -39,6 +51,10 @@ pub struct JwtDecoder {
     /// 用于配置如何验证 `exp`, `nbf`, `iss`, `aud` 等标准声明。
     #[cfg(feature = "server-side")]
     validation: Validation,
+
+    /// 已吊销的令牌，没有设置时不做检查
+    #[cfg(feature = "server-side")]
+    revocations: Option<Arc<dyn RevocationStore>>,
 }
 
 /// ## 表示一个完整的 JWT，包含标准声明和自定义载荷。
@@ -83,7 +99,9 @@ pub struct Permission {
     /// 定义此令牌可以访问的资源路径，支持通配符 `*` 和 `?` (Glob 模式)。
     ///
     /// 如果是 None，那么表示这个令牌没有任何对象的操作权限
-    #[validate(length(max = 128))]
+    ///
+    /// **复杂度有限制，参见 [`GlobLimits`]**
+    #[validate(custom(function = "Self::validate_resource_pattern"))]
     pub resource_pattern: Option<String>,
 
     /// ## 允许上传的最大对象大小 (字节)。
@@ -95,9 +113,79 @@ pub struct Permission {
     ///
     /// 支持通配符，例如 `image/*` 或 `*` (Glob 模式)。
     ///
-    /// **大小有限制，每一个通配模式不超过 128 字节、最多 8 个模式**
+    /// **大小有限制，最多 8 个模式，每一个通配模式的复杂度参见 [`GlobLimits`]**
     #[validate(custom(function = "Self::validate_content_type_pattern"))]
     pub allowed_content_types: Vec<String>,
+
+    /// ## 明确禁止的操作列表。
+    ///
+    /// 与 `denied_resource_pattern` 一起构成一条禁止规则，空列表表示所有方法，参见 [`CompiledPermission::denies`]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub denied_methods: Vec<HttpMethod>,
+
+    /// ## 明确禁止访问的资源路径模式。
+    ///
+    /// 与 `denied_methods` 一起构成一条禁止规则，[`None`] 表示所有路径，参见 [`CompiledPermission::denies`]
+    ///
+    /// **复杂度有限制，参见 [`GlobLimits`]**
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[validate(custom(function = "Self::validate_resource_pattern"))]
+    pub denied_resource_pattern: Option<String>,
+
+    /// ## 是否允许列出 bucket 中的 object。
+    ///
+    /// object 的名称本身也可能是敏感的，为 `false` 时只能访问已知名称的 object，参见 [`CompiledPermissionSet::allows_listing`]。
+    ///
+    /// 之前签发的令牌中没有这个字段，视为 `true`，与之前的行为相同；为 `true` 时也不会被序列化
+    #[serde(default = "Permission::listable", skip_serializing_if = "Permission::is_listable")]
+    pub allow_list: bool,
+
+    /// ## object 的标签条件。
+    ///
+    /// 标签的键到值的通配模式，object 带有其中所有的标签、并且值都与模式匹配时这条权限才生效，
+    /// 还不存在的 object 没有标签。空表示不检查标签，参见 [`CompiledPermission::resolve_tags`]
+    ///
+    /// **最多 8 个条件，每一个通配模式的复杂度参见 [`GlobLimits`]**
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    #[validate(custom(function = "Self::validate_tag_patterns"))]
+    pub tags: BTreeMap<String, String>,
+
+    /// ## 方法和路径之外额外授予的操作。
+    ///
+    /// 只作用于这条权限允许的方法和路径，参见 [`CompiledPermissionSet::allows_capability`]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub capabilities: Vec<Capability>,
+}
+
+/// 一个令牌最多携带的权限条数，参见 [`PermissionSet::compile_with_limits`]
+pub const MAX_PERMISSIONS: usize = 16;
+
+/// ## 令牌中的一组权限
+///
+/// 一个请求只要被其中 **任意一条** 权限完整地允许（方法、路径、大小、内容类型都满足）即可，
+/// 不会把不同权限的条件拼凑起来。
+///
+/// 载荷既可以是单个 [`Permission`] 对象，也可以是它们组成的数组。只有一条权限时序列化为单个对象，
+/// 这样签发出来的令牌与只认识 `Jwt<Permission>` 的旧版本兼容
+#[derive(Serialize, Deserialize, Validate, Clone, Debug, PartialEq)]
+#[serde(from = "PermissionSetRepr", into = "PermissionSetRepr")]
+pub struct PermissionSet {
+    /// 至少一条，最多 [`MAX_PERMISSIONS`] 条
+    #[validate(length(min = 1, max = 16), nested)]
+    pub permissions: Vec<Permission>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum PermissionSetRepr {
+    One(Permission),
+    Many(Vec<Permission>),
+}
+
+#[cfg(feature = "server-side")]
+#[derive(Clone)]
+pub struct CompiledPermissionSet {
+    pub permissions: Vec<CompiledPermission>,
 }
 
 #[cfg(feature = "server-side")]
@@ -107,8 +195,16 @@ pub struct CompiledPermission {
     pub resource_pattern: Option<String>,
     pub max_size: Option<usize>,
     pub allowed_content_types: Vec<String>,
+    pub denied_methods: Vec<HttpMethod>,
+    pub denied_resource_pattern: Option<String>,
+    pub allow_list: bool,
+    pub tags: BTreeMap<String, String>,
+    pub capabilities: Vec<Capability>,
     resource_pattern_cache: Option<Pattern>,
     allowed_content_types_cache: Vec<Pattern>,
+    denied_resource_pattern_cache: Option<Pattern>,
+    tags_cache: Vec<(String, Option<Pattern>)>,
+    tags_satisfied: bool,
 }
 
 /// HTTP 操作方法枚举。
@@ -136,6 +232,18 @@ pub enum HttpMethod {
     Unsafe,
 }
 
+/// ## 影响远超一次请求的操作
+///
+/// 仅仅允许请求的方法和路径还不够，令牌中的权限必须明确地列出它们
+#[derive(Serialize, Deserialize, PartialEq, Eq, Hash, Clone, Copy, Debug, ValueEnum)]
+#[serde(rename_all = "camelCase")]
+pub enum Capability {
+    /// 连同其中所有的 object 一起删除 bucket，需要同时允许 `DELETE` 这个 bucket
+    ForceDelete,
+    /// 解除 object 的合法保留，需要同时允许 `PUT` 这个 object
+    LegalHold,
+}
+
 impl JwtEncoder {
     #[inline]
     pub fn new(encoding_key: HashMap<String, (EncodingKey, Algorithm)>) -> Self {
@@ -194,6 +302,8 @@ impl JwtDecoder {
     /// - [`possible_audience`](JwtDecoder::possible_audience)
     /// - [`leeway`](JwtDecoder::leeway)
     /// - [`reject_tokens_expiring_in_less_than`](JwtDecoder::reject_tokens_expiring_in_less_than)
+    /// - [`revocation_store`](JwtDecoder::revocation_store)
+    /// - [`accept_key`](JwtDecoder::accept_key)
     ///
     /// ### 然后可以使用方法 [`decode`](JwtDecoder::decode) 来解码、校验一个 jwt
     ///
@@ -224,6 +334,7 @@ impl JwtDecoder {
         Self {
             decoding_keys: mapping,
             validation,
+            revocations: None,
         }
     }
 
@@ -271,12 +382,48 @@ impl JwtDecoder {
         self
     }
 
+    /// ## 在保留现有密钥的同时额外接受一个密钥
+    ///
+    /// 用于密钥轮换的宽限期：新的配置中已经没有这个密钥，但用它签发的令牌还没有过期。
+    /// `iss` 和 `algorithm` 也会被加入到接受的签发者和算法中；(iss, kid) 已经存在时不会覆盖
+    pub fn accept_key(
+        mut self,
+        iss: &str,
+        kid: &str,
+        algorithm: Algorithm,
+        key: DecodingKey,
+    ) -> Self {
+        self.decoding_keys
+            .entry((iss.to_string(), kid.to_string()))
+            .or_insert(key);
+
+        if !self.validation.algorithms.contains(&algorithm) {
+            self.validation.algorithms.push(algorithm);
+        }
+
+        if let Some(issuers) = &mut self.validation.iss {
+            issuers.insert(iss.to_string());
+        }
+
+        self
+    }
+
+    /// ## 设置吊销列表
+    ///
+    /// 通过了签名和时间验证，但 `jti` 在吊销列表中的令牌会被拒绝
+    #[inline]
+    pub fn revocation_store(mut self, store: Arc<dyn RevocationStore>) -> Self {
+        self.revocations = Some(store);
+        self
+    }
+
     /// ## 使用给定的配置解码并验证一个字符串形式的 Token。
     ///
     /// 此函数会执行完整的验证流程，包括：
     /// 1. 检查签名是否有效。
     /// 2. 验证 `exp` 和 `nbf` 时间戳。
     /// 3. 根据 `config.validation` 中的设置验证 `iss` 和 `aud`。
+    /// 4. 如果设置了吊销列表，检查 `jti` 是否已被吊销。
     ///
     /// ### 泛型参数说明
     ///
@@ -342,7 +489,17 @@ impl JwtDecoder {
             .get(&(body_unchecked.iss, kid))
             .ok_or(AuthError::InvalidIssuer)?;
 
-        Ok(jsonwebtoken::decode::<Jwt<P>>(token, key, &self.validation)?.claims)
+        let jwt = jsonwebtoken::decode::<Jwt<P>>(token, key, &self.validation)?.claims;
+
+        if self
+            .revocations
+            .as_ref()
+            .is_some_and(|v| v.is_revoked(&jwt.jti))
+        {
+            return Err(AuthError::TokenRevoked);
+        }
+
+        Ok(jwt)
     }
 
     /// ## **\[不安全\]** 在不验证签名的情况下解码 JWT 的载荷。
@@ -448,14 +605,42 @@ impl Default for Permission {
 }
 
 impl Permission {
-    fn validate_content_type_pattern(patterns: &[String]) -> Result<(), ValidationError> {
-        if patterns.len() <= 8 && patterns.iter().all(|s| s.len() <= 128) {
+    fn validate_resource_pattern(pattern: &str) -> Result<(), ValidationError> {
+        GlobLimits::default()
+            .check(pattern)
+            .map_err(|_| ValidationError::new("pattern too complex for matching"))
+    }
+
+    pub(crate) fn validate_content_type_pattern(
+        patterns: &[String],
+    ) -> Result<(), ValidationError> {
+        let limits = GlobLimits::default();
+        if patterns.len() <= 8 && patterns.iter().all(|s| limits.check(s).is_ok()) {
             Ok(())
         } else {
             Err(ValidationError::new("pattern too long/much for parsing"))
         }
     }
 
+    pub(crate) fn validate_tag_patterns(
+        patterns: &BTreeMap<String, String>,
+    ) -> Result<(), ValidationError> {
+        let limits = GlobLimits::default();
+        if patterns.len() <= 8 && patterns.values().all(|s| limits.check(s).is_ok()) {
+            Ok(())
+        } else {
+            Err(ValidationError::new("tag pattern too long/much for parsing"))
+        }
+    }
+
+    fn listable() -> bool {
+        true
+    }
+
+    fn is_listable(allow_list: &bool) -> bool {
+        *allow_list
+    }
+
     #[inline]
     pub const fn new() -> Self {
         Self::new_minimum()
@@ -471,12 +656,19 @@ impl Permission {
     /// - 允许资源: [`Some("*".to_string())`](Some) (所有路径)
     /// - 大小限制：[`None`]
     /// - MIME: **所有**
+    /// - 列出 object：允许
+    /// - 额外的操作：[`Capability::ALL`]
     pub fn new_root() -> Self {
         Self {
             methods: vec![HttpMethod::All],
             resource_pattern: Some("*".to_string()),
             max_size: None,
             allowed_content_types: vec!["*".to_string()],
+            denied_methods: vec![],
+            denied_resource_pattern: None,
+            allow_list: true,
+            tags: BTreeMap::new(),
+            capabilities: Capability::ALL.to_vec(),
         }
     }
 
@@ -490,12 +682,44 @@ impl Permission {
     /// - 允许资源: [`None`] (所有路径都不允许)
     /// - 大小限制：[`Some(0)`](Some) (上传的最大包大小为 0 字节)
     /// - MIME: **所有都不行**
+    /// - 列出 object：不允许
+    /// - 额外的操作：无
     pub const fn new_minimum() -> Self {
         Self {
             methods: vec![],
             resource_pattern: None,
             max_size: Some(0),
             allowed_content_types: vec![],
+            denied_methods: vec![],
+            denied_resource_pattern: None,
+            allow_list: false,
+            tags: BTreeMap::new(),
+            capabilities: vec![],
+        }
+    }
+
+    /// 创建一个用于 <u>**预签名 URL**</u> 的 `Permission`
+    ///
+    /// 只允许对 `path` 这一个资源执行 `method`，`path` 中的通配符会被转义，
+    /// 所以 `path` 应当与服务端看到的请求路径完全一致（包括百分号编码）
+    ///
+    /// - 允许操作: `method`
+    /// - 允许资源: 只有 `path`
+    /// - 大小限制：[`None`]
+    /// - MIME: **所有**
+    /// - 列出 object：不允许
+    /// - 额外的操作：无
+    pub fn new_presigned(method: HttpMethod, path: &str) -> Self {
+        Self {
+            methods: vec![method],
+            resource_pattern: Some(glob::Pattern::escape(path)),
+            max_size: None,
+            allowed_content_types: vec!["*".to_string()],
+            denied_methods: vec![],
+            denied_resource_pattern: None,
+            allow_list: false,
+            tags: BTreeMap::new(),
+            capabilities: vec![],
         }
     }
 
@@ -548,41 +772,327 @@ impl Permission {
         self
     }
 
+    /// 明确禁止的 operations，与 [`deny_resource_pattern`](Permission::deny_resource_pattern) 一起构成禁止规则
+    ///
+    /// 注意这会**更换**，而不是添加
+    #[inline]
+    pub fn deny_method(mut self, methods: Vec<HttpMethod>) -> Self {
+        self.denied_methods = methods;
+        self
+    }
+
+    /// 明确禁止访问的资源路径，即使它也能被 `resource_pattern` 匹配
+    #[inline]
+    pub fn deny_resource_pattern<T>(mut self, pattern: T) -> Self
+    where
+        T: Into<String>,
+    {
+        self.denied_resource_pattern = Some(pattern.into());
+        self
+    }
+
+    #[inline]
+    pub fn deny_resource_pattern_option<T>(mut self, pattern: Option<T>) -> Self
+    where
+        T: Into<String>,
+    {
+        self.denied_resource_pattern = pattern.map(T::into);
+        self
+    }
+
+    /// 是否允许列出 bucket 中的 object
+    #[inline]
+    pub const fn permit_list(mut self, allowed: bool) -> Self {
+        self.allow_list = allowed;
+        self
+    }
+
+    /// 要求 object 带有标签 `key`，并且它的值与 `pattern` 匹配
+    #[inline]
+    pub fn require_tag<K, V>(mut self, key: K, pattern: V) -> Self
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.tags.insert(key.into(), pattern.into());
+        self
+    }
+
+    /// 更换所有的标签条件
+    #[inline]
+    pub fn require_tags(mut self, tags: BTreeMap<String, String>) -> Self {
+        self.tags = tags;
+        self
+    }
+
+    /// 额外授予的操作
+    ///
+    /// 注意这会**更换**，而不是添加
+    #[inline]
+    pub fn permit_capability(mut self, capabilities: Vec<Capability>) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    /// 使用默认的 [`GlobLimits`] 编译这个权限，参见 [`compile_with_limits`](Permission::compile_with_limits)
     #[cfg(feature = "server-side")]
+    #[inline]
     pub fn compile(self) -> CompiledPermission {
+        self.compile_with_limits(&GlobLimits::default())
+    }
+
+    /// 编译这个权限中的通配模式
+    ///
+    /// 超出 `limits` 或者不合法的模式会被丢弃，也就是说它们什么都匹配不上。
+    /// 禁止规则中的模式例外，它无法编译时视为禁止所有路径，而不是什么都不禁止
+    #[cfg(feature = "server-side")]
+    pub fn compile_with_limits(self, limits: &GlobLimits) -> CompiledPermission {
         let Permission {
             methods,
             resource_pattern,
             max_size,
             allowed_content_types,
+            denied_methods,
+            denied_resource_pattern,
+            allow_list,
+            tags,
+            capabilities,
         } = self;
 
-        let resource_pattern_cache = match &resource_pattern {
-            Some(pat) => Pattern::new(pat).ok(),
-            None => None,
-        };
+        let resource_pattern_cache = resource_pattern.as_deref().and_then(|v| limits.compile(v));
 
-        let mut allowed_content_types_cache = vec![];
+        let denied_resource_pattern_cache = denied_resource_pattern.as_deref().map(|v| {
+            limits
+                .compile(v)
+                .unwrap_or_else(|| Pattern::new("*").expect("`*` is a valid pattern"))
+        });
 
-        for pat in &allowed_content_types {
-            if let Ok(pat) = Pattern::new(pat) {
-                allowed_content_types_cache.push(pat)
-            }
-        }
+        let allowed_content_types_cache = allowed_content_types
+            .iter()
+            .filter_map(|v| limits.compile(v))
+            .collect();
+
+        let tags_cache = tags
+            .iter()
+            .map(|(key, pattern)| (key.clone(), limits.compile(pattern)))
+            .collect();
 
         CompiledPermission {
             methods,
             resource_pattern,
             max_size,
             allowed_content_types,
+            denied_methods,
+            denied_resource_pattern,
+            allow_list,
+            tags_satisfied: tags.is_empty(),
+            tags,
+            capabilities,
             resource_pattern_cache,
             allowed_content_types_cache,
+            denied_resource_pattern_cache,
+            tags_cache,
+        }
+    }
+}
+
+impl PermissionSet {
+    #[inline]
+    pub fn new(permissions: Vec<Permission>) -> Self {
+        Self { permissions }
+    }
+
+    /// 只包含一条 [`Permission::new_root`] 的权限集合
+    #[inline]
+    pub fn new_root() -> Self {
+        Permission::new_root().into()
+    }
+
+    /// 再授予一条权限
+    #[inline]
+    pub fn grant(mut self, permission: Permission) -> Self {
+        self.permissions.push(permission);
+        self
+    }
+
+    /// 使用默认的 [`GlobLimits`] 编译这组权限，参见 [`compile_with_limits`](PermissionSet::compile_with_limits)
+    #[cfg(feature = "server-side")]
+    #[inline]
+    pub fn compile(self) -> CompiledPermissionSet {
+        self.compile_with_limits(&GlobLimits::default())
+    }
+
+    /// 编译其中的每一条权限
+    ///
+    /// 只保留前 [`MAX_PERMISSIONS`] 条，与超出限制的通配模式一样，多出来的权限什么都不允许
+    #[cfg(feature = "server-side")]
+    pub fn compile_with_limits(self, limits: &GlobLimits) -> CompiledPermissionSet {
+        CompiledPermissionSet {
+            permissions: self
+                .permissions
+                .into_iter()
+                .take(MAX_PERMISSIONS)
+                .map(|v| v.compile_with_limits(limits))
+                .collect(),
+        }
+    }
+}
+
+impl From<Permission> for PermissionSet {
+    fn from(value: Permission) -> Self {
+        Self::new(vec![value])
+    }
+}
+
+impl From<Vec<Permission>> for PermissionSet {
+    fn from(value: Vec<Permission>) -> Self {
+        Self::new(value)
+    }
+}
+
+impl FromIterator<Permission> for PermissionSet {
+    fn from_iter<T: IntoIterator<Item = Permission>>(iter: T) -> Self {
+        Self::new(iter.into_iter().collect())
+    }
+}
+
+impl From<PermissionSetRepr> for PermissionSet {
+    fn from(value: PermissionSetRepr) -> Self {
+        match value {
+            PermissionSetRepr::One(permission) => permission.into(),
+            PermissionSetRepr::Many(permissions) => permissions.into(),
+        }
+    }
+}
+
+impl From<PermissionSet> for PermissionSetRepr {
+    fn from(mut value: PermissionSet) -> Self {
+        match value.permissions.len() {
+            1 => Self::One(value.permissions.remove(0)),
+            _ => Self::Many(value.permissions),
+        }
+    }
+}
+
+#[cfg(feature = "server-side")]
+impl CompiledPermissionSet {
+    /// 能够对 `path` 执行 `method` 的那些权限，后续的大小和内容类型检查只应当在它们之中进行
+    pub fn matching<'a>(
+        &'a self,
+        method: HttpMethod,
+        path: &'a str,
+    ) -> impl Iterator<Item = &'a CompiledPermission> {
+        self.permissions
+            .iter()
+            .filter(move |v| v.permits(method, path))
+    }
+
+    /// 是否有任意一条权限能够对 `path` 执行 `method`
+    pub fn allows(&self, method: HttpMethod, path: &str) -> bool {
+        self.matching(method, path).next().is_some()
+    }
+
+    /// 是否允许列出 object，`path` 是 `/{bucket}/{prefix}`
+    ///
+    /// 需要有一条权限同时允许 `GET` 这个路径和列出 object，也就是说只能列出令牌能够读取的前缀
+    pub fn allows_listing(&self, path: &str) -> bool {
+        self.matching(HttpMethod::Get, path).any(|v| v.allow_list)
+    }
+
+    /// 是否有一条权限在允许对 `path` 执行 `method` 的同时授予了 `capability`
+    pub fn allows_capability(
+        &self,
+        capability: Capability,
+        method: HttpMethod,
+        path: &str,
+    ) -> bool {
+        self.matching(method, path)
+            .any(|v| v.capabilities.contains(&capability))
+    }
+
+    /// 检查对 `path` 执行 `method` 时 `size` 是否在限制之内
+    ///
+    /// 没有任何一条权限匹配 `method` 和 `path` 时（例如鉴权中间件不检查路径的 bucket 级别的请求），
+    /// 只要任意一条权限的限制允许即可
+    pub fn check_size(&self, method: HttpMethod, path: &str, size: usize) -> bool {
+        let mut matching = self.matching(method, path).peekable();
+        match matching.peek() {
+            Some(_) => matching.any(|v| v.check_size(size)),
+            None => self.permissions.iter().any(|v| v.check_size(size)),
+        }
+    }
+
+    /// 是否有权限带有标签条件，没有时不需要读取 object 的标签
+    pub fn needs_tags(&self) -> bool {
+        self.permissions.iter().any(CompiledPermission::needs_tags)
+    }
+
+    /// 对其中的每一条权限调用 [`CompiledPermission::resolve_tags`]
+    pub fn resolve_tags(&mut self, tags: &BTreeMap<String, String>) {
+        for permission in &mut self.permissions {
+            permission.resolve_tags(tags);
         }
     }
 }
 
 #[cfg(feature = "server-side")]
 impl CompiledPermission {
+    /// ## 检查此权限是否允许对 `path` 执行 `method`。
+    ///
+    /// 先检查禁止规则，被禁止时即使 `methods` 和 `resource_pattern` 允许也返回 `false`
+    ///
+    /// 带有标签条件的权限在 [`resolve_tags`](CompiledPermission::resolve_tags) 确认标签满足之前不允许任何请求
+    pub fn permits(&self, method: HttpMethod, path: &str) -> bool {
+        self.tags_satisfied
+            && !self.denies(method, path)
+            && self.can_perform_method(method)
+            && self.can_access(path)
+    }
+
+    /// 是否带有标签条件
+    #[inline]
+    pub fn needs_tags(&self) -> bool {
+        !self.tags_cache.is_empty()
+    }
+
+    /// ## 根据 object 的标签判断标签条件是否满足。
+    ///
+    /// `tags` 是请求的 object 现有的标签，每一个条件的键都要存在，值都要与模式匹配；
+    /// 无法编译的模式什么都匹配不上
+    pub fn resolve_tags(&mut self, tags: &BTreeMap<String, String>) {
+        self.tags_satisfied = self.tags_cache.iter().all(|(key, pattern)| {
+            pattern
+                .as_ref()
+                .zip(tags.get(key))
+                .is_some_and(|(pattern, value)| pattern.matches(value))
+        });
+    }
+
+    /// ## 检查禁止规则是否命中。
+    ///
+    /// `denied_methods` 和 `denied_resource_pattern` 都没有设置时没有禁止规则；否则方法和路径都命中时才算命中，
+    /// 其中空的 `denied_methods` 命中所有方法，[`None`] 的 `denied_resource_pattern` 命中所有路径。
+    ///
+    /// `denied_methods` 中的 [`All`](HttpMethod::All)、[`Safe`](HttpMethod::Safe)、[`Unsafe`](HttpMethod::Unsafe)
+    /// 与 [`can_perform_method`](CompiledPermission::can_perform_method) 中的含义相同
+    pub fn denies(&self, method: HttpMethod, path: &str) -> bool {
+        if self.denied_methods.is_empty() && self.denied_resource_pattern_cache.is_none() {
+            return false;
+        }
+
+        let method_denied = self.denied_methods.is_empty()
+            || self.denied_methods.contains(&HttpMethod::All)
+            || self.denied_methods.contains(&method)
+            || (self.denied_methods.contains(&HttpMethod::Safe) && method.safe())
+            || (self.denied_methods.contains(&HttpMethod::Unsafe) && !method.safe());
+
+        method_denied
+            && self
+                .denied_resource_pattern_cache
+                .as_ref()
+                .is_none_or(|pat| pat.matches(path))
+    }
+
     /// ## 检查此权限是否允许执行给定的 HTTP 方法。
     ///
     /// 此方法会依次检查：
@@ -655,6 +1165,11 @@ impl From<axum::http::Method> for HttpMethod {
     }
 }
 
+impl Capability {
+    /// 所有的操作，[`Permission::new_root`] 拥有它们
+    pub const ALL: &[Capability] = &[Capability::ForceDelete, Capability::LegalHold];
+}
+
 impl HttpMethod {
     /// ## 判断一个方法是否安全
     ///
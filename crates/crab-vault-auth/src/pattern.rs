@@ -0,0 +1,86 @@
+use thiserror::Error;
+
+#[cfg(feature = "server-side")]
+use glob::Pattern;
+
+/// ## 通配模式的复杂度限制
+///
+/// 鉴权时每个请求都要做通配匹配，过长或者通配符过多的模式会让匹配过程中的回溯变得很慢，
+/// 所以所有的通配模式（资源路径、内容类型、路径规则）在编译之前都要通过这里的检查
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GlobLimits {
+    /// 模式的最大字节数
+    pub max_len: usize,
+
+    /// 模式中最多能出现的通配符个数，连续的 `*` 视为一个，每个 `?` 和 `[...]` 各算一个
+    pub max_wildcards: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum GlobLimitError {
+    #[error("pattern is {len} bytes long, at most {max} bytes are allowed")]
+    TooLong { len: usize, max: usize },
+
+    #[error("pattern contains {count} wildcards, at most {max} are allowed")]
+    TooManyWildcards { count: usize, max: usize },
+}
+
+impl Default for GlobLimits {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            max_len: 128,
+            max_wildcards: 8,
+        }
+    }
+}
+
+impl GlobLimits {
+    /// 检查一个模式是否超出了限制
+    pub fn check(&self, pattern: &str) -> Result<(), GlobLimitError> {
+        if pattern.len() > self.max_len {
+            return Err(GlobLimitError::TooLong {
+                len: pattern.len(),
+                max: self.max_len,
+            });
+        }
+
+        let count = wildcard_count(pattern);
+        if count > self.max_wildcards {
+            return Err(GlobLimitError::TooManyWildcards {
+                count,
+                max: self.max_wildcards,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// 检查并编译一个模式，超出限制或者不是合法模式的都返回 [`None`]
+    #[cfg(feature = "server-side")]
+    pub fn compile(&self, pattern: &str) -> Option<Pattern> {
+        self.check(pattern).ok()?;
+        Pattern::new(pattern).ok()
+    }
+}
+
+/// 统计一个模式中通配符的个数
+pub fn wildcard_count(pattern: &str) -> usize {
+    let (mut count, mut prev_star, mut in_class) = (0, false, false);
+
+    for c in pattern.chars() {
+        match c {
+            _ if in_class => in_class = c != ']',
+            '*' if prev_star => {}
+            '*' | '?' => count += 1,
+            '[' => {
+                count += 1;
+                in_class = true;
+            }
+            _ => {}
+        }
+        prev_star = c == '*';
+    }
+
+    count
+}
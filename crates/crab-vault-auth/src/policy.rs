@@ -0,0 +1,386 @@
+//! # bucket 策略
+//!
+//! 令牌中的权限在签发时就固定了，bucket 策略则保存在 bucket 的元数据中，可以在运行时修改，
+//! 用来开放公共读、或者限制某个 bucket 中允许的写入。
+//!
+//! 鉴权时按照下面的顺序合并二者，参见 [`PolicyEngine`]：
+//!
+//! 1. 命中任意一条 [`Deny`](Effect::Deny) 语句的请求被拒绝，令牌中的权限也不能覆盖它
+//! 2. 令牌中的某一条权限允许这个请求
+//! 3. 某一条 [`Allow`](Effect::Allow) 语句允许这个请求，没有令牌的请求只能通过这种方式被允许
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+use crate::{HttpMethod, Permission};
+
+#[cfg(feature = "server-side")]
+use std::sync::Arc;
+
+#[cfg(feature = "server-side")]
+use crate::{CompiledPermission, CompiledPermissionSet, PermissionSet, pattern::GlobLimits};
+
+/// 一份 bucket 策略最多包含的语句条数
+pub const MAX_STATEMENTS: usize = 16;
+
+/// ## bucket 策略文档
+///
+/// ```json
+/// {
+///     "statements": [
+///         { "effect": "allow", "principal": "*", "methods": ["GET", "HEAD"], "resources": ["public/*"] },
+///         { "effect": "deny", "principal": "authenticated", "methods": ["DELETE"] }
+///     ]
+/// }
+/// ```
+#[derive(Serialize, Deserialize, Validate, Clone, Debug, PartialEq, Default)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct BucketPolicy {
+    /// 最多 [`MAX_STATEMENTS`] 条
+    #[validate(length(max = 16), nested)]
+    pub statements: Vec<PolicyStatement>,
+}
+
+/// 策略中的一条语句
+#[derive(Serialize, Deserialize, Validate, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct PolicyStatement {
+    pub effect: Effect,
+
+    pub principal: Principal,
+
+    /// 这条语句作用的方法，默认为 [`All`](HttpMethod::All)
+    #[serde(default = "PolicyStatement::all_methods")]
+    #[validate(length(min = 1))]
+    pub methods: Vec<HttpMethod>,
+
+    /// 这条语句作用的 object 名称模式，相对于 bucket，不以 `/` 开头，默认为 `*`
+    ///
+    /// 空字符串表示 bucket 本身，例如列出 bucket 中的 object。**最多 8 个模式，复杂度参见 [`GlobLimits`](crate::pattern::GlobLimits)**
+    #[serde(default = "PolicyStatement::any")]
+    #[validate(custom(function = "Permission::validate_content_type_pattern"))]
+    pub resources: Vec<String>,
+
+    /// 允许上传的最大对象大小，只对 [`Allow`](Effect::Allow) 有意义，[`None`] 表示没有限制
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_size: Option<usize>,
+
+    /// 允许的内容类型，只对 [`Allow`](Effect::Allow) 有意义，默认为 `*`
+    #[serde(default = "PolicyStatement::any")]
+    #[validate(custom(function = "Permission::validate_content_type_pattern"))]
+    pub content_types: Vec<String>,
+
+    /// object 的标签条件，只对 [`Allow`](Effect::Allow) 有意义，与 [`Permission::tags`] 相同
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    #[validate(custom(function = "Permission::validate_tag_patterns"))]
+    pub tags: BTreeMap<String, String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum Effect {
+    Allow,
+    Deny,
+}
+
+/// 语句作用于哪些请求
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum Principal {
+    /// 所有请求，包括没有携带令牌的请求
+    #[serde(rename = "*")]
+    Anyone,
+
+    /// 携带了有效令牌的请求
+    Authenticated,
+}
+
+impl BucketPolicy {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 添加一条语句
+    #[inline]
+    pub fn statement(mut self, statement: PolicyStatement) -> Self {
+        self.statements.push(statement);
+        self
+    }
+
+    /// 使用默认的 [`GlobLimits`] 编译这份策略，参见 [`compile_with_limits`](BucketPolicy::compile_with_limits)
+    #[cfg(feature = "server-side")]
+    #[inline]
+    pub fn compile(self, bucket: &str) -> CompiledBucketPolicy {
+        self.compile_with_limits(bucket, &GlobLimits::default())
+    }
+
+    /// 把语句编译为作用于 `/{bucket}/...` 的权限，`bucket` 是请求路径中的原始片段，其中的通配符会被转义
+    ///
+    /// 与令牌一样，超出 `limits` 的允许模式什么都匹配不上，而超出限制的禁止模式匹配所有路径。
+    /// 只保留前 [`MAX_STATEMENTS`] 条语句
+    #[cfg(feature = "server-side")]
+    pub fn compile_with_limits(self, bucket: &str, limits: &GlobLimits) -> CompiledBucketPolicy {
+        let bucket = glob::Pattern::escape(bucket);
+        let mut compiled = CompiledBucketPolicy::default();
+
+        for statement in self.statements.into_iter().take(MAX_STATEMENTS) {
+            for resource in &statement.resources {
+                let pattern = match resource.is_empty() {
+                    true => format!("/{bucket}"),
+                    false => format!("/{bucket}/{resource}"),
+                };
+
+                let (rules, permission) = match statement.effect {
+                    Effect::Allow => (
+                        &mut compiled.allows,
+                        Permission::new_minimum()
+                            .permit_method(statement.methods.clone())
+                            .permit_resource_pattern(pattern)
+                            .restrict_maximum_size_option(statement.max_size)
+                            .permit_content_type(statement.content_types.clone())
+                            .require_tags(statement.tags.clone()),
+                    ),
+                    Effect::Deny => (
+                        &mut compiled.denies,
+                        Permission::new_minimum()
+                            .deny_method(statement.methods.clone())
+                            .deny_resource_pattern(pattern),
+                    ),
+                };
+                rules.push((statement.principal, permission.compile_with_limits(limits)));
+            }
+        }
+
+        compiled
+    }
+}
+
+impl PolicyStatement {
+    fn all_methods() -> Vec<HttpMethod> {
+        vec![HttpMethod::All]
+    }
+
+    fn any() -> Vec<String> {
+        vec!["*".to_string()]
+    }
+
+    /// 一条作用于所有方法和所有 object 的 [`Allow`](Effect::Allow) 语句
+    pub fn allow(principal: Principal) -> Self {
+        Self {
+            effect: Effect::Allow,
+            principal,
+            methods: Self::all_methods(),
+            resources: Self::any(),
+            max_size: None,
+            content_types: Self::any(),
+            tags: BTreeMap::new(),
+        }
+    }
+
+    /// 一条作用于所有方法和所有 object 的 [`Deny`](Effect::Deny) 语句
+    pub fn deny(principal: Principal) -> Self {
+        Self {
+            effect: Effect::Deny,
+            ..Self::allow(principal)
+        }
+    }
+
+    /// 更换这条语句作用的方法
+    #[inline]
+    pub fn methods(mut self, methods: Vec<HttpMethod>) -> Self {
+        self.methods = methods;
+        self
+    }
+
+    /// 更换这条语句作用的 object 名称模式
+    #[inline]
+    pub fn resources(mut self, resources: Vec<String>) -> Self {
+        self.resources = resources;
+        self
+    }
+
+    #[inline]
+    pub fn restrict_maximum_size(mut self, max: usize) -> Self {
+        self.max_size = Some(max);
+        self
+    }
+
+    #[inline]
+    pub fn content_types(mut self, content_types: Vec<String>) -> Self {
+        self.content_types = content_types;
+        self
+    }
+
+    /// 要求 object 带有标签 `key`，并且它的值与 `pattern` 匹配
+    #[inline]
+    pub fn require_tag<K, V>(mut self, key: K, pattern: V) -> Self
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.tags.insert(key.into(), pattern.into());
+        self
+    }
+}
+
+#[cfg(feature = "server-side")]
+impl Principal {
+    fn includes(self, authenticated: bool) -> bool {
+        match self {
+            Principal::Anyone => true,
+            Principal::Authenticated => authenticated,
+        }
+    }
+}
+
+/// 编译之后的 [`BucketPolicy`]
+#[cfg(feature = "server-side")]
+#[derive(Clone, Default)]
+pub struct CompiledBucketPolicy {
+    allows: Vec<(Principal, CompiledPermission)>,
+    denies: Vec<(Principal, CompiledPermission)>,
+}
+
+#[cfg(feature = "server-side")]
+impl CompiledBucketPolicy {
+    /// 合并两份策略中的语句，用于在保存的策略之外附加服务端生成的语句
+    pub fn merge(mut self, mut other: CompiledBucketPolicy) -> Self {
+        self.allows.append(&mut other.allows);
+        self.denies.append(&mut other.denies);
+        self
+    }
+}
+
+/// ## 令牌中的权限与 bucket 策略合并之后的结果
+///
+/// 鉴权中间件把它放在请求的 extensions 中，之后的大小检查、批量删除中的逐个检查都以它为准
+#[cfg(feature = "server-side")]
+#[derive(Clone)]
+pub struct PolicyEngine {
+    permissions: CompiledPermissionSet,
+    policy: Option<Arc<CompiledBucketPolicy>>,
+    authenticated: bool,
+}
+
+#[cfg(feature = "server-side")]
+impl PolicyEngine {
+    /// `permissions` 是请求携带的令牌中的权限，没有令牌时为 [`None`]
+    pub fn new(
+        permissions: Option<CompiledPermissionSet>,
+        policy: Option<Arc<CompiledBucketPolicy>>,
+    ) -> Self {
+        Self {
+            authenticated: permissions.is_some(),
+            permissions: permissions.unwrap_or(CompiledPermissionSet {
+                permissions: vec![],
+            }),
+            policy,
+        }
+    }
+
+    /// 拥有所有权限，并且不受任何 bucket 策略约束
+    pub fn new_root() -> Self {
+        Self::new(Some(PermissionSet::new_root().compile()), None)
+    }
+
+    /// 令牌中的权限，没有令牌时为空
+    #[inline]
+    pub fn permissions(&self) -> &CompiledPermissionSet {
+        &self.permissions
+    }
+
+    /// 请求是否携带了有效的令牌
+    #[inline]
+    pub fn authenticated(&self) -> bool {
+        self.authenticated
+    }
+
+    /// 是否有作用于这个请求的 [`Deny`](Effect::Deny) 语句命中了 `method` 和 `path`
+    pub fn denies(&self, method: HttpMethod, path: &str) -> bool {
+        self.policy.as_ref().is_some_and(|policy| {
+            policy
+                .denies
+                .iter()
+                .any(|(who, rule)| who.includes(self.authenticated) && rule.denies(method, path))
+        })
+    }
+
+    /// 能够对 `path` 执行 `method` 的那些权限，包括令牌中的权限和作用于这个请求的 [`Allow`](Effect::Allow) 语句
+    ///
+    /// 被禁止时没有任何权限匹配
+    pub fn matching<'a>(
+        &'a self,
+        method: HttpMethod,
+        path: &'a str,
+    ) -> impl Iterator<Item = &'a CompiledPermission> {
+        let denied = self.denies(method, path);
+        let allows = self
+            .policy
+            .iter()
+            .flat_map(|policy| &policy.allows)
+            .filter(|(who, _)| who.includes(self.authenticated))
+            .map(|(_, rule)| rule);
+
+        self.permissions
+            .permissions
+            .iter()
+            .chain(allows)
+            .filter(move |v| !denied && v.permits(method, path))
+    }
+
+    /// 是否有任意一条权限能够对 `path` 执行 `method`
+    pub fn allows(&self, method: HttpMethod, path: &str) -> bool {
+        self.matching(method, path).next().is_some()
+    }
+
+    /// 是否允许列出 `bucket` 中以 `prefix` 开头的 object，`bucket` 是 `/{bucket}`，`prefix` 是 `/{bucket}/{prefix}`
+    ///
+    /// 令牌中的权限参见 [`CompiledPermissionSet::allows_listing`]；策略中则由允许 `GET` 这个 bucket 本身
+    /// （`resources` 中的空字符串）的语句决定。禁止语句命中二者中的任意一个时不允许
+    pub fn allows_listing(&self, bucket: &str, prefix: &str) -> bool {
+        if self.denies(HttpMethod::Get, bucket) || self.denies(HttpMethod::Get, prefix) {
+            return false;
+        }
+
+        self.permissions.allows_listing(prefix)
+            || self.policy.iter().flat_map(|policy| &policy.allows).any(|(who, rule)| {
+                who.includes(self.authenticated) && rule.permits(HttpMethod::Get, bucket)
+            })
+    }
+
+    /// 与 [`CompiledPermissionSet::check_size`] 相同，只是同时考虑 bucket 策略
+    pub fn check_size(&self, method: HttpMethod, path: &str, size: usize) -> bool {
+        let mut matching = self.matching(method, path).peekable();
+        match matching.peek() {
+            Some(_) => matching.any(|v| v.check_size(size)),
+            None => self
+                .permissions
+                .permissions
+                .iter()
+                .any(|v| v.check_size(size)),
+        }
+    }
+
+    /// 令牌中的权限或者作用于这个请求的 [`Allow`](Effect::Allow) 语句是否带有标签条件
+    pub fn needs_object_tags(&self) -> bool {
+        self.permissions.needs_tags()
+            || self.policy.iter().flat_map(|policy| &policy.allows).any(|(who, rule)| {
+                who.includes(self.authenticated) && rule.needs_tags()
+            })
+    }
+
+    /// 根据请求的 object 现有的标签判断所有的标签条件，参见 [`CompiledPermission::resolve_tags`]
+    pub fn resolve_tags(&mut self, tags: &BTreeMap<String, String>) {
+        self.permissions.resolve_tags(tags);
+        if let Some(policy) = &mut self.policy
+            && policy.allows.iter().any(|(_, rule)| rule.needs_tags())
+        {
+            for (_, rule) in &mut Arc::make_mut(policy).allows {
+                rule.resolve_tags(tags);
+            }
+        }
+    }
+}
@@ -0,0 +1,116 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::RwLock,
+};
+
+use uuid::Uuid;
+
+use crate::error::AuthError;
+
+/// ## 已吊销的令牌的 `jti`
+///
+/// [`JwtDecoder`](crate::JwtDecoder) 在验证签名和时间之后会查询这里，已吊销的令牌返回 [`AuthError::TokenRevoked`]
+///
+/// 每条记录都带有令牌的过期时间，令牌过期之后它本来就无法通过验证，所以这条记录可以被清理
+pub trait RevocationStore: Send + Sync {
+    /// 吊销一个令牌，`expires_at` 是这个令牌的过期时间，Unix 时间戳
+    fn revoke(&self, jti: Uuid, expires_at: i64) -> Result<(), AuthError>;
+
+    /// 这个令牌是否已经被吊销
+    fn is_revoked(&self, jti: &Uuid) -> bool;
+}
+
+/// 只保存在内存中的 [`RevocationStore`]，重启后所有的吊销记录都会丢失
+#[derive(Default)]
+pub struct MemoryRevocationStore {
+    entries: RwLock<HashMap<Uuid, i64>>,
+}
+
+/// 保存在一个 JSON 文件中的 [`RevocationStore`]，每次吊销都会重写整个文件
+pub struct FileRevocationStore {
+    path: PathBuf,
+    entries: RwLock<HashMap<Uuid, i64>>,
+}
+
+impl MemoryRevocationStore {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl RevocationStore for MemoryRevocationStore {
+    fn revoke(&self, jti: Uuid, expires_at: i64) -> Result<(), AuthError> {
+        let mut entries = self.entries.write().map_err(|_| poisoned())?;
+        insert_and_purge(&mut entries, jti, expires_at);
+        Ok(())
+    }
+
+    fn is_revoked(&self, jti: &Uuid) -> bool {
+        // 锁中毒时宁可拒绝所有令牌
+        self.entries.read().map_or(true, |v| v.contains_key(jti))
+    }
+}
+
+impl FileRevocationStore {
+    /// 打开一个吊销列表文件，文件不存在时视为空列表，第一次吊销时才会创建
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, AuthError> {
+        let path = path.as_ref().to_path_buf();
+
+        let entries = match std::fs::read(&path) {
+            Ok(data) => serde_json::from_slice(&data).map_err(|e| {
+                AuthError::InternalError(format!("{e} while parsing {}", path.display()))
+            })?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(io_error(e, &path)),
+        };
+
+        Ok(Self {
+            path,
+            entries: RwLock::new(entries),
+        })
+    }
+
+    /// 先写入临时文件再重命名，避免进程中途退出时留下写了一半的文件
+    fn persist(&self, entries: &HashMap<Uuid, i64>) -> Result<(), AuthError> {
+        let data =
+            serde_json::to_vec(entries).map_err(|e| AuthError::InternalError(e.to_string()))?;
+
+        let mut tmp_path = self.path.clone().into_os_string();
+        tmp_path.push(".tmp");
+
+        std::fs::write(&tmp_path, data)
+            .and_then(|_| std::fs::rename(&tmp_path, &self.path))
+            .map_err(|e| io_error(e, &self.path))
+    }
+}
+
+impl RevocationStore for FileRevocationStore {
+    fn revoke(&self, jti: Uuid, expires_at: i64) -> Result<(), AuthError> {
+        let mut entries = self.entries.write().map_err(|_| poisoned())?;
+        insert_and_purge(&mut entries, jti, expires_at);
+        self.persist(&entries)
+    }
+
+    fn is_revoked(&self, jti: &Uuid) -> bool {
+        self.entries.read().map_or(true, |v| v.contains_key(jti))
+    }
+}
+
+fn insert_and_purge(entries: &mut HashMap<Uuid, i64>, jti: Uuid, expires_at: i64) {
+    let now = chrono::Utc::now().timestamp();
+    entries.retain(|_, v| *v >= now);
+
+    // 同一个令牌被吊销多次时保留较晚的过期时间
+    let entry = entries.entry(jti).or_insert(expires_at);
+    *entry = (*entry).max(expires_at);
+}
+
+fn poisoned() -> AuthError {
+    AuthError::InternalError("revocation list lock poisoned".into())
+}
+
+fn io_error(e: std::io::Error, path: &Path) -> AuthError {
+    AuthError::InternalError(format!("{e} while accessing {}", path.display()))
+}
@@ -0,0 +1,225 @@
+#![cfg(feature = "server-side")]
+
+use std::{collections::BTreeMap, sync::Arc};
+
+use crab_vault_auth::{
+    HttpMethod, Permission, PermissionSet,
+    pattern::GlobLimits,
+    policy::{BucketPolicy, PolicyEngine, PolicyStatement, Principal},
+};
+use validator::Validate;
+
+fn public_read() -> BucketPolicy {
+    BucketPolicy::new().statement(
+        PolicyStatement::allow(Principal::Anyone)
+            .methods(vec![HttpMethod::Get, HttpMethod::Head])
+            .resources(vec!["".to_string(), "public/*".to_string()]),
+    )
+}
+
+fn engine(token: Option<Permission>, policy: BucketPolicy) -> PolicyEngine {
+    PolicyEngine::new(
+        token.map(|v| PermissionSet::from(v).compile()),
+        Some(Arc::new(policy.compile("bucket"))),
+    )
+}
+
+#[test]
+fn test_public_read() {
+    let anonymous = engine(None, public_read());
+    assert!(!anonymous.authenticated());
+
+    assert!(anonymous.allows(HttpMethod::Get, "/bucket/public/a.png"));
+    assert!(anonymous.allows(HttpMethod::Get, "/bucket"));
+    assert!(!anonymous.allows(HttpMethod::Get, "/bucket/private/a.png"));
+    assert!(!anonymous.allows(HttpMethod::Put, "/bucket/public/a.png"));
+
+    // 策略只作用于它所在的 bucket
+    assert!(!anonymous.allows(HttpMethod::Get, "/other/public/a.png"));
+}
+
+#[test]
+fn test_deny_beats_token() {
+    let policy = public_read().statement(
+        PolicyStatement::deny(Principal::Authenticated).methods(vec![HttpMethod::Delete]),
+    );
+
+    let root = engine(Some(Permission::new_root()), policy.clone());
+    assert!(root.allows(HttpMethod::Put, "/bucket/a"));
+    assert!(root.denies(HttpMethod::Delete, "/bucket/a"));
+    assert!(!root.allows(HttpMethod::Delete, "/bucket/a"));
+
+    // 作用于已认证请求的语句不会作用于匿名请求
+    let anonymous = engine(None, policy);
+    assert!(!anonymous.denies(HttpMethod::Delete, "/bucket/a"));
+}
+
+#[test]
+fn test_write_restrictions() {
+    let policy = BucketPolicy::new().statement(
+        PolicyStatement::allow(Principal::Authenticated)
+            .methods(vec![HttpMethod::Put])
+            .resources(vec!["uploads/*".to_string()])
+            .restrict_maximum_size(1024)
+            .content_types(vec!["image/*".to_string()]),
+    );
+
+    // 令牌本身只能读，策略允许已认证的请求上传
+    let reader = Permission::new_minimum()
+        .permit_method(vec![HttpMethod::Get])
+        .permit_resource_pattern("*");
+    let engine = engine(Some(reader), policy);
+
+    let matching = engine
+        .matching(HttpMethod::Put, "/bucket/uploads/a.png")
+        .collect::<Vec<_>>();
+    assert_eq!(matching.len(), 1);
+    assert!(matching[0].check_content_type("image/png"));
+    assert!(!matching[0].check_content_type("text/plain"));
+
+    assert!(engine.check_size(HttpMethod::Put, "/bucket/uploads/a.png", 1024));
+    assert!(!engine.check_size(HttpMethod::Put, "/bucket/uploads/a.png", 1025));
+    assert!(!engine.allows(HttpMethod::Put, "/bucket/a.png"));
+}
+
+#[test]
+fn test_bucket_name_is_escaped() {
+    let policy = BucketPolicy::new().statement(PolicyStatement::allow(Principal::Anyone));
+    let engine = PolicyEngine::new(None, Some(Arc::new(policy.compile("b*"))));
+
+    assert!(engine.allows(HttpMethod::Get, "/b*/a"));
+    assert!(!engine.allows(HttpMethod::Get, "/bucket/a"));
+}
+
+#[test]
+fn test_deny_fails_closed() {
+    let limits = GlobLimits {
+        max_len: 8,
+        max_wildcards: 1,
+    };
+    let policy = BucketPolicy::new()
+        .statement(PolicyStatement::allow(Principal::Anyone))
+        .statement(
+            PolicyStatement::deny(Principal::Anyone).resources(vec!["secrets/*".to_string()]),
+        );
+
+    // 超出限制的允许模式什么都不允许，禁止模式则禁止所有路径
+    let engine = PolicyEngine::new(
+        Some(PermissionSet::new_root().compile()),
+        Some(Arc::new(policy.compile_with_limits("bucket", &limits))),
+    );
+    assert!(engine.denies(HttpMethod::Get, "/bucket/a"));
+}
+
+#[test]
+fn test_serde_defaults_and_validation() {
+    let policy: BucketPolicy = serde_json::from_str(
+        r#"{ "statements": [{ "effect": "allow", "principal": "*", "methods": ["GET"] }] }"#,
+    )
+    .unwrap();
+    assert_eq!(
+        policy,
+        BucketPolicy::new()
+            .statement(PolicyStatement::allow(Principal::Anyone).methods(vec![HttpMethod::Get]))
+    );
+    assert!(policy.validate().is_ok());
+
+    // 必须明确给出作用于谁
+    assert!(
+        serde_json::from_str::<BucketPolicy>(r#"{ "statements": [{ "effect": "deny" }] }"#)
+            .is_err()
+    );
+    assert!(serde_json::from_str::<BucketPolicy>(r#"{ "statements": [], "version": 1 }"#).is_err());
+
+    let empty_methods =
+        BucketPolicy::new().statement(PolicyStatement::deny(Principal::Anyone).methods(vec![]));
+    assert!(empty_methods.validate().is_err());
+
+    let too_many = BucketPolicy {
+        statements: vec![PolicyStatement::allow(Principal::Anyone); 17],
+    };
+    assert!(too_many.validate().is_err());
+}
+
+#[test]
+fn test_listing() {
+    // 策略中允许 GET bucket 本身的语句决定能否列出
+    let anonymous = engine(None, public_read());
+    assert!(anonymous.allows_listing("/bucket", "/bucket/"));
+
+    let objects_only = BucketPolicy::new()
+        .statement(PolicyStatement::allow(Principal::Anyone).methods(vec![HttpMethod::Get]));
+    assert!(!engine(None, objects_only).allows_listing("/bucket", "/bucket/"));
+
+    // 禁止语句命中前缀时，即使令牌允许也不能列出
+    let policy = BucketPolicy::new().statement(
+        PolicyStatement::deny(Principal::Authenticated).resources(vec!["secrets/*".to_string()]),
+    );
+    let root = engine(Some(Permission::new_root()), policy);
+    assert!(root.allows_listing("/bucket", "/bucket/"));
+    assert!(!root.allows_listing("/bucket", "/bucket/secrets/"));
+}
+
+#[test]
+fn test_merge() {
+    let deny_secrets = BucketPolicy::new().statement(
+        PolicyStatement::deny(Principal::Anyone).resources(vec!["secrets/*".to_string()]),
+    );
+    let merged = deny_secrets
+        .compile("bucket")
+        .merge(public_read().compile("bucket"));
+    let anonymous = PolicyEngine::new(None, Some(Arc::new(merged)));
+
+    // 两份策略中的语句都生效，禁止语句仍然优先
+    assert!(anonymous.allows(HttpMethod::Get, "/bucket/public/a.png"));
+    assert!(!anonymous.allows(HttpMethod::Get, "/bucket/secrets/a.png"));
+}
+
+#[test]
+fn test_tag_conditions() {
+    let tags = |pairs: &[(&str, &str)]| {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect::<BTreeMap<_, _>>()
+    };
+
+    let token = Permission::new_root().require_tag("stage", "prod-*");
+    let policy = BucketPolicy::new().statement(
+        PolicyStatement::allow(Principal::Anyone)
+            .methods(vec![HttpMethod::Get])
+            .require_tag("public", "yes"),
+    );
+
+    // 判断标签之前，带有标签条件的权限和语句什么都不允许
+    let mut authed = engine(Some(token.clone()), BucketPolicy::new());
+    let mut anonymous = engine(None, policy.clone());
+    assert!(authed.needs_object_tags());
+    assert!(anonymous.needs_object_tags());
+    assert!(!authed.allows(HttpMethod::Put, "/bucket/a"));
+    assert!(!anonymous.allows(HttpMethod::Get, "/bucket/a"));
+
+    authed.resolve_tags(&tags(&[("stage", "prod-1"), ("team", "infra")]));
+    anonymous.resolve_tags(&tags(&[("public", "yes")]));
+    assert!(authed.allows(HttpMethod::Put, "/bucket/a"));
+    assert!(anonymous.allows(HttpMethod::Get, "/bucket/a"));
+
+    authed.resolve_tags(&tags(&[("stage", "dev")]));
+    anonymous.resolve_tags(&BTreeMap::new());
+    assert!(!authed.allows(HttpMethod::Put, "/bucket/a"));
+    assert!(!anonymous.allows(HttpMethod::Get, "/bucket/a"));
+
+    // 不作用于这个请求的语句带有标签条件时，不需要读取标签
+    let authenticated_only = BucketPolicy::new()
+        .statement(PolicyStatement::allow(Principal::Authenticated).require_tag("public", "yes"));
+    assert!(!engine(None, authenticated_only).needs_object_tags());
+    assert!(!engine(Some(Permission::new_root()), public_read()).needs_object_tags());
+
+    assert!(token.validate().is_ok());
+    let too_many = (0..9).fold(Permission::new_root(), |v, i| {
+        v.require_tag(i.to_string(), "*")
+    });
+    assert!(too_many.validate().is_err());
+    let too_complex = Permission::new_root().require_tag("a", "*".repeat(1000));
+    assert!(too_complex.validate().is_err());
+}
@@ -0,0 +1,56 @@
+use std::sync::Arc;
+
+use base64::{Engine, prelude::BASE64_STANDARD};
+use crab_vault_auth::error::AuthError;
+use jsonwebtoken::Algorithm;
+
+/// 每一个变体与它的 `code`，服务端的日志和统计依赖这些值，修改之前请确认是有意为之
+fn all() -> Vec<(AuthError, &'static str)> {
+    let utf8 = String::from_utf8(vec![0xff]).unwrap_err();
+    let json = serde_json::from_str::<u8>("x").unwrap_err();
+    let base64 = BASE64_STANDARD.decode("!").unwrap_err();
+
+    vec![
+        (AuthError::MissingAuthHeader, "missingAuthHeader"),
+        (
+            AuthError::InvalidAlgorithm(Algorithm::HS256),
+            "invalidAlgorithm",
+        ),
+        (AuthError::InvalidAuthFormat, "invalidAuthFormat"),
+        (AuthError::InvalidKeyId, "invalidKeyId"),
+        (AuthError::InvalidUtf8(utf8), "invalidUtf8"),
+        (AuthError::InvalidJson(Arc::new(json)), "invalidJson"),
+        (AuthError::InvalidBase64(base64), "invalidBase64"),
+        (AuthError::InvalidToken, "invalidToken"),
+        (AuthError::TokenExpired, "tokenExpired"),
+        (AuthError::TokenNotYetValid, "tokenNotYetValid"),
+        (AuthError::InvalidSignature, "invalidSignature"),
+        (AuthError::InvalidIssuer, "invalidIssuer"),
+        (AuthError::InvalidAudience, "invalidAudience"),
+        (AuthError::InvalidSubject, "invalidSubject"),
+        (AuthError::MissingClaim("exp".into()), "missingClaim"),
+        (
+            AuthError::InsufficientPermissions,
+            "insufficientPermissions",
+        ),
+        (AuthError::TokenRevoked, "tokenRevoked"),
+        (AuthError::InternalError("key".into()), "internalError"),
+    ]
+}
+
+#[test]
+fn test_error_codes_are_stable() {
+    let errors = all();
+    for (error, code) in &errors {
+        assert_eq!(error.error_code(), *code, "{error}");
+        // 带有数据的变体无法按照 `code` 标签序列化，只检查能够序列化的
+        if let Ok(value) = serde_json::to_value(error) {
+            assert_eq!(value["code"], *code, "{error}");
+        }
+    }
+
+    let mut codes: Vec<_> = errors.iter().map(|(_, code)| *code).collect();
+    codes.sort_unstable();
+    codes.dedup();
+    assert_eq!(codes.len(), errors.len());
+}
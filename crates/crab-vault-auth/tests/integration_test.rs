@@ -2,6 +2,8 @@
 
 // 只有在开启 server-side 特性时才运行这些测试，因为 JwtDecoder 需要它
 #![cfg(feature = "server-side")]
+// 测试中用 `assert!(true)` 标记预期的分支
+#![allow(clippy::assertions_on_constants)]
 
 use chrono::Duration;
 use crab_vault_auth::{
@@ -347,4 +349,23 @@ fn test_multiple_audience() {
     let token = encoder.encode(&claims, &kid).unwrap();
 
     assert!(decoder.decode::<UserPayload>(&token).is_ok());
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_accept_retired_key() {
+    let (kid, enc_key, dec_key) = setup_keys();
+    let old_encoder = create_encoder(&kid, enc_key);
+    let new_encoder = create_encoder("key_v2", EncodingKey::from_secret(b"rotated_secret"));
+
+    // 新配置中只有 key_v2，旧的 key_v1 仍处于宽限期
+    let decoder = create_decoder("iss", "key_v2", DecodingKey::from_secret(b"rotated_secret"), "aud")
+        .accept_key("iss", &kid, Algorithm::HS256, dec_key);
+
+    let claims = Jwt::new("iss", &["aud"], Permission::new_root());
+    assert!(decoder.decode::<Permission>(&old_encoder.encode(&claims, &kid).unwrap()).is_ok());
+    assert!(decoder.decode::<Permission>(&new_encoder.encode(&claims, "key_v2").unwrap()).is_ok());
+
+    // 已经存在的密钥不会被覆盖
+    let decoder = decoder.accept_key("iss", "key_v2", Algorithm::HS256, DecodingKey::from_secret(b"other"));
+    assert!(decoder.decode::<Permission>(&new_encoder.encode(&claims, "key_v2").unwrap()).is_ok());
+}
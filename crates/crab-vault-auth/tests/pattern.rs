@@ -0,0 +1,70 @@
+use crab_vault_auth::{
+    Permission,
+    pattern::{GlobLimitError, GlobLimits, wildcard_count},
+};
+use validator::Validate;
+
+#[test]
+fn test_wildcard_count() {
+    assert_eq!(wildcard_count("/bucket/object"), 0);
+    assert_eq!(wildcard_count("/bucket/*"), 1);
+    // 连续的 `*` 视为一个
+    assert_eq!(wildcard_count("/bucket/**/x"), 1);
+    assert_eq!(wildcard_count("/b?cket/*/*"), 3);
+    // 字符类中的 `*` 和 `?` 不计数
+    assert_eq!(wildcard_count("/[*?]/x"), 1);
+}
+
+#[test]
+fn test_limits_check() {
+    let limits = GlobLimits {
+        max_len: 16,
+        max_wildcards: 2,
+    };
+
+    assert!(limits.check("/a/*/b/*").is_ok());
+    assert_eq!(
+        limits.check("/a/*/b/*/c/*"),
+        Err(GlobLimitError::TooManyWildcards { count: 3, max: 2 })
+    );
+    assert_eq!(
+        limits.check("/a-very-long-bucket/*"),
+        Err(GlobLimitError::TooLong { len: 21, max: 16 })
+    );
+}
+
+#[test]
+fn test_permission_validation_uses_limits() {
+    let ok = Permission::new_root();
+    assert!(ok.validate().is_ok());
+
+    let complex = Permission::new_root().permit_resource_pattern("*a*a*a*a*a*a*a*a*a*");
+    assert!(complex.validate().is_err());
+
+    let complex = Permission::new_root().permit_content_type(vec!["*/*+*;*=*,*?*?*?".to_string()]);
+    assert!(complex.validate().is_err());
+}
+
+#[cfg(feature = "server-side")]
+#[test]
+fn test_compile_drops_complex_patterns() {
+    let limits = GlobLimits {
+        max_len: 128,
+        max_wildcards: 2,
+    };
+
+    let compiled = Permission::new_root()
+        .permit_resource_pattern("/*/*/*")
+        .permit_content_type(vec!["image/*".to_string(), "*/*+*".to_string()])
+        .compile_with_limits(&limits);
+
+    // 超出限制的模式什么都匹配不上
+    assert!(!compiled.can_access("/a/b/c"));
+    assert!(compiled.check_content_type("image/png"));
+    assert!(!compiled.check_content_type("application/ld+json"));
+
+    let compiled = Permission::new_root()
+        .permit_resource_pattern("/*/*/*")
+        .compile();
+    assert!(compiled.can_access("/a/b/c"));
+}
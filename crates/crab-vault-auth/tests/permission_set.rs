@@ -0,0 +1,231 @@
+#![cfg(feature = "server-side")]
+
+use std::collections::HashMap;
+
+use crab_vault_auth::{
+    Capability, HttpMethod, Jwt, JwtDecoder, JwtEncoder, MAX_PERMISSIONS, Permission,
+    PermissionSet, pattern::GlobLimits,
+};
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey};
+use validator::Validate;
+
+fn reader() -> Permission {
+    Permission::new_minimum()
+        .permit_method(vec![HttpMethod::Get, HttpMethod::Head])
+        .permit_resource_pattern("/public/*")
+}
+
+fn uploader() -> Permission {
+    Permission::new_minimum()
+        .permit_method(vec![HttpMethod::Put])
+        .permit_resource_pattern("/uploads/alice/*")
+        .restrict_maximum_size(1024)
+        .permit_content_type(vec!["image/*".to_string()])
+}
+
+#[test]
+fn test_single_permission_serializes_as_object() {
+    let single = PermissionSet::from(reader());
+    let value = serde_json::to_value(&single).unwrap();
+    assert_eq!(value, serde_json::to_value(reader()).unwrap());
+
+    // 旧的单个对象形式和数组形式都能解析
+    let parsed: PermissionSet = serde_json::from_value(value).unwrap();
+    assert_eq!(parsed, single);
+
+    let multiple = PermissionSet::from(reader()).grant(uploader());
+    let value = serde_json::to_value(&multiple).unwrap();
+    assert!(value.is_array());
+    assert_eq!(serde_json::from_value::<PermissionSet>(value).unwrap(), multiple);
+}
+
+#[test]
+fn test_allow_if_any() {
+    let set = PermissionSet::from(reader()).grant(uploader()).compile();
+
+    assert!(set.allows(HttpMethod::Get, "/public/logo.png"));
+    assert!(set.allows(HttpMethod::Put, "/uploads/alice/avatar.png"));
+
+    // 方法和路径必须来自同一条权限
+    assert!(!set.allows(HttpMethod::Put, "/public/logo.png"));
+    assert!(!set.allows(HttpMethod::Get, "/uploads/alice/avatar.png"));
+    assert!(!set.allows(HttpMethod::Delete, "/uploads/alice/avatar.png"));
+
+    let matching = set
+        .matching(HttpMethod::Put, "/uploads/alice/avatar.png")
+        .collect::<Vec<_>>();
+    assert_eq!(matching.len(), 1);
+    assert!(matching[0].check_content_type("image/png"));
+    assert!(!matching[0].check_content_type("text/plain"));
+}
+
+#[test]
+fn test_check_size() {
+    let set = PermissionSet::from(reader()).grant(uploader()).compile();
+
+    // 匹配的权限限制了大小
+    assert!(set.check_size(HttpMethod::Put, "/uploads/alice/a.png", 1024));
+    assert!(!set.check_size(HttpMethod::Put, "/uploads/alice/a.png", 1025));
+
+    // 只读的权限的限制是 0，不能放宽上传的限制
+    assert!(!set.check_size(HttpMethod::Get, "/public/a.png", 1));
+
+    // 没有匹配的权限时，任意一条允许即可
+    assert!(set.check_size(HttpMethod::Post, "/uploads", 1024));
+    assert!(!set.check_size(HttpMethod::Post, "/uploads", 1025));
+}
+
+#[test]
+fn test_limits() {
+    assert!(PermissionSet::new(vec![]).validate().is_err());
+    assert!(PermissionSet::new(vec![reader(); MAX_PERMISSIONS]).validate().is_ok());
+    assert!(
+        PermissionSet::new(vec![reader(); MAX_PERMISSIONS + 1])
+            .validate()
+            .is_err()
+    );
+
+    // 超出的权限在编译时被丢弃
+    let mut permissions = vec![reader(); MAX_PERMISSIONS];
+    permissions.push(uploader());
+    let set = PermissionSet::new(permissions).compile();
+    assert_eq!(set.permissions.len(), MAX_PERMISSIONS);
+    assert!(!set.allows(HttpMethod::Put, "/uploads/alice/a.png"));
+}
+
+#[test]
+fn test_decode_legacy_token_as_set() {
+    let secret = b"permission_set_secret";
+    let encoder = JwtEncoder::new(HashMap::from([(
+        "kid".to_string(),
+        (EncodingKey::from_secret(secret), Algorithm::HS256),
+    )]));
+    let decoder = JwtDecoder::new(
+        HashMap::from([(
+            ("iss".to_string(), "kid".to_string()),
+            DecodingKey::from_secret(secret),
+        )]),
+        &[Algorithm::HS256],
+        &["iss"],
+        &["aud"],
+    );
+
+    let legacy = encoder
+        .encode(&Jwt::new("iss", &["aud"], reader()), "kid")
+        .unwrap();
+    let jwt: Jwt<PermissionSet> = decoder.decode(&legacy).unwrap();
+    assert_eq!(jwt.load, PermissionSet::from(reader()));
+
+    let multiple = PermissionSet::from(reader()).grant(uploader());
+    let token = encoder
+        .encode(&Jwt::new("iss", &["aud"], multiple.clone()), "kid")
+        .unwrap();
+    let jwt: Jwt<PermissionSet> = decoder.decode(&token).unwrap();
+    assert_eq!(jwt.load, multiple);
+}
+
+#[test]
+fn test_deny_beats_allow() {
+    let everything_but_secrets = Permission::new_root()
+        .deny_resource_pattern("*/secrets/*")
+        .compile();
+
+    assert!(everything_but_secrets.permits(HttpMethod::Get, "/bucket/public/a"));
+    assert!(!everything_but_secrets.permits(HttpMethod::Get, "/bucket/secrets/a"));
+    assert!(!everything_but_secrets.permits(HttpMethod::Put, "/bucket/secrets/a"));
+
+    // 方法和路径都命中时才会禁止
+    let no_archive_delete = Permission::new_root()
+        .deny_method(vec![HttpMethod::Delete])
+        .deny_resource_pattern("/archive/*")
+        .compile();
+    assert!(no_archive_delete.permits(HttpMethod::Put, "/archive/a"));
+    assert!(no_archive_delete.permits(HttpMethod::Delete, "/bucket/a"));
+    assert!(!no_archive_delete.permits(HttpMethod::Delete, "/archive/a"));
+
+    // 只设置方法时禁止所有路径上的这些方法
+    let read_only = Permission::new_root()
+        .deny_method(vec![HttpMethod::Unsafe])
+        .compile();
+    assert!(read_only.permits(HttpMethod::Get, "/bucket/a"));
+    assert!(!read_only.permits(HttpMethod::Post, "/bucket/a"));
+
+    // 禁止规则只作用于它所在的权限
+    let set = PermissionSet::from(Permission::new_root().deny_resource_pattern("*/secrets/*"))
+        .grant(reader())
+        .compile();
+    assert!(!set.allows(HttpMethod::Get, "/bucket/secrets/a"));
+    assert!(set.allows(HttpMethod::Get, "/public/secrets/a"));
+}
+
+#[test]
+fn test_deny_rules_fail_closed() {
+    let limits = GlobLimits {
+        max_len: 8,
+        max_wildcards: 1,
+    };
+
+    // 超出限制的禁止模式禁止所有路径
+    let permission = Permission::new_root()
+        .deny_resource_pattern("/*/secrets/*")
+        .compile_with_limits(&limits);
+    assert!(!permission.permits(HttpMethod::Get, "/bucket/a"));
+
+    // 没有禁止规则时序列化结果与之前相同
+    let value = serde_json::to_value(Permission::new_root()).unwrap();
+    assert!(value.get("deniedMethods").is_none());
+    assert!(value.get("deniedResourcePattern").is_none());
+}
+
+#[test]
+fn test_listing() {
+    // 之前签发的令牌没有这个字段，仍然可以列出 object
+    let mut legacy = serde_json::to_value(Permission::new_root()).unwrap();
+    assert!(legacy.get("allowList").is_none());
+    legacy.as_object_mut().unwrap().remove("allowList");
+    assert!(serde_json::from_value::<Permission>(legacy).unwrap().allow_list);
+
+    let value = serde_json::to_value(Permission::new_root().permit_list(false)).unwrap();
+    assert_eq!(value["allowList"], false);
+
+    // 只能列出能够读取的前缀
+    let photos = Permission::new_minimum()
+        .permit_method(vec![HttpMethod::Get])
+        .permit_resource_pattern("/bucket/photos/*")
+        .permit_list(true);
+    let set = PermissionSet::from(photos.clone()).compile();
+    assert!(set.allows_listing("/bucket/photos/"));
+    assert!(set.allows_listing("/bucket/photos/2025/"));
+    assert!(!set.allows_listing("/bucket/"));
+
+    // 能够读取但是不能列出
+    let fetch_only = PermissionSet::from(photos.permit_list(false)).compile();
+    assert!(fetch_only.allows(HttpMethod::Get, "/bucket/photos/a.png"));
+    assert!(!fetch_only.allows_listing("/bucket/photos/"));
+}
+
+#[test]
+fn test_capabilities() {
+    // 没有额外的操作时序列化结果与之前相同
+    let value = serde_json::to_value(reader()).unwrap();
+    assert!(value.get("capabilities").is_none());
+    let value = serde_json::to_value(Permission::new_root()).unwrap();
+    assert_eq!(value["capabilities"], serde_json::json!(["forceDelete", "legalHold"]));
+
+    let deleter = Permission::new_minimum()
+        .permit_method(vec![HttpMethod::Delete])
+        .permit_resource_pattern("/tmp*");
+    let set = PermissionSet::from(deleter.clone()).compile();
+    assert!(set.allows(HttpMethod::Delete, "/tmp1"));
+    assert!(!set.allows_capability(Capability::ForceDelete, HttpMethod::Delete, "/tmp1"));
+
+    // 只作用于这条权限允许的方法和路径
+    let force = deleter.permit_capability(vec![Capability::ForceDelete]);
+    let set = PermissionSet::from(force.clone()).grant(reader()).compile();
+    assert!(set.allows_capability(Capability::ForceDelete, HttpMethod::Delete, "/tmp1"));
+    assert!(!set.allows_capability(Capability::ForceDelete, HttpMethod::Get, "/tmp1"));
+    assert!(!set.allows_capability(Capability::ForceDelete, HttpMethod::Delete, "/photos"));
+
+    let denied = PermissionSet::from(force.deny_resource_pattern("/tmp-keep")).compile();
+    assert!(!denied.allows_capability(Capability::ForceDelete, HttpMethod::Delete, "/tmp-keep"));
+}
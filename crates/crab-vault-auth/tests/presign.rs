@@ -0,0 +1,29 @@
+#![cfg(feature = "server-side")]
+
+use crab_vault_auth::{HttpMethod, Permission};
+
+#[test]
+fn test_presigned_permission_only_matches_its_path() {
+    let perm = Permission::new_presigned(HttpMethod::Get, "/bucket/object").compile();
+
+    assert!(perm.can_access("/bucket/object"));
+    assert!(!perm.can_access("/bucket/object2"));
+    assert!(!perm.can_access("/bucket/other"));
+
+    assert!(perm.can_perform_method(HttpMethod::Get));
+    assert!(!perm.can_perform_method(HttpMethod::Head));
+    assert!(!perm.can_perform_method(HttpMethod::Put));
+}
+
+#[test]
+fn test_presigned_permission_escapes_wildcards() {
+    let perm = Permission::new_presigned(HttpMethod::Put, "/bucket/[a]*?").compile();
+
+    assert!(perm.can_access("/bucket/[a]*?"));
+    assert!(!perm.can_access("/bucket/a"));
+    assert!(!perm.can_access("/bucket/[a]xyz"));
+
+    assert!(perm.can_perform_method(HttpMethod::Put));
+    assert!(perm.check_size(usize::MAX));
+    assert!(perm.check_content_type("application/octet-stream"));
+}
@@ -0,0 +1,81 @@
+#![cfg(feature = "server-side")]
+
+use std::{collections::HashMap, sync::Arc};
+
+use crab_vault_auth::{
+    Jwt, JwtDecoder, JwtEncoder, Permission,
+    error::AuthError,
+    revocation::{FileRevocationStore, MemoryRevocationStore, RevocationStore},
+};
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey};
+use uuid::Uuid;
+
+const SECRET: &[u8] = b"revocation_secret";
+
+fn encoder() -> JwtEncoder {
+    let mut map = HashMap::new();
+    map.insert(
+        "kid".to_string(),
+        (EncodingKey::from_secret(SECRET), Algorithm::HS256),
+    );
+    JwtEncoder::new(map)
+}
+
+fn decoder() -> JwtDecoder {
+    let mut map = HashMap::new();
+    map.insert(
+        ("crab-vault".to_string(), "kid".to_string()),
+        DecodingKey::from_secret(SECRET),
+    );
+    JwtDecoder::new(map, &[Algorithm::HS256], &["crab-vault"], &["crab-vault"])
+}
+
+#[test]
+fn test_revoked_token_is_rejected() {
+    let store = Arc::new(MemoryRevocationStore::new());
+    let decoder = decoder().revocation_store(store.clone());
+
+    let revoked = Jwt::new("crab-vault", &["crab-vault"], Permission::new_root());
+    let other = Jwt::new("crab-vault", &["crab-vault"], Permission::new_root());
+    let (revoked_token, other_token) = (
+        encoder().encode(&revoked, "kid").unwrap(),
+        encoder().encode(&other, "kid").unwrap(),
+    );
+
+    assert!(decoder.decode::<Permission>(&revoked_token).is_ok());
+
+    store.revoke(revoked.jti, revoked.exp).unwrap();
+    assert!(matches!(
+        decoder.decode::<Permission>(&revoked_token),
+        Err(AuthError::TokenRevoked)
+    ));
+    assert!(decoder.decode::<Permission>(&other_token).is_ok());
+}
+
+#[test]
+fn test_expired_entries_are_purged() {
+    let store = MemoryRevocationStore::new();
+    let (expired, alive) = (Uuid::new_v4(), Uuid::new_v4());
+
+    store.revoke(expired, 0).unwrap();
+    assert!(store.is_revoked(&expired));
+
+    store.revoke(alive, i64::MAX).unwrap();
+    assert!(!store.is_revoked(&expired));
+    assert!(store.is_revoked(&alive));
+}
+
+#[test]
+fn test_file_store_survives_reopen() {
+    let path = std::env::temp_dir().join(format!("crab-vault-revocation-{}.json", Uuid::new_v4()));
+    let jti = Uuid::new_v4();
+
+    let store = FileRevocationStore::open(&path).unwrap();
+    assert!(!store.is_revoked(&jti));
+    store.revoke(jti, i64::MAX).unwrap();
+
+    let reopened = FileRevocationStore::open(&path).unwrap();
+    assert!(reopened.is_revoked(&jti));
+
+    std::fs::remove_file(&path).unwrap();
+}
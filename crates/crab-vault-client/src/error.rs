@@ -0,0 +1,184 @@
+use reqwest::StatusCode;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use thiserror::Error;
+
+use crab_vault_auth::error::AuthError;
+
+pub type ClientResult<T> = Result<T, ClientError>;
+
+#[derive(Debug, Error)]
+pub enum ClientError {
+    #[error("`{0}` is not an http or https URL")]
+    InvalidEndpoint(String),
+
+    /// 请求头的值中有不可见的字符，例如 `content_type`
+    #[error("`{0}` cannot be used in a header")]
+    InvalidHeader(String),
+
+    /// 连接失败、超时，或者读取响应体时出错
+    #[error("request failed: {0}")]
+    Http(#[from] reqwest::Error),
+
+    /// 服务端返回了错误的状态码
+    #[error(transparent)]
+    Api(Box<ApiError>),
+
+    /// 签发令牌失败
+    #[error("cannot sign a token: {0}")]
+    Auth(#[from] AuthError),
+
+    /// 服务端的响应无法解析，通常说明客户端与服务端的版本不匹配
+    #[error("unexpected response: {0}")]
+    InvalidResponse(String),
+}
+
+/// 服务端返回的错误，与服务端的错误响应 `{code, message, resource, request_id, context, ...}` 对应
+///
+/// 响应体不是错误 JSON 时（例如经过的代理返回的错误）只有状态码，这时 `code` 为 `None`
+#[derive(Debug, Clone, Error)]
+#[error("{status}{}", self.describe())]
+pub struct ApiError {
+    pub status: StatusCode,
+    pub code: Option<String>,
+
+    /// 响应中的 `message`，旧版本的服务端中为 `msg`
+    pub msg: Option<String>,
+
+    /// 出错的 bucket 或者 object，形如 `/{bucket}/{object}`
+    pub resource: Option<String>,
+
+    /// 与服务端日志中的 `req_id` 相同，报告问题时附上它
+    pub request_id: Option<String>,
+    pub context: Option<ErrorContext>,
+
+    /// 除了上面几个字段之外的其他字段，例如 `bucketNotFound` 中的 `bucket`
+    pub details: Map<String, Value>,
+}
+
+/// 出错时服务端正在执行的操作，参见服务端文档中的 `operation`
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ErrorContext {
+    pub operation: String,
+    pub bucket: Option<String>,
+    pub object: Option<String>,
+}
+
+/// 按照状态码对 [`ApiError`] 的粗略分类
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// 401，令牌缺失、无效、过期或者已经被吊销
+    Unauthorized,
+
+    /// 403，令牌或者存储桶策略不允许这个操作，或者超出了配额
+    Forbidden,
+
+    /// 404，存储桶或者对象不存在
+    NotFound,
+
+    /// 409，例如删除非空的存储桶
+    Conflict,
+
+    /// 410，对象已经过期，或者旧路径已经停止服务
+    Gone,
+
+    /// 412，带有 `If-None-Match: *` 但对象已经存在
+    PreconditionFailed,
+
+    /// 429，超出了服务端的限流
+    TooManyRequests,
+
+    /// 507，写入之后会超出存储桶的配额
+    InsufficientStorage,
+
+    /// 其他 4xx，请求本身有问题
+    InvalidRequest,
+
+    /// 5xx
+    Server,
+}
+
+#[derive(Deserialize)]
+struct ErrorBody {
+    code: String,
+    #[serde(alias = "msg")]
+    message: Option<String>,
+    resource: Option<String>,
+    request_id: Option<String>,
+    context: Option<ErrorContext>,
+
+    #[serde(flatten)]
+    details: Map<String, Value>,
+}
+
+impl ApiError {
+    /// 从状态码和响应体构造，响应体不是错误 JSON 时只保留状态码
+    pub fn new(status: StatusCode, body: &[u8]) -> Self {
+        let mut error = Self {
+            status,
+            code: None,
+            msg: None,
+            resource: None,
+            request_id: None,
+            context: None,
+            details: Map::new(),
+        };
+
+        if let Ok(body) = serde_json::from_slice::<ErrorBody>(body) {
+            error.code = Some(body.code);
+            error.msg = body.message;
+            error.resource = body.resource;
+            error.request_id = body.request_id;
+            error.context = body.context;
+            error.details = body.details;
+        }
+        error
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        match self.status {
+            StatusCode::UNAUTHORIZED => ErrorKind::Unauthorized,
+            StatusCode::FORBIDDEN => ErrorKind::Forbidden,
+            StatusCode::NOT_FOUND => ErrorKind::NotFound,
+            StatusCode::CONFLICT => ErrorKind::Conflict,
+            StatusCode::GONE => ErrorKind::Gone,
+            StatusCode::PRECONDITION_FAILED => ErrorKind::PreconditionFailed,
+            StatusCode::TOO_MANY_REQUESTS => ErrorKind::TooManyRequests,
+            StatusCode::INSUFFICIENT_STORAGE => ErrorKind::InsufficientStorage,
+            status if status.is_server_error() => ErrorKind::Server,
+            _ => ErrorKind::InvalidRequest,
+        }
+    }
+
+    pub fn is_not_found(&self) -> bool {
+        self.kind() == ErrorKind::NotFound
+    }
+
+    fn describe(&self) -> String {
+        match (&self.code, &self.msg) {
+            (Some(code), Some(msg)) => format!(" ({code}): {msg}"),
+            (Some(code), None) => format!(" ({code})"),
+            _ => String::new(),
+        }
+    }
+}
+
+impl From<ApiError> for ClientError {
+    fn from(value: ApiError) -> Self {
+        Self::Api(Box::new(value))
+    }
+}
+
+impl ClientError {
+    /// 服务端返回的错误，其他错误返回 `None`
+    pub fn api(&self) -> Option<&ApiError> {
+        match self {
+            ClientError::Api(e) => Some(e),
+            _ => None,
+        }
+    }
+
+    pub fn is_not_found(&self) -> bool {
+        self.api().is_some_and(ApiError::is_not_found)
+    }
+}
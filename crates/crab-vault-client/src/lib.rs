@@ -0,0 +1,531 @@
+//! ## crab-vault 的 Rust 客户端
+//!
+//! ```no_run
+//! # async fn run() -> crab_vault_client::error::ClientResult<()> {
+//! use crab_vault_client::{Client, PutOptions};
+//!
+//! let client = Client::builder("http://localhost:32767/v1")
+//!     .token("eyJ...")
+//!     .build()?;
+//!
+//! client.create_bucket("photos", &Default::default()).await?;
+//! client
+//!     .put_object("photos", "2025/paris.jpg", vec![0u8; 16], &PutOptions::default())
+//!     .await?;
+//! let object = client.get_object("photos", "2025/paris.jpg").await?;
+//! assert_eq!(object.data.len(), 16);
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod error;
+mod model;
+mod token;
+
+use std::{sync::Arc, time::Duration};
+
+use base64::{Engine, prelude::BASE64_STANDARD};
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use futures::{StreamExt, TryStreamExt};
+use percent_encoding::{AsciiSet, CONTROLS, utf8_percent_encode};
+use reqwest::{
+    Method, Response, StatusCode, Url,
+    header::{
+        CONTENT_LENGTH, CONTENT_TYPE, ETAG, HeaderMap, HeaderName, HeaderValue, IF_MATCH,
+        IF_NONE_MATCH, LAST_MODIFIED,
+    },
+};
+use serde_json::Value;
+
+use crate::{
+    error::{ApiError, ClientError, ClientResult},
+    token::Credentials,
+};
+
+pub use model::{
+    BucketInfo, BucketOptions, ListObjectsOptions, Object, ObjectInfo, ObjectPage, ObjectStream,
+    PutOptions,
+};
+pub use token::TokenSigner;
+
+/// 对象名称放入路径时需要编码的字符，与服务端相同
+const PATH_SEGMENT: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
+const DEFAULT_CONTENT_TYPE: &str = "application/octet-stream";
+
+const X_CRAB_VAULT_USER_META: HeaderName = HeaderName::from_static("x-crab-vault-user-meta");
+const X_CRAB_VAULT_CREATED_AT: HeaderName = HeaderName::from_static("x-crab-vault-created-at");
+const X_CRAB_VAULT_BUCKET_NAME: HeaderName = HeaderName::from_static("x-crab-vault-bucket-name");
+const X_CRAB_VAULT_OBJECT_NAME: HeaderName = HeaderName::from_static("x-crab-vault-object-name");
+const X_CRAB_VAULT_EXPIRES_AT: HeaderName = HeaderName::from_static("x-crab-vault-expires-at");
+const X_CRAB_VAULT_MAX_BYTES: HeaderName = HeaderName::from_static("x-crab-vault-max-bytes");
+const X_CRAB_VAULT_MAX_OBJECTS: HeaderName = HeaderName::from_static("x-crab-vault-max-objects");
+const X_CRAB_VAULT_OBJECT_COUNT: HeaderName = HeaderName::from_static("x-crab-vault-object-count");
+const X_CRAB_VAULT_TOTAL_BYTES: HeaderName = HeaderName::from_static("x-crab-vault-total-bytes");
+
+/// ## crab-vault 的异步客户端
+///
+/// 克隆的开销很小，克隆出来的客户端共享连接池和令牌
+#[derive(Clone)]
+pub struct Client {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    http: reqwest::Client,
+
+    /// 去掉了末尾的 `/`
+    endpoint: String,
+    credentials: Credentials,
+}
+
+pub struct ClientBuilder {
+    endpoint: String,
+    credentials: Credentials,
+    timeout: Option<Duration>,
+    http: Option<reqwest::Client>,
+}
+
+impl ClientBuilder {
+    /// 每个请求都携带这个令牌
+    pub fn token<T: ToString>(mut self, token: T) -> Self {
+        self.credentials = Credentials::Static(token.to_string());
+        self
+    }
+
+    /// 用本地的密钥签发令牌，快要过期或者被服务端拒绝时重新签发
+    pub fn signer(mut self, signer: TokenSigner) -> Self {
+        self.credentials = Credentials::signed(signer);
+        self
+    }
+
+    /// 单个请求的超时，包括接收响应体的时间，默认不限制
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// 使用已有的 [`reqwest::Client`]，这时 [`timeout`](Self::timeout) 不起作用
+    pub fn http_client(mut self, http: reqwest::Client) -> Self {
+        self.http = Some(http);
+        self
+    }
+
+    pub fn build(self) -> ClientResult<Client> {
+        let endpoint = self.endpoint.trim_end_matches('/').to_string();
+        let valid = Url::parse(&endpoint).is_ok_and(|v| {
+            matches!(v.scheme(), "http" | "https") && v.has_host() && v.query().is_none()
+        });
+        if !valid {
+            return Err(ClientError::InvalidEndpoint(self.endpoint));
+        }
+
+        let http = match self.http {
+            Some(http) => http,
+            None => {
+                let mut builder = reqwest::Client::builder();
+                if let Some(timeout) = self.timeout {
+                    builder = builder.timeout(timeout);
+                }
+                builder.build()?
+            }
+        };
+
+        Ok(Client {
+            inner: Arc::new(Inner {
+                http,
+                endpoint,
+                credentials: self.credentials,
+            }),
+        })
+    }
+}
+
+impl Client {
+    /// `endpoint` 是服务端的地址，通常带有版本前缀，例如 `http://localhost:32767/v1`
+    pub fn builder<T: ToString>(endpoint: T) -> ClientBuilder {
+        ClientBuilder {
+            endpoint: endpoint.to_string(),
+            credentials: Credentials::Anonymous,
+            timeout: None,
+            http: None,
+        }
+    }
+
+    /// 创建存储桶，已经存在时什么也不做
+    pub async fn create_bucket(&self, bucket: &str, options: &BucketOptions) -> ClientResult<()> {
+        let mut headers = HeaderMap::new();
+        if let Some(user_meta) = &options.user_meta {
+            insert_user_meta(&mut headers, user_meta)?;
+        }
+        for (name, value) in [
+            (X_CRAB_VAULT_MAX_BYTES, options.max_bytes),
+            (X_CRAB_VAULT_MAX_OBJECTS, options.max_objects),
+        ] {
+            if let Some(value) = value {
+                headers.insert(name, HeaderValue::from(value));
+            }
+        }
+
+        self.send(
+            Method::PUT,
+            &bucket_path(bucket),
+            &[],
+            headers,
+            Bytes::new(),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// 删除空的存储桶
+    pub async fn delete_bucket(&self, bucket: &str) -> ClientResult<()> {
+        self.send(
+            Method::DELETE,
+            &bucket_path(bucket),
+            &[],
+            HeaderMap::new(),
+            Bytes::new(),
+        )
+        .await?;
+        Ok(())
+    }
+
+    pub async fn head_bucket(&self, bucket: &str) -> ClientResult<BucketInfo> {
+        let response = self
+            .send(
+                Method::HEAD,
+                &bucket_path(bucket),
+                &[],
+                HeaderMap::new(),
+                Bytes::new(),
+            )
+            .await?;
+        bucket_from_headers(response.headers())
+    }
+
+    /// 列出名称以 `prefix` 开头的所有存储桶，按名称排序
+    pub async fn list_buckets(&self, prefix: Option<&str>) -> ClientResult<Vec<BucketInfo>> {
+        #[derive(serde::Deserialize)]
+        struct Entry {
+            meta: BucketInfo,
+        }
+
+        let query: Vec<_> = prefix
+            .map(|v| ("prefix", v.to_string()))
+            .into_iter()
+            .collect();
+        let response = self
+            .send(Method::GET, "/", &query, HeaderMap::new(), Bytes::new())
+            .await?;
+        let entries: Vec<Entry> = parse_json(response).await?;
+        Ok(entries.into_iter().map(|v| v.meta).collect())
+    }
+
+    /// 上传对象，已经存在时整体替换
+    pub async fn put_object<T: Into<Bytes>>(
+        &self,
+        bucket: &str,
+        object: &str,
+        data: T,
+        options: &PutOptions,
+    ) -> ClientResult<()> {
+        let mut headers = HeaderMap::new();
+        let content_type = options
+            .content_type
+            .as_deref()
+            .unwrap_or(DEFAULT_CONTENT_TYPE);
+        headers.insert(CONTENT_TYPE, header_value(content_type)?);
+        if let Some(user_meta) = &options.user_meta {
+            insert_user_meta(&mut headers, user_meta)?;
+        }
+        if options.if_not_exists {
+            headers.insert(IF_NONE_MATCH, HeaderValue::from_static("*"));
+        }
+        if let Some(etag) = &options.if_match {
+            headers.insert(IF_MATCH, header_value(etag)?);
+        }
+        if let Some(expires_at) = options.expires_at {
+            headers.insert(
+                X_CRAB_VAULT_EXPIRES_AT,
+                header_value(&expires_at.to_rfc3339())?,
+            );
+        }
+
+        self.send(
+            Method::PUT,
+            &object_path(bucket, object),
+            &[],
+            headers,
+            data.into(),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// 下载整个对象
+    pub async fn get_object(&self, bucket: &str, object: &str) -> ClientResult<Object> {
+        let response = self.get(bucket, object).await?;
+        let info = object_from_headers(response.headers())?;
+        let data = response.bytes().await?;
+        Ok(Object { info, data })
+    }
+
+    /// 下载对象，数据在读取返回的 [`ObjectStream`] 时才被接收，适合很大的对象
+    pub async fn get_object_stream(
+        &self,
+        bucket: &str,
+        object: &str,
+    ) -> ClientResult<ObjectStream> {
+        let response = self.get(bucket, object).await?;
+        let info = object_from_headers(response.headers())?;
+        let body = response.bytes_stream().map_err(ClientError::from).boxed();
+        Ok(ObjectStream { info, body })
+    }
+
+    pub async fn head_object(&self, bucket: &str, object: &str) -> ClientResult<ObjectInfo> {
+        let response = self
+            .send(
+                Method::HEAD,
+                &object_path(bucket, object),
+                &[],
+                HeaderMap::new(),
+                Bytes::new(),
+            )
+            .await?;
+        object_from_headers(response.headers())
+    }
+
+    /// 删除对象，对象本来就不存在时同样成功
+    pub async fn delete_object(&self, bucket: &str, object: &str) -> ClientResult<()> {
+        self.send(
+            Method::DELETE,
+            &object_path(bucket, object),
+            &[],
+            HeaderMap::new(),
+            Bytes::new(),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// 列出一页对象，`is_truncated` 为真时用 `next_continuation_token` 获取下一页
+    pub async fn list_objects(
+        &self,
+        bucket: &str,
+        options: &ListObjectsOptions,
+    ) -> ClientResult<ObjectPage> {
+        let query: Vec<_> = [
+            ("prefix", options.prefix.clone()),
+            ("delimiter", options.delimiter.clone()),
+            ("max-keys", options.max_keys.map(|v| v.to_string())),
+            ("continuation-token", options.continuation_token.clone()),
+            ("query", options.query.clone()),
+        ]
+        .into_iter()
+        .filter_map(|(key, value)| value.map(|v| (key, v)))
+        .collect();
+
+        let response = self
+            .send(
+                Method::GET,
+                &bucket_path(bucket),
+                &query,
+                HeaderMap::new(),
+                Bytes::new(),
+            )
+            .await?;
+        parse_json(response).await
+    }
+
+    async fn get(&self, bucket: &str, object: &str) -> ClientResult<Response> {
+        self.send(
+            Method::GET,
+            &object_path(bucket, object),
+            &[],
+            HeaderMap::new(),
+            Bytes::new(),
+        )
+        .await
+    }
+
+    /// 发送请求，状态码不是 2xx 时返回 [`ClientError::Api`]
+    ///
+    /// 签发的令牌被拒绝时重新签发一次，例如服务端的时钟比本地快
+    async fn send(
+        &self,
+        method: Method,
+        path: &str,
+        query: &[(&str, String)],
+        headers: HeaderMap,
+        body: Bytes,
+    ) -> ClientResult<Response> {
+        let credentials = &self.inner.credentials;
+        let mut response = self
+            .request(&method, path, query, &headers, &body, false)
+            .await?;
+        if response.status() == StatusCode::UNAUTHORIZED && credentials.refreshable() {
+            response = self
+                .request(&method, path, query, &headers, &body, true)
+                .await?;
+        }
+
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+        // `HEAD` 请求的错误响应没有响应体，请求 ID 只在响应头中
+        let request_id = response
+            .headers()
+            .get("x-request-id")
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
+        let body = response.bytes().await.unwrap_or_default();
+        let mut error = ApiError::new(status, &body);
+        error.request_id = error.request_id.or(request_id);
+        Err(error.into())
+    }
+
+    async fn request(
+        &self,
+        method: &Method,
+        path: &str,
+        query: &[(&str, String)],
+        headers: &HeaderMap,
+        body: &Bytes,
+        refresh: bool,
+    ) -> ClientResult<Response> {
+        let Inner {
+            http,
+            endpoint,
+            credentials,
+        } = self.inner.as_ref();
+
+        let mut request = http
+            .request(method.clone(), format!("{endpoint}{path}"))
+            .headers(headers.clone());
+        if !query.is_empty() {
+            request = request.query(query);
+        }
+        // 服务端在鉴权时要求修改对象的请求都带有这两个头部，没有请求体的 `DELETE` 也不例外
+        if !headers.contains_key(CONTENT_TYPE) {
+            request = request.header(CONTENT_TYPE, DEFAULT_CONTENT_TYPE);
+        }
+        if *method != Method::GET && *method != Method::HEAD {
+            request = request
+                .header(CONTENT_LENGTH, body.len())
+                .body(body.clone());
+        }
+        if let Some(token) = credentials.token(refresh)? {
+            request = request.bearer_auth(token);
+        }
+
+        Ok(request.send().await?)
+    }
+}
+
+fn bucket_path(bucket: &str) -> String {
+    format!("/{}", utf8_percent_encode(bucket, PATH_SEGMENT))
+}
+
+fn object_path(bucket: &str, object: &str) -> String {
+    format!(
+        "/{}/{}",
+        utf8_percent_encode(bucket, PATH_SEGMENT),
+        utf8_percent_encode(object, PATH_SEGMENT)
+    )
+}
+
+fn header_value(value: &str) -> ClientResult<HeaderValue> {
+    HeaderValue::from_str(value).map_err(|_| ClientError::InvalidHeader(value.to_string()))
+}
+
+fn insert_user_meta(headers: &mut HeaderMap, user_meta: &Value) -> ClientResult<()> {
+    let value = BASE64_STANDARD.encode(user_meta.to_string());
+    headers.insert(X_CRAB_VAULT_USER_META, header_value(&value)?);
+    Ok(())
+}
+
+async fn parse_json<T: serde::de::DeserializeOwned>(response: Response) -> ClientResult<T> {
+    let body = response.bytes().await?;
+    serde_json::from_slice(&body).map_err(|e| ClientError::InvalidResponse(e.to_string()))
+}
+
+fn missing(name: &HeaderName) -> ClientError {
+    ClientError::InvalidResponse(format!("header `{name}` is missing or invalid"))
+}
+
+fn header_str<'a>(headers: &'a HeaderMap, name: &HeaderName) -> Option<&'a str> {
+    headers.get(name).and_then(|v| v.to_str().ok())
+}
+
+fn header_time(headers: &HeaderMap, name: &HeaderName) -> ClientResult<DateTime<Utc>> {
+    header_str(headers, name)
+        .and_then(|v| DateTime::parse_from_rfc2822(v).ok())
+        .map(|v| v.to_utc())
+        .ok_or_else(|| missing(name))
+}
+
+fn header_number(headers: &HeaderMap, name: &HeaderName) -> Option<u64> {
+    header_str(headers, name).and_then(|v| v.parse().ok())
+}
+
+fn user_meta_from_headers(headers: &HeaderMap) -> ClientResult<Value> {
+    let Some(value) = header_str(headers, &X_CRAB_VAULT_USER_META) else {
+        return Ok(Value::Object(Default::default()));
+    };
+    BASE64_STANDARD
+        .decode(value)
+        .ok()
+        .and_then(|v| serde_json::from_slice(&v).ok())
+        .ok_or_else(|| missing(&X_CRAB_VAULT_USER_META))
+}
+
+fn bucket_from_headers(headers: &HeaderMap) -> ClientResult<BucketInfo> {
+    Ok(BucketInfo {
+        name: header_str(headers, &X_CRAB_VAULT_BUCKET_NAME)
+            .ok_or_else(|| missing(&X_CRAB_VAULT_BUCKET_NAME))?
+            .to_string(),
+        user_meta: user_meta_from_headers(headers)?,
+        max_bytes: header_number(headers, &X_CRAB_VAULT_MAX_BYTES),
+        max_objects: header_number(headers, &X_CRAB_VAULT_MAX_OBJECTS),
+        object_count: header_number(headers, &X_CRAB_VAULT_OBJECT_COUNT),
+        total_bytes: header_number(headers, &X_CRAB_VAULT_TOTAL_BYTES),
+        created_at: header_time(headers, &X_CRAB_VAULT_CREATED_AT)?,
+        updated_at: header_time(headers, &LAST_MODIFIED)?,
+    })
+}
+
+fn object_from_headers(headers: &HeaderMap) -> ClientResult<ObjectInfo> {
+    let string = |name: &HeaderName| {
+        header_str(headers, name)
+            .map(ToString::to_string)
+            .ok_or_else(|| missing(name))
+    };
+
+    Ok(ObjectInfo {
+        bucket_name: string(&X_CRAB_VAULT_BUCKET_NAME)?,
+        object_name: string(&X_CRAB_VAULT_OBJECT_NAME)?,
+        size: header_number(headers, &CONTENT_LENGTH).ok_or_else(|| missing(&CONTENT_LENGTH))?,
+        content_type: string(&CONTENT_TYPE)?,
+        etag: string(&ETAG)?,
+        user_meta: user_meta_from_headers(headers)?,
+        expires_at: match headers.contains_key(X_CRAB_VAULT_EXPIRES_AT) {
+            true => Some(header_time(headers, &X_CRAB_VAULT_EXPIRES_AT)?),
+            false => None,
+        },
+        created_at: header_time(headers, &X_CRAB_VAULT_CREATED_AT)?,
+        updated_at: header_time(headers, &LAST_MODIFIED)?,
+    })
+}
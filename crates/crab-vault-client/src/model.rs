@@ -0,0 +1,140 @@
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use futures::{Stream, stream::BoxStream};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::error::ClientResult;
+
+/// 存储桶的元数据
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub struct BucketInfo {
+    pub name: String,
+
+    #[serde(default)]
+    pub user_meta: Value,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_bytes: Option<u64>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_objects: Option<u64>,
+
+    /// 只有 [`head_bucket`](crate::Client::head_bucket) 并且服务端统计过用量时才有
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub object_count: Option<u64>,
+
+    /// 同 `object_count`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total_bytes: Option<u64>,
+
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// 对象的元数据
+///
+/// 从响应头中得到的时间只精确到秒
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub struct ObjectInfo {
+    pub bucket_name: String,
+    pub object_name: String,
+    pub size: u64,
+    pub content_type: String,
+    pub etag: String,
+
+    #[serde(default)]
+    pub user_meta: Value,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
+
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// `list_objects` 的一页结果
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub struct ObjectPage {
+    pub objects: Vec<ObjectInfo>,
+
+    #[serde(default)]
+    pub common_prefixes: Vec<String>,
+
+    #[serde(default)]
+    pub is_truncated: bool,
+
+    /// 下一页的 `continuation_token`
+    #[serde(default)]
+    pub next_continuation_token: Option<String>,
+}
+
+/// 创建存储桶时的选项
+#[derive(Clone, Debug, Default)]
+pub struct BucketOptions {
+    pub user_meta: Option<Value>,
+    pub max_bytes: Option<u64>,
+    pub max_objects: Option<u64>,
+}
+
+/// 上传对象时的选项
+#[derive(Clone, Debug, Default)]
+pub struct PutOptions {
+    /// 不设置时为 `application/octet-stream`
+    pub content_type: Option<String>,
+
+    pub user_meta: Option<Value>,
+
+    /// 只在对象不存在时创建，已经存在时返回 412
+    pub if_not_exists: bool,
+
+    /// 只在对象存在并且 etag 仍然等于它时覆盖，否则返回 412，不能与 `if_not_exists` 同时使用
+    pub if_match: Option<String>,
+
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// 列出对象时的查询条件，与服务端的查询参数一一对应
+#[derive(Clone, Debug, Default)]
+pub struct ListObjectsOptions {
+    pub prefix: Option<String>,
+    pub delimiter: Option<String>,
+
+    /// 默认且最多为 1000
+    pub max_keys: Option<u32>,
+
+    /// 上一页的 [`next_continuation_token`](ObjectPage::next_continuation_token)
+    pub continuation_token: Option<String>,
+
+    /// 过滤表达式，例如 `content_type^=image/ and size<1048576`
+    pub query: Option<String>,
+}
+
+/// 下载得到的完整对象
+#[derive(Clone, Debug)]
+pub struct Object {
+    pub info: ObjectInfo,
+    pub data: Bytes,
+}
+
+/// 边接收边读取的对象，本身是数据块组成的 [`Stream`]
+pub struct ObjectStream {
+    pub info: ObjectInfo,
+    pub(crate) body: BoxStream<'static, ClientResult<Bytes>>,
+}
+
+impl Stream for ObjectStream {
+    type Item = ClientResult<Bytes>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.body.as_mut().poll_next(cx)
+    }
+}
@@ -0,0 +1,110 @@
+use std::sync::Mutex;
+
+use chrono::Utc;
+use crab_vault_auth::{Jwt, JwtEncoder, PermissionSet, error::AuthError};
+
+/// 快要过期的令牌提前这么多秒重新签发，避免请求在路上时过期
+const REFRESH_MARGIN: i64 = 60;
+
+/// ## 用本地的密钥签发令牌
+///
+/// 适合与服务端共享签名密钥的服务，令牌快要过期时自动重新签发，不需要额外的令牌服务
+#[derive(Clone)]
+pub struct TokenSigner {
+    encoder: JwtEncoder,
+    kid: Option<String>,
+    issuer: String,
+    audience: Vec<String>,
+    permissions: PermissionSet,
+    lifetime: chrono::Duration,
+}
+
+/// 请求携带的令牌
+pub(crate) enum Credentials {
+    Anonymous,
+    Static(String),
+    Signed {
+        signer: Box<TokenSigner>,
+
+        /// 当前的令牌以及它的过期时间
+        cached: Mutex<Option<(String, i64)>>,
+    },
+}
+
+impl TokenSigner {
+    /// 默认使用随机的密钥，签发的令牌一小时内有效
+    pub fn new<T: ToString, U: ToString>(
+        encoder: JwtEncoder,
+        issuer: T,
+        audience: &[U],
+        permissions: PermissionSet,
+    ) -> Self {
+        Self {
+            encoder,
+            kid: None,
+            issuer: issuer.to_string(),
+            audience: audience.iter().map(ToString::to_string).collect(),
+            permissions,
+            lifetime: chrono::Duration::hours(1),
+        }
+    }
+
+    /// 使用这个 kid 对应的密钥签名
+    pub fn kid<T: ToString>(mut self, kid: T) -> Self {
+        self.kid = Some(kid.to_string());
+        self
+    }
+
+    /// 每个令牌的有效期
+    pub fn lifetime(mut self, lifetime: chrono::Duration) -> Self {
+        self.lifetime = lifetime;
+        self
+    }
+
+    /// 签发一个新的令牌，返回令牌和它的过期时间
+    pub fn sign(&self) -> Result<(String, i64), AuthError> {
+        let claims = Jwt::new(&self.issuer, &self.audience, self.permissions.clone())
+            .expires_in(self.lifetime);
+        let token = match &self.kid {
+            Some(kid) => self.encoder.encode(&claims, kid),
+            None => self.encoder.encode_randomly(&claims),
+        }?;
+        Ok((token, claims.exp))
+    }
+}
+
+impl Credentials {
+    pub(crate) fn signed(signer: TokenSigner) -> Self {
+        Self::Signed {
+            signer: Box::new(signer),
+            cached: Mutex::new(None),
+        }
+    }
+
+    /// 被服务端拒绝之后能否换一个令牌重试
+    pub(crate) fn refreshable(&self) -> bool {
+        matches!(self, Credentials::Signed { .. })
+    }
+
+    /// 当前应当使用的令牌，`refresh` 为真时总是重新签发
+    pub(crate) fn token(&self, refresh: bool) -> Result<Option<String>, AuthError> {
+        let (signer, cached) = match self {
+            Credentials::Anonymous => return Ok(None),
+            Credentials::Static(token) => return Ok(Some(token.clone())),
+            Credentials::Signed { signer, cached } => (signer, cached),
+        };
+
+        let mut cached = cached.lock().unwrap_or_else(|e| e.into_inner());
+        let margin = REFRESH_MARGIN.min(signer.lifetime.num_seconds() / 2);
+        match cached.as_ref() {
+            Some((token, exp)) if !refresh && exp - Utc::now().timestamp() > margin => {
+                Ok(Some(token.clone()))
+            }
+            _ => {
+                let (token, exp) = signer.sign()?;
+                *cached = Some((token.clone(), exp));
+                Ok(Some(token))
+            }
+        }
+    }
+}
@@ -0,0 +1,310 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use axum::{
+    Router,
+    body::Bytes,
+    extract::{Request, State},
+    http::{HeaderMap, Method, StatusCode},
+    response::{IntoResponse, Response},
+};
+use base64::{Engine, prelude::BASE64_STANDARD};
+use crab_vault_auth::{JwtEncoder, Permission, PermissionSet};
+use crab_vault_client::{
+    BucketOptions, Client, ListObjectsOptions, PutOptions, TokenSigner,
+    error::{ClientError, ErrorKind},
+};
+use futures::TryStreamExt;
+use jsonwebtoken::{Algorithm, EncodingKey};
+use serde_json::json;
+
+const TOKEN: &str = "token";
+const CREATED_AT: &str = "Mon, 18 Aug 2025 05:02:40 +0000";
+
+/// 只实现了测试需要的那部分接口，对象保存在内存中
+#[derive(Clone, Default)]
+struct Mock {
+    objects: Arc<Mutex<HashMap<String, (HeaderMap, Bytes)>>>,
+
+    /// 收到的每个请求的方法、路径和查询参数、请求头
+    requests: Arc<Mutex<Vec<(Method, String, HeaderMap)>>>,
+
+    /// 拒绝前几个请求，模拟服务端不再接受之前的令牌
+    reject: Arc<Mutex<usize>>,
+}
+
+async fn handle(State(mock): State<Mock>, req: Request) -> Response {
+    let (parts, body) = req.into_parts();
+    let body = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+    let uri = parts.uri.to_string();
+    mock.requests
+        .lock()
+        .unwrap()
+        .push((parts.method.clone(), uri.clone(), parts.headers.clone()));
+
+    {
+        let mut reject = mock.reject.lock().unwrap();
+        if *reject > 0 {
+            *reject -= 1;
+            return StatusCode::UNAUTHORIZED.into_response();
+        }
+    }
+    if !parts.headers.contains_key("authorization") {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+
+    let path = parts.uri.path().to_string();
+    let mut objects = mock.objects.lock().unwrap();
+    match parts.method {
+        Method::PUT => {
+            objects.insert(path, (parts.headers, body));
+            StatusCode::CREATED.into_response()
+        }
+        Method::DELETE => {
+            objects.remove(&path);
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Method::GET if path == "/v1/photos" => axum::Json(json!({
+            "objects": [{
+                "bucket-name": "photos",
+                "object-name": "2025/a.jpg",
+                "size": 3,
+                "content-type": "image/jpeg",
+                "etag": "etag",
+                "created-at": "2025-08-20T05:08:23.789410600Z",
+                "updated-at": "2025-08-20T05:08:23.789411100Z",
+                "user-meta": {"user": "alex"}
+            }],
+            "common-prefixes": ["2025/raw/"],
+            "is-truncated": true,
+            "next-continuation-token": "next"
+        }))
+        .into_response(),
+        Method::GET | Method::HEAD => match objects.get(&path) {
+            Some((headers, data)) => {
+                let response = [
+                    ("content-type", headers["content-type"].to_str().unwrap()),
+                    ("etag", "etag"),
+                    ("last-modified", CREATED_AT),
+                    ("x-crab-vault-created-at", CREATED_AT),
+                    ("x-crab-vault-bucket-name", "photos"),
+                    ("x-crab-vault-object-name", "2025/paris trip#1.jpg"),
+                    (
+                        "x-crab-vault-user-meta",
+                        headers["x-crab-vault-user-meta"].to_str().unwrap(),
+                    ),
+                ];
+                (response, data.clone()).into_response()
+            }
+            None => (
+                StatusCode::NOT_FOUND,
+                axum::Json(json!({
+                    "code": "objectMetaNotFound",
+                    "message": "object meta not found",
+                    "resource": "/photos/missing",
+                    "request_id": "cmVxdWVzdA==",
+                    "context": {"operation": "getObject", "bucket": "photos", "object": "missing"},
+                    "bucket": "photos",
+                    "object": "missing"
+                })),
+            )
+                .into_response(),
+        },
+        _ => StatusCode::METHOD_NOT_ALLOWED.into_response(),
+    }
+}
+
+async fn serve(mock: Mock) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let app = Router::new().fallback(handle).with_state(mock);
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    format!("http://{addr}/v1/")
+}
+
+#[tokio::test]
+async fn test_object_round_trip() {
+    let mock = Mock::default();
+    let client = Client::builder(serve(mock.clone()).await)
+        .token(TOKEN)
+        .build()
+        .unwrap();
+
+    let options = PutOptions {
+        content_type: Some("image/jpeg".into()),
+        user_meta: Some(json!({"camera": "x100"})),
+        if_not_exists: true,
+        ..Default::default()
+    };
+    client
+        .put_object("photos", "2025/paris trip#1.jpg", "jpg", &options)
+        .await
+        .unwrap();
+
+    let (method, uri, headers) = mock.requests.lock().unwrap()[0].clone();
+    assert_eq!(method, Method::PUT);
+    assert_eq!(uri, "/v1/photos/2025/paris%20trip%231.jpg");
+    assert_eq!(headers["authorization"], "Bearer token");
+    assert_eq!(headers["content-length"], "3");
+    assert_eq!(headers["if-none-match"], "*");
+    let user_meta = BASE64_STANDARD
+        .decode(headers["x-crab-vault-user-meta"].as_bytes())
+        .unwrap();
+    assert_eq!(
+        serde_json::from_slice::<serde_json::Value>(&user_meta).unwrap(),
+        json!({"camera": "x100"})
+    );
+
+    let object = client
+        .get_object("photos", "2025/paris trip#1.jpg")
+        .await
+        .unwrap();
+    assert_eq!(object.data, "jpg");
+    assert_eq!(object.info.object_name, "2025/paris trip#1.jpg");
+    assert_eq!(object.info.content_type, "image/jpeg");
+    assert_eq!(object.info.size, 3);
+    assert_eq!(object.info.user_meta, json!({"camera": "x100"}));
+    assert_eq!(object.info.created_at.to_rfc2822(), CREATED_AT);
+
+    let stream = client
+        .get_object_stream("photos", "2025/paris trip#1.jpg")
+        .await
+        .unwrap();
+    assert_eq!(stream.info, object.info);
+    let chunks: Vec<_> = stream.try_collect().await.unwrap();
+    assert_eq!(chunks.concat(), b"jpg");
+
+    client
+        .delete_object("photos", "2025/paris trip#1.jpg")
+        .await
+        .unwrap();
+    let (method, _, headers) = mock.requests.lock().unwrap().last().unwrap().clone();
+    assert_eq!(method, Method::DELETE);
+    assert_eq!(headers["content-length"], "0");
+    assert!(headers.contains_key("content-type"));
+}
+
+#[tokio::test]
+async fn test_api_error() {
+    let client = Client::builder(serve(Mock::default()).await)
+        .token(TOKEN)
+        .build()
+        .unwrap();
+
+    let error = client.get_object("photos", "missing").await.unwrap_err();
+    assert!(error.is_not_found());
+    let error = error.api().unwrap();
+    assert_eq!(error.kind(), ErrorKind::NotFound);
+    assert_eq!(error.code.as_deref(), Some("objectMetaNotFound"));
+    assert_eq!(error.msg.as_deref(), Some("object meta not found"));
+    assert_eq!(error.resource.as_deref(), Some("/photos/missing"));
+    assert_eq!(error.request_id.as_deref(), Some("cmVxdWVzdA=="));
+    assert_eq!(error.context.as_ref().unwrap().operation, "getObject");
+    assert_eq!(error.details["object"], "missing");
+
+    // 鉴权失败时只有状态码
+    let anonymous = Client::builder(serve(Mock::default()).await)
+        .build()
+        .unwrap();
+    let error = anonymous.head_object("photos", "a").await.unwrap_err();
+    let error = error.api().unwrap();
+    assert_eq!(error.kind(), ErrorKind::Unauthorized);
+    assert_eq!(error.code, None);
+}
+
+#[tokio::test]
+async fn test_list_objects() {
+    let mock = Mock::default();
+    let client = Client::builder(serve(mock.clone()).await)
+        .token(TOKEN)
+        .build()
+        .unwrap();
+
+    let options = ListObjectsOptions {
+        prefix: Some("2025/".into()),
+        delimiter: Some("/".into()),
+        max_keys: Some(1),
+        continuation_token: Some("abc".into()),
+        ..Default::default()
+    };
+    let page = client.list_objects("photos", &options).await.unwrap();
+    assert_eq!(page.objects.len(), 1);
+    assert_eq!(page.objects[0].user_meta["user"], "alex");
+    assert_eq!(page.common_prefixes, ["2025/raw/"]);
+    assert!(page.is_truncated);
+    assert_eq!(page.next_continuation_token.as_deref(), Some("next"));
+
+    let (_, uri, _) = mock.requests.lock().unwrap()[0].clone();
+    assert_eq!(
+        uri,
+        "/v1/photos?prefix=2025%2F&delimiter=%2F&max-keys=1&continuation-token=abc"
+    );
+}
+
+#[tokio::test]
+async fn test_bucket_options() {
+    let mock = Mock::default();
+    let client = Client::builder(serve(mock.clone()).await)
+        .token(TOKEN)
+        .build()
+        .unwrap();
+
+    let options = BucketOptions {
+        max_bytes: Some(1024),
+        ..Default::default()
+    };
+    client.create_bucket("photos", &options).await.unwrap();
+
+    let (method, uri, headers) = mock.requests.lock().unwrap()[0].clone();
+    assert_eq!((method, uri.as_str()), (Method::PUT, "/v1/photos"));
+    assert_eq!(headers["x-crab-vault-max-bytes"], "1024");
+    assert!(!headers.contains_key("x-crab-vault-max-objects"));
+}
+
+#[tokio::test]
+async fn test_signer_refreshes_rejected_token() {
+    let mock = Mock::default();
+    *mock.reject.lock().unwrap() = 1;
+
+    let encoder = JwtEncoder::new(HashMap::from([(
+        "kid".to_string(),
+        (EncodingKey::from_secret(b"secret"), Algorithm::HS256),
+    )]));
+    let permissions = PermissionSet::new(vec![Permission::new_root()]);
+    let signer = TokenSigner::new(encoder, "crab-vault", &["crab-vault"], permissions);
+    let client = Client::builder(serve(mock.clone()).await)
+        .signer(signer)
+        .build()
+        .unwrap();
+
+    client.delete_bucket("photos").await.unwrap();
+    client.delete_bucket("photos").await.unwrap();
+
+    // 第一个令牌被拒绝后重新签发，之后一直使用新的令牌
+    let tokens: Vec<_> = mock
+        .requests
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(_, _, headers)| headers["authorization"].clone())
+        .collect();
+    assert_eq!(tokens.len(), 3);
+    assert_ne!(tokens[0], tokens[1]);
+    assert_eq!(tokens[1], tokens[2]);
+}
+
+#[test]
+fn test_invalid_endpoint() {
+    for endpoint in [
+        "localhost:32767",
+        "ftp://localhost",
+        "http://localhost/?a=b",
+    ] {
+        assert!(matches!(
+            Client::builder(endpoint).build(),
+            Err(ClientError::InvalidEndpoint(_))
+        ));
+    }
+}
@@ -0,0 +1,18 @@
+CREATE TABLE IF NOT EXISTS bucket_meta (
+    name        TEXT        PRIMARY KEY,
+    user_meta   JSONB       NOT NULL DEFAULT '{}'::jsonb,
+    created_at  TIMESTAMPTZ NOT NULL,
+    updated_at  TIMESTAMPTZ NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS object_meta (
+    bucket_name  TEXT        NOT NULL,
+    object_name  TEXT        NOT NULL,
+    size         BIGINT      NOT NULL,
+    content_type TEXT        NOT NULL,
+    etag         TEXT        NOT NULL,
+    user_meta    JSONB       NOT NULL DEFAULT '{}'::jsonb,
+    created_at   TIMESTAMPTZ NOT NULL,
+    updated_at   TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (bucket_name, object_name)
+);
@@ -0,0 +1 @@
+ALTER TABLE bucket_meta ADD COLUMN IF NOT EXISTS data_key JSONB;
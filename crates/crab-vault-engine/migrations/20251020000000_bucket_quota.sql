@@ -0,0 +1,2 @@
+ALTER TABLE bucket_meta ADD COLUMN IF NOT EXISTS max_bytes BIGINT;
+ALTER TABLE bucket_meta ADD COLUMN IF NOT EXISTS max_objects BIGINT;
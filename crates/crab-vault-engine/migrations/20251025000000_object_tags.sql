@@ -0,0 +1 @@
+ALTER TABLE object_meta ADD COLUMN IF NOT EXISTS tags JSONB;
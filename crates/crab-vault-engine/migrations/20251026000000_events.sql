@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS events (
+    seq     BIGSERIAL   PRIMARY KEY,
+    op      TEXT        NOT NULL,
+    bucket  TEXT        NOT NULL,
+    object  TEXT,
+    etag    TEXT,
+    time    TIMESTAMPTZ NOT NULL
+);
@@ -0,0 +1,403 @@
+use base64::{Engine, prelude::BASE64_STANDARD};
+use chrono::{DateTime, Utc};
+use serde_json::{Value, json};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    BucketMeta, ObjectMeta, clock,
+    compression::Compression,
+    crypto::WrappedKey,
+    error::{EngineError, EngineResult},
+    inventory::InventoryConfig,
+    lifecycle::LifecycleConfig,
+    retention::ObjectRetention,
+    tagging::Tags,
+};
+
+/// object 未指定 content type 时使用的默认值
+pub const DEFAULT_CONTENT_TYPE: &str = "application/octet-stream";
+
+/// 计算一段数据的 etag，即 sha256 摘要的标准 base64 编码
+pub fn compute_etag(data: &[u8]) -> String {
+    let mut hasher = EtagHasher::new();
+    hasher.update(data);
+    hasher.finish()
+}
+
+/// 分段计算 etag，结果与对拼接起来的数据调用 [`compute_etag`] 相同
+#[derive(Default, Clone)]
+pub struct EtagHasher(Sha256);
+
+impl EtagHasher {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn update(&mut self, data: &[u8]) {
+        self.0.update(data);
+    }
+
+    pub fn finish(self) -> String {
+        BASE64_STANDARD.encode(self.0.finalize())
+    }
+}
+
+/// 可以交给接受任意摘要算法的工具，例如 `crab_vault_utils::hashing::HashingWriter`
+impl sha2::digest::Update for EtagHasher {
+    #[inline]
+    fn update(&mut self, data: &[u8]) {
+        Digest::update(&mut self.0, data);
+    }
+}
+
+/// 检查一个 etag 是否是 [`compute_etag`] 能产生的格式
+pub fn is_valid_etag(etag: &str) -> bool {
+    BASE64_STANDARD
+        .decode(etag)
+        .is_ok_and(|v| v.len() == Sha256::output_size())
+}
+
+#[inline(always)]
+fn invalid(msg: String) -> EngineError {
+    EngineError::InvalidArgument(msg)
+}
+
+/// 检查时间戳，没有给出的都使用同一个 `now`，保证新建的元数据 `created_at == updated_at`
+fn timestamps(
+    created_at: Option<DateTime<Utc>>,
+    updated_at: Option<DateTime<Utc>>,
+) -> EngineResult<(DateTime<Utc>, DateTime<Utc>)> {
+    let now = clock::now();
+    let created_at = created_at.unwrap_or(now);
+    let updated_at = updated_at.unwrap_or(created_at.max(now));
+
+    if updated_at < created_at {
+        return Err(invalid(format!(
+            "updated_at ({updated_at}) is earlier than created_at ({created_at})"
+        )));
+    }
+
+    Ok((created_at, updated_at))
+}
+
+/// [`BucketMeta`] 的构造器，参见 [`BucketMeta::builder`]
+#[derive(Default)]
+pub struct BucketMetaBuilder {
+    name: String,
+    user_meta: Option<Value>,
+    data_key: Option<WrappedKey>,
+    policy: Option<Value>,
+    lifecycle: Option<LifecycleConfig>,
+    cors: Option<Value>,
+    inventory: Option<InventoryConfig>,
+    max_bytes: Option<u64>,
+    max_objects: Option<u64>,
+    created_at: Option<DateTime<Utc>>,
+    updated_at: Option<DateTime<Utc>>,
+}
+
+/// [`ObjectMeta`] 的构造器，参见 [`ObjectMeta::builder`]
+#[derive(Default)]
+pub struct ObjectMetaBuilder {
+    bucket_name: String,
+    object_name: String,
+    size: Option<u64>,
+    content_type: Option<String>,
+    etag: Option<String>,
+    /// 由 [`data`](ObjectMetaBuilder::data) 计算得出的 (size, etag)
+    computed: Option<(u64, String)>,
+    user_meta: Option<Value>,
+    created_at: Option<DateTime<Utc>>,
+    updated_at: Option<DateTime<Utc>>,
+    expires_at: Option<DateTime<Utc>>,
+    compression: Option<Compression>,
+    tags: Tags,
+    retention: ObjectRetention,
+}
+
+impl BucketMeta {
+    /// ## 构造一个 [`BucketMeta`]
+    ///
+    /// [`build`](BucketMetaBuilder::build) 时保证：
+    ///
+    /// - `name` 非空
+    /// - `user_meta` 没有给出时为 `{}`
+    /// - 没有给出的时间戳都取同一个当前时间，且 `updated_at` 不早于 `created_at`
+    #[inline]
+    pub fn builder() -> BucketMetaBuilder {
+        BucketMetaBuilder::default()
+    }
+}
+
+impl ObjectMeta {
+    /// ## 构造一个 [`ObjectMeta`]
+    ///
+    /// [`build`](ObjectMetaBuilder::build) 时保证：
+    ///
+    /// - `bucket_name` 和 `object_name` 非空
+    /// - 给出了 [`data`](ObjectMetaBuilder::data) 时，`size` 和 `etag` 由数据计算得出，
+    ///   同时手动给出的 `size`、`etag` 必须与之一致
+    /// - `etag` 是 sha256 摘要的标准 base64 编码
+    /// - `user_meta` 没有给出时为 `{}`
+    /// - 没有给出的时间戳都取同一个当前时间，且 `updated_at` 不早于 `created_at`
+    #[inline]
+    pub fn builder() -> ObjectMetaBuilder {
+        ObjectMetaBuilder::default()
+    }
+}
+
+impl BucketMetaBuilder {
+    #[inline]
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    #[inline]
+    pub fn user_meta(mut self, user_meta: Value) -> Self {
+        self.user_meta = Some(user_meta);
+        self
+    }
+
+    #[inline]
+    pub fn data_key(mut self, data_key: Option<WrappedKey>) -> Self {
+        self.data_key = data_key;
+        self
+    }
+
+    #[inline]
+    pub fn policy(mut self, policy: Option<Value>) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    #[inline]
+    pub fn lifecycle(mut self, lifecycle: Option<LifecycleConfig>) -> Self {
+        self.lifecycle = lifecycle;
+        self
+    }
+
+    #[inline]
+    pub fn cors(mut self, cors: Option<Value>) -> Self {
+        self.cors = cors;
+        self
+    }
+
+    #[inline]
+    pub fn inventory(mut self, inventory: Option<InventoryConfig>) -> Self {
+        self.inventory = inventory;
+        self
+    }
+
+    #[inline]
+    pub fn max_bytes(mut self, max_bytes: Option<u64>) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    #[inline]
+    pub fn max_objects(mut self, max_objects: Option<u64>) -> Self {
+        self.max_objects = max_objects;
+        self
+    }
+
+    #[inline]
+    pub fn created_at(mut self, created_at: DateTime<Utc>) -> Self {
+        self.created_at = Some(created_at);
+        self
+    }
+
+    #[inline]
+    pub fn updated_at(mut self, updated_at: DateTime<Utc>) -> Self {
+        self.updated_at = Some(updated_at);
+        self
+    }
+
+    pub fn build(self) -> EngineResult<BucketMeta> {
+        let BucketMetaBuilder {
+            name,
+            user_meta,
+            data_key,
+            policy,
+            lifecycle,
+            cors,
+            inventory,
+            max_bytes,
+            max_objects,
+            created_at,
+            updated_at,
+        } = self;
+
+        if name.is_empty() {
+            return Err(invalid("bucket name should not be empty".into()));
+        }
+
+        let (created_at, updated_at) = timestamps(created_at, updated_at)?;
+
+        Ok(BucketMeta {
+            name,
+            user_meta: user_meta.unwrap_or_else(|| json!({})),
+            data_key,
+            policy,
+            lifecycle,
+            cors,
+            inventory,
+            max_bytes,
+            max_objects,
+            created_at,
+            updated_at,
+        })
+    }
+}
+
+impl ObjectMetaBuilder {
+    #[inline]
+    pub fn bucket_name(mut self, bucket_name: impl Into<String>) -> Self {
+        self.bucket_name = bucket_name.into();
+        self
+    }
+
+    #[inline]
+    pub fn object_name(mut self, object_name: impl Into<String>) -> Self {
+        self.object_name = object_name.into();
+        self
+    }
+
+    /// 根据数据计算 `size` 和 `etag`
+    #[inline]
+    pub fn data(mut self, data: &[u8]) -> Self {
+        self.computed = Some((data.len() as u64, compute_etag(data)));
+        self
+    }
+
+    /// 手动指定大小，通常只在从存储中读出元数据时使用
+    #[inline]
+    pub fn size(mut self, size: u64) -> Self {
+        self.size = Some(size);
+        self
+    }
+
+    /// 手动指定 etag，通常只在从存储中读出元数据时使用
+    #[inline]
+    pub fn etag(mut self, etag: impl Into<String>) -> Self {
+        self.etag = Some(etag.into());
+        self
+    }
+
+    #[inline]
+    pub fn content_type(mut self, content_type: impl Into<String>) -> Self {
+        self.content_type = Some(content_type.into());
+        self
+    }
+
+    #[inline]
+    pub fn user_meta(mut self, user_meta: Value) -> Self {
+        self.user_meta = Some(user_meta);
+        self
+    }
+
+    #[inline]
+    pub fn created_at(mut self, created_at: DateTime<Utc>) -> Self {
+        self.created_at = Some(created_at);
+        self
+    }
+
+    #[inline]
+    pub fn updated_at(mut self, updated_at: DateTime<Utc>) -> Self {
+        self.updated_at = Some(updated_at);
+        self
+    }
+
+    #[inline]
+    pub fn expires_at(mut self, expires_at: Option<DateTime<Utc>>) -> Self {
+        self.expires_at = expires_at;
+        self
+    }
+
+    #[inline]
+    pub fn compression(mut self, compression: Option<Compression>) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    #[inline]
+    pub fn tags(mut self, tags: Tags) -> Self {
+        self.tags = tags;
+        self
+    }
+
+    #[inline]
+    pub fn retention(mut self, retention: ObjectRetention) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    pub fn build(self) -> EngineResult<ObjectMeta> {
+        let ObjectMetaBuilder {
+            bucket_name,
+            object_name,
+            size,
+            content_type,
+            etag,
+            computed,
+            user_meta,
+            created_at,
+            updated_at,
+            expires_at,
+            compression,
+            tags,
+            retention,
+        } = self;
+
+        if bucket_name.is_empty() || object_name.is_empty() {
+            return Err(invalid(format!(
+                "bucket name and object name should not be empty, got `{bucket_name}/{object_name}`"
+            )));
+        }
+
+        let path = format!("{bucket_name}/{object_name}");
+
+        let (size, etag) = match (computed, size, etag) {
+            (Some((computed_size, _)), Some(size), _) if size != computed_size => {
+                return Err(invalid(format!(
+                    "size of `{path}` is {size}, but the data is {computed_size} bytes long"
+                )));
+            }
+            (Some((_, computed_etag)), _, Some(etag)) if etag != computed_etag => {
+                return Err(invalid(format!("etag of `{path}` does not match its data")));
+            }
+            (Some(computed), _, _) => computed,
+            (None, Some(size), Some(etag)) => (size, etag),
+            (None, _, _) => {
+                return Err(invalid(format!(
+                    "either the data or both size and etag of `{path}` should be given"
+                )));
+            }
+        };
+
+        if !is_valid_etag(&etag) {
+            return Err(invalid(format!(
+                "etag of `{path}` should be a base64 encoded sha256 digest, got `{etag}`"
+            )));
+        }
+
+        let (created_at, updated_at) = timestamps(created_at, updated_at)?;
+
+        Ok(ObjectMeta {
+            object_name,
+            bucket_name,
+            size,
+            content_type: content_type.unwrap_or_else(|| DEFAULT_CONTENT_TYPE.to_string()),
+            etag,
+            user_meta: user_meta.unwrap_or_else(|| json!({})),
+            created_at,
+            updated_at,
+            expires_at,
+            compression,
+            tags,
+            retention,
+        })
+    }
+}
@@ -0,0 +1,398 @@
+//! # 小 object 的数据缓存
+//!
+//! [`CachedDataEngine`] 包裹另一个 [`DataEngine`]，把读出的小 object 保存在内存中，总大小超出预算时淘汰最久没有读取的。
+//! 通过它写入、删除 object 时对应的缓存立即失效；绕过它直接修改后端的写入（例如另一个服务实例）不会被察觉
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    io,
+    path::Path,
+    pin::Pin,
+    sync::{Arc, Mutex, MutexGuard},
+    task::{Context, Poll},
+};
+
+use futures::{AsyncRead, io::Cursor};
+
+use crate::{DataEngine, ObjectReader, error::EngineResult};
+
+/// 默认只缓存不超过这个字节数的 object
+pub const DEFAULT_MAX_OBJECT_SIZE: usize = 256 << 10;
+
+/// 带有 LRU 缓存的 [`DataEngine`]，缓存的是后端中实际保存的数据，压缩、加密过的 object 仍然是压缩、加密的
+pub struct CachedDataEngine<D> {
+    inner: D,
+    max_object_size: usize,
+    lru: Arc<Mutex<Lru>>,
+}
+
+type Key = (String, String);
+
+struct Lru {
+    /// 所有缓存的数据的总字节数不超过这个值
+    max_memory: usize,
+    used: usize,
+    entries: HashMap<Key, Entry>,
+
+    /// 按照最后一次读取的先后排列，最前面的最先被淘汰
+    order: BTreeMap<u64, Key>,
+    tick: u64,
+
+    /// 每次失效都会增加，读取开始之后发生过失效时，读出的数据可能已经过时，不能放入缓存
+    generation: u64,
+}
+
+struct Entry {
+    data: Vec<u8>,
+    tick: u64,
+}
+
+impl<D> CachedDataEngine<D> {
+    /// 缓存的数据总共不超过 `max_memory` 字节
+    pub fn new(inner: D, max_memory: usize) -> Self {
+        Self {
+            inner,
+            max_object_size: DEFAULT_MAX_OBJECT_SIZE,
+            lru: Arc::new(Mutex::new(Lru::new(max_memory))),
+        }
+    }
+
+    /// 设置能够被缓存的 object 的最大字节数，默认为 [`DEFAULT_MAX_OBJECT_SIZE`]
+    pub fn max_object_size(mut self, max_object_size: usize) -> Self {
+        self.max_object_size = max_object_size;
+        self
+    }
+
+    /// 被包裹的后端，直接通过它写入不会使缓存失效
+    #[inline]
+    pub fn inner(&self) -> &D {
+        &self.inner
+    }
+
+    /// 当前缓存的数据的总字节数
+    pub fn cached_bytes(&self) -> usize {
+        self.lru().used
+    }
+
+    fn lru(&self) -> MutexGuard<'_, Lru> {
+        lock(&self.lru)
+    }
+
+    fn invalidate(&self, bucket_name: &str, object_name: &str) {
+        let mut lru = self.lru();
+        lru.generation += 1;
+        lru.remove(&(bucket_name.to_string(), object_name.to_string()));
+    }
+
+    fn invalidate_bucket(&self, bucket_name: &str) {
+        let mut lru = self.lru();
+        lru.generation += 1;
+        let keys: Vec<_> = lru
+            .entries
+            .keys()
+            .filter(|(bucket, _)| bucket == bucket_name)
+            .cloned()
+            .collect();
+        for key in keys {
+            lru.remove(&key);
+        }
+    }
+}
+
+fn lock(lru: &Mutex<Lru>) -> MutexGuard<'_, Lru> {
+    // 持有锁期间不会 panic，锁被污染时其中的数据仍然是一致的
+    lru.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// 一边读出后端的数据一边保存，读完时数据不超过大小限制就放入缓存
+struct FillingReader {
+    reader: ObjectReader,
+    lru: Arc<Mutex<Lru>>,
+    key: Key,
+    generation: u64,
+    max_object_size: usize,
+
+    /// 超过大小限制之后为 [`None`]
+    data: Option<Vec<u8>>,
+}
+
+impl AsyncRead for FillingReader {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = &mut *self;
+        let n = match this.reader.as_mut().poll_read(cx, buf) {
+            Poll::Ready(Ok(n)) => n,
+            other => return other,
+        };
+
+        match this.data.take() {
+            // 读完了，读取期间没有发生过失效时放入缓存
+            Some(data) if n == 0 => {
+                let mut lru = lock(&this.lru);
+                if lru.generation == this.generation {
+                    lru.insert(this.key.clone(), data);
+                }
+            }
+            Some(mut data) if data.len() + n <= this.max_object_size => {
+                data.extend_from_slice(&buf[..n]);
+                this.data = Some(data);
+            }
+            _ => {}
+        }
+        Poll::Ready(Ok(n))
+    }
+}
+
+impl Lru {
+    fn new(max_memory: usize) -> Self {
+        Self {
+            max_memory,
+            used: 0,
+            entries: HashMap::new(),
+            order: BTreeMap::new(),
+            tick: 0,
+            generation: 0,
+        }
+    }
+
+    fn get(&mut self, key: &Key) -> Option<Vec<u8>> {
+        let entry = self.entries.get_mut(key)?;
+        self.order.remove(&entry.tick);
+        self.tick += 1;
+        entry.tick = self.tick;
+        self.order.insert(self.tick, key.clone());
+        Some(entry.data.clone())
+    }
+
+    fn insert(&mut self, key: Key, data: Vec<u8>) {
+        if data.len() > self.max_memory {
+            return;
+        }
+
+        self.remove(&key);
+        while self.used + data.len() > self.max_memory {
+            let Some((_, oldest)) = self.order.pop_first() else {
+                break;
+            };
+            if let Some(entry) = self.entries.remove(&oldest) {
+                self.used -= entry.data.len();
+            }
+        }
+
+        self.tick += 1;
+        self.used += data.len();
+        self.order.insert(self.tick, key.clone());
+        self.entries.insert(
+            key,
+            Entry {
+                data,
+                tick: self.tick,
+            },
+        );
+    }
+
+    fn remove(&mut self, key: &Key) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.order.remove(&entry.tick);
+            self.used -= entry.data.len();
+        }
+    }
+}
+
+impl<D: DataEngine + Sync> DataEngine for CachedDataEngine<D> {
+    type Uri = D::Uri;
+
+    /// 使用 `D` 的默认构造方式，缓存大小为 0，也就是不缓存，之后需要通过 [`CachedDataEngine::new`] 设置
+    fn new<T: AsRef<Self::Uri>>(base_dir: T) -> EngineResult<Self> {
+        D::new(base_dir).map(|inner| Self::new(inner, 0))
+    }
+
+    async fn create_bucket(&self, bucket_name: &str) -> EngineResult<()> {
+        self.inner.create_bucket(bucket_name).await
+    }
+
+    async fn delete_bucket(&self, bucket_name: &str) -> EngineResult<()> {
+        let result = self.inner.delete_bucket(bucket_name).await;
+        self.invalidate_bucket(bucket_name);
+        result
+    }
+
+    async fn list_buckets(&self) -> EngineResult<Vec<String>> {
+        self.inner.list_buckets().await
+    }
+
+    async fn list_objects(&self, bucket_name: &str) -> EngineResult<Vec<String>> {
+        self.inner.list_objects(bucket_name).await
+    }
+
+    async fn create_object(
+        &self,
+        bucket_name: &str,
+        object_name: &str,
+        data: &[u8],
+    ) -> EngineResult<()> {
+        let result = self
+            .inner
+            .create_object(bucket_name, object_name, data)
+            .await;
+        self.invalidate(bucket_name, object_name);
+        result
+    }
+
+    async fn create_object_if_absent(
+        &self,
+        bucket_name: &str,
+        object_name: &str,
+        data: &[u8],
+    ) -> EngineResult<()> {
+        let result = self
+            .inner
+            .create_object_if_absent(bucket_name, object_name, data)
+            .await;
+        self.invalidate(bucket_name, object_name);
+        result
+    }
+
+    async fn create_object_from_file(
+        &self,
+        bucket_name: &str,
+        object_name: &str,
+        path: &Path,
+    ) -> EngineResult<()> {
+        let result = self
+            .inner
+            .create_object_from_file(bucket_name, object_name, path)
+            .await;
+        self.invalidate(bucket_name, object_name);
+        result
+    }
+
+    async fn read_object(&self, bucket_name: &str, object_name: &str) -> EngineResult<Vec<u8>> {
+        let key = (bucket_name.to_string(), object_name.to_string());
+        let generation = {
+            let mut lru = self.lru();
+            if let Some(data) = lru.get(&key) {
+                return Ok(data);
+            }
+            lru.generation
+        };
+
+        let data = self.inner.read_object(bucket_name, object_name).await?;
+        if data.len() <= self.max_object_size {
+            let mut lru = self.lru();
+            if lru.generation == generation {
+                lru.insert(key, data.clone());
+            }
+        }
+        Ok(data)
+    }
+
+    /// 没有缓存时使用后端的 reader，完整读完的小 object 同样会被放入缓存
+    async fn read_object_reader(
+        &self,
+        bucket_name: &str,
+        object_name: &str,
+    ) -> EngineResult<ObjectReader> {
+        let key = (bucket_name.to_string(), object_name.to_string());
+        let generation = {
+            let mut lru = self.lru();
+            if let Some(data) = lru.get(&key) {
+                return Ok(Box::pin(Cursor::new(data)));
+            }
+            lru.generation
+        };
+
+        let reader = self
+            .inner
+            .read_object_reader(bucket_name, object_name)
+            .await?;
+        Ok(Box::pin(FillingReader {
+            reader,
+            lru: self.lru.clone(),
+            key,
+            generation,
+            max_object_size: self.max_object_size,
+            data: Some(Vec::new()),
+        }))
+    }
+
+    async fn delete_object(&self, bucket_name: &str, object_name: &str) -> EngineResult<()> {
+        let result = self.inner.delete_object(bucket_name, object_name).await;
+        self.invalidate(bucket_name, object_name);
+        result
+    }
+
+    async fn copy_object(
+        &self,
+        src_bucket: &str,
+        src_object: &str,
+        dst_bucket: &str,
+        dst_object: &str,
+    ) -> EngineResult<()> {
+        let result = self
+            .inner
+            .copy_object(src_bucket, src_object, dst_bucket, dst_object)
+            .await;
+        self.invalidate(dst_bucket, dst_object);
+        result
+    }
+
+    async fn write_object_at(
+        &self,
+        bucket_name: &str,
+        object_name: &str,
+        offset: u64,
+        data: &[u8],
+    ) -> EngineResult<()> {
+        let result = self
+            .inner
+            .write_object_at(bucket_name, object_name, offset, data)
+            .await;
+        self.invalidate(bucket_name, object_name);
+        result
+    }
+
+    async fn append_object(
+        &self,
+        bucket_name: &str,
+        object_name: &str,
+        data: &[u8],
+    ) -> EngineResult<u64> {
+        let result = self
+            .inner
+            .append_object(bucket_name, object_name, data)
+            .await;
+        self.invalidate(bucket_name, object_name);
+        result
+    }
+
+    async fn punch_hole(
+        &self,
+        bucket_name: &str,
+        object_name: &str,
+        offset: u64,
+        len: u64,
+    ) -> EngineResult<()> {
+        let result = self
+            .inner
+            .punch_hole(bucket_name, object_name, offset, len)
+            .await;
+        self.invalidate(bucket_name, object_name);
+        result
+    }
+
+    async fn delete_objects(
+        &self,
+        bucket_name: &str,
+        object_names: &[String],
+    ) -> Vec<EngineResult<()>> {
+        let results = self.inner.delete_objects(bucket_name, object_names).await;
+        for object_name in object_names {
+            self.invalidate(bucket_name, object_name);
+        }
+        results
+    }
+}
@@ -0,0 +1,71 @@
+//! # 时间来源
+//!
+//! 所有后端写入的 `created_at`、`updated_at` 都来自 [`now`]，默认就是系统时间。
+//! 在打开任何后端之前调用 [`install`] 可以换成别的时间来源，
+//! 例如用 [`ManualClock`] 固定时间，使得响应中的时间戳在每次运行时都相同
+
+use std::sync::{
+    Arc, OnceLock,
+    atomic::{AtomicI64, Ordering},
+};
+
+use chrono::{DateTime, TimeDelta, Utc};
+
+/// 提供当前时间
+pub trait Clock: Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+}
+
+/// 系统时间，即 [`Utc::now`]
+#[derive(Default, Clone, Copy, Debug)]
+pub struct SystemClock;
+
+/// 只在手动修改时才会变化的时间，精确到微秒
+#[derive(Debug)]
+pub struct ManualClock {
+    micros: AtomicI64,
+}
+
+static CLOCK: OnceLock<Arc<dyn Clock>> = OnceLock::new();
+
+/// 替换全局的时间来源，只在第一次调用 [`now`] 之前有效
+///
+/// 返回是否替换成功，已经安装过或者已经使用了系统时间时返回 `false`
+pub fn install(clock: Arc<dyn Clock>) -> bool {
+    CLOCK.set(clock).is_ok()
+}
+
+/// 从全局的时间来源获取当前时间，没有安装过时使用 [`SystemClock`]
+pub fn now() -> DateTime<Utc> {
+    CLOCK.get_or_init(|| Arc::new(SystemClock)).now()
+}
+
+impl Clock for SystemClock {
+    #[inline]
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+impl ManualClock {
+    pub fn new(now: DateTime<Utc>) -> Self {
+        Self {
+            micros: AtomicI64::new(now.timestamp_micros()),
+        }
+    }
+
+    pub fn set(&self, now: DateTime<Utc>) {
+        self.micros.store(now.timestamp_micros(), Ordering::SeqCst);
+    }
+
+    pub fn advance(&self, delta: TimeDelta) {
+        let delta = delta.num_microseconds().unwrap_or(i64::MAX);
+        self.micros.fetch_add(delta, Ordering::SeqCst);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> DateTime<Utc> {
+        DateTime::from_timestamp_micros(self.micros.load(Ordering::SeqCst)).unwrap_or_default()
+    }
+}
@@ -0,0 +1,99 @@
+//! # 透明压缩
+//!
+//! 数据引擎只负责保存字节，压缩由调用者在写入之前完成，读出之后再解压。使用的算法和压缩后的大小记录在
+//! [`ObjectMeta::compression`] 中，`size` 和 `etag` 仍然是原始数据的，所以配额、列表和条件请求都不受影响。
+//! 服务端复制直接复制压缩后的数据，目标 object 继承源的压缩信息
+
+use std::io::{Read, Write};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ObjectMeta,
+    error::{EngineError, EngineResult},
+};
+
+/// 压缩算法
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Codec {
+    Gzip,
+    Zstd,
+}
+
+/// 保存在 [`ObjectMeta`] 中的压缩信息，没有压缩的 object 没有这一项
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub struct Compression {
+    pub codec: Codec,
+
+    /// 实际保存的字节数
+    pub stored_size: u64,
+}
+
+impl Codec {
+    /// 在 `Content-Encoding` 和 `Accept-Encoding` 中使用的名称
+    pub fn token(self) -> &'static str {
+        match self {
+            Codec::Gzip => "gzip",
+            Codec::Zstd => "zstd",
+        }
+    }
+
+    pub fn compress(self, data: &[u8]) -> EngineResult<Vec<u8>> {
+        match self {
+            Codec::Gzip => {
+                let mut encoder =
+                    flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(data).map_err(compression_error)?;
+                encoder.finish().map_err(compression_error)
+            }
+            Codec::Zstd => {
+                zstd::encode_all(data, zstd::DEFAULT_COMPRESSION_LEVEL).map_err(compression_error)
+            }
+        }
+    }
+
+    pub fn decompress(self, data: &[u8]) -> EngineResult<Vec<u8>> {
+        let mut decompressed = vec![];
+        match self {
+            Codec::Gzip => flate2::read::GzDecoder::new(data)
+                .read_to_end(&mut decompressed)
+                .map_err(compression_error)?,
+            Codec::Zstd => zstd::Decoder::new(data)
+                .and_then(|mut v| v.read_to_end(&mut decompressed))
+                .map_err(compression_error)?,
+        };
+        Ok(decompressed)
+    }
+}
+
+impl Compression {
+    /// ## 用 `codec` 压缩 `data`，压缩之后没有变小时返回 [`None`]
+    ///
+    /// 已经压缩过的格式（图片、视频、压缩包等）再压缩通常只会变大，这时保存原始数据
+    pub fn apply(codec: Codec, data: &[u8]) -> EngineResult<Option<(Self, Vec<u8>)>> {
+        let compressed = codec.compress(data)?;
+        Ok((compressed.len() < data.len()).then(|| {
+            let compression = Compression {
+                codec,
+                stored_size: compressed.len() as u64,
+            };
+            (compression, compressed)
+        }))
+    }
+}
+
+impl ObjectMeta {
+    /// 把从数据引擎读出的数据还原为原始数据，没有压缩时原样返回
+    pub fn decode(&self, data: Vec<u8>) -> EngineResult<Vec<u8>> {
+        match self.compression {
+            Some(compression) => compression.codec.decompress(&data),
+            None => Ok(data),
+        }
+    }
+}
+
+fn compression_error(e: std::io::Error) -> EngineError {
+    EngineError::Compression(e.to_string())
+}
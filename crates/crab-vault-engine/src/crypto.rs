@@ -0,0 +1,349 @@
+use std::{collections::HashMap, sync::Arc};
+
+use aes_gcm::{
+    Aes256Gcm, Key, KeyInit, Nonce,
+    aead::{Aead, AeadCore, OsRng},
+};
+use base64::{Engine, prelude::BASE64_STANDARD};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    ObjectMeta,
+    builder::compute_etag,
+    error::{EngineError, EngineResult},
+};
+
+pub mod stream;
+
+/// AES-256 密钥的长度
+pub const KEY_LEN: usize = 32;
+
+/// user meta 中以此开头的键保留给服务端，客户端不能设置，也不会在响应中看到
+pub const RESERVED_META_PREFIX: &str = "crab-vault:";
+
+/// object 的加密信息在 user meta 中的键，参见 [`ObjectEncryption`]
+pub const ENCRYPTION_META_KEY: &str = "crab-vault:sse";
+
+/// 由客户密钥包裹的数据密钥的 [`WrappedKey::master_key_id`]
+pub const CUSTOMER_KEY_ID: &str = "customer-provided";
+
+/// 一个明文的数据密钥，只应当存在于内存中
+///
+/// 每一个 bucket 有一个，开启了加密时每一个 object 也有一个
+#[derive(Clone)]
+pub struct DataKey([u8; KEY_LEN]);
+
+/// 被主密钥加密（包裹）过的数据密钥，保存在 [`BucketMeta`](crate::BucketMeta) 中
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub struct WrappedKey {
+    /// 包裹这个数据密钥的主密钥的 id
+    pub master_key_id: String,
+
+    /// base64 编码的 nonce
+    pub nonce: String,
+
+    /// base64 编码的密文
+    pub ciphertext: String,
+}
+
+/// ## 主密钥，用于包裹和解包数据密钥
+///
+/// 配置文件中的主密钥是本地的 AES-256-GCM 密钥。主密钥保存在外部的 KMS 中时，实现这个 trait 并通过
+/// [`KeyRing::with_master_keys`] 使用它，数据密钥的明文只会交给这个实现
+pub trait MasterKey: Send + Sync {
+    /// 包裹一个数据密钥，返回 nonce 和密文，不需要 nonce 的实现可以返回空的 nonce
+    fn wrap(&self, data_key: &[u8; KEY_LEN]) -> EngineResult<(Vec<u8>, Vec<u8>)>;
+
+    /// 解包 [`wrap`](Self::wrap) 的结果
+    fn unwrap(&self, nonce: &[u8], ciphertext: &[u8]) -> EngineResult<[u8; KEY_LEN]>;
+}
+
+/// 主密钥的集合，用于包裹和解包数据密钥
+///
+/// 新的数据密钥总是使用 `active` 指定的主密钥包裹，其余的主密钥只用于解包旧的数据密钥
+#[derive(Clone)]
+pub struct KeyRing {
+    master_keys: HashMap<String, Arc<dyn MasterKey>>,
+    active: String,
+}
+
+/// ## object 的加密信息，保存在 user meta 的 [`ENCRYPTION_META_KEY`] 中
+///
+/// 数据使用只属于这个 object 的数据密钥以 [`stream`] 的格式加密，数据密钥由主密钥包裹
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub struct ObjectEncryption {
+    #[serde(flatten)]
+    pub data_key: WrappedKey,
+
+    /// 密文的 etag，用于在没有主密钥的情况下检查数据和元数据是否一致
+    pub stored_etag: String,
+
+    /// 数据密钥由客户提供的密钥包裹时，这个密钥的指纹，参见 [`CustomerKey`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub customer_key_sha256: Option<String>,
+}
+
+/// ## 客户在请求中提供的密钥（SSE-C）
+///
+/// 它代替主密钥包裹每个 object 自己的数据密钥，因此同一个客户密钥加密的多个 object 不会重复使用 nonce。
+/// 服务端只保存它的 sha256 指纹，读取时必须提供同一个密钥
+#[derive(Clone)]
+pub struct CustomerKey {
+    key_ring: Arc<KeyRing>,
+    fingerprint: String,
+}
+
+/// 配置文件中的主密钥
+struct LocalMasterKey(Aes256Gcm);
+
+impl DataKey {
+    /// 随机生成一个新的数据密钥
+    pub fn generate() -> Self {
+        Self(Aes256Gcm::generate_key(OsRng).into())
+    }
+
+    #[inline]
+    pub fn as_bytes(&self) -> &[u8; KEY_LEN] {
+        &self.0
+    }
+}
+
+impl MasterKey for LocalMasterKey {
+    fn wrap(&self, data_key: &[u8; KEY_LEN]) -> EngineResult<(Vec<u8>, Vec<u8>)> {
+        let nonce = Aes256Gcm::generate_nonce(OsRng);
+        let ciphertext = self
+            .0
+            .encrypt(&nonce, data_key.as_slice())
+            .map_err(|e| EngineError::Encryption(format!("cannot wrap data key: {e}")))?;
+        Ok((nonce.to_vec(), ciphertext))
+    }
+
+    fn unwrap(&self, nonce: &[u8], ciphertext: &[u8]) -> EngineResult<[u8; KEY_LEN]> {
+        if nonce.len() != 12 {
+            return Err(EngineError::Encryption(
+                "malformed wrapped key: nonce must be 12 bytes".into(),
+            ));
+        }
+
+        let plaintext = self
+            .0
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|e| EngineError::Encryption(format!("cannot unwrap data key: {e}")))?;
+
+        plaintext.try_into().map_err(|_| {
+            EngineError::Encryption("unwrapped data key has an invalid length".into())
+        })
+    }
+}
+
+impl KeyRing {
+    /// 创建一个 [`KeyRing`]
+    ///
+    /// `master_keys` 是主密钥 id 到密钥本身的映射，`active` 必须是其中的一个 id
+    pub fn new(master_keys: HashMap<String, [u8; KEY_LEN]>, active: String) -> EngineResult<Self> {
+        let master_keys = master_keys
+            .into_iter()
+            .map(|(id, key)| {
+                let key = LocalMasterKey(Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key)));
+                (id, Arc::new(key) as Arc<dyn MasterKey>)
+            })
+            .collect();
+
+        Self::with_master_keys(master_keys, active)
+    }
+
+    /// 使用任意的 [`MasterKey`] 实现创建一个 [`KeyRing`]，`active` 必须是其中的一个 id
+    pub fn with_master_keys(
+        master_keys: HashMap<String, Arc<dyn MasterKey>>,
+        active: String,
+    ) -> EngineResult<Self> {
+        if !master_keys.contains_key(&active) {
+            return Err(EngineError::Encryption(format!(
+                "active master key `{active}` is not in the key ring"
+            )));
+        }
+
+        Ok(Self {
+            master_keys,
+            active,
+        })
+    }
+
+    /// 当前用于包裹新密钥的主密钥 id
+    #[inline]
+    pub fn active_key_id(&self) -> &str {
+        &self.active
+    }
+
+    /// 使用当前的主密钥包裹一个数据密钥
+    pub fn wrap_key(&self, data_key: &DataKey) -> EngineResult<WrappedKey> {
+        let (nonce, ciphertext) = self.master_keys[&self.active].wrap(&data_key.0)?;
+
+        Ok(WrappedKey {
+            master_key_id: self.active.clone(),
+            nonce: BASE64_STANDARD.encode(nonce),
+            ciphertext: BASE64_STANDARD.encode(ciphertext),
+        })
+    }
+
+    /// 使用 `wrapped` 中记录的主密钥解包数据密钥
+    pub fn unwrap_key(&self, wrapped: &WrappedKey) -> EngineResult<DataKey> {
+        let master_key = self.master_keys.get(&wrapped.master_key_id).ok_or_else(|| {
+            EngineError::Encryption(format!(
+                "master key `{}` is not in the key ring",
+                wrapped.master_key_id
+            ))
+        })?;
+
+        let decode = |value: &str| {
+            BASE64_STANDARD
+                .decode(value)
+                .map_err(|e| EngineError::Encryption(format!("malformed wrapped key: {e}")))
+        };
+
+        master_key
+            .unwrap(&decode(&wrapped.nonce)?, &decode(&wrapped.ciphertext)?)
+            .map(DataKey)
+    }
+
+    /// 生成一个新的数据密钥并立即包裹
+    pub fn generate_wrapped(&self) -> EngineResult<WrappedKey> {
+        self.wrap_key(&DataKey::generate())
+    }
+
+    /// 将一个数据密钥改为由当前主密钥包裹，数据密钥本身不变，因此无需重新加密 object
+    ///
+    /// 如果已经由当前主密钥包裹，返回 [`None`]
+    pub fn rewrap(&self, wrapped: &WrappedKey) -> EngineResult<Option<WrappedKey>> {
+        if wrapped.master_key_id == self.active {
+            return Ok(None);
+        }
+
+        self.wrap_key(&self.unwrap_key(wrapped)?).map(Some)
+    }
+
+    /// 为一个 object 生成新的数据密钥，返回包裹后的数据密钥和用于加密数据的 [`StreamEncryptor`](stream::StreamEncryptor)
+    pub fn encryptor(&self) -> EngineResult<(WrappedKey, stream::StreamEncryptor)> {
+        let data_key = DataKey::generate();
+        Ok((
+            self.wrap_key(&data_key)?,
+            stream::StreamEncryptor::new(&data_key),
+        ))
+    }
+
+    /// 使用新的数据密钥加密一个 object 的全部数据
+    pub fn seal(&self, data: &[u8]) -> EngineResult<(ObjectEncryption, Vec<u8>)> {
+        let data_key = DataKey::generate();
+        let ciphertext = stream::encrypt(&data_key, data)?;
+        let encryption = ObjectEncryption {
+            data_key: self.wrap_key(&data_key)?,
+            stored_etag: compute_etag(&ciphertext),
+            customer_key_sha256: None,
+        };
+        Ok((encryption, ciphertext))
+    }
+
+    /// 解密 [`seal`](Self::seal) 或者 [`encryptor`](Self::encryptor) 加密的数据
+    pub fn open(&self, encryption: &ObjectEncryption, data: &[u8]) -> EngineResult<Vec<u8>> {
+        stream::decrypt(&self.unwrap_key(&encryption.data_key)?, data)
+    }
+}
+
+impl CustomerKey {
+    pub fn new(key: [u8; KEY_LEN]) -> Self {
+        let fingerprint = BASE64_STANDARD.encode(Sha256::digest(key));
+        let master_keys = HashMap::from([(CUSTOMER_KEY_ID.to_string(), key)]);
+        let key_ring = KeyRing::new(master_keys, CUSTOMER_KEY_ID.into())
+            .expect("the only master key is the active one");
+
+        Self {
+            key_ring: Arc::new(key_ring),
+            fingerprint,
+        }
+    }
+
+    /// 标准 base64 编码的密钥的 sha256 摘要
+    #[inline]
+    pub fn fingerprint(&self) -> &str {
+        &self.fingerprint
+    }
+
+    /// 只包含这个密钥的 [`KeyRing`]，用于加密和解密数据，加密信息需要再由 [`mark`](Self::mark) 标记
+    #[inline]
+    pub fn key_ring(&self) -> &Arc<KeyRing> {
+        &self.key_ring
+    }
+
+    /// 在加密信息中记录这个密钥的指纹
+    pub fn mark(&self, encryption: &mut ObjectEncryption) {
+        encryption.customer_key_sha256 = Some(self.fingerprint.clone());
+    }
+}
+
+impl ObjectMeta {
+    /// ## 检查请求中给出的客户密钥是否能够读取这个 object
+    ///
+    /// 以客户密钥加密的 object 必须给出同一个密钥，其他 object 不能给出客户密钥
+    pub fn check_customer_key(&self, key: Option<&CustomerKey>) -> EngineResult<()> {
+        let expected = self.encryption()?.and_then(|v| v.customer_key_sha256);
+        match (expected, key) {
+            (None, None) => Ok(()),
+            (Some(expected), Some(key)) if expected == key.fingerprint => Ok(()),
+            (Some(_), None) => Err(EngineError::CustomerKeyRequired {
+                bucket: self.bucket_name.clone(),
+                object: self.object_name.clone(),
+            }),
+            _ => Err(EngineError::CustomerKeyMismatch {
+                bucket: self.bucket_name.clone(),
+                object: self.object_name.clone(),
+            }),
+        }
+    }
+
+    /// 这个 object 的加密信息，没有加密时为 [`None`]
+    pub fn encryption(&self) -> EngineResult<Option<ObjectEncryption>> {
+        match self.user_meta.get(ENCRYPTION_META_KEY) {
+            Some(value) => serde_json::from_value(value.clone()).map(Some).map_err(|e| {
+                EngineError::Encryption(format!("malformed encryption metadata: {e}"))
+            }),
+            None => Ok(None),
+        }
+    }
+
+    /// 记录或者清除加密信息，user meta 不是 JSON 对象时会被替换为只包含加密信息的对象
+    pub fn set_encryption(&mut self, encryption: Option<&ObjectEncryption>) -> EngineResult<()> {
+        if !self.user_meta.is_object() {
+            self.user_meta = Value::Object(Default::default());
+        }
+        let Value::Object(map) = &mut self.user_meta else {
+            unreachable!()
+        };
+
+        match encryption {
+            Some(encryption) => {
+                map.insert(ENCRYPTION_META_KEY.into(), serde_json::to_value(encryption)?);
+            }
+            None => {
+                map.remove(ENCRYPTION_META_KEY);
+            }
+        }
+        Ok(())
+    }
+
+    /// 去掉 user meta 中保留给服务端的键，用于把元数据返回给客户端
+    pub fn hide_reserved_meta(mut self) -> Self {
+        if let Value::Object(map) = &mut self.user_meta {
+            map.retain(|key, _| !is_reserved_meta_key(key));
+        }
+        self
+    }
+}
+
+/// `key` 是否是保留给服务端的 user meta 键，参见 [`RESERVED_META_PREFIX`]
+pub fn is_reserved_meta_key(key: &str) -> bool {
+    key.starts_with(RESERVED_META_PREFIX)
+}
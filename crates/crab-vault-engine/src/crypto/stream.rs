@@ -0,0 +1,152 @@
+//! # 分段的 AES-256-GCM
+//!
+//! 明文被切分为 [`SEGMENT_LEN`] 字节的分段，每一段单独加密并附带 16 字节的认证标签，因此加密和解密都只需要
+//! 在内存中保留一个分段。nonce 由分段的序号和“是否为最后一段”组成（即 STREAM 构造），调换、删除分段或者
+//! 截断密文都会导致解密失败
+//!
+//! 每个数据密钥只用于加密一个 object，所以 nonce 不需要随机的部分
+
+use aes_gcm::{Aes256Gcm, Key, KeyInit, Nonce, aead::Aead};
+
+use crate::{
+    crypto::DataKey,
+    error::{EngineError, EngineResult},
+};
+
+/// 每一段明文的长度，最后一段可以更短，但至少有一段
+pub const SEGMENT_LEN: usize = 64 * 1024;
+
+/// 每一段密文附带的认证标签的长度
+pub const TAG_LEN: usize = 16;
+
+/// 逐段加密，参见 [模块文档](self)
+pub struct StreamEncryptor {
+    cipher: Aes256Gcm,
+    counter: u32,
+    buffer: Vec<u8>,
+}
+
+/// 逐段解密，参见 [模块文档](self)
+pub struct StreamDecryptor {
+    cipher: Aes256Gcm,
+    counter: u32,
+    buffer: Vec<u8>,
+}
+
+/// 一次加密全部数据
+pub fn encrypt(key: &DataKey, data: &[u8]) -> EngineResult<Vec<u8>> {
+    let mut encryptor = StreamEncryptor::new(key);
+    let mut ciphertext = encryptor.update(data)?;
+    ciphertext.extend(encryptor.finish()?);
+    Ok(ciphertext)
+}
+
+/// 一次解密全部数据
+pub fn decrypt(key: &DataKey, data: &[u8]) -> EngineResult<Vec<u8>> {
+    let mut decryptor = StreamDecryptor::new(key);
+    let mut plaintext = decryptor.update(data)?;
+    plaintext.extend(decryptor.finish()?);
+    Ok(plaintext)
+}
+
+/// 加密之后的长度
+pub fn encrypted_len(len: u64) -> u64 {
+    let segments = len.div_ceil(SEGMENT_LEN as u64).max(1);
+    len + segments * TAG_LEN as u64
+}
+
+impl StreamEncryptor {
+    pub fn new(key: &DataKey) -> Self {
+        Self {
+            cipher: cipher_of(key),
+            counter: 0,
+            buffer: vec![],
+        }
+    }
+
+    /// 追加明文，返回已经可以输出的密文
+    ///
+    /// 最后一段要等到 [`finish`](Self::finish) 时才能确定，所以总是留下至少一个字节
+    pub fn update(&mut self, data: &[u8]) -> EngineResult<Vec<u8>> {
+        self.buffer.extend_from_slice(data);
+
+        let mut ciphertext = vec![];
+        while self.buffer.len() > SEGMENT_LEN {
+            let segment: Vec<u8> = self.buffer.drain(..SEGMENT_LEN).collect();
+            ciphertext.extend(self.seal(&segment, false)?);
+        }
+        Ok(ciphertext)
+    }
+
+    /// 加密剩下的数据作为最后一段
+    pub fn finish(mut self) -> EngineResult<Vec<u8>> {
+        let segment = std::mem::take(&mut self.buffer);
+        self.seal(&segment, true)
+    }
+
+    fn seal(&mut self, segment: &[u8], last: bool) -> EngineResult<Vec<u8>> {
+        let nonce = nonce_of(self.counter, last)?;
+        self.counter += 1;
+        self.cipher
+            .encrypt(Nonce::from_slice(&nonce), segment)
+            .map_err(|e| EngineError::Encryption(format!("cannot encrypt object data: {e}")))
+    }
+}
+
+impl StreamDecryptor {
+    pub fn new(key: &DataKey) -> Self {
+        Self {
+            cipher: cipher_of(key),
+            counter: 0,
+            buffer: vec![],
+        }
+    }
+
+    /// 追加密文，返回已经可以输出的明文，与 [`StreamEncryptor::update`] 一样总是留下最后一段
+    pub fn update(&mut self, data: &[u8]) -> EngineResult<Vec<u8>> {
+        self.buffer.extend_from_slice(data);
+
+        let mut plaintext = vec![];
+        while self.buffer.len() > SEGMENT_LEN + TAG_LEN {
+            let segment: Vec<u8> = self.buffer.drain(..SEGMENT_LEN + TAG_LEN).collect();
+            plaintext.extend(self.open(&segment, false)?);
+        }
+        Ok(plaintext)
+    }
+
+    /// 解密最后一段，密文被截断时在这里失败
+    pub fn finish(mut self) -> EngineResult<Vec<u8>> {
+        let segment = std::mem::take(&mut self.buffer);
+        self.open(&segment, true)
+    }
+
+    fn open(&mut self, segment: &[u8], last: bool) -> EngineResult<Vec<u8>> {
+        let nonce = nonce_of(self.counter, last)?;
+        self.counter += 1;
+        self.cipher
+            .decrypt(Nonce::from_slice(&nonce), segment)
+            .map_err(|_| {
+                EngineError::Encryption(
+                    "cannot decrypt object data, it is corrupt or truncated".into(),
+                )
+            })
+    }
+}
+
+fn cipher_of(key: &DataKey) -> Aes256Gcm {
+    Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key.as_bytes()))
+}
+
+/// 7 字节的 0、4 字节大端序的分段序号、1 字节的最后一段标记
+fn nonce_of(counter: u32, last: bool) -> EngineResult<[u8; 12]> {
+    if counter == u32::MAX {
+        return Err(EngineError::Encryption(
+            "object is too large to be encrypted".into(),
+        ));
+    }
+
+    let mut nonce = [0; 12];
+    nonce[7..11].copy_from_slice(&counter.to_be_bytes());
+    nonce[11] = last as u8;
+    Ok(nonce)
+}
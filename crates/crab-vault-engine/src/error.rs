@@ -36,19 +36,96 @@ pub enum EngineError {
     #[error("object not found: {bucket}/{object}")]
     ObjectNotFound { bucket: String, object: String },
 
+    #[error("object already exists: {bucket}/{object}")]
+    ObjectAlreadyExists { bucket: String, object: String },
+
     #[error("object meta not found: {bucket}/{object}")]
     ObjectMetaNotFound { bucket: String, object: String },
 
+    /// 条件写入时 object 不存在，或者它的 etag 与期望的不同，参见 [`MetaEngine::create_object_meta_if`](crate::MetaEngine::create_object_meta_if)
+    #[error("etag of {bucket}/{object} does not match")]
+    EtagMismatch { bucket: String, object: String },
+
+    /// object 的保留仍然生效，不能被删除或者覆盖，参见 [`retention`](crate::retention)
+    #[error("object is locked by its retention settings: {bucket}/{object}")]
+    ObjectLocked { bucket: String, object: String },
+
+    /// 回收站中没有这个 object，或者没有指定的那一次删除，参见 [`trash`](crate::trash)
+    #[error("object not found in the trash: {bucket}/{object}")]
+    TrashEntryNotFound { bucket: String, object: String },
+
     #[allow(dead_code)]
     #[error("some other errors: {0}")]
-    Other(String),
+    Other(#[serde(skip)] String),
 
     #[allow(dead_code)]
     #[error("backend error: {0}")]
-    BackendError(String),
+    BackendError(#[serde(skip)] String),
+
+    /// 参见 [`name`](crate::name)
+    #[error("invalid bucket name {bucket:?}: {reason}")]
+    InvalidBucketName { bucket: String, reason: &'static str },
+
+    /// 参见 [`name`](crate::name)
+    #[error("invalid object name {object:?}: {reason}")]
+    InvalidObjectName { object: String, reason: &'static str },
 
     #[error("invalid argument: {0}")]
-    InvalidArgument(String),
+    InvalidArgument(#[serde(skip)] String),
+
+    #[error("encryption error: {0}")]
+    Encryption(#[serde(skip)] String),
+
+    /// 压缩或者解压失败，参见 [`compression`](crate::compression)
+    #[error("compression error: {0}")]
+    Compression(#[serde(skip)] String),
+
+    /// 列举时遇到的无法解析的元数据，`entry` 是 bucket 或 object 的名称
+    #[error("corrupt metadata: {entry}")]
+    CorruptMeta { entry: String },
+
+    /// 写入之后 bucket 中 object 的总字节数会超过 `max_bytes`，参见 [`usage`](crate::usage)
+    #[error("bucket {bucket} would exceed its quota of {limit} bytes")]
+    ByteQuotaExceeded { bucket: String, limit: u64 },
+
+    /// 写入之后 bucket 中 object 的个数会超过 `max_objects`，参见 [`usage`](crate::usage)
+    #[error("bucket {bucket} would exceed its quota of {limit} objects")]
+    ObjectQuotaExceeded { bucket: String, limit: u64 },
+
+    /// object 已经超过了 `expires_at`，只是还没有被生命周期任务删除，参见 [`lifecycle`](crate::lifecycle)
+    #[error("object expired: {bucket}/{object}")]
+    ObjectExpired { bucket: String, object: String },
+
+    /// object 以客户提供的密钥加密，但是请求中没有给出密钥，参见 [`CustomerKey`](crate::crypto::CustomerKey)
+    #[error("object {bucket}/{object} is encrypted with a customer-provided key, but no key was given")]
+    CustomerKeyRequired { bucket: String, object: String },
+
+    /// 请求中给出的客户密钥与加密 object 时使用的不同，或者 object 并没有以客户密钥加密
+    #[error("the customer-provided key does not match object {bucket}/{object}")]
+    CustomerKeyMismatch { bucket: String, object: String },
+
+    /// 本地目录的结构与期望的不同，通常是配置中的路径指向了错误的目录，`reason` 中包含修复建议
+    #[error("unexpected directory layout at {path}: {reason}")]
+    InvalidLayout {
+        path: String,
+        #[serde(skip)]
+        reason: String,
+    },
+}
+
+impl EngineError {
+    /// 批量操作整体失败时，为每一项生成一个相同的 [`BackendError`](EngineError::BackendError)
+    #[cfg(any(feature = "postgres", feature = "s3"))]
+    pub(crate) fn fan_out<T>(self, n: usize) -> Vec<EngineResult<T>> {
+        let detail = match self {
+            EngineError::BackendError(detail) => detail,
+            e => e.to_string(),
+        };
+
+        (0..n)
+            .map(|_| Err(EngineError::BackendError(detail.clone())))
+            .collect()
+    }
 }
 
 impl From<serde_json::error::Error> for EngineError {
@@ -69,10 +146,18 @@ impl From<serde_json::error::Error> for EngineError {
     }
 }
 
-impl IntoResponse for EngineError {
-    fn into_response(self) -> Response {
+#[cfg(feature = "postgres")]
+impl From<sqlx::Error> for EngineError {
+    fn from(value: sqlx::Error) -> Self {
+        EngineError::BackendError(value.to_string())
+    }
+}
+
+impl EngineError {
+    /// 这个错误对应的 HTTP 状态码
+    pub fn status_code(&self) -> StatusCode {
         use EngineError::*;
-        let code = match &self {
+        match self {
             Serde {
                 error: _,
                 line: _,
@@ -80,6 +165,10 @@ impl IntoResponse for EngineError {
             }
             | Io { error: _, path: _ }
             | BackendError(_)
+            | Encryption(_)
+            | Compression(_)
+            | CorruptMeta { entry: _ }
+            | InvalidLayout { path: _, reason: _ }
             | Other(_) => StatusCode::INTERNAL_SERVER_ERROR,
 
             ObjectNotFound {
@@ -92,24 +181,156 @@ impl IntoResponse for EngineError {
                 bucket: _,
                 object: _,
             }
+            | TrashEntryNotFound {
+                bucket: _,
+                object: _,
+            }
             | BucketMetaNotFound { bucket: _ } => StatusCode::NOT_FOUND,
 
             BucketNotEmpty { bucket: _ } => StatusCode::CONFLICT,
+            ObjectAlreadyExists {
+                bucket: _,
+                object: _,
+            }
+            | EtagMismatch {
+                bucket: _,
+                object: _,
+            } => StatusCode::PRECONDITION_FAILED,
             InvalidArgument(_) => StatusCode::UNPROCESSABLE_ENTITY,
-        };
+            InvalidBucketName {
+                bucket: _,
+                reason: _,
+            }
+            | InvalidObjectName {
+                object: _,
+                reason: _,
+            } => StatusCode::BAD_REQUEST,
+            ByteQuotaExceeded {
+                bucket: _,
+                limit: _,
+            } => StatusCode::INSUFFICIENT_STORAGE,
+            ObjectQuotaExceeded {
+                bucket: _,
+                limit: _,
+            } => StatusCode::FORBIDDEN,
+            ObjectExpired {
+                bucket: _,
+                object: _,
+            } => StatusCode::GONE,
+            ObjectLocked {
+                bucket: _,
+                object: _,
+            } => StatusCode::LOCKED,
+            CustomerKeyRequired {
+                bucket: _,
+                object: _,
+            } => StatusCode::BAD_REQUEST,
+            CustomerKeyMismatch {
+                bucket: _,
+                object: _,
+            } => StatusCode::FORBIDDEN,
+        }
+    }
+}
+
+impl EngineError {
+    /// ## 错误响应中的 `code`
+    ///
+    /// 客户端依靠它区分错误，一经发布就不再修改；与变体的名称分开写，重命名变体不会改变它
+    pub const fn error_code(&self) -> &'static str {
+        use EngineError::*;
+        match self {
+            Io { .. } => "io",
+            Serde { .. } => "serde",
+            BucketNotFound { .. } => "bucketNotFound",
+            BucketMetaNotFound { .. } => "bucketMetaNotFound",
+            BucketNotEmpty { .. } => "bucketNotEmpty",
+            ObjectNotFound { .. } => "objectNotFound",
+            ObjectAlreadyExists { .. } => "objectAlreadyExists",
+            ObjectMetaNotFound { .. } => "objectMetaNotFound",
+            EtagMismatch { .. } => "etagMismatch",
+            ObjectLocked { .. } => "objectLocked",
+            TrashEntryNotFound { .. } => "trashEntryNotFound",
+            Other(_) => "other",
+            BackendError(_) => "backendError",
+            InvalidBucketName { .. } => "invalidBucketName",
+            InvalidObjectName { .. } => "invalidObjectName",
+            InvalidArgument(_) => "invalidArgument",
+            Encryption(_) => "encryption",
+            Compression(_) => "compression",
+            CorruptMeta { .. } => "corruptMeta",
+            ByteQuotaExceeded { .. } => "byteQuotaExceeded",
+            ObjectQuotaExceeded { .. } => "objectQuotaExceeded",
+            ObjectExpired { .. } => "objectExpired",
+            CustomerKeyRequired { .. } => "customerKeyRequired",
+            CustomerKeyMismatch { .. } => "customerKeyMismatch",
+            InvalidLayout { .. } => "invalidLayout",
+        }
+    }
+
+    /// 出错的 bucket 或者 object，形如 `/{bucket}` 或者 `/{bucket}/{object}`，与具体资源无关的错误为 [`None`]
+    pub fn resource(&self) -> Option<String> {
+        use EngineError::*;
+        match self {
+            BucketNotFound { bucket }
+            | BucketMetaNotFound { bucket }
+            | BucketNotEmpty { bucket }
+            | InvalidBucketName { bucket, reason: _ }
+            | ByteQuotaExceeded { bucket, limit: _ }
+            | ObjectQuotaExceeded { bucket, limit: _ } => Some(format!("/{bucket}")),
+
+            ObjectNotFound { bucket, object }
+            | ObjectAlreadyExists { bucket, object }
+            | ObjectMetaNotFound { bucket, object }
+            | EtagMismatch { bucket, object }
+            | ObjectLocked { bucket, object }
+            | TrashEntryNotFound { bucket, object }
+            | ObjectExpired { bucket, object }
+            | CustomerKeyRequired { bucket, object }
+            | CustomerKeyMismatch { bucket, object } => Some(format!("/{bucket}/{object}")),
+
+            Io { .. }
+            | Serde { .. }
+            | Other(_)
+            | BackendError(_)
+            | InvalidObjectName { .. }
+            | InvalidArgument(_)
+            | Encryption(_)
+            | Compression(_)
+            | CorruptMeta { .. }
+            | InvalidLayout { .. } => None,
+        }
+    }
+}
+
+/// 与服务端的错误响应格式相同，只是没有 `request_id`
+impl IntoResponse for EngineError {
+    fn into_response(self) -> Response {
+        let code = self.status_code();
 
         #[derive(Serialize)]
-        struct Msg {
+        struct Body {
+            code: &'static str,
+            message: String,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            resource: Option<String>,
             #[serde(flatten)]
-            error: EngineError,
-            msg: String,
+            details: serde_json::Map<String, serde_json::Value>,
         }
 
+        let mut details = match serde_json::to_value(&self) {
+            Ok(serde_json::Value::Object(details)) => details,
+            _ => Default::default(),
+        };
+        details.remove("code");
+
         (
             code,
-            axum::Json(Msg {
-                msg: self.to_string(),
-                error: self,
+            axum::Json(Body {
+                code: self.error_code(),
+                message: self.to_string(),
+                resource: self.resource(),
+                details,
             }),
         )
             .into_response()
@@ -0,0 +1,112 @@
+//! # 变更事件日志
+//!
+//! 开启之后 [`MetaSource`](crate::MetaSource) 在每次修改元数据之后追加一条 [`Event`]，序号由后端分配并且严格递增，
+//! 外部的索引器和副本记下看到的最后一个序号，之后只读取更新的事件，不需要重新扫描所有元数据。
+//!
+//! 日志只保留最近的若干条，参见 [`MetaSource::record_events`](crate::MetaSource::record_events)，
+//! 落后太多的读者会错过被清理的事件，需要重新扫描一次
+
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{ObjectMeta, clock};
+
+/// 一次读取最多返回的事件数
+pub const MAX_EVENTS_PER_PAGE: usize = 1000;
+
+/// 事件对应的修改
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum EventOp {
+    /// 创建 bucket，或者修改了它的元数据、策略等
+    PutBucket,
+    DeleteBucket,
+
+    /// 写入 object，或者修改了它的元数据、标签等
+    PutObject,
+    DeleteObject,
+}
+
+/// 日志中的一条事件
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub struct Event {
+    /// 由后端在追加时分配，追加之前的值会被忽略
+    pub seq: u64,
+    pub op: EventOp,
+    pub bucket: String,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub object: Option<String>,
+
+    /// 写入之后 object 的 etag，只有 [`EventOp::PutObject`] 才有
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub etag: Option<String>,
+
+    pub time: DateTime<Utc>,
+}
+
+impl EventOp {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EventOp::PutBucket => "put-bucket",
+            EventOp::DeleteBucket => "delete-bucket",
+            EventOp::PutObject => "put-object",
+            EventOp::DeleteObject => "delete-object",
+        }
+    }
+
+    /// [`as_str`](Self::as_str) 的逆操作
+    pub fn from_name(name: &str) -> Option<Self> {
+        [
+            EventOp::PutBucket,
+            EventOp::DeleteBucket,
+            EventOp::PutObject,
+            EventOp::DeleteObject,
+        ]
+        .into_iter()
+        .find(|v| v.as_str() == name)
+    }
+}
+
+impl fmt::Display for EventOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Event {
+    fn new(op: EventOp, bucket: &str, object: Option<&str>, etag: Option<&str>) -> Self {
+        Self {
+            seq: 0,
+            op,
+            bucket: bucket.to_string(),
+            object: object.map(str::to_string),
+            etag: etag.map(str::to_string),
+            time: clock::now(),
+        }
+    }
+
+    pub fn put_bucket(bucket: &str) -> Self {
+        Self::new(EventOp::PutBucket, bucket, None, None)
+    }
+
+    pub fn delete_bucket(bucket: &str) -> Self {
+        Self::new(EventOp::DeleteBucket, bucket, None, None)
+    }
+
+    pub fn put_object(meta: &ObjectMeta) -> Self {
+        Self::new(
+            EventOp::PutObject,
+            &meta.bucket_name,
+            Some(&meta.object_name),
+            Some(&meta.etag),
+        )
+    }
+
+    pub fn delete_object(bucket: &str, object: &str) -> Self {
+        Self::new(EventOp::DeleteObject, bucket, Some(object), None)
+    }
+}
@@ -0,0 +1,267 @@
+//! # object 元数据的过滤表达式
+//!
+//! 表达式由若干个用 `and` 连接的条件组成，所有条件都满足时 object 才会被返回，例如：
+//!
+//! ```text
+//! content_type^=image/ and size>=1024 and size<1048576 and updated_at>=2025-01-01T00:00:00Z and meta.owner=alice
+//! ```
+//!
+//! - `content_type`：`=` 完全相同，`^=` 以此开头
+//! - `size`：`=`、`<`、`<=`、`>`、`>=`，单位为字节
+//! - `updated_at`：`<`、`<=`、`>`、`>=`，RFC 3339 格式
+//! - `meta.{key}`：`=`，用户元数据中这个键的值是相同的字符串，或者 JSON 表示相同的数字、布尔值
+//!
+//! 运算符两边可以有空白，值中有空白时用双引号括起来，其中的 `\"` 和 `\\` 是转义。
+//! 文件系统后端在内存中逐个检查，数据库后端把条件翻译为 SQL 交给数据库过滤
+
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{
+    ObjectMeta,
+    crypto::is_reserved_meta_key,
+    error::{EngineError, EngineResult},
+};
+
+/// 一个表达式最多的条件数
+pub const MAX_CONDITIONS: usize = 16;
+
+/// 解析之后的过滤表达式
+#[derive(Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(try_from = "String")]
+pub struct MetaFilter {
+    pub conditions: Vec<Condition>,
+}
+
+/// 表达式中的一个条件
+#[derive(Clone, Debug, PartialEq)]
+pub enum Condition {
+    /// `prefix` 为 `true` 时 `content_type` 以 `value` 开头即可
+    ContentType {
+        value: String,
+        prefix: bool,
+    },
+    Size(Comparison, u64),
+    UpdatedAt(Comparison, DateTime<Utc>),
+    UserMeta {
+        key: String,
+        value: String,
+    },
+}
+
+/// 比较运算符，数据库后端依赖这里的顺序对条件分组
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Comparison {
+    Eq,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl MetaFilter {
+    /// `meta` 是否满足所有的条件
+    pub fn matches(&self, meta: &ObjectMeta) -> bool {
+        self.conditions.iter().all(|v| v.matches(meta))
+    }
+}
+
+impl Condition {
+    pub fn matches(&self, meta: &ObjectMeta) -> bool {
+        match self {
+            Condition::ContentType {
+                value,
+                prefix: true,
+            } => meta.content_type.starts_with(value),
+            Condition::ContentType {
+                value,
+                prefix: false,
+            } => meta.content_type == *value,
+            Condition::Size(cmp, size) => cmp.holds(&meta.size, size),
+            Condition::UpdatedAt(cmp, time) => cmp.holds(&meta.updated_at, time),
+            Condition::UserMeta { key, value } => match meta.user_meta.get(key) {
+                Some(Value::String(v)) => v == value,
+                Some(v @ (Value::Number(_) | Value::Bool(_))) => {
+                    value.parse::<Value>().is_ok_and(|parsed| parsed == *v)
+                }
+                _ => false,
+            },
+        }
+    }
+}
+
+impl Comparison {
+    /// `lhs` 与 `rhs` 是否满足这个比较
+    pub fn holds<T: Ord>(self, lhs: &T, rhs: &T) -> bool {
+        match self {
+            Comparison::Eq => lhs == rhs,
+            Comparison::Lt => lhs < rhs,
+            Comparison::Le => lhs <= rhs,
+            Comparison::Gt => lhs > rhs,
+            Comparison::Ge => lhs >= rhs,
+        }
+    }
+}
+
+impl FromStr for MetaFilter {
+    type Err = EngineError;
+
+    fn from_str(expr: &str) -> EngineResult<Self> {
+        let mut parser = Parser { rest: expr };
+        let mut conditions = vec![];
+
+        loop {
+            if conditions.len() == MAX_CONDITIONS {
+                return Err(invalid(format!(
+                    "at most {MAX_CONDITIONS} conditions are allowed"
+                )));
+            }
+            conditions.push(parser.condition()?);
+
+            parser.skip_whitespace();
+            if parser.rest.is_empty() {
+                break;
+            }
+            parser.keyword("and")?;
+        }
+
+        Ok(Self { conditions })
+    }
+}
+
+impl TryFrom<String> for MetaFilter {
+    type Error = EngineError;
+
+    fn try_from(value: String) -> EngineResult<Self> {
+        value.parse()
+    }
+}
+
+/// 从左到右逐个读出条件
+struct Parser<'a> {
+    rest: &'a str,
+}
+
+/// 运算符，较长的排在前面
+const OPERATORS: [(&str, Option<Comparison>); 6] = [
+    ("^=", None),
+    ("<=", Some(Comparison::Le)),
+    (">=", Some(Comparison::Ge)),
+    ("=", Some(Comparison::Eq)),
+    ("<", Some(Comparison::Lt)),
+    (">", Some(Comparison::Gt)),
+];
+
+impl Parser<'_> {
+    fn condition(&mut self) -> EngineResult<Condition> {
+        self.skip_whitespace();
+        let end = self
+            .rest
+            .find(|c: char| c.is_whitespace() || "^=<>\"".contains(c))
+            .unwrap_or(self.rest.len());
+        let (field, rest) = self.rest.split_at(end);
+        self.rest = rest.trim_start();
+        if field.is_empty() {
+            return Err(invalid("expected a field".to_string()));
+        }
+
+        let (op, cmp) = OPERATORS
+            .into_iter()
+            .find(|(op, _)| self.rest.starts_with(op))
+            .ok_or_else(|| invalid(format!("expected an operator after `{field}`")))?;
+        self.rest = self.rest[op.len()..].trim_start();
+        let value = self.value()?;
+
+        let unsupported = || invalid(format!("`{field}` does not support `{op}`"));
+        match (field, cmp) {
+            ("content_type", Some(Comparison::Eq)) => Ok(Condition::ContentType {
+                value,
+                prefix: false,
+            }),
+            ("content_type", None) => Ok(Condition::ContentType {
+                value,
+                prefix: true,
+            }),
+            ("size", Some(cmp)) => value
+                .parse()
+                .map(|v| Condition::Size(cmp, v))
+                .map_err(|_| invalid(format!("`{value}` is not a valid size"))),
+            ("updated_at", Some(cmp)) if cmp != Comparison::Eq => {
+                DateTime::parse_from_rfc3339(&value)
+                    .map(|v| Condition::UpdatedAt(cmp, v.to_utc()))
+                    .map_err(|_| invalid(format!("`{value}` is not an RFC 3339 time")))
+            }
+            (field, Some(Comparison::Eq)) if field.starts_with("meta.") => {
+                let key = &field["meta.".len()..];
+                if key.is_empty() || is_reserved_meta_key(key) {
+                    return Err(invalid(format!("`{key}` cannot be queried")));
+                }
+                Ok(Condition::UserMeta {
+                    key: key.to_string(),
+                    value,
+                })
+            }
+            ("content_type" | "size" | "updated_at", _) => Err(unsupported()),
+            (field, _) if field.starts_with("meta.") => Err(unsupported()),
+            (field, _) => Err(invalid(format!("unknown field `{field}`"))),
+        }
+    }
+
+    /// 引号括起来的值，或者直到下一个空白的值
+    fn value(&mut self) -> EngineResult<String> {
+        let Some(quoted) = self.rest.strip_prefix('"') else {
+            let end = self
+                .rest
+                .find(char::is_whitespace)
+                .unwrap_or(self.rest.len());
+            let (value, rest) = self.rest.split_at(end);
+            self.rest = rest;
+            return match value.is_empty() {
+                true => Err(invalid("expected a value".to_string())),
+                false => Ok(value.to_string()),
+            };
+        };
+
+        let mut value = String::new();
+        let mut chars = quoted.char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '\\' => value.extend(chars.next().map(|(_, c)| c)),
+                '"' => {
+                    self.rest = &quoted[i + 1..];
+                    return Ok(value);
+                }
+                c => value.push(c),
+            }
+        }
+        Err(invalid("unterminated quoted value".to_string()))
+    }
+
+    fn keyword(&mut self, keyword: &str) -> EngineResult<()> {
+        let found = self
+            .rest
+            .get(..keyword.len())
+            .is_some_and(|v| v.eq_ignore_ascii_case(keyword))
+            && self.rest[keyword.len()..].starts_with(char::is_whitespace);
+        if !found {
+            return Err(invalid(format!(
+                "expected `{keyword}` before `{}`",
+                self.rest
+            )));
+        }
+
+        self.rest = &self.rest[keyword.len()..];
+        Ok(())
+    }
+
+    fn skip_whitespace(&mut self) {
+        self.rest = self.rest.trim_start();
+    }
+}
+
+fn invalid(reason: String) -> EngineError {
+    EngineError::InvalidArgument(format!("invalid query: {reason}"))
+}
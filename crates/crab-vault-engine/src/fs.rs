@@ -1,27 +1,509 @@
+use futures::{Stream, TryStreamExt, future::ready, lock::Mutex, stream};
 use serde::de::DeserializeOwned;
-use std::path::{Path, PathBuf};
-use tokio::{
-    fs::{self, File},
-    io::{AsyncReadExt, AsyncWriteExt},
+use sha2::{Digest, Sha256};
+use std::{
+    borrow::Cow,
+    collections::BTreeSet,
+    path::{Path, PathBuf},
 };
-
 use crate::{
     error::{EngineError, EngineResult},
-    {BucketMeta, DataEngine, MetaEngine, ObjectMeta},
+    events::Event,
+    layout::{
+        self, BLOBS_DIR, BUCKETS_DIR, DIR_MARKER, EVENTS_FILE, FANOUT_DIR, FANOUT_LEVELS,
+        LONG_SEGMENT_PREFIX, LayoutKind, MAX_SEGMENT_LEN, OBJECTS_DIR,
+    },
+    name, rt,
+    list::{ListObjectsQuery, MetaStream},
+    usage::{self, BucketUsage, StorageStats, UsageCounters},
+    {BucketMeta, DataEngine, MetaEngine, ObjectMeta, ObjectReader},
 };
 
 pub struct FsDataEngine {
     base_dir: PathBuf,
+    sparse: SparseMode,
+    durability: Durability,
+    object_layout: ObjectLayout,
+    dedup: bool,
+}
+
+/// ## 文件系统后端写入后如何同步到磁盘
+///
+/// 无论哪一种，整体写入的文件都是先写入同一目录中的临时文件再重命名，进程崩溃不会留下截断的文件，
+/// 这里决定的是断电或者系统崩溃之后最近的写入是否还在。部分写入（[`write_object_at`](DataEngine::write_object_at)）
+/// 直接修改原来的文件，不受影响
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Durability {
+    /// 不主动同步，由操作系统决定何时写回，断电后最近的写入可能丢失
+    Off,
+
+    /// 重命名之前同步临时文件的内容
+    File,
+
+    /// 在 [`File`](Durability::File) 的基础上，重命名之后再同步所在的目录，保证重命名本身也已经写入磁盘
+    #[default]
+    Full,
+}
+
+impl Durability {
+    fn from_query(value: &str) -> Option<Self> {
+        match value {
+            "off" => Some(Self::Off),
+            "file" => Some(Self::File),
+            "full" => Some(Self::Full),
+            _ => None,
+        }
+    }
+}
+
+/// [`FsDataEngine`] 是否使用稀疏文件
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SparseMode {
+    /// 部分写入越过文件末尾时留下空洞，[`punch_hole`](DataEngine::punch_hole) 时释放磁盘空间，
+    /// 文件系统不支持释放时退回到写入 0
+    #[default]
+    Auto,
+
+    /// 从不产生空洞，所有的 0 都实际写入磁盘，适用于不能很好地处理稀疏文件的备份或者同步工具
+    Off,
+}
+
+/// ## [`FsDataEngine`] 如何在 bucket 目录中存放 object
+///
+/// 只决定新创建的 bucket 使用哪一种，已有的 bucket 保持创建时的方式，所以修改配置不需要迁移数据。
+/// 已有的平铺的 bucket 可以用 [`FsDataEngine::fan_out_bucket`] 转换
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ObjectLayout {
+    /// object 名称直接对应 bucket 目录中的路径
+    #[default]
+    Flat,
+
+    /// 按照摘要分散到 [`FANOUT_DIR`] 下的子目录中，适用于有大量 object 的 bucket，
+    /// 否则它们都在同一个目录中，文件系统的性能会严重下降
+    FanOut,
 }
 
 impl FsDataEngine {
-    fn path_of_object(&self, bucket_name: &str, object_name: &str) -> PathBuf {
-        self.base_dir.join(bucket_name).join(object_name)
+    /// ## 从 `path?sparse=auto|off&durability=off|file|full&layout=flat|fanout&dedup=on|off` 形式的 uri 创建
+    ///
+    /// 这是注册表中 `file` scheme 使用的构造方式，没有查询参数时与 [`new`](DataEngine::new) 相同
+    pub fn from_uri(uri: &str) -> EngineResult<Self> {
+        let invalid = |msg: &str| EngineError::InvalidArgument(format!("invalid file uri `{uri}`: {msg}"));
+
+        let (path, query) = uri.split_once('?').unwrap_or((uri, ""));
+        let mut sparse = SparseMode::default();
+        let mut durability = Durability::default();
+        let mut object_layout = ObjectLayout::default();
+        let mut dedup = false;
+
+        for pair in query.split('&').filter(|v| !v.is_empty()) {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            match (key, value) {
+                ("layout", "flat") => object_layout = ObjectLayout::Flat,
+                ("layout", "fanout") => object_layout = ObjectLayout::FanOut,
+                ("layout", _) => return Err(invalid("`layout` should be `flat` or `fanout`")),
+                ("sparse", "auto") => sparse = SparseMode::Auto,
+                ("sparse", "off") => sparse = SparseMode::Off,
+                ("sparse", _) => return Err(invalid("`sparse` should be `auto` or `off`")),
+                ("dedup", "on") if cfg!(unix) => dedup = true,
+                ("dedup", "on") => return Err(invalid("`dedup` is only supported on unix")),
+                ("dedup", "off") => dedup = false,
+                ("dedup", _) => return Err(invalid("`dedup` should be `on` or `off`")),
+                ("durability", value) => {
+                    durability = Durability::from_query(value)
+                        .ok_or_else(|| invalid("`durability` should be `off`, `file` or `full`"))?
+                }
+                _ => return Err(invalid(&format!("unknown parameter `{key}`"))),
+            }
+        }
+
+        Ok(Self::new(path)?
+            .sparse(sparse)
+            .durability(durability)
+            .object_layout(object_layout)
+            .dedup(dedup))
+    }
+
+    /// 设置稀疏文件的使用方式，默认为 [`SparseMode::Auto`]
+    pub fn sparse(mut self, sparse: SparseMode) -> Self {
+        self.sparse = sparse;
+        self
+    }
+
+    /// 设置写入后的同步方式，默认为 [`Durability::Full`]
+    pub fn durability(mut self, durability: Durability) -> Self {
+        self.durability = durability;
+        self
+    }
+
+    /// 设置新创建的 bucket 中 object 的存放方式，默认为 [`ObjectLayout::Flat`]
+    pub fn object_layout(mut self, object_layout: ObjectLayout) -> Self {
+        self.object_layout = object_layout;
+        self
+    }
+
+    /// ## 是否对写入的数据去重，默认不去重
+    ///
+    /// 开启时每一份不同的数据只在 [`BLOBS_DIR`] 中保存一次，object 文件都是它的硬链接，
+    /// 链接数就是引用计数。原地修改（[`write_object_at`](DataEngine::write_object_at)、
+    /// [`punch_hole`](DataEngine::punch_hole)）之前先复制出独立的文件，不会影响共享同一份数据的其他 object。
+    /// 删除或者替换某一份数据的最后一个 object 时，这份数据同样被删除，这需要读一遍文件来找到它。
+    ///
+    /// 只影响之后的写入，已有的 object 保持原样；关闭之后已经去重的 object 仍然会被正确地修改和释放。
+    /// 硬链接要求 [`BLOBS_DIR`] 与 bucket 在同一个文件系统中，并且只在 unix 上可用
+    pub fn dedup(mut self, dedup: bool) -> Self {
+        self.dedup = dedup;
+        self
+    }
+
+    fn blobs_dir(&self) -> PathBuf {
+        self.base_dir.join(BLOBS_DIR)
+    }
+
+    /// 摘要为 `digest` 的数据在 [`BLOBS_DIR`] 中的位置
+    fn path_of_blob(&self, digest: &[u8; 32]) -> PathBuf {
+        let digest = hex(digest);
+        self.blobs_dir().join(&digest[..2]).join(digest)
+    }
+
+    /// ## 把 `data` 保存为 blob，然后让 `path` 成为它的硬链接
+    ///
+    /// blob 可能恰好在两步之间因为最后一个引用被删除而被删除，这时重新保存一次
+    async fn link_blob(&self, data: &[u8], path: &Path, exclusive: bool) -> std::io::Result<()> {
+        let blob = self.path_of_blob(&Sha256::digest(data).into());
+        loop {
+            if !blob.exists() {
+                if let Some(parent) = blob.parent() {
+                    rt::create_dir_all(parent).await?;
+                }
+                match rt::write_atomic_new(&blob, data, self.durability).await {
+                    Err(e) if e.kind() != std::io::ErrorKind::AlreadyExists => return Err(e),
+                    _ => {}
+                }
+            }
+
+            match rt::link_atomic(&blob, path, self.durability, exclusive).await {
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound && !blob.exists() => continue,
+                other => return other,
+            }
+        }
+    }
+
+    /// 与 [`link_blob`](Self::link_blob) 相同，数据来自文件 `src`
+    async fn link_blob_from_file(&self, src: &Path, path: &Path) -> std::io::Result<()> {
+        let blob = self.path_of_blob(&rt::digest_file(src).await?);
+        loop {
+            if !blob.exists() {
+                if let Some(parent) = blob.parent() {
+                    rt::create_dir_all(parent).await?;
+                }
+                rt::copy_atomic(src, &blob, self.durability).await?;
+            }
+
+            match rt::link_atomic(&blob, path, self.durability, false).await {
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound && !blob.exists() => continue,
+                other => return other,
+            }
+        }
+    }
+
+    /// ## 修改或者删除 `path` 之前调用，返回之后可能不再被引用的 blob
+    ///
+    /// 只有 `path` 和 blob 两个链接时，`path` 是这个 blob 的最后一个引用。blob 只能通过内容找到，
+    /// 所以这时需要读一遍文件。修改之后把结果交给 [`prune_blob`](Self::prune_blob)
+    async fn release(&self, path: &Path) -> EngineResult<Option<PathBuf>> {
+        if link_count(path) != 2 || !self.blobs_dir().is_dir() {
+            return Ok(None);
+        }
+
+        let digest = rt::digest_file(path)
+            .await
+            .map_err(|e| io_error(e, path))?;
+        let blob = self.path_of_blob(&digest);
+        Ok(same_file(path, &blob).then_some(blob))
+    }
+
+    /// 删除已经没有 object 引用的 blob，参见 [`release`](Self::release)
+    async fn prune_blob(&self, blob: Option<PathBuf>) {
+        if let Some(blob) = blob
+            && link_count(&blob) == 1
+            && rt::remove_file(&blob).await.is_ok()
+        {
+            prune_empty_dirs(&blob, &self.blobs_dir()).await;
+        }
+    }
+
+    /// 原地修改之前调用，`path` 与其他文件共享数据时先复制出独立的文件
+    async fn unshare(&self, path: &Path) -> EngineResult<()> {
+        if link_count(path) > 1 {
+            rt::copy_atomic(path, path, self.durability)
+                .await
+                .map_err(|e| io_error(e, path))?;
+        }
+        Ok(())
+    }
+
+    /// 名称不合法时返回错误，参见 [`name`](crate::name)
+    fn path_of_object(&self, bucket_name: &str, object_name: &str) -> EngineResult<PathBuf> {
+        name::check_object_name(object_name)?;
+        let relative = relative_path_of(object_name, "");
+        let root = self.objects_root(bucket_name)?;
+        Ok(match root.ends_with(FANOUT_DIR) {
+            true => root.join(shard_of(&relative)).join(relative),
+            false => root.join(relative),
+        })
+    }
+
+    /// bucket 中 object 文件所在的目录，分散存放时是其中的 [`FANOUT_DIR`]，否则是 bucket 目录本身
+    fn objects_root(&self, bucket_name: &str) -> EngineResult<PathBuf> {
+        let bucket_dir = self.path_of_bucket(bucket_name)?;
+        let fanout = bucket_dir.join(FANOUT_DIR);
+        Ok(match fanout.is_dir() {
+            true => fanout,
+            false => bucket_dir,
+        })
+    }
+
+    fn path_of_bucket(&self, bucket_name: &str) -> EngineResult<PathBuf> {
+        name::check_bucket_name(bucket_name)?;
+        Ok(self.base_dir.join(bucket_name))
+    }
+
+    /// 检查 bucket 是否存在，名称中带有 `/` 的 object 还需要先创建中间目录
+    async fn prepare_parent(&self, bucket_name: &str, path: &Path) -> EngineResult<()> {
+        if !self.path_of_bucket(bucket_name)?.is_dir() {
+            return Err(EngineError::BucketNotFound {
+                bucket: bucket_name.to_string(),
+            });
+        }
+
+        if let Some(parent) = path.parent() {
+            rt::create_dir_all(parent)
+                .await
+                .map_err(|e| io_error(e, parent))?;
+        }
+
+        Ok(())
+    }
+
+    /// ## 把平铺的 bucket 转换为 [`ObjectLayout::FanOut`]，返回移动了多少个文件
+    ///
+    /// 转换过程中 object 时有时无，只应该在服务停止时进行。中途失败时再次调用会完成剩下的部分，
+    /// 已经转换过的 bucket 不会有任何变化
+    pub async fn fan_out_bucket(&self, bucket_name: &str) -> EngineResult<usize> {
+        let bucket_dir = self.path_of_bucket(bucket_name)?;
+        if !bucket_dir.is_dir() {
+            return Err(EngineError::BucketNotFound {
+                bucket: bucket_name.to_string(),
+            });
+        }
+
+        let fanout = bucket_dir.join(FANOUT_DIR);
+        rt::create_dir_all(&fanout)
+            .await
+            .map_err(|e| io_error(e, &fanout))?;
+
+        let mut moved = 0;
+        for path in walk_files(&bucket_dir).await? {
+            let Ok(relative) = path.strip_prefix(&bucket_dir) else {
+                continue;
+            };
+            if relative.starts_with(FANOUT_DIR) {
+                continue;
+            }
+
+            let target = fanout.join(shard_of(relative)).join(relative);
+            // 上次中断之后，服务写入的新内容已经在分散存放的位置
+            if target.exists() {
+                rt::remove_file(&path)
+                    .await
+                    .map_err(|e| io_error(e, &path))?;
+            } else {
+                if let Some(parent) = target.parent() {
+                    rt::create_dir_all(parent)
+                        .await
+                        .map_err(|e| io_error(e, parent))?;
+                }
+                rt::rename(&path, &target)
+                    .await
+                    .map_err(|e| io_error(e, &path))?;
+            }
+            prune_empty_dirs(&path, &bucket_dir).await;
+            moved += 1;
+        }
+
+        Ok(moved)
+    }
+
+    /// ## 找出 [`BLOBS_DIR`] 中没有任何 object 引用的 blob，返回它们的摘要，不做任何修改
+    ///
+    /// 同时删除共享同一个 blob 的最后几个 object 时，每个都看到还有其他链接，或者在删除 object
+    /// 和 blob 之间崩溃，都会留下只剩一个链接的 blob。只看链接数，不需要读文件
+    pub async fn orphan_blobs(&self) -> EngineResult<Vec<String>> {
+        let blobs_dir = self.blobs_dir();
+        if !blobs_dir.is_dir() {
+            return Ok(vec![]);
+        }
+
+        let mut orphans: Vec<_> = walk_files(&blobs_dir)
+            .await?
+            .into_iter()
+            .filter(|v| link_count(v) == 1)
+            .filter_map(|v| Some(v.file_name()?.to_string_lossy().to_string()))
+            .collect();
+        orphans.sort_unstable();
+        Ok(orphans)
+    }
+
+    /// 删除 [`orphan_blobs`](Self::orphan_blobs) 找到的 blob，返回删除了多少个
+    ///
+    /// 此期间重新被引用的 blob 会被保留；正在保存的 blob 被删除时，写入会重新保存它
+    pub async fn prune_blobs(&self, digests: &[String]) -> EngineResult<usize> {
+        let mut pruned = 0;
+        for digest in digests {
+            if digest.len() != 64 || !digest.bytes().all(|v| v.is_ascii_hexdigit()) {
+                continue;
+            }
+            let blob = self.blobs_dir().join(&digest[..2]).join(digest);
+            if blob.exists() {
+                self.prune_blob(Some(blob.clone())).await;
+                pruned += !blob.exists() as usize;
+            }
+        }
+        Ok(pruned)
+    }
+}
+
+/// object 在文件系统中对应的相对路径，`suffix` 加在最后一段上
+///
+/// 以 `/` 结尾的 object 使用 [`DIR_MARKER`]，超过 [`MAX_SEGMENT_LEN`] 的段使用 [`LONG_SEGMENT_PREFIX`] 加上它的摘要
+fn relative_path_of(object_name: &str, suffix: &str) -> PathBuf {
+    let name = match object_name.ends_with('/') {
+        true => Cow::Owned(format!("{object_name}{DIR_MARKER}")),
+        false => Cow::Borrowed(object_name),
+    };
+
+    let mut segments = name
+        .split('/')
+        .map(|v| match v.len() > MAX_SEGMENT_LEN {
+            true => Cow::Owned(format!("{LONG_SEGMENT_PREFIX}{}", hex_digest(v))),
+            false => Cow::Borrowed(v),
+        })
+        .collect::<Vec<_>>();
+    if let Some(last) = segments.last_mut() {
+        last.to_mut().push_str(suffix);
+    }
+    segments.iter().map(|v| v.as_ref()).collect()
+}
+
+/// 分散存放时 `relative` 所在的子目录，即它的摘要的前 [`FANOUT_LEVELS`] 个字节，每个字节一层
+fn shard_of(relative: &Path) -> PathBuf {
+    let digest = hex_digest(&relative.to_string_lossy());
+    (0..FANOUT_LEVELS).map(|i| &digest[i * 2..i * 2 + 2]).collect()
+}
+
+fn hex_digest(segment: &str) -> String {
+    hex(&Sha256::digest(segment.as_bytes()))
+}
+
+fn hex(digest: &[u8]) -> String {
+    digest.iter().map(|v| format!("{v:02x}")).collect()
+}
+
+/// 文件的硬链接数，文件不存在时为 0，不是 unix 时总是 1
+fn link_count(path: &Path) -> u64 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        std::fs::metadata(path).map(|v| v.nlink()).unwrap_or(0)
+    }
+    #[cfg(not(unix))]
+    {
+        path.exists() as u64
+    }
+}
+
+/// 两个路径是否指向同一个文件
+#[cfg(unix)]
+fn same_file(a: &Path, b: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    match (std::fs::metadata(a), std::fs::metadata(b)) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+        _ => false,
+    }
+}
+
+#[cfg(not(unix))]
+fn same_file(_a: &Path, _b: &Path) -> bool {
+    false
+}
+
+/// 删除 `path` 之后，依次删除变空的上级目录，直到 `root` 为止
+///
+/// 否则删除了名称中带有 `/` 的 object 之后会留下空目录，bucket 永远不会被认为是空的
+async fn prune_empty_dirs(path: &Path, root: &Path) {
+    let mut dir = path.parent();
+    while let Some(current) = dir
+        && current != root
+        && current.starts_with(root)
+    {
+        // 目录不为空时删除失败，这正是停止的条件
+        if rt::remove_dir(current).await.is_err() {
+            break;
+        }
+        dir = current.parent();
+    }
+}
+
+/// 递归列出 bucket 目录中的所有 object
+///
+/// 转换到 [`ObjectLayout::FanOut`] 的过程中断时，两种方式存放的 object 都会列出
+async fn walk_objects(bucket_dir: &Path) -> EngineResult<Vec<String>> {
+    let mut objects = vec![];
+    for path in walk_files(bucket_dir).await? {
+        let Ok(relative) = path.strip_prefix(bucket_dir) else {
+            continue;
+        };
+        let relative: PathBuf = match relative.starts_with(FANOUT_DIR) {
+            true => relative.components().skip(1 + FANOUT_LEVELS).collect(),
+            false => relative.to_path_buf(),
+        };
+        // 使用摘要的段无法还原出原来的名称
+        let hashed = relative
+            .components()
+            .any(|v| v.as_os_str().to_string_lossy().starts_with(LONG_SEGMENT_PREFIX));
+        if hashed || relative.as_os_str().is_empty() {
+            continue;
+        }
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let relative = relative.to_string_lossy();
+        objects.push(match relative.strip_suffix(DIR_MARKER) {
+            Some(folder) if name == DIR_MARKER => folder.to_string(),
+            _ => relative.to_string(),
+        });
     }
+    Ok(objects)
+}
 
-    fn path_of_bucket(&self, bucket_name: &str) -> PathBuf {
-        self.base_dir.join(bucket_name)
+/// 递归列出 `dir` 中的所有文件，跳过写了一半的临时文件
+async fn walk_files(dir: &Path) -> EngineResult<Vec<PathBuf>> {
+    let mut files = vec![];
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let mut entries = rt::read_dir(&dir).await.map_err(|e| io_error(e, &dir))?;
+        while let Some(path) = entries.next_entry().await.map_err(|e| io_error(e, &dir))? {
+            if path.is_dir() {
+                pending.push(path);
+                continue;
+            }
+
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            if !(name.starts_with('.') && name.ends_with(".tmp")) {
+                files.push(path);
+            }
+        }
     }
+    Ok(files)
 }
 
 /// helper function，将 [IO Error](std::io::Error) 转换为 [`StorageError`]
@@ -36,16 +518,29 @@ fn io_error<P: AsRef<Path> + ?Sized>(e: std::io::Error, path: &P) -> EngineError
 impl DataEngine for FsDataEngine {
     type Uri = Path;
 
+    /// 检查目录的结构，参见 [`layout`](crate::layout)
     fn new<P: AsRef<Path>>(base_dir: P) -> EngineResult<Self> {
         let base_dir = base_dir.as_ref().to_path_buf();
-        std::fs::create_dir_all(&base_dir).map_err(|e| io_error(e, &base_dir))?;
-        Ok(Self { base_dir })
+        layout::ensure(&base_dir, LayoutKind::Data)?;
+        Ok(Self {
+            base_dir,
+            sparse: SparseMode::default(),
+            durability: Durability::default(),
+            object_layout: ObjectLayout::default(),
+            dedup: false,
+        })
     }
 
+    /// 已经存在的 bucket 不会改变存放方式
     async fn create_bucket(&self, bucket_name: &str) -> EngineResult<()> {
-        let path = self.path_of_bucket(bucket_name);
+        let path = self.path_of_bucket(bucket_name)?;
+        let path = match self.object_layout {
+            _ if path.is_dir() => path,
+            ObjectLayout::Flat => path,
+            ObjectLayout::FanOut => path.join(FANOUT_DIR),
+        };
 
-        fs::create_dir_all(&path)
+        rt::create_dir_all(&path)
             .await
             .map_err(|e| io_error(e, &path))?;
 
@@ -53,10 +548,24 @@ impl DataEngine for FsDataEngine {
     }
 
     async fn delete_bucket(&self, bucket_name: &str) -> EngineResult<()> {
-        let path = self.path_of_bucket(bucket_name);
+        let path = self.path_of_bucket(bucket_name)?;
+
+        // 分散存放时先删除其中的 FANOUT_DIR，它不为空时 bucket 也不为空
+        let fanout = path.join(FANOUT_DIR);
+        if fanout.is_dir()
+            && let Err(e) = rt::remove_dir(&fanout).await
+        {
+            if e.kind() == std::io::ErrorKind::DirectoryNotEmpty {
+                return Err(EngineError::BucketNotEmpty {
+                    bucket: bucket_name.to_string(),
+                });
+            } else if e.kind() != std::io::ErrorKind::NotFound {
+                return Err(io_error(e, &fanout));
+            }
+        }
 
         // 直接尝试删除目录
-        if let Err(e) = fs::remove_dir(&path).await {
+        if let Err(e) = rt::remove_dir(&path).await {
             if e.kind() == std::io::ErrorKind::DirectoryNotEmpty && path.is_dir() {
                 return Err(EngineError::BucketNotEmpty {
                     bucket: bucket_name.to_string(),
@@ -71,124 +580,489 @@ impl DataEngine for FsDataEngine {
         Ok(())
     }
 
+    /// 与元数据共用目录时跳过元数据的子目录
+    async fn list_buckets(&self) -> EngineResult<Vec<String>> {
+        let shared = layout::has_meta(&self.base_dir);
+        let mut buckets = vec![];
+        let mut entries = rt::read_dir(&self.base_dir)
+            .await
+            .map_err(|e| io_error(e, &self.base_dir))?;
+        while let Some(path) = entries
+            .next_entry()
+            .await
+            .map_err(|e| io_error(e, &self.base_dir))?
+        {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            let reserved = shared && (name == BUCKETS_DIR || name == OBJECTS_DIR);
+            let hidden = name.starts_with('.') && !name::is_internal_bucket(&name);
+            if path.is_dir() && !hidden && !reserved {
+                buckets.push(name.to_string());
+            }
+        }
+        Ok(buckets)
+    }
+
+    async fn list_objects(&self, bucket_name: &str) -> EngineResult<Vec<String>> {
+        let path = self.path_of_bucket(bucket_name)?;
+        if !path.is_dir() {
+            return Err(EngineError::BucketNotFound {
+                bucket: bucket_name.to_string(),
+            });
+        }
+        walk_objects(&path).await
+    }
+
     async fn create_object(
         &self,
         bucket_name: &str,
         object_name: &str,
         data: &[u8],
     ) -> EngineResult<()> {
-        let path = self.path_of_object(bucket_name, object_name);
+        let path = self.path_of_object(bucket_name, object_name)?;
 
-        if let Some(parent) = path.parent()
-            && !parent.exists()
-        {
-            return Err(EngineError::BucketNotFound {
-                bucket: bucket_name.to_string(),
-            });
-        }
+        self.prepare_parent(bucket_name, &path).await?;
 
-        // 异步写入文件
-        let mut file = File::create(&path).await.map_err(|e| io_error(e, &path))?;
-        file.write_all(data).await.map_err(|e| io_error(e, &path))?;
-        file.flush().await.map_err(|e| io_error(e, &path))?;
+        let released = self.release(&path).await?;
+        match self.dedup {
+            true => self.link_blob(data, &path, false).await,
+            false => rt::write_atomic(&path, data, self.durability).await,
+        }
+        .map_err(|e| io_error(e, &path))?;
+        self.prune_blob(released).await;
 
         Ok(())
     }
 
-    async fn read_object(&self, bucket_name: &str, object_name: &str) -> EngineResult<Vec<u8>> {
-        let path = self.path_of_object(bucket_name, object_name);
-        let map_io_err = |e| io_error(e, &path);
+    /// 写入临时文件后硬链接到目标位置，由文件系统保证只有一个调用者能够创建成功
+    async fn create_object_if_absent(
+        &self,
+        bucket_name: &str,
+        object_name: &str,
+        data: &[u8],
+    ) -> EngineResult<()> {
+        let path = self.path_of_object(bucket_name, object_name)?;
 
-        // 直接尝试打开文件，并处理 NotFound 错误
-        let mut file = match File::open(&path).await {
-            Ok(file) => file,
-            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
-                return Err(EngineError::ObjectNotFound {
+        self.prepare_parent(bucket_name, &path).await?;
+
+        let created = match self.dedup {
+            true => self.link_blob(data, &path, true).await,
+            false => rt::write_atomic_new(&path, data, self.durability).await,
+        };
+        match created {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                Err(EngineError::ObjectAlreadyExists {
                     bucket: bucket_name.to_string(),
                     object: object_name.to_string(),
-                });
+                })
             }
-            Err(e) => return Err(map_io_err(e)),
-        };
+            Err(e) => Err(io_error(e, &path)),
+        }
+    }
+
+    async fn read_object(&self, bucket_name: &str, object_name: &str) -> EngineResult<Vec<u8>> {
+        let path = self.path_of_object(bucket_name, object_name)?;
 
-        let mut contents = Vec::new();
-        file.read_to_end(&mut contents).await.map_err(map_io_err)?;
+        // 直接尝试读取文件，并处理 NotFound 错误
+        match rt::read(&path).await {
+            Ok(contents) => Ok(contents),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Err(EngineError::ObjectNotFound {
+                bucket: bucket_name.to_string(),
+                object: object_name.to_string(),
+            }),
+            Err(e) => Err(io_error(e, &path)),
+        }
+    }
 
-        Ok(contents)
+    /// 直接返回打开的文件。覆盖和删除只替换目录项，已经打开的文件仍然读出原来的内容，
+    /// 但是原地修改（[`write_object_at`](DataEngine::write_object_at) 等）对正在读取的调用者可见
+    async fn read_object_reader(
+        &self,
+        bucket_name: &str,
+        object_name: &str,
+    ) -> EngineResult<ObjectReader> {
+        let path = self.path_of_object(bucket_name, object_name)?;
+
+        match rt::open_reader(&path).await {
+            Ok(reader) => Ok(reader),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Err(EngineError::ObjectNotFound {
+                bucket: bucket_name.to_string(),
+                object: object_name.to_string(),
+            }),
+            Err(e) => Err(io_error(e, &path)),
+        }
     }
 
     async fn delete_object(&self, bucket_name: &str, object_name: &str) -> EngineResult<()> {
-        let path = self.path_of_object(bucket_name, object_name);
+        let path = self.path_of_object(bucket_name, object_name)?;
 
-        match fs::remove_file(&path).await {
-            Ok(_) => Ok(()),
+        let released = self.release(&path).await?;
+        match rt::remove_file(&path).await {
+            Ok(_) => {
+                prune_empty_dirs(&path, &self.objects_root(bucket_name)?).await;
+                self.prune_blob(released).await;
+                Ok(())
+            }
             // 如果文件不存在，我们认为删除操作是成功的（幂等性）
             Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
             Err(e) => Err(io_error(e, &path)),
         }
     }
+
+    /// 直接复制文件，数据不经过内存
+    async fn copy_object(
+        &self,
+        src_bucket: &str,
+        src_object: &str,
+        dst_bucket: &str,
+        dst_object: &str,
+    ) -> EngineResult<()> {
+        let src = self.path_of_object(src_bucket, src_object)?;
+        let dst = self.path_of_object(dst_bucket, dst_object)?;
+
+        if !src.is_file() {
+            return Err(EngineError::ObjectNotFound {
+                bucket: src_bucket.to_string(),
+                object: src_object.to_string(),
+            });
+        }
+
+        self.prepare_parent(dst_bucket, &dst).await?;
+
+        // 去重时两个 object 直接共享同一份数据
+        let released = self.release(&dst).await?;
+        match self.dedup {
+            true => rt::link_atomic(&src, &dst, self.durability, false).await,
+            false => rt::copy_atomic(&src, &dst, self.durability).await.map(|_| ()),
+        }
+        .map_err(|e| io_error(e, &dst))?;
+        self.prune_blob(released).await;
+
+        Ok(())
+    }
+
+    /// 由文件系统直接复制，不经过内存
+    async fn create_object_from_file(
+        &self,
+        bucket_name: &str,
+        object_name: &str,
+        src: &Path,
+    ) -> EngineResult<()> {
+        let path = self.path_of_object(bucket_name, object_name)?;
+
+        self.prepare_parent(bucket_name, &path).await?;
+
+        let released = self.release(&path).await?;
+        match self.dedup {
+            true => self.link_blob_from_file(src, &path).await,
+            false => rt::copy_atomic(src, &path, self.durability).await.map(|_| ()),
+        }
+        .map_err(|e| io_error(e, &path))?;
+        self.prune_blob(released).await;
+
+        Ok(())
+    }
+
+    /// 原地写入文件，越过文件末尾的部分按照 [`SparseMode`] 处理
+    async fn write_object_at(
+        &self,
+        bucket_name: &str,
+        object_name: &str,
+        offset: u64,
+        data: &[u8],
+    ) -> EngineResult<()> {
+        let path = self.path_of_object(bucket_name, object_name)?;
+
+        self.prepare_parent(bucket_name, &path).await?;
+
+        let released = self.release(&path).await?;
+        self.unshare(&path).await?;
+        rt::write_at(&path, offset, data, self.sparse == SparseMode::Auto)
+            .await
+            .map_err(|e| io_error(e, &path))?;
+        self.prune_blob(released).await;
+
+        Ok(())
+    }
+
+    /// 以追加模式打开文件，不需要读出原来的内容
+    async fn append_object(
+        &self,
+        bucket_name: &str,
+        object_name: &str,
+        data: &[u8],
+    ) -> EngineResult<u64> {
+        let path = self.path_of_object(bucket_name, object_name)?;
+        let not_found = || EngineError::ObjectNotFound {
+            bucket: bucket_name.to_string(),
+            object: object_name.to_string(),
+        };
+        if !path.is_file() {
+            return Err(not_found());
+        }
+
+        let released = self.release(&path).await?;
+        self.unshare(&path).await?;
+        match rt::append(&path, data).await {
+            Ok(offset) => {
+                self.prune_blob(released).await;
+                Ok(offset)
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Err(not_found()),
+            Err(e) => Err(io_error(e, &path)),
+        }
+    }
+
+    /// [`SparseMode::Auto`] 时使用 `fallocate` 释放空间，否则写入 0
+    async fn punch_hole(
+        &self,
+        bucket_name: &str,
+        object_name: &str,
+        offset: u64,
+        len: u64,
+    ) -> EngineResult<()> {
+        let path = self.path_of_object(bucket_name, object_name)?;
+
+        let released = self.release(&path).await?;
+        self.unshare(&path).await?;
+        match rt::punch_hole(&path, offset, len, self.sparse == SparseMode::Auto).await {
+            Ok(()) => {
+                self.prune_blob(released).await;
+                Ok(())
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Err(EngineError::ObjectNotFound {
+                bucket: bucket_name.to_string(),
+                object: object_name.to_string(),
+            }),
+            Err(e) => Err(io_error(e, &path)),
+        }
+    }
 }
 
+/// 列举元数据时默认同时读取的文件数
+pub const DEFAULT_LIST_CONCURRENCY: usize = 32;
+
 pub struct FsMetaEngine {
     base_dir: PathBuf,
+    durability: Durability,
+    usage: UsageCounters,
+
+    /// 列举时同时读取的元数据文件数
+    list_concurrency: usize,
+
+    /// 事件日志中最后一个序号，第一次追加时从 [`EVENTS_FILE`] 中读出，同时保证追加是串行的
+    last_event: Mutex<Option<u64>>,
+
+    /// 条件写入在读出、比较和写入期间持有，只能排除同一个进程中的其他条件写入
+    conditional: Mutex<()>,
 }
 
 impl FsMetaEngine {
+    /// ## 从 `path?durability=off|file|full&list_concurrency=N` 形式的 uri 创建
+    ///
+    /// 这是注册表中 `file` scheme 使用的构造方式，没有查询参数时与 [`new`](MetaEngine::new) 相同
+    pub fn from_uri(uri: &str) -> EngineResult<Self> {
+        let invalid = |msg: &str| EngineError::InvalidArgument(format!("invalid file uri `{uri}`: {msg}"));
+
+        let (path, query) = uri.split_once('?').unwrap_or((uri, ""));
+        let mut durability = Durability::default();
+        let mut list_concurrency = DEFAULT_LIST_CONCURRENCY;
+
+        for pair in query.split('&').filter(|v| !v.is_empty()) {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            match key {
+                "durability" => {
+                    durability = Durability::from_query(value)
+                        .ok_or_else(|| invalid("`durability` should be `off`, `file` or `full`"))?
+                }
+                "list_concurrency" => {
+                    list_concurrency =
+                        value.parse().ok().filter(|v| *v > 0).ok_or_else(|| {
+                            invalid("`list_concurrency` should be a positive integer")
+                        })?
+                }
+                _ => return Err(invalid(&format!("unknown parameter `{key}`"))),
+            }
+        }
+
+        Ok(Self::new(path)?
+            .durability(durability)
+            .list_concurrency(list_concurrency))
+    }
+
+    /// 设置写入后的同步方式，默认为 [`Durability::Full`]
+    pub fn durability(mut self, durability: Durability) -> Self {
+        self.durability = durability;
+        self
+    }
+
+    /// 设置列举时同时读取的元数据文件数，默认为 [`DEFAULT_LIST_CONCURRENCY`]，`0` 视为 `1`
+    pub fn list_concurrency(mut self, list_concurrency: usize) -> Self {
+        self.list_concurrency = list_concurrency.max(1);
+        self
+    }
+
+    fn events_path(&self) -> PathBuf {
+        self.base_dir.join(EVENTS_FILE)
+    }
+
+    /// 读出事件日志中完整的行，文件不存在时为空
+    ///
+    /// 追加到一半时崩溃会在末尾留下不完整的一行，它和无法解析的行一起被跳过
+    async fn read_events(&self) -> EngineResult<(Vec<Event>, String)> {
+        let path = self.events_path();
+        let data = match rt::read_to_string(&path).await {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(io_error(e, &path)),
+        };
+
+        let events = data
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect();
+        Ok((events, data))
+    }
+
+    /// 已有的 object 元数据中记录的大小，元数据不存在或者已经损坏时为 [`None`]
+    async fn old_size(&self, bucket_name: &str, object_name: &str) -> EngineResult<Option<u64>> {
+        match self.read_object_meta(bucket_name, object_name).await {
+            Ok(meta) => Ok(Some(meta.size)),
+            Err(EngineError::ObjectMetaNotFound { .. } | EngineError::Serde { .. }) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
     // 优化的路径结构
-    fn bucket_meta_path(&self, bucket_name: &str) -> PathBuf {
-        self.base_dir
-            .join("buckets")
-            .join(format!("{}.json", bucket_name))
+    fn bucket_meta_path(&self, bucket_name: &str) -> EngineResult<PathBuf> {
+        name::check_bucket_name(bucket_name)?;
+        Ok(self
+            .base_dir
+            .join(BUCKETS_DIR)
+            .join(format!("{}.json", bucket_name)))
     }
 
-    fn object_meta_path(&self, bucket_name: &str, object_name: &str) -> PathBuf {
-        self.base_dir
-            .join("objects")
-            .join(bucket_name)
-            .join(format!("{}.json", object_name))
+    fn object_meta_path(&self, bucket_name: &str, object_name: &str) -> EngineResult<PathBuf> {
+        name::check_object_name(object_name)?;
+        Ok(self
+            .objects_dir_path(bucket_name)?
+            .join(relative_path_of(object_name, ".json")))
     }
 
     // 获取对象元数据目录的路径
-    fn objects_dir_path(&self, bucket_name: &str) -> PathBuf {
-        self.base_dir.join("objects").join(bucket_name)
+    fn objects_dir_path(&self, bucket_name: &str) -> EngineResult<PathBuf> {
+        name::check_bucket_name(bucket_name)?;
+        Ok(self.base_dir.join(OBJECTS_DIR).join(bucket_name))
     }
 
     // 获取 bucket 元数据目录的路径
     fn buckets_dir_path(&self) -> PathBuf {
-        self.base_dir.join("buckets")
+        self.base_dir.join(BUCKETS_DIR)
     }
 }
 
 /// 辅助函数，用于从目录中列出并反序列化所有JSON元数据文件。
-async fn list_meta_from_dir<T: DeserializeOwned>(dir_path: &Path) -> EngineResult<Vec<T>> {
-    // 如果目录不存在，这是一个正常情况，只返回一个空列表。
-    if !dir_path.exists() {
-        return Ok(Vec::new());
+async fn list_meta_from_dir<T: DeserializeOwned + Send + 'static>(
+    dir_path: &Path,
+    concurrency: usize,
+) -> EngineResult<Vec<T>> {
+    stream_meta_from_dir(dir_path.to_path_buf(), concurrency)
+        .try_collect()
+        .await
+}
+
+/// ## 递归读取目录中的所有 `.json` 文件，每读出一个就产生一条元数据
+///
+/// 遍历目录是串行的，找到的文件最多同时读取 `concurrency` 个，所以元数据的顺序不固定。
+/// 单个文件损坏时只产生一个 [`CorruptMeta`](EngineError::CorruptMeta)，是否跳过由调用者决定
+fn stream_meta_from_dir<T: DeserializeOwned + Send + 'static>(
+    dir_path: PathBuf,
+    concurrency: usize,
+) -> MetaStream<'static, T> {
+    let root = dir_path.clone();
+    Box::pin(
+        walk_meta_files(dir_path)
+            .map_ok(move |path| read_meta_file(root.clone(), path))
+            .try_buffer_unordered(concurrency)
+            .try_filter_map(|v| ready(Ok(v))),
+    )
+}
+
+/// 读出一个元数据文件，遍历之后已经被删除的文件为 [`None`]
+async fn read_meta_file<T: DeserializeOwned>(
+    root: PathBuf,
+    path: PathBuf,
+) -> EngineResult<Option<T>> {
+    let data = match rt::read_to_string(&path).await {
+        Ok(data) => data,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(io_error(e, &path)),
+    };
+
+    serde_json::from_str(&data).map(Some).map_err(|_| {
+        let entry = path.strip_prefix(&root).unwrap_or(&path);
+        EngineError::CorruptMeta {
+            entry: entry.with_extension("").to_string_lossy().to_string(),
+        }
+    })
+}
+
+/// 递归遍历目录，产生其中所有 `.json` 文件的路径
+///
+/// 名称中带有 `/` 的 object 元数据保存在子目录中，所以需要递归
+fn walk_meta_files(dir_path: PathBuf) -> impl Stream<Item = EngineResult<PathBuf>> + Send {
+    struct State {
+        pending: Vec<PathBuf>,
+        current: Option<(PathBuf, rt::ReadDir)>,
     }
 
-    let mut entries = fs::read_dir(dir_path)
-        .await
-        .map_err(|e| io_error(e, dir_path))?;
+    /// 出错时无法继续遍历
+    async fn next(state: &mut State) -> EngineResult<Option<PathBuf>> {
+        loop {
+            let Some((dir_path, entries)) = &mut state.current else {
+                let Some(dir_path) = state.pending.pop() else {
+                    return Ok(None);
+                };
 
-    let mut results = Vec::new();
+                match rt::read_dir(&dir_path).await {
+                    Ok(entries) => state.current = Some((dir_path, entries)),
+                    // 如果目录不存在，这是一个正常情况，当作空目录处理。
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                    Err(e) => return Err(io_error(e, &dir_path)),
+                }
+                continue;
+            };
 
-    while let Some(entry) = entries
-        .next_entry()
-        .await
-        .map_err(|e| io_error(e, dir_path))?
-    {
-        let path = entry.path();
-        if path.is_file() && path.extension().and_then(|s| s.to_str()) == Some("json") {
-            let data = fs::read_to_string(&path)
+            let Some(path) = entries
+                .next_entry()
                 .await
-                .map_err(|e| io_error(e, &path))?;
-            // 如果单个文件损坏，我们可以选择跳过它或返回错误。这里我们选择失败。
-            let meta: T = serde_json::from_str(&data)?;
-            results.push(meta);
+                .map_err(|e| io_error(e, dir_path))?
+            else {
+                state.current = None;
+                continue;
+            };
+
+            if path.is_dir() {
+                state.pending.push(path);
+            } else if path.is_file() && path.extension().and_then(|s| s.to_str()) == Some("json") {
+                return Ok(Some(path));
+            }
         }
     }
 
-    Ok(results)
+    let state = State {
+        pending: vec![dir_path],
+        current: None,
+    };
+
+    stream::unfold(Some(state), |state| async move {
+        let mut state = state?;
+        match next(&mut state).await {
+            Ok(Some(path)) => Some((Ok(path), Some(state))),
+            Ok(None) => None,
+            Err(e) => Some((Err(e), None)),
+        }
+    })
 }
 
 impl MetaEngine for FsMetaEngine {
@@ -196,22 +1070,56 @@ impl MetaEngine for FsMetaEngine {
 
     fn new<P: AsRef<Path>>(base_dir: P) -> EngineResult<Self> {
         let base_dir = base_dir.as_ref().to_path_buf();
-        // 在初始化时创建元数据根目录
-        std::fs::create_dir_all(&base_dir).map_err(|e| io_error(e, &base_dir))?;
-        Ok(Self { base_dir })
+        // 在初始化时检查元数据根目录的结构，创建缺少的子目录
+        layout::ensure(&base_dir, LayoutKind::Meta)?;
+        Ok(Self {
+            base_dir,
+            durability: Durability::default(),
+            usage: UsageCounters::default(),
+            list_concurrency: DEFAULT_LIST_CONCURRENCY,
+            last_event: Mutex::new(None),
+            conditional: Mutex::new(()),
+        })
     }
 
     async fn create_object_meta(&self, meta: &ObjectMeta) -> EngineResult<()> {
-        let path = self.object_meta_path(&meta.bucket_name, &meta.object_name);
+        let path = self.object_meta_path(&meta.bucket_name, &meta.object_name)?;
 
         if let Some(parent) = path.parent() {
-            fs::create_dir_all(parent)
+            rt::create_dir_all(parent)
                 .await
                 .map_err(|e| io_error(e, parent))?;
         }
 
+        // 只有正在维护用量的 bucket 才需要知道被替换的 object 的大小
+        let old = match self.usage.tracks(&meta.bucket_name) {
+            true => self.old_size(&meta.bucket_name, &meta.object_name).await?,
+            false => None,
+        };
+
         let json = serde_json::to_string_pretty(meta)?;
-        fs::write(&path, json).await.map_err(|e| io_error(e, &path))
+        rt::write_atomic(&path, json, self.durability)
+            .await
+            .map_err(|e| io_error(e, &path))?;
+
+        self.usage.replace(&meta.bucket_name, old, Some(meta.size));
+        Ok(())
+    }
+
+    async fn create_object_meta_if(&self, meta: &ObjectMeta, expected_etag: &str) -> EngineResult<()> {
+        let _guard = self.conditional.lock().await;
+
+        match self
+            .read_object_meta(&meta.bucket_name, &meta.object_name)
+            .await
+        {
+            Ok(current) if current.etag == expected_etag => self.create_object_meta(meta).await,
+            Ok(_) | Err(EngineError::ObjectMetaNotFound { .. }) => Err(EngineError::EtagMismatch {
+                bucket: meta.bucket_name.clone(),
+                object: meta.object_name.clone(),
+            }),
+            Err(e) => Err(e),
+        }
     }
 
     async fn read_object_meta(
@@ -219,9 +1127,9 @@ impl MetaEngine for FsMetaEngine {
         bucket_name: &str,
         object_name: &str,
     ) -> EngineResult<ObjectMeta> {
-        let path = self.object_meta_path(bucket_name, object_name);
+        let path = self.object_meta_path(bucket_name, object_name)?;
 
-        match fs::read_to_string(&path).await {
+        match rt::read_to_string(&path).await {
             Ok(data) => Ok(serde_json::from_str(&data)?),
             Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
                 Err(EngineError::ObjectMetaNotFound {
@@ -234,28 +1142,36 @@ impl MetaEngine for FsMetaEngine {
     }
 
     async fn delete_object_meta(&self, bucket_name: &str, object_name: &str) -> EngineResult<()> {
-        let path = self.object_meta_path(bucket_name, object_name);
+        let path = self.object_meta_path(bucket_name, object_name)?;
+        let old = match self.usage.tracks(bucket_name) {
+            true => self.old_size(bucket_name, object_name).await?,
+            false => None,
+        };
 
-        match fs::remove_file(&path).await {
-            Ok(_) => Ok(()),
+        match rt::remove_file(&path).await {
+            Ok(_) => {
+                self.usage.replace(bucket_name, old, None);
+                prune_empty_dirs(&path, &self.objects_dir_path(bucket_name)?).await;
+                Ok(())
+            }
             Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
             Err(e) => Err(io_error(e, &path)),
         }
     }
 
     async fn list_objects_meta(&self, bucket_name: &str) -> EngineResult<Vec<ObjectMeta>> {
-        let dir_path = self.objects_dir_path(bucket_name);
-        list_meta_from_dir(&dir_path).await
+        let dir_path = self.objects_dir_path(bucket_name)?;
+        list_meta_from_dir(&dir_path, self.list_concurrency).await
     }
 
     async fn touch_object(&self, bucket_name: &str, object_name: &str) -> EngineResult<()> {
-        let path = self.object_meta_path(bucket_name, object_name);
+        let path = self.object_meta_path(bucket_name, object_name)?;
 
-        match fs::read_to_string(&path).await {
+        match rt::read_to_string(&path).await {
             Ok(data) => {
                 let mut meta: ObjectMeta = serde_json::from_str(&data)?;
-                meta.updated_at = chrono::Utc::now();
-                fs::write(&path, serde_json::to_string_pretty(&meta)?)
+                meta.updated_at = crate::clock::now();
+                rt::write_atomic(&path, serde_json::to_string_pretty(&meta)?, self.durability)
                     .await
                     .map_err(|e| io_error(e, &path))
             }
@@ -270,22 +1186,24 @@ impl MetaEngine for FsMetaEngine {
     }
 
     async fn create_bucket_meta(&self, meta: &BucketMeta) -> EngineResult<()> {
-        let path = self.bucket_meta_path(&meta.name);
+        let path = self.bucket_meta_path(&meta.name)?;
 
         if let Some(parent) = path.parent() {
-            fs::create_dir_all(parent)
+            rt::create_dir_all(parent)
                 .await
                 .map_err(|e| io_error(e, parent))?;
         }
 
         let json = serde_json::to_string_pretty(meta)?;
-        fs::write(&path, json).await.map_err(|e| io_error(e, &path))
+        rt::write_atomic(&path, json, self.durability)
+            .await
+            .map_err(|e| io_error(e, &path))
     }
 
     async fn read_bucket_meta(&self, name: &str) -> EngineResult<BucketMeta> {
-        let path = self.bucket_meta_path(name);
+        let path = self.bucket_meta_path(name)?;
 
-        match fs::read_to_string(&path).await {
+        match rt::read_to_string(&path).await {
             Ok(data) => Ok(serde_json::from_str(&data)?),
             Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
                 Err(EngineError::BucketMetaNotFound {
@@ -297,31 +1215,32 @@ impl MetaEngine for FsMetaEngine {
     }
 
     async fn delete_bucket_meta(&self, name: &str) -> EngineResult<()> {
-        let path = self.bucket_meta_path(name);
+        let path = self.bucket_meta_path(name)?;
 
-        match fs::remove_file(&path).await {
+        match rt::remove_file(&path).await {
             Ok(_) => Ok(()),
             Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
             Err(e) => Err(io_error(e, &path)),
         }?;
 
-        match fs::remove_dir(self.objects_dir_path(name)).await {
+        match rt::remove_dir(&self.objects_dir_path(name)?).await {
             Ok(_) => Ok(()),
             Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
             Err(e) => Err(io_error(e, &path)),
         }?;
 
+        self.usage.forget(name);
         Ok(())
     }
 
     async fn touch_bucket(&self, bucket_name: &str) -> EngineResult<()> {
-        let path = self.bucket_meta_path(bucket_name);
+        let path = self.bucket_meta_path(bucket_name)?;
 
-        match fs::read_to_string(&path).await {
+        match rt::read_to_string(&path).await {
             Ok(data) => {
                 let mut meta: BucketMeta = serde_json::from_str(&data)?;
-                meta.updated_at = chrono::Utc::now();
-                fs::write(&path, serde_json::to_string_pretty(&meta)?)
+                meta.updated_at = crate::clock::now();
+                rt::write_atomic(&path, serde_json::to_string_pretty(&meta)?, self.durability)
                     .await
                     .map_err(|e| io_error(e, &path))
             }
@@ -336,6 +1255,134 @@ impl MetaEngine for FsMetaEngine {
 
     async fn list_buckets_meta(&self) -> EngineResult<Vec<BucketMeta>> {
         let dir_path = self.buckets_dir_path();
-        list_meta_from_dir(&dir_path).await
+        list_meta_from_dir(&dir_path, self.list_concurrency).await
+    }
+
+    fn stream_buckets_meta(&self) -> MetaStream<'_, BucketMeta> {
+        stream_meta_from_dir(self.buckets_dir_path(), self.list_concurrency)
+    }
+
+    /// bucket 元数据文件和 object 元数据目录中出现的 bucket 都会被统计，
+    /// 用量来自 [`bucket_usage`](MetaEngine::bucket_usage) 维护的计数
+    async fn stats(&self) -> EngineResult<StorageStats> {
+        let mut names = BTreeSet::new();
+        let dirs = [
+            (self.buckets_dir_path(), false),
+            (self.base_dir.join(OBJECTS_DIR), true),
+        ];
+        for (dir, is_dir) in dirs {
+            let mut entries = match rt::read_dir(&dir).await {
+                Ok(entries) => entries,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(io_error(e, &dir)),
+            };
+            while let Some(path) = entries
+                .next_entry()
+                .await
+                .map_err(|e| io_error(e, &dir))?
+            {
+                let name = match is_dir {
+                    true if path.is_dir() => path.file_name(),
+                    false if path.extension().is_some_and(|v| v == "json") => path.file_stem(),
+                    _ => None,
+                };
+                if let Some(name) = name {
+                    names.insert(name.to_string_lossy().to_string());
+                }
+            }
+        }
+        usage::collect_stats(self, names).await
+    }
+
+    /// 第一次调用时遍历一次元数据目录，之后直接返回随写入和删除维护的计数
+    async fn bucket_usage(&self, bucket_name: &str) -> EngineResult<BucketUsage> {
+        if let Some(usage) = self.usage.get(bucket_name) {
+            return Ok(usage);
+        }
+
+        let mut usage = BucketUsage::default();
+        let mut stream = stream_meta_from_dir::<ObjectMeta>(
+            self.objects_dir_path(bucket_name)?,
+            self.list_concurrency,
+        );
+        while let Some(meta) = stream.try_next().await? {
+            usage.add(meta.size);
+        }
+        Ok(self.usage.load(bucket_name, usage))
+    }
+
+    fn stream_objects_meta<'a>(
+        &'a self,
+        bucket_name: &'a str,
+        query: &'a ListObjectsQuery,
+    ) -> MetaStream<'a, ObjectMeta> {
+        let dir_path = match self.objects_dir_path(bucket_name) {
+            Ok(dir_path) => dir_path,
+            Err(e) => return Box::pin(stream::once(ready(Err(e)))),
+        };
+        Box::pin(
+            stream_meta_from_dir(dir_path, self.list_concurrency)
+                .try_filter(move |v| ready(query.matches(v))),
+        )
+    }
+
+    /// 追加到 [`EVENTS_FILE`] 的末尾，不按照 [`Durability`] 同步
+    async fn append_event(&self, event: &Event) -> EngineResult<u64> {
+        let path = self.events_path();
+        let mut last_event = self.last_event.lock().await;
+
+        let last_seq = match *last_event {
+            Some(seq) => seq,
+            None => {
+                let (events, data) = self.read_events().await?;
+                // 补上缺少的换行，新的事件才不会接在不完整的一行后面
+                let repaired = match data.is_empty() || data.ends_with('\n') {
+                    true => data,
+                    false => data + "\n",
+                };
+                rt::write_atomic(&path, repaired, self.durability)
+                    .await
+                    .map_err(|e| io_error(e, &path))?;
+                events.last().map_or(0, |v| v.seq)
+            }
+        };
+
+        let seq = last_seq + 1;
+        let mut line = serde_json::to_string(&Event {
+            seq,
+            ..event.clone()
+        })?;
+        line.push('\n');
+        rt::append(&path, line)
+            .await
+            .map_err(|e| io_error(e, &path))?;
+
+        *last_event = Some(seq);
+        Ok(seq)
+    }
+
+    async fn list_events(&self, since: u64, limit: usize) -> EngineResult<Vec<Event>> {
+        let (events, _) = self.read_events().await?;
+        Ok(events
+            .into_iter()
+            .filter(|v| v.seq > since)
+            .take(limit)
+            .collect())
+    }
+
+    async fn trim_events(&self, up_to: u64) -> EngineResult<()> {
+        let path = self.events_path();
+        // 持有锁，避免覆盖清理期间追加的事件
+        let _last_event = self.last_event.lock().await;
+
+        let (events, _) = self.read_events().await?;
+        let mut data = String::new();
+        for event in events.iter().filter(|v| v.seq > up_to) {
+            data.push_str(&serde_json::to_string(event)?);
+            data.push('\n');
+        }
+        rt::write_atomic(&path, data, self.durability)
+            .await
+            .map_err(|e| io_error(e, &path))
     }
 }
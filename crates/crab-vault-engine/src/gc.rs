@@ -0,0 +1,199 @@
+//! # 清理孤立的数据和元数据
+//!
+//! 没有开启意图日志时崩溃，或者有人直接修改了数据目录，都会留下没有元数据的数据、没有数据的元数据，
+//! 以及既没有 object 也没有元数据的 bucket 目录。[`scan`] 找出它们，[`collect`] 删除它们。
+//!
+//! 上传先写数据再写元数据，所以服务运行时扫描可能把正在上传的 object 当作孤立的数据。
+//! 在后台定期清理时，只删除连续两次扫描都发现的部分（[`Garbage::intersect`]），
+//! 并且跳过意图日志中还没有完成的 object（[`Garbage::exclude`]）。
+//! [内部的 bucket](crate::name::internal_bucket) 由使用它的功能自己维护，不会被扫描
+
+use std::collections::{BTreeSet, HashSet};
+
+use serde::Serialize;
+
+use crate::{
+    DataEngine, MetaEngine,
+    error::{EngineError, EngineResult},
+    journal::Intent,
+    name,
+};
+
+/// 一个 bucket 中的一个 object
+#[derive(Serialize, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct ObjectRef {
+    pub bucket: String,
+    pub object: String,
+}
+
+/// [`scan`] 的结果，每一项都按照名称排序
+#[derive(Serialize, Clone, PartialEq, Eq, Default, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub struct Garbage {
+    /// 有数据但没有元数据的 object
+    pub orphan_data: Vec<ObjectRef>,
+
+    /// 有元数据但没有数据的 object
+    pub orphan_meta: Vec<ObjectRef>,
+
+    /// 没有任何 object，也没有 bucket 元数据的 bucket 目录
+    pub empty_buckets: Vec<String>,
+}
+
+impl ObjectRef {
+    pub fn new(bucket: impl Into<String>, object: impl Into<String>) -> Self {
+        Self {
+            bucket: bucket.into(),
+            object: object.into(),
+        }
+    }
+}
+
+impl Garbage {
+    pub fn is_empty(&self) -> bool {
+        self.orphan_data.is_empty() && self.orphan_meta.is_empty() && self.empty_buckets.is_empty()
+    }
+
+    /// 只保留两次扫描都发现的部分
+    pub fn intersect(&self, other: &Garbage) -> Garbage {
+        fn both<T: Clone + Eq + std::hash::Hash>(a: &[T], b: &[T]) -> Vec<T> {
+            let b: HashSet<_> = b.iter().collect();
+            a.iter().filter(|v| b.contains(v)).cloned().collect()
+        }
+
+        Garbage {
+            orphan_data: both(&self.orphan_data, &other.orphan_data),
+            orphan_meta: both(&self.orphan_meta, &other.orphan_meta),
+            empty_buckets: both(&self.empty_buckets, &other.empty_buckets),
+        }
+    }
+
+    /// 去掉 `intents` 涉及的 object 和 bucket，它们由 [`replay`](crate::journal::replay) 处理
+    pub fn exclude(&mut self, intents: &[Intent]) {
+        let mut objects = HashSet::new();
+        for intent in intents {
+            match intent {
+                Intent::Put { meta } => {
+                    objects.insert(ObjectRef::new(&meta.bucket_name, &meta.object_name));
+                }
+                Intent::Delete {
+                    bucket,
+                    objects: names,
+                } => {
+                    objects.extend(names.iter().map(|v| ObjectRef::new(bucket, v)));
+                }
+            }
+        }
+        let buckets: HashSet<_> = objects.iter().map(|v| v.bucket.as_str()).collect();
+
+        self.orphan_data.retain(|v| !objects.contains(v));
+        self.orphan_meta.retain(|v| !objects.contains(v));
+        self.empty_buckets.retain(|v| !buckets.contains(v.as_str()));
+    }
+}
+
+/// 找出所有孤立的数据、元数据和空的 bucket 目录，不做任何修改
+///
+/// 每个候选都会再读一次确认，对应的元数据损坏时不会被当作孤立的数据
+pub async fn scan<D, M>(data: &D, meta: &M) -> EngineResult<Garbage>
+where
+    D: DataEngine + Sync,
+    M: MetaEngine + Sync,
+{
+    let bucket_metas: BTreeSet<String> = meta
+        .list_buckets_meta()
+        .await?
+        .into_iter()
+        .map(|v| v.name)
+        .filter(|v| !name::is_internal_bucket(v))
+        .collect();
+    let data_buckets: BTreeSet<String> = data
+        .list_buckets()
+        .await?
+        .into_iter()
+        .filter(|v| !name::is_internal_bucket(v))
+        .collect();
+
+    // 没有 bucket 元数据的 bucket 中也可能有 object 的元数据
+    let mut object_metas = BTreeSet::new();
+    for bucket in bucket_metas.union(&data_buckets) {
+        for object in meta.list_objects_meta(bucket).await? {
+            object_metas.insert(ObjectRef::new(bucket, object.object_name));
+        }
+    }
+
+    // 先列出元数据再列出数据，两次列出之间完成的上传不会被当作孤立的元数据
+    let mut stored = BTreeSet::new();
+    let mut empty_buckets = vec![];
+    for bucket in &data_buckets {
+        let objects = match data.list_objects(bucket).await {
+            Ok(objects) => objects,
+            // 列出之后被删除了
+            Err(EngineError::BucketNotFound { .. }) => continue,
+            Err(e) => return Err(e),
+        };
+
+        let has_meta = bucket_metas.contains(bucket)
+            || object_metas
+                .range(ObjectRef::new(bucket, "")..)
+                .next()
+                .is_some_and(|v| &v.bucket == bucket);
+        if objects.is_empty() && !has_meta {
+            empty_buckets.push(bucket.clone());
+        }
+        stored.extend(objects.into_iter().map(|v| ObjectRef::new(bucket, v)));
+    }
+
+    let mut garbage = Garbage {
+        empty_buckets,
+        ..Garbage::default()
+    };
+
+    for candidate in stored.difference(&object_metas) {
+        match meta
+            .read_object_meta(&candidate.bucket, &candidate.object)
+            .await
+        {
+            Err(EngineError::ObjectMetaNotFound { .. }) => {
+                garbage.orphan_data.push(candidate.clone())
+            }
+            Ok(_) | Err(EngineError::CorruptMeta { .. }) => {}
+            Err(e) => return Err(e),
+        }
+    }
+
+    for candidate in object_metas.difference(&stored) {
+        match data.read_object(&candidate.bucket, &candidate.object).await {
+            Err(EngineError::ObjectNotFound { .. } | EngineError::BucketNotFound { .. }) => {
+                garbage.orphan_meta.push(candidate.clone())
+            }
+            Ok(_) => {}
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(garbage)
+}
+
+/// 删除 [`scan`] 找到的所有内容，已经不存在的部分会被忽略
+///
+/// 有人在此期间往空的 bucket 目录中写入了 object 时，这个 bucket 会被保留
+pub async fn collect<D, M>(data: &D, meta: &M, garbage: &Garbage) -> EngineResult<()>
+where
+    D: DataEngine + Sync,
+    M: MetaEngine + Sync,
+{
+    for v in &garbage.orphan_data {
+        data.delete_object(&v.bucket, &v.object).await?;
+    }
+    for v in &garbage.orphan_meta {
+        meta.delete_object_meta(&v.bucket, &v.object).await?;
+    }
+    for bucket in &garbage.empty_buckets {
+        match data.delete_bucket(bucket).await {
+            Ok(()) | Err(EngineError::BucketNotEmpty { .. }) => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,281 @@
+//! # 清单
+//!
+//! 保存在 [`BucketMeta`](crate::BucketMeta) 中，定期把 bucket 中所有 object 的列表写入另一个 bucket，
+//! 类似于 S3 Inventory。引擎只负责生成清单的内容和判断是否到了生成的时候，
+//! 写入目标 bucket 由调用者完成，这样清单可以和其他 object 一样被压缩、加密。
+//!
+//! 清单的 object 名称是 `{destination-prefix}{bucket}/{生成时间}.{csv|jsonl}`，
+//! 按照名称排序就是按照生成时间排序
+
+use chrono::{DateTime, Duration, SecondsFormat, Utc};
+use futures::TryStreamExt;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    MetaEngine, ObjectMeta,
+    error::{EngineError, EngineResult},
+    list::ListObjectsQuery,
+    name,
+};
+
+/// 没有给出 `destination-prefix` 时清单所在的目录
+pub const DEFAULT_DESTINATION_PREFIX: &str = "inventory/";
+
+/// CSV 清单的第一行，JSON 清单中每个对象的字段与之相同
+pub const CSV_HEADER: &str = "key,size,etag,content_type,updated_at";
+
+/// 清单 object 名称中生成时间的格式
+const TIME_FORMAT: &str = "%Y-%m-%dT%H-%M-%SZ";
+
+/// 一个 bucket 的清单配置
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct InventoryConfig {
+    /// 写入清单的 bucket，必须已经存在。可以是这个 bucket 本身，
+    /// 这时 [`prefix`](Self::prefix) 不能包括清单所在的目录，否则清单会列出自己
+    pub destination: String,
+
+    /// 清单在目标 bucket 中的名称前缀
+    #[serde(default = "default_destination_prefix")]
+    pub destination_prefix: String,
+
+    /// 只列出名称以此开头的 object，默认列出整个 bucket
+    #[serde(default)]
+    pub prefix: String,
+
+    #[serde(default)]
+    pub format: InventoryFormat,
+
+    #[serde(default)]
+    pub frequency: InventoryFrequency,
+}
+
+#[derive(Serialize, Deserialize, Default, PartialEq, Eq, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum InventoryFormat {
+    /// 第一行是 [`CSV_HEADER`]，每个字段都用双引号括起来
+    #[default]
+    Csv,
+
+    /// 每行一个 JSON 对象
+    Json,
+}
+
+#[derive(Serialize, Deserialize, Default, PartialEq, Eq, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum InventoryFrequency {
+    #[default]
+    Daily,
+    Weekly,
+}
+
+/// 清单中的一行
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct InventoryEntry {
+    pub key: String,
+    pub size: u64,
+    pub etag: String,
+    pub content_type: String,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// 由 [`generate`] 生成的清单
+#[derive(PartialEq, Clone, Debug)]
+pub struct Inventory {
+    /// 按照 [`InventoryConfig::format`] 编码之后的内容
+    pub data: Vec<u8>,
+
+    /// 清单中 object 的个数
+    pub objects: u64,
+
+    /// 清单中 object 的总字节数
+    pub bytes: u64,
+}
+
+fn default_destination_prefix() -> String {
+    DEFAULT_DESTINATION_PREFIX.to_string()
+}
+
+impl InventoryConfig {
+    pub fn new(destination: impl Into<String>) -> Self {
+        Self {
+            destination: destination.into(),
+            destination_prefix: default_destination_prefix(),
+            prefix: String::new(),
+            format: InventoryFormat::default(),
+            frequency: InventoryFrequency::default(),
+        }
+    }
+
+    /// 检查 `bucket` 的清单配置是否有意义，不合法时返回原因
+    pub fn validate(&self, bucket: &str) -> Result<(), String> {
+        name::validate_bucket_name(&self.destination)
+            .map_err(|e| format!("invalid `destination`: {e}"))?;
+        name::validate_object_name(&self.object_name(bucket, DateTime::UNIX_EPOCH))
+            .map_err(|e| format!("invalid `destination-prefix`: {e}"))?;
+
+        let manifest_prefix = self.manifest_prefix(bucket);
+        if self.destination == bucket
+            && (manifest_prefix.starts_with(&self.prefix)
+                || self.prefix.starts_with(&manifest_prefix))
+        {
+            return Err(format!(
+                "`prefix` must not cover `{manifest_prefix}` when `destination` is the bucket itself"
+            ));
+        }
+        Ok(())
+    }
+
+    /// `bucket` 在 `now` 生成的清单的 object 名称
+    pub fn object_name(&self, bucket: &str, now: DateTime<Utc>) -> String {
+        format!(
+            "{}{}.{}",
+            self.manifest_prefix(bucket),
+            now.format(TIME_FORMAT),
+            self.format.extension()
+        )
+    }
+
+    /// `bucket` 的所有清单在目标 bucket 中的公共前缀
+    pub fn manifest_prefix(&self, bucket: &str) -> String {
+        format!("{}{bucket}/", self.destination_prefix)
+    }
+
+    /// 上一次生成清单的时间为 `last` 时，`now` 是否应该再生成一次
+    pub fn is_due(&self, last: Option<DateTime<Utc>>, now: DateTime<Utc>) -> bool {
+        last.is_none_or(|last| last + self.frequency.period() <= now)
+    }
+}
+
+impl InventoryFormat {
+    pub const fn content_type(self) -> &'static str {
+        match self {
+            InventoryFormat::Csv => "text/csv",
+            InventoryFormat::Json => "application/x-ndjson",
+        }
+    }
+
+    pub const fn extension(self) -> &'static str {
+        match self {
+            InventoryFormat::Csv => "csv",
+            InventoryFormat::Json => "jsonl",
+        }
+    }
+
+    /// 把清单开头的内容写入 `data`，只有 CSV 清单有
+    pub fn write_header(self, data: &mut Vec<u8>) {
+        if self == InventoryFormat::Csv {
+            data.extend_from_slice(CSV_HEADER.as_bytes());
+            data.push(b'\n');
+        }
+    }
+
+    /// 把 `entry` 编码为清单中的一行，追加到 `data` 的末尾
+    pub fn write_entry(self, data: &mut Vec<u8>, entry: &InventoryEntry) -> EngineResult<()> {
+        match self {
+            InventoryFormat::Csv => {
+                let fields = [
+                    entry.key.as_str(),
+                    &entry.size.to_string(),
+                    &entry.etag,
+                    &entry.content_type,
+                    &entry
+                        .updated_at
+                        .to_rfc3339_opts(SecondsFormat::Millis, true),
+                ];
+                let line: Vec<_> = fields.iter().map(|v| csv_field(v)).collect();
+                data.extend_from_slice(line.join(",").as_bytes());
+            }
+            InventoryFormat::Json => serde_json::to_writer(&mut *data, entry)?,
+        }
+        data.push(b'\n');
+        Ok(())
+    }
+}
+
+impl InventoryFrequency {
+    pub fn period(self) -> Duration {
+        match self {
+            InventoryFrequency::Daily => Duration::days(1),
+            InventoryFrequency::Weekly => Duration::weeks(1),
+        }
+    }
+}
+
+impl From<ObjectMeta> for InventoryEntry {
+    fn from(meta: ObjectMeta) -> Self {
+        Self {
+            key: meta.object_name,
+            size: meta.size,
+            etag: meta.etag,
+            content_type: meta.content_type,
+            updated_at: meta.updated_at,
+        }
+    }
+}
+
+/// 按照 RFC 4180 用双引号括起来，其中的双引号写两次
+fn csv_field(value: &str) -> String {
+    format!("\"{}\"", value.replace('"', "\"\""))
+}
+
+/// ## 生成 `bucket_name` 的清单
+///
+/// 边读取元数据边编码，不会把整个 bucket 的元数据留在内存中，所以清单中 object 的顺序不固定。
+/// 与 [`list_objects_meta`](MetaEngine::list_objects_meta) 一样不包括回收站中的 object
+pub async fn generate<M: MetaEngine + Sync>(
+    meta: &M,
+    bucket_name: &str,
+    config: &InventoryConfig,
+) -> EngineResult<Inventory> {
+    let query = ListObjectsQuery {
+        prefix: (!config.prefix.is_empty()).then(|| config.prefix.clone()),
+        ..ListObjectsQuery::default()
+    };
+    let mut inventory = Inventory {
+        data: vec![],
+        objects: 0,
+        bytes: 0,
+    };
+    config.format.write_header(&mut inventory.data);
+
+    let mut stream = meta.stream_objects_meta(bucket_name, &query);
+    while let Some(meta) = stream.try_next().await? {
+        inventory.objects += 1;
+        inventory.bytes += meta.size;
+        config
+            .format
+            .write_entry(&mut inventory.data, &InventoryEntry::from(meta))?;
+    }
+
+    Ok(inventory)
+}
+
+/// `bucket_name` 上一次生成清单的时间，即目标 bucket 中它最新的清单的创建时间
+///
+/// 目标 bucket 不存在或者其中没有清单时返回 [`None`]
+pub async fn last_generated<M: MetaEngine + Sync>(
+    meta: &M,
+    bucket_name: &str,
+    config: &InventoryConfig,
+) -> EngineResult<Option<DateTime<Utc>>> {
+    let query = ListObjectsQuery {
+        prefix: Some(config.manifest_prefix(bucket_name)),
+        ..ListObjectsQuery::default()
+    };
+    let last = meta
+        .stream_objects_meta(&config.destination, &query)
+        .try_fold(None, |last: Option<DateTime<Utc>>, v| async move {
+            Ok(Some(
+                last.map_or(v.created_at, |last| last.max(v.created_at)),
+            ))
+        })
+        .await;
+
+    match last {
+        Err(EngineError::BucketNotFound { .. } | EngineError::BucketMetaNotFound { .. }) => {
+            Ok(None)
+        }
+        other => other,
+    }
+}
@@ -0,0 +1,395 @@
+//! # 意图日志
+//!
+//! 上传和删除都要分别修改数据和元数据，两步之间崩溃会留下没有元数据的数据，或者指向不存在数据的元数据。
+//! 修改之前先用 [`Journal::begin`] 记录下要做什么，两步都完成后再用 [`Journal::finish`] 删除这条记录，
+//! 启动时 [`replay`] 检查所有没有完成的记录，按照记录把数据和元数据恢复到一致的状态。
+//!
+//! 每条记录是日志目录中的一个 JSON 文件，文件名是递增的序号，所以按照文件名排序就是记录的先后顺序
+
+use std::{
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    DataEngine, MetaEngine, ObjectMeta,
+    builder::compute_etag,
+    error::{EngineError, EngineResult},
+    fs::Durability,
+    rt,
+};
+
+/// 一条没有完成的修改
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+#[serde(rename_all = "lowercase", tag = "op")]
+pub enum Intent {
+    /// 先写入数据，再写入 `meta`
+    Put { meta: ObjectMeta },
+
+    /// 先删除数据，再删除元数据
+    Delete {
+        bucket: String,
+        objects: Vec<String>,
+    },
+}
+
+/// [`Journal::begin`] 返回的记录序号
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub struct IntentId(u64);
+
+/// 保存在本地目录中的意图日志
+#[derive(Debug)]
+pub struct Journal {
+    dir: PathBuf,
+    durability: Durability,
+    next: AtomicU64,
+}
+
+/// 检查一个 object 时发现的状态
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum Finding {
+    /// 数据和元数据一致
+    Consistent,
+
+    /// 数据和元数据都不存在
+    Absent,
+
+    /// 数据已经按照意图写入，但元数据没有，修复时补写元数据
+    Unfinished,
+
+    /// 元数据指向的数据不存在，修复时删除元数据
+    DanglingMeta,
+
+    /// 数据没有对应的元数据，修复时根据数据生成元数据
+    OrphanData,
+
+    /// 元数据中的大小和 etag 与数据不符，修复时根据数据更新
+    StaleMeta,
+
+    /// 删除没有完成，修复时删除剩下的数据和元数据
+    PartiallyDeleted,
+}
+
+/// 对一个 object 的检查结果
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub struct Report {
+    pub bucket: String,
+    pub object: String,
+    pub finding: Finding,
+
+    /// 是否已经修复，[`Finding::Consistent`] 和 [`Finding::Absent`] 总是 `false`
+    pub repaired: bool,
+}
+
+impl Finding {
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Finding::Consistent => "consistent",
+            Finding::Absent => "absent",
+            Finding::Unfinished => "unfinished",
+            Finding::DanglingMeta => "dangling-meta",
+            Finding::OrphanData => "orphan-data",
+            Finding::StaleMeta => "stale-meta",
+            Finding::PartiallyDeleted => "partially-deleted",
+        }
+    }
+
+    /// 数据和元数据是否需要修复
+    pub const fn needs_repair(&self) -> bool {
+        !matches!(self, Finding::Consistent | Finding::Absent)
+    }
+}
+
+impl Intent {
+    pub fn put(meta: &ObjectMeta) -> Self {
+        Self::Put { meta: meta.clone() }
+    }
+
+    pub fn delete(bucket: &str, objects: &[String]) -> Self {
+        Self::Delete {
+            bucket: bucket.to_string(),
+            objects: objects.to_vec(),
+        }
+    }
+
+    /// 这条记录是否涉及 `bucket` 中的 `object`
+    pub fn touches(&self, bucket: &str, object: &str) -> bool {
+        match self {
+            Intent::Put { meta } => meta.bucket_name == bucket && meta.object_name == object,
+            Intent::Delete {
+                bucket: deleted,
+                objects,
+            } => deleted == bucket && objects.iter().any(|v| v == object),
+        }
+    }
+}
+
+impl Journal {
+    /// 打开 `dir` 作为日志目录，不存在时创建它
+    pub async fn open(dir: impl AsRef<Path>) -> EngineResult<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        rt::create_dir_all(&dir)
+            .await
+            .map_err(|e| io_error(e, &dir))?;
+
+        let next = entries(&dir).await?.last().map_or(0, |(id, _)| id.0 + 1);
+        Ok(Self {
+            dir,
+            durability: Durability::default(),
+            next: AtomicU64::new(next),
+        })
+    }
+
+    /// 写入记录时的持久性，默认为 [`Durability::Full`]
+    pub fn durability(mut self, durability: Durability) -> Self {
+        self.durability = durability;
+        self
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// 记录将要进行的修改，返回之后才能开始修改
+    pub async fn begin(&self, intent: &Intent) -> EngineResult<IntentId> {
+        let id = IntentId(self.next.fetch_add(1, Ordering::Relaxed));
+        let path = self.path_of(id);
+        rt::write_atomic_new(&path, serde_json::to_vec(intent)?, self.durability)
+            .await
+            .map_err(|e| io_error(e, &path))?;
+        Ok(id)
+    }
+
+    /// 修改已经全部完成，删除对应的记录
+    pub async fn finish(&self, id: IntentId) -> EngineResult<()> {
+        let path = self.path_of(id);
+        match rt::remove_file(&path).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(io_error(e, &path)),
+            _ => Ok(()),
+        }
+    }
+
+    /// 所有没有完成的记录，按照记录的先后排序
+    ///
+    /// 无法解析的记录产生 [`CorruptMeta`](EngineError::CorruptMeta)，`entry` 是记录的文件名
+    pub async fn pending(&self) -> EngineResult<Vec<(IntentId, Intent)>> {
+        let mut pending = vec![];
+        for (id, path) in entries(&self.dir).await? {
+            let data = rt::read(&path).await.map_err(|e| io_error(e, &path))?;
+            let intent = serde_json::from_slice(&data).map_err(|_| EngineError::CorruptMeta {
+                entry: path
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .to_string(),
+            })?;
+            pending.push((id, intent));
+        }
+        Ok(pending)
+    }
+
+    fn path_of(&self, id: IntentId) -> PathBuf {
+        self.dir.join(format!("{:020}.json", id.0))
+    }
+}
+
+/// 检查一个 object 的数据和元数据是否一致，`repair` 为 `true` 时顺便修复
+///
+/// `intended` 是没有完成的 [`Intent::Put`] 中的元数据，数据与它一致时说明只差写入元数据
+pub async fn reconcile<D, M>(
+    data: &D,
+    meta: &M,
+    bucket: &str,
+    object: &str,
+    intended: Option<&ObjectMeta>,
+    repair: bool,
+) -> EngineResult<Report>
+where
+    D: DataEngine + Sync,
+    M: MetaEngine + Sync,
+{
+    let stored = match data.read_object(bucket, object).await {
+        Ok(stored) => Some(stored),
+        Err(EngineError::ObjectNotFound { .. } | EngineError::BucketNotFound { .. }) => None,
+        Err(e) => return Err(e),
+    };
+    let current = match meta.read_object_meta(bucket, object).await {
+        Ok(current) => Some(current),
+        Err(EngineError::ObjectMetaNotFound { .. }) => None,
+        Err(e) => return Err(e),
+    };
+    let matches = |meta: &ObjectMeta| stored.as_deref().is_some_and(|v| data_matches(meta, v));
+
+    let finding = match (&stored, &current) {
+        (Some(_), current) if intended.is_some_and(matches) => match current {
+            Some(current) if matches(current) => Finding::Consistent,
+            _ => Finding::Unfinished,
+        },
+        (Some(_), Some(current)) if matches(current) => Finding::Consistent,
+        (Some(_), Some(_)) => Finding::StaleMeta,
+        (Some(_), None) => Finding::OrphanData,
+        (None, Some(_)) => Finding::DanglingMeta,
+        (None, None) => Finding::Absent,
+    };
+
+    let repaired = repair && finding.needs_repair();
+    if repaired {
+        match finding {
+            Finding::Unfinished => meta.create_object_meta(intended.unwrap()).await?,
+            Finding::DanglingMeta => meta.delete_object_meta(bucket, object).await?,
+            Finding::StaleMeta | Finding::OrphanData => {
+                let stored = stored.unwrap_or_default();
+                let mut builder = ObjectMeta::builder()
+                    .bucket_name(bucket)
+                    .object_name(object)
+                    .data(&stored);
+                // 保留原有元数据中与数据无关的部分，加密信息描述的是原来的数据，不再适用
+                if let Some(current) = current {
+                    let current = current.hide_reserved_meta();
+                    builder = builder
+                        .content_type(current.content_type)
+                        .user_meta(current.user_meta)
+                        .created_at(current.created_at)
+                        .retention(current.retention);
+                }
+                meta.create_object_meta(&builder.build()?).await?
+            }
+            _ => {}
+        }
+    }
+
+    Ok(Report {
+        bucket: bucket.to_string(),
+        object: object.to_string(),
+        finding,
+        repaired,
+    })
+}
+
+/// 数据后端中保存的 `stored` 是否就是 `meta` 描述的数据
+///
+/// 加密过的数据与元数据中记录的密文的 etag 比较，不需要主密钥；
+/// 压缩过的数据要按照元数据中的压缩信息还原之后再比较
+pub fn data_matches(meta: &ObjectMeta, stored: &[u8]) -> bool {
+    match meta.encryption() {
+        Ok(Some(encryption)) => compute_etag(stored) == encryption.stored_etag,
+        Ok(None) => match &meta.compression {
+            Some(compression) => compression
+                .codec
+                .decompress(stored)
+                .is_ok_and(|v| compute_etag(&v) == meta.etag),
+            None => compute_etag(stored) == meta.etag,
+        },
+        Err(_) => false,
+    }
+}
+
+/// 重放所有没有完成的记录，检查涉及的 object，返回每个 object 的检查结果
+///
+/// `repair` 为 `true` 时把它们恢复到一致的状态并删除记录，应当在开始处理请求之前调用
+pub async fn replay<D, M>(
+    journal: &Journal,
+    data: &D,
+    meta: &M,
+    repair: bool,
+) -> EngineResult<Vec<Report>>
+where
+    D: DataEngine + Sync,
+    M: MetaEngine + Sync,
+{
+    let mut reports = vec![];
+    for (id, intent) in journal.pending().await? {
+        match intent {
+            Intent::Put { meta: intended } => {
+                let (bucket, object) = (&intended.bucket_name, &intended.object_name);
+                reports.push(reconcile(data, meta, bucket, object, Some(&intended), repair).await?);
+            }
+            Intent::Delete { bucket, objects } => {
+                for object in objects {
+                    reports.push(finish_delete(data, meta, &bucket, &object, repair).await?);
+                }
+            }
+        }
+        if repair {
+            journal.finish(id).await?;
+        }
+    }
+    Ok(reports)
+}
+
+/// 完成没有完成的删除，与删除本身一样先删除数据，再删除元数据
+///
+/// 删除本身是幂等的，所以先检查还剩下什么
+async fn finish_delete<D, M>(
+    data: &D,
+    meta: &M,
+    bucket: &str,
+    object: &str,
+    repair: bool,
+) -> EngineResult<Report>
+where
+    D: DataEngine + Sync,
+    M: MetaEngine + Sync,
+{
+    let data_left = match data.read_object(bucket, object).await {
+        Ok(_) => true,
+        Err(EngineError::ObjectNotFound { .. } | EngineError::BucketNotFound { .. }) => false,
+        Err(e) => return Err(e),
+    };
+    let meta_left = match meta.read_object_meta(bucket, object).await {
+        Ok(_) => true,
+        Err(EngineError::ObjectMetaNotFound { .. }) => false,
+        Err(e) => return Err(e),
+    };
+
+    if repair && data_left {
+        data.delete_object(bucket, object).await?;
+    }
+    if repair && meta_left {
+        meta.delete_object_meta(bucket, object).await?;
+    }
+
+    let left = data_left || meta_left;
+    Ok(Report {
+        bucket: bucket.to_string(),
+        object: object.to_string(),
+        finding: if left {
+            Finding::PartiallyDeleted
+        } else {
+            Finding::Absent
+        },
+        repaired: repair && left,
+    })
+}
+
+/// 日志目录中的所有记录，按照序号排序，忽略写了一半的临时文件
+async fn entries(dir: &Path) -> EngineResult<Vec<(IntentId, PathBuf)>> {
+    let mut entries = vec![];
+    let mut dir_entries = rt::read_dir(dir).await.map_err(|e| io_error(e, dir))?;
+    while let Some(path) = dir_entries
+        .next_entry()
+        .await
+        .map_err(|e| io_error(e, dir))?
+    {
+        let id = path
+            .extension()
+            .filter(|v| *v == "json")
+            .and_then(|_| path.file_stem()?.to_str()?.parse().ok());
+        if let Some(id) = id {
+            entries.push((IntentId(id), path));
+        }
+    }
+    entries.sort();
+    Ok(entries)
+}
+
+fn io_error(e: std::io::Error, path: &Path) -> EngineError {
+    EngineError::Io {
+        error: e,
+        path: path.to_string_lossy().to_string(),
+    }
+}
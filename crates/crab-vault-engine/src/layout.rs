@@ -0,0 +1,259 @@
+//! # 本地目录的结构
+//!
+//! [`FsDataEngine`](crate::fs::FsDataEngine) 和 [`FsMetaEngine`](crate::fs::FsMetaEngine) 在创建时检查目录的结构，
+//! 配置中的路径指向了错误的目录时立即报错并给出修复建议，而不是之后在每个请求上产生 IO 错误。
+//!
+//! 每个目录中都有一个 [`LAYOUT_FILE`]，记录哪些后端在使用这个目录，以及目录结构的版本：
+//!
+//! - 空目录或者不存在的目录视为新目录，创建需要的子目录和这个文件
+//! - 没有这个文件但是结构符合预期的目录是之前的版本创建的，补上这个文件
+//! - 其他情况都视为错误，不做任何修改
+//!
+//! 默认配置中数据和元数据使用同一个目录，所以一个目录可以同时被二者使用
+
+use std::{ffi::OsString, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{EngineError, EngineResult};
+
+/// 记录目录结构的文件名
+pub const LAYOUT_FILE: &str = ".crab-vault-layout";
+
+/// 以 `/` 结尾的 object 在所在目录中使用的文件名
+///
+/// 这样的 object 是同步工具创建的目录标记，它与名称以它为前缀的 object 共存，所以保存为同名目录中的一个文件，
+/// 数据是 `folder/.crab-vault-dir`，元数据是 `folder/.crab-vault-dir.json`
+pub const DIR_MARKER: &str = ".crab-vault-dir";
+
+/// 超过 [`MAX_SEGMENT_LEN`] 的段在本地使用的文件名的前缀，后面是这一段的 sha256 摘要的十六进制编码
+///
+/// 大多数文件系统限制文件名不超过 255 字节，元数据文件和写入时的临时文件还要在原来的名称上加上后缀，
+/// 所以过长的段改为使用它的摘要。这样的文件无法还原出原来的名称，只能通过元数据找到
+pub const LONG_SEGMENT_PREFIX: &str = ".crab-vault-long-";
+
+/// object 名称中的一段在本地直接用作文件名的最大字节数
+pub const MAX_SEGMENT_LEN: usize = 200;
+
+/// 分散存放 object 的 bucket 中，object 文件所在的子目录，参见 [`ObjectLayout`](crate::fs::ObjectLayout)
+///
+/// 它存在时 bucket 中的 object 都保存为 `.crab-vault-fanout/ab/cd/name`，其中 `ab`、`cd` 是 object 的本地相对路径的
+/// sha256 摘要的前两个字节。不存在时 object 直接保存在 bucket 目录中
+pub const FANOUT_DIR: &str = ".crab-vault-fanout";
+
+/// [`FANOUT_DIR`] 下的子目录层数
+pub const FANOUT_LEVELS: usize = 2;
+
+/// 开启去重时保存 object 数据的目录，参见 [`FsDataEngine::dedup`](crate::fs::FsDataEngine::dedup)
+///
+/// 每一份不同的数据保存为 `.crab-vault-blobs/ab/<sha256>`，其中 `ab` 是摘要的第一个字节，
+/// bucket 中的 object 文件都是它们的硬链接
+pub const BLOBS_DIR: &str = ".crab-vault-blobs";
+
+/// 元数据目录中的变更事件日志，每行是一条 JSON 格式的 [`Event`](crate::events::Event)
+pub const EVENTS_FILE: &str = ".crab-vault-events";
+
+/// 当前的目录结构版本
+pub const LAYOUT_VERSION: u32 = 1;
+
+/// 元数据目录中 bucket 元数据所在的子目录
+pub(crate) const BUCKETS_DIR: &str = "buckets";
+
+/// 元数据目录中 object 元数据所在的子目录
+pub(crate) const OBJECTS_DIR: &str = "objects";
+
+/// 目录中保存的是什么
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LayoutKind {
+    Data,
+    Meta,
+}
+
+/// [`LAYOUT_FILE`] 的内容
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Layout {
+    pub kinds: Vec<LayoutKind>,
+    pub version: u32,
+}
+
+impl LayoutKind {
+    fn describe(self) -> &'static str {
+        match self {
+            LayoutKind::Data => "data",
+            LayoutKind::Meta => "metadata",
+        }
+    }
+
+    fn config_key(self) -> &'static str {
+        match self {
+            LayoutKind::Data => "data.source",
+            LayoutKind::Meta => "meta.source",
+        }
+    }
+}
+
+/// 检查 `base_dir` 的结构，必要时创建缺少的部分，参见[模块文档](self)
+pub fn ensure(base_dir: &Path, kind: LayoutKind) -> EngineResult<()> {
+    if base_dir.exists() && !base_dir.is_dir() {
+        return Err(invalid(
+            base_dir,
+            "it is not a directory",
+            format!("point `{}` at a directory", kind.config_key()),
+        ));
+    }
+    std::fs::create_dir_all(base_dir).map_err(|e| io_error(e, base_dir))?;
+
+    let marker = base_dir.join(LAYOUT_FILE);
+    let layout = match std::fs::read(&marker) {
+        Ok(data) => read_marker(base_dir, kind, &data)?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            check_unmarked(base_dir, kind)?;
+            Layout {
+                kinds: vec![],
+                version: LAYOUT_VERSION,
+            }
+        }
+        Err(e) => return Err(io_error(e, &marker)),
+    };
+
+    if !layout.kinds.contains(&kind) {
+        let mut layout = layout;
+        layout.kinds.push(kind);
+        let data = serde_json::to_vec(&layout)?;
+        std::fs::write(&marker, data).map_err(|e| io_error(e, &marker))?;
+    }
+
+    // 新目录中还没有这两个子目录，旧的目录中它们也可能被误删，重新创建空目录是安全的
+    if kind == LayoutKind::Meta {
+        for dir in [BUCKETS_DIR, OBJECTS_DIR] {
+            let dir = base_dir.join(dir);
+            match dir.is_dir() {
+                true => {}
+                false if dir.exists() => {
+                    return Err(invalid(
+                        &dir,
+                        "it should be a directory",
+                        format!("move this file away, `{}` is reserved", dir.display()),
+                    ));
+                }
+                false => std::fs::create_dir_all(&dir).map_err(|e| io_error(e, &dir))?,
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// `base_dir` 是否同时被元数据使用，此时其中的 [`BUCKETS_DIR`] 和 [`OBJECTS_DIR`] 不是 bucket
+pub(crate) fn has_meta(base_dir: &Path) -> bool {
+    std::fs::read(base_dir.join(LAYOUT_FILE))
+        .ok()
+        .and_then(|data| serde_json::from_slice::<Layout>(&data).ok())
+        .is_some_and(|layout| layout.kinds.contains(&LayoutKind::Meta))
+}
+
+fn read_marker(base_dir: &Path, kind: LayoutKind, data: &[u8]) -> EngineResult<Layout> {
+    let Ok(layout) = serde_json::from_slice::<Layout>(data) else {
+        let expected = Layout {
+            kinds: vec![kind],
+            version: LAYOUT_VERSION,
+        };
+        return Err(invalid(
+            base_dir,
+            format!("`{LAYOUT_FILE}` is not readable"),
+            format!(
+                "if this is the right directory, replace `{LAYOUT_FILE}` with {}",
+                serde_json::to_string(&expected).unwrap_or_default()
+            ),
+        ));
+    };
+
+    if layout.version > LAYOUT_VERSION {
+        return Err(invalid(
+            base_dir,
+            format!(
+                "layout version {} is newer than the supported version {LAYOUT_VERSION}",
+                layout.version
+            ),
+            "this directory was written by a newer crab-vault, upgrade the server",
+        ));
+    }
+
+    Ok(layout)
+}
+
+/// 没有标记文件的目录要么是空的，要么是之前的版本创建的
+///
+/// 数据目录中的每一项都是一个 bucket；元数据目录中只有 [`BUCKETS_DIR`] 和 [`OBJECTS_DIR`]，
+/// 与数据共用目录时还会有 bucket 的数据目录。这两种目录中都不应该有普通文件
+fn check_unmarked(base_dir: &Path, kind: LayoutKind) -> EngineResult<()> {
+    let entries = std::fs::read_dir(base_dir)
+        .map_err(|e| io_error(e, base_dir))?
+        .map(|v| v.map(|v| (v.file_name(), v.path())))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| io_error(e, base_dir))?
+        .into_iter()
+        // 隐藏文件通常是操作系统或者其他工具留下的
+        .filter(|(name, _)| !name.to_string_lossy().starts_with('.') && name != "lost+found")
+        .collect::<Vec<_>>();
+
+    let has_meta = base_dir.join(BUCKETS_DIR).is_dir();
+    let unexpected = entries
+        .iter()
+        .filter(|(name, path)| match path.is_dir() {
+            true => {
+                kind == LayoutKind::Meta && !has_meta && name != BUCKETS_DIR && name != OBJECTS_DIR
+            }
+            false => true,
+        })
+        .map(|(name, _)| name.clone())
+        .collect::<Vec<_>>();
+
+    if unexpected.is_empty() {
+        return Ok(());
+    }
+
+    Err(invalid(
+        base_dir,
+        format!(
+            "it does not look like a {} directory, unexpected entries: {}",
+            kind.describe(),
+            join(&unexpected)
+        ),
+        format!(
+            "check `{}`, or point it at an empty directory; if this is the right directory, \
+             move the unexpected entries away",
+            kind.config_key()
+        ),
+    ))
+}
+
+fn join(names: &[OsString]) -> String {
+    const SHOWN: usize = 5;
+
+    let mut joined = names
+        .iter()
+        .take(SHOWN)
+        .map(|v| format!("`{}`", v.to_string_lossy()))
+        .collect::<Vec<_>>()
+        .join(", ");
+    if names.len() > SHOWN {
+        joined.push_str(&format!(" and {} more", names.len() - SHOWN));
+    }
+    joined
+}
+
+fn invalid(path: &Path, reason: impl AsRef<str>, hint: impl AsRef<str>) -> EngineError {
+    EngineError::InvalidLayout {
+        path: path.to_string_lossy().to_string(),
+        reason: format!("{}; hint: {}", reason.as_ref(), hint.as_ref()),
+    }
+}
+
+fn io_error(e: std::io::Error, path: &Path) -> EngineError {
+    EngineError::Io {
+        error: e,
+        path: path.to_string_lossy().to_string(),
+    }
+}
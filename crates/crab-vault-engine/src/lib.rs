@@ -1,22 +1,106 @@
+use std::{path::Path, pin::Pin, time::Duration};
+
 use chrono::{DateTime, Utc};
+use futures::{AsyncRead, TryStreamExt, future::ready, io::Cursor, stream};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
-use crate::error::EngineResult;
-
+use crate::{
+    compression::Compression,
+    crypto::WrappedKey,
+    error::{EngineError, EngineResult},
+    events::Event,
+    inventory::InventoryConfig,
+    lifecycle::LifecycleConfig,
+    list::{BucketPage, ListBucketsQuery, ListObjectsQuery, MetaStream, ObjectPage},
+    retention::ObjectRetention,
+    tagging::Tags,
+    usage::{BucketUsage, StorageStats},
+};
+
+pub mod builder;
+pub mod cache;
+pub mod clock;
+pub mod compression;
+pub mod crypto;
 pub mod error;
+pub mod events;
+pub mod filter;
 pub mod fs;
-
-pub type DataSource = fs::FsDataEngine;
-pub type MetaSource = fs::FsMetaEngine;
+pub mod gc;
+pub mod inventory;
+pub mod journal;
+pub mod layout;
+pub mod lifecycle;
+pub mod list;
+pub mod mem;
+pub mod name;
+#[cfg(feature = "postgres")]
+pub mod postgres;
+pub mod registry;
+pub mod retention;
+mod rt;
+#[cfg(feature = "s3")]
+pub mod s3;
+mod source;
+pub mod tagging;
+pub mod trash;
+pub mod usage;
+
+pub use registry::EngineRegistry;
+pub use source::{DataSource, MetaSource};
+
+/// 数据库类后端的连接池配置，文件系统后端会忽略这些配置
+#[derive(Clone, Debug, PartialEq)]
+pub struct PoolConfig {
+    /// 连接池中最多保持的连接数
+    pub max_connections: u32,
+
+    /// 连接池中至少保持的连接数
+    pub min_connections: u32,
+
+    /// 从连接池中获取一个连接的最长等待时间
+    pub acquire_timeout: Duration,
+
+    /// 空闲连接被关闭前的最长存活时间，[`None`] 表示不关闭
+    pub idle_timeout: Option<Duration>,
+}
 
 /// Bucket 的元数据结构
-#[derive(Serialize, Deserialize, Default, PartialEq, Debug)]
+#[derive(Serialize, Deserialize, Default, PartialEq, Clone, Debug)]
 #[serde(rename_all = "kebab-case")]
 pub struct BucketMeta {
     pub name: String,
     pub user_meta: Value,
 
+    /// 被主密钥包裹的数据密钥，未启用加密时为 [`None`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data_key: Option<WrappedKey>,
+
+    /// bucket 的访问策略，引擎不解释它的内容，没有设置时为 [`None`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub policy: Option<Value>,
+
+    /// 生命周期规则，参见 [`lifecycle`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lifecycle: Option<LifecycleConfig>,
+
+    /// 跨域 (CORS) 规则，与 bucket 策略一样引擎不解释它的内容，没有设置时为 [`None`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cors: Option<Value>,
+
+    /// 清单配置，参见 [`inventory`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub inventory: Option<InventoryConfig>,
+
+    /// object 的总字节数的上限，参见 [`usage`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_bytes: Option<u64>,
+
+    /// object 个数的上限，参见 [`usage`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_objects: Option<u64>,
+
     #[serde(alias = "createdAt")]
     pub created_at: DateTime<Utc>,
 
@@ -25,7 +109,7 @@ pub struct BucketMeta {
 }
 
 /// Object 的元数据结构
-#[derive(Serialize, Deserialize, Default, PartialEq, Debug)]
+#[derive(Serialize, Deserialize, Default, PartialEq, Clone, Debug)]
 #[serde(rename_all = "kebab-case")]
 pub struct ObjectMeta {
     pub object_name: String,
@@ -40,8 +124,27 @@ pub struct ObjectMeta {
 
     #[serde(alias = "updatedAt")]
     pub updated_at: DateTime<Utc>,
+
+    /// 过期时间，过期之后不能再读取，并由生命周期任务删除，参见 [`lifecycle`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
+
+    /// 数据是否经过压缩，参见 [`compression`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compression: Option<Compression>,
+
+    /// 标签，参见 [`tagging`]
+    #[serde(default, skip_serializing_if = "Tags::is_empty")]
+    pub tags: Tags,
+
+    /// 保留期和合法保留，参见 [`retention`]
+    #[serde(default, skip_serializing_if = "ObjectRetention::is_empty")]
+    pub retention: ObjectRetention,
 }
 
+/// 逐块读取 object 数据的 reader，参见 [`DataEngine::read_object_reader`]
+pub type ObjectReader = Pin<Box<dyn AsyncRead + Send>>;
+
 /// 此 trait 定义了 object 从何处来，所有的操作，都是幂等的
 pub trait DataEngine: Sized {
     type Uri: ?Sized;
@@ -55,6 +158,18 @@ pub trait DataEngine: Sized {
     /// 删除一个 bucket，如果不存在，那么不会有任何改变
     fn delete_bucket(&self, bucket_name: &str) -> impl Future<Output = EngineResult<()>> + Send;
 
+    /// 列出所有 bucket 的名称，没有顺序上的保证
+    fn list_buckets(&self) -> impl Future<Output = EngineResult<Vec<String>>> + Send;
+
+    /// # 列出一个 bucket 中所有 object 的名称
+    ///
+    /// 只用于维护性质的操作（例如找出没有元数据的数据），没有顺序上的保证；
+    /// `bucket_name` 不存在时抛出 [`BucketNotFound`](crate::error::EngineError::BucketNotFound)
+    fn list_objects(
+        &self,
+        bucket_name: &str,
+    ) -> impl Future<Output = EngineResult<Vec<String>>> + Send;
+
     /// # 创建一个 object
     ///
     /// 如果 这个 object 已经存在，将覆盖之
@@ -67,6 +182,43 @@ pub trait DataEngine: Sized {
         data: &[u8],
     ) -> impl Future<Output = EngineResult<()>> + Send;
 
+    /// # 只在 object 不存在时创建它
+    ///
+    /// 检查和写入是原子的，并发调用时只有一个调用者能够成功，其余的得到
+    /// [`ObjectAlreadyExists`](crate::error::EngineError::ObjectAlreadyExists)；
+    /// `bucket_name` 不存在时与 [`create_object`](DataEngine::create_object) 一样抛出
+    /// [`BucketNotFound`](crate::error::EngineError::BucketNotFound)
+    fn create_object_if_absent(
+        &self,
+        bucket_name: &str,
+        object_name: &str,
+        data: &[u8],
+    ) -> impl Future<Output = EngineResult<()>> + Send;
+
+    /// # 使用一个本地文件的内容创建 object
+    ///
+    /// 与 [`create_object`](DataEngine::create_object) 的语义相同，`path` 仍然属于调用者，调用结束后不会被删除
+    ///
+    /// 默认实现把整个文件读入内存，能够直接从文件复制的后端应当覆盖这个方法
+    fn create_object_from_file(
+        &self,
+        bucket_name: &str,
+        object_name: &str,
+        path: &Path,
+    ) -> impl Future<Output = EngineResult<()>> + Send
+    where
+        Self: Sync,
+    {
+        async move {
+            let data = rt::read(path).await.map_err(|error| EngineError::Io {
+                error,
+                path: path.to_string_lossy().to_string(),
+            })?;
+
+            self.create_object(bucket_name, object_name, &data).await
+        }
+    }
+
     /// 读取一个 object
     fn read_object(
         &self,
@@ -74,12 +226,166 @@ pub trait DataEngine: Sized {
         object_name: &str,
     ) -> impl Future<Output = EngineResult<Vec<u8>>> + Send;
 
+    /// # 以 [`ObjectReader`] 的形式读取一个 object
+    ///
+    /// 读出的数据与 [`read_object`](DataEngine::read_object) 相同，调用者可以边读边发送，不必先把整个 object 放进内存
+    ///
+    /// 默认实现仍然先读出整个 object，能够直接从存储中逐块读取的后端应当覆盖这个方法
+    fn read_object_reader(
+        &self,
+        bucket_name: &str,
+        object_name: &str,
+    ) -> impl Future<Output = EngineResult<ObjectReader>> + Send
+    where
+        Self: Sync,
+    {
+        async move {
+            let data = self.read_object(bucket_name, object_name).await?;
+            Ok(Box::pin(Cursor::new(data)) as ObjectReader)
+        }
+    }
+
     /// 删除一个 object
     fn delete_object(
         &self,
         bucket_name: &str,
         object_name: &str,
     ) -> impl Future<Output = EngineResult<()>> + Send;
+
+    /// # 在服务端复制一个 object
+    ///
+    /// 如果目标 object 已经存在，将覆盖之
+    /// 源 object 不存在时抛出 [`ObjectNotFound`](crate::error::EngineError::ObjectNotFound)，
+    /// 目标 bucket 不存在时抛出 [`BucketNotFound`](crate::error::EngineError::BucketNotFound)
+    ///
+    /// 默认实现先读出整个源 object 再写入，能够在存储层直接复制的后端应当覆盖这个方法
+    fn copy_object(
+        &self,
+        src_bucket: &str,
+        src_object: &str,
+        dst_bucket: &str,
+        dst_object: &str,
+    ) -> impl Future<Output = EngineResult<()>> + Send
+    where
+        Self: Sync,
+    {
+        async move {
+            let data = self.read_object(src_bucket, src_object).await?;
+            self.create_object(dst_bucket, dst_object, &data).await
+        }
+    }
+
+    /// # 从 `offset` 开始覆盖 object 的一部分
+    ///
+    /// object 不存在时视为一个空的 object，`offset` 超过 object 末尾时，中间空出的部分读出为 0；
+    /// 这个方法只修改数据，调用者需要自行更新元数据中的大小和 etag
+    ///
+    /// 默认实现读出整个 object 修改后再写回，能够原地修改的后端应当覆盖这个方法
+    fn write_object_at(
+        &self,
+        bucket_name: &str,
+        object_name: &str,
+        offset: u64,
+        data: &[u8],
+    ) -> impl Future<Output = EngineResult<()>> + Send
+    where
+        Self: Sync,
+    {
+        async move {
+            let mut object = match self.read_object(bucket_name, object_name).await {
+                Ok(object) => object,
+                Err(EngineError::ObjectNotFound { .. }) => vec![],
+                Err(e) => return Err(e),
+            };
+
+            let (start, end) = byte_range(offset, data.len() as u64)?;
+            if object.len() < end {
+                object.resize(end, 0);
+            }
+            object[start..end].copy_from_slice(data);
+
+            self.create_object(bucket_name, object_name, &object).await
+        }
+    }
+
+    /// # 追加到 object 的末尾
+    ///
+    /// 返回追加之前 object 的长度，也就是 `data` 在 object 中的起始位置；object 不存在时抛出
+    /// [`ObjectNotFound`](crate::error::EngineError::ObjectNotFound)。与 [`write_object_at`](DataEngine::write_object_at)
+    /// 一样只修改数据
+    ///
+    /// 默认实现读出整个 object 修改后再写回，能够原地追加的后端应当覆盖这个方法
+    fn append_object(
+        &self,
+        bucket_name: &str,
+        object_name: &str,
+        data: &[u8],
+    ) -> impl Future<Output = EngineResult<u64>> + Send
+    where
+        Self: Sync,
+    {
+        async move {
+            let mut object = self.read_object(bucket_name, object_name).await?;
+            let offset = object.len() as u64;
+            object.extend_from_slice(data);
+
+            self.create_object(bucket_name, object_name, &object).await?;
+            Ok(offset)
+        }
+    }
+
+    /// # 将 object 中从 `offset` 开始的 `len` 个字节置为 0
+    ///
+    /// object 的长度不会改变，超出末尾的部分会被忽略；object 不存在时抛出
+    /// [`ObjectNotFound`](crate::error::EngineError::ObjectNotFound)
+    ///
+    /// 支持稀疏文件的后端会释放这部分占用的存储空间，默认实现读出整个 object 修改后再写回
+    fn punch_hole(
+        &self,
+        bucket_name: &str,
+        object_name: &str,
+        offset: u64,
+        len: u64,
+    ) -> impl Future<Output = EngineResult<()>> + Send
+    where
+        Self: Sync,
+    {
+        async move {
+            let mut object = self.read_object(bucket_name, object_name).await?;
+
+            let size = object.len() as u64;
+            if offset >= size || len == 0 {
+                return Ok(());
+            }
+
+            let (start, end) = byte_range(offset, len.min(size - offset))?;
+            object[start..end].fill(0);
+
+            self.create_object(bucket_name, object_name, &object).await
+        }
+    }
+
+    /// # 批量删除同一个 bucket 下的多个 object
+    ///
+    /// 返回的结果与 `object_names` 一一对应，一个 object 删除失败不会影响其他 object
+    ///
+    /// 默认实现逐个调用 [`delete_object`](DataEngine::delete_object)，支持批量删除的后端应当覆盖这个方法
+    fn delete_objects(
+        &self,
+        bucket_name: &str,
+        object_names: &[String],
+    ) -> impl Future<Output = Vec<EngineResult<()>>> + Send
+    where
+        Self: Sync,
+    {
+        async move {
+            let mut results = Vec::with_capacity(object_names.len());
+            for object_name in object_names {
+                results.push(self.delete_object(bucket_name, object_name).await);
+            }
+            results
+        }
+    }
 }
 
 /// 此 trait 定义了 metadata 从何处来，所有的操作，都是幂等的
@@ -111,6 +417,34 @@ pub trait MetaEngine: Sized {
     /// 列出所有的 Bucket 的元数据
     fn list_buckets_meta(&self) -> impl Future<Output = EngineResult<Vec<BucketMeta>>> + Send;
 
+    /// # 分页列出 Bucket 的元数据
+    ///
+    /// 默认实现先通过 [`list_buckets_meta`](MetaEngine::list_buckets_meta) 取出所有元数据，再在内存中分页，
+    /// 能够在存储层完成过滤的后端应当覆盖这个方法
+    fn list_buckets_meta_page(
+        &self,
+        query: &ListBucketsQuery,
+    ) -> impl Future<Output = EngineResult<BucketPage>> + Send
+    where
+        Self: Sync,
+    {
+        async move {
+            let buckets = self.list_buckets_meta().await?;
+            query.paginate(buckets)
+        }
+    }
+
+    /// 逐条产生所有 Bucket 的元数据，不保证顺序
+    ///
+    /// 默认实现基于 [`list_buckets_meta`](MetaEngine::list_buckets_meta)，仍然会一次性读出所有元数据
+    fn stream_buckets_meta(&self) -> MetaStream<'_, BucketMeta> {
+        Box::pin(
+            stream::once(self.list_buckets_meta())
+                .map_ok(|v| stream::iter(v.into_iter().map(Ok)))
+                .try_flatten(),
+        )
+    }
+
     /// 更新一个 object 的 last_update 字段
     fn touch_object(
         &self,
@@ -126,6 +460,16 @@ pub trait MetaEngine: Sized {
         meta: &ObjectMeta,
     ) -> impl Future<Output = EngineResult<()>> + Send;
 
+    /// # 只在已有元数据的 etag 等于 `expected_etag` 时替换它
+    ///
+    /// 比较和写入是原子的，与之并发的条件写入不会插入到两者之间；元数据不存在或者 etag 不同时抛出
+    /// [`EtagMismatch`](crate::error::EngineError::EtagMismatch)，已有的元数据保持不变
+    fn create_object_meta_if(
+        &self,
+        meta: &ObjectMeta,
+        expected_etag: &str,
+    ) -> impl Future<Output = EngineResult<()>> + Send;
+
     /// 获取指定 Object 的元数据
     fn read_object_meta(
         &self,
@@ -140,14 +484,137 @@ pub trait MetaEngine: Sized {
         object_name: &str,
     ) -> impl Future<Output = EngineResult<()>> + Send;
 
+    /// # 批量删除同一个 Bucket 下的多个 Object 元数据
+    ///
+    /// 返回的结果与 `object_names` 一一对应，默认实现逐个调用 [`delete_object_meta`](MetaEngine::delete_object_meta)
+    fn delete_objects_meta(
+        &self,
+        bucket_name: &str,
+        object_names: &[String],
+    ) -> impl Future<Output = Vec<EngineResult<()>>> + Send
+    where
+        Self: Sync,
+    {
+        async move {
+            let mut results = Vec::with_capacity(object_names.len());
+            for object_name in object_names {
+                results.push(self.delete_object_meta(bucket_name, object_name).await);
+            }
+            results
+        }
+    }
+
     /// 列出指定 Bucket 内的所有 Object 元数据
     fn list_objects_meta(
         &self,
         bucket_name: &str,
     ) -> impl Future<Output = EngineResult<Vec<ObjectMeta>>> + Send;
 
+    /// # 分页列出指定 Bucket 内的 Object 元数据
+    ///
+    /// 默认实现先通过 [`list_objects_meta`](MetaEngine::list_objects_meta) 取出所有元数据，再在内存中分页，
+    /// 能够在存储层完成过滤的后端应当覆盖这个方法
+    fn list_objects_meta_page(
+        &self,
+        bucket_name: &str,
+        query: &ListObjectsQuery,
+    ) -> impl Future<Output = EngineResult<ObjectPage>> + Send
+    where
+        Self: Sync,
+    {
+        async move {
+            let objects = self.list_objects_meta(bucket_name).await?;
+            query.paginate(objects)
+        }
+    }
+
+    /// # 逐条产生指定 Bucket 内满足条件的 Object 元数据，不保证顺序
+    ///
+    /// 只应用 `query` 中的前缀、时间和过滤表达式，分页相关的条件会被忽略
+    ///
+    /// 默认实现基于 [`list_objects_meta`](MetaEngine::list_objects_meta)，仍然会一次性读出所有元数据
+    fn stream_objects_meta<'a>(
+        &'a self,
+        bucket_name: &'a str,
+        query: &'a ListObjectsQuery,
+    ) -> MetaStream<'a, ObjectMeta> {
+        Box::pin(
+            stream::once(self.list_objects_meta(bucket_name))
+                .map_ok(|v| stream::iter(v.into_iter().map(Ok)))
+                .try_flatten()
+                .try_filter(move |v| ready(query.matches(v))),
+        )
+    }
+
+    /// # 指定 Bucket 中 object 的个数和总大小
+    ///
+    /// 默认实现基于 [`stream_objects_meta`](MetaEngine::stream_objects_meta)，每次都会读出所有元数据，
+    /// 能够维护计数或者在存储层统计的后端应当覆盖这个方法
+    fn bucket_usage(
+        &self,
+        bucket_name: &str,
+    ) -> impl Future<Output = EngineResult<BucketUsage>> + Send
+    where
+        Self: Sync,
+    {
+        async move {
+            let query = ListObjectsQuery::default();
+            self.stream_objects_meta(bucket_name, &query)
+                .try_fold(BucketUsage::default(), |mut usage, meta| {
+                    usage.add(meta.size);
+                    ready(Ok(usage))
+                })
+                .await
+        }
+    }
+
+    /// # 所有 Bucket 的用量
+    ///
+    /// 默认实现对 [`stream_buckets_meta`](MetaEngine::stream_buckets_meta) 中的每一个 Bucket 调用
+    /// [`bucket_usage`](MetaEngine::bucket_usage)，只有 object 元数据而没有 Bucket 元数据的 Bucket 不会被统计，
+    /// 能够一次统计所有 Bucket 的后端应当覆盖这个方法
+    fn stats(&self) -> impl Future<Output = EngineResult<StorageStats>> + Send
+    where
+        Self: Sync,
+    {
+        async move {
+            let names = self
+                .stream_buckets_meta()
+                .map_ok(|v| v.name)
+                .try_collect()
+                .await?;
+            usage::collect_stats(self, names).await
+        }
+    }
+
     /// 更新一个 object 的 last_update 字段
     fn touch_bucket(&self, bucket_name: &str) -> impl Future<Output = EngineResult<()>> + Send;
+
+    // --- Event Log ---
+
+    /// 追加一条变更事件，返回分配给它的序号，序号严格递增，参见 [`events`]
+    fn append_event(&self, event: &Event) -> impl Future<Output = EngineResult<u64>> + Send;
+
+    /// 按序号升序列出序号大于 `since` 的事件，最多 `limit` 条
+    fn list_events(
+        &self,
+        since: u64,
+        limit: usize,
+    ) -> impl Future<Output = EngineResult<Vec<Event>>> + Send;
+
+    /// 删除序号不大于 `up_to` 的事件
+    fn trim_events(&self, up_to: u64) -> impl Future<Output = EngineResult<()>> + Send;
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        Self {
+            max_connections: 10,
+            min_connections: 0,
+            acquire_timeout: Duration::from_secs(30),
+            idle_timeout: Some(Duration::from_secs(600)),
+        }
+    }
 }
 
 impl ObjectMeta {
@@ -159,11 +626,19 @@ impl ObjectMeta {
 
 impl BucketMeta {
     pub fn new(name: String, user_meta: Value) -> Self {
+        let now = clock::now();
         Self {
             name,
             user_meta,
-            created_at: Utc::now(),
-            updated_at: Utc::now(),
+            data_key: None,
+            policy: None,
+            lifecycle: None,
+            cors: None,
+            inventory: None,
+            max_bytes: None,
+            max_objects: None,
+            created_at: now,
+            updated_at: now,
         }
     }
 
@@ -172,3 +647,13 @@ impl BucketMeta {
         rhs
     }
 }
+
+/// 将 `offset` 开始的 `len` 个字节转换为切片的下标，超出地址空间时返回 [`EngineError::InvalidArgument`]
+fn byte_range(offset: u64, len: u64) -> EngineResult<(usize, usize)> {
+    offset
+        .checked_add(len)
+        .and_then(|end| Some((usize::try_from(offset).ok()?, usize::try_from(end).ok()?)))
+        .ok_or_else(|| {
+            EngineError::InvalidArgument(format!("range {offset}+{len} is out of address space"))
+        })
+}
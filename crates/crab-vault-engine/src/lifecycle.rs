@@ -0,0 +1,151 @@
+//! # 生命周期规则
+//!
+//! 保存在 [`BucketMeta`](crate::BucketMeta) 中，例如"删除 `tmp/` 下 7 天没有修改过的 object"。
+//! 引擎只负责找出过期的 object，删除由调用者完成，这样删除可以和其他写入一样记录在意图日志中。
+//!
+//! object 的年龄从 `updated_at` 开始计算，所以修改元数据同样会推迟它的过期，修改标签则不会。
+//! 没有保存历史版本，所以没有针对非当前版本的规则
+//!
+//! 单个 object 还可以有自己的过期时间 [`ObjectMeta::expires_at`]，它与 bucket 的规则无关，
+//! 过期之后 [`ObjectMeta::check_expiry`] 拒绝读取，直到 object 被删除
+
+use chrono::{DateTime, Duration, Utc};
+use futures::TryStreamExt;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    MetaEngine, ObjectMeta,
+    error::{EngineError, EngineResult},
+    list::ListObjectsQuery,
+    tagging::{self, Tags},
+};
+
+/// 因为 [`ObjectMeta::expires_at`] 而过期的 object 在 [`ExpiredObject::rule`] 中的名称
+pub const EXPIRES_AT_RULE: &str = "expires-at";
+
+/// 一个 bucket 最多的规则数
+pub const MAX_LIFECYCLE_RULES: usize = 100;
+
+/// 一个 bucket 的生命周期规则，任意一条规则满足时 object 过期
+#[derive(Serialize, Deserialize, Default, PartialEq, Clone, Debug)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct LifecycleConfig {
+    pub rules: Vec<LifecycleRule>,
+}
+
+#[derive(Serialize, Deserialize, Default, PartialEq, Clone, Debug)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct LifecycleRule {
+    /// 出现在删除日志中，便于找到是哪一条规则删除了 object
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+
+    /// 只作用于名称以此开头的 object，默认作用于整个 bucket
+    #[serde(default)]
+    pub prefix: String,
+
+    /// 只作用于带有所有这些标签的 object，值必须完全相同，默认不检查标签
+    #[serde(default, skip_serializing_if = "Tags::is_empty")]
+    pub tags: Tags,
+
+    /// 最后一次修改之后经过多少天过期，必须大于 0
+    pub expiration_days: u32,
+}
+
+/// 由 [`scan`] 找到的过期的 object
+#[derive(PartialEq, Clone, Debug)]
+pub struct ExpiredObject {
+    pub object: String,
+
+    /// 使它过期的规则，参见 [`LifecycleRule::describe`] 和 [`EXPIRES_AT_RULE`]
+    pub rule: String,
+}
+
+impl LifecycleConfig {
+    /// 检查规则是否有意义，不合法时返回原因
+    pub fn validate(&self) -> Result<(), String> {
+        if self.rules.is_empty() {
+            return Err("at least one rule is required".into());
+        }
+        if self.rules.len() > MAX_LIFECYCLE_RULES {
+            return Err(format!("at most {MAX_LIFECYCLE_RULES} rules are allowed"));
+        }
+        for (i, rule) in self.rules.iter().enumerate() {
+            if rule.expiration_days == 0 {
+                return Err(format!("rule {i}: `expiration-days` must be positive"));
+            }
+            tagging::validate(&rule.tags).map_err(|e| format!("rule {i}: {e}"))?;
+        }
+        Ok(())
+    }
+
+    /// 在 `now` 时使 `meta` 过期的第一条规则
+    pub fn expiring_rule(&self, meta: &ObjectMeta, now: DateTime<Utc>) -> Option<&LifecycleRule> {
+        self.rules.iter().find(|v| v.expires(meta, now))
+    }
+}
+
+impl LifecycleRule {
+    pub fn expires(&self, meta: &ObjectMeta, now: DateTime<Utc>) -> bool {
+        meta.object_name.starts_with(&self.prefix)
+            && meta.has_tags(&self.tags)
+            && meta.updated_at + Duration::days(self.expiration_days.into()) <= now
+    }
+
+    /// 有 `id` 时就是 `id`，否则是规则的内容
+    pub fn describe(&self) -> String {
+        match &self.id {
+            Some(id) => id.clone(),
+            None => format!("{:?} after {} days", self.prefix, self.expiration_days),
+        }
+    }
+}
+
+impl ObjectMeta {
+    /// 设置了过期时间，并且在 `now` 时已经过期
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at.is_some_and(|v| v <= now)
+    }
+
+    /// 已经过期时返回 [`ObjectExpired`](EngineError::ObjectExpired)
+    pub fn check_expiry(&self, now: DateTime<Utc>) -> EngineResult<()> {
+        match self.is_expired(now) {
+            true => Err(EngineError::ObjectExpired {
+                bucket: self.bucket_name.clone(),
+                object: self.object_name.clone(),
+            }),
+            false => Ok(()),
+        }
+    }
+}
+
+/// ## 找出 `bucket_name` 中在 `now` 时已经过期的 object
+///
+/// `lifecycle` 是这个 bucket 的规则，没有规则时只找出超过了自己的过期时间的 object。
+/// 保留仍然生效的 object 即使过期也不会被找出，参见 [`retention`](crate::retention)
+pub async fn scan<M: MetaEngine + Sync>(
+    meta: &M,
+    bucket_name: &str,
+    lifecycle: Option<&LifecycleConfig>,
+    now: DateTime<Utc>,
+) -> EngineResult<Vec<ExpiredObject>> {
+    let query = ListObjectsQuery::default();
+    meta.stream_objects_meta(bucket_name, &query)
+        .try_filter_map(|v| async move {
+            if v.retention.is_active(now) {
+                return Ok(None);
+            }
+            let rule = match v.is_expired(now) {
+                true => Some(EXPIRES_AT_RULE.to_string()),
+                false => lifecycle
+                    .and_then(|lifecycle| lifecycle.expiring_rule(&v, now))
+                    .map(LifecycleRule::describe),
+            };
+            Ok(rule.map(|rule| ExpiredObject {
+                object: v.object_name,
+                rule,
+            }))
+        })
+        .try_collect()
+        .await
+}
@@ -0,0 +1,348 @@
+use std::{cmp::Ordering, pin::Pin};
+
+use base64::{Engine, prelude::BASE64_URL_SAFE_NO_PAD};
+use chrono::{DateTime, SecondsFormat, Utc};
+use futures::Stream;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    BucketMeta, ObjectMeta,
+    error::{EngineError, EngineResult},
+    filter::MetaFilter,
+    name,
+};
+
+/// 逐条产生元数据的流，参见 [`MetaEngine::stream_objects_meta`](crate::MetaEngine::stream_objects_meta)
+pub type MetaStream<'a, T> = Pin<Box<dyn Stream<Item = EngineResult<T>> + Send + 'a>>;
+
+/// 一页最多返回的条目数，`max-keys` 超过这个值时会被截断
+pub const MAX_KEYS: usize = 1000;
+
+/// ## 分页列出 object 时的查询条件
+///
+/// 语义和 S3 的 `ListObjectsV2` 相同：
+///
+/// - `prefix`：只列出以此开头的 object
+/// - `delimiter`：`prefix` 之后第一次出现 `delimiter` 的 object 会被折叠为一个公共前缀（"文件夹"）
+/// - `max_keys`：一页最多返回的 object 和公共前缀的总数，默认且最多为 [`MAX_KEYS`]
+/// - `continuation_token`：上一页返回的 `next-continuation-token`
+///
+/// 此外还可以按时间过滤，时间使用 RFC 3339 格式，边界本身不包含在内：
+///
+/// - `created_after`、`created_before`：按 `created_at` 过滤
+/// - `updated_after`、`updated_before`：按 `updated_at` 过滤，可以用来增量同步某一时刻之后变化的 object
+///
+/// `query` 是一个过滤表达式，可以按内容类型、大小和用户元数据过滤，参见 [`filter`](crate::filter)
+///
+/// 查询参数同时接受 `kebab-case` 和 `snake_case` 两种写法
+#[derive(Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct ListObjectsQuery {
+    pub prefix: Option<String>,
+    pub delimiter: Option<String>,
+    #[serde(alias = "max_keys")]
+    pub max_keys: Option<usize>,
+    #[serde(alias = "continuation_token")]
+    pub continuation_token: Option<String>,
+    #[serde(alias = "created_after")]
+    pub created_after: Option<DateTime<Utc>>,
+    #[serde(alias = "created_before")]
+    pub created_before: Option<DateTime<Utc>>,
+    #[serde(alias = "updated_after")]
+    pub updated_after: Option<DateTime<Utc>>,
+    #[serde(alias = "updated_before")]
+    pub updated_before: Option<DateTime<Utc>>,
+    pub query: Option<MetaFilter>,
+}
+
+/// 分页列出 object 的一页结果
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub struct ObjectPage {
+    pub objects: Vec<ObjectMeta>,
+    pub common_prefixes: Vec<String>,
+    pub is_truncated: bool,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_continuation_token: Option<String>,
+
+    /// 因为元数据损坏而被跳过的 object，只有 [`OnCorrupt::Skip`] 时才可能非空
+    #[serde(skip)]
+    pub skipped: Vec<String>,
+}
+
+/// ## 分页列出 bucket 时的查询条件
+///
+/// - `prefix`：只列出名称以此开头的 bucket
+/// - `max_buckets`：一页最多返回的 bucket 数，最多为 [`MAX_KEYS`]；不设置时返回所有的 bucket
+/// - `continuation_token`：上一页返回的 `next-continuation-token`，必须与上一页使用同样的 `sort` 和 `order`
+/// - `sort`：排序的字段，参见 [`BucketSort`]
+/// - `order`：`asc` 或者 `desc`
+///
+/// 内部的 bucket 不会出现在结果中，参见 [`name::is_internal_bucket`]。
+/// 查询参数同样接受 `kebab-case` 和 `snake_case` 两种写法
+#[derive(Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct ListBucketsQuery {
+    pub prefix: Option<String>,
+    #[serde(alias = "max_buckets")]
+    pub max_buckets: Option<usize>,
+    #[serde(alias = "continuation_token")]
+    pub continuation_token: Option<String>,
+    pub sort: BucketSort,
+    pub order: SortOrder,
+}
+
+/// 列出 bucket 时排序的字段，值相同的 bucket 再按名称排序
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum BucketSort {
+    #[default]
+    Name,
+    #[serde(alias = "created_at")]
+    CreatedAt,
+    #[serde(alias = "updated_at")]
+    UpdatedAt,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+/// 分页列出 bucket 的一页结果
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BucketPage {
+    pub buckets: Vec<BucketMeta>,
+
+    /// 满足前缀条件的 bucket 的总数，与分页无关
+    pub total: usize,
+    pub is_truncated: bool,
+    pub next_continuation_token: Option<String>,
+
+    /// 因为元数据损坏而被跳过的 bucket，只有 [`OnCorrupt::Skip`] 时才可能非空
+    pub skipped: Vec<String>,
+}
+
+/// 列举时遇到损坏的元数据的处理方式
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OnCorrupt {
+    /// 整个列举失败，返回 [`CorruptMeta`](EngineError::CorruptMeta)
+    Fail,
+
+    /// 跳过损坏的条目，并报告它们的名称
+    #[default]
+    Skip,
+}
+
+impl ListObjectsQuery {
+    #[inline]
+    pub fn prefix(&self) -> &str {
+        self.prefix.as_deref().unwrap_or_default()
+    }
+
+    /// 空的 delimiter 视为没有设置
+    #[inline]
+    pub fn delimiter(&self) -> Option<&str> {
+        self.delimiter.as_deref().filter(|v| !v.is_empty())
+    }
+
+    #[inline]
+    pub fn max_keys(&self) -> usize {
+        self.max_keys.unwrap_or(MAX_KEYS).min(MAX_KEYS)
+    }
+
+    /// 解码 `continuation_token`，得到上一页最后返回的 object 名称或者公共前缀
+    pub fn start_after(&self) -> EngineResult<Option<String>> {
+        let Some(token) = &self.continuation_token else {
+            return Ok(None);
+        };
+
+        BASE64_URL_SAFE_NO_PAD
+            .decode(token)
+            .ok()
+            .and_then(|v| String::from_utf8(v).ok())
+            .map(Some)
+            .ok_or_else(|| {
+                EngineError::InvalidArgument(format!("invalid continuation token `{token}`"))
+            })
+    }
+
+    /// 检查 object 是否满足查询中的前缀、时间和过滤表达式
+    pub fn matches(&self, meta: &ObjectMeta) -> bool {
+        meta.object_name.starts_with(self.prefix())
+            && self.matches_time(meta)
+            && self.query.as_ref().is_none_or(|v| v.matches(meta))
+    }
+
+    /// 检查 object 的时间戳是否满足查询中的时间过滤条件
+    pub fn matches_time(&self, meta: &ObjectMeta) -> bool {
+        self.created_after.is_none_or(|v| meta.created_at > v)
+            && self.created_before.is_none_or(|v| meta.created_at < v)
+            && self.updated_after.is_none_or(|v| meta.updated_at > v)
+            && self.updated_before.is_none_or(|v| meta.updated_at < v)
+    }
+
+    /// 在一个 bucket 中所有的 object 上应用这个查询，`objects` 不需要事先排序
+    pub fn paginate(&self, mut objects: Vec<ObjectMeta>) -> EngineResult<ObjectPage> {
+        let (prefix, delimiter, max_keys) = (self.prefix(), self.delimiter(), self.max_keys());
+        let start_after = self.start_after()?;
+
+        objects.retain(|v| self.matches(v));
+        objects.sort_unstable_by(|a, b| a.object_name.cmp(&b.object_name));
+
+        let mut page = ObjectPage::default();
+        let mut last_key = None;
+
+        for object in objects {
+            let name = object.object_name.as_str();
+
+            if let Some(start_after) = &start_after
+                && (name <= start_after.as_str()
+                    || (delimiter.is_some_and(|v| start_after.ends_with(v))
+                        && name.starts_with(start_after.as_str())))
+            {
+                continue;
+            }
+
+            // 折叠到公共前缀里的 object 不单独返回
+            let common_prefix = delimiter.and_then(|delimiter| {
+                name[prefix.len()..]
+                    .find(delimiter)
+                    .map(|i| name[..prefix.len() + i + delimiter.len()].to_string())
+            });
+
+            if common_prefix.is_some() && page.common_prefixes.last() == common_prefix.as_ref() {
+                continue;
+            }
+
+            if page.objects.len() + page.common_prefixes.len() >= max_keys {
+                page.is_truncated = true;
+                page.next_continuation_token =
+                    last_key.map(|v: String| BASE64_URL_SAFE_NO_PAD.encode(v));
+                break;
+            }
+
+            match common_prefix {
+                Some(common_prefix) => {
+                    last_key = Some(common_prefix.clone());
+                    page.common_prefixes.push(common_prefix);
+                }
+                None => {
+                    last_key = Some(object.object_name.clone());
+                    page.objects.push(object);
+                }
+            }
+        }
+
+        Ok(page)
+    }
+}
+
+impl ListBucketsQuery {
+    #[inline]
+    pub fn prefix(&self) -> &str {
+        self.prefix.as_deref().unwrap_or_default()
+    }
+
+    #[inline]
+    pub fn max_buckets(&self) -> usize {
+        self.max_buckets.map_or(usize::MAX, |v| v.min(MAX_KEYS))
+    }
+
+    /// 检查 bucket 是否满足查询中的前缀条件，内部的 bucket 总是不满足
+    pub fn matches(&self, meta: &BucketMeta) -> bool {
+        meta.name.starts_with(self.prefix()) && !name::is_internal_bucket(&meta.name)
+    }
+
+    /// 按照查询中的 `sort` 和 `order` 比较两个 bucket
+    pub fn compare(&self, lhs: &BucketMeta, rhs: &BucketMeta) -> Ordering {
+        let ordering = match self.sort {
+            BucketSort::Name => Ordering::Equal,
+            BucketSort::CreatedAt => lhs.created_at.cmp(&rhs.created_at),
+            BucketSort::UpdatedAt => lhs.updated_at.cmp(&rhs.updated_at),
+        }
+        .then_with(|| lhs.name.cmp(&rhs.name));
+
+        match self.order {
+            SortOrder::Asc => ordering,
+            SortOrder::Desc => ordering.reverse(),
+        }
+    }
+
+    /// 记录 `meta` 中参与排序的字段，下一页从它之后开始
+    fn continuation_token_of(&self, meta: &BucketMeta) -> String {
+        let time = |v: &DateTime<Utc>| v.to_rfc3339_opts(SecondsFormat::Nanos, true);
+        let cursor = match self.sort {
+            BucketSort::Name => meta.name.clone(),
+            BucketSort::CreatedAt => format!("{}/{}", time(&meta.created_at), meta.name),
+            BucketSort::UpdatedAt => format!("{}/{}", time(&meta.updated_at), meta.name),
+        };
+        BASE64_URL_SAFE_NO_PAD.encode(cursor)
+    }
+
+    /// 解码 `continuation_token`，得到一个只有参与排序的字段的 bucket，结果中的 bucket 都排在它之后
+    pub fn start_after(&self) -> EngineResult<Option<BucketMeta>> {
+        let Some(token) = &self.continuation_token else {
+            return Ok(None);
+        };
+        let invalid =
+            || EngineError::InvalidArgument(format!("invalid continuation token `{token}`"));
+
+        let cursor = BASE64_URL_SAFE_NO_PAD
+            .decode(token)
+            .ok()
+            .and_then(|v| String::from_utf8(v).ok())
+            .ok_or_else(invalid)?;
+
+        // bucket 名称中不能有 `/`，时间中也没有
+        let mut meta = BucketMeta::default();
+        match self.sort {
+            BucketSort::Name => meta.name = cursor,
+            sort => {
+                let (time, name) = cursor.split_once('/').ok_or_else(invalid)?;
+                let time = DateTime::parse_from_rfc3339(time)
+                    .map_err(|_| invalid())?
+                    .to_utc();
+                meta.name = name.to_string();
+                match sort {
+                    BucketSort::CreatedAt => meta.created_at = time,
+                    _ => meta.updated_at = time,
+                }
+            }
+        }
+
+        Ok(Some(meta))
+    }
+
+    /// 在所有的 bucket 上应用这个查询，`buckets` 不需要事先排序
+    pub fn paginate(&self, mut buckets: Vec<BucketMeta>) -> EngineResult<BucketPage> {
+        let start_after = self.start_after()?;
+
+        buckets.retain(|v| self.matches(v));
+        let total = buckets.len();
+
+        buckets.sort_unstable_by(|a, b| self.compare(a, b));
+        if let Some(start_after) = &start_after {
+            buckets.retain(|v| self.compare(v, start_after).is_gt());
+        }
+
+        let is_truncated = buckets.len() > self.max_buckets();
+        buckets.truncate(self.max_buckets());
+
+        Ok(BucketPage {
+            next_continuation_token: buckets
+                .last()
+                .filter(|_| is_truncated)
+                .map(|v| self.continuation_token_of(v)),
+            buckets,
+            total,
+            is_truncated,
+            skipped: vec![],
+        })
+    }
+}
@@ -0,0 +1,308 @@
+use std::{collections::VecDeque, sync::Mutex};
+
+use dashmap::{DashMap, mapref::entry::Entry};
+
+use crate::{
+    error::{EngineError, EngineResult},
+    events::Event,
+    usage::{self, BucketUsage, StorageStats},
+    {BucketMeta, DataEngine, MetaEngine, ObjectMeta},
+};
+
+/// 所有数据都保存在内存中的 [`DataEngine`]，进程退出后数据全部丢失
+///
+/// 主要用于测试和示例，不需要接触磁盘
+#[derive(Default)]
+pub struct MemDataEngine {
+    buckets: DashMap<String, DashMap<String, Vec<u8>>>,
+}
+
+impl DataEngine for MemDataEngine {
+    type Uri = str;
+
+    /// 每次调用都会得到一个新的、空的实例，`uri` 会被忽略
+    fn new<T: AsRef<str>>(_uri: T) -> EngineResult<Self> {
+        Ok(Self::default())
+    }
+
+    async fn create_bucket(&self, bucket_name: &str) -> EngineResult<()> {
+        self.buckets.entry(bucket_name.to_string()).or_default();
+        Ok(())
+    }
+
+    async fn delete_bucket(&self, bucket_name: &str) -> EngineResult<()> {
+        match self
+            .buckets
+            .remove_if(bucket_name, |_, objects| objects.is_empty())
+        {
+            Some(_) => Ok(()),
+            None if self.buckets.contains_key(bucket_name) => Err(EngineError::BucketNotEmpty {
+                bucket: bucket_name.to_string(),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    async fn list_buckets(&self) -> EngineResult<Vec<String>> {
+        Ok(self.buckets.iter().map(|v| v.key().clone()).collect())
+    }
+
+    async fn list_objects(&self, bucket_name: &str) -> EngineResult<Vec<String>> {
+        let bucket = self
+            .buckets
+            .get(bucket_name)
+            .ok_or_else(|| EngineError::BucketNotFound {
+                bucket: bucket_name.to_string(),
+            })?;
+
+        Ok(bucket.iter().map(|v| v.key().clone()).collect())
+    }
+
+    async fn create_object(
+        &self,
+        bucket_name: &str,
+        object_name: &str,
+        data: &[u8],
+    ) -> EngineResult<()> {
+        let bucket = self
+            .buckets
+            .get(bucket_name)
+            .ok_or_else(|| EngineError::BucketNotFound {
+                bucket: bucket_name.to_string(),
+            })?;
+
+        bucket.insert(object_name.to_string(), data.to_vec());
+        Ok(())
+    }
+
+    async fn create_object_if_absent(
+        &self,
+        bucket_name: &str,
+        object_name: &str,
+        data: &[u8],
+    ) -> EngineResult<()> {
+        let bucket = self
+            .buckets
+            .get(bucket_name)
+            .ok_or_else(|| EngineError::BucketNotFound {
+                bucket: bucket_name.to_string(),
+            })?;
+
+        match bucket.entry(object_name.to_string()) {
+            Entry::Vacant(entry) => {
+                entry.insert(data.to_vec());
+                Ok(())
+            }
+            Entry::Occupied(_) => Err(EngineError::ObjectAlreadyExists {
+                bucket: bucket_name.to_string(),
+                object: object_name.to_string(),
+            }),
+        }
+    }
+
+    async fn read_object(&self, bucket_name: &str, object_name: &str) -> EngineResult<Vec<u8>> {
+        self.buckets
+            .get(bucket_name)
+            .and_then(|bucket| bucket.get(object_name).map(|v| v.clone()))
+            .ok_or_else(|| EngineError::ObjectNotFound {
+                bucket: bucket_name.to_string(),
+                object: object_name.to_string(),
+            })
+    }
+
+    async fn delete_object(&self, bucket_name: &str, object_name: &str) -> EngineResult<()> {
+        if let Some(bucket) = self.buckets.get(bucket_name) {
+            bucket.remove(object_name);
+        }
+        Ok(())
+    }
+}
+
+/// 所有元数据都保存在内存中的 [`MetaEngine`]，进程退出后数据全部丢失
+#[derive(Default)]
+pub struct MemMetaEngine {
+    buckets: DashMap<String, BucketMeta>,
+    objects: DashMap<String, DashMap<String, ObjectMeta>>,
+    events: Mutex<MemEvents>,
+}
+
+/// 清理之后仍然需要记住最后一个序号，新的事件才能继续递增
+#[derive(Default)]
+struct MemEvents {
+    last_seq: u64,
+    events: VecDeque<Event>,
+}
+
+impl MetaEngine for MemMetaEngine {
+    type Uri = str;
+
+    /// 每次调用都会得到一个新的、空的实例，`uri` 会被忽略
+    fn new<T: AsRef<str>>(_uri: T) -> EngineResult<Self> {
+        Ok(Self::default())
+    }
+
+    async fn create_bucket_meta(&self, meta: &BucketMeta) -> EngineResult<()> {
+        self.buckets.insert(meta.name.clone(), meta.clone());
+        Ok(())
+    }
+
+    async fn read_bucket_meta(&self, bucket_name: &str) -> EngineResult<BucketMeta> {
+        self.buckets
+            .get(bucket_name)
+            .map(|v| v.clone())
+            .ok_or_else(|| EngineError::BucketMetaNotFound {
+                bucket: bucket_name.to_string(),
+            })
+    }
+
+    async fn delete_bucket_meta(&self, bucket_name: &str) -> EngineResult<()> {
+        if self
+            .objects
+            .get(bucket_name)
+            .is_some_and(|objects| !objects.is_empty())
+        {
+            return Err(EngineError::BucketNotEmpty {
+                bucket: bucket_name.to_string(),
+            });
+        }
+
+        self.objects.remove(bucket_name);
+        self.buckets.remove(bucket_name);
+        Ok(())
+    }
+
+    async fn list_buckets_meta(&self) -> EngineResult<Vec<BucketMeta>> {
+        Ok(self.buckets.iter().map(|v| v.value().clone()).collect())
+    }
+
+    async fn touch_object(&self, bucket_name: &str, object_name: &str) -> EngineResult<()> {
+        let objects = self.objects.get(bucket_name);
+        let mut meta = objects
+            .as_ref()
+            .and_then(|objects| objects.get_mut(object_name))
+            .ok_or_else(|| EngineError::ObjectMetaNotFound {
+                bucket: bucket_name.to_string(),
+                object: object_name.to_string(),
+            })?;
+
+        meta.updated_at = crate::clock::now();
+        Ok(())
+    }
+
+    async fn create_object_meta(&self, meta: &ObjectMeta) -> EngineResult<()> {
+        self.objects
+            .entry(meta.bucket_name.clone())
+            .or_default()
+            .insert(meta.object_name.clone(), meta.clone());
+        Ok(())
+    }
+
+    async fn create_object_meta_if(
+        &self,
+        meta: &ObjectMeta,
+        expected_etag: &str,
+    ) -> EngineResult<()> {
+        // 持有 object 的写锁时比较并替换
+        let objects = self.objects.get(&meta.bucket_name);
+        match objects.as_ref().and_then(|v| v.get_mut(&meta.object_name)) {
+            Some(mut current) if current.etag == expected_etag => {
+                *current = meta.clone();
+                Ok(())
+            }
+            _ => Err(EngineError::EtagMismatch {
+                bucket: meta.bucket_name.clone(),
+                object: meta.object_name.clone(),
+            }),
+        }
+    }
+
+    async fn read_object_meta(
+        &self,
+        bucket_name: &str,
+        object_name: &str,
+    ) -> EngineResult<ObjectMeta> {
+        self.objects
+            .get(bucket_name)
+            .and_then(|objects| objects.get(object_name).map(|v| v.clone()))
+            .ok_or_else(|| EngineError::ObjectMetaNotFound {
+                bucket: bucket_name.to_string(),
+                object: object_name.to_string(),
+            })
+    }
+
+    async fn delete_object_meta(&self, bucket_name: &str, object_name: &str) -> EngineResult<()> {
+        if let Some(objects) = self.objects.get(bucket_name) {
+            objects.remove(object_name);
+        }
+        Ok(())
+    }
+
+    async fn list_objects_meta(&self, bucket_name: &str) -> EngineResult<Vec<ObjectMeta>> {
+        Ok(self
+            .objects
+            .get(bucket_name)
+            .map(|objects| objects.iter().map(|v| v.value().clone()).collect())
+            .unwrap_or_default())
+    }
+
+    async fn bucket_usage(&self, bucket_name: &str) -> EngineResult<BucketUsage> {
+        let mut usage = BucketUsage::default();
+        if let Some(objects) = self.objects.get(bucket_name) {
+            objects.iter().for_each(|v| usage.add(v.size));
+        }
+        Ok(usage)
+    }
+
+    /// 只有 object 元数据的 bucket 同样会被统计
+    async fn stats(&self) -> EngineResult<StorageStats> {
+        let names = self
+            .buckets
+            .iter()
+            .map(|v| v.key().clone())
+            .chain(self.objects.iter().map(|v| v.key().clone()))
+            .collect();
+        usage::collect_stats(self, names).await
+    }
+
+    async fn touch_bucket(&self, bucket_name: &str) -> EngineResult<()> {
+        let mut meta =
+            self.buckets
+                .get_mut(bucket_name)
+                .ok_or_else(|| EngineError::BucketMetaNotFound {
+                    bucket: bucket_name.to_string(),
+                })?;
+
+        meta.updated_at = crate::clock::now();
+        Ok(())
+    }
+
+    async fn append_event(&self, event: &Event) -> EngineResult<u64> {
+        let mut log = self.events.lock().unwrap_or_else(|e| e.into_inner());
+        log.last_seq += 1;
+        let seq = log.last_seq;
+        log.events.push_back(Event {
+            seq,
+            ..event.clone()
+        });
+        Ok(seq)
+    }
+
+    async fn list_events(&self, since: u64, limit: usize) -> EngineResult<Vec<Event>> {
+        let log = self.events.lock().unwrap_or_else(|e| e.into_inner());
+        Ok(log
+            .events
+            .iter()
+            .filter(|v| v.seq > since)
+            .take(limit)
+            .cloned()
+            .collect())
+    }
+
+    async fn trim_events(&self, up_to: u64) -> EngineResult<()> {
+        let mut log = self.events.lock().unwrap_or_else(|e| e.into_inner());
+        while log.events.front().is_some_and(|v| v.seq <= up_to) {
+            log.events.pop_front();
+        }
+        Ok(())
+    }
+}
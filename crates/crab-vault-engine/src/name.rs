@@ -0,0 +1,238 @@
+//! # bucket 和 object 的名称
+//!
+//! 名称会成为本地路径的一部分，所以在使用之前必须检查，否则 `../../etc/passwd` 这样的名称会逃出数据目录。
+//! 规则对所有后端都相同，同一个名称在换一个后端之后仍然合法：
+//!
+//! - 不能为空，不能超过长度限制，不能包含控制字符和 `\`
+//! - object 名称按照 `/` 分段，除了表示目录标记的末尾的 `/` 以外，不能有空的段，也不能有 `.` 和 `..`
+//! - 以 [`RESERVED_PREFIX`] 开头的段由本地目录的结构使用，参见 [`layout`](crate::layout)
+//! - bucket 名称是一个段，此外不能以 `.` 开头
+//!
+//! 名为 [`internal_bucket`] 的 bucket 保留给服务端自己使用，例如保存清单、审计导出和分段上传的中间状态，
+//! 客户端不能创建、访问这个 bucket，列出 bucket 时也不会看到它。后端使用 [`check_bucket_name`]，
+//! 允许这个 bucket，客户端的请求使用 [`validate_bucket_name`]
+//!
+//! 客户端的请求还可以使用更严格的 [`NamingRules::S3`]，参见 [`set_rules`]
+
+use std::{net::Ipv4Addr, sync::OnceLock};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{EngineError, EngineResult};
+
+/// bucket 名称的最大字节数
+pub const MAX_BUCKET_NAME_LEN: usize = 128;
+
+/// object 名称的最大字节数
+pub const MAX_OBJECT_NAME_LEN: usize = 1024;
+
+/// 保留给本地目录结构使用的前缀
+pub const RESERVED_PREFIX: &str = ".crab-vault";
+
+/// 默认的内部 bucket 的名称，客户端的 bucket 不能以 `.` 开头，所以不会与它冲突
+pub const DEFAULT_INTERNAL_BUCKET: &str = ".crab-vault";
+
+/// [`NamingRules::S3`] 下 bucket 名称的长度范围
+pub const S3_BUCKET_NAME_LEN: std::ops::RangeInclusive<usize> = 3..=63;
+
+/// S3 建议不要在 object 名称中使用的字符
+const S3_AVOIDED_CHARS: &str = "\\{}^%`[]\"<>~#|";
+
+static INTERNAL_BUCKET: OnceLock<String> = OnceLock::new();
+
+static RULES: OnceLock<NamingRules> = OnceLock::new();
+
+/// 客户端请求中的名称需要满足的规则，后端始终只使用 [`Relaxed`](NamingRules::Relaxed)
+#[derive(Deserialize, Serialize, Clone, Copy, Default, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum NamingRules {
+    /// 只有模块文档中列出的规则，排除会逃出数据目录或者与目录结构冲突的名称
+    #[default]
+    Relaxed,
+
+    /// 在 `Relaxed` 之上遵循 S3 的规则：bucket 名称与 DNS 兼容，只能由 3 到 63 个小写字母、数字、`.` 和 `-` 组成，
+    /// 首尾是字母或数字，不能有相邻的 `.`，不能形如 ip 地址，不能使用 S3 保留的前缀和后缀；
+    /// object 名称不能包含 S3 建议避免的字符
+    S3,
+}
+
+impl NamingRules {
+    /// 按照这套规则检查客户端请求中的 bucket 名称，参见 [`validate_bucket_name`]
+    pub fn validate_bucket_name(self, bucket: &str) -> EngineResult<()> {
+        if is_internal_bucket(bucket) {
+            return Err(EngineError::InvalidBucketName {
+                bucket: bucket.to_string(),
+                reason: "the bucket is reserved for internal use",
+            });
+        }
+        check_shape(bucket)?;
+
+        match self {
+            NamingRules::Relaxed => Ok(()),
+            NamingRules::S3 => match check_s3_bucket(bucket) {
+                Some(reason) => Err(EngineError::InvalidBucketName {
+                    bucket: bucket.to_string(),
+                    reason,
+                }),
+                None => Ok(()),
+            },
+        }
+    }
+
+    /// 按照这套规则检查客户端请求中的 object 名称，参见 [`validate_object_name`]
+    pub fn validate_object_name(self, object: &str) -> EngineResult<()> {
+        check_object_name(object)?;
+
+        match self {
+            NamingRules::S3 if object.contains(|c| S3_AVOIDED_CHARS.contains(c)) => {
+                Err(EngineError::InvalidObjectName {
+                    object: object.to_string(),
+                    reason: "S3 recommends avoiding these characters: \\ { } ^ % ` [ ] \" < > ~ # |",
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// 设置客户端请求中的名称需要满足的规则，只在第一次调用 [`rules`] 之前有效
+///
+/// 返回是否设置成功，已经设置过或者已经使用了默认的规则时返回 `false`
+pub fn set_rules(rules: NamingRules) -> bool {
+    RULES.set(rules).is_ok()
+}
+
+/// 客户端请求中的名称需要满足的规则，没有设置过时是 [`NamingRules::Relaxed`]
+pub fn rules() -> NamingRules {
+    *RULES.get_or_init(NamingRules::default)
+}
+
+/// 把内部 bucket 换成 `bucket`，只在第一次调用 [`internal_bucket`] 之前有效
+///
+/// 除了 [`DEFAULT_INTERNAL_BUCKET`] 以外，`bucket` 必须是合法的 bucket 名称。
+/// 返回是否替换成功，已经替换过或者已经使用了默认的名称时返回 `false`
+pub fn reserve(bucket: &str) -> EngineResult<bool> {
+    if bucket != DEFAULT_INTERNAL_BUCKET {
+        check_shape(bucket)?;
+    }
+    Ok(INTERNAL_BUCKET.set(bucket.to_string()).is_ok())
+}
+
+/// 保留给服务端使用的 bucket，没有替换过时是 [`DEFAULT_INTERNAL_BUCKET`]
+pub fn internal_bucket() -> &'static str {
+    INTERNAL_BUCKET.get_or_init(|| DEFAULT_INTERNAL_BUCKET.to_string())
+}
+
+pub fn is_internal_bucket(bucket: &str) -> bool {
+    bucket == internal_bucket()
+}
+
+/// 按照 [`rules`] 检查客户端请求中的 bucket 名称，不合法或者是 [`internal_bucket`] 时返回
+/// [`InvalidBucketName`](EngineError::InvalidBucketName)
+pub fn validate_bucket_name(bucket: &str) -> EngineResult<()> {
+    rules().validate_bucket_name(bucket)
+}
+
+/// 检查后端收到的 bucket 名称，与 [`validate_bucket_name`] 相同，但是允许 [`internal_bucket`]
+pub fn check_bucket_name(bucket: &str) -> EngineResult<()> {
+    match is_internal_bucket(bucket) {
+        true => Ok(()),
+        false => check_shape(bucket),
+    }
+}
+
+fn check_shape(bucket: &str) -> EngineResult<()> {
+    let invalid = |reason| {
+        Err(EngineError::InvalidBucketName {
+            bucket: bucket.to_string(),
+            reason,
+        })
+    };
+
+    if bucket.len() > MAX_BUCKET_NAME_LEN {
+        return invalid("the name is too long");
+    }
+    if bucket.starts_with('.') {
+        return invalid("the name must not start with `.`");
+    }
+    if bucket.contains('/') {
+        return invalid("the name must not contain `/`");
+    }
+    match check_segment(bucket) {
+        Some(reason) => invalid(reason),
+        None => Ok(()),
+    }
+}
+
+/// 按照 [`rules`] 检查客户端请求中的 object 名称，不合法时返回
+/// [`InvalidObjectName`](EngineError::InvalidObjectName)
+pub fn validate_object_name(object: &str) -> EngineResult<()> {
+    rules().validate_object_name(object)
+}
+
+/// 检查后端收到的 object 名称，只使用 [`NamingRules::Relaxed`]
+pub fn check_object_name(object: &str) -> EngineResult<()> {
+    let invalid = |reason| {
+        Err(EngineError::InvalidObjectName {
+            object: object.to_string(),
+            reason,
+        })
+    };
+
+    if object.len() > MAX_OBJECT_NAME_LEN {
+        return invalid("the name is too long");
+    }
+
+    // 以 `/` 结尾的是目录标记，只有最后一段可以为空
+    let segments = object.strip_suffix('/').unwrap_or(object);
+    for segment in segments.split('/') {
+        if let Some(reason) = check_segment(segment) {
+            return invalid(reason);
+        }
+    }
+    Ok(())
+}
+
+/// [`NamingRules::S3`] 额外的 bucket 名称规则，合法时返回 [`None`]
+fn check_s3_bucket(bucket: &str) -> Option<&'static str> {
+    let alphanumeric =
+        |c: Option<u8>| c.is_some_and(|c| c.is_ascii_lowercase() || c.is_ascii_digit());
+
+    if !S3_BUCKET_NAME_LEN.contains(&bucket.len()) {
+        Some("the name must be between 3 and 63 characters long")
+    } else if !bucket
+        .bytes()
+        .all(|c| alphanumeric(Some(c)) || c == b'.' || c == b'-')
+    {
+        Some("only lowercase letters, digits, `.` and `-` are allowed")
+    } else if !alphanumeric(bucket.bytes().next()) || !alphanumeric(bucket.bytes().last()) {
+        Some("the name must begin and end with a letter or digit")
+    } else if bucket.contains("..") {
+        Some("adjacent periods are not allowed")
+    } else if bucket.parse::<Ipv4Addr>().is_ok() {
+        Some("the name must not be formatted as an IP address")
+    } else if bucket.starts_with("xn--") || bucket.starts_with("sthree-") {
+        Some("the prefixes `xn--` and `sthree-` are reserved")
+    } else if bucket.ends_with("-s3alias") || bucket.ends_with("--ol-s3") {
+        Some("the suffixes `-s3alias` and `--ol-s3` are reserved")
+    } else {
+        None
+    }
+}
+
+/// 名称中的一段，合法时返回 [`None`]
+fn check_segment(segment: &str) -> Option<&'static str> {
+    if segment.is_empty() {
+        Some("empty segments are not allowed")
+    } else if segment == "." || segment == ".." {
+        Some("`.` and `..` segments are not allowed")
+    } else if segment.chars().any(char::is_control) {
+        Some("control characters are not allowed")
+    } else if segment.contains('\\') {
+        Some("`\\` is not allowed")
+    } else if segment.starts_with(RESERVED_PREFIX) {
+        Some("segments starting with `.crab-vault` are reserved")
+    } else {
+        None
+    }
+}
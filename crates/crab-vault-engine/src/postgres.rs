@@ -0,0 +1,644 @@
+use chrono::{DateTime, Utc};
+use futures::{StreamExt, TryStreamExt, stream};
+use sqlx::{
+    PgPool, Postgres, Row,
+    migrate::Migrator,
+    postgres::{PgArguments, PgPoolOptions, PgRow},
+    query::Query,
+    types::Json,
+};
+use tokio::sync::OnceCell;
+
+use crate::{
+    BucketMeta, MetaEngine, ObjectMeta, PoolConfig, clock,
+    compression::Compression,
+    crypto::WrappedKey,
+    error::{EngineError, EngineResult},
+    events::{Event, EventOp},
+    filter::Condition,
+    inventory::InventoryConfig,
+    lifecycle::LifecycleConfig,
+    list::{BucketPage, ListBucketsQuery, ListObjectsQuery, MetaStream, ObjectPage},
+    retention::ObjectRetention,
+    tagging::Tags,
+    usage::{BucketStats, BucketUsage, StorageStats},
+};
+
+/// 编译期嵌入的迁移脚本，第一次访问数据库时自动执行
+static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
+/// 追加事件时持有的事务级咨询锁，使事件按照序号的顺序提交，读者不会先看到较大的序号
+const EVENTS_LOCK: i64 = 0x6372_6162_6576_7473;
+
+pub struct PgMetaEngine {
+    pool: PgPool,
+    migrated: OnceCell<()>,
+}
+
+impl PgMetaEngine {
+    /// 使用给定的连接池配置创建一个 [`PgMetaEngine`]
+    ///
+    /// 连接是惰性建立的，所以这里不会访问数据库，迁移也会推迟到第一次操作时执行
+    pub fn with_pool_config(url: &str, config: &PoolConfig) -> EngineResult<Self> {
+        let mut options = PgPoolOptions::new()
+            .max_connections(config.max_connections)
+            .min_connections(config.min_connections)
+            .acquire_timeout(config.acquire_timeout);
+
+        if let Some(idle_timeout) = config.idle_timeout {
+            options = options.idle_timeout(idle_timeout);
+        }
+
+        let pool = options.connect_lazy(url)?;
+
+        Ok(Self {
+            pool,
+            migrated: OnceCell::new(),
+        })
+    }
+
+    /// 获取连接池，保证在此之前迁移已经执行过
+    async fn pool(&self) -> EngineResult<&PgPool> {
+        self.migrated
+            .get_or_try_init(|| async {
+                MIGRATOR
+                    .run(&self.pool)
+                    .await
+                    .map_err(|e| EngineError::BackendError(e.to_string()))
+            })
+            .await?;
+
+        Ok(&self.pool)
+    }
+}
+
+fn bucket_meta_from_row(row: PgRow) -> EngineResult<BucketMeta> {
+    BucketMeta::builder()
+        .name(row.try_get::<String, _>("name")?)
+        .user_meta(row.try_get("user_meta")?)
+        .data_key(
+            row.try_get::<Option<Json<WrappedKey>>, _>("data_key")?
+                .map(|v| v.0),
+        )
+        .policy(row.try_get("policy")?)
+        .lifecycle(
+            row.try_get::<Option<Json<LifecycleConfig>>, _>("lifecycle")?
+                .map(|v| v.0),
+        )
+        .cors(row.try_get("cors")?)
+        .inventory(
+            row.try_get::<Option<Json<InventoryConfig>>, _>("inventory")?
+                .map(|v| v.0),
+        )
+        .max_bytes(row.try_get::<Option<i64>, _>("max_bytes")?.map(|v| v as u64))
+        .max_objects(row.try_get::<Option<i64>, _>("max_objects")?.map(|v| v as u64))
+        .created_at(row.try_get("created_at")?)
+        .updated_at(row.try_get("updated_at")?)
+        .build()
+}
+
+fn object_meta_from_row(row: PgRow) -> EngineResult<ObjectMeta> {
+    ObjectMeta::builder()
+        .object_name(row.try_get::<String, _>("object_name")?)
+        .bucket_name(row.try_get::<String, _>("bucket_name")?)
+        .size(row.try_get::<i64, _>("size")? as u64)
+        .content_type(row.try_get::<String, _>("content_type")?)
+        .etag(row.try_get::<String, _>("etag")?)
+        .user_meta(row.try_get("user_meta")?)
+        .created_at(row.try_get("created_at")?)
+        .updated_at(row.try_get("updated_at")?)
+        .expires_at(row.try_get("expires_at")?)
+        .compression(
+            row.try_get::<Option<Json<Compression>>, _>("compression")?
+                .map(|v| v.0),
+        )
+        .tags(
+            row.try_get::<Option<Json<Tags>>, _>("tags")?
+                .map(|v| v.0)
+                .unwrap_or_default(),
+        )
+        .retention(
+            row.try_get::<Option<Json<ObjectRetention>>, _>("retention")?
+                .map(|v| v.0)
+                .unwrap_or_default(),
+        )
+        .build()
+}
+
+fn event_from_row(row: PgRow) -> EngineResult<Event> {
+    let op = row.try_get::<String, _>("op")?;
+    Ok(Event {
+        seq: row.try_get::<i64, _>("seq")? as u64,
+        op: EventOp::from_name(&op)
+            .ok_or_else(|| EngineError::BackendError(format!("unknown event op `{op}`")))?,
+        bucket: row.try_get("bucket")?,
+        object: row.try_get("object")?,
+        etag: row.try_get("etag")?,
+        time: row.try_get("time")?,
+    })
+}
+
+/// 列出 object 的查询
+///
+/// 过滤表达式的条件按字段和运算符分组，作为数组参数与 `ALL` 比较，空数组总是满足。
+/// 这样无论表达式有多少条件，SQL 本身都是固定的
+fn select_objects<'q>(
+    bucket_name: &'q str,
+    query: &'q ListObjectsQuery,
+    start_after: Option<String>,
+) -> Query<'q, Postgres, PgArguments> {
+    let mut content_types = vec![];
+    let mut content_type_prefixes = vec![];
+    // 按 `Comparison` 的顺序分组：`=`、`<`、`<=`、`>`、`>=`
+    let mut sizes: [Vec<i64>; 5] = Default::default();
+    let mut updated_at: [Vec<DateTime<Utc>>; 5] = Default::default();
+    let (mut meta_keys, mut meta_values) = (vec![], vec![]);
+
+    for condition in query.query.iter().flat_map(|v| &v.conditions) {
+        match condition {
+            Condition::ContentType {
+                value,
+                prefix: false,
+            } => content_types.push(value.as_str()),
+            Condition::ContentType {
+                value,
+                prefix: true,
+            } => content_type_prefixes.push(value.as_str()),
+            Condition::Size(cmp, size) => {
+                sizes[*cmp as usize].push(i64::try_from(*size).unwrap_or(i64::MAX))
+            }
+            Condition::UpdatedAt(cmp, time) => updated_at[*cmp as usize].push(*time),
+            Condition::UserMeta { key, value } => {
+                meta_keys.push(key.as_str());
+                meta_values.push(value.as_str());
+            }
+        }
+    }
+
+    let [size_eq, size_lt, size_le, size_gt, size_ge] = sizes;
+    let [updated_eq, updated_lt, updated_le, updated_gt, updated_ge] = updated_at;
+
+    sqlx::query(
+        r#"SELECT * FROM object_meta
+        WHERE bucket_name = $1
+            AND left(object_name, length($2)) = $2
+            AND ($3::TEXT IS NULL OR object_name COLLATE "C" > $3)
+            AND ($4::TIMESTAMPTZ IS NULL OR created_at > $4)
+            AND ($5::TIMESTAMPTZ IS NULL OR created_at < $5)
+            AND ($6::TIMESTAMPTZ IS NULL OR updated_at > $6)
+            AND ($7::TIMESTAMPTZ IS NULL OR updated_at < $7)
+            AND content_type = ALL($8::TEXT[])
+            AND NOT EXISTS (
+                SELECT 1 FROM unnest($9::TEXT[]) AS p(prefix)
+                WHERE left(content_type, length(p.prefix)) <> p.prefix
+            )
+            AND size = ALL($10::BIGINT[]) AND size < ALL($11::BIGINT[]) AND size <= ALL($12::BIGINT[])
+            AND size > ALL($13::BIGINT[]) AND size >= ALL($14::BIGINT[])
+            AND updated_at = ALL($15::TIMESTAMPTZ[]) AND updated_at < ALL($16::TIMESTAMPTZ[])
+            AND updated_at <= ALL($17::TIMESTAMPTZ[]) AND updated_at > ALL($18::TIMESTAMPTZ[])
+            AND updated_at >= ALL($19::TIMESTAMPTZ[])
+            AND NOT EXISTS (
+                SELECT 1 FROM unnest($20::TEXT[], $21::TEXT[]) AS m(key, value)
+                WHERE NOT coalesce(
+                    jsonb_typeof(user_meta -> m.key) IN ('string', 'number', 'boolean')
+                        AND user_meta ->> m.key = m.value,
+                    FALSE
+                )
+            )
+        ORDER BY object_name COLLATE "C""#,
+    )
+    .bind(bucket_name)
+    .bind(query.prefix())
+    .bind(start_after)
+    .bind(query.created_after)
+    .bind(query.created_before)
+    .bind(query.updated_after)
+    .bind(query.updated_before)
+    .bind(content_types)
+    .bind(content_type_prefixes)
+    .bind(size_eq)
+    .bind(size_lt)
+    .bind(size_le)
+    .bind(size_gt)
+    .bind(size_ge)
+    .bind(updated_eq)
+    .bind(updated_lt)
+    .bind(updated_le)
+    .bind(updated_gt)
+    .bind(updated_ge)
+    .bind(meta_keys)
+    .bind(meta_values)
+}
+
+impl MetaEngine for PgMetaEngine {
+    type Uri = str;
+
+    fn new<T: AsRef<str>>(url: T) -> EngineResult<Self> {
+        Self::with_pool_config(url.as_ref(), &PoolConfig::default())
+    }
+
+    async fn create_bucket_meta(&self, meta: &BucketMeta) -> EngineResult<()> {
+        sqlx::query(
+            "INSERT INTO bucket_meta \
+                (name, user_meta, data_key, policy, lifecycle, cors, inventory, max_bytes, max_objects, created_at, updated_at) \
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11) \
+             ON CONFLICT (name) DO UPDATE SET \
+                user_meta = EXCLUDED.user_meta, \
+                data_key = EXCLUDED.data_key, \
+                policy = EXCLUDED.policy, \
+                lifecycle = EXCLUDED.lifecycle, \
+                cors = EXCLUDED.cors, \
+                inventory = EXCLUDED.inventory, \
+                max_bytes = EXCLUDED.max_bytes, \
+                max_objects = EXCLUDED.max_objects, \
+                created_at = EXCLUDED.created_at, \
+                updated_at = EXCLUDED.updated_at",
+        )
+        .bind(&meta.name)
+        .bind(&meta.user_meta)
+        .bind(meta.data_key.as_ref().map(Json))
+        .bind(&meta.policy)
+        .bind(meta.lifecycle.as_ref().map(Json))
+        .bind(&meta.cors)
+        .bind(meta.inventory.as_ref().map(Json))
+        .bind(meta.max_bytes.map(|v| v as i64))
+        .bind(meta.max_objects.map(|v| v as i64))
+        .bind(meta.created_at)
+        .bind(meta.updated_at)
+        .execute(self.pool().await?)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn read_bucket_meta(&self, bucket_name: &str) -> EngineResult<BucketMeta> {
+        sqlx::query("SELECT * FROM bucket_meta WHERE name = $1")
+            .bind(bucket_name)
+            .fetch_optional(self.pool().await?)
+            .await?
+            .map(bucket_meta_from_row)
+            .unwrap_or_else(|| {
+                Err(EngineError::BucketMetaNotFound {
+                    bucket: bucket_name.to_string(),
+                })
+            })
+    }
+
+    async fn delete_bucket_meta(&self, bucket_name: &str) -> EngineResult<()> {
+        let pool = self.pool().await?;
+
+        // 与文件系统的实现保持一致，bucket 中仍有 object 时拒绝删除
+        let has_objects: bool =
+            sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM object_meta WHERE bucket_name = $1)")
+                .bind(bucket_name)
+                .fetch_one(pool)
+                .await?;
+
+        if has_objects {
+            return Err(EngineError::BucketNotEmpty {
+                bucket: bucket_name.to_string(),
+            });
+        }
+
+        sqlx::query("DELETE FROM bucket_meta WHERE name = $1")
+            .bind(bucket_name)
+            .execute(pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn list_buckets_meta(&self) -> EngineResult<Vec<BucketMeta>> {
+        sqlx::query("SELECT * FROM bucket_meta ORDER BY name")
+            .fetch_all(self.pool().await?)
+            .await?
+            .into_iter()
+            .map(bucket_meta_from_row)
+            .collect()
+    }
+
+    /// 前缀过滤交给数据库完成，排序和分页仍然由 [`ListBucketsQuery::paginate`] 完成
+    async fn list_buckets_meta_page(&self, query: &ListBucketsQuery) -> EngineResult<BucketPage> {
+        let buckets = sqlx::query("SELECT * FROM bucket_meta WHERE left(name, length($1)) = $1")
+            .bind(query.prefix())
+            .fetch_all(self.pool().await?)
+            .await?
+            .into_iter()
+            .map(bucket_meta_from_row)
+            .collect::<EngineResult<_>>()?;
+
+        query.paginate(buckets)
+    }
+
+    fn stream_buckets_meta(&self) -> MetaStream<'_, BucketMeta> {
+        Box::pin(
+            stream::once(self.pool())
+                .map_ok(|pool| {
+                    sqlx::query("SELECT * FROM bucket_meta ORDER BY name")
+                        .fetch(pool)
+                        .map(|row| bucket_meta_from_row(row?))
+                })
+                .try_flatten(),
+        )
+    }
+
+    async fn touch_object(&self, bucket_name: &str, object_name: &str) -> EngineResult<()> {
+        let affected = sqlx::query(
+            "UPDATE object_meta SET updated_at = $3 WHERE bucket_name = $1 AND object_name = $2",
+        )
+        .bind(bucket_name)
+        .bind(object_name)
+        .bind(clock::now())
+        .execute(self.pool().await?)
+        .await?
+        .rows_affected();
+
+        if affected == 0 {
+            return Err(EngineError::ObjectMetaNotFound {
+                bucket: bucket_name.to_string(),
+                object: object_name.to_string(),
+            });
+        }
+
+        Ok(())
+    }
+
+    async fn create_object_meta(&self, meta: &ObjectMeta) -> EngineResult<()> {
+        sqlx::query(
+            "INSERT INTO object_meta \
+                (bucket_name, object_name, size, content_type, etag, user_meta, created_at, updated_at, expires_at, compression, tags, retention) \
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12) \
+             ON CONFLICT (bucket_name, object_name) DO UPDATE SET \
+                size = EXCLUDED.size, \
+                content_type = EXCLUDED.content_type, \
+                etag = EXCLUDED.etag, \
+                user_meta = EXCLUDED.user_meta, \
+                created_at = EXCLUDED.created_at, \
+                updated_at = EXCLUDED.updated_at, \
+                expires_at = EXCLUDED.expires_at, \
+                compression = EXCLUDED.compression, \
+                tags = EXCLUDED.tags, \
+                retention = EXCLUDED.retention",
+        )
+        .bind(&meta.bucket_name)
+        .bind(&meta.object_name)
+        .bind(meta.size as i64)
+        .bind(&meta.content_type)
+        .bind(&meta.etag)
+        .bind(&meta.user_meta)
+        .bind(meta.created_at)
+        .bind(meta.updated_at)
+        .bind(meta.expires_at)
+        .bind(meta.compression.as_ref().map(Json))
+        .bind((!meta.tags.is_empty()).then_some(Json(&meta.tags)))
+        .bind((!meta.retention.is_empty()).then_some(Json(&meta.retention)))
+        .execute(self.pool().await?)
+        .await?;
+
+        Ok(())
+    }
+
+    /// 由数据库在同一条 `UPDATE` 中比较和写入，多个实例共用一个数据库时同样是原子的
+    async fn create_object_meta_if(&self, meta: &ObjectMeta, expected_etag: &str) -> EngineResult<()> {
+        let result = sqlx::query(
+            "UPDATE object_meta SET \
+                size = $3, \
+                content_type = $4, \
+                etag = $5, \
+                user_meta = $6, \
+                created_at = $7, \
+                updated_at = $8, \
+                expires_at = $9, \
+                compression = $10, \
+                tags = $11, \
+                retention = $12 \
+             WHERE bucket_name = $1 AND object_name = $2 AND etag = $13",
+        )
+        .bind(&meta.bucket_name)
+        .bind(&meta.object_name)
+        .bind(meta.size as i64)
+        .bind(&meta.content_type)
+        .bind(&meta.etag)
+        .bind(&meta.user_meta)
+        .bind(meta.created_at)
+        .bind(meta.updated_at)
+        .bind(meta.expires_at)
+        .bind(meta.compression.as_ref().map(Json))
+        .bind((!meta.tags.is_empty()).then_some(Json(&meta.tags)))
+        .bind((!meta.retention.is_empty()).then_some(Json(&meta.retention)))
+        .bind(expected_etag)
+        .execute(self.pool().await?)
+        .await?;
+
+        match result.rows_affected() {
+            0 => Err(EngineError::EtagMismatch {
+                bucket: meta.bucket_name.clone(),
+                object: meta.object_name.clone(),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    async fn read_object_meta(
+        &self,
+        bucket_name: &str,
+        object_name: &str,
+    ) -> EngineResult<ObjectMeta> {
+        sqlx::query("SELECT * FROM object_meta WHERE bucket_name = $1 AND object_name = $2")
+            .bind(bucket_name)
+            .bind(object_name)
+            .fetch_optional(self.pool().await?)
+            .await?
+            .map(object_meta_from_row)
+            .unwrap_or_else(|| {
+                Err(EngineError::ObjectMetaNotFound {
+                    bucket: bucket_name.to_string(),
+                    object: object_name.to_string(),
+                })
+            })
+    }
+
+    async fn delete_object_meta(&self, bucket_name: &str, object_name: &str) -> EngineResult<()> {
+        sqlx::query("DELETE FROM object_meta WHERE bucket_name = $1 AND object_name = $2")
+            .bind(bucket_name)
+            .bind(object_name)
+            .execute(self.pool().await?)
+            .await?;
+
+        Ok(())
+    }
+
+    /// 一条语句删除所有元数据，语句失败时所有 object 都视为删除失败
+    async fn delete_objects_meta(
+        &self,
+        bucket_name: &str,
+        object_names: &[String],
+    ) -> Vec<EngineResult<()>> {
+        let result = async {
+            sqlx::query(
+                "DELETE FROM object_meta WHERE bucket_name = $1 AND object_name = ANY($2)",
+            )
+            .bind(bucket_name)
+            .bind(object_names)
+            .execute(self.pool().await?)
+            .await?;
+
+            EngineResult::Ok(())
+        }
+        .await;
+
+        match result {
+            Ok(()) => object_names.iter().map(|_| Ok(())).collect(),
+            Err(e) => e.fan_out(object_names.len()),
+        }
+    }
+
+    async fn list_objects_meta(&self, bucket_name: &str) -> EngineResult<Vec<ObjectMeta>> {
+        sqlx::query("SELECT * FROM object_meta WHERE bucket_name = $1 ORDER BY object_name")
+            .bind(bucket_name)
+            .fetch_all(self.pool().await?)
+            .await?
+            .into_iter()
+            .map(object_meta_from_row)
+            .collect()
+    }
+
+    /// 由数据库统计，不需要读出元数据本身
+    async fn bucket_usage(&self, bucket_name: &str) -> EngineResult<BucketUsage> {
+        let row = sqlx::query(
+            "SELECT count(*) AS object_count, coalesce(sum(size), 0)::BIGINT AS total_bytes \
+             FROM object_meta WHERE bucket_name = $1",
+        )
+        .bind(bucket_name)
+        .fetch_one(self.pool().await?)
+        .await?;
+
+        Ok(BucketUsage {
+            object_count: row.try_get::<i64, _>("object_count")? as u64,
+            total_bytes: row.try_get::<i64, _>("total_bytes")? as u64,
+        })
+    }
+
+    /// 一条语句统计所有 bucket，只有 object 元数据的 bucket 同样会被统计
+    async fn stats(&self) -> EngineResult<StorageStats> {
+        let rows = sqlx::query(
+            "SELECT coalesce(b.name, o.bucket_name) AS name, \
+                coalesce(o.object_count, 0) AS object_count, \
+                coalesce(o.total_bytes, 0) AS total_bytes \
+             FROM bucket_meta b FULL OUTER JOIN ( \
+                SELECT bucket_name, count(*) AS object_count, sum(size)::BIGINT AS total_bytes \
+                FROM object_meta GROUP BY bucket_name \
+             ) o ON b.name = o.bucket_name",
+        )
+        .fetch_all(self.pool().await?)
+        .await?;
+
+        let buckets = rows
+            .into_iter()
+            .map(|row| {
+                EngineResult::Ok(BucketStats {
+                    name: row.try_get("name")?,
+                    usage: BucketUsage {
+                        object_count: row.try_get::<i64, _>("object_count")? as u64,
+                        total_bytes: row.try_get::<i64, _>("total_bytes")? as u64,
+                    },
+                })
+            })
+            .collect::<EngineResult<_>>()?;
+
+        Ok(StorageStats::new(buckets))
+    }
+
+    /// 前缀、起始位置、时间和过滤表达式交给数据库完成，使用 `"C"` 排序规则保证与 [`ListObjectsQuery::paginate`] 的字节序一致
+    async fn list_objects_meta_page(
+        &self,
+        bucket_name: &str,
+        query: &ListObjectsQuery,
+    ) -> EngineResult<ObjectPage> {
+        let objects = select_objects(bucket_name, query, query.start_after()?)
+            .fetch_all(self.pool().await?)
+            .await?
+            .into_iter()
+            .map(object_meta_from_row)
+            .collect::<EngineResult<_>>()?;
+
+        query.paginate(objects)
+    }
+
+    /// 数据库逐行返回结果，按名称排序
+    fn stream_objects_meta<'a>(
+        &'a self,
+        bucket_name: &'a str,
+        query: &'a ListObjectsQuery,
+    ) -> MetaStream<'a, ObjectMeta> {
+        Box::pin(
+            stream::once(self.pool())
+                .map_ok(move |pool| {
+                    select_objects(bucket_name, query, None)
+                        .fetch(pool)
+                        .map(|row| object_meta_from_row(row?))
+                })
+                .try_flatten(),
+        )
+    }
+
+    async fn touch_bucket(&self, bucket_name: &str) -> EngineResult<()> {
+        let affected = sqlx::query("UPDATE bucket_meta SET updated_at = $2 WHERE name = $1")
+            .bind(bucket_name)
+            .bind(clock::now())
+            .execute(self.pool().await?)
+            .await?
+            .rows_affected();
+
+        if affected == 0 {
+            return Err(EngineError::BucketMetaNotFound {
+                bucket: bucket_name.to_string(),
+            });
+        }
+
+        Ok(())
+    }
+
+    async fn append_event(&self, event: &Event) -> EngineResult<u64> {
+        let mut tx = self.pool().await?.begin().await?;
+        sqlx::query("SELECT pg_advisory_xact_lock($1)")
+            .bind(EVENTS_LOCK)
+            .execute(&mut *tx)
+            .await?;
+
+        let seq = sqlx::query_scalar::<_, i64>(
+            r#"INSERT INTO events (op, bucket, object, etag, time)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING seq"#,
+        )
+        .bind(event.op.as_str())
+        .bind(&event.bucket)
+        .bind(&event.object)
+        .bind(&event.etag)
+        .bind(event.time)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(seq as u64)
+    }
+
+    async fn list_events(&self, since: u64, limit: usize) -> EngineResult<Vec<Event>> {
+        sqlx::query("SELECT * FROM events WHERE seq > $1 ORDER BY seq LIMIT $2")
+            .bind(i64::try_from(since).unwrap_or(i64::MAX))
+            .bind(i64::try_from(limit).unwrap_or(i64::MAX))
+            .fetch_all(self.pool().await?)
+            .await?
+            .into_iter()
+            .map(event_from_row)
+            .collect()
+    }
+
+    async fn trim_events(&self, up_to: u64) -> EngineResult<()> {
+        sqlx::query("DELETE FROM events WHERE seq <= $1")
+            .bind(i64::try_from(up_to).unwrap_or(i64::MAX))
+            .execute(self.pool().await?)
+            .await?;
+        Ok(())
+    }
+}
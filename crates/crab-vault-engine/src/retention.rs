@@ -0,0 +1,95 @@
+//! # 保留期和合法保留
+//!
+//! 保留设置在 [`ObjectMeta::retention`] 中。生效期间 object 不能被删除、覆盖或者修改内容，与请求者的权限无关：
+//! 保留期只能延长，不能缩短或者取消；合法保留没有期限，直到被明确地解除。
+//!
+//! [`MetaSource`](crate::MetaSource) 拒绝删除保留中的 object 的元数据，也拒绝用内容不同、
+//! 或者会让它在保留期内过期的元数据覆盖它；
+//! [`DataSource::guard_retention`](crate::DataSource::guard_retention) 之后的 [`DataSource`](crate::DataSource)
+//! 在覆盖、修改和删除数据之前同样检查。具体的引擎本身不检查，调用者可以通过 [`check`] 或者
+//! [`ObjectMeta::check_retention`] 提前检查，生命周期规则不会删除保留中的 object，参见 [`lifecycle::scan`](crate::lifecycle::scan)
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    MetaEngine, ObjectMeta,
+    error::{EngineError, EngineResult},
+};
+
+/// object 的保留设置，读写保留的接口也使用这个文档
+#[derive(Serialize, Deserialize, Default, PartialEq, Eq, Clone, Debug)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct ObjectRetention {
+    /// 在这个时间之前 object 不能被删除或者覆盖
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retain_until: Option<DateTime<Utc>>,
+
+    /// 合法保留，解除之前 object 不能被删除或者覆盖
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub legal_hold: bool,
+}
+
+impl ObjectRetention {
+    pub fn is_empty(&self) -> bool {
+        self.retain_until.is_none() && !self.legal_hold
+    }
+
+    /// 在 `now` 时是否仍然生效
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        self.legal_hold || self.retain_until.is_some_and(|v| v > now)
+    }
+
+    /// 检查新的设置本身，不合法时返回原因
+    pub fn validate(&self, now: DateTime<Utc>) -> Result<(), String> {
+        match self.retain_until {
+            Some(v) if v <= now => Err(format!("retain-until {v} is not in the future")),
+            _ => Ok(()),
+        }
+    }
+
+    /// 替换为 `new` 是否会缩短仍然生效的保留期
+    pub fn shortened_by(&self, new: &Self, now: DateTime<Utc>) -> bool {
+        match self.retain_until.filter(|v| *v > now) {
+            Some(current) => new.retain_until.is_none_or(|v| v < current),
+            None => false,
+        }
+    }
+
+    /// 在 `expires_at` 过期是否发生在保留生效期间，这时 object 在保留期内就无法被读取
+    pub fn covers(&self, expires_at: DateTime<Utc>) -> bool {
+        self.legal_hold || self.retain_until.is_some_and(|v| expires_at < v)
+    }
+
+    /// 替换为 `new` 是否会解除合法保留
+    pub fn released_by(&self, new: &Self) -> bool {
+        self.legal_hold && !new.legal_hold
+    }
+}
+
+impl ObjectMeta {
+    /// 保留仍然生效时返回 [`ObjectLocked`](EngineError::ObjectLocked)
+    pub fn check_retention(&self, now: DateTime<Utc>) -> EngineResult<()> {
+        match self.retention.is_active(now) {
+            true => Err(EngineError::ObjectLocked {
+                bucket: self.bucket_name.clone(),
+                object: self.object_name.clone(),
+            }),
+            false => Ok(()),
+        }
+    }
+}
+
+/// 检查 `bucket_name/object_name` 现在能否被删除或者覆盖，object 不存在时总是可以
+pub async fn check<M: MetaEngine + Sync>(
+    meta_engine: &M,
+    bucket_name: &str,
+    object_name: &str,
+    now: DateTime<Utc>,
+) -> EngineResult<()> {
+    match meta_engine.read_object_meta(bucket_name, object_name).await {
+        Ok(meta) => meta.check_retention(now),
+        Err(EngineError::ObjectMetaNotFound { .. }) => Ok(()),
+        Err(e) => Err(e),
+    }
+}
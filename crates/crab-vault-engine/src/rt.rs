@@ -0,0 +1,497 @@
+//! # 文件系统后端使用的异步 IO
+//!
+//! 开启 `tokio` feature（默认开启）时直接使用 [`tokio::fs`]，没有对应异步接口的操作放到 tokio 的阻塞线程池中执行，
+//! 它们都要求调用者运行在 tokio 运行时中。
+//!
+//! 关闭这个 feature 后，每一次 IO 都在一个新的线程中以阻塞的方式完成，完成后通过 channel 唤醒调用者，
+//! 所以不依赖任何特定的运行时，可以在 smol、async-std 或者 [`futures::executor`] 中使用，代价是每次 IO 都要创建一个线程。
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use crate::{ObjectReader, fs::Durability};
+
+pub(crate) async fn create_dir_all(path: &Path) -> io::Result<()> {
+    imp::create_dir_all(path).await
+}
+
+pub(crate) async fn remove_dir(path: &Path) -> io::Result<()> {
+    imp::remove_dir(path).await
+}
+
+pub(crate) async fn remove_file(path: &Path) -> io::Result<()> {
+    imp::remove_file(path).await
+}
+
+pub(crate) async fn rename(from: &Path, to: &Path) -> io::Result<()> {
+    imp::rename(from, to).await
+}
+
+pub(crate) async fn read(path: &Path) -> io::Result<Vec<u8>> {
+    imp::read(path).await
+}
+
+pub(crate) async fn read_to_string(path: &Path) -> io::Result<String> {
+    imp::read_to_string(path).await
+}
+
+/// 打开文件用于逐块读取，关闭 `tokio` feature 时一次读出整个文件
+pub(crate) async fn open_reader(path: &Path) -> io::Result<ObjectReader> {
+    imp::open_reader(path).await
+}
+
+/// 先写入同一目录中的临时文件，按照 `durability` 同步后再重命名为 `path`
+///
+/// 其他读者要么看到原来的内容，要么看到完整的新内容，不会看到写了一半的文件
+pub(crate) async fn write_atomic(
+    path: &Path,
+    data: impl AsRef<[u8]>,
+    durability: Durability,
+) -> io::Result<()> {
+    let (path, data) = (path.to_path_buf(), data.as_ref().to_vec());
+    imp::blocking(move || {
+        atomic::commit(&path, durability, false, |file| {
+            io::Write::write_all(file, &data).map(|_| data.len() as u64)
+        })
+        .map(|_| ())
+    })
+    .await
+}
+
+/// 与 [`write_atomic`] 相同，但 `path` 已经存在时返回 [`io::ErrorKind::AlreadyExists`]
+pub(crate) async fn write_atomic_new(
+    path: &Path,
+    data: impl AsRef<[u8]>,
+    durability: Durability,
+) -> io::Result<()> {
+    let (path, data) = (path.to_path_buf(), data.as_ref().to_vec());
+    imp::blocking(move || {
+        atomic::commit(&path, durability, true, |file| {
+            io::Write::write_all(file, &data).map(|_| data.len() as u64)
+        })
+        .map(|_| ())
+    })
+    .await
+}
+
+/// 将 `src` 复制为 `dst`，同样先复制到临时文件，参见 [`write_atomic`]
+pub(crate) async fn copy_atomic(src: &Path, dst: &Path, durability: Durability) -> io::Result<u64> {
+    let (src, dst) = (src.to_path_buf(), dst.to_path_buf());
+    imp::blocking(move || {
+        let mut src = std::fs::File::open(&src)?;
+        atomic::commit(&dst, durability, false, |file| io::copy(&mut src, file))
+    })
+    .await
+}
+
+/// ## 让 `path` 成为 `src` 的硬链接，二者共享同一份数据
+///
+/// 已经存在的 `path` 被原子地替换；`exclusive` 时不替换，`path` 已经存在时返回 [`io::ErrorKind::AlreadyExists`]
+pub(crate) async fn link_atomic(
+    src: &Path,
+    path: &Path,
+    durability: Durability,
+    exclusive: bool,
+) -> io::Result<()> {
+    let (src, path) = (src.to_path_buf(), path.to_path_buf());
+    imp::blocking(move || atomic::link(&src, &path, durability, exclusive)).await
+}
+
+/// 文件内容的 sha256 摘要，不会把整个文件读入内存
+pub(crate) async fn digest_file(path: &Path) -> io::Result<[u8; 32]> {
+    let path = path.to_path_buf();
+    imp::blocking(move || {
+        use sha2::{Digest, Sha256};
+
+        let mut hasher = Sha256::new();
+        io::copy(&mut std::fs::File::open(path)?, &mut hasher)?;
+        Ok(hasher.finalize().into())
+    })
+    .await
+}
+
+/// 从 `offset` 开始写入，文件不存在时创建它
+///
+/// `offset` 超过文件末尾时，`sparse` 为 `true` 则留下一个空洞，否则显式地写入 0
+pub(crate) async fn write_at(
+    path: &Path,
+    offset: u64,
+    data: impl AsRef<[u8]>,
+    sparse: bool,
+) -> io::Result<()> {
+    let (path, data) = (path.to_path_buf(), data.as_ref().to_vec());
+    imp::blocking(move || sparse::write_at(&path, offset, &data, sparse)).await
+}
+
+/// 追加到文件的末尾，返回追加之前文件的长度；文件不存在时返回 [`io::ErrorKind::NotFound`]
+pub(crate) async fn append(path: &Path, data: impl AsRef<[u8]>) -> io::Result<u64> {
+    let (path, data) = (path.to_path_buf(), data.as_ref().to_vec());
+    imp::blocking(move || {
+        let mut file = std::fs::OpenOptions::new().append(true).open(path)?;
+        let offset = file.metadata()?.len();
+        io::Write::write_all(&mut file, &data)?;
+        Ok(offset)
+    })
+    .await
+}
+
+/// 将从 `offset` 开始的 `len` 个字节置为 0，不改变文件的长度
+///
+/// `sparse` 为 `true` 时尽量释放这部分占用的磁盘空间，文件系统不支持时退回到写入 0
+pub(crate) async fn punch_hole(path: &Path, offset: u64, len: u64, sparse: bool) -> io::Result<()> {
+    let path = path.to_path_buf();
+    imp::blocking(move || sparse::punch_hole(&path, offset, len, sparse)).await
+}
+
+pub(crate) async fn read_dir(path: &Path) -> io::Result<ReadDir> {
+    imp::read_dir(path).await
+}
+
+/// 目录中的条目，参见 [`read_dir`]
+pub(crate) struct ReadDir(imp::ReadDir);
+
+impl ReadDir {
+    /// 下一个条目的路径，读完时返回 [`None`]
+    pub(crate) async fn next_entry(&mut self) -> io::Result<Option<PathBuf>> {
+        self.0.next_entry().await
+    }
+}
+
+/// 原子写入相关的阻塞操作，由 [`imp::blocking`] 放到合适的线程中执行
+mod atomic {
+    use std::{
+        fs::{self, File, OpenOptions},
+        io,
+        path::{Path, PathBuf},
+        sync::atomic::{AtomicU64, Ordering},
+    };
+
+    use crate::fs::Durability;
+
+    static NEXT_TEMP: AtomicU64 = AtomicU64::new(0);
+
+    /// 以 `.` 开头、以 `.tmp` 结尾，不会被当作元数据读出，也不会被目录结构检查当作异常的条目
+    fn temp_path(path: &Path) -> PathBuf {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        path.with_file_name(format!(
+            ".{name}.{}-{}.tmp",
+            std::process::id(),
+            NEXT_TEMP.fetch_add(1, Ordering::Relaxed)
+        ))
+    }
+
+    /// 用 `fill` 写入临时文件，然后放到 `path`，任何一步失败都会删除临时文件
+    ///
+    /// `exclusive` 时使用硬链接而不是重命名，`path` 已经存在时链接失败，由文件系统保证只有一个调用者成功
+    pub(super) fn commit(
+        path: &Path,
+        durability: Durability,
+        exclusive: bool,
+        fill: impl FnOnce(&mut File) -> io::Result<u64>,
+    ) -> io::Result<u64> {
+        let temp = temp_path(path);
+        let written = place(&temp, path, durability, exclusive, fill);
+
+        // 重命名成功后临时文件已经不存在，这里只会删除失败时留下的，或者硬链接的另一个名字
+        let _ = fs::remove_file(&temp);
+        let written = written?;
+
+        if durability == Durability::Full
+            && let Some(parent) = path.parent()
+        {
+            sync_dir(parent)?;
+        }
+
+        Ok(written)
+    }
+
+    pub(super) fn link(
+        src: &Path,
+        path: &Path,
+        durability: Durability,
+        exclusive: bool,
+    ) -> io::Result<()> {
+        match exclusive {
+            true => fs::hard_link(src, path)?,
+            false => {
+                let temp = temp_path(path);
+                let linked = fs::hard_link(src, &temp).and_then(|_| fs::rename(&temp, path));
+                let _ = fs::remove_file(&temp);
+                linked?;
+            }
+        }
+
+        if durability == Durability::Full
+            && let Some(parent) = path.parent()
+        {
+            sync_dir(parent)?;
+        }
+        Ok(())
+    }
+
+    fn place(
+        temp: &Path,
+        path: &Path,
+        durability: Durability,
+        exclusive: bool,
+        fill: impl FnOnce(&mut File) -> io::Result<u64>,
+    ) -> io::Result<u64> {
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(temp)?;
+        let written = fill(&mut file)?;
+        if durability != Durability::Off {
+            file.sync_all()?;
+        }
+        drop(file);
+
+        match exclusive {
+            true => fs::hard_link(temp, path)?,
+            false => fs::rename(temp, path)?,
+        }
+        Ok(written)
+    }
+
+    /// 目录项的修改在同步目录之后才一定会写入磁盘
+    #[cfg(unix)]
+    fn sync_dir(path: &Path) -> io::Result<()> {
+        File::open(path)?.sync_all()
+    }
+
+    /// 其他平台上无法以这种方式同步目录，只同步文件本身
+    #[cfg(not(unix))]
+    fn sync_dir(_path: &Path) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// 稀疏文件相关的阻塞操作，由 [`imp::blocking`] 放到合适的线程中执行
+mod sparse {
+    use std::{
+        fs::{File, OpenOptions},
+        io::{self, Read, Seek, SeekFrom, Write},
+        path::Path,
+    };
+
+    pub(super) fn write_at(path: &Path, offset: u64, data: &[u8], sparse: bool) -> io::Result<()> {
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+
+        let size = file.metadata()?.len();
+        if !sparse && offset > size {
+            write_zeros(&mut file, size, offset - size)?;
+        }
+
+        file.seek(SeekFrom::Start(offset))?;
+        file.write_all(data)
+    }
+
+    pub(super) fn punch_hole(path: &Path, offset: u64, len: u64, sparse: bool) -> io::Result<()> {
+        let mut file = OpenOptions::new().write(true).open(path)?;
+
+        let size = file.metadata()?.len();
+        if offset >= size || len == 0 {
+            return Ok(());
+        }
+        let len = len.min(size - offset);
+
+        if sparse && deallocate(&file, offset, len)? {
+            return Ok(());
+        }
+
+        write_zeros(&mut file, offset, len)
+    }
+
+    fn write_zeros(file: &mut File, offset: u64, len: u64) -> io::Result<()> {
+        file.seek(SeekFrom::Start(offset))?;
+        io::copy(&mut io::repeat(0).take(len), file)?;
+        Ok(())
+    }
+
+    /// 使用 `fallocate(FALLOC_FL_PUNCH_HOLE)` 释放空间，文件系统不支持时返回 `false`
+    #[cfg(target_os = "linux")]
+    fn deallocate(file: &File, offset: u64, len: u64) -> io::Result<bool> {
+        use std::os::fd::AsRawFd;
+
+        let (Ok(offset), Ok(len)) = (libc::off_t::try_from(offset), libc::off_t::try_from(len))
+        else {
+            return Ok(false);
+        };
+
+        // SAFETY: fd 在 file 的生命周期内有效，fallocate 不会访问调用者的内存
+        let ret = unsafe {
+            libc::fallocate(
+                file.as_raw_fd(),
+                libc::FALLOC_FL_PUNCH_HOLE | libc::FALLOC_FL_KEEP_SIZE,
+                offset,
+                len,
+            )
+        };
+        if ret == 0 {
+            return Ok(true);
+        }
+
+        let e = io::Error::last_os_error();
+        match e.raw_os_error() {
+            Some(libc::EOPNOTSUPP | libc::ENOSYS) => Ok(false),
+            _ => Err(e),
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn deallocate(_file: &File, _offset: u64, _len: u64) -> io::Result<bool> {
+        Ok(false)
+    }
+}
+
+#[cfg(feature = "tokio")]
+mod imp {
+    use std::{
+        io,
+        path::{Path, PathBuf},
+    };
+
+    use tokio::fs;
+    use tokio_util::compat::TokioAsyncReadCompatExt;
+
+    /// 在 tokio 的阻塞线程池中执行
+    pub(super) async fn blocking<T, F>(f: F) -> io::Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> io::Result<T> + Send + 'static,
+    {
+        tokio::task::spawn_blocking(f)
+            .await
+            .unwrap_or_else(|e| Err(io::Error::other(e)))
+    }
+
+    pub(super) async fn create_dir_all(path: &Path) -> io::Result<()> {
+        fs::create_dir_all(path).await
+    }
+
+    pub(super) async fn remove_dir(path: &Path) -> io::Result<()> {
+        fs::remove_dir(path).await
+    }
+
+    pub(super) async fn remove_file(path: &Path) -> io::Result<()> {
+        fs::remove_file(path).await
+    }
+
+    pub(super) async fn rename(from: &Path, to: &Path) -> io::Result<()> {
+        fs::rename(from, to).await
+    }
+
+    pub(super) async fn read(path: &Path) -> io::Result<Vec<u8>> {
+        fs::read(path).await
+    }
+
+    pub(super) async fn read_to_string(path: &Path) -> io::Result<String> {
+        fs::read_to_string(path).await
+    }
+
+    pub(super) async fn open_reader(path: &Path) -> io::Result<crate::ObjectReader> {
+        let file = fs::File::open(path).await?;
+        Ok(Box::pin(file.compat()))
+    }
+
+    pub(super) async fn read_dir(path: &Path) -> io::Result<super::ReadDir> {
+        Ok(super::ReadDir(ReadDir(fs::read_dir(path).await?)))
+    }
+
+    pub(super) struct ReadDir(fs::ReadDir);
+
+    impl ReadDir {
+        pub(super) async fn next_entry(&mut self) -> io::Result<Option<PathBuf>> {
+            Ok(self.0.next_entry().await?.map(|v| v.path()))
+        }
+    }
+}
+
+#[cfg(not(feature = "tokio"))]
+mod imp {
+    use std::{
+        fs, io,
+        path::{Path, PathBuf},
+        vec,
+    };
+
+    use futures::channel::oneshot;
+
+    /// 在一个新的线程中执行阻塞的 IO
+    pub(super) async fn blocking<T, F>(f: F) -> io::Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> io::Result<T> + Send + 'static,
+    {
+        let (tx, rx) = oneshot::channel();
+        std::thread::spawn(move || {
+            let _ = tx.send(f());
+        });
+
+        rx.await
+            .unwrap_or_else(|_| Err(io::Error::other("io thread exited unexpectedly")))
+    }
+
+    pub(super) async fn create_dir_all(path: &Path) -> io::Result<()> {
+        let path = path.to_path_buf();
+        blocking(move || fs::create_dir_all(path)).await
+    }
+
+    pub(super) async fn remove_dir(path: &Path) -> io::Result<()> {
+        let path = path.to_path_buf();
+        blocking(move || fs::remove_dir(path)).await
+    }
+
+    pub(super) async fn remove_file(path: &Path) -> io::Result<()> {
+        let path = path.to_path_buf();
+        blocking(move || fs::remove_file(path)).await
+    }
+
+    pub(super) async fn rename(from: &Path, to: &Path) -> io::Result<()> {
+        let (from, to) = (from.to_path_buf(), to.to_path_buf());
+        blocking(move || fs::rename(from, to)).await
+    }
+
+    pub(super) async fn read(path: &Path) -> io::Result<Vec<u8>> {
+        let path = path.to_path_buf();
+        blocking(move || fs::read(path)).await
+    }
+
+    pub(super) async fn read_to_string(path: &Path) -> io::Result<String> {
+        let path = path.to_path_buf();
+        blocking(move || fs::read_to_string(path)).await
+    }
+
+    /// 逐块读取时每一块都需要一个线程，不如一次读完
+    pub(super) async fn open_reader(path: &Path) -> io::Result<crate::ObjectReader> {
+        let data = read(path).await?;
+        Ok(Box::pin(futures::io::Cursor::new(data)))
+    }
+
+    /// 一次性读出目录中的所有条目，避免每读一个条目就创建一个线程
+    pub(super) async fn read_dir(path: &Path) -> io::Result<super::ReadDir> {
+        let path = path.to_path_buf();
+        let entries = blocking(move || {
+            fs::read_dir(path)?
+                .map(|v| v.map(|v| v.path()))
+                .collect::<io::Result<Vec<_>>>()
+        })
+        .await?;
+
+        Ok(super::ReadDir(ReadDir(entries.into_iter())))
+    }
+
+    pub(super) struct ReadDir(vec::IntoIter<PathBuf>);
+
+    impl ReadDir {
+        pub(super) async fn next_entry(&mut self) -> io::Result<Option<PathBuf>> {
+            Ok(self.0.next())
+        }
+    }
+}
@@ -0,0 +1,636 @@
+use std::collections::HashMap;
+
+use aws_sdk_s3::{
+    Client,
+    config::{BehaviorVersion, Credentials, Region},
+    error::{ProvideErrorMetadata, SdkError},
+    primitives::ByteStream,
+    types::{Delete, ObjectIdentifier},
+};
+
+use crate::{
+    DataEngine,
+    error::{EngineError, EngineResult},
+    registry::redact_uri,
+};
+
+/// `DeleteObjects` 一次请求最多能够删除的 object 个数
+const DELETE_OBJECTS_LIMIT: usize = 1000;
+
+/// 将 crab vault 的 bucket 和 object 映射到一个远端 S3 bucket 中
+///
+/// crab vault 的 bucket `b` 中的 object `o` 保存为远端的 `{prefix}b/o`，
+/// 而 bucket 本身用一个空的 `{prefix}b/` 标记对象表示
+pub struct S3DataEngine {
+    client: Client,
+    bucket: String,
+    prefix: String,
+}
+
+/// # 直接读写一个远端 S3 bucket 中的 object
+///
+/// 与 [`S3DataEngine`] 使用相同形式的 uri，但 object 名称就是去掉 `prefix` 之后的 key，不再映射 bucket，
+/// 用于与 MinIO 等其他 S3 兼容服务之间迁移数据
+pub struct S3Bucket {
+    client: Client,
+    bucket: String,
+    prefix: String,
+}
+
+/// 远端 object 的内容和元数据
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct S3Object {
+    pub data: Vec<u8>,
+    pub content_type: Option<String>,
+
+    /// `x-amz-meta-*` 用户元数据，键中不含这个前缀
+    pub metadata: HashMap<String, String>,
+}
+
+/// 从 `s3://bucket?endpoint=...` 形式的 uri 中解析出的连接参数
+struct S3Uri {
+    bucket: String,
+    prefix: String,
+    endpoint: Option<String>,
+    region: String,
+    access_key: Option<String>,
+    secret_key: Option<String>,
+    path_style: bool,
+}
+
+impl S3Uri {
+    /// 支持的查询参数：`endpoint`、`region`、`prefix`、`access_key`、`secret_key`、`path_style`
+    ///
+    /// 没有提供 `access_key`/`secret_key` 时，从 `AWS_ACCESS_KEY_ID`/`AWS_SECRET_ACCESS_KEY` 环境变量中读取
+    fn parse(uri: &str) -> EngineResult<Self> {
+        let invalid = |msg: &str| {
+            EngineError::InvalidArgument(format!("invalid s3 uri `{}`: {msg}", redact_uri(uri)))
+        };
+
+        let rest = uri
+            .strip_prefix("s3://")
+            .ok_or_else(|| invalid("should start with `s3://`"))?;
+        let (bucket, query) = rest.split_once('?').unwrap_or((rest, ""));
+        let bucket = bucket.trim_end_matches('/');
+
+        if bucket.is_empty() {
+            return Err(invalid("missing bucket name"));
+        }
+
+        let mut parsed = Self {
+            bucket: bucket.to_string(),
+            prefix: String::new(),
+            endpoint: None,
+            region: "us-east-1".to_string(),
+            access_key: std::env::var("AWS_ACCESS_KEY_ID").ok(),
+            secret_key: std::env::var("AWS_SECRET_ACCESS_KEY").ok(),
+            path_style: false,
+        };
+
+        for pair in query.split('&').filter(|v| !v.is_empty()) {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            let value = value.to_string();
+            match key {
+                "endpoint" => {
+                    parsed.endpoint = Some(value);
+                    // 自建的 S3 兼容服务（MinIO 等）通常只支持 path style
+                    parsed.path_style = true;
+                }
+                "region" => parsed.region = value,
+                "prefix" if value.is_empty() || value.ends_with('/') => parsed.prefix = value,
+                "prefix" => parsed.prefix = format!("{value}/"),
+                "access_key" => parsed.access_key = Some(value),
+                "secret_key" => parsed.secret_key = Some(value),
+                "path_style" => {
+                    parsed.path_style = value
+                        .parse()
+                        .map_err(|_| invalid("`path_style` should be `true` or `false`"))?
+                }
+                _ => return Err(invalid(&format!("unknown parameter `{key}`"))),
+            }
+        }
+
+        Ok(parsed)
+    }
+
+    fn client(&self) -> Client {
+        let mut config = aws_sdk_s3::Config::builder()
+            .behavior_version(BehaviorVersion::latest())
+            .region(Region::new(self.region.clone()))
+            .force_path_style(self.path_style);
+
+        if let Some(endpoint) = &self.endpoint {
+            config = config.endpoint_url(endpoint);
+        }
+
+        if let (Some(access_key), Some(secret_key)) = (&self.access_key, &self.secret_key) {
+            config = config.credentials_provider(Credentials::new(
+                access_key,
+                secret_key,
+                None,
+                None,
+                "crab-vault",
+            ));
+        }
+
+        Client::from_conf(config.build())
+    }
+}
+
+/// helper function，将 S3 的错误转换为 [`EngineError::BackendError`]
+#[inline(always)]
+fn backend_error<E: ProvideErrorMetadata, R>(e: SdkError<E, R>) -> EngineError {
+    let detail = match e.as_service_error() {
+        Some(service) => format!(
+            "{}: {}",
+            service.code().unwrap_or("unknown"),
+            service.message().unwrap_or_default()
+        ),
+        None => e.to_string(),
+    };
+
+    EngineError::BackendError(format!("s3 error: {detail}"))
+}
+
+impl S3DataEngine {
+    fn key_of_object(&self, bucket_name: &str, object_name: &str) -> String {
+        format!("{}{}/{}", self.prefix, bucket_name, object_name)
+    }
+
+    /// 一次 `DeleteObjects` 请求，`object_names` 不能超过 [`DELETE_OBJECTS_LIMIT`] 个
+    async fn delete_chunk(
+        &self,
+        bucket_name: &str,
+        object_names: &[String],
+    ) -> Vec<EngineResult<()>> {
+        let keys = object_names
+            .iter()
+            .map(|v| self.key_of_object(bucket_name, v))
+            .collect::<Vec<_>>();
+
+        let delete = keys
+            .iter()
+            .map(|key| ObjectIdentifier::builder().key(key).build())
+            .collect::<Result<Vec<_>, _>>()
+            .and_then(|objects| {
+                Delete::builder()
+                    .set_objects(Some(objects))
+                    .quiet(true)
+                    .build()
+            })
+            .map_err(|e| EngineError::BackendError(format!("s3 error: {e}")));
+
+        let output = match delete {
+            Ok(delete) => self
+                .client
+                .delete_objects()
+                .bucket(&self.bucket)
+                .delete(delete)
+                .send()
+                .await
+                .map_err(backend_error),
+            Err(e) => Err(e),
+        };
+
+        let output = match output {
+            Ok(v) => v,
+            Err(e) => return e.fan_out(keys.len()),
+        };
+
+        // quiet 模式下只返回删除失败的 object
+        keys.iter()
+            .map(|key| {
+                match output.errors().iter().find(|v| v.key() == Some(key.as_str())) {
+                    Some(e) => Err(EngineError::BackendError(format!(
+                        "s3 error: {}: {}",
+                        e.code().unwrap_or("unknown"),
+                        e.message().unwrap_or_default()
+                    ))),
+                    None => Ok(()),
+                }
+            })
+            .collect()
+    }
+
+    fn key_of_bucket(&self, bucket_name: &str) -> String {
+        format!("{}{}/", self.prefix, bucket_name)
+    }
+
+    /// 列出 `prefix` 下的所有 key，`delimiter` 为 `true` 时只列出下一级的公共前缀
+    async fn list_keys(&self, prefix: &str, delimiter: bool) -> EngineResult<Vec<String>> {
+        list_keys(&self.client, &self.bucket, prefix, delimiter, None).await
+    }
+
+    async fn bucket_exists(&self, bucket_name: &str) -> EngineResult<bool> {
+        match self
+            .client
+            .head_object()
+            .bucket(&self.bucket)
+            .key(self.key_of_bucket(bucket_name))
+            .send()
+            .await
+        {
+            Ok(_) => Ok(true),
+            Err(e) if e.as_service_error().is_some_and(|e| e.is_not_found()) => Ok(false),
+            Err(e) => Err(backend_error(e)),
+        }
+    }
+}
+
+impl DataEngine for S3DataEngine {
+    type Uri = str;
+
+    fn new<T: AsRef<str>>(uri: T) -> EngineResult<Self> {
+        let uri = S3Uri::parse(uri.as_ref())?;
+        Ok(Self {
+            client: uri.client(),
+            bucket: uri.bucket,
+            prefix: uri.prefix,
+        })
+    }
+
+    async fn create_bucket(&self, bucket_name: &str) -> EngineResult<()> {
+        self.client
+            .put_object()
+            .bucket(&self.bucket)
+            .key(self.key_of_bucket(bucket_name))
+            .body(ByteStream::from_static(&[]))
+            .send()
+            .await
+            .map_err(backend_error)?;
+
+        Ok(())
+    }
+
+    async fn delete_bucket(&self, bucket_name: &str) -> EngineResult<()> {
+        let marker = self.key_of_bucket(bucket_name);
+
+        // 除了标记对象以外，只要还有一个 object 就不能删除
+        let listed = self
+            .client
+            .list_objects_v2()
+            .bucket(&self.bucket)
+            .prefix(&marker)
+            .max_keys(2)
+            .send()
+            .await
+            .map_err(backend_error)?;
+
+        if listed
+            .contents()
+            .iter()
+            .any(|v| v.key().is_some_and(|key| key != marker))
+        {
+            return Err(EngineError::BucketNotEmpty {
+                bucket: bucket_name.to_string(),
+            });
+        }
+
+        self.client
+            .delete_object()
+            .bucket(&self.bucket)
+            .key(marker)
+            .send()
+            .await
+            .map_err(backend_error)?;
+
+        Ok(())
+    }
+
+    async fn list_buckets(&self) -> EngineResult<Vec<String>> {
+        Ok(self
+            .list_keys(&self.prefix, true)
+            .await?
+            .into_iter()
+            .filter_map(|v| {
+                let name = v.strip_prefix(&self.prefix)?.strip_suffix('/')?;
+                (!name.is_empty()).then(|| name.to_string())
+            })
+            .collect())
+    }
+
+    async fn list_objects(&self, bucket_name: &str) -> EngineResult<Vec<String>> {
+        if !self.bucket_exists(bucket_name).await? {
+            return Err(EngineError::BucketNotFound {
+                bucket: bucket_name.to_string(),
+            });
+        }
+
+        let marker = self.key_of_bucket(bucket_name);
+        Ok(self
+            .list_keys(&marker, false)
+            .await?
+            .into_iter()
+            .filter_map(|v| v.strip_prefix(&marker).map(str::to_string))
+            .filter(|v| !v.is_empty())
+            .collect())
+    }
+
+    async fn create_object(
+        &self,
+        bucket_name: &str,
+        object_name: &str,
+        data: &[u8],
+    ) -> EngineResult<()> {
+        if !self.bucket_exists(bucket_name).await? {
+            return Err(EngineError::BucketNotFound {
+                bucket: bucket_name.to_string(),
+            });
+        }
+
+        self.client
+            .put_object()
+            .bucket(&self.bucket)
+            .key(self.key_of_object(bucket_name, object_name))
+            .body(ByteStream::from(data.to_vec()))
+            .send()
+            .await
+            .map_err(backend_error)?;
+
+        Ok(())
+    }
+
+    /// 使用带有 `If-None-Match: *` 的 `PutObject`，由 S3 保证只有一个写入者能够成功
+    async fn create_object_if_absent(
+        &self,
+        bucket_name: &str,
+        object_name: &str,
+        data: &[u8],
+    ) -> EngineResult<()> {
+        if !self.bucket_exists(bucket_name).await? {
+            return Err(EngineError::BucketNotFound {
+                bucket: bucket_name.to_string(),
+            });
+        }
+
+        match self
+            .client
+            .put_object()
+            .bucket(&self.bucket)
+            .key(self.key_of_object(bucket_name, object_name))
+            .if_none_match("*")
+            .body(ByteStream::from(data.to_vec()))
+            .send()
+            .await
+        {
+            Ok(_) => Ok(()),
+            // 另一个带条件的写入正在进行时返回 ConditionalRequestConflict，同样视为已经存在
+            Err(e)
+                if matches!(
+                    e.code(),
+                    Some("PreconditionFailed" | "ConditionalRequestConflict")
+                ) =>
+            {
+                Err(EngineError::ObjectAlreadyExists {
+                    bucket: bucket_name.to_string(),
+                    object: object_name.to_string(),
+                })
+            }
+            Err(e) => Err(backend_error(e)),
+        }
+    }
+
+    async fn read_object(&self, bucket_name: &str, object_name: &str) -> EngineResult<Vec<u8>> {
+        let output = match self
+            .client
+            .get_object()
+            .bucket(&self.bucket)
+            .key(self.key_of_object(bucket_name, object_name))
+            .send()
+            .await
+        {
+            Ok(output) => output,
+            Err(e) if e.as_service_error().is_some_and(|e| e.is_no_such_key()) => {
+                return Err(EngineError::ObjectNotFound {
+                    bucket: bucket_name.to_string(),
+                    object: object_name.to_string(),
+                });
+            }
+            Err(e) => return Err(backend_error(e)),
+        };
+
+        let data = output
+            .body
+            .collect()
+            .await
+            .map_err(|e| EngineError::BackendError(format!("s3 error: {e}")))?;
+
+        Ok(data.into_bytes().to_vec())
+    }
+
+    async fn delete_object(&self, bucket_name: &str, object_name: &str) -> EngineResult<()> {
+        // S3 的删除本身就是幂等的，object 不存在时也会成功
+        self.client
+            .delete_object()
+            .bucket(&self.bucket)
+            .key(self.key_of_object(bucket_name, object_name))
+            .send()
+            .await
+            .map_err(backend_error)?;
+
+        Ok(())
+    }
+
+    /// 使用 S3 的 `CopyObject`，数据不经过本服务
+    async fn copy_object(
+        &self,
+        src_bucket: &str,
+        src_object: &str,
+        dst_bucket: &str,
+        dst_object: &str,
+    ) -> EngineResult<()> {
+        if !self.bucket_exists(dst_bucket).await? {
+            return Err(EngineError::BucketNotFound {
+                bucket: dst_bucket.to_string(),
+            });
+        }
+
+        let copy_source = format!(
+            "{}/{}",
+            self.bucket,
+            encode_key(&self.key_of_object(src_bucket, src_object))
+        );
+
+        match self
+            .client
+            .copy_object()
+            .bucket(&self.bucket)
+            .key(self.key_of_object(dst_bucket, dst_object))
+            .copy_source(copy_source)
+            .send()
+            .await
+        {
+            Ok(_) => Ok(()),
+            Err(e) if e.code() == Some("NoSuchKey") => Err(EngineError::ObjectNotFound {
+                bucket: src_bucket.to_string(),
+                object: src_object.to_string(),
+            }),
+            Err(e) => Err(backend_error(e)),
+        }
+    }
+
+    /// 使用 S3 的 `DeleteObjects`，每 [`DELETE_OBJECTS_LIMIT`] 个 object 发送一次请求
+    async fn delete_objects(
+        &self,
+        bucket_name: &str,
+        object_names: &[String],
+    ) -> Vec<EngineResult<()>> {
+        let mut results = Vec::with_capacity(object_names.len());
+        for chunk in object_names.chunks(DELETE_OBJECTS_LIMIT) {
+            results.extend(self.delete_chunk(bucket_name, chunk).await);
+        }
+        results
+    }
+}
+
+impl S3Bucket {
+    pub fn new(uri: &str) -> EngineResult<Self> {
+        let uri = S3Uri::parse(uri)?;
+        Ok(Self {
+            client: uri.client(),
+            bucket: uri.bucket,
+            prefix: uri.prefix,
+        })
+    }
+
+    /// 远端 bucket 的名称
+    pub fn name(&self) -> &str {
+        &self.bucket
+    }
+
+    fn key_of(&self, object_name: &str) -> String {
+        format!("{}{}", self.prefix, object_name)
+    }
+
+    /// 按照字典序列出名称以 `prefix` 开头、排在 `start_after` 之后的所有 object
+    pub async fn list_objects(
+        &self,
+        prefix: &str,
+        start_after: Option<&str>,
+    ) -> EngineResult<Vec<String>> {
+        let start_after = start_after.map(|v| self.key_of(v));
+        Ok(list_keys(
+            &self.client,
+            &self.bucket,
+            &self.key_of(prefix),
+            false,
+            start_after.as_deref(),
+        )
+        .await?
+        .into_iter()
+        .filter_map(|v| v.strip_prefix(&self.prefix).map(str::to_string))
+        .collect())
+    }
+
+    pub async fn get_object(&self, object_name: &str) -> EngineResult<S3Object> {
+        let output = match self
+            .client
+            .get_object()
+            .bucket(&self.bucket)
+            .key(self.key_of(object_name))
+            .send()
+            .await
+        {
+            Ok(output) => output,
+            Err(e) if e.as_service_error().is_some_and(|e| e.is_no_such_key()) => {
+                return Err(EngineError::ObjectNotFound {
+                    bucket: self.bucket.clone(),
+                    object: object_name.to_string(),
+                });
+            }
+            Err(e) => return Err(backend_error(e)),
+        };
+
+        let content_type = output.content_type().map(str::to_string);
+        let metadata = output.metadata().cloned().unwrap_or_default();
+        let data = output
+            .body
+            .collect()
+            .await
+            .map_err(|e| EngineError::BackendError(format!("s3 error: {e}")))?;
+
+        Ok(S3Object {
+            data: data.into_bytes().to_vec(),
+            content_type,
+            metadata,
+        })
+    }
+
+    /// 写入一个 object，已经存在时覆盖
+    pub async fn put_object(&self, object_name: &str, object: S3Object) -> EngineResult<()> {
+        let S3Object {
+            data,
+            content_type,
+            metadata,
+        } = object;
+
+        self.client
+            .put_object()
+            .bucket(&self.bucket)
+            .key(self.key_of(object_name))
+            .set_content_type(content_type)
+            .set_metadata((!metadata.is_empty()).then_some(metadata))
+            .body(ByteStream::from(data))
+            .send()
+            .await
+            .map_err(backend_error)?;
+
+        Ok(())
+    }
+}
+
+/// 按照字典序列出 `prefix` 下排在 `start_after` 之后的所有 key，`delimiter` 为 `true` 时只列出下一级的公共前缀
+async fn list_keys(
+    client: &Client,
+    bucket: &str,
+    prefix: &str,
+    delimiter: bool,
+    start_after: Option<&str>,
+) -> EngineResult<Vec<String>> {
+    let mut keys = vec![];
+    let mut token = None;
+    loop {
+        let mut request = client
+            .list_objects_v2()
+            .bucket(bucket)
+            .prefix(prefix)
+            .set_start_after(start_after.map(str::to_string))
+            .set_continuation_token(token);
+        if delimiter {
+            request = request.delimiter("/");
+        }
+        let listed = request.send().await.map_err(backend_error)?;
+
+        match delimiter {
+            true => keys.extend(
+                listed
+                    .common_prefixes()
+                    .iter()
+                    .filter_map(|v| v.prefix().map(str::to_string)),
+            ),
+            false => keys.extend(
+                listed
+                    .contents()
+                    .iter()
+                    .filter_map(|v| v.key().map(str::to_string)),
+            ),
+        }
+
+        token = listed.next_continuation_token().map(str::to_string);
+        if token.is_none() {
+            return Ok(keys);
+        }
+    }
+}
+
+/// `CopyObject` 要求 `copy_source` 是百分号编码的，保留 `/` 和 RFC 3986 中的非保留字符
+fn encode_key(key: &str) -> String {
+    key.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{b:02X}"),
+        })
+        .collect()
+}
@@ -0,0 +1,547 @@
+use std::{path::Path, sync::Arc};
+
+use futures::{StreamExt, future::ready};
+
+use crate::{
+    BucketMeta, DataEngine, MetaEngine, ObjectMeta, ObjectReader, PoolConfig,
+    cache::CachedDataEngine,
+    clock,
+    error::{EngineError, EngineResult},
+    events::Event,
+    list::{BucketPage, ListBucketsQuery, ListObjectsQuery, MetaStream, ObjectPage, OnCorrupt},
+    registry::{DynDataEngine, DynMetaEngine, EngineRegistry},
+    retention,
+    usage::{self, BucketUsage, StorageStats},
+};
+
+/// 运行时根据 `data.source` 的 scheme 选择的 [`DataEngine`]，参见 [`EngineRegistry`]
+pub struct DataSource {
+    scheme: String,
+    engine: Box<dyn DynDataEngine>,
+
+    /// 覆盖和删除 object 之前从这里读取它的保留，不检查时为 [`None`]
+    retention: Option<Arc<MetaSource>>,
+}
+
+/// 运行时根据 `meta.source` 的 scheme 选择的 [`MetaEngine`]，参见 [`EngineRegistry`]
+pub struct MetaSource {
+    scheme: String,
+    engine: Box<dyn DynMetaEngine>,
+    on_corrupt: OnCorrupt,
+
+    /// 记录变更事件时保留的最多条数，不记录时为 [`None`]
+    max_events: Option<u64>,
+}
+
+/// 每追加这么多条事件清理一次旧的事件
+const TRIM_EVENTS_EVERY: u64 = 1024;
+
+impl DataSource {
+    pub fn from_boxed(scheme: impl Into<String>, engine: Box<dyn DynDataEngine>) -> Self {
+        Self {
+            scheme: scheme.into(),
+            engine,
+            retention: None,
+        }
+    }
+
+    /// 创建这个后端时使用的 scheme，本地路径为 [`DEFAULT_SCHEME`](crate::registry::DEFAULT_SCHEME)
+    #[inline]
+    pub fn scheme(&self) -> &str {
+        &self.scheme
+    }
+
+    /// 在读取时缓存不超过 `max_object_size` 字节的 object，总共最多 `max_memory` 字节，参见 [`CachedDataEngine`]
+    pub fn cached(self, max_memory: usize, max_object_size: usize) -> Self {
+        let scheme = self.scheme.clone();
+        let engine = CachedDataEngine::new(self, max_memory).max_object_size(max_object_size);
+        Self::from_boxed(scheme, Box::new(engine))
+    }
+
+    /// 覆盖、修改和删除 object 之前检查 `meta_src` 中记录的保留，生效时抛出
+    /// [`ObjectLocked`](EngineError::ObjectLocked)，参见 [`retention`]
+    pub fn guard_retention(mut self, meta_src: Arc<MetaSource>) -> Self {
+        self.retention = Some(meta_src);
+        self
+    }
+
+    async fn check_retention(&self, bucket_name: &str, object_name: &str) -> EngineResult<()> {
+        match &self.retention {
+            Some(meta_src) => {
+                retention::check(meta_src.as_ref(), bucket_name, object_name, clock::now()).await
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+impl MetaSource {
+    pub fn from_boxed(scheme: impl Into<String>, engine: Box<dyn DynMetaEngine>) -> Self {
+        Self {
+            scheme: scheme.into(),
+            engine,
+            on_corrupt: OnCorrupt::default(),
+            max_events: None,
+        }
+    }
+
+    /// 设置列举时遇到损坏的元数据的处理方式，默认为 [`OnCorrupt::Skip`]，一个损坏的文件不会让整个列举失败
+    pub fn on_corrupt(mut self, on_corrupt: OnCorrupt) -> Self {
+        self.on_corrupt = on_corrupt;
+        self
+    }
+
+    /// 在每次修改元数据之后追加一条变更事件，只保留最近的 `max_events` 条，参见 [`events`](crate::events)
+    pub fn record_events(mut self, max_events: u64) -> Self {
+        self.max_events = Some(max_events);
+        self
+    }
+
+    /// 是否在记录变更事件
+    #[inline]
+    pub fn records_events(&self) -> bool {
+        self.max_events.is_some()
+    }
+
+    /// 修改成功之后追加事件，旧的事件每隔 [`TRIM_EVENTS_EVERY`] 条清理一次
+    async fn record(&self, event: Event) -> EngineResult<()> {
+        let Some(max_events) = self.max_events else {
+            return Ok(());
+        };
+
+        let seq = self.engine.append_event(&event).await?;
+        if seq % TRIM_EVENTS_EVERY == 0 && seq > max_events {
+            self.engine.trim_events(seq - max_events).await?;
+        }
+        Ok(())
+    }
+
+    /// 保留生效期间只能写入内容不变、不缩短保留期、也不会让 object 在保留期内过期的元数据，
+    /// 例如修改标签或者延长保留期，参见 [`retention`]
+    async fn check_overwrite(&self, meta: &ObjectMeta) -> EngineResult<()> {
+        let now = clock::now();
+        let current = match self
+            .engine
+            .read_object_meta(&meta.bucket_name, &meta.object_name)
+            .await
+        {
+            Ok(current) => current,
+            Err(EngineError::ObjectMetaNotFound { .. }) => return Ok(()),
+            Err(e) => return Err(e),
+        };
+
+        let expires_early = current.expires_at != meta.expires_at
+            && meta
+                .expires_at
+                .is_some_and(|v| current.retention.covers(v));
+        if current.etag != meta.etag
+            || current.retention.shortened_by(&meta.retention, now)
+            || expires_early
+        {
+            current.check_retention(now)?;
+        }
+        Ok(())
+    }
+
+    /// 与 [`list_buckets_meta`](MetaEngine::list_buckets_meta) 相同，同时返回因为元数据损坏而被跳过的 bucket
+    pub async fn list_buckets_meta_reporting(&self) -> EngineResult<(Vec<BucketMeta>, Vec<String>)> {
+        match self.engine.list_buckets_meta().await {
+            Err(EngineError::CorruptMeta { .. }) if self.on_corrupt == OnCorrupt::Skip => {
+                collect_skipping(self.engine.stream_buckets_meta()).await
+            }
+            result => result.map(|v| (v, vec![])),
+        }
+    }
+
+    /// 在 [`OnCorrupt::Skip`] 时去掉流中的 [`CorruptMeta`](EngineError::CorruptMeta)
+    fn skip_corrupt<'a, T: Send + 'a>(&self, stream: MetaStream<'a, T>) -> MetaStream<'a, T> {
+        match self.on_corrupt {
+            OnCorrupt::Fail => stream,
+            OnCorrupt::Skip => Box::pin(
+                stream.filter(|v| ready(!matches!(v, Err(EngineError::CorruptMeta { .. })))),
+            ),
+        }
+    }
+
+    /// 创建这个后端时使用的 scheme，本地路径为 [`DEFAULT_SCHEME`](crate::registry::DEFAULT_SCHEME)
+    #[inline]
+    pub fn scheme(&self) -> &str {
+        &self.scheme
+    }
+
+    /// 使用默认的 [`EngineRegistry`] 创建后端，`pool` 只对数据库类的后端生效
+    pub fn with_pool_config(source: &str, pool: &PoolConfig) -> EngineResult<Self> {
+        EngineRegistry::default().meta_source(source, pool)
+    }
+}
+
+impl DataEngine for DataSource {
+    type Uri = str;
+
+    /// 使用默认的 [`EngineRegistry`] 创建后端
+    fn new<T: AsRef<str>>(source: T) -> EngineResult<Self> {
+        EngineRegistry::default().data_source(source.as_ref())
+    }
+
+    async fn create_bucket(&self, bucket_name: &str) -> EngineResult<()> {
+        self.engine.create_bucket(bucket_name).await
+    }
+
+    async fn delete_bucket(&self, bucket_name: &str) -> EngineResult<()> {
+        self.engine.delete_bucket(bucket_name).await
+    }
+
+    async fn list_buckets(&self) -> EngineResult<Vec<String>> {
+        self.engine.list_buckets().await
+    }
+
+    async fn list_objects(&self, bucket_name: &str) -> EngineResult<Vec<String>> {
+        self.engine.list_objects(bucket_name).await
+    }
+
+    async fn create_object(
+        &self,
+        bucket_name: &str,
+        object_name: &str,
+        data: &[u8],
+    ) -> EngineResult<()> {
+        self.check_retention(bucket_name, object_name).await?;
+        self.engine
+            .create_object(bucket_name, object_name, data)
+            .await
+    }
+
+    async fn create_object_if_absent(
+        &self,
+        bucket_name: &str,
+        object_name: &str,
+        data: &[u8],
+    ) -> EngineResult<()> {
+        self.engine
+            .create_object_if_absent(bucket_name, object_name, data)
+            .await
+    }
+
+    async fn read_object(&self, bucket_name: &str, object_name: &str) -> EngineResult<Vec<u8>> {
+        self.engine.read_object(bucket_name, object_name).await
+    }
+
+    async fn read_object_reader(
+        &self,
+        bucket_name: &str,
+        object_name: &str,
+    ) -> EngineResult<ObjectReader> {
+        self.engine
+            .read_object_reader(bucket_name, object_name)
+            .await
+    }
+
+    async fn delete_object(&self, bucket_name: &str, object_name: &str) -> EngineResult<()> {
+        self.check_retention(bucket_name, object_name).await?;
+        self.engine.delete_object(bucket_name, object_name).await
+    }
+
+    async fn copy_object(
+        &self,
+        src_bucket: &str,
+        src_object: &str,
+        dst_bucket: &str,
+        dst_object: &str,
+    ) -> EngineResult<()> {
+        self.check_retention(dst_bucket, dst_object).await?;
+        self.engine
+            .copy_object(src_bucket, src_object, dst_bucket, dst_object)
+            .await
+    }
+
+    async fn create_object_from_file(
+        &self,
+        bucket_name: &str,
+        object_name: &str,
+        path: &Path,
+    ) -> EngineResult<()> {
+        self.check_retention(bucket_name, object_name).await?;
+        self.engine
+            .create_object_from_file(bucket_name, object_name, path)
+            .await
+    }
+
+    async fn write_object_at(
+        &self,
+        bucket_name: &str,
+        object_name: &str,
+        offset: u64,
+        data: &[u8],
+    ) -> EngineResult<()> {
+        self.check_retention(bucket_name, object_name).await?;
+        self.engine
+            .write_object_at(bucket_name, object_name, offset, data)
+            .await
+    }
+
+    async fn append_object(
+        &self,
+        bucket_name: &str,
+        object_name: &str,
+        data: &[u8],
+    ) -> EngineResult<u64> {
+        self.check_retention(bucket_name, object_name).await?;
+        self.engine
+            .append_object(bucket_name, object_name, data)
+            .await
+    }
+
+    async fn punch_hole(
+        &self,
+        bucket_name: &str,
+        object_name: &str,
+        offset: u64,
+        len: u64,
+    ) -> EngineResult<()> {
+        self.check_retention(bucket_name, object_name).await?;
+        self.engine
+            .punch_hole(bucket_name, object_name, offset, len)
+            .await
+    }
+
+    async fn delete_objects(
+        &self,
+        bucket_name: &str,
+        object_names: &[String],
+    ) -> Vec<EngineResult<()>> {
+        if self.retention.is_none() {
+            return self.engine.delete_objects(bucket_name, object_names).await;
+        }
+
+        // 只删除不在保留中的 object，结果仍然与 `object_names` 一一对应
+        let mut results = Vec::with_capacity(object_names.len());
+        let mut unlocked = vec![];
+        for object_name in object_names {
+            let result = self.check_retention(bucket_name, object_name).await;
+            if result.is_ok() {
+                unlocked.push(object_name.clone());
+            }
+            results.push(result);
+        }
+
+        let mut deleted = self.engine.delete_objects(bucket_name, &unlocked).await.into_iter();
+        for result in results.iter_mut().filter(|v| v.is_ok()) {
+            *result = deleted.next().unwrap_or(Ok(()));
+        }
+        results
+    }
+}
+
+impl MetaEngine for MetaSource {
+    type Uri = str;
+
+    fn new<T: AsRef<str>>(source: T) -> EngineResult<Self> {
+        Self::with_pool_config(source.as_ref(), &PoolConfig::default())
+    }
+
+    async fn create_bucket_meta(&self, meta: &BucketMeta) -> EngineResult<()> {
+        self.engine.create_bucket_meta(meta).await?;
+        self.record(Event::put_bucket(&meta.name)).await
+    }
+
+    async fn read_bucket_meta(&self, bucket_name: &str) -> EngineResult<BucketMeta> {
+        self.engine.read_bucket_meta(bucket_name).await
+    }
+
+    async fn delete_bucket_meta(&self, bucket_name: &str) -> EngineResult<()> {
+        self.engine.delete_bucket_meta(bucket_name).await?;
+        self.record(Event::delete_bucket(bucket_name)).await
+    }
+
+    async fn list_buckets_meta(&self) -> EngineResult<Vec<BucketMeta>> {
+        Ok(self.list_buckets_meta_reporting().await?.0)
+    }
+
+    async fn list_buckets_meta_page(&self, query: &ListBucketsQuery) -> EngineResult<BucketPage> {
+        match self.engine.list_buckets_meta_page(query).await {
+            Err(EngineError::CorruptMeta { .. }) if self.on_corrupt == OnCorrupt::Skip => {
+                let (buckets, mut skipped) =
+                    collect_skipping(self.engine.stream_buckets_meta()).await?;
+                skipped.retain(|v| v.starts_with(query.prefix()));
+
+                let mut page = query.paginate(buckets)?;
+                page.skipped = skipped;
+                Ok(page)
+            }
+            result => result,
+        }
+    }
+
+    fn stream_buckets_meta(&self) -> MetaStream<'_, BucketMeta> {
+        self.skip_corrupt(self.engine.stream_buckets_meta())
+    }
+
+    async fn touch_object(&self, bucket_name: &str, object_name: &str) -> EngineResult<()> {
+        self.engine.touch_object(bucket_name, object_name).await
+    }
+
+    async fn create_object_meta(&self, meta: &ObjectMeta) -> EngineResult<()> {
+        self.check_overwrite(meta).await?;
+        self.engine.create_object_meta(meta).await?;
+        self.record(Event::put_object(meta)).await
+    }
+
+    async fn create_object_meta_if(&self, meta: &ObjectMeta, expected_etag: &str) -> EngineResult<()> {
+        self.check_overwrite(meta).await?;
+        self.engine.create_object_meta_if(meta, expected_etag).await?;
+        self.record(Event::put_object(meta)).await
+    }
+
+    async fn read_object_meta(
+        &self,
+        bucket_name: &str,
+        object_name: &str,
+    ) -> EngineResult<ObjectMeta> {
+        self.engine.read_object_meta(bucket_name, object_name).await
+    }
+
+    async fn delete_object_meta(&self, bucket_name: &str, object_name: &str) -> EngineResult<()> {
+        retention::check(self, bucket_name, object_name, clock::now()).await?;
+        self.engine
+            .delete_object_meta(bucket_name, object_name)
+            .await?;
+        self.record(Event::delete_object(bucket_name, object_name))
+            .await
+    }
+
+    async fn list_objects_meta(&self, bucket_name: &str) -> EngineResult<Vec<ObjectMeta>> {
+        match self.engine.list_objects_meta(bucket_name).await {
+            Err(EngineError::CorruptMeta { .. }) if self.on_corrupt == OnCorrupt::Skip => {
+                let query = ListObjectsQuery::default();
+                let stream = self.engine.stream_objects_meta(bucket_name, &query);
+                Ok(collect_skipping(stream).await?.0)
+            }
+            result => result,
+        }
+    }
+
+    async fn delete_objects_meta(
+        &self,
+        bucket_name: &str,
+        object_names: &[String],
+    ) -> Vec<EngineResult<()>> {
+        let now = clock::now();
+        let mut results = Vec::with_capacity(object_names.len());
+        let mut unlocked = vec![];
+        for object_name in object_names {
+            let result = retention::check(self, bucket_name, object_name, now).await;
+            if result.is_ok() {
+                unlocked.push(object_name.clone());
+            }
+            results.push(result);
+        }
+
+        let mut deleted = self
+            .engine
+            .delete_objects_meta(bucket_name, &unlocked)
+            .await
+            .into_iter()
+            .zip(&unlocked);
+        for result in results.iter_mut().filter(|v| v.is_ok()) {
+            let Some((deleted, object_name)) = deleted.next() else {
+                break;
+            };
+            *result = match deleted {
+                Ok(()) => {
+                    self.record(Event::delete_object(bucket_name, object_name))
+                        .await
+                }
+                Err(e) => Err(e),
+            };
+        }
+        results
+    }
+
+    async fn list_objects_meta_page(
+        &self,
+        bucket_name: &str,
+        query: &ListObjectsQuery,
+    ) -> EngineResult<ObjectPage> {
+        match self.engine.list_objects_meta_page(bucket_name, query).await {
+            Err(EngineError::CorruptMeta { .. }) if self.on_corrupt == OnCorrupt::Skip => {
+                let stream = self.engine.stream_objects_meta(bucket_name, query);
+                let (objects, mut skipped) = collect_skipping(stream).await?;
+
+                // 损坏的条目无法按时间过滤，只能按名称过滤
+                skipped.retain(|v| v.starts_with(query.prefix()));
+
+                let mut page = query.paginate(objects)?;
+                page.skipped = skipped;
+                Ok(page)
+            }
+            result => result,
+        }
+    }
+
+    fn stream_objects_meta<'a>(
+        &'a self,
+        bucket_name: &'a str,
+        query: &'a ListObjectsQuery,
+    ) -> MetaStream<'a, ObjectMeta> {
+        self.skip_corrupt(self.engine.stream_objects_meta(bucket_name, query))
+    }
+
+    async fn bucket_usage(&self, bucket_name: &str) -> EngineResult<BucketUsage> {
+        match self.engine.bucket_usage(bucket_name).await {
+            Err(EngineError::CorruptMeta { .. }) if self.on_corrupt == OnCorrupt::Skip => {
+                let query = ListObjectsQuery::default();
+                let stream = self.engine.stream_objects_meta(bucket_name, &query);
+                let (objects, _) = collect_skipping(stream).await?;
+
+                let mut usage = BucketUsage::default();
+                objects.iter().for_each(|v| usage.add(v.size));
+                Ok(usage)
+            }
+            result => result,
+        }
+    }
+
+    /// 跳过损坏的条目时退回到逐个 bucket 统计，只有 object 元数据的 bucket 不会被统计
+    async fn stats(&self) -> EngineResult<StorageStats> {
+        match self.engine.stats().await {
+            Err(EngineError::CorruptMeta { .. }) if self.on_corrupt == OnCorrupt::Skip => {
+                let (buckets, _) = self.list_buckets_meta_reporting().await?;
+                let names = buckets.into_iter().map(|v| v.name).collect();
+                usage::collect_stats(self, names).await
+            }
+            result => result,
+        }
+    }
+
+    async fn touch_bucket(&self, bucket_name: &str) -> EngineResult<()> {
+        self.engine.touch_bucket(bucket_name).await
+    }
+
+    async fn append_event(&self, event: &Event) -> EngineResult<u64> {
+        self.engine.append_event(event).await
+    }
+
+    async fn list_events(&self, since: u64, limit: usize) -> EngineResult<Vec<Event>> {
+        self.engine.list_events(since, limit).await
+    }
+
+    async fn trim_events(&self, up_to: u64) -> EngineResult<()> {
+        self.engine.trim_events(up_to).await
+    }
+}
+
+/// 收集流中所有完好的元数据，以及损坏的条目名称，遇到其他错误时失败
+async fn collect_skipping<T>(mut stream: MetaStream<'_, T>) -> EngineResult<(Vec<T>, Vec<String>)> {
+    let (mut items, mut skipped) = (vec![], vec![]);
+
+    while let Some(item) = stream.next().await {
+        match item {
+            Ok(item) => items.push(item),
+            Err(EngineError::CorruptMeta { entry }) => skipped.push(entry),
+            Err(e) => return Err(e),
+        }
+    }
+
+    skipped.sort_unstable();
+    Ok((items, skipped))
+}
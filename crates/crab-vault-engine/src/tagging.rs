@@ -0,0 +1,72 @@
+//! # object 的标签
+//!
+//! 标签保存在 [`ObjectMeta::tags`] 中，与用户元数据不同，它是数量有限的字符串键值对，
+//! 由单独的接口读写，可以作为生命周期规则和访问权限的条件。
+//!
+//! 修改标签不会改变 object 的 `updated_at`，所以也不会推迟它按照生命周期规则过期
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::ObjectMeta;
+
+/// 一个 object 最多的标签数
+pub const MAX_TAGS: usize = 10;
+
+/// 标签的键最多的字符数
+pub const MAX_TAG_KEY_LEN: usize = 128;
+
+/// 标签的值最多的字符数
+pub const MAX_TAG_VALUE_LEN: usize = 256;
+
+/// 标签的键到值的映射
+pub type Tags = BTreeMap<String, String>;
+
+/// 读写标签的接口使用的文档
+#[derive(Serialize, Deserialize, Default, PartialEq, Clone, Debug)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Tagging {
+    pub tags: Tags,
+}
+
+impl Tagging {
+    /// 检查标签的数量和长度，不合法时返回原因
+    pub fn validate(&self) -> Result<(), String> {
+        validate(&self.tags)
+    }
+}
+
+/// 检查标签的数量和长度，不合法时返回原因，生命周期规则中的标签条件同样适用
+pub fn validate(tags: &Tags) -> Result<(), String> {
+    if tags.len() > MAX_TAGS {
+        return Err(format!("at most {MAX_TAGS} tags are allowed"));
+    }
+
+    for (key, value) in tags {
+        if key.is_empty() || key.chars().count() > MAX_TAG_KEY_LEN {
+            return Err(format!(
+                "tag key `{key}` should be 1 to {MAX_TAG_KEY_LEN} characters long"
+            ));
+        }
+        if value.chars().count() > MAX_TAG_VALUE_LEN {
+            return Err(format!(
+                "the value of tag `{key}` should be at most {MAX_TAG_VALUE_LEN} characters long"
+            ));
+        }
+        if key.chars().chain(value.chars()).any(char::is_control) {
+            return Err(format!("tag `{key}` contains control characters"));
+        }
+    }
+
+    Ok(())
+}
+
+impl ObjectMeta {
+    /// `filter` 中的每一个标签都以相同的值出现在这个 object 上，空的 `filter` 总是满足
+    pub fn has_tags(&self, filter: &Tags) -> bool {
+        filter
+            .iter()
+            .all(|(key, value)| self.tags.get(key) == Some(value))
+    }
+}
@@ -0,0 +1,247 @@
+//! # 回收站
+//!
+//! 开启回收站时，删除的 object 先连同元数据一起复制到 [内部 bucket](crate::name::internal_bucket) 中，
+//! 在保留期内可以恢复，过了保留期由调用者通过 [`expired`] 和 [`remove`] 清除。
+//! 与 [`lifecycle`](crate::lifecycle) 一样，引擎只负责复制和查找，删除原来的 object 由调用者完成。
+//!
+//! 回收站中的 object 名为 `trash/{bucket}/{id}/{object}`，`id` 是删除时间的微秒数，
+//! 所以同一个 object 被删除多次时每一次都有自己的条目。元数据保持原样，只替换了 bucket 和 object 的名称，
+//! 加密和压缩信息都随之保留，恢复时原样写回
+
+use std::future::ready;
+
+use chrono::{DateTime, Utc};
+use futures::TryStreamExt;
+use serde::Serialize;
+
+use crate::{
+    DataEngine, MetaEngine, ObjectMeta,
+    error::{EngineError, EngineResult},
+    list::ListObjectsQuery,
+    name,
+};
+
+/// 回收站在内部 bucket 中使用的前缀
+pub const TRASH_PREFIX: &str = "trash/";
+
+/// 回收站中的一个条目，也就是某个 object 的一次删除
+#[derive(Serialize, Clone, PartialEq, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub struct TrashEntry {
+    /// 恢复时用来指定这一次删除，参见 [`find`]
+    pub id: String,
+    pub object_name: String,
+    pub size: u64,
+    pub content_type: String,
+    pub etag: String,
+    pub deleted_at: DateTime<Utc>,
+}
+
+impl TrashEntry {
+    /// 在 `now` 时删除 `meta` 对应的 object 产生的条目，删除时间只精确到微秒
+    pub fn new(meta: &ObjectMeta, now: DateTime<Utc>) -> Self {
+        let micros = now.timestamp_micros();
+        Self {
+            id: micros.to_string(),
+            object_name: meta.object_name.clone(),
+            size: meta.size,
+            content_type: meta.content_type.clone(),
+            etag: meta.etag.clone(),
+            deleted_at: DateTime::from_timestamp_micros(micros).unwrap_or(now),
+        }
+    }
+
+    /// 从内部 bucket 中的元数据解析出原来的 bucket 和条目，不是回收站中的 object 时返回 [`None`]
+    pub fn parse(meta: &ObjectMeta) -> Option<(String, Self)> {
+        let rest = meta.object_name.strip_prefix(TRASH_PREFIX)?;
+        let (bucket, rest) = rest.split_once('/')?;
+        let (id, object) = rest.split_once('/')?;
+        let deleted_at = DateTime::from_timestamp_micros(id.parse().ok()?)?;
+
+        let entry = Self {
+            id: id.to_string(),
+            object_name: object.to_string(),
+            size: meta.size,
+            content_type: meta.content_type.clone(),
+            etag: meta.etag.clone(),
+            deleted_at,
+        };
+        Some((bucket.to_string(), entry))
+    }
+
+    /// 这个条目在内部 bucket 中的 object 名称
+    pub fn key(&self, bucket_name: &str) -> String {
+        format!(
+            "{TRASH_PREFIX}{bucket_name}/{}/{}",
+            self.id, self.object_name
+        )
+    }
+}
+
+/// 把 `meta` 对应的 object 的数据和元数据复制到回收站，原来的 object 保持不变
+pub async fn put<D, M>(
+    data: &D,
+    meta_engine: &M,
+    meta: &ObjectMeta,
+    now: DateTime<Utc>,
+) -> EngineResult<TrashEntry>
+where
+    D: DataEngine + Sync,
+    M: MetaEngine + Sync,
+{
+    let entry = TrashEntry::new(meta, now);
+    let internal = name::internal_bucket();
+    let key = entry.key(&meta.bucket_name);
+
+    let copy = || data.copy_object(&meta.bucket_name, &meta.object_name, internal, &key);
+    match copy().await {
+        Err(EngineError::BucketNotFound { .. }) => {
+            data.create_bucket(internal).await?;
+            copy().await?;
+        }
+        other => other?,
+    }
+
+    let mut trashed = meta.clone();
+    trashed.bucket_name = internal.to_string();
+    trashed.object_name = key;
+    meta_engine.create_object_meta(&trashed).await?;
+    Ok(entry)
+}
+
+/// 回收站中 `bucket_name` 的所有条目，最近删除的在前
+pub async fn list<M: MetaEngine + Sync>(
+    meta_engine: &M,
+    bucket_name: &str,
+) -> EngineResult<Vec<TrashEntry>> {
+    let prefix = format!("{TRASH_PREFIX}{bucket_name}/");
+    let mut entries: Vec<_> = scan(meta_engine, &prefix)
+        .await?
+        .into_iter()
+        .map(|(_, entry)| entry)
+        .collect();
+
+    entries.sort_by(|a, b| {
+        b.deleted_at
+            .cmp(&a.deleted_at)
+            .then_with(|| a.object_name.cmp(&b.object_name))
+    });
+    Ok(entries)
+}
+
+/// 找到 `object_name` 在回收站中的条目，没有给出 `id` 时是最近一次删除的
+pub async fn find<M: MetaEngine + Sync>(
+    meta_engine: &M,
+    bucket_name: &str,
+    object_name: &str,
+    id: Option<&str>,
+) -> EngineResult<TrashEntry> {
+    list(meta_engine, bucket_name)
+        .await?
+        .into_iter()
+        .find(|v| v.object_name == object_name && id.is_none_or(|id| v.id == id))
+        .ok_or_else(|| EngineError::TrashEntryNotFound {
+            bucket: bucket_name.to_string(),
+            object: object_name.to_string(),
+        })
+}
+
+/// 条目对应的 object 被删除时的元数据，也就是恢复之后的元数据
+pub async fn read<M: MetaEngine + Sync>(
+    meta_engine: &M,
+    bucket_name: &str,
+    entry: &TrashEntry,
+) -> EngineResult<ObjectMeta> {
+    let key = entry.key(bucket_name);
+    let mut meta = meta_engine
+        .read_object_meta(name::internal_bucket(), &key)
+        .await
+        .map_err(|e| match e {
+            EngineError::ObjectMetaNotFound { .. } => EngineError::TrashEntryNotFound {
+                bucket: bucket_name.to_string(),
+                object: entry.object_name.clone(),
+            },
+            e => e,
+        })?;
+
+    meta.bucket_name = bucket_name.to_string();
+    meta.object_name = entry.object_name.clone();
+    Ok(meta)
+}
+
+/// 把条目复制回原来的位置，然后从回收站中删除它，同名的 object 会被覆盖
+///
+/// `meta` 是 [`read`] 读出的元数据
+pub async fn restore<D, M>(
+    data: &D,
+    meta_engine: &M,
+    entry: &TrashEntry,
+    meta: &ObjectMeta,
+) -> EngineResult<()>
+where
+    D: DataEngine + Sync,
+    M: MetaEngine + Sync,
+{
+    let key = entry.key(&meta.bucket_name);
+    data.copy_object(
+        name::internal_bucket(),
+        &key,
+        &meta.bucket_name,
+        &meta.object_name,
+    )
+    .await?;
+    meta_engine.create_object_meta(meta).await?;
+
+    remove(data, meta_engine, &meta.bucket_name, entry).await
+}
+
+/// 从回收站中删除一个条目，先删除数据再删除元数据，所以中途失败时仍然能被 [`expired`] 找到
+pub async fn remove<D, M>(
+    data: &D,
+    meta_engine: &M,
+    bucket_name: &str,
+    entry: &TrashEntry,
+) -> EngineResult<()>
+where
+    D: DataEngine + Sync,
+    M: MetaEngine + Sync,
+{
+    let key = entry.key(bucket_name);
+    data.delete_object(name::internal_bucket(), &key).await?;
+    meta_engine
+        .delete_object_meta(name::internal_bucket(), &key)
+        .await
+}
+
+/// 所有 bucket 中在 `before` 之前删除的条目，包括已经不存在的 bucket
+pub async fn expired<M: MetaEngine + Sync>(
+    meta_engine: &M,
+    before: DateTime<Utc>,
+) -> EngineResult<Vec<(String, TrashEntry)>> {
+    let mut entries = scan(meta_engine, TRASH_PREFIX).await?;
+    entries.retain(|(_, entry)| entry.deleted_at <= before);
+    Ok(entries)
+}
+
+/// 内部 bucket 中以 `prefix` 开头的条目，还没有任何 object 进入过回收站时为空
+async fn scan<M: MetaEngine + Sync>(
+    meta_engine: &M,
+    prefix: &str,
+) -> EngineResult<Vec<(String, TrashEntry)>> {
+    let query = ListObjectsQuery {
+        prefix: Some(prefix.to_string()),
+        ..Default::default()
+    };
+    let result = meta_engine
+        .stream_objects_meta(name::internal_bucket(), &query)
+        .try_filter_map(|v| ready(Ok(TrashEntry::parse(&v))))
+        .try_collect()
+        .await;
+
+    match result {
+        Err(EngineError::BucketMetaNotFound { .. } | EngineError::BucketNotFound { .. }) => {
+            Ok(vec![])
+        }
+        other => other,
+    }
+}
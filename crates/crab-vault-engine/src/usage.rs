@@ -0,0 +1,175 @@
+//! # bucket 的用量和配额
+//!
+//! [`BucketMeta`] 中的 `max_bytes` 和 `max_objects` 分别限制一个 bucket 中 object 的总字节数和个数，
+//! 没有设置时不限制。用量由 [`MetaEngine::bucket_usage`](crate::MetaEngine::bucket_usage) 给出。
+//!
+//! 配额只在写入之前检查，引擎本身不在检查和写入之间加锁，调用者需要让同一个 bucket 中受配额限制的写入依次进行，
+//! 否则并发的写入可能一起超过配额。
+//! 只会让用量变小的写入总是被允许，即使用量已经超过了配额，例如把一个 object 替换成更小的版本
+//!
+//! 所有 bucket 的用量由 [`MetaEngine::stats`] 汇总为 [`StorageStats`]
+
+use std::collections::BTreeSet;
+
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    BucketMeta, MetaEngine,
+    error::{EngineError, EngineResult},
+};
+
+/// 一个 bucket 中 object 的个数和总字节数
+#[derive(Serialize, Deserialize, Default, PartialEq, Eq, Clone, Copy, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub struct BucketUsage {
+    pub object_count: u64,
+    pub total_bytes: u64,
+}
+
+/// 一个 bucket 的名称和用量，参见 [`StorageStats`]
+#[derive(Serialize, Deserialize, Default, PartialEq, Eq, Clone, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub struct BucketStats {
+    pub name: String,
+
+    #[serde(flatten)]
+    pub usage: BucketUsage,
+}
+
+/// 所有 bucket 的用量，以及它们的总和
+#[derive(Serialize, Deserialize, Default, PartialEq, Eq, Clone, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub struct StorageStats {
+    pub bucket_count: u64,
+    pub object_count: u64,
+    pub total_bytes: u64,
+
+    /// 按照名称排序
+    pub buckets: Vec<BucketStats>,
+}
+
+impl StorageStats {
+    /// 汇总各个 bucket 的用量
+    pub fn new(mut buckets: Vec<BucketStats>) -> Self {
+        buckets.sort_by(|a, b| a.name.cmp(&b.name));
+        Self {
+            bucket_count: buckets.len() as u64,
+            object_count: buckets.iter().map(|v| v.usage.object_count).sum(),
+            total_bytes: buckets.iter().map(|v| v.usage.total_bytes).sum(),
+            buckets,
+        }
+    }
+}
+
+/// 逐个读取 `names` 中的 bucket 的用量并汇总
+pub(crate) async fn collect_stats<E: MetaEngine + Sync>(
+    engine: &E,
+    names: BTreeSet<String>,
+) -> EngineResult<StorageStats> {
+    let mut buckets = Vec::with_capacity(names.len());
+    for name in names {
+        let usage = engine.bucket_usage(&name).await?;
+        buckets.push(BucketStats { name, usage });
+    }
+    Ok(StorageStats::new(buckets))
+}
+
+impl BucketUsage {
+    /// 加上一个大小为 `size` 的 object
+    pub fn add(&mut self, size: u64) {
+        self.object_count += 1;
+        self.total_bytes += size;
+    }
+
+    /// 减去一个大小为 `size` 的 object
+    pub fn remove(&mut self, size: u64) {
+        self.object_count = self.object_count.saturating_sub(1);
+        self.total_bytes = self.total_bytes.saturating_sub(size);
+    }
+
+    /// 把大小为 `old` 的 object 替换成大小为 `new` 的 object，[`None`] 表示 object 不存在
+    pub fn replace(&mut self, old: Option<u64>, new: Option<u64>) {
+        if let Some(old) = old {
+            self.remove(old);
+        }
+        if let Some(new) = new {
+            self.add(new);
+        }
+    }
+}
+
+impl BucketMeta {
+    /// 是否设置了任何一个配额
+    pub fn has_quota(&self) -> bool {
+        self.max_bytes.is_some() || self.max_objects.is_some()
+    }
+
+    /// ## 检查把大小为 `old` 的 object 替换成大小为 `new` 的 object 之后是否超出配额
+    ///
+    /// `old` 为 [`None`] 表示新建一个 object，`usage` 是写入之前的用量。
+    /// 超出 `max_objects` 时返回 [`ObjectQuotaExceeded`](EngineError::ObjectQuotaExceeded)，
+    /// 超出 `max_bytes` 时返回 [`ByteQuotaExceeded`](EngineError::ByteQuotaExceeded)
+    pub fn check_quota(&self, usage: BucketUsage, old: Option<u64>, new: u64) -> EngineResult<()> {
+        let mut after = usage;
+        after.replace(old, Some(new));
+
+        if let Some(limit) = self.max_objects
+            && after.object_count > limit
+            && after.object_count > usage.object_count
+        {
+            return Err(EngineError::ObjectQuotaExceeded {
+                bucket: self.name.clone(),
+                limit,
+            });
+        }
+
+        if let Some(limit) = self.max_bytes
+            && after.total_bytes > limit
+            && after.total_bytes > usage.total_bytes
+        {
+            return Err(EngineError::ByteQuotaExceeded {
+                bucket: self.name.clone(),
+                limit,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// ## 在进程内维护的各个 bucket 的用量
+///
+/// 第一次读取某个 bucket 的用量时统计一次，之后随着元数据的写入和删除增减，没有读取过的 bucket 不维护。
+/// 只适用于独占元数据存储的后端，其他进程的修改不会反映在计数中
+#[derive(Default)]
+pub(crate) struct UsageCounters {
+    buckets: DashMap<String, BucketUsage>,
+}
+
+impl UsageCounters {
+    pub fn get(&self, bucket_name: &str) -> Option<BucketUsage> {
+        self.buckets.get(bucket_name).map(|v| *v)
+    }
+
+    /// 是否正在维护这个 bucket 的用量，写入元数据之前用它决定是否需要读出旧的大小
+    pub fn tracks(&self, bucket_name: &str) -> bool {
+        self.buckets.contains_key(bucket_name)
+    }
+
+    /// 记录统计得到的用量，统计期间已经有其他调用者记录过时以先记录的为准
+    pub fn load(&self, bucket_name: &str, usage: BucketUsage) -> BucketUsage {
+        *self.buckets.entry(bucket_name.to_string()).or_insert(usage)
+    }
+
+    /// 参见 [`BucketUsage::replace`]，没有维护这个 bucket 时什么都不做
+    pub fn replace(&self, bucket_name: &str, old: Option<u64>, new: Option<u64>) {
+        if let Some(mut usage) = self.buckets.get_mut(bucket_name) {
+            usage.replace(old, new);
+        }
+    }
+
+    pub fn forget(&self, bucket_name: &str) {
+        self.buckets.remove(bucket_name);
+    }
+}
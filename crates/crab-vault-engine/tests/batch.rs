@@ -0,0 +1,105 @@
+use std::path::PathBuf;
+
+use crab_vault_engine::{
+    DataEngine, DataSource, MetaEngine, MetaSource, ObjectMeta,
+    error::EngineError,
+    fs::{FsDataEngine, FsMetaEngine},
+    mem::{MemDataEngine, MemMetaEngine},
+};
+
+const BUCKET: &str = "bucket";
+
+fn names(names: &[&str]) -> Vec<String> {
+    names.iter().map(|v| v.to_string()).collect()
+}
+
+async fn check_data_engine<E: DataEngine + Sync>(engine: E) {
+    engine.create_bucket(BUCKET).await.unwrap();
+    for name in ["a", "b", "c"] {
+        engine.create_object(BUCKET, name, b"data").await.unwrap();
+    }
+
+    // 不存在的 object 也视为删除成功
+    let results = engine
+        .delete_objects(BUCKET, &names(&["a", "missing", "c"]))
+        .await;
+    assert_eq!(results.len(), 3);
+    assert!(results.iter().all(Result::is_ok));
+
+    for name in ["a", "c"] {
+        assert!(engine.read_object(BUCKET, name).await.is_err());
+    }
+    assert_eq!(engine.read_object(BUCKET, "b").await.unwrap(), b"data");
+
+    assert!(engine.delete_objects(BUCKET, &[]).await.is_empty());
+}
+
+async fn check_meta_engine<E: MetaEngine + Sync>(engine: E) {
+    for name in ["a", "b", "docs/c"] {
+        let meta = ObjectMeta {
+            bucket_name: BUCKET.to_string(),
+            object_name: name.to_string(),
+            ..ObjectMeta::default()
+        };
+        engine.create_object_meta(&meta).await.unwrap();
+    }
+
+    let results = engine
+        .delete_objects_meta(BUCKET, &names(&["a", "missing", "docs/c"]))
+        .await;
+    assert_eq!(results.len(), 3);
+    assert!(results.iter().all(Result::is_ok));
+
+    let remaining = engine
+        .list_objects_meta(BUCKET)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|v| v.object_name)
+        .collect::<Vec<_>>();
+    assert_eq!(remaining, ["b"]);
+}
+
+async fn clean(base_dir: &PathBuf) {
+    if base_dir.exists() {
+        tokio::fs::remove_dir_all(base_dir).await.unwrap();
+    }
+}
+
+#[tokio::test]
+async fn test_mem_delete_objects() {
+    check_data_engine(MemDataEngine::new("mem://").unwrap()).await;
+    check_meta_engine(MemMetaEngine::new("mem://").unwrap()).await;
+}
+
+#[tokio::test]
+async fn test_fs_delete_objects() {
+    let data_dir = PathBuf::from("./data_test").join("batch");
+    let meta_dir = PathBuf::from("./meta_test").join("batch");
+    clean(&data_dir).await;
+    clean(&meta_dir).await;
+
+    check_data_engine(FsDataEngine::new(&data_dir).unwrap()).await;
+    check_meta_engine(FsMetaEngine::new(&meta_dir).unwrap()).await;
+}
+
+#[tokio::test]
+async fn test_source_delete_objects() {
+    let data_dir = PathBuf::from("./data_test").join("batch_source");
+    let meta_dir = PathBuf::from("./meta_test").join("batch_source");
+    clean(&data_dir).await;
+    clean(&meta_dir).await;
+
+    check_data_engine(DataSource::new(data_dir.to_str().unwrap()).unwrap()).await;
+    check_meta_engine(MetaSource::new(meta_dir.to_str().unwrap()).unwrap()).await;
+}
+
+#[test]
+fn test_errors_with_messages_serialize_as_code() {
+    // 批量操作的结果中会逐个序列化错误，详细信息只出现在 Display 中
+    let value = serde_json::to_value(EngineError::InvalidArgument("bad name".into())).unwrap();
+    assert_eq!(value, serde_json::json!({ "code": "invalidArgument" }));
+
+    let value = serde_json::to_value(EngineError::BackendError("down".into())).unwrap();
+    assert_eq!(value, serde_json::json!({ "code": "backendError" }));
+}
@@ -0,0 +1,103 @@
+use chrono::{Duration, Utc};
+use crab_vault_engine::{
+    BucketMeta, ObjectMeta,
+    builder::{DEFAULT_CONTENT_TYPE, compute_etag, is_valid_etag},
+    error::EngineError,
+};
+use serde_json::json;
+
+#[test]
+fn test_bucket_meta_builder() {
+    let meta = BucketMeta::builder().name("bucket").build().unwrap();
+    assert_eq!(meta.name, "bucket");
+    assert_eq!(meta.user_meta, json!({}));
+    assert_eq!(meta.created_at, meta.updated_at);
+
+    assert!(matches!(
+        BucketMeta::builder().build(),
+        Err(EngineError::InvalidArgument(_))
+    ));
+}
+
+#[test]
+fn test_object_meta_from_data() {
+    let meta = ObjectMeta::builder()
+        .bucket_name("bucket")
+        .object_name("a/b.txt")
+        .user_meta(json!({ "k": "v" }))
+        .data(b"hello")
+        .build()
+        .unwrap();
+
+    assert_eq!(meta.size, 5);
+    assert_eq!(meta.etag, compute_etag(b"hello"));
+    assert!(is_valid_etag(&meta.etag));
+    assert_eq!(meta.content_type, DEFAULT_CONTENT_TYPE);
+    assert_eq!(meta.created_at, meta.updated_at);
+}
+
+#[test]
+fn test_object_meta_invariants() {
+    let base = || {
+        ObjectMeta::builder()
+            .bucket_name("bucket")
+            .object_name("object")
+    };
+
+    // 名称不能为空
+    assert!(
+        ObjectMeta::builder()
+            .bucket_name("bucket")
+            .data(b"")
+            .build()
+            .is_err()
+    );
+
+    // 既没有数据，也没有 size 和 etag
+    assert!(base().build().is_err());
+    assert!(base().size(1).build().is_err());
+
+    // size 和 etag 必须与数据一致
+    assert!(base().data(b"abc").size(4).build().is_err());
+    assert!(
+        base()
+            .data(b"abc")
+            .etag(compute_etag(b"abd"))
+            .build()
+            .is_err()
+    );
+    assert!(
+        base()
+            .data(b"abc")
+            .size(3)
+            .etag(compute_etag(b"abc"))
+            .build()
+            .is_ok()
+    );
+
+    // etag 必须是 sha256 摘要的 base64 编码
+    assert!(base().size(3).etag("not an etag").build().is_err());
+    assert!(base().size(3).etag(compute_etag(b"abc")).build().is_ok());
+}
+
+#[test]
+fn test_timestamps() {
+    let created_at = Utc::now() - Duration::days(1);
+
+    let meta = BucketMeta::builder()
+        .name("bucket")
+        .created_at(created_at)
+        .build()
+        .unwrap();
+    assert_eq!(meta.created_at, created_at);
+    assert!(meta.updated_at > created_at);
+
+    assert!(
+        BucketMeta::builder()
+            .name("bucket")
+            .created_at(created_at)
+            .updated_at(created_at - Duration::seconds(1))
+            .build()
+            .is_err()
+    );
+}
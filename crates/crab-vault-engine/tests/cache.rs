@@ -0,0 +1,134 @@
+use crab_vault_engine::{
+    DataEngine, cache::CachedDataEngine, error::EngineError, mem::MemDataEngine,
+};
+
+const BUCKET: &str = "bucket";
+
+async fn setup(max_memory: usize) -> CachedDataEngine<MemDataEngine> {
+    let engine =
+        CachedDataEngine::new(MemDataEngine::new("mem://").unwrap(), max_memory).max_object_size(8);
+    engine.create_bucket(BUCKET).await.unwrap();
+    engine
+}
+
+#[tokio::test]
+async fn test_cache_serves_reads_and_invalidates_on_write() {
+    let engine = setup(64).await;
+    engine.create_object(BUCKET, "a", b"first").await.unwrap();
+    assert_eq!(engine.read_object(BUCKET, "a").await.unwrap(), b"first");
+    assert_eq!(engine.cached_bytes(), 5);
+
+    // 绕过缓存直接修改后端，读到的仍然是缓存中的数据
+    engine
+        .inner()
+        .create_object(BUCKET, "a", b"bypass")
+        .await
+        .unwrap();
+    assert_eq!(engine.read_object(BUCKET, "a").await.unwrap(), b"first");
+
+    engine.create_object(BUCKET, "a", b"second").await.unwrap();
+    assert_eq!(engine.read_object(BUCKET, "a").await.unwrap(), b"second");
+
+    engine.append_object(BUCKET, "a", b"!").await.unwrap();
+    assert_eq!(engine.read_object(BUCKET, "a").await.unwrap(), b"second!");
+
+    engine.write_object_at(BUCKET, "a", 0, b"S").await.unwrap();
+    assert_eq!(engine.read_object(BUCKET, "a").await.unwrap(), b"Second!");
+
+    engine.punch_hole(BUCKET, "a", 0, 1).await.unwrap();
+    assert_eq!(engine.read_object(BUCKET, "a").await.unwrap(), b"\0econd!");
+
+    engine.create_object(BUCKET, "b", b"other").await.unwrap();
+    engine.copy_object(BUCKET, "b", BUCKET, "a").await.unwrap();
+    assert_eq!(engine.read_object(BUCKET, "a").await.unwrap(), b"other");
+
+    engine.delete_object(BUCKET, "a").await.unwrap();
+    assert!(matches!(
+        engine.read_object(BUCKET, "a").await,
+        Err(EngineError::ObjectNotFound { .. })
+    ));
+}
+
+#[tokio::test]
+async fn test_cache_skips_large_objects() {
+    let engine = setup(64).await;
+    engine
+        .create_object(BUCKET, "large", b"more than eight bytes")
+        .await
+        .unwrap();
+    engine.read_object(BUCKET, "large").await.unwrap();
+    assert_eq!(engine.cached_bytes(), 0);
+}
+
+#[tokio::test]
+async fn test_cache_evicts_least_recently_read() {
+    let engine = setup(12).await;
+    for name in ["a", "b", "c"] {
+        engine.create_object(BUCKET, name, b"1234").await.unwrap();
+    }
+    engine.read_object(BUCKET, "a").await.unwrap();
+    engine.read_object(BUCKET, "b").await.unwrap();
+    engine.read_object(BUCKET, "c").await.unwrap();
+    assert_eq!(engine.cached_bytes(), 12);
+
+    // `a` 最近被读取过，放入 `d` 时淘汰的是 `b`
+    engine.read_object(BUCKET, "a").await.unwrap();
+    engine.create_object(BUCKET, "d", b"1234").await.unwrap();
+    engine.read_object(BUCKET, "d").await.unwrap();
+    assert_eq!(engine.cached_bytes(), 12);
+
+    for name in ["a", "b", "c", "d"] {
+        engine
+            .inner()
+            .create_object(BUCKET, name, b"new!")
+            .await
+            .unwrap();
+    }
+    assert_eq!(engine.read_object(BUCKET, "a").await.unwrap(), b"1234");
+    assert_eq!(engine.read_object(BUCKET, "b").await.unwrap(), b"new!");
+}
+
+#[tokio::test]
+async fn test_cache_invalidated_by_batch_and_bucket_delete() {
+    let engine = setup(64).await;
+    for name in ["a", "b"] {
+        engine.create_object(BUCKET, name, b"data").await.unwrap();
+        engine.read_object(BUCKET, name).await.unwrap();
+    }
+
+    let results = engine.delete_objects(BUCKET, &["a".to_string()]).await;
+    assert!(results.iter().all(Result::is_ok));
+    assert_eq!(engine.cached_bytes(), 4);
+
+    // 只有空的 bucket 才能删除，`b` 仍然在缓存中
+    engine.inner().delete_object(BUCKET, "b").await.unwrap();
+    engine.delete_bucket(BUCKET).await.unwrap();
+    assert_eq!(engine.cached_bytes(), 0);
+}
+
+#[tokio::test]
+async fn test_cache_filled_by_reader() {
+    use futures::AsyncReadExt;
+
+    let engine = setup(64).await;
+    engine.create_object(BUCKET, "a", b"small").await.unwrap();
+    engine
+        .create_object(BUCKET, "large", b"more than eight bytes")
+        .await
+        .unwrap();
+
+    for name in ["a", "large"] {
+        let mut reader = engine.read_object_reader(BUCKET, name).await.unwrap();
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).await.unwrap();
+    }
+    assert_eq!(engine.cached_bytes(), 5);
+
+    // 读取期间发生了写入，读出的数据不会放入缓存
+    engine.create_object(BUCKET, "b", b"old").await.unwrap();
+    let mut reader = engine.read_object_reader(BUCKET, "b").await.unwrap();
+    engine.create_object(BUCKET, "b", b"new").await.unwrap();
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data).await.unwrap();
+    assert_eq!(engine.cached_bytes(), 5);
+}
@@ -0,0 +1,47 @@
+use std::sync::Arc;
+
+use chrono::{DateTime, TimeDelta};
+use crab_vault_engine::{
+    BucketMeta, MetaEngine, ObjectMeta,
+    clock::{self, ManualClock, SystemClock},
+    mem::MemMetaEngine,
+};
+
+#[tokio::test]
+async fn test_manual_clock() {
+    let start = DateTime::parse_from_rfc3339("2025-01-01T00:00:00Z")
+        .unwrap()
+        .to_utc();
+    let manual = Arc::new(ManualClock::new(start));
+    assert!(clock::install(manual.clone()));
+
+    // 只能安装一次
+    assert!(!clock::install(Arc::new(SystemClock)));
+    assert_eq!(clock::now(), start);
+
+    let bucket = BucketMeta::builder().name("bucket").build().unwrap();
+    assert_eq!(bucket.created_at, start);
+    assert_eq!(
+        BucketMeta::new("bucket".into(), Default::default()).updated_at,
+        start
+    );
+
+    let engine = MemMetaEngine::new("mem://").unwrap();
+    let meta = ObjectMeta::builder()
+        .bucket_name("bucket")
+        .object_name("a.txt")
+        .data(b"a")
+        .build()
+        .unwrap();
+    engine.create_object_meta(&meta).await.unwrap();
+
+    // 只有手动修改时时间才会变化
+    manual.advance(TimeDelta::seconds(90));
+    engine.touch_object("bucket", "a.txt").await.unwrap();
+    let touched = engine.read_object_meta("bucket", "a.txt").await.unwrap();
+    assert_eq!(touched.created_at, start);
+    assert_eq!(touched.updated_at, start + TimeDelta::seconds(90));
+
+    manual.set(start);
+    assert_eq!(clock::now(), start);
+}
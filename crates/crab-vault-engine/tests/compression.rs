@@ -0,0 +1,118 @@
+use crab_vault_engine::{
+    DataEngine, MetaEngine, ObjectMeta,
+    compression::{Codec, Compression},
+    error::EngineError,
+    journal::{self, Finding},
+    mem::{MemDataEngine, MemMetaEngine},
+};
+
+const BUCKET: &str = "bucket";
+
+fn text() -> Vec<u8> {
+    b"crab vault stores objects, ".repeat(64)
+}
+
+#[test]
+fn test_round_trip() {
+    let data = text();
+    for codec in [Codec::Gzip, Codec::Zstd] {
+        let (compression, compressed) = Compression::apply(codec, &data).unwrap().unwrap();
+        assert_eq!(compression.codec, codec);
+        assert_eq!(compression.stored_size, compressed.len() as u64);
+        assert!(compressed.len() < data.len());
+        assert_eq!(codec.decompress(&compressed).unwrap(), data);
+
+        // 损坏的数据无法解压
+        assert!(matches!(
+            codec.decompress(&data),
+            Err(EngineError::Compression(_))
+        ));
+    }
+}
+
+#[test]
+fn test_incompressible() {
+    // 太短的数据压缩之后只会变大
+    assert!(Compression::apply(Codec::Gzip, b"crab").unwrap().is_none());
+    assert!(Compression::apply(Codec::Zstd, b"").unwrap().is_none());
+}
+
+#[test]
+fn test_meta() {
+    let data = text();
+    let (compression, compressed) = Compression::apply(Codec::Zstd, &data).unwrap().unwrap();
+    let meta = ObjectMeta::builder()
+        .bucket_name(BUCKET)
+        .object_name("a.txt")
+        .data(&data)
+        .compression(Some(compression))
+        .build()
+        .unwrap();
+
+    // 大小是原始数据的
+    assert_eq!(meta.size, data.len() as u64);
+    assert_eq!(meta.decode(compressed).unwrap(), data);
+
+    let json = serde_json::to_value(&meta).unwrap();
+    assert_eq!(json["compression"]["codec"], "zstd");
+    assert_eq!(json["compression"]["stored-size"], compression.stored_size);
+    assert_eq!(serde_json::from_value::<ObjectMeta>(json).unwrap(), meta);
+
+    // 没有压缩时原样返回，序列化结果中也没有这一项
+    let plain = ObjectMeta::builder()
+        .bucket_name(BUCKET)
+        .object_name("b.txt")
+        .data(&data)
+        .build()
+        .unwrap();
+    assert_eq!(plain.decode(data.clone()).unwrap(), data);
+    assert!(
+        serde_json::to_value(&plain)
+            .unwrap()
+            .get("compression")
+            .is_none()
+    );
+}
+
+#[tokio::test]
+async fn test_reconcile_compressed() {
+    let data_src = MemDataEngine::new("mem://").unwrap();
+    let meta_src = MemMetaEngine::new("mem://").unwrap();
+    data_src.create_bucket(BUCKET).await.unwrap();
+
+    let data = text();
+    let (compression, compressed) = Compression::apply(Codec::Gzip, &data).unwrap().unwrap();
+    let meta = ObjectMeta::builder()
+        .bucket_name(BUCKET)
+        .object_name("a.txt")
+        .data(&data)
+        .compression(Some(compression))
+        .build()
+        .unwrap();
+
+    // 保存的是压缩过的数据，还原之后与元数据一致
+    data_src
+        .create_object(BUCKET, "a.txt", &compressed)
+        .await
+        .unwrap();
+    let report = journal::reconcile(&data_src, &meta_src, BUCKET, "a.txt", Some(&meta), false)
+        .await
+        .unwrap();
+    assert_eq!(report.finding, Finding::Unfinished);
+
+    meta_src.create_object_meta(&meta).await.unwrap();
+    let report = journal::reconcile(&data_src, &meta_src, BUCKET, "a.txt", None, false)
+        .await
+        .unwrap();
+    assert_eq!(report.finding, Finding::Consistent);
+
+    // 数据被替换为未压缩的内容之后不再一致
+    data_src
+        .create_object(BUCKET, "a.txt", &data)
+        .await
+        .unwrap();
+    let report = journal::reconcile(&data_src, &meta_src, BUCKET, "a.txt", None, false)
+        .await
+        .unwrap();
+    assert_eq!(report.finding, Finding::StaleMeta);
+}
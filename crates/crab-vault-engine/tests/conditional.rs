@@ -0,0 +1,169 @@
+use std::{path::PathBuf, sync::Arc};
+
+use crab_vault_engine::{
+    DataEngine, DataSource, MetaEngine, MetaSource, ObjectMeta,
+    error::EngineError,
+    fs::{FsDataEngine, FsMetaEngine},
+    mem::{MemDataEngine, MemMetaEngine},
+};
+
+const BUCKET: &str = "bucket";
+
+async fn check_data_engine<E: DataEngine + Send + Sync + 'static>(engine: E) {
+    assert!(matches!(
+        engine.create_object_if_absent(BUCKET, "claim", b"a").await,
+        Err(EngineError::BucketNotFound { .. })
+    ));
+
+    engine.create_bucket(BUCKET).await.unwrap();
+    engine
+        .create_object_if_absent(BUCKET, "claim", b"a")
+        .await
+        .unwrap();
+
+    // 已经存在时不会覆盖
+    assert!(matches!(
+        engine.create_object_if_absent(BUCKET, "claim", b"b").await,
+        Err(EngineError::ObjectAlreadyExists { .. })
+    ));
+    assert_eq!(engine.read_object(BUCKET, "claim").await.unwrap(), b"a");
+
+    // 删除之后可以再次创建
+    engine.delete_object(BUCKET, "claim").await.unwrap();
+    engine
+        .create_object_if_absent(BUCKET, "claim", b"c")
+        .await
+        .unwrap();
+    assert_eq!(engine.read_object(BUCKET, "claim").await.unwrap(), b"c");
+
+    // 并发争抢同一个 object 时只有一个调用者成功
+    let engine = Arc::new(engine);
+    let tasks = (0..16u8)
+        .map(|i| {
+            let engine = engine.clone();
+            tokio::spawn(async move {
+                engine
+                    .create_object_if_absent(BUCKET, "leader", &[i])
+                    .await
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let mut winners = vec![];
+    for (i, task) in tasks.into_iter().enumerate() {
+        match task.await.unwrap() {
+            Ok(()) => winners.push(i as u8),
+            Err(EngineError::ObjectAlreadyExists { .. }) => {}
+            Err(e) => panic!("unexpected error: {e}"),
+        }
+    }
+
+    assert_eq!(winners.len(), 1);
+    assert_eq!(engine.read_object(BUCKET, "leader").await.unwrap(), winners);
+}
+
+fn meta(etag: &str) -> ObjectMeta {
+    ObjectMeta {
+        bucket_name: BUCKET.to_string(),
+        object_name: "counter".to_string(),
+        etag: etag.to_string(),
+        ..ObjectMeta::default()
+    }
+}
+
+async fn check_meta_engine<E: MetaEngine + Send + Sync + 'static>(engine: E) {
+    // 不存在时与 etag 不同一样失败
+    assert!(matches!(
+        engine.create_object_meta_if(&meta("1"), "0").await,
+        Err(EngineError::EtagMismatch { .. })
+    ));
+
+    engine.create_object_meta(&meta("0")).await.unwrap();
+    engine.create_object_meta_if(&meta("1"), "0").await.unwrap();
+
+    // etag 已经改变时不会覆盖
+    assert!(matches!(
+        engine.create_object_meta_if(&meta("2"), "0").await,
+        Err(EngineError::EtagMismatch { .. })
+    ));
+    assert_eq!(
+        engine.read_object_meta(BUCKET, "counter").await.unwrap().etag,
+        "1"
+    );
+
+    // 并发地从同一个 etag 出发修改时只有一个调用者成功
+    let engine = Arc::new(engine);
+    let tasks = (0..16)
+        .map(|i| {
+            let engine = engine.clone();
+            tokio::spawn(async move {
+                engine
+                    .create_object_meta_if(&meta(&format!("next-{i}")), "1")
+                    .await
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let mut winners = vec![];
+    for (i, task) in tasks.into_iter().enumerate() {
+        match task.await.unwrap() {
+            Ok(()) => winners.push(format!("next-{i}")),
+            Err(EngineError::EtagMismatch { .. }) => {}
+            Err(e) => panic!("unexpected error: {e}"),
+        }
+    }
+
+    assert_eq!(winners.len(), 1);
+    assert_eq!(
+        engine.read_object_meta(BUCKET, "counter").await.unwrap().etag,
+        winners[0]
+    );
+}
+
+async fn clean(base_dir: &PathBuf) {
+    if base_dir.exists() {
+        tokio::fs::remove_dir_all(base_dir).await.unwrap();
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_mem_create_if_absent() {
+    check_data_engine(MemDataEngine::new("mem://").unwrap()).await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_fs_create_if_absent() {
+    let data_dir = PathBuf::from("./data_test").join("conditional");
+    clean(&data_dir).await;
+
+    check_data_engine(FsDataEngine::new(&data_dir).unwrap()).await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_source_create_if_absent() {
+    let data_dir = PathBuf::from("./data_test").join("conditional_source");
+    clean(&data_dir).await;
+
+    check_data_engine(DataSource::new(data_dir.to_str().unwrap()).unwrap()).await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_mem_create_meta_if() {
+    check_meta_engine(MemMetaEngine::new("mem://").unwrap()).await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_fs_create_meta_if() {
+    let meta_dir = PathBuf::from("./meta_test").join("conditional");
+    clean(&meta_dir).await;
+
+    check_meta_engine(FsMetaEngine::new(&meta_dir).unwrap()).await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_source_create_meta_if() {
+    let meta_dir = PathBuf::from("./meta_test").join("conditional_source");
+    clean(&meta_dir).await;
+
+    check_meta_engine(MetaSource::new(meta_dir.to_str().unwrap()).unwrap()).await;
+}
@@ -0,0 +1,65 @@
+use std::path::PathBuf;
+
+use crab_vault_engine::{
+    DataEngine, DataSource, error::EngineError, fs::FsDataEngine, mem::MemDataEngine,
+};
+
+async fn check_engine<E: DataEngine + Sync>(engine: E, nested: bool) {
+    for bucket in ["src", "dst"] {
+        engine.create_bucket(bucket).await.unwrap();
+    }
+    engine.create_object("src", "a", b"hello").await.unwrap();
+
+    engine.copy_object("src", "a", "dst", "b").await.unwrap();
+    assert_eq!(engine.read_object("dst", "b").await.unwrap(), b"hello");
+    // 源 object 保持不变
+    assert_eq!(engine.read_object("src", "a").await.unwrap(), b"hello");
+
+    // 复制到同一个 bucket 中，并覆盖已有的 object
+    engine.create_object("src", "c", b"old").await.unwrap();
+    engine.copy_object("src", "a", "src", "c").await.unwrap();
+    assert_eq!(engine.read_object("src", "c").await.unwrap(), b"hello");
+
+    if nested {
+        engine
+            .copy_object("src", "a", "dst", "docs/img/a")
+            .await
+            .unwrap();
+        assert_eq!(
+            engine.read_object("dst", "docs/img/a").await.unwrap(),
+            b"hello"
+        );
+    }
+
+    assert!(matches!(
+        engine.copy_object("src", "missing", "dst", "b").await,
+        Err(EngineError::ObjectNotFound { .. })
+    ));
+    assert!(matches!(
+        engine.copy_object("src", "a", "missing", "b").await,
+        Err(EngineError::BucketNotFound { .. })
+    ));
+}
+
+#[tokio::test]
+async fn test_mem_copy() {
+    check_engine(MemDataEngine::new("mem://").unwrap(), false).await;
+}
+
+#[tokio::test]
+async fn test_fs_copy() {
+    let base_dir = PathBuf::from("./data_test").join("copy");
+    if base_dir.exists() {
+        tokio::fs::remove_dir_all(&base_dir).await.unwrap();
+    }
+    check_engine(FsDataEngine::new(&base_dir).unwrap(), true).await;
+}
+
+#[tokio::test]
+async fn test_source_copy() {
+    let base_dir = PathBuf::from("./data_test").join("copy_source");
+    if base_dir.exists() {
+        tokio::fs::remove_dir_all(&base_dir).await.unwrap();
+    }
+    check_engine(DataSource::new(base_dir.to_str().unwrap()).unwrap(), true).await;
+}
@@ -0,0 +1,104 @@
+use crab_vault_engine::{
+    BucketMeta, MetaEngine, MetaSource, ObjectMeta,
+    error::EngineError,
+    list::{ListObjectsQuery, OnCorrupt},
+};
+use futures::TryStreamExt;
+
+async fn prepare(base_dir: &str) {
+    if std::path::Path::new(base_dir).exists() {
+        tokio::fs::remove_dir_all(base_dir).await.unwrap();
+    }
+
+    let source = MetaSource::new(base_dir).unwrap();
+    for name in ["a", "b"] {
+        let meta = BucketMeta {
+            name: name.to_string(),
+            ..BucketMeta::default()
+        };
+        source.create_bucket_meta(&meta).await.unwrap();
+    }
+    for name in ["docs/x", "docs/y", "z"] {
+        let meta = ObjectMeta {
+            bucket_name: "a".to_string(),
+            object_name: name.to_string(),
+            ..ObjectMeta::default()
+        };
+        source.create_object_meta(&meta).await.unwrap();
+    }
+
+    // 写了一半的元数据文件
+    let base_dir = std::path::Path::new(base_dir);
+    tokio::fs::write(base_dir.join("buckets/broken.json"), "{\"name\":")
+        .await
+        .unwrap();
+    tokio::fs::write(base_dir.join("objects/a/docs/y.json"), "not json")
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_corrupt_entries_fail() {
+    let base_dir = "./meta_test/corrupt_fail";
+    prepare(base_dir).await;
+    let source = MetaSource::new(base_dir)
+        .unwrap()
+        .on_corrupt(OnCorrupt::Fail);
+
+    assert!(matches!(
+        source.list_buckets_meta().await,
+        Err(EngineError::CorruptMeta { entry }) if entry == "broken"
+    ));
+    assert!(matches!(
+        source
+            .list_objects_meta_page("a", &ListObjectsQuery::default())
+            .await,
+        Err(EngineError::CorruptMeta { entry }) if entry == "docs/y"
+    ));
+
+    let streamed: Result<Vec<_>, _> = source.stream_buckets_meta().try_collect().await;
+    assert!(matches!(streamed, Err(EngineError::CorruptMeta { .. })));
+}
+
+#[tokio::test]
+async fn test_corrupt_entries_skipped_by_default() {
+    let base_dir = "./meta_test/corrupt_skip";
+    prepare(base_dir).await;
+    let source = MetaSource::new(base_dir).unwrap();
+
+    let (buckets, skipped) = source.list_buckets_meta_reporting().await.unwrap();
+    let mut names: Vec<_> = buckets.into_iter().map(|v| v.name).collect();
+    names.sort();
+    assert_eq!(names, ["a", "b"]);
+    assert_eq!(skipped, ["broken"]);
+
+    let page = source
+        .list_objects_meta_page("a", &ListObjectsQuery::default())
+        .await
+        .unwrap();
+    let names: Vec<_> = page
+        .objects
+        .iter()
+        .map(|v| v.object_name.as_str())
+        .collect();
+    assert_eq!(names, ["docs/x", "z"]);
+    assert_eq!(page.skipped, ["docs/y"]);
+
+    // 不在前缀内的损坏条目不会被报告
+    let query = ListObjectsQuery {
+        prefix: Some("z".to_string()),
+        ..ListObjectsQuery::default()
+    };
+    let page = source.list_objects_meta_page("a", &query).await.unwrap();
+    assert_eq!(page.objects.len(), 1);
+    assert!(page.skipped.is_empty());
+
+    assert_eq!(source.list_objects_meta("a").await.unwrap().len(), 2);
+
+    let streamed: Vec<_> = source
+        .stream_objects_meta("a", &ListObjectsQuery::default())
+        .try_collect()
+        .await
+        .unwrap();
+    assert_eq!(streamed.len(), 2);
+}
@@ -0,0 +1,328 @@
+use std::{collections::HashMap, sync::Arc};
+
+use base64::{Engine, prelude::BASE64_STANDARD};
+use crab_vault_engine::{
+    BucketMeta, DataEngine, MetaEngine, ObjectMeta,
+    crypto::{
+        self, CustomerKey, DataKey, ENCRYPTION_META_KEY, KEY_LEN, KeyRing, MasterKey,
+        stream::{self, SEGMENT_LEN, StreamDecryptor, StreamEncryptor, TAG_LEN},
+    },
+    error::{EngineError, EngineResult},
+    journal::{self, Finding},
+    mem::{MemDataEngine, MemMetaEngine},
+};
+use serde_json::json;
+
+fn key_ring(keys: &[(&str, u8)], active: &str) -> KeyRing {
+    let keys: HashMap<_, _> = keys
+        .iter()
+        .map(|(id, byte)| (id.to_string(), [*byte; 32]))
+        .collect();
+    KeyRing::new(keys, active.to_string()).unwrap()
+}
+
+#[test]
+fn test_wrap_and_unwrap_data_key() {
+    let ring = key_ring(&[("k1", 1)], "k1");
+    let data_key = DataKey::generate();
+
+    let wrapped = ring.wrap_key(&data_key).unwrap();
+    assert_eq!(wrapped.master_key_id, "k1");
+
+    let unwrapped = ring.unwrap_key(&wrapped).unwrap();
+    assert_eq!(unwrapped.as_bytes(), data_key.as_bytes());
+}
+
+#[test]
+fn test_rewrap_keeps_data_key() {
+    let old_ring = key_ring(&[("k1", 1)], "k1");
+    let new_ring = key_ring(&[("k1", 1), ("k2", 2)], "k2");
+
+    let data_key = DataKey::generate();
+    let wrapped = old_ring.wrap_key(&data_key).unwrap();
+
+    let rewrapped = new_ring.rewrap(&wrapped).unwrap().unwrap();
+    assert_eq!(rewrapped.master_key_id, "k2");
+    assert_eq!(
+        new_ring.unwrap_key(&rewrapped).unwrap().as_bytes(),
+        data_key.as_bytes()
+    );
+
+    // 已经由当前主密钥包裹的，无需再次处理
+    assert!(new_ring.rewrap(&rewrapped).unwrap().is_none());
+}
+
+#[test]
+fn test_unwrap_with_unknown_master_key_fails() {
+    let ring = key_ring(&[("k1", 1)], "k1");
+    let other = key_ring(&[("k2", 2)], "k2");
+
+    let wrapped = other.generate_wrapped().unwrap();
+    assert!(matches!(
+        ring.unwrap_key(&wrapped),
+        Err(EngineError::Encryption(_))
+    ));
+}
+
+#[test]
+fn test_active_key_must_exist() {
+    let result = KeyRing::new(HashMap::from([("k1".to_string(), [0; 32])]), "k2".into());
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_bucket_meta_without_data_key_still_deserializes() {
+    let json = r#"{"name":"b","user-meta":{},"created-at":"2025-01-01T00:00:00Z","updated-at":"2025-01-01T00:00:00Z"}"#;
+    let meta: BucketMeta = serde_json::from_str(json).unwrap();
+    assert!(meta.data_key.is_none());
+    assert!(!serde_json::to_string(&meta).unwrap().contains("data-key"));
+}
+
+#[test]
+fn test_stream_round_trip() {
+    let key = DataKey::generate();
+    for len in [
+        0,
+        1,
+        SEGMENT_LEN - 1,
+        SEGMENT_LEN,
+        SEGMENT_LEN + 1,
+        3 * SEGMENT_LEN,
+    ] {
+        let data: Vec<u8> = (0..len).map(|v| v as u8).collect();
+        let ciphertext = stream::encrypt(&key, &data).unwrap();
+        assert_eq!(ciphertext.len() as u64, stream::encrypted_len(len as u64));
+        assert_eq!(stream::decrypt(&key, &ciphertext).unwrap(), data);
+    }
+}
+
+#[test]
+fn test_stream_in_pieces() {
+    let key = DataKey::generate();
+    let data: Vec<u8> = (0..2 * SEGMENT_LEN + 100)
+        .map(|v| (v % 251) as u8)
+        .collect();
+
+    // 任意切分输入，结果与一次加密相同
+    let mut encryptor = StreamEncryptor::new(&key);
+    let mut ciphertext = vec![];
+    for chunk in data.chunks(1000) {
+        ciphertext.extend(encryptor.update(chunk).unwrap());
+    }
+    ciphertext.extend(encryptor.finish().unwrap());
+
+    let mut decryptor = StreamDecryptor::new(&key);
+    let mut plaintext = vec![];
+    for chunk in ciphertext.chunks(777) {
+        plaintext.extend(decryptor.update(chunk).unwrap());
+    }
+    plaintext.extend(decryptor.finish().unwrap());
+    assert_eq!(plaintext, data);
+}
+
+#[test]
+fn test_stream_detects_tampering() {
+    let key = DataKey::generate();
+    let data = vec![7; 2 * SEGMENT_LEN + 10];
+    let ciphertext = stream::encrypt(&key, &data).unwrap();
+    let decrypt = |v: &[u8]| stream::decrypt(&key, v);
+
+    // 截断在分段边界上也能被发现
+    let truncated = &ciphertext[..2 * (SEGMENT_LEN + TAG_LEN)];
+    assert!(matches!(
+        decrypt(truncated),
+        Err(EngineError::Encryption(_))
+    ));
+
+    let mut flipped = ciphertext.clone();
+    flipped[10] ^= 1;
+    assert!(matches!(decrypt(&flipped), Err(EngineError::Encryption(_))));
+
+    let mut swapped = ciphertext.clone();
+    swapped[..SEGMENT_LEN + TAG_LEN]
+        .copy_from_slice(&ciphertext[SEGMENT_LEN + TAG_LEN..2 * (SEGMENT_LEN + TAG_LEN)]);
+    assert!(matches!(decrypt(&swapped), Err(EngineError::Encryption(_))));
+
+    let other = DataKey::generate();
+    assert!(stream::decrypt(&other, &ciphertext).is_err());
+}
+
+#[test]
+fn test_seal_and_open_object() {
+    let ring = key_ring(&[("k1", 1)], "k1");
+    let data = b"top secret".to_vec();
+
+    let (encryption, ciphertext) = ring.seal(&data).unwrap();
+    assert_ne!(ciphertext, data);
+    assert_eq!(encryption.data_key.master_key_id, "k1");
+    assert_eq!(ring.open(&encryption, &ciphertext).unwrap(), data);
+
+    // 每次加密都使用新的数据密钥
+    let (again, _) = ring.seal(&data).unwrap();
+    assert_ne!(again.data_key, encryption.data_key);
+
+    // 轮换主密钥之后，旧的数据仍然可以用重新包裹过的数据密钥解密
+    let new_ring = key_ring(&[("k1", 1), ("k2", 2)], "k2");
+    let mut rotated = encryption.clone();
+    rotated.data_key = new_ring.rewrap(&encryption.data_key).unwrap().unwrap();
+    assert_eq!(new_ring.open(&rotated, &ciphertext).unwrap(), data);
+}
+
+#[test]
+fn test_customer_key() {
+    let customer_key = CustomerKey::new([7; KEY_LEN]);
+    assert_eq!(
+        customer_key.fingerprint(),
+        CustomerKey::new([7; KEY_LEN]).fingerprint()
+    );
+    assert_ne!(
+        customer_key.fingerprint(),
+        CustomerKey::new([8; KEY_LEN]).fingerprint()
+    );
+
+    let data = b"bring your own key".to_vec();
+    let (mut encryption, ciphertext) = customer_key.key_ring().seal(&data).unwrap();
+    customer_key.mark(&mut encryption);
+    assert_eq!(encryption.data_key.master_key_id, crypto::CUSTOMER_KEY_ID);
+    assert_eq!(
+        customer_key
+            .key_ring()
+            .open(&encryption, &ciphertext)
+            .unwrap(),
+        data
+    );
+
+    // 其他密钥无法解开数据密钥
+    let other = CustomerKey::new([8; KEY_LEN]);
+    assert!(other.key_ring().open(&encryption, &ciphertext).is_err());
+
+    let mut meta = ObjectMeta::builder()
+        .bucket_name("b")
+        .object_name("o")
+        .data(&data)
+        .build()
+        .unwrap();
+    meta.check_customer_key(None).unwrap();
+    assert!(matches!(
+        meta.check_customer_key(Some(&customer_key)),
+        Err(EngineError::CustomerKeyMismatch { .. })
+    ));
+
+    meta.set_encryption(Some(&encryption)).unwrap();
+    meta.check_customer_key(Some(&customer_key)).unwrap();
+    assert!(matches!(
+        meta.check_customer_key(None),
+        Err(EngineError::CustomerKeyRequired { .. })
+    ));
+    assert!(matches!(
+        meta.check_customer_key(Some(&other)),
+        Err(EngineError::CustomerKeyMismatch { .. })
+    ));
+
+    // 只保存了指纹，而不是密钥本身
+    let saved = serde_json::to_string(&meta.user_meta).unwrap();
+    assert!(saved.contains(customer_key.fingerprint()));
+    assert!(!saved.contains(&BASE64_STANDARD.encode([7; KEY_LEN])));
+}
+
+#[test]
+fn test_encryption_in_user_meta() {
+    let ring = key_ring(&[("k1", 1)], "k1");
+    let (encryption, _) = ring.seal(b"data").unwrap();
+
+    let mut meta = ObjectMeta::builder()
+        .bucket_name("b")
+        .object_name("o")
+        .data(b"data")
+        .user_meta(json!({ "owner": "crab" }))
+        .build()
+        .unwrap();
+    assert!(meta.encryption().unwrap().is_none());
+
+    meta.set_encryption(Some(&encryption)).unwrap();
+    assert_eq!(meta.encryption().unwrap(), Some(encryption.clone()));
+    assert!(meta.user_meta[ENCRYPTION_META_KEY]["stored-etag"].is_string());
+    assert!(crypto::is_reserved_meta_key(ENCRYPTION_META_KEY));
+    assert!(!crypto::is_reserved_meta_key("owner"));
+
+    // 返回给客户端时只剩下客户端自己的元数据
+    let hidden = meta.clone().hide_reserved_meta();
+    assert_eq!(hidden.user_meta, json!({ "owner": "crab" }));
+
+    meta.set_encryption(None).unwrap();
+    assert_eq!(meta.user_meta, json!({ "owner": "crab" }));
+
+    meta.user_meta[ENCRYPTION_META_KEY] = json!("garbage");
+    assert!(matches!(meta.encryption(), Err(EngineError::Encryption(_))));
+}
+
+/// 用异或代替加密的主密钥，模拟外部的 KMS
+struct XorMasterKey(u8);
+
+impl MasterKey for XorMasterKey {
+    fn wrap(&self, data_key: &[u8; KEY_LEN]) -> EngineResult<(Vec<u8>, Vec<u8>)> {
+        Ok((vec![], data_key.iter().map(|v| v ^ self.0).collect()))
+    }
+
+    fn unwrap(&self, _nonce: &[u8], ciphertext: &[u8]) -> EngineResult<[u8; KEY_LEN]> {
+        let key: Vec<u8> = ciphertext.iter().map(|v| v ^ self.0).collect();
+        key.try_into()
+            .map_err(|_| EngineError::Encryption("bad key".into()))
+    }
+}
+
+#[test]
+fn test_custom_master_key() {
+    let master_keys = HashMap::from([(
+        "kms".to_string(),
+        Arc::new(XorMasterKey(0x5a)) as Arc<dyn MasterKey>,
+    )]);
+    let ring = KeyRing::with_master_keys(master_keys, "kms".into()).unwrap();
+
+    let data_key = DataKey::generate();
+    let wrapped = ring.wrap_key(&data_key).unwrap();
+    assert_eq!(wrapped.nonce, "");
+    assert_eq!(
+        ring.unwrap_key(&wrapped).unwrap().as_bytes(),
+        data_key.as_bytes()
+    );
+
+    let (encryption, ciphertext) = ring.seal(b"via kms").unwrap();
+    assert_eq!(ring.open(&encryption, &ciphertext).unwrap(), b"via kms");
+}
+
+#[tokio::test]
+async fn test_reconcile_encrypted() {
+    let ring = key_ring(&[("k1", 1)], "k1");
+    let data_src = MemDataEngine::new("mem://").unwrap();
+    let meta_src = MemMetaEngine::new("mem://").unwrap();
+    data_src.create_bucket("b").await.unwrap();
+
+    let data = b"plaintext".to_vec();
+    let (encryption, ciphertext) = ring.seal(&data).unwrap();
+    let mut meta = ObjectMeta::builder()
+        .bucket_name("b")
+        .object_name("o")
+        .data(&data)
+        .build()
+        .unwrap();
+    meta.set_encryption(Some(&encryption)).unwrap();
+
+    data_src.create_object("b", "o", &ciphertext).await.unwrap();
+    meta_src.create_object_meta(&meta).await.unwrap();
+
+    // 不需要主密钥也能确认密文与元数据一致
+    let report = journal::reconcile(&data_src, &meta_src, "b", "o", None, false)
+        .await
+        .unwrap();
+    assert_eq!(report.finding, Finding::Consistent);
+
+    // 数据被替换之后修复，加密信息不再保留
+    data_src.create_object("b", "o", b"other").await.unwrap();
+    let report = journal::reconcile(&data_src, &meta_src, "b", "o", None, true)
+        .await
+        .unwrap();
+    assert_eq!(report.finding, Finding::StaleMeta);
+    let repaired = meta_src.read_object_meta("b", "o").await.unwrap();
+    assert!(repaired.encryption().unwrap().is_none());
+}
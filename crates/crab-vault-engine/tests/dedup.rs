@@ -0,0 +1,208 @@
+#![cfg(unix)]
+
+use std::{
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+};
+
+use crab_vault_engine::{DataEngine, error::EngineError, fs::FsDataEngine, layout::BLOBS_DIR};
+
+const BUCKET: &str = "bucket";
+
+fn fresh(name: &str) -> PathBuf {
+    let dir = PathBuf::from("./data_test").join(name);
+    if dir.exists() {
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+    dir
+}
+
+async fn dedup_engine(dir: &Path) -> FsDataEngine {
+    let engine = FsDataEngine::new(dir).unwrap().dedup(true);
+    engine.create_bucket(BUCKET).await.unwrap();
+    engine
+}
+
+/// [`BLOBS_DIR`] 中每个 blob 的硬链接数
+fn blob_links(dir: &Path) -> Vec<u64> {
+    let mut links = vec![];
+    let Ok(shards) = std::fs::read_dir(dir.join(BLOBS_DIR)) else {
+        return links;
+    };
+    for shard in shards {
+        for blob in std::fs::read_dir(shard.unwrap().path()).unwrap() {
+            links.push(blob.unwrap().metadata().unwrap().nlink());
+        }
+    }
+    links.sort();
+    links
+}
+
+fn inode(dir: &Path, object: &str) -> u64 {
+    std::fs::metadata(dir.join(BUCKET).join(object))
+        .unwrap()
+        .ino()
+}
+
+#[tokio::test]
+async fn test_dedup_shares_data() {
+    let dir = fresh("dedup_share");
+    let engine = dedup_engine(&dir).await;
+
+    engine.create_object(BUCKET, "a", b"same").await.unwrap();
+    engine
+        .create_object(BUCKET, "dir/b", b"same")
+        .await
+        .unwrap();
+    engine.create_object(BUCKET, "c", b"other").await.unwrap();
+    engine.copy_object(BUCKET, "c", BUCKET, "d").await.unwrap();
+    assert_eq!(inode(&dir, "a"), inode(&dir, "dir/b"));
+    assert_eq!(inode(&dir, "c"), inode(&dir, "d"));
+    assert_eq!(blob_links(&dir), vec![3, 3]);
+
+    // 只在不存在时创建的 object 同样共享数据
+    engine
+        .create_object_if_absent(BUCKET, "e", b"same")
+        .await
+        .unwrap();
+    assert!(matches!(
+        engine.create_object_if_absent(BUCKET, "e", b"same").await,
+        Err(EngineError::ObjectAlreadyExists { .. })
+    ));
+    assert_eq!(blob_links(&dir), vec![3, 4]);
+
+    let mut listed = engine.list_objects(BUCKET).await.unwrap();
+    listed.sort();
+    assert_eq!(listed, ["a", "c", "d", "dir/b", "e"]);
+    assert_eq!(engine.list_buckets().await.unwrap(), [BUCKET]);
+}
+
+#[tokio::test]
+async fn test_dedup_releases_blobs() {
+    let dir = fresh("dedup_release");
+    let engine = dedup_engine(&dir).await;
+
+    engine.create_object(BUCKET, "a", b"same").await.unwrap();
+    engine.create_object(BUCKET, "b", b"same").await.unwrap();
+
+    // 替换其中一个不影响另一个，最后一个引用被删除时 blob 同样被删除
+    engine.create_object(BUCKET, "a", b"new").await.unwrap();
+    assert_eq!(engine.read_object(BUCKET, "b").await.unwrap(), b"same");
+    assert_eq!(blob_links(&dir), vec![2, 2]);
+
+    engine.delete_object(BUCKET, "b").await.unwrap();
+    assert_eq!(blob_links(&dir), vec![2]);
+    engine.delete_object(BUCKET, "a").await.unwrap();
+    assert!(blob_links(&dir).is_empty());
+    engine.delete_bucket(BUCKET).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_dedup_prune_orphan_blobs() {
+    let dir = fresh("dedup_prune");
+    let engine = dedup_engine(&dir).await;
+
+    // 同时删除最后两个引用时，两边都可能看到对方还在而不删除 blob
+    for _ in 0..8 {
+        engine.create_object(BUCKET, "a", b"same").await.unwrap();
+        engine.create_object(BUCKET, "b", b"same").await.unwrap();
+        let (a, b) = tokio::join!(
+            engine.delete_object(BUCKET, "a"),
+            engine.delete_object(BUCKET, "b")
+        );
+        a.unwrap();
+        b.unwrap();
+        assert!(blob_links(&dir).iter().all(|v| *v == 1));
+
+        let orphans = engine.orphan_blobs().await.unwrap();
+        assert_eq!(engine.prune_blobs(&orphans).await.unwrap(), orphans.len());
+        assert!(blob_links(&dir).is_empty());
+    }
+
+    // 在删除 object 和 blob 之间崩溃
+    engine.create_object(BUCKET, "c", b"kept").await.unwrap();
+    engine.create_object(BUCKET, "d", b"lost").await.unwrap();
+    std::fs::remove_file(dir.join(BUCKET).join("d")).unwrap();
+    assert_eq!(blob_links(&dir), vec![1, 2]);
+
+    let orphans = engine.orphan_blobs().await.unwrap();
+    assert_eq!(orphans.len(), 1);
+    assert_eq!(engine.prune_blobs(&orphans).await.unwrap(), 1);
+    assert_eq!(blob_links(&dir), vec![2]);
+    assert_eq!(engine.read_object(BUCKET, "c").await.unwrap(), b"kept");
+
+    // 已经重新被引用的 blob 会被保留
+    engine.create_object(BUCKET, "e", b"lost").await.unwrap();
+    assert_eq!(engine.prune_blobs(&orphans).await.unwrap(), 0);
+    assert_eq!(engine.read_object(BUCKET, "e").await.unwrap(), b"lost");
+}
+
+#[tokio::test]
+async fn test_dedup_copy_on_write() {
+    let dir = fresh("dedup_cow");
+    let engine = dedup_engine(&dir).await;
+
+    engine.create_object(BUCKET, "a", b"shared").await.unwrap();
+    engine.create_object(BUCKET, "b", b"shared").await.unwrap();
+
+    engine.write_object_at(BUCKET, "a", 0, b"S").await.unwrap();
+    engine.punch_hole(BUCKET, "b", 0, 1).await.unwrap();
+    assert_eq!(engine.read_object(BUCKET, "a").await.unwrap(), b"Shared");
+    assert_eq!(engine.read_object(BUCKET, "b").await.unwrap(), b"\0hared");
+
+    // 两个 object 都已经不再引用原来的 blob
+    assert!(blob_links(&dir).is_empty());
+
+    // 追加同样只修改自己的数据
+    engine.create_object(BUCKET, "c", b"shared").await.unwrap();
+    engine.create_object(BUCKET, "d", b"shared").await.unwrap();
+    engine.append_object(BUCKET, "c", b"!").await.unwrap();
+    assert_eq!(engine.read_object(BUCKET, "c").await.unwrap(), b"shared!");
+    assert_eq!(engine.read_object(BUCKET, "d").await.unwrap(), b"shared");
+}
+
+#[tokio::test]
+async fn test_dedup_from_file() {
+    let dir = fresh("dedup_from_file");
+    let engine = dedup_engine(&dir).await;
+    let src = dir.join("upload.tmp");
+    std::fs::write(&src, b"spooled").unwrap();
+
+    engine
+        .create_object_from_file(BUCKET, "a", &src)
+        .await
+        .unwrap();
+    engine.create_object(BUCKET, "b", b"spooled").await.unwrap();
+    assert_eq!(inode(&dir, "a"), inode(&dir, "b"));
+    assert_eq!(blob_links(&dir), vec![3]);
+    assert!(src.exists());
+}
+
+#[tokio::test]
+async fn test_dedup_disabled_later() {
+    let dir = fresh("dedup_disabled");
+    let engine = dedup_engine(&dir).await;
+    engine.create_object(BUCKET, "a", b"same").await.unwrap();
+    engine.create_object(BUCKET, "b", b"same").await.unwrap();
+
+    // 关闭去重之后，已经去重的 object 仍然会被释放
+    let engine = FsDataEngine::new(&dir).unwrap();
+    engine.create_object(BUCKET, "c", b"same").await.unwrap();
+    assert_eq!(blob_links(&dir), vec![3]);
+    engine.delete_object(BUCKET, "a").await.unwrap();
+    engine.delete_object(BUCKET, "b").await.unwrap();
+    assert!(blob_links(&dir).is_empty());
+    assert_eq!(engine.read_object(BUCKET, "c").await.unwrap(), b"same");
+}
+
+#[test]
+fn test_dedup_uri() {
+    let dir = fresh("dedup_uri");
+    let uri = |query: &str| format!("{}?{query}", dir.display());
+    assert!(FsDataEngine::from_uri(&uri("dedup=on&layout=fanout")).is_ok());
+    assert!(FsDataEngine::from_uri(&uri("dedup=off")).is_ok());
+    assert!(matches!(
+        FsDataEngine::from_uri(&uri("dedup=yes")),
+        Err(EngineError::InvalidArgument(_))
+    ));
+}
@@ -0,0 +1,131 @@
+use std::path::{Path, PathBuf};
+
+use crab_vault_engine::{
+    DataEngine, DataSource, MetaEngine, MetaSource, ObjectMeta,
+    error::EngineError,
+    fs::{Durability, FsDataEngine, FsMetaEngine},
+};
+
+const BUCKET: &str = "bucket";
+
+fn fresh(base_dir: &str, name: &str) -> PathBuf {
+    let dir = PathBuf::from(base_dir).join(name);
+    if dir.exists() {
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+    dir
+}
+
+/// 递归找出留下的临时文件
+fn leftovers(dir: &Path) -> Vec<PathBuf> {
+    let mut found = vec![];
+    for entry in std::fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            found.extend(leftovers(&path));
+        } else if path.extension().is_some_and(|v| v == "tmp") {
+            found.push(path);
+        }
+    }
+    found
+}
+
+async fn check_data_engine<E: DataEngine + Sync>(engine: E, base_dir: &Path) {
+    engine.create_bucket(BUCKET).await.unwrap();
+
+    engine.create_object(BUCKET, "a", b"first").await.unwrap();
+    engine.create_object(BUCKET, "a", b"second").await.unwrap();
+    assert_eq!(engine.read_object(BUCKET, "a").await.unwrap(), b"second");
+
+    // 已经存在时不会覆盖
+    assert!(matches!(
+        engine.create_object_if_absent(BUCKET, "a", b"third").await,
+        Err(EngineError::ObjectAlreadyExists { .. })
+    ));
+    engine
+        .create_object_if_absent(BUCKET, "b", b"created")
+        .await
+        .unwrap();
+    assert_eq!(engine.read_object(BUCKET, "a").await.unwrap(), b"second");
+    assert_eq!(engine.read_object(BUCKET, "b").await.unwrap(), b"created");
+
+    engine.copy_object(BUCKET, "a", BUCKET, "b").await.unwrap();
+    assert_eq!(engine.read_object(BUCKET, "b").await.unwrap(), b"second");
+
+    assert!(leftovers(base_dir).is_empty());
+
+    // 删除临时文件后 bucket 是空的
+    engine.delete_object(BUCKET, "a").await.unwrap();
+    engine.delete_object(BUCKET, "b").await.unwrap();
+    engine.delete_bucket(BUCKET).await.unwrap();
+}
+
+async fn check_meta_engine<E: MetaEngine + Sync>(engine: E, base_dir: &Path) {
+    let meta = ObjectMeta::builder()
+        .bucket_name(BUCKET)
+        .object_name("dir/a")
+        .data(b"a")
+        .build()
+        .unwrap();
+    engine.create_object_meta(&meta).await.unwrap();
+    engine.touch_object(BUCKET, "dir/a").await.unwrap();
+
+    assert!(leftovers(base_dir).is_empty());
+    assert_eq!(engine.list_objects_meta(BUCKET).await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_writes_leave_no_temp_files() {
+    for (name, durability) in [
+        ("durability_off", Durability::Off),
+        ("durability_file", Durability::File),
+        ("durability_full", Durability::Full),
+    ] {
+        let data_dir = fresh("./data_test", name);
+        let engine = FsDataEngine::new(&data_dir).unwrap().durability(durability);
+        check_data_engine(engine, &data_dir).await;
+
+        let meta_dir = fresh("./meta_test", name);
+        let engine = FsMetaEngine::new(&meta_dir).unwrap().durability(durability);
+        check_meta_engine(engine, &meta_dir).await;
+    }
+}
+
+#[tokio::test]
+async fn test_leftover_temp_files_are_not_listed() {
+    let meta_dir = fresh("./meta_test", "durability_leftover");
+    let engine = FsMetaEngine::new(&meta_dir).unwrap();
+    check_meta_engine(engine, &meta_dir).await;
+
+    // 写入过程中崩溃时留下的临时文件
+    let objects = meta_dir.join("objects").join(BUCKET).join("dir");
+    std::fs::write(objects.join(".b.json.1-0.tmp"), "{").unwrap();
+
+    let engine = FsMetaEngine::new(&meta_dir).unwrap();
+    assert_eq!(engine.list_objects_meta(BUCKET).await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_uri_parameters() {
+    let data_dir = fresh("./data_test", "durability_uri");
+    let uri = format!("{}?durability=off&sparse=off", data_dir.display());
+    check_data_engine(DataSource::new(&uri).unwrap(), &data_dir).await;
+
+    let meta_dir = fresh("./meta_test", "durability_uri");
+    let uri = format!("{}?durability=file", meta_dir.display());
+    check_meta_engine(MetaSource::new(&uri).unwrap(), &meta_dir).await;
+
+    assert!(matches!(
+        FsDataEngine::from_uri("./data_test/durability_uri?durability=always"),
+        Err(EngineError::InvalidArgument(_))
+    ));
+    for uri in [
+        "./meta_test/durability_uri?durability=",
+        "./meta_test/durability_uri?sparse=off",
+    ] {
+        assert!(matches!(
+            FsMetaEngine::from_uri(uri),
+            Err(EngineError::InvalidArgument(_))
+        ));
+    }
+}
@@ -0,0 +1,140 @@
+use crab_vault_engine::error::EngineError;
+
+fn object(f: fn(String, String) -> EngineError) -> EngineError {
+    f("photos".into(), "a.jpg".into())
+}
+
+/// 每一个变体与它的 `code`，这里的值是对客户端的承诺，修改之前请确认是有意为之
+fn all() -> Vec<(EngineError, &'static str)> {
+    use EngineError::*;
+    let bucket = || "photos".to_string();
+    vec![
+        (
+            Io {
+                error: std::io::Error::other("disk"),
+                path: "/data".into(),
+            },
+            "io",
+        ),
+        (
+            Serde {
+                error: "syntax",
+                line: 1,
+                column: 2,
+            },
+            "serde",
+        ),
+        (BucketNotFound { bucket: bucket() }, "bucketNotFound"),
+        (
+            BucketMetaNotFound { bucket: bucket() },
+            "bucketMetaNotFound",
+        ),
+        (BucketNotEmpty { bucket: bucket() }, "bucketNotEmpty"),
+        (
+            object(|bucket, object| ObjectNotFound { bucket, object }),
+            "objectNotFound",
+        ),
+        (
+            object(|bucket, object| ObjectAlreadyExists { bucket, object }),
+            "objectAlreadyExists",
+        ),
+        (
+            object(|bucket, object| ObjectMetaNotFound { bucket, object }),
+            "objectMetaNotFound",
+        ),
+        (
+            object(|bucket, object| EtagMismatch { bucket, object }),
+            "etagMismatch",
+        ),
+        (
+            object(|bucket, object| ObjectLocked { bucket, object }),
+            "objectLocked",
+        ),
+        (
+            object(|bucket, object| TrashEntryNotFound { bucket, object }),
+            "trashEntryNotFound",
+        ),
+        (Other("other".into()), "other"),
+        (BackendError("down".into()), "backendError"),
+        (
+            InvalidBucketName {
+                bucket: bucket(),
+                reason: "too long",
+            },
+            "invalidBucketName",
+        ),
+        (
+            InvalidObjectName {
+                object: "a".into(),
+                reason: "too long",
+            },
+            "invalidObjectName",
+        ),
+        (InvalidArgument("limit".into()), "invalidArgument"),
+        (Encryption("tag".into()), "encryption"),
+        (Compression("zstd".into()), "compression"),
+        (CorruptMeta { entry: "a".into() }, "corruptMeta"),
+        (
+            ByteQuotaExceeded {
+                bucket: bucket(),
+                limit: 1,
+            },
+            "byteQuotaExceeded",
+        ),
+        (
+            ObjectQuotaExceeded {
+                bucket: bucket(),
+                limit: 1,
+            },
+            "objectQuotaExceeded",
+        ),
+        (
+            object(|bucket, object| ObjectExpired { bucket, object }),
+            "objectExpired",
+        ),
+        (
+            object(|bucket, object| CustomerKeyRequired { bucket, object }),
+            "customerKeyRequired",
+        ),
+        (
+            object(|bucket, object| CustomerKeyMismatch { bucket, object }),
+            "customerKeyMismatch",
+        ),
+        (
+            InvalidLayout {
+                path: "/data".into(),
+                reason: "not a directory".into(),
+            },
+            "invalidLayout",
+        ),
+    ]
+}
+
+#[test]
+fn test_error_codes_are_stable() {
+    let errors = all();
+    for (error, code) in &errors {
+        assert_eq!(error.error_code(), *code, "{error}");
+        // 序列化出的 `code` 与之相同，重命名变体时这里会失败
+        let value = serde_json::to_value(error).unwrap();
+        assert_eq!(value["code"], *code, "{error}");
+    }
+
+    let mut codes: Vec<_> = errors.iter().map(|(_, code)| *code).collect();
+    codes.sort_unstable();
+    codes.dedup();
+    assert_eq!(codes.len(), errors.len());
+}
+
+#[test]
+fn test_resource() {
+    let error = EngineError::BucketNotEmpty {
+        bucket: "photos".into(),
+    };
+    assert_eq!(error.resource().as_deref(), Some("/photos"));
+
+    let error = object(|bucket, object| EngineError::ObjectNotFound { bucket, object });
+    assert_eq!(error.resource().as_deref(), Some("/photos/a.jpg"));
+
+    assert_eq!(EngineError::BackendError("down".into()).resource(), None);
+}
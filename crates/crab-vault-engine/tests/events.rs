@@ -0,0 +1,199 @@
+use std::path::PathBuf;
+
+use crab_vault_engine::{
+    BucketMeta, MetaEngine, MetaSource, ObjectMeta,
+    events::{Event, EventOp},
+    fs::FsMetaEngine,
+    layout::EVENTS_FILE,
+    mem::MemMetaEngine,
+};
+
+const BUCKET: &str = "bucket";
+
+fn fresh(name: &str) -> PathBuf {
+    let dir = PathBuf::from("./meta_test").join(name);
+    if dir.exists() {
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+    dir
+}
+
+fn object(name: &str, etag: &str) -> ObjectMeta {
+    ObjectMeta {
+        bucket_name: BUCKET.to_string(),
+        object_name: name.to_string(),
+        etag: etag.to_string(),
+        ..ObjectMeta::default()
+    }
+}
+
+fn seqs(events: &[Event]) -> Vec<u64> {
+    events.iter().map(|v| v.seq).collect()
+}
+
+/// 序号从 1 开始递增，清理之后仍然从原来的位置继续
+async fn check_engine<E: MetaEngine + Sync>(engine: &E) {
+    assert!(engine.list_events(0, 10).await.unwrap().is_empty());
+
+    for i in 1..=5 {
+        let seq = engine
+            .append_event(&Event::delete_object(BUCKET, &format!("o{i}")))
+            .await
+            .unwrap();
+        assert_eq!(seq, i);
+    }
+
+    assert_eq!(
+        seqs(&engine.list_events(0, 10).await.unwrap()),
+        [1, 2, 3, 4, 5]
+    );
+    assert_eq!(seqs(&engine.list_events(2, 2).await.unwrap()), [3, 4]);
+    assert!(engine.list_events(5, 10).await.unwrap().is_empty());
+
+    engine.trim_events(3).await.unwrap();
+    assert_eq!(seqs(&engine.list_events(0, 10).await.unwrap()), [4, 5]);
+
+    let seq = engine
+        .append_event(&Event::put_object(&object("a", "etag")))
+        .await
+        .unwrap();
+    assert_eq!(seq, 6);
+
+    let event = engine.list_events(5, 10).await.unwrap().remove(0);
+    assert_eq!(event.op, EventOp::PutObject);
+    assert_eq!(event.object.as_deref(), Some("a"));
+    assert_eq!(event.etag.as_deref(), Some("etag"));
+}
+
+#[tokio::test]
+async fn test_mem_events() {
+    check_engine(&MemMetaEngine::new("mem://").unwrap()).await;
+}
+
+#[tokio::test]
+async fn test_fs_events() {
+    let base_dir = fresh("events_fs");
+    check_engine(&FsMetaEngine::new(&base_dir).unwrap()).await;
+
+    // 重新打开之后从文件中读出最后一个序号
+    let engine = FsMetaEngine::new(&base_dir).unwrap();
+    let seq = engine
+        .append_event(&Event::delete_bucket(BUCKET))
+        .await
+        .unwrap();
+    assert_eq!(seq, 7);
+}
+
+#[tokio::test]
+async fn test_fs_skips_torn_line() {
+    let base_dir = fresh("events_torn");
+    let engine = FsMetaEngine::new(&base_dir).unwrap();
+    engine
+        .append_event(&Event::put_bucket(BUCKET))
+        .await
+        .unwrap();
+
+    // 追加到一半时崩溃
+    let path = base_dir.join(EVENTS_FILE);
+    let mut data = std::fs::read_to_string(&path).unwrap();
+    data.push_str(r#"{"seq":2,"op":"put-bu"#);
+    std::fs::write(&path, data).unwrap();
+
+    let engine = FsMetaEngine::new(&base_dir).unwrap();
+    assert_eq!(seqs(&engine.list_events(0, 10).await.unwrap()), [1]);
+
+    let seq = engine
+        .append_event(&Event::delete_bucket(BUCKET))
+        .await
+        .unwrap();
+    assert_eq!(seq, 2);
+    let events = engine.list_events(0, 10).await.unwrap();
+    assert_eq!(seqs(&events), [1, 2]);
+    assert_eq!(events[1].op, EventOp::DeleteBucket);
+}
+
+#[tokio::test]
+async fn test_source_records_mutations() {
+    let source = MetaSource::new("mem://").unwrap().record_events(100);
+    assert!(source.records_events());
+
+    source
+        .create_bucket_meta(&BucketMeta::new(BUCKET.to_string(), serde_json::json!({})))
+        .await
+        .unwrap();
+    source.create_object_meta(&object("a", "e1")).await.unwrap();
+    source.create_object_meta(&object("b", "e2")).await.unwrap();
+    source.touch_object(BUCKET, "a").await.unwrap();
+    source.delete_object_meta(BUCKET, "a").await.unwrap();
+    source
+        .delete_objects_meta(BUCKET, &["b".to_string()])
+        .await
+        .into_iter()
+        .for_each(|v| v.unwrap());
+    source.delete_bucket_meta(BUCKET).await.unwrap();
+
+    let events = source.list_events(0, 100).await.unwrap();
+    let ops: Vec<_> = events
+        .iter()
+        .map(|v| (v.op, v.object.as_deref(), v.etag.as_deref()))
+        .collect();
+    assert_eq!(
+        ops,
+        [
+            (EventOp::PutBucket, None, None),
+            (EventOp::PutObject, Some("a"), Some("e1")),
+            (EventOp::PutObject, Some("b"), Some("e2")),
+            (EventOp::DeleteObject, Some("a"), None),
+            (EventOp::DeleteObject, Some("b"), None),
+            (EventOp::DeleteBucket, None, None),
+        ]
+    );
+    assert_eq!(seqs(&events), [1, 2, 3, 4, 5, 6]);
+}
+
+#[tokio::test]
+async fn test_source_without_events() {
+    let source = MetaSource::new("mem://").unwrap();
+    assert!(!source.records_events());
+
+    source.create_object_meta(&object("a", "e1")).await.unwrap();
+    assert!(source.list_events(0, 10).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_source_trims_old_events() {
+    let source = MetaSource::new("mem://").unwrap().record_events(10);
+    for i in 0..1024 {
+        source
+            .create_object_meta(&object(&format!("o{i}"), "e"))
+            .await
+            .unwrap();
+    }
+
+    let events = source.list_events(0, 2000).await.unwrap();
+    assert_eq!(events.first().map(|v| v.seq), Some(1015));
+    assert_eq!(events.last().map(|v| v.seq), Some(1024));
+}
+
+#[test]
+fn test_event_serde() {
+    let event = Event {
+        seq: 7,
+        ..Event::delete_object(BUCKET, "a b")
+    };
+    let json = serde_json::to_value(&event).unwrap();
+    assert_eq!(json["op"], "delete-object");
+    assert!(json.get("etag").is_none());
+    assert_eq!(serde_json::from_value::<Event>(json).unwrap(), event);
+
+    for op in [
+        EventOp::PutBucket,
+        EventOp::DeleteBucket,
+        EventOp::PutObject,
+        EventOp::DeleteObject,
+    ] {
+        assert_eq!(EventOp::from_name(op.as_str()), Some(op));
+        assert_eq!(serde_json::to_value(op).unwrap(), op.as_str());
+    }
+    assert_eq!(EventOp::from_name("put"), None);
+}
@@ -0,0 +1,127 @@
+use std::path::{Path, PathBuf};
+
+use crab_vault_engine::{
+    DataEngine,
+    error::EngineError,
+    fs::{FsDataEngine, ObjectLayout},
+    layout::FANOUT_DIR,
+};
+
+const BUCKET: &str = "bucket";
+
+const OBJECTS: [(&str, &[u8]); 4] = [
+    ("top", b"top"),
+    ("a/b/c", b"c"),
+    ("folder/", b""),
+    ("with space", b"space"),
+];
+
+fn fresh(name: &str) -> PathBuf {
+    let dir = PathBuf::from("./data_test").join(name);
+    if dir.exists() {
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+    dir
+}
+
+/// bucket 目录中除了 [`FANOUT_DIR`] 以外的条目
+fn flat_entries(bucket_dir: &Path) -> Vec<String> {
+    std::fs::read_dir(bucket_dir)
+        .unwrap()
+        .map(|v| v.unwrap().file_name().to_string_lossy().to_string())
+        .filter(|v| v != FANOUT_DIR)
+        .collect()
+}
+
+async fn check_objects(engine: &FsDataEngine) {
+    for (object, data) in OBJECTS {
+        assert_eq!(engine.read_object(BUCKET, object).await.unwrap(), data);
+    }
+
+    let mut listed = engine.list_objects(BUCKET).await.unwrap();
+    listed.sort();
+    let mut expected: Vec<_> = OBJECTS.iter().map(|(v, _)| v.to_string()).collect();
+    expected.sort();
+    assert_eq!(listed, expected);
+}
+
+#[tokio::test]
+async fn test_fanout_layout() {
+    let dir = fresh("fanout");
+    let engine = FsDataEngine::new(&dir)
+        .unwrap()
+        .object_layout(ObjectLayout::FanOut);
+    engine.create_bucket(BUCKET).await.unwrap();
+    for (object, data) in OBJECTS {
+        engine.create_object(BUCKET, object, data).await.unwrap();
+    }
+    engine
+        .copy_object(BUCKET, "top", BUCKET, "copied")
+        .await
+        .unwrap();
+
+    // 所有的文件都在 FANOUT_DIR 下的两级子目录中
+    let bucket_dir = dir.join(BUCKET);
+    assert!(flat_entries(&bucket_dir).is_empty());
+    let top = std::fs::read_dir(bucket_dir.join(FANOUT_DIR))
+        .unwrap()
+        .map(|v| v.unwrap().file_name().to_string_lossy().to_string())
+        .collect::<Vec<_>>();
+    assert!(top.iter().all(|v| v.len() == 2));
+
+    engine.delete_object(BUCKET, "copied").await.unwrap();
+    check_objects(&engine).await;
+
+    assert!(matches!(
+        engine.delete_bucket(BUCKET).await,
+        Err(EngineError::BucketNotEmpty { .. })
+    ));
+    for (object, _) in OBJECTS {
+        engine.delete_object(BUCKET, object).await.unwrap();
+    }
+    assert!(engine.list_objects(BUCKET).await.unwrap().is_empty());
+    engine.delete_bucket(BUCKET).await.unwrap();
+    assert!(!bucket_dir.exists());
+}
+
+#[tokio::test]
+async fn test_fan_out_existing_bucket() {
+    let dir = fresh("fanout_migrate");
+    let flat = FsDataEngine::new(&dir).unwrap();
+    flat.create_bucket(BUCKET).await.unwrap();
+    for (object, data) in OBJECTS {
+        flat.create_object(BUCKET, object, data).await.unwrap();
+    }
+
+    // 修改配置不会改变已有的 bucket
+    let engine = FsDataEngine::new(&dir)
+        .unwrap()
+        .object_layout(ObjectLayout::FanOut);
+    engine.create_bucket(BUCKET).await.unwrap();
+    assert!(!dir.join(BUCKET).join(FANOUT_DIR).exists());
+    check_objects(&engine).await;
+
+    assert_eq!(engine.fan_out_bucket(BUCKET).await.unwrap(), OBJECTS.len());
+    assert!(flat_entries(&dir.join(BUCKET)).is_empty());
+    check_objects(&engine).await;
+    check_objects(&flat).await;
+
+    assert_eq!(engine.fan_out_bucket(BUCKET).await.unwrap(), 0);
+    assert!(matches!(
+        engine.fan_out_bucket("missing").await,
+        Err(EngineError::BucketNotFound { .. })
+    ));
+}
+
+#[tokio::test]
+async fn test_layout_from_uri() {
+    let dir = fresh("fanout_uri");
+    let engine = FsDataEngine::from_uri(&format!("{}?layout=fanout", dir.display())).unwrap();
+    engine.create_bucket(BUCKET).await.unwrap();
+    assert!(dir.join(BUCKET).join(FANOUT_DIR).is_dir());
+
+    assert!(matches!(
+        FsDataEngine::from_uri(&format!("{}?layout=deep", dir.display())),
+        Err(EngineError::InvalidArgument(_))
+    ));
+}
@@ -0,0 +1,152 @@
+use chrono::{DateTime, Utc};
+use futures::TryStreamExt;
+use serde_json::json;
+
+use crab_vault_engine::{
+    MetaEngine, ObjectMeta,
+    filter::{Comparison, Condition, MAX_CONDITIONS, MetaFilter},
+    list::ListObjectsQuery,
+    mem::MemMetaEngine,
+};
+
+const BUCKET: &str = "bucket";
+
+fn time(s: &str) -> DateTime<Utc> {
+    DateTime::parse_from_rfc3339(s).unwrap().to_utc()
+}
+
+fn object(name: &str, content_type: &str, size: u64, updated_at: &str) -> ObjectMeta {
+    ObjectMeta {
+        bucket_name: BUCKET.to_string(),
+        object_name: name.to_string(),
+        content_type: content_type.to_string(),
+        size,
+        updated_at: time(updated_at),
+        ..ObjectMeta::default()
+    }
+}
+
+fn parse(expr: &str) -> MetaFilter {
+    expr.parse().unwrap()
+}
+
+#[test]
+fn test_parse() {
+    let filter = parse(
+        r#"content_type^=image/ and size >= 1024 AND updated_at<2025-01-01T00:00:00Z and meta.owner="alice \"a\"""#,
+    );
+    assert_eq!(
+        filter.conditions,
+        [
+            Condition::ContentType {
+                value: "image/".to_string(),
+                prefix: true,
+            },
+            Condition::Size(Comparison::Ge, 1024),
+            Condition::UpdatedAt(Comparison::Lt, time("2025-01-01T00:00:00Z")),
+            Condition::UserMeta {
+                key: "owner".to_string(),
+                value: "alice \"a\"".to_string(),
+            },
+        ]
+    );
+
+    assert_eq!(
+        parse("content_type=text/plain").conditions,
+        [Condition::ContentType {
+            value: "text/plain".to_string(),
+            prefix: false,
+        }]
+    );
+}
+
+#[test]
+fn test_parse_errors() {
+    for expr in [
+        "",
+        "size",
+        "size>=",
+        "size>=-1",
+        "size>=1 size<2",
+        "size>=1 or size<2",
+        "updated_at=2025-01-01T00:00:00Z",
+        "updated_at>yesterday",
+        "content_type<image/",
+        "meta.owner^=a",
+        "meta.=a",
+        "meta.crab-vault:encryption=a",
+        "name=a",
+        "meta.owner=\"alice",
+    ] {
+        assert!(expr.parse::<MetaFilter>().is_err(), "{expr}");
+    }
+
+    let many = vec!["size>=0"; MAX_CONDITIONS + 1].join(" and ");
+    assert!(many.parse::<MetaFilter>().is_err());
+    let most = vec!["size>=0"; MAX_CONDITIONS].join(" and ");
+    assert!(most.parse::<MetaFilter>().is_ok());
+}
+
+#[test]
+fn test_matches() {
+    let mut meta = object("a.png", "image/png", 2048, "2025-06-01T00:00:00Z");
+    meta.user_meta = json!({ "owner": "alice", "rank": 3, "public": true });
+
+    for expr in [
+        "content_type^=image/",
+        "content_type=image/png",
+        "size=2048 and size>2047 and size<=2048",
+        "updated_at>=2025-06-01T00:00:00Z and updated_at<2025-06-02T00:00:00Z",
+        "meta.owner=alice and meta.rank=3 and meta.public=true",
+    ] {
+        assert!(parse(expr).matches(&meta), "{expr}");
+    }
+
+    for expr in [
+        "content_type^=text/",
+        "content_type=image/",
+        "size<2048",
+        "updated_at>2025-06-01T00:00:00Z",
+        "meta.owner=bob",
+        "meta.rank=\"4\"",
+        "meta.missing=1",
+        "content_type^=image/ and size>4096",
+    ] {
+        assert!(!parse(expr).matches(&meta), "{expr}");
+    }
+}
+
+#[tokio::test]
+async fn test_list_with_query() {
+    let engine = MemMetaEngine::new("mem://").unwrap();
+    for meta in [
+        object("a.png", "image/png", 10, "2025-01-01T00:00:00Z"),
+        object("b.jpg", "image/jpeg", 5000, "2025-02-01T00:00:00Z"),
+        object("c.txt", "text/plain", 20, "2025-03-01T00:00:00Z"),
+    ] {
+        engine.create_object_meta(&meta).await.unwrap();
+    }
+
+    let query: ListObjectsQuery =
+        serde_json::from_value(json!({ "query": "content_type^=image/ and size<1000" })).unwrap();
+    let page = engine.list_objects_meta_page(BUCKET, &query).await.unwrap();
+    let names: Vec<_> = page
+        .objects
+        .iter()
+        .map(|v| v.object_name.as_str())
+        .collect();
+    assert_eq!(names, ["a.png"]);
+
+    let query: ListObjectsQuery =
+        serde_json::from_value(json!({ "query": "updated_at>2025-01-15T00:00:00Z" })).unwrap();
+    let mut streamed: Vec<_> = engine
+        .stream_objects_meta(BUCKET, &query)
+        .map_ok(|v| v.object_name)
+        .try_collect()
+        .await
+        .unwrap();
+    streamed.sort();
+    assert_eq!(streamed, ["b.jpg", "c.txt"]);
+
+    assert!(serde_json::from_value::<ListObjectsQuery>(json!({ "query": "size~1" })).is_err());
+}
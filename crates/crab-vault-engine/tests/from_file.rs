@@ -0,0 +1,83 @@
+use std::path::{Path, PathBuf};
+
+use crab_vault_engine::{
+    DataEngine, DataSource,
+    builder::{EtagHasher, compute_etag},
+    error::EngineError,
+    fs::FsDataEngine,
+    mem::MemDataEngine,
+};
+
+const BUCKET: &str = "bucket";
+
+async fn check_data_engine<E: DataEngine + Sync>(engine: E, src: &Path) {
+    assert!(matches!(
+        engine.create_object_from_file(BUCKET, "copied", src).await,
+        Err(EngineError::BucketNotFound { .. })
+    ));
+
+    engine.create_bucket(BUCKET).await.unwrap();
+    engine
+        .create_object_from_file(BUCKET, "copied", src)
+        .await
+        .unwrap();
+    assert_eq!(
+        engine.read_object(BUCKET, "copied").await.unwrap(),
+        std::fs::read(src).unwrap()
+    );
+
+    // 源文件仍然属于调用者
+    assert!(src.exists());
+
+    assert!(matches!(
+        engine
+            .create_object_from_file(BUCKET, "missing", &src.with_extension("missing"))
+            .await,
+        Err(EngineError::Io { .. })
+    ));
+}
+
+async fn prepare(base_dir: &PathBuf) -> PathBuf {
+    if base_dir.exists() {
+        tokio::fs::remove_dir_all(base_dir).await.unwrap();
+    }
+    tokio::fs::create_dir_all(base_dir).await.unwrap();
+
+    let src = base_dir.join("source.bin");
+    tokio::fs::write(&src, (0..=255u8).cycle().take(100_000).collect::<Vec<_>>())
+        .await
+        .unwrap();
+    src
+}
+
+#[tokio::test]
+async fn test_mem_create_from_file() {
+    let src = prepare(&PathBuf::from("./data_test/from_file_mem")).await;
+    check_data_engine(MemDataEngine::new("mem://").unwrap(), &src).await;
+}
+
+#[tokio::test]
+async fn test_fs_create_from_file() {
+    let base_dir = PathBuf::from("./data_test/from_file_fs");
+    let src = prepare(&base_dir).await;
+    check_data_engine(FsDataEngine::new(base_dir.join("data")).unwrap(), &src).await;
+}
+
+#[tokio::test]
+async fn test_source_create_from_file() {
+    let base_dir = PathBuf::from("./data_test/from_file_source");
+    let src = prepare(&base_dir).await;
+    let uri = base_dir.join("data");
+    check_data_engine(DataSource::new(uri.to_str().unwrap()).unwrap(), &src).await;
+}
+
+#[test]
+fn test_incremental_etag() {
+    let data = (0..=255u8).cycle().take(10_000).collect::<Vec<_>>();
+
+    let mut hasher = EtagHasher::new();
+    for chunk in data.chunks(777) {
+        hasher.update(chunk);
+    }
+    assert_eq!(hasher.finish(), compute_etag(&data));
+}
@@ -167,4 +167,30 @@ async fn test_overwrite_object() {
 
     let read_data2 = storage.read_object(bucket_name, object_name).await.unwrap();
     assert_eq!(read_data2, new_data);
-}
\ No newline at end of file
+}
+
+#[tokio::test]
+async fn test_read_object_reader() {
+    use futures::AsyncReadExt;
+
+    let (storage, _base_dir) = setup("read_object_reader").await;
+    let bucket_name = "bucket";
+    let data: Vec<u8> = (0..200_000u32).map(|v| v as u8).collect();
+
+    storage.create_bucket(bucket_name).await.unwrap();
+    storage
+        .create_object(bucket_name, "large", &data)
+        .await
+        .unwrap();
+
+    let mut reader = storage
+        .read_object_reader(bucket_name, "large")
+        .await
+        .unwrap();
+    let mut read_data = Vec::new();
+    reader.read_to_end(&mut read_data).await.unwrap();
+    assert_eq!(read_data, data);
+
+    let result = storage.read_object_reader(bucket_name, "missing").await;
+    assert!(matches!(result, Err(EngineError::ObjectNotFound { .. })));
+}
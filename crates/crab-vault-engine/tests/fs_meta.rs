@@ -148,3 +148,38 @@ async fn test_list_objects_from_nonexistent_bucket_returns_empty() {
         .unwrap();
     assert!(objects.is_empty());
 }
+
+#[tokio::test]
+async fn test_list_objects_with_bounded_concurrency() {
+    let (_, base_dir) = setup("list_concurrency").await;
+    let uri = format!("{}?list_concurrency=3", base_dir.display());
+    let storage = FsMetaEngine::from_uri(&uri).unwrap();
+
+    let bucket_name = "my-bucket";
+    let mut expected: Vec<_> = (0..40).map(|i| format!("dir-{}/obj-{i}", i % 4)).collect();
+    for object_name in &expected {
+        let meta = ObjectMeta {
+            bucket_name: bucket_name.to_string(),
+            object_name: object_name.clone(),
+            ..ObjectMeta::default()
+        };
+        storage.create_object_meta(&meta).await.unwrap();
+    }
+
+    // 同时读取时顺序不固定
+    let mut names: Vec<_> = storage
+        .list_objects_meta(bucket_name)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|v| v.object_name)
+        .collect();
+    names.sort();
+    expected.sort();
+    assert_eq!(names, expected);
+
+    for value in ["0", "many"] {
+        let uri = format!("{}?list_concurrency={value}", base_dir.display());
+        assert!(FsMetaEngine::from_uri(&uri).is_err());
+    }
+}
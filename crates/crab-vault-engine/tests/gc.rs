@@ -0,0 +1,160 @@
+use std::path::PathBuf;
+
+use crab_vault_engine::{
+    BucketMeta, DataEngine, MetaEngine, ObjectMeta,
+    fs::{FsDataEngine, FsMetaEngine},
+    gc::{self, Garbage, ObjectRef},
+    journal::Intent,
+    mem::{MemDataEngine, MemMetaEngine},
+};
+
+fn fresh(name: &str) -> PathBuf {
+    let dir = PathBuf::from("./data_test").join(name);
+    if dir.exists() {
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+    dir
+}
+
+fn meta_of(bucket: &str, object: &str, data: &[u8]) -> ObjectMeta {
+    ObjectMeta::builder()
+        .bucket_name(bucket)
+        .object_name(object)
+        .data(data)
+        .build()
+        .unwrap()
+}
+
+fn bucket_meta(name: &str) -> BucketMeta {
+    BucketMeta::builder().name(name).build().unwrap()
+}
+
+/// 一个正常的 bucket、一个只有数据的 bucket、一个空的 bucket 目录，以及各种孤立的 object
+async fn populate<D, M>(data: &D, meta: &M)
+where
+    D: DataEngine + Sync,
+    M: MetaEngine + Sync,
+{
+    data.create_bucket("bucket").await.unwrap();
+    meta.create_bucket_meta(&bucket_meta("bucket"))
+        .await
+        .unwrap();
+    for (object, body) in [("ok", &b"ok"[..]), ("folder/", b""), ("a/b", b"b")] {
+        data.create_object("bucket", object, body).await.unwrap();
+        meta.create_object_meta(&meta_of("bucket", object, body))
+            .await
+            .unwrap();
+    }
+
+    data.create_object("bucket", "orphan/data", b"x")
+        .await
+        .unwrap();
+    meta.create_object_meta(&meta_of("bucket", "orphan-meta", b"y"))
+        .await
+        .unwrap();
+
+    // 没有 bucket 元数据的 bucket 中的数据和元数据
+    data.create_bucket("loose").await.unwrap();
+    data.create_object("loose", "kept", b"k").await.unwrap();
+    meta.create_object_meta(&meta_of("loose", "kept", b"k"))
+        .await
+        .unwrap();
+    data.create_object("loose", "lost", b"l").await.unwrap();
+
+    data.create_bucket("empty").await.unwrap();
+
+    // 有 bucket 元数据的空 bucket 是正常的
+    data.create_bucket("fresh").await.unwrap();
+    meta.create_bucket_meta(&bucket_meta("fresh"))
+        .await
+        .unwrap();
+}
+
+fn expected() -> Garbage {
+    Garbage {
+        orphan_data: vec![
+            ObjectRef::new("bucket", "orphan/data"),
+            ObjectRef::new("loose", "lost"),
+        ],
+        orphan_meta: vec![ObjectRef::new("bucket", "orphan-meta")],
+        empty_buckets: vec!["empty".into()],
+    }
+}
+
+async fn check<D, M>(data: D, meta: M)
+where
+    D: DataEngine + Sync,
+    M: MetaEngine + Sync,
+{
+    populate(&data, &meta).await;
+
+    let garbage = gc::scan(&data, &meta).await.unwrap();
+    assert_eq!(garbage, expected());
+
+    gc::collect(&data, &meta, &garbage).await.unwrap();
+    assert!(gc::scan(&data, &meta).await.unwrap().is_empty());
+
+    // 正常的内容都被保留
+    assert_eq!(data.read_object("bucket", "a/b").await.unwrap(), b"b");
+    assert_eq!(data.read_object("bucket", "folder/").await.unwrap(), b"");
+    assert_eq!(data.read_object("loose", "kept").await.unwrap(), b"k");
+    data.create_object("fresh", "new", b"n").await.unwrap();
+    data.create_bucket("empty").await.unwrap();
+    assert!(data.list_objects("empty").await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_mem_gc() {
+    check(
+        MemDataEngine::new("mem://").unwrap(),
+        MemMetaEngine::new("mem://").unwrap(),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn test_fs_gc() {
+    // 与元数据共用目录时，元数据的子目录不是 bucket
+    let dir = fresh("gc_shared");
+    check(
+        FsDataEngine::new(&dir).unwrap(),
+        FsMetaEngine::new(&dir).unwrap(),
+    )
+    .await;
+
+    let data = FsDataEngine::new(fresh("gc_data")).unwrap();
+    data.create_bucket("bucket").await.unwrap();
+    data.create_object("bucket", "a/b/", b"").await.unwrap();
+    data.create_object("bucket", "a/b/c", b"c").await.unwrap();
+    let mut objects = data.list_objects("bucket").await.unwrap();
+    objects.sort();
+    assert_eq!(objects, ["a/b/", "a/b/c"]);
+}
+
+#[tokio::test]
+async fn test_exclude_and_intersect() {
+    let mut garbage = expected();
+    garbage.exclude(&[
+        Intent::put(&meta_of("loose", "lost", b"l")),
+        Intent::delete("bucket", &["orphan-meta".into()]),
+    ]);
+    assert_eq!(
+        garbage.orphan_data,
+        [ObjectRef::new("bucket", "orphan/data")]
+    );
+    assert!(garbage.orphan_meta.is_empty());
+    assert_eq!(garbage.empty_buckets, ["empty"]);
+
+    let later = Garbage {
+        empty_buckets: vec![],
+        ..expected()
+    };
+    assert_eq!(
+        expected().intersect(&later),
+        Garbage {
+            empty_buckets: vec![],
+            ..expected()
+        }
+    );
+    assert!(garbage.intersect(&Garbage::default()).is_empty());
+}
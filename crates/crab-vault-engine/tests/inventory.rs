@@ -0,0 +1,183 @@
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use crab_vault_engine::{
+    MetaEngine, ObjectMeta,
+    inventory::{self, InventoryConfig, InventoryEntry, InventoryFormat, InventoryFrequency},
+    mem::MemMetaEngine,
+};
+
+const BUCKET: &str = "photos";
+
+fn time() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2026, 1, 2, 3, 4, 5).unwrap()
+}
+
+fn object(bucket: &str, name: &str, size: u64) -> ObjectMeta {
+    ObjectMeta {
+        bucket_name: bucket.to_string(),
+        object_name: name.to_string(),
+        size,
+        etag: format!("etag-{name}"),
+        content_type: "image/png".to_string(),
+        created_at: time(),
+        updated_at: time(),
+        ..ObjectMeta::default()
+    }
+}
+
+#[test]
+fn test_config() {
+    let parsed: InventoryConfig =
+        serde_json::from_str(r#"{"destination":"reports","format":"json"}"#).unwrap();
+    let config = InventoryConfig {
+        format: InventoryFormat::Json,
+        ..InventoryConfig::new("reports")
+    };
+    assert_eq!(parsed, config);
+    assert_eq!(parsed.frequency, InventoryFrequency::Daily);
+    assert!(serde_json::from_str::<InventoryConfig>(r#"{"bucket":"reports"}"#).is_err());
+
+    assert!(config.validate(BUCKET).is_ok());
+    assert!(InventoryConfig::new("").validate(BUCKET).is_err());
+    let config = InventoryConfig {
+        destination_prefix: "../".into(),
+        ..InventoryConfig::new("reports")
+    };
+    assert!(config.validate(BUCKET).is_err());
+
+    // 写入自己时清单不能列出自己
+    assert!(InventoryConfig::new(BUCKET).validate(BUCKET).is_err());
+    for (prefix, ok) in [
+        ("inv", false),
+        ("inventory/photos/2026", false),
+        ("raw/", true),
+    ] {
+        let config = InventoryConfig {
+            prefix: prefix.into(),
+            ..InventoryConfig::new(BUCKET)
+        };
+        assert_eq!(config.validate(BUCKET).is_ok(), ok, "{prefix}");
+    }
+    assert!(InventoryConfig::new(BUCKET).validate("other").is_ok());
+}
+
+#[test]
+fn test_object_name() {
+    let config = InventoryConfig::new("reports");
+    assert_eq!(
+        config.object_name(BUCKET, time()),
+        "inventory/photos/2026-01-02T03-04-05Z.csv"
+    );
+
+    let config = InventoryConfig {
+        destination_prefix: String::new(),
+        format: InventoryFormat::Json,
+        ..config
+    };
+    assert_eq!(
+        config.object_name(BUCKET, time()),
+        "photos/2026-01-02T03-04-05Z.jsonl"
+    );
+}
+
+#[test]
+fn test_is_due() {
+    let config = InventoryConfig::new("reports");
+    let now = time();
+    assert!(config.is_due(None, now));
+    assert!(config.is_due(Some(now - Duration::days(1)), now));
+    assert!(!config.is_due(Some(now - Duration::hours(23)), now));
+
+    let config = InventoryConfig {
+        frequency: InventoryFrequency::Weekly,
+        ..config
+    };
+    assert!(!config.is_due(Some(now - Duration::days(6)), now));
+    assert!(config.is_due(Some(now - Duration::days(7)), now));
+}
+
+#[test]
+fn test_encode() {
+    let entry = InventoryEntry::from(object(BUCKET, "a \"b\", c.png", 3));
+
+    let mut csv = vec![];
+    InventoryFormat::Csv.write_header(&mut csv);
+    InventoryFormat::Csv.write_entry(&mut csv, &entry).unwrap();
+    assert_eq!(
+        String::from_utf8(csv).unwrap(),
+        "key,size,etag,content_type,updated_at\n\
+         \"a \"\"b\"\", c.png\",\"3\",\"etag-a \"\"b\"\", c.png\",\"image/png\",\"2026-01-02T03:04:05.000Z\"\n"
+    );
+
+    let mut json = vec![];
+    InventoryFormat::Json.write_header(&mut json);
+    InventoryFormat::Json
+        .write_entry(&mut json, &entry)
+        .unwrap();
+    let line = String::from_utf8(json).unwrap();
+    assert!(line.ends_with('\n'));
+    let parsed: InventoryEntry = serde_json::from_str(line.trim_end()).unwrap();
+    assert_eq!(parsed, entry);
+}
+
+#[tokio::test]
+async fn test_generate() {
+    let engine = MemMetaEngine::default();
+    for (name, size) in [("b.png", 2), ("a.png", 1), ("raw/c.png", 4)] {
+        engine
+            .create_object_meta(&object(BUCKET, name, size))
+            .await
+            .unwrap();
+    }
+
+    let config = InventoryConfig::new("reports");
+    let inventory = inventory::generate(&engine, BUCKET, &config).await.unwrap();
+    assert_eq!((inventory.objects, inventory.bytes), (3, 7));
+    let mut keys: Vec<_> = String::from_utf8(inventory.data)
+        .unwrap()
+        .lines()
+        .skip(1)
+        .map(|v| v.split(',').next().unwrap().to_string())
+        .collect();
+    keys.sort();
+    assert_eq!(keys, ["\"a.png\"", "\"b.png\"", "\"raw/c.png\""]);
+
+    let config = InventoryConfig {
+        prefix: "raw/".into(),
+        ..config
+    };
+    let inventory = inventory::generate(&engine, BUCKET, &config).await.unwrap();
+    assert_eq!((inventory.objects, inventory.bytes), (1, 4));
+}
+
+#[tokio::test]
+async fn test_last_generated() {
+    let engine = MemMetaEngine::default();
+    let config = InventoryConfig::new("reports");
+    assert_eq!(
+        inventory::last_generated(&engine, BUCKET, &config)
+            .await
+            .unwrap(),
+        None
+    );
+
+    for created_at in [time() - Duration::days(2), time()] {
+        let meta = ObjectMeta {
+            created_at,
+            ..object("reports", &config.object_name(BUCKET, created_at), 1)
+        };
+        engine.create_object_meta(&meta).await.unwrap();
+    }
+    // 其他 bucket 的清单不算
+    let other = ObjectMeta {
+        created_at: time() + Duration::days(1),
+        ..object("reports", &config.object_name("photos2", time()), 1)
+    };
+    engine.create_object_meta(&other).await.unwrap();
+
+    assert_eq!(
+        inventory::last_generated(&engine, BUCKET, &config)
+            .await
+            .unwrap(),
+        Some(time())
+    );
+}
@@ -0,0 +1,231 @@
+use std::path::PathBuf;
+
+use crab_vault_engine::{
+    DataEngine, MetaEngine, ObjectMeta,
+    compression::{Codec, Compression},
+    error::EngineError,
+    journal::{self, Finding, Intent, Journal},
+    mem::{MemDataEngine, MemMetaEngine},
+};
+
+const BUCKET: &str = "bucket";
+
+fn fresh(name: &str) -> PathBuf {
+    let dir = PathBuf::from("./data_test").join(name);
+    if dir.exists() {
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+    dir
+}
+
+fn meta_of(object: &str, data: &[u8]) -> ObjectMeta {
+    ObjectMeta::builder()
+        .bucket_name(BUCKET)
+        .object_name(object)
+        .data(data)
+        .content_type("text/plain")
+        .build()
+        .unwrap()
+}
+
+fn engines() -> (MemDataEngine, MemMetaEngine) {
+    (
+        MemDataEngine::new("mem://").unwrap(),
+        MemMetaEngine::new("mem://").unwrap(),
+    )
+}
+
+#[tokio::test]
+async fn test_journal_entries() {
+    let dir = fresh("journal_entries");
+    let journal = Journal::open(&dir).await.unwrap();
+
+    let put = Intent::put(&meta_of("a", b"a"));
+    let delete = Intent::delete(BUCKET, &["b".into(), "c".into()]);
+    let first = journal.begin(&put).await.unwrap();
+    let second = journal.begin(&delete).await.unwrap();
+    assert!(first < second);
+
+    journal.finish(first).await.unwrap();
+    // 重复完成不是错误
+    journal.finish(first).await.unwrap();
+
+    // 重新打开后序号继续递增
+    let journal = Journal::open(&dir).await.unwrap();
+    let pending = journal.pending().await.unwrap();
+    assert_eq!(pending, vec![(second, delete)]);
+    assert!(journal.begin(&put).await.unwrap() > second);
+
+    std::fs::write(dir.join(".00000000000000000009.json.1-0.tmp"), "{").unwrap();
+    assert_eq!(journal.pending().await.unwrap().len(), 2);
+
+    std::fs::write(dir.join("00000000000000000009.json"), "{").unwrap();
+    assert!(matches!(
+        journal.pending().await,
+        Err(EngineError::CorruptMeta { .. })
+    ));
+}
+
+#[tokio::test]
+async fn test_reconcile() {
+    let (data, meta) = engines();
+    data.create_bucket(BUCKET).await.unwrap();
+
+    // 一致的 object
+    data.create_object(BUCKET, "ok", b"ok").await.unwrap();
+    meta.create_object_meta(&meta_of("ok", b"ok"))
+        .await
+        .unwrap();
+
+    // 元数据指向的数据不存在
+    meta.create_object_meta(&meta_of("dangling", b"gone"))
+        .await
+        .unwrap();
+
+    // 数据没有元数据
+    data.create_object(BUCKET, "orphan", b"orphan")
+        .await
+        .unwrap();
+
+    // 元数据落后于数据
+    data.create_object(BUCKET, "stale", b"new").await.unwrap();
+    meta.create_object_meta(&meta_of("stale", b"old"))
+        .await
+        .unwrap();
+
+    let cases = [
+        ("ok", Finding::Consistent),
+        ("dangling", Finding::DanglingMeta),
+        ("orphan", Finding::OrphanData),
+        ("stale", Finding::StaleMeta),
+        ("missing", Finding::Absent),
+    ];
+
+    // 只检查时不做任何修改
+    for (object, finding) in cases {
+        let report = journal::reconcile(&data, &meta, BUCKET, object, None, false)
+            .await
+            .unwrap();
+        assert_eq!(report.finding, finding, "{object}");
+        assert!(!report.repaired);
+    }
+
+    for (object, finding) in cases {
+        let report = journal::reconcile(&data, &meta, BUCKET, object, None, true)
+            .await
+            .unwrap();
+        assert_eq!(report.finding, finding, "{object}");
+        assert_eq!(report.repaired, finding.needs_repair());
+    }
+
+    assert!(matches!(
+        meta.read_object_meta(BUCKET, "dangling").await,
+        Err(EngineError::ObjectMetaNotFound { .. })
+    ));
+    let orphan = meta.read_object_meta(BUCKET, "orphan").await.unwrap();
+    assert_eq!(orphan.etag, meta_of("orphan", b"orphan").etag);
+
+    // 只有大小和 etag 被更新
+    let stale = meta.read_object_meta(BUCKET, "stale").await.unwrap();
+    assert_eq!(stale.etag, meta_of("stale", b"new").etag);
+    assert_eq!(stale.size, 3);
+    assert_eq!(stale.content_type, "text/plain");
+
+    for (object, _) in cases {
+        let report = journal::reconcile(&data, &meta, BUCKET, object, None, false)
+            .await
+            .unwrap();
+        assert!(!report.finding.needs_repair(), "{object}");
+    }
+}
+
+#[tokio::test]
+async fn test_replay() {
+    let (data, meta) = engines();
+    data.create_bucket(BUCKET).await.unwrap();
+    let journal = Journal::open(fresh("journal_replay")).await.unwrap();
+
+    // 数据写入后崩溃
+    let written = meta_of("written", b"new");
+    data.create_object(BUCKET, "written", b"new").await.unwrap();
+    journal.begin(&Intent::put(&written)).await.unwrap();
+
+    // 数据写入前崩溃，原来的 object 保持不变
+    data.create_object(BUCKET, "kept", b"old").await.unwrap();
+    meta.create_object_meta(&meta_of("kept", b"old"))
+        .await
+        .unwrap();
+    journal
+        .begin(&Intent::put(&meta_of("kept", b"new")))
+        .await
+        .unwrap();
+
+    // 删除数据后崩溃
+    meta.create_object_meta(&meta_of("deleted", b"x"))
+        .await
+        .unwrap();
+    journal
+        .begin(&Intent::delete(BUCKET, &["deleted".into()]))
+        .await
+        .unwrap();
+
+    let reports = journal::replay(&journal, &data, &meta, false)
+        .await
+        .unwrap();
+    let findings: Vec<_> = reports.iter().map(|v| v.finding).collect();
+    assert_eq!(
+        findings,
+        [
+            Finding::Unfinished,
+            Finding::Consistent,
+            Finding::PartiallyDeleted
+        ]
+    );
+    assert_eq!(journal.pending().await.unwrap().len(), 3);
+
+    let reports = journal::replay(&journal, &data, &meta, true).await.unwrap();
+    assert_eq!(reports.iter().filter(|v| v.repaired).count(), 2);
+    assert!(journal.pending().await.unwrap().is_empty());
+
+    assert_eq!(
+        meta.read_object_meta(BUCKET, "written").await.unwrap(),
+        written
+    );
+    assert_eq!(
+        meta.read_object_meta(BUCKET, "kept").await.unwrap().etag,
+        meta_of("kept", b"old").etag
+    );
+    assert!(matches!(
+        meta.read_object_meta(BUCKET, "deleted").await,
+        Err(EngineError::ObjectMetaNotFound { .. })
+    ));
+}
+
+#[test]
+fn test_data_matches() {
+    let data = vec![b'a'; 4096];
+    let meta = meta_of("a", &data);
+    assert!(journal::data_matches(&meta, &data));
+    assert!(!journal::data_matches(&meta, b"b"));
+
+    // 压缩过的数据按照元数据中的压缩信息还原之后比较
+    let (compression, compressed) = Compression::apply(Codec::Zstd, &data).unwrap().unwrap();
+    let meta = ObjectMeta {
+        compression: Some(compression),
+        ..meta
+    };
+    assert!(journal::data_matches(&meta, &compressed));
+    assert!(!journal::data_matches(&meta, &data));
+}
+
+#[test]
+fn test_intent_touches() {
+    let put = Intent::put(&meta_of("a", b"a"));
+    assert!(put.touches(BUCKET, "a"));
+    assert!(!put.touches(BUCKET, "b"));
+    assert!(!put.touches("other", "a"));
+
+    let delete = Intent::delete(BUCKET, &["b".into(), "c".into()]);
+    assert!(delete.touches(BUCKET, "c"));
+    assert!(!delete.touches(BUCKET, "a"));
+}
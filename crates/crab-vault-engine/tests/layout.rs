@@ -0,0 +1,124 @@
+use std::path::{Path, PathBuf};
+
+use crab_vault_engine::{
+    DataEngine, MetaEngine,
+    error::EngineError,
+    fs::{FsDataEngine, FsMetaEngine},
+    layout::{LAYOUT_FILE, LAYOUT_VERSION, Layout, LayoutKind},
+};
+
+fn fresh(name: &str) -> PathBuf {
+    let base_dir = PathBuf::from("./data_test").join(name);
+    if base_dir.exists() {
+        std::fs::remove_dir_all(&base_dir).unwrap();
+    }
+    base_dir
+}
+
+fn read_layout(base_dir: &Path) -> Layout {
+    serde_json::from_slice(&std::fs::read(base_dir.join(LAYOUT_FILE)).unwrap()).unwrap()
+}
+
+#[test]
+fn test_fresh_directories_are_marked() {
+    let data_dir = fresh("layout_fresh_data");
+    let meta_dir = fresh("layout_fresh_meta");
+
+    FsDataEngine::new(&data_dir).unwrap();
+    FsMetaEngine::new(&meta_dir).unwrap();
+
+    assert_eq!(read_layout(&data_dir).kinds, vec![LayoutKind::Data]);
+    assert_eq!(read_layout(&meta_dir).version, LAYOUT_VERSION);
+    assert!(meta_dir.join("buckets").is_dir());
+    assert!(meta_dir.join("objects").is_dir());
+
+    // 再次打开时使用已有的标记文件，误删的子目录会被重新创建
+    std::fs::remove_dir(meta_dir.join("objects")).unwrap();
+    FsMetaEngine::new(&meta_dir).unwrap();
+    assert!(meta_dir.join("objects").is_dir());
+}
+
+#[test]
+fn test_legacy_directories_are_accepted() {
+    let data_dir = fresh("layout_legacy_data");
+    let meta_dir = fresh("layout_legacy_meta");
+    std::fs::create_dir_all(data_dir.join("photos")).unwrap();
+    std::fs::create_dir_all(meta_dir.join("buckets")).unwrap();
+    std::fs::write(meta_dir.join("buckets").join("photos.json"), "{}").unwrap();
+
+    FsDataEngine::new(&data_dir).unwrap();
+    FsMetaEngine::new(&meta_dir).unwrap();
+    assert_eq!(read_layout(&data_dir).kinds, vec![LayoutKind::Data]);
+    assert_eq!(read_layout(&meta_dir).kinds, vec![LayoutKind::Meta]);
+
+    // 默认配置中数据和元数据使用同一个目录
+    let shared = fresh("layout_legacy_shared");
+    std::fs::create_dir_all(shared.join("photos")).unwrap();
+    std::fs::create_dir_all(shared.join("buckets")).unwrap();
+    std::fs::write(shared.join("buckets").join("photos.json"), "{}").unwrap();
+    FsMetaEngine::new(&shared).unwrap();
+    FsDataEngine::new(&shared).unwrap();
+    assert_eq!(
+        read_layout(&shared).kinds,
+        vec![LayoutKind::Meta, LayoutKind::Data]
+    );
+}
+
+#[test]
+fn test_wrong_directories_are_rejected() {
+    // 普通文件不会出现在这两种目录中
+    let home = fresh("layout_unrelated");
+    std::fs::create_dir_all(&home).unwrap();
+    std::fs::write(home.join("notes.txt"), "").unwrap();
+    let err = FsDataEngine::new(&home).err().unwrap();
+    assert!(matches!(err, EngineError::InvalidLayout { .. }));
+    assert!(err.to_string().contains("`notes.txt`"));
+    assert!(!home.join(LAYOUT_FILE).exists());
+
+    // 只有数据的目录被当作元数据目录，不做任何修改
+    let data_dir = fresh("layout_data_as_meta");
+    std::fs::create_dir_all(data_dir.join("photos")).unwrap();
+    let err = FsMetaEngine::new(&data_dir).err().unwrap();
+    assert!(err.to_string().contains("`photos`"));
+    assert!(!data_dir.join(LAYOUT_FILE).exists());
+    assert!(!data_dir.join("buckets").exists());
+
+    // 保留的子目录被占用
+    let meta_dir = fresh("layout_reserved");
+    FsMetaEngine::new(&meta_dir).unwrap();
+    std::fs::remove_dir(meta_dir.join("objects")).unwrap();
+    std::fs::write(meta_dir.join("objects"), "").unwrap();
+    assert!(matches!(
+        FsMetaEngine::new(&meta_dir),
+        Err(EngineError::InvalidLayout { .. })
+    ));
+
+    // 更新的版本创建的目录
+    let newer = fresh("layout_newer");
+    std::fs::create_dir_all(&newer).unwrap();
+    let layout = Layout {
+        kinds: vec![LayoutKind::Data],
+        version: LAYOUT_VERSION + 1,
+    };
+    std::fs::write(
+        newer.join(LAYOUT_FILE),
+        serde_json::to_vec(&layout).unwrap(),
+    )
+    .unwrap();
+    assert!(matches!(
+        FsDataEngine::new(&newer),
+        Err(EngineError::InvalidLayout { .. })
+    ));
+}
+
+#[tokio::test]
+async fn test_marker_is_not_a_bucket() {
+    let meta_dir = fresh("layout_listing");
+    let engine = FsMetaEngine::new(&meta_dir).unwrap();
+    assert!(engine.list_buckets_meta().await.unwrap().is_empty());
+
+    let data_dir = fresh("layout_listing_data");
+    let engine = FsDataEngine::new(&data_dir).unwrap();
+    engine.create_bucket("photos").await.unwrap();
+    assert!(FsDataEngine::new(&data_dir).is_ok());
+}
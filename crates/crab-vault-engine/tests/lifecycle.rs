@@ -0,0 +1,171 @@
+use chrono::{DateTime, Duration, Utc};
+use crab_vault_engine::{
+    MetaEngine, ObjectMeta,
+    error::EngineError,
+    lifecycle::{
+        self, EXPIRES_AT_RULE, ExpiredObject, LifecycleConfig, LifecycleRule, MAX_LIFECYCLE_RULES,
+    },
+    mem::MemMetaEngine,
+    tagging::Tags,
+};
+
+const BUCKET: &str = "bucket";
+
+fn rule(id: Option<&str>, prefix: &str, expiration_days: u32) -> LifecycleRule {
+    LifecycleRule {
+        id: id.map(String::from),
+        prefix: prefix.to_string(),
+        expiration_days,
+        ..LifecycleRule::default()
+    }
+}
+
+/// 内存后端列出 object 的顺序是不确定的
+async fn scan(
+    engine: &MemMetaEngine,
+    lifecycle: Option<&LifecycleConfig>,
+    now: DateTime<Utc>,
+) -> Vec<ExpiredObject> {
+    let mut expired = lifecycle::scan(engine, BUCKET, lifecycle, now)
+        .await
+        .unwrap();
+    expired.sort_by(|a, b| a.object.cmp(&b.object));
+    expired
+}
+
+fn object(name: &str, updated_at: DateTime<Utc>) -> ObjectMeta {
+    ObjectMeta {
+        bucket_name: BUCKET.to_string(),
+        object_name: name.to_string(),
+        updated_at,
+        ..ObjectMeta::default()
+    }
+}
+
+#[test]
+fn test_validate() {
+    let config = |rules| LifecycleConfig { rules };
+    assert!(config(vec![rule(None, "tmp/", 7)]).validate().is_ok());
+    assert!(config(vec![]).validate().is_err());
+    assert!(
+        config(vec![rule(None, "", 1), rule(None, "", 0)])
+            .validate()
+            .is_err()
+    );
+    assert!(
+        config(vec![rule(None, "", 1); MAX_LIFECYCLE_RULES + 1])
+            .validate()
+            .is_err()
+    );
+
+    let parsed: LifecycleConfig =
+        serde_json::from_str(r#"{"rules":[{"id":"tmp","prefix":"tmp/","expiration-days":7}]}"#)
+            .unwrap();
+    assert_eq!(parsed, config(vec![rule(Some("tmp"), "tmp/", 7)]));
+    assert!(serde_json::from_str::<LifecycleConfig>(r#"{"rules":[{"days":7}]}"#).is_err());
+}
+
+#[test]
+fn test_expiring_rule() {
+    let now = Utc::now();
+    let config = LifecycleConfig {
+        rules: vec![rule(Some("tmp"), "tmp/", 7), rule(None, "", 30)],
+    };
+
+    // 恰好满 7 天时过期
+    let meta = object("tmp/a", now - Duration::days(7));
+    assert_eq!(config.expiring_rule(&meta, now).unwrap().describe(), "tmp");
+    let meta = object("tmp/a", now - Duration::days(7) + Duration::seconds(1));
+    assert!(config.expiring_rule(&meta, now).is_none());
+
+    // 前缀不匹配时只有作用于整个 bucket 的规则生效
+    let meta = object("data/a", now - Duration::days(10));
+    assert!(config.expiring_rule(&meta, now).is_none());
+    let meta = object("data/a", now - Duration::days(30));
+    assert_eq!(
+        config.expiring_rule(&meta, now).unwrap().describe(),
+        r#""" after 30 days"#
+    );
+}
+
+#[test]
+fn test_tag_filter() {
+    let now = Utc::now();
+    let mut tagged = rule(Some("scratch"), "", 1);
+    tagged.tags = Tags::from([("class".to_string(), "scratch".to_string())]);
+    let config = LifecycleConfig {
+        rules: vec![tagged],
+    };
+    assert!(config.validate().is_ok());
+
+    let mut meta = object("a", now - Duration::days(1));
+    assert!(config.expiring_rule(&meta, now).is_none());
+    meta.tags.insert("class".to_string(), "keep".to_string());
+    assert!(config.expiring_rule(&meta, now).is_none());
+    meta.tags.insert("class".to_string(), "scratch".to_string());
+    meta.tags.insert("owner".to_string(), "ci".to_string());
+    assert_eq!(
+        config.expiring_rule(&meta, now).unwrap().describe(),
+        "scratch"
+    );
+
+    let parsed: LifecycleConfig = serde_json::from_str(
+        r#"{"rules":[{"id":"scratch","tags":{"class":"scratch"},"expiration-days":1}]}"#,
+    )
+    .unwrap();
+    assert_eq!(parsed, config);
+
+    let mut invalid = config.clone();
+    invalid.rules[0].tags.insert(String::new(), "x".to_string());
+    assert!(invalid.validate().is_err());
+}
+
+#[test]
+fn test_expires_at() {
+    let now = Utc::now();
+    let mut meta = object("a", now);
+    assert!(!meta.is_expired(now));
+
+    meta.expires_at = Some(now + Duration::seconds(1));
+    assert!(!meta.is_expired(now));
+    assert!(meta.check_expiry(now).is_ok());
+
+    // 恰好到达过期时间时过期
+    meta.expires_at = Some(now);
+    assert!(meta.is_expired(now));
+    assert!(matches!(
+        meta.check_expiry(now),
+        Err(EngineError::ObjectExpired { .. })
+    ));
+}
+
+#[tokio::test]
+async fn test_scan() {
+    let now = Utc::now();
+    let engine = MemMetaEngine::default();
+    for (name, days) in [("tmp/old", 8), ("tmp/new", 1), ("keep", 100)] {
+        let meta = object(name, now - Duration::days(days));
+        engine.create_object_meta(&meta).await.unwrap();
+    }
+    let mut ttl = object("ttl", now);
+    ttl.expires_at = Some(now - Duration::seconds(1));
+    engine.create_object_meta(&ttl).await.unwrap();
+
+    // 没有规则时只有超过了自己的过期时间的 object
+    let expired = |object: &str, rule: &str| ExpiredObject {
+        object: object.to_string(),
+        rule: rule.to_string(),
+    };
+    assert_eq!(
+        scan(&engine, None, now).await,
+        vec![expired("ttl", EXPIRES_AT_RULE)]
+    );
+
+    let lifecycle = LifecycleConfig {
+        rules: vec![rule(Some("tmp"), "tmp/", 7)],
+    };
+    assert_eq!(
+        scan(&engine, Some(&lifecycle), now).await,
+        vec![expired("tmp/old", "tmp"), expired("ttl", EXPIRES_AT_RULE)]
+    );
+}
@@ -0,0 +1,368 @@
+use std::path::PathBuf;
+
+use chrono::{DateTime, Duration, Utc};
+use serde_json::json;
+
+use crab_vault_engine::{
+    BucketMeta, MetaEngine, ObjectMeta,
+    error::EngineError,
+    fs::FsMetaEngine,
+    list::{
+        BucketPage, BucketSort, ListBucketsQuery, ListObjectsQuery, MAX_KEYS, ObjectPage, SortOrder,
+    },
+    mem::MemMetaEngine,
+    name::DEFAULT_INTERNAL_BUCKET,
+};
+
+const BUCKET: &str = "bucket";
+
+fn object(name: &str) -> ObjectMeta {
+    ObjectMeta {
+        bucket_name: BUCKET.to_string(),
+        object_name: name.to_string(),
+        ..ObjectMeta::default()
+    }
+}
+
+async fn setup(names: &[&str]) -> MemMetaEngine {
+    let engine = MemMetaEngine::new("mem://").unwrap();
+    for name in names {
+        engine.create_object_meta(&object(name)).await.unwrap();
+    }
+    engine
+}
+
+fn names(page: &ObjectPage) -> Vec<&str> {
+    page.objects
+        .iter()
+        .map(|v| v.object_name.as_str())
+        .collect()
+}
+
+fn query(
+    prefix: Option<&str>,
+    delimiter: Option<&str>,
+    max_keys: Option<usize>,
+) -> ListObjectsQuery {
+    ListObjectsQuery {
+        prefix: prefix.map(str::to_string),
+        delimiter: delimiter.map(str::to_string),
+        max_keys,
+        ..ListObjectsQuery::default()
+    }
+}
+
+#[tokio::test]
+async fn test_list_all_sorted() {
+    let engine = setup(&["c", "a", "b"]).await;
+    let page = engine
+        .list_objects_meta_page(BUCKET, &ListObjectsQuery::default())
+        .await
+        .unwrap();
+
+    assert_eq!(names(&page), ["a", "b", "c"]);
+    assert!(page.common_prefixes.is_empty());
+    assert!(!page.is_truncated);
+    assert!(page.next_continuation_token.is_none());
+}
+
+#[tokio::test]
+async fn test_list_with_prefix() {
+    let engine = setup(&["docs/a", "docs/b", "images/a", "docsx"]).await;
+    let page = engine
+        .list_objects_meta_page(BUCKET, &query(Some("docs/"), None, None))
+        .await
+        .unwrap();
+
+    assert_eq!(names(&page), ["docs/a", "docs/b"]);
+}
+
+#[tokio::test]
+async fn test_list_with_delimiter_groups_common_prefixes() {
+    let engine = setup(&["a", "docs/a", "docs/b", "docs/img/c", "images/a"]).await;
+
+    let page = engine
+        .list_objects_meta_page(BUCKET, &query(None, Some("/"), None))
+        .await
+        .unwrap();
+    assert_eq!(names(&page), ["a"]);
+    assert_eq!(page.common_prefixes, ["docs/", "images/"]);
+
+    let page = engine
+        .list_objects_meta_page(BUCKET, &query(Some("docs/"), Some("/"), None))
+        .await
+        .unwrap();
+    assert_eq!(names(&page), ["docs/a", "docs/b"]);
+    assert_eq!(page.common_prefixes, ["docs/img/"]);
+}
+
+#[tokio::test]
+async fn test_paging_with_continuation_token() {
+    let engine = setup(&["a", "b", "c", "d", "e"]).await;
+    let mut query = query(None, None, Some(2));
+    let mut pages = Vec::new();
+
+    loop {
+        let page = engine.list_objects_meta_page(BUCKET, &query).await.unwrap();
+        pages.push(names(&page).join(","));
+
+        if !page.is_truncated {
+            assert!(page.next_continuation_token.is_none());
+            break;
+        }
+        query.continuation_token = page.next_continuation_token;
+    }
+
+    assert_eq!(pages, ["a,b", "c,d", "e"]);
+}
+
+#[tokio::test]
+async fn test_common_prefixes_count_towards_max_keys() {
+    let engine = setup(&["a/1", "a/2", "b", "c/1", "d"]).await;
+    let mut query = query(None, Some("/"), Some(2));
+
+    let page = engine.list_objects_meta_page(BUCKET, &query).await.unwrap();
+    assert_eq!(page.common_prefixes, ["a/"]);
+    assert_eq!(names(&page), ["b"]);
+    assert!(page.is_truncated);
+
+    // 下一页不会再返回 `c/` 下的 object
+    query.continuation_token = page.next_continuation_token;
+    let page = engine.list_objects_meta_page(BUCKET, &query).await.unwrap();
+    assert_eq!(page.common_prefixes, ["c/"]);
+    assert_eq!(names(&page), ["d"]);
+    assert!(!page.is_truncated);
+}
+
+#[tokio::test]
+async fn test_max_keys_is_capped() {
+    let names = (0..MAX_KEYS + 1)
+        .map(|i| format!("{i:05}"))
+        .collect::<Vec<_>>();
+    let engine = setup(&names.iter().map(String::as_str).collect::<Vec<_>>()).await;
+
+    let page = engine
+        .list_objects_meta_page(BUCKET, &query(None, None, Some(MAX_KEYS * 2)))
+        .await
+        .unwrap();
+    assert_eq!(page.objects.len(), MAX_KEYS);
+    assert!(page.is_truncated);
+}
+
+#[tokio::test]
+async fn test_invalid_continuation_token() {
+    let engine = setup(&["a"]).await;
+    let query = ListObjectsQuery {
+        continuation_token: Some("not a token!".to_string()),
+        ..ListObjectsQuery::default()
+    };
+
+    assert!(matches!(
+        engine.list_objects_meta_page(BUCKET, &query).await,
+        Err(EngineError::InvalidArgument(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_fs_lists_nested_objects() {
+    let base_dir = PathBuf::from("./meta_test").join("list_nested_objects");
+    if base_dir.exists() {
+        tokio::fs::remove_dir_all(&base_dir).await.unwrap();
+    }
+    let engine = FsMetaEngine::new(&base_dir).unwrap();
+    for name in ["a", "docs/a", "docs/img/b"] {
+        engine.create_object_meta(&object(name)).await.unwrap();
+    }
+
+    let page = engine
+        .list_objects_meta_page(BUCKET, &query(None, Some("/"), None))
+        .await
+        .unwrap();
+    assert_eq!(names(&page), ["a"]);
+    assert_eq!(page.common_prefixes, ["docs/"]);
+
+    let page = engine
+        .list_objects_meta_page(BUCKET, &ListObjectsQuery::default())
+        .await
+        .unwrap();
+    assert_eq!(names(&page), ["a", "docs/a", "docs/img/b"]);
+}
+
+fn object_at(name: &str, created_at: DateTime<Utc>, updated_at: DateTime<Utc>) -> ObjectMeta {
+    ObjectMeta {
+        created_at,
+        updated_at,
+        ..object(name)
+    }
+}
+
+#[tokio::test]
+async fn test_list_with_timestamp_filters() {
+    let t0 = Utc::now();
+    let (t1, t2) = (t0 + Duration::seconds(1), t0 + Duration::seconds(2));
+    let engine = MemMetaEngine::new("mem://").unwrap();
+    for meta in [
+        object_at("old", t0, t0),
+        object_at("touched", t0, t2),
+        object_at("new", t2, t2),
+    ] {
+        engine.create_object_meta(&meta).await.unwrap();
+    }
+
+    let list = async |query: ListObjectsQuery| {
+        let page = engine.list_objects_meta_page(BUCKET, &query).await.unwrap();
+        names(&page).join(",")
+    };
+
+    let query = ListObjectsQuery {
+        updated_after: Some(t1),
+        ..ListObjectsQuery::default()
+    };
+    assert_eq!(list(query).await, "new,touched");
+
+    let query = ListObjectsQuery {
+        created_after: Some(t1),
+        ..ListObjectsQuery::default()
+    };
+    assert_eq!(list(query).await, "new");
+
+    let query = ListObjectsQuery {
+        updated_before: Some(t1),
+        ..ListObjectsQuery::default()
+    };
+    assert_eq!(list(query).await, "old");
+
+    // 边界本身不包含在内
+    let query = ListObjectsQuery {
+        created_before: Some(t0),
+        updated_after: Some(t2),
+        ..ListObjectsQuery::default()
+    };
+    assert_eq!(list(query).await, "");
+}
+
+#[test]
+fn test_query_accepts_both_cases() {
+    let kebab: ListObjectsQuery = serde_json::from_value(json!({
+        "max-keys": 10,
+        "created-after": "2025-08-20T05:02:40Z",
+        "updated-before": "2025-08-21T00:00:00+08:00",
+    }))
+    .unwrap();
+    let snake: ListObjectsQuery = serde_json::from_value(json!({
+        "max_keys": 10,
+        "created_after": "2025-08-20T05:02:40Z",
+        "updated_before": "2025-08-20T16:00:00Z",
+    }))
+    .unwrap();
+
+    assert_eq!(kebab, snake);
+    assert_eq!(kebab.max_keys, Some(10));
+}
+
+fn bucket_at(name: &str, created_at: DateTime<Utc>) -> BucketMeta {
+    BucketMeta {
+        name: name.to_string(),
+        created_at,
+        updated_at: created_at,
+        ..BucketMeta::default()
+    }
+}
+
+fn bucket_names(page: &BucketPage) -> Vec<&str> {
+    page.buckets.iter().map(|v| v.name.as_str()).collect()
+}
+
+#[tokio::test]
+async fn test_list_buckets_paging_and_prefix() {
+    let t0 = Utc::now();
+    let engine = MemMetaEngine::new("mem://").unwrap();
+    for name in [
+        "logs-b",
+        "photos",
+        "logs-a",
+        "logs-c",
+        DEFAULT_INTERNAL_BUCKET,
+    ] {
+        engine
+            .create_bucket_meta(&bucket_at(name, t0))
+            .await
+            .unwrap();
+    }
+
+    // 内部的 bucket 不会出现
+    let all = engine
+        .list_buckets_meta_page(&ListBucketsQuery::default())
+        .await
+        .unwrap();
+    assert_eq!(bucket_names(&all), ["logs-a", "logs-b", "logs-c", "photos"]);
+    assert!(!all.is_truncated);
+
+    let mut query = ListBucketsQuery {
+        prefix: Some("logs-".into()),
+        max_buckets: Some(2),
+        ..ListBucketsQuery::default()
+    };
+    let first = engine.list_buckets_meta_page(&query).await.unwrap();
+    assert_eq!(bucket_names(&first), ["logs-a", "logs-b"]);
+    assert_eq!(first.total, 3);
+    assert!(first.is_truncated);
+
+    query.continuation_token = first.next_continuation_token;
+    let second = engine.list_buckets_meta_page(&query).await.unwrap();
+    assert_eq!(bucket_names(&second), ["logs-c"]);
+    assert!(!second.is_truncated);
+    assert!(second.next_continuation_token.is_none());
+}
+
+#[tokio::test]
+async fn test_list_buckets_sorted_by_time() {
+    let t0 = Utc::now();
+    let engine = MemMetaEngine::new("mem://").unwrap();
+    for (name, secs) in [("a", 2), ("b", 0), ("c", 1), ("d", 1)] {
+        let meta = bucket_at(name, t0 + Duration::seconds(secs));
+        engine.create_bucket_meta(&meta).await.unwrap();
+    }
+
+    let mut query = ListBucketsQuery {
+        sort: BucketSort::CreatedAt,
+        order: SortOrder::Desc,
+        max_buckets: Some(2),
+        ..ListBucketsQuery::default()
+    };
+    let first = engine.list_buckets_meta_page(&query).await.unwrap();
+    assert_eq!(bucket_names(&first), ["a", "d"]);
+
+    // 时间相同的 bucket 按名称排序，令牌记录了时间和名称
+    query.continuation_token = first.next_continuation_token;
+    let second = engine.list_buckets_meta_page(&query).await.unwrap();
+    assert_eq!(bucket_names(&second), ["c", "b"]);
+    assert!(!second.is_truncated);
+
+    // 令牌与排序字段不匹配
+    query.sort = BucketSort::UpdatedAt;
+    query.continuation_token = Some("YQ".into());
+    assert!(matches!(
+        engine.list_buckets_meta_page(&query).await,
+        Err(EngineError::InvalidArgument(_))
+    ));
+}
+
+#[test]
+fn test_bucket_query_accepts_both_cases() {
+    let kebab: ListBucketsQuery = serde_json::from_value(json!({
+        "max-buckets": 10,
+        "sort": "created-at",
+        "order": "desc",
+    }))
+    .unwrap();
+    let snake: ListBucketsQuery = serde_json::from_value(json!({
+        "max_buckets": 10,
+        "sort": "created_at",
+        "order": "desc",
+    }))
+    .unwrap();
+
+    assert_eq!(kebab, snake);
+    assert_eq!(kebab.sort, BucketSort::CreatedAt);
+}
@@ -0,0 +1,188 @@
+use std::path::PathBuf;
+
+use crab_vault_engine::{
+    DataEngine, MetaEngine, ObjectMeta,
+    error::EngineError,
+    fs::{FsDataEngine, FsMetaEngine},
+    list::{ListObjectsQuery, ObjectPage},
+    mem::{MemDataEngine, MemMetaEngine},
+};
+
+const BUCKET: &str = "bucket";
+
+fn fresh(base_dir: &str, name: &str) -> PathBuf {
+    let dir = PathBuf::from(base_dir).join(name);
+    if dir.exists() {
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+    dir
+}
+
+fn meta_of(object: &str, data: &[u8]) -> ObjectMeta {
+    ObjectMeta::builder()
+        .bucket_name(BUCKET)
+        .object_name(object)
+        .data(data)
+        .build()
+        .unwrap()
+}
+
+fn query(prefix: Option<&str>, delimiter: Option<&str>) -> ListObjectsQuery {
+    ListObjectsQuery {
+        prefix: prefix.map(str::to_string),
+        delimiter: delimiter.map(str::to_string),
+        ..ListObjectsQuery::default()
+    }
+}
+
+fn names(page: &ObjectPage) -> Vec<&str> {
+    page.objects
+        .iter()
+        .map(|v| v.object_name.as_str())
+        .collect()
+}
+
+/// 空 object、目录标记以及标记下面的 object
+async fn check_data_engine<E: DataEngine + Sync>(engine: E) {
+    engine.create_bucket(BUCKET).await.unwrap();
+
+    engine.create_object(BUCKET, "empty", b"").await.unwrap();
+    engine.create_object(BUCKET, "folder/", b"").await.unwrap();
+    engine
+        .create_object(BUCKET, "folder/a", b"a")
+        .await
+        .unwrap();
+    engine
+        .create_object(BUCKET, "folder/sub/", b"")
+        .await
+        .unwrap();
+
+    assert_eq!(engine.read_object(BUCKET, "empty").await.unwrap(), b"");
+    assert_eq!(engine.read_object(BUCKET, "folder/").await.unwrap(), b"");
+    assert_eq!(engine.read_object(BUCKET, "folder/a").await.unwrap(), b"a");
+    assert_eq!(
+        engine.read_object(BUCKET, "folder/sub/").await.unwrap(),
+        b""
+    );
+
+    // 标记可以有内容，条件写入同样区分有无 `/`
+    engine
+        .create_object(BUCKET, "folder/", b"marker")
+        .await
+        .unwrap();
+    assert_eq!(
+        engine.read_object(BUCKET, "folder/").await.unwrap(),
+        b"marker"
+    );
+    assert!(matches!(
+        engine.create_object_if_absent(BUCKET, "folder/", b"").await,
+        Err(EngineError::ObjectAlreadyExists { .. })
+    ));
+
+    engine
+        .copy_object(BUCKET, "folder/", BUCKET, "copied/")
+        .await
+        .unwrap();
+    assert_eq!(
+        engine.read_object(BUCKET, "copied/").await.unwrap(),
+        b"marker"
+    );
+
+    // 删除之后 bucket 是空的，中间目录也被一起删除
+    for object in ["empty", "folder/", "folder/a", "folder/sub/", "copied/"] {
+        engine.delete_object(BUCKET, object).await.unwrap();
+    }
+    assert!(matches!(
+        engine.read_object(BUCKET, "folder/").await,
+        Err(EngineError::ObjectNotFound { .. })
+    ));
+    engine.delete_bucket(BUCKET).await.unwrap();
+}
+
+async fn check_meta_engine<E: MetaEngine + Sync>(engine: E) {
+    for (name, data) in [
+        ("empty", &b""[..]),
+        ("folder/", b""),
+        ("folder/a", b"a"),
+        ("folder/sub/", b""),
+        ("other", b"o"),
+    ] {
+        engine
+            .create_object_meta(&meta_of(name, data))
+            .await
+            .unwrap();
+    }
+
+    let marker = engine.read_object_meta(BUCKET, "folder/").await.unwrap();
+    assert_eq!(marker.object_name, "folder/");
+    assert_eq!(marker.size, 0);
+    assert_eq!(
+        engine.read_object_meta(BUCKET, "empty").await.unwrap().etag,
+        meta_of("empty", b"").etag
+    );
+
+    // 顶层的标记被折叠为公共前缀
+    let page = engine
+        .list_objects_meta_page(BUCKET, &query(None, Some("/")))
+        .await
+        .unwrap();
+    assert_eq!(names(&page), ["empty", "other"]);
+    assert_eq!(page.common_prefixes, ["folder/"]);
+
+    // 在标记自己的前缀下，标记是一个普通的 object
+    let page = engine
+        .list_objects_meta_page(BUCKET, &query(Some("folder/"), Some("/")))
+        .await
+        .unwrap();
+    assert_eq!(names(&page), ["folder/", "folder/a"]);
+    assert_eq!(page.common_prefixes, ["folder/sub/"]);
+
+    let page = engine
+        .list_objects_meta_page(BUCKET, &ListObjectsQuery::default())
+        .await
+        .unwrap();
+    assert_eq!(
+        names(&page),
+        ["empty", "folder/", "folder/a", "folder/sub/", "other"]
+    );
+
+    engine.delete_object_meta(BUCKET, "folder/").await.unwrap();
+    assert!(matches!(
+        engine.read_object_meta(BUCKET, "folder/").await,
+        Err(EngineError::ObjectMetaNotFound { .. })
+    ));
+    assert!(engine.read_object_meta(BUCKET, "folder/a").await.is_ok());
+    assert_eq!(engine.list_objects_meta(BUCKET).await.unwrap().len(), 4);
+}
+
+#[tokio::test]
+async fn test_fs_markers() {
+    let data_dir = fresh("./data_test", "markers");
+    check_data_engine(FsDataEngine::new(&data_dir).unwrap()).await;
+
+    let meta_dir = fresh("./meta_test", "markers");
+    check_meta_engine(FsMetaEngine::new(&meta_dir).unwrap()).await;
+}
+
+#[tokio::test]
+async fn test_mem_markers() {
+    check_data_engine(MemDataEngine::new("mem://").unwrap()).await;
+    check_meta_engine(MemMetaEngine::new("mem://").unwrap()).await;
+}
+
+#[tokio::test]
+async fn test_fs_nested_objects_need_no_parent() {
+    let engine = FsDataEngine::new(fresh("./data_test", "markers_nested")).unwrap();
+
+    assert!(matches!(
+        engine.create_object(BUCKET, "a/b/c", b"c").await,
+        Err(EngineError::BucketNotFound { .. })
+    ));
+
+    engine.create_bucket(BUCKET).await.unwrap();
+    engine.create_object(BUCKET, "a/b/c", b"c").await.unwrap();
+    assert_eq!(engine.read_object(BUCKET, "a/b/c").await.unwrap(), b"c");
+
+    engine.delete_object(BUCKET, "a/b/c").await.unwrap();
+    engine.delete_bucket(BUCKET).await.unwrap();
+}
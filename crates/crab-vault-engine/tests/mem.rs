@@ -0,0 +1,111 @@
+use crab_vault_engine::{
+    BucketMeta, DataEngine, DataSource, MetaEngine, MetaSource, ObjectMeta,
+    error::EngineError,
+    mem::{MemDataEngine, MemMetaEngine},
+};
+
+#[tokio::test]
+async fn test_mem_data_roundtrip() {
+    let engine = MemDataEngine::new("mem://").unwrap();
+
+    assert!(matches!(
+        engine.create_object("bucket", "object", b"data").await,
+        Err(EngineError::BucketNotFound { .. })
+    ));
+
+    engine.create_bucket("bucket").await.unwrap();
+    engine
+        .create_object("bucket", "object", b"data")
+        .await
+        .unwrap();
+    assert_eq!(
+        engine.read_object("bucket", "object").await.unwrap(),
+        b"data"
+    );
+
+    // 覆盖已有的 object
+    engine
+        .create_object("bucket", "object", b"new")
+        .await
+        .unwrap();
+    assert_eq!(
+        engine.read_object("bucket", "object").await.unwrap(),
+        b"new"
+    );
+
+    assert!(matches!(
+        engine.delete_bucket("bucket").await,
+        Err(EngineError::BucketNotEmpty { .. })
+    ));
+
+    engine.delete_object("bucket", "object").await.unwrap();
+    engine.delete_object("bucket", "object").await.unwrap();
+    assert!(matches!(
+        engine.read_object("bucket", "object").await,
+        Err(EngineError::ObjectNotFound { .. })
+    ));
+
+    engine.delete_bucket("bucket").await.unwrap();
+    engine.delete_bucket("bucket").await.unwrap();
+}
+
+#[tokio::test]
+async fn test_mem_meta_roundtrip() {
+    let engine = MemMetaEngine::new("mem://").unwrap();
+    let bucket = BucketMeta::new("bucket".to_string(), serde_json::json!({ "k": "v" }));
+    let object = ObjectMeta {
+        bucket_name: "bucket".to_string(),
+        object_name: "object".to_string(),
+        size: 4,
+        ..ObjectMeta::default()
+    };
+
+    engine.create_bucket_meta(&bucket).await.unwrap();
+    engine.create_object_meta(&object).await.unwrap();
+
+    assert_eq!(engine.read_bucket_meta("bucket").await.unwrap(), bucket);
+    assert_eq!(
+        engine.read_object_meta("bucket", "object").await.unwrap(),
+        object
+    );
+    assert_eq!(engine.list_buckets_meta().await.unwrap(), vec![bucket]);
+    assert_eq!(
+        engine.list_objects_meta("bucket").await.unwrap(),
+        vec![object.clone()]
+    );
+    assert!(engine.list_objects_meta("other").await.unwrap().is_empty());
+
+    engine.touch_object("bucket", "object").await.unwrap();
+    engine.touch_bucket("bucket").await.unwrap();
+    assert!(
+        engine
+            .read_object_meta("bucket", "object")
+            .await
+            .unwrap()
+            .updated_at
+            > object.updated_at
+    );
+
+    assert!(matches!(
+        engine.delete_bucket_meta("bucket").await,
+        Err(EngineError::BucketNotEmpty { .. })
+    ));
+
+    engine.delete_object_meta("bucket", "object").await.unwrap();
+    engine.delete_bucket_meta("bucket").await.unwrap();
+
+    assert!(matches!(
+        engine.read_bucket_meta("bucket").await,
+        Err(EngineError::BucketMetaNotFound { .. })
+    ));
+    assert!(matches!(
+        engine.touch_object("bucket", "object").await,
+        Err(EngineError::ObjectMetaNotFound { .. })
+    ));
+}
+
+#[tokio::test]
+async fn test_mem_scheme_is_recognized() {
+    assert_eq!(DataSource::new("mem://").unwrap().scheme(), "mem");
+    assert_eq!(MetaSource::new("mem://").unwrap().scheme(), "mem");
+}
@@ -0,0 +1,232 @@
+use std::path::PathBuf;
+
+use crab_vault_engine::{
+    DataEngine, MetaEngine, ObjectMeta,
+    error::EngineError,
+    fs::{FsDataEngine, FsMetaEngine},
+    gc,
+    name::{
+        self, DEFAULT_INTERNAL_BUCKET, MAX_OBJECT_NAME_LEN, NamingRules, check_bucket_name,
+        validate_bucket_name, validate_object_name,
+    },
+};
+
+fn fresh(name: &str) -> PathBuf {
+    let dir = PathBuf::from("./data_test").join(name);
+    if dir.exists() {
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+    dir
+}
+
+#[test]
+fn test_object_names() {
+    for valid in [
+        "a",
+        "a/b/c",
+        "folder/",
+        "..a",
+        "a..",
+        ".hidden",
+        "with space",
+        "中文/名称",
+    ] {
+        assert!(validate_object_name(valid).is_ok(), "{valid}");
+    }
+
+    let too_long = "a".repeat(MAX_OBJECT_NAME_LEN + 1);
+    for invalid in [
+        "",
+        "/",
+        "/a",
+        "a//b",
+        "a//",
+        ".",
+        "..",
+        "../../etc/passwd",
+        "a/./b",
+        "a/../b",
+        "a\\b",
+        "a\nb",
+        "a\0b",
+        "a/.crab-vault-dir",
+        ".crab-vault-layout",
+        &too_long,
+    ] {
+        assert!(
+            matches!(
+                validate_object_name(invalid),
+                Err(EngineError::InvalidObjectName { .. })
+            ),
+            "{invalid:?}"
+        );
+    }
+}
+
+#[test]
+fn test_bucket_names() {
+    assert!(validate_bucket_name("bucket").is_ok());
+    assert!(validate_bucket_name("my.bucket-1").is_ok());
+
+    for invalid in ["", ".", "..", ".hidden", "a/b", "a\\b", "a\tb"] {
+        assert!(
+            matches!(
+                validate_bucket_name(invalid),
+                Err(EngineError::InvalidBucketName { .. })
+            ),
+            "{invalid:?}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_fs_rejects_traversal() {
+    let dir = fresh("name_traversal");
+    let data = FsDataEngine::new(dir.join("data")).unwrap();
+    let meta = FsMetaEngine::new(dir.join("meta")).unwrap();
+    data.create_bucket("bucket").await.unwrap();
+
+    assert!(matches!(
+        data.create_object("bucket", "../../escape", b"x").await,
+        Err(EngineError::InvalidObjectName { .. })
+    ));
+    assert!(matches!(
+        data.read_object("..", "data/bucket").await,
+        Err(EngineError::InvalidBucketName { .. })
+    ));
+    assert!(matches!(
+        meta.read_object_meta("bucket", "../../../data").await,
+        Err(EngineError::InvalidObjectName { .. })
+    ));
+    assert!(!dir.join("escape").exists());
+}
+
+#[tokio::test]
+async fn test_fs_long_names() {
+    let dir = fresh("name_long");
+    let data = FsDataEngine::new(dir.join("data")).unwrap();
+    let meta = FsMetaEngine::new(dir.join("meta")).unwrap();
+    data.create_bucket("bucket").await.unwrap();
+
+    // 超过文件名长度限制的段，以及只有最后几个字符不同的两个名称
+    let long = "x".repeat(300);
+    let first = format!("dir/{long}a");
+    let second = format!("{long}/b/");
+    for (object, body) in [(&first, &b"a"[..]), (&second, b"b")] {
+        data.create_object("bucket", object, body).await.unwrap();
+        meta.create_object_meta(
+            &ObjectMeta::builder()
+                .bucket_name("bucket")
+                .object_name(object)
+                .data(body)
+                .build()
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    }
+
+    assert_eq!(data.read_object("bucket", &first).await.unwrap(), b"a");
+    assert_eq!(data.read_object("bucket", &second).await.unwrap(), b"b");
+    assert!(matches!(
+        data.read_object("bucket", &format!("dir/{long}b")).await,
+        Err(EngineError::ObjectNotFound { .. })
+    ));
+
+    let mut names: Vec<_> = meta
+        .list_objects_meta("bucket")
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|v| v.object_name)
+        .collect();
+    names.sort();
+    assert_eq!(names, [first.clone(), second.clone()]);
+
+    // 数据中无法还原的名称不会被当作孤立的数据
+    assert!(gc::scan(&data, &meta).await.unwrap().is_empty());
+
+    for object in [&first, &second] {
+        data.delete_object("bucket", object).await.unwrap();
+        meta.delete_object_meta("bucket", object).await.unwrap();
+    }
+    data.delete_bucket("bucket").await.unwrap();
+}
+
+#[tokio::test]
+async fn test_internal_bucket() {
+    assert_eq!(name::internal_bucket(), DEFAULT_INTERNAL_BUCKET);
+    assert!(matches!(
+        validate_bucket_name(DEFAULT_INTERNAL_BUCKET),
+        Err(EngineError::InvalidBucketName { .. })
+    ));
+    assert!(check_bucket_name(DEFAULT_INTERNAL_BUCKET).is_ok());
+    assert!(!name::reserve("other").unwrap());
+
+    // 后端可以使用内部的 bucket，清理时不会把它当作空的 bucket
+    let dir = fresh("name_internal");
+    let data = FsDataEngine::new(&dir).unwrap();
+    let meta = FsMetaEngine::new(&dir).unwrap();
+    data.create_bucket(DEFAULT_INTERNAL_BUCKET).await.unwrap();
+    data.create_object(DEFAULT_INTERNAL_BUCKET, "inventory/manifest", b"m")
+        .await
+        .unwrap();
+    assert_eq!(
+        data.list_buckets().await.unwrap(),
+        [DEFAULT_INTERNAL_BUCKET]
+    );
+    assert_eq!(
+        data.read_object(DEFAULT_INTERNAL_BUCKET, "inventory/manifest")
+            .await
+            .unwrap(),
+        b"m"
+    );
+    assert!(gc::scan(&data, &meta).await.unwrap().is_empty());
+}
+
+#[test]
+fn test_s3_naming_rules() {
+    let s3 = NamingRules::S3;
+    for bucket in ["my-bucket", "a.b.c", "bucket-2026"] {
+        assert!(s3.validate_bucket_name(bucket).is_ok(), "{bucket}");
+    }
+    for bucket in [
+        "ab",
+        "My_Bucket",
+        "-a-",
+        "a..b",
+        "192.168.1.1",
+        "xn--abc",
+        "sthree-abc",
+        "x-s3alias",
+        "x--ol-s3",
+        DEFAULT_INTERNAL_BUCKET,
+    ] {
+        assert!(
+            matches!(
+                s3.validate_bucket_name(bucket),
+                Err(EngineError::InvalidBucketName { .. })
+            ),
+            "{bucket}"
+        );
+    }
+
+    assert!(s3.validate_object_name("photos/2026/cat.png").is_ok());
+    for object in ["a#b", "a{b", "100%", "a|b"] {
+        assert!(
+            matches!(
+                s3.validate_object_name(object),
+                Err(EngineError::InvalidObjectName { .. })
+            ),
+            "{object}"
+        );
+    }
+
+    // 默认的规则仍然接受这些名称
+    assert!(
+        NamingRules::Relaxed
+            .validate_bucket_name("My_Bucket")
+            .is_ok()
+    );
+    assert!(NamingRules::Relaxed.validate_object_name("a#b").is_ok());
+}
@@ -0,0 +1,191 @@
+use std::sync::Arc;
+
+use chrono::{DateTime, Duration, Utc};
+use crab_vault_engine::{
+    DataEngine, DataSource, MetaEngine, MetaSource, ObjectMeta,
+    error::EngineError,
+    lifecycle,
+    mem::MemMetaEngine,
+    retention::{self, ObjectRetention},
+};
+
+const BUCKET: &str = "bucket";
+
+fn until(v: DateTime<Utc>) -> ObjectRetention {
+    ObjectRetention {
+        retain_until: Some(v),
+        legal_hold: false,
+    }
+}
+
+fn hold() -> ObjectRetention {
+    ObjectRetention {
+        retain_until: None,
+        legal_hold: true,
+    }
+}
+
+fn object(name: &str, retention: ObjectRetention) -> ObjectMeta {
+    ObjectMeta {
+        bucket_name: BUCKET.to_string(),
+        object_name: name.to_string(),
+        retention,
+        ..ObjectMeta::default()
+    }
+}
+
+#[test]
+fn test_active() {
+    let now = Utc::now();
+    assert!(!ObjectRetention::default().is_active(now));
+    assert!(until(now + Duration::days(1)).is_active(now));
+    assert!(!until(now).is_active(now));
+    assert!(hold().is_active(now));
+
+    assert!(until(now + Duration::seconds(1)).validate(now).is_ok());
+    assert!(until(now - Duration::seconds(1)).validate(now).is_err());
+    assert!(hold().validate(now).is_ok());
+
+    assert!(matches!(
+        object("a", hold()).check_retention(now),
+        Err(EngineError::ObjectLocked { .. })
+    ));
+    assert!(object("a", until(now)).check_retention(now).is_ok());
+}
+
+#[test]
+fn test_change() {
+    let now = Utc::now();
+    let current = until(now + Duration::days(7));
+
+    // 保留期只能延长
+    assert!(!current.shortened_by(&until(now + Duration::days(8)), now));
+    assert!(!current.shortened_by(&current, now));
+    assert!(current.shortened_by(&until(now + Duration::days(1)), now));
+    assert!(current.shortened_by(&hold(), now));
+    assert!(current.shortened_by(&ObjectRetention::default(), now));
+
+    // 已经过去的保留期可以随意修改
+    let passed = until(now - Duration::days(1));
+    assert!(!passed.shortened_by(&ObjectRetention::default(), now));
+
+    assert!(hold().released_by(&current));
+    assert!(!hold().released_by(&hold()));
+    assert!(!current.released_by(&hold()));
+}
+
+#[test]
+fn test_serde() {
+    let retention: ObjectRetention =
+        serde_json::from_str(r#"{"retain-until":"2030-01-01T00:00:00Z","legal-hold":true}"#)
+            .unwrap();
+    assert!(retention.legal_hold);
+    assert!(serde_json::from_str::<ObjectRetention>(r#"{"mode":"compliance"}"#).is_err());
+
+    // 没有保留的 object 序列化之后与以前相同
+    let meta = serde_json::to_value(object("a", ObjectRetention::default())).unwrap();
+    assert!(meta.get("retention").is_none());
+}
+
+#[tokio::test]
+async fn test_check() {
+    let engine = MemMetaEngine::new("mem://").unwrap();
+    let now = Utc::now();
+    engine
+        .create_object_meta(&object("held", hold()))
+        .await
+        .unwrap();
+    engine
+        .create_object_meta(&object("free", ObjectRetention::default()))
+        .await
+        .unwrap();
+
+    assert!(matches!(
+        retention::check(&engine, BUCKET, "held", now).await,
+        Err(EngineError::ObjectLocked { .. })
+    ));
+    assert!(retention::check(&engine, BUCKET, "free", now).await.is_ok());
+    assert!(
+        retention::check(&engine, BUCKET, "missing", now)
+            .await
+            .is_ok()
+    );
+}
+
+#[tokio::test]
+async fn test_source_enforces() {
+    let meta_src = Arc::new(MetaSource::new("mem://").unwrap());
+    let data_src = DataSource::new("mem://")
+        .unwrap()
+        .guard_retention(meta_src.clone());
+    data_src.create_bucket(BUCKET).await.unwrap();
+
+    let mut held = object("held", hold());
+    held.etag = "a".to_string();
+    data_src.create_object(BUCKET, "held", b"a").await.unwrap();
+    meta_src.create_object_meta(&held).await.unwrap();
+    data_src.create_object(BUCKET, "free", b"b").await.unwrap();
+
+    let locked = |v| matches!(v, Err(EngineError::ObjectLocked { .. }));
+    assert!(locked(data_src.create_object(BUCKET, "held", b"c").await));
+    assert!(locked(
+        data_src
+            .append_object(BUCKET, "held", b"c")
+            .await
+            .map(|_| ())
+    ));
+    assert!(locked(data_src.delete_object(BUCKET, "held").await));
+    assert!(locked(
+        data_src.copy_object(BUCKET, "free", BUCKET, "held").await
+    ));
+    assert!(locked(meta_src.delete_object_meta(BUCKET, "held").await));
+
+    let names = ["held".to_string(), "free".to_string()];
+    let results = data_src.delete_objects(BUCKET, &names).await;
+    assert!(locked(results.into_iter().next().unwrap()));
+    assert_eq!(data_src.read_object(BUCKET, "held").await.unwrap(), b"a");
+    assert!(data_src.read_object(BUCKET, "free").await.is_err());
+
+    // 内容不变时可以修改其他元数据，也可以延长保留期，但是不能换成别的内容或者去掉保留期
+    let mut retained = held.clone();
+    retained.retention = until(Utc::now() + Duration::days(1));
+    retained.retention.legal_hold = true;
+    meta_src.create_object_meta(&retained).await.unwrap();
+
+    let mut replaced = retained.clone();
+    replaced.etag = "c".to_string();
+    assert!(locked(meta_src.create_object_meta(&replaced).await));
+    assert!(locked(meta_src.create_object_meta(&held).await));
+
+    // 不能让 object 在保留期内过期，过期时间在保留期之后时可以
+    let mut expiring = retained.clone();
+    expiring.expires_at = Some(Utc::now() - Duration::hours(1));
+    assert!(locked(meta_src.create_object_meta(&expiring).await));
+
+    expiring.retention.legal_hold = false;
+    expiring.expires_at = None;
+    meta_src.create_object_meta(&expiring).await.unwrap();
+    expiring.expires_at = Some(Utc::now() + Duration::days(2));
+    meta_src.create_object_meta(&expiring).await.unwrap();
+    expiring.expires_at = Some(Utc::now() + Duration::hours(1));
+    assert!(locked(meta_src.create_object_meta(&expiring).await));
+}
+
+#[tokio::test]
+async fn test_lifecycle_skips_locked() {
+    let engine = MemMetaEngine::new("mem://").unwrap();
+    let now = Utc::now();
+    for (name, retention) in [
+        ("held", hold()),
+        ("until", until(now + Duration::days(1))),
+        ("passed", until(now - Duration::days(1))),
+    ] {
+        let mut meta = object(name, retention);
+        meta.expires_at = Some(now - Duration::hours(1));
+        engine.create_object_meta(&meta).await.unwrap();
+    }
+
+    let expired = lifecycle::scan(&engine, BUCKET, None, now).await.unwrap();
+    let names: Vec<_> = expired.into_iter().map(|v| v.object).collect();
+    assert_eq!(names, ["passed"]);
+}
@@ -0,0 +1,33 @@
+// 不开启 tokio feature 时，文件系统后端不需要运行在 tokio 运行时中
+#![cfg(not(feature = "tokio"))]
+
+use crab_vault_engine::{
+    BucketMeta, DataEngine, MetaEngine,
+    fs::{FsDataEngine, FsMetaEngine},
+};
+use futures::executor::block_on;
+
+#[test]
+fn test_fs_engines_without_tokio() {
+    let (data_dir, meta_dir) = ("./data_test/no_tokio", "./meta_test/no_tokio");
+    for dir in [data_dir, meta_dir] {
+        if std::path::Path::new(dir).exists() {
+            std::fs::remove_dir_all(dir).unwrap();
+        }
+    }
+
+    block_on(async {
+        let data = FsDataEngine::new(data_dir).unwrap();
+        data.create_bucket("bucket").await.unwrap();
+        data.create_object("bucket", "a", b"hello").await.unwrap();
+        assert_eq!(data.read_object("bucket", "a").await.unwrap(), b"hello");
+
+        let meta = FsMetaEngine::new(meta_dir).unwrap();
+        let bucket = BucketMeta {
+            name: "bucket".to_string(),
+            ..BucketMeta::default()
+        };
+        meta.create_bucket_meta(&bucket).await.unwrap();
+        assert_eq!(meta.list_buckets_meta().await.unwrap(), [bucket]);
+    });
+}
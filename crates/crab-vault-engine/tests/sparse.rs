@@ -0,0 +1,139 @@
+use std::path::PathBuf;
+
+use crab_vault_engine::{
+    DataEngine, DataSource,
+    error::EngineError,
+    fs::{FsDataEngine, SparseMode},
+    mem::MemDataEngine,
+};
+
+const BUCKET: &str = "bucket";
+
+async fn check_data_engine<E: DataEngine + Sync>(engine: E) {
+    engine.create_bucket(BUCKET).await.unwrap();
+
+    // 不存在的 object 视为空的，越过末尾的部分读出为 0
+    engine
+        .write_object_at(BUCKET, "chunked", 4, b"efgh")
+        .await
+        .unwrap();
+    assert_eq!(
+        engine.read_object(BUCKET, "chunked").await.unwrap(),
+        b"\0\0\0\0efgh"
+    );
+
+    // 覆盖中间的一部分，不改变长度
+    engine
+        .write_object_at(BUCKET, "chunked", 0, b"abcdE")
+        .await
+        .unwrap();
+    assert_eq!(
+        engine.read_object(BUCKET, "chunked").await.unwrap(),
+        b"abcdEfgh"
+    );
+
+    // 追加
+    engine
+        .write_object_at(BUCKET, "chunked", 8, b"ij")
+        .await
+        .unwrap();
+    assert_eq!(
+        engine.read_object(BUCKET, "chunked").await.unwrap(),
+        b"abcdEfghij"
+    );
+
+    // 超出末尾的部分被忽略
+    engine.punch_hole(BUCKET, "chunked", 2, 3).await.unwrap();
+    engine.punch_hole(BUCKET, "chunked", 8, 100).await.unwrap();
+    engine.punch_hole(BUCKET, "chunked", 100, 1).await.unwrap();
+    assert_eq!(
+        engine.read_object(BUCKET, "chunked").await.unwrap(),
+        b"ab\0\0\0fgh\0\0"
+    );
+
+    // 追加返回原来的长度
+    assert_eq!(
+        engine.append_object(BUCKET, "chunked", b"kl").await.unwrap(),
+        10
+    );
+    assert_eq!(
+        engine.read_object(BUCKET, "chunked").await.unwrap(),
+        b"ab\0\0\0fgh\0\0kl"
+    );
+
+    assert!(matches!(
+        engine.punch_hole(BUCKET, "missing", 0, 1).await,
+        Err(EngineError::ObjectNotFound { .. })
+    ));
+    assert!(matches!(
+        engine.append_object(BUCKET, "missing", b"data").await,
+        Err(EngineError::ObjectNotFound { .. })
+    ));
+    assert!(matches!(
+        engine.write_object_at("missing", "object", 0, b"data").await,
+        Err(EngineError::BucketNotFound { .. })
+    ));
+}
+
+async fn clean(base_dir: &PathBuf) {
+    if base_dir.exists() {
+        tokio::fs::remove_dir_all(base_dir).await.unwrap();
+    }
+}
+
+#[tokio::test]
+async fn test_mem_partial_writes() {
+    check_data_engine(MemDataEngine::new("mem://").unwrap()).await;
+}
+
+#[tokio::test]
+async fn test_fs_partial_writes() {
+    for (name, sparse) in [("sparse_auto", SparseMode::Auto), ("sparse_off", SparseMode::Off)] {
+        let data_dir = PathBuf::from("./data_test").join(name);
+        clean(&data_dir).await;
+
+        check_data_engine(FsDataEngine::new(&data_dir).unwrap().sparse(sparse)).await;
+    }
+}
+
+#[tokio::test]
+async fn test_fs_uri_parameters() {
+    let data_dir = PathBuf::from("./data_test").join("sparse_uri");
+    clean(&data_dir).await;
+
+    let uri = format!("{}?sparse=off", data_dir.display());
+    check_data_engine(DataSource::new(&uri).unwrap()).await;
+
+    for uri in ["./data_test/sparse_uri?sparse=yes", "./data_test/sparse_uri?unknown=1"] {
+        assert!(matches!(
+            FsDataEngine::from_uri(uri),
+            Err(EngineError::InvalidArgument(_))
+        ));
+    }
+}
+
+/// 只在支持 `FALLOC_FL_PUNCH_HOLE` 的文件系统上才会真正释放空间，所以这里只检查占用没有增加
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn test_fs_punch_hole_does_not_allocate() {
+    use std::os::unix::fs::MetadataExt;
+
+    let data_dir = PathBuf::from("./data_test").join("sparse_blocks");
+    clean(&data_dir).await;
+
+    let engine = FsDataEngine::new(&data_dir).unwrap();
+    engine.create_bucket(BUCKET).await.unwrap();
+    engine
+        .create_object(BUCKET, "large", &vec![1; 1 << 20])
+        .await
+        .unwrap();
+
+    let path = data_dir.join(BUCKET).join("large");
+    let before = std::fs::metadata(&path).unwrap();
+
+    engine.punch_hole(BUCKET, "large", 0, 1 << 19).await.unwrap();
+
+    let after = std::fs::metadata(&path).unwrap();
+    assert_eq!(after.len(), before.len());
+    assert!(after.blocks() <= before.blocks());
+}
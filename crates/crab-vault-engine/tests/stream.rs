@@ -0,0 +1,103 @@
+use std::path::PathBuf;
+
+use chrono::{Duration, Utc};
+use crab_vault_engine::{
+    BucketMeta, MetaEngine, ObjectMeta, fs::FsMetaEngine, list::ListObjectsQuery,
+    mem::MemMetaEngine,
+};
+use futures::TryStreamExt;
+
+const BUCKET: &str = "bucket";
+
+fn object(name: &str) -> ObjectMeta {
+    ObjectMeta {
+        bucket_name: BUCKET.to_string(),
+        object_name: name.to_string(),
+        ..ObjectMeta::default()
+    }
+}
+
+fn bucket(name: &str) -> BucketMeta {
+    BucketMeta {
+        name: name.to_string(),
+        ..BucketMeta::default()
+    }
+}
+
+async fn stream_names<E: MetaEngine>(engine: &E, query: &ListObjectsQuery) -> Vec<String> {
+    let mut names = engine
+        .stream_objects_meta(BUCKET, query)
+        .map_ok(|v| v.object_name)
+        .try_collect::<Vec<_>>()
+        .await
+        .unwrap();
+    names.sort();
+    names
+}
+
+async fn check_engine<E: MetaEngine>(engine: E) {
+    for name in ["a", "b"] {
+        engine.create_bucket_meta(&bucket(name)).await.unwrap();
+    }
+    for name in ["a", "docs/a", "docs/img/b"] {
+        engine.create_object_meta(&object(name)).await.unwrap();
+    }
+    let future = ObjectMeta {
+        created_at: Utc::now() + Duration::hours(1),
+        ..object("docs/future")
+    };
+    engine.create_object_meta(&future).await.unwrap();
+
+    let mut buckets = engine
+        .stream_buckets_meta()
+        .map_ok(|v| v.name)
+        .try_collect::<Vec<_>>()
+        .await
+        .unwrap();
+    buckets.sort();
+    assert_eq!(buckets, ["a", "b"]);
+
+    assert_eq!(
+        stream_names(&engine, &ListObjectsQuery::default()).await,
+        ["a", "docs/a", "docs/future", "docs/img/b"]
+    );
+
+    // 分页相关的条件被忽略，前缀和时间条件生效
+    let query = ListObjectsQuery {
+        prefix: Some("docs/".to_string()),
+        delimiter: Some("/".to_string()),
+        max_keys: Some(1),
+        created_before: Some(Utc::now()),
+        ..ListObjectsQuery::default()
+    };
+    assert_eq!(
+        stream_names(&engine, &query).await,
+        ["docs/a", "docs/img/b"]
+    );
+}
+
+#[tokio::test]
+async fn test_mem_stream() {
+    check_engine(MemMetaEngine::new("mem://").unwrap()).await;
+}
+
+#[tokio::test]
+async fn test_fs_stream() {
+    let base_dir = PathBuf::from("./meta_test").join("stream");
+    if base_dir.exists() {
+        tokio::fs::remove_dir_all(&base_dir).await.unwrap();
+    }
+    check_engine(FsMetaEngine::new(&base_dir).unwrap()).await;
+}
+
+#[tokio::test]
+async fn test_stream_from_nonexistent_bucket_is_empty() {
+    let base_dir = PathBuf::from("./meta_test").join("stream_empty");
+    let engine = FsMetaEngine::new(&base_dir).unwrap();
+
+    assert!(
+        stream_names(&engine, &ListObjectsQuery::default())
+            .await
+            .is_empty()
+    );
+}
@@ -0,0 +1,71 @@
+use crab_vault_engine::{
+    MetaEngine, ObjectMeta,
+    mem::MemMetaEngine,
+    tagging::{self, MAX_TAG_KEY_LEN, MAX_TAG_VALUE_LEN, MAX_TAGS, Tagging, Tags},
+};
+
+fn tags(pairs: &[(&str, &str)]) -> Tags {
+    pairs
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect()
+}
+
+#[test]
+fn test_validate() {
+    assert!(tagging::validate(&Tags::new()).is_ok());
+    assert!(tagging::validate(&tags(&[("project", "apollo"), ("empty", "")])).is_ok());
+
+    let many = (0..=MAX_TAGS)
+        .map(|i| (format!("k{i}"), "v".to_string()))
+        .collect();
+    assert!(tagging::validate(&many).is_err());
+
+    assert!(tagging::validate(&tags(&[("", "v")])).is_err());
+    assert!(tagging::validate(&tags(&[(&"k".repeat(MAX_TAG_KEY_LEN + 1), "v")])).is_err());
+    assert!(tagging::validate(&tags(&[("k", &"v".repeat(MAX_TAG_VALUE_LEN + 1))])).is_err());
+    assert!(tagging::validate(&tags(&[("k", "a\nb")])).is_err());
+
+    // 长度按字符计算
+    assert!(tagging::validate(&tags(&[(&"键".repeat(MAX_TAG_KEY_LEN), "值")])).is_ok());
+
+    let parsed: Tagging = serde_json::from_str(r#"{"tags":{"project":"apollo"}}"#).unwrap();
+    assert_eq!(parsed.tags, tags(&[("project", "apollo")]));
+    assert!(serde_json::from_str::<Tagging>(r#"{"tag-set":[]}"#).is_err());
+}
+
+#[test]
+fn test_has_tags() {
+    let meta = ObjectMeta {
+        tags: tags(&[("project", "apollo"), ("stage", "dev")]),
+        ..ObjectMeta::default()
+    };
+
+    assert!(meta.has_tags(&Tags::new()));
+    assert!(meta.has_tags(&tags(&[("project", "apollo")])));
+    assert!(meta.has_tags(&tags(&[("project", "apollo"), ("stage", "dev")])));
+    assert!(!meta.has_tags(&tags(&[("project", "gemini")])));
+    assert!(!meta.has_tags(&tags(&[("owner", "ci")])));
+}
+
+#[tokio::test]
+async fn test_tags_are_stored() {
+    let engine = MemMetaEngine::new("mem://").unwrap();
+    let meta = ObjectMeta {
+        bucket_name: "bucket".to_string(),
+        object_name: "object".to_string(),
+        tags: tags(&[("project", "apollo")]),
+        ..ObjectMeta::default()
+    };
+    engine.create_object_meta(&meta).await.unwrap();
+    assert_eq!(
+        engine.read_object_meta("bucket", "object").await.unwrap(),
+        meta
+    );
+
+    // 没有标签时不写入这个字段，之前保存的元数据中也没有它
+    let json = serde_json::to_value(ObjectMeta::default()).unwrap();
+    assert!(json.get("tags").is_none());
+    let parsed: ObjectMeta = serde_json::from_value(json).unwrap();
+    assert!(parsed.tags.is_empty());
+}
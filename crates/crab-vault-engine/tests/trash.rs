@@ -0,0 +1,182 @@
+use std::path::PathBuf;
+
+use chrono::{DateTime, Duration, Utc};
+use crab_vault_engine::{
+    DataEngine, MetaEngine, ObjectMeta,
+    error::EngineError,
+    fs::{FsDataEngine, FsMetaEngine},
+    mem::{MemDataEngine, MemMetaEngine},
+    name,
+    trash::{self, TRASH_PREFIX, TrashEntry},
+};
+
+const BUCKET: &str = "bucket";
+
+fn object(name: &str, data: &[u8]) -> ObjectMeta {
+    ObjectMeta::builder()
+        .bucket_name(BUCKET)
+        .object_name(name)
+        .data(data)
+        .content_type("text/plain")
+        .build()
+        .unwrap()
+}
+
+async fn store<D, M>(data: &D, meta: &M, object: &ObjectMeta, content: &[u8])
+where
+    D: DataEngine + Sync,
+    M: MetaEngine + Sync,
+{
+    data.create_object(BUCKET, &object.object_name, content)
+        .await
+        .unwrap();
+    meta.create_object_meta(object).await.unwrap();
+}
+
+/// 模拟删除：先放进回收站，再删除原来的 object
+async fn delete<D, M>(data: &D, meta: &M, object: &ObjectMeta, now: DateTime<Utc>) -> TrashEntry
+where
+    D: DataEngine + Sync,
+    M: MetaEngine + Sync,
+{
+    let entry = trash::put(data, meta, object, now).await.unwrap();
+    data.delete_object(BUCKET, &object.object_name)
+        .await
+        .unwrap();
+    meta.delete_object_meta(BUCKET, &object.object_name)
+        .await
+        .unwrap();
+    entry
+}
+
+async fn check_engine<D, M>(data: D, meta: M)
+where
+    D: DataEngine + Sync,
+    M: MetaEngine + Sync,
+{
+    data.create_bucket(BUCKET).await.unwrap();
+    assert!(trash::list(&meta, BUCKET).await.unwrap().is_empty());
+
+    let now = Utc::now();
+    let v1 = object("docs/a", b"first");
+    store(&data, &meta, &v1, b"first").await;
+    let first = delete(&data, &meta, &v1, now - Duration::hours(2)).await;
+
+    let v2 = object("docs/a", b"second");
+    store(&data, &meta, &v2, b"second").await;
+    let second = delete(&data, &meta, &v2, now - Duration::hours(1)).await;
+
+    let other = object("b", b"other");
+    store(&data, &meta, &other, b"other").await;
+    delete(&data, &meta, &other, now).await;
+
+    // 最近删除的在前，同一个 object 的每一次删除都有自己的条目
+    let entries = trash::list(&meta, BUCKET).await.unwrap();
+    let names: Vec<_> = entries.iter().map(|v| v.object_name.as_str()).collect();
+    assert_eq!(names, ["b", "docs/a", "docs/a"]);
+    assert_eq!(entries[1], second);
+    assert_eq!(entries[2], first);
+    assert_eq!(first.size, 5);
+
+    // 回收站中的 object 在内部 bucket 中，与其他 bucket 互不干扰
+    let key = first.key(BUCKET);
+    assert!(key.starts_with(TRASH_PREFIX));
+    assert!(
+        data.read_object(name::internal_bucket(), &key)
+            .await
+            .is_ok()
+    );
+    assert!(trash::list(&meta, "another").await.unwrap().is_empty());
+
+    // 没有给出 id 时恢复最近的一次删除
+    let found = trash::find(&meta, BUCKET, "docs/a", None).await.unwrap();
+    assert_eq!(found, second);
+    let found = trash::find(&meta, BUCKET, "docs/a", Some(&first.id))
+        .await
+        .unwrap();
+    assert_eq!(found, first);
+    assert!(matches!(
+        trash::find(&meta, BUCKET, "missing", None).await,
+        Err(EngineError::TrashEntryNotFound { .. })
+    ));
+    assert!(matches!(
+        trash::find(&meta, BUCKET, "docs/a", Some("0")).await,
+        Err(EngineError::TrashEntryNotFound { .. })
+    ));
+
+    let restored = trash::read(&meta, BUCKET, &first).await.unwrap();
+    assert_eq!(restored, v1);
+    trash::restore(&data, &meta, &first, &restored)
+        .await
+        .unwrap();
+    assert_eq!(data.read_object(BUCKET, "docs/a").await.unwrap(), b"first");
+    assert_eq!(meta.read_object_meta(BUCKET, "docs/a").await.unwrap(), v1);
+    assert!(
+        data.read_object(name::internal_bucket(), &key)
+            .await
+            .is_err()
+    );
+    assert_eq!(trash::list(&meta, BUCKET).await.unwrap().len(), 2);
+    assert!(matches!(
+        trash::read(&meta, BUCKET, &first).await,
+        Err(EngineError::TrashEntryNotFound { .. })
+    ));
+
+    // 只有保留期之前删除的条目过期
+    let expired = trash::expired(&meta, now - Duration::minutes(30))
+        .await
+        .unwrap();
+    assert_eq!(expired, vec![(BUCKET.to_string(), second.clone())]);
+    trash::remove(&data, &meta, BUCKET, &second).await.unwrap();
+    let names: Vec<_> = trash::list(&meta, BUCKET)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|v| v.object_name)
+        .collect();
+    assert_eq!(names, ["b"]);
+}
+
+#[test]
+fn test_parse() {
+    let now = DateTime::from_timestamp_micros(1_700_000_000_123_456).unwrap();
+    let entry = TrashEntry::new(&object("a/b", b"data"), now);
+    assert_eq!(entry.id, "1700000000123456");
+
+    let mut meta = object("a/b", b"data");
+    meta.object_name = entry.key(BUCKET);
+    assert_eq!(meta.object_name, "trash/bucket/1700000000123456/a/b");
+    assert_eq!(TrashEntry::parse(&meta), Some((BUCKET.to_string(), entry)));
+
+    for name in [
+        "audit/x",
+        "trash/bucket",
+        "trash/bucket/abc/a",
+        "trash/bucket/123",
+    ] {
+        meta.object_name = name.to_string();
+        assert_eq!(TrashEntry::parse(&meta), None);
+    }
+}
+
+#[tokio::test]
+async fn test_mem_trash() {
+    check_engine(
+        MemDataEngine::new("mem://").unwrap(),
+        MemMetaEngine::new("mem://").unwrap(),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn test_fs_trash() {
+    let base_dir = PathBuf::from("./data_test").join("trash");
+    if base_dir.exists() {
+        tokio::fs::remove_dir_all(&base_dir).await.unwrap();
+    }
+    check_engine(
+        FsDataEngine::new(base_dir.join("data")).unwrap(),
+        FsMetaEngine::new(base_dir.join("meta")).unwrap(),
+    )
+    .await;
+}
@@ -0,0 +1,161 @@
+use std::path::PathBuf;
+
+use crab_vault_engine::{
+    BucketMeta, MetaEngine, MetaSource, ObjectMeta,
+    error::EngineError,
+    fs::FsMetaEngine,
+    mem::MemMetaEngine,
+    usage::{BucketStats, BucketUsage, StorageStats},
+};
+
+const BUCKET: &str = "bucket";
+
+fn fresh(name: &str) -> PathBuf {
+    let dir = PathBuf::from("./meta_test").join(name);
+    if dir.exists() {
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+    dir
+}
+
+fn object(name: &str, size: u64) -> ObjectMeta {
+    ObjectMeta {
+        bucket_name: BUCKET.to_string(),
+        object_name: name.to_string(),
+        size,
+        ..ObjectMeta::default()
+    }
+}
+
+fn usage(object_count: u64, total_bytes: u64) -> BucketUsage {
+    BucketUsage {
+        object_count,
+        total_bytes,
+    }
+}
+
+/// 先读取一次用量，之后的修改都应该反映在计数中
+async fn check_engine<E: MetaEngine + Sync>(engine: &E) {
+    assert_eq!(engine.bucket_usage(BUCKET).await.unwrap(), usage(0, 0));
+
+    engine.create_object_meta(&object("a", 10)).await.unwrap();
+    engine
+        .create_object_meta(&object("dir/b", 5))
+        .await
+        .unwrap();
+    assert_eq!(engine.bucket_usage(BUCKET).await.unwrap(), usage(2, 15));
+
+    // 替换只改变大小，删除不存在的 object 不改变用量
+    engine.create_object_meta(&object("a", 3)).await.unwrap();
+    engine.delete_object_meta(BUCKET, "missing").await.unwrap();
+    assert_eq!(engine.bucket_usage(BUCKET).await.unwrap(), usage(2, 8));
+
+    engine.delete_object_meta(BUCKET, "dir/b").await.unwrap();
+    assert_eq!(engine.bucket_usage(BUCKET).await.unwrap(), usage(1, 3));
+    assert_eq!(engine.bucket_usage("other").await.unwrap(), usage(0, 0));
+}
+
+#[tokio::test]
+async fn test_mem_usage() {
+    check_engine(&MemMetaEngine::default()).await;
+}
+
+#[tokio::test]
+async fn test_fs_usage() {
+    let dir = fresh("usage");
+    check_engine(&FsMetaEngine::new(&dir).unwrap()).await;
+
+    // 新的实例重新统计，得到同样的结果
+    let engine = FsMetaEngine::new(&dir).unwrap();
+    assert_eq!(engine.bucket_usage(BUCKET).await.unwrap(), usage(1, 3));
+
+    // 没有读取过用量时的修改在第一次读取时统计进去
+    let engine = FsMetaEngine::new(&dir).unwrap();
+    engine.create_object_meta(&object("c", 7)).await.unwrap();
+    assert_eq!(engine.bucket_usage(BUCKET).await.unwrap(), usage(2, 10));
+}
+
+/// 一个空的 bucket、一个有 object 的 bucket，以及一个只有 object 元数据的 bucket
+async fn check_stats<E: MetaEngine + Sync>(engine: &E) {
+    assert_eq!(engine.stats().await.unwrap(), StorageStats::default());
+
+    for name in ["empty", BUCKET] {
+        let meta = BucketMeta {
+            name: name.to_string(),
+            ..BucketMeta::default()
+        };
+        engine.create_bucket_meta(&meta).await.unwrap();
+    }
+    engine.create_object_meta(&object("a", 10)).await.unwrap();
+    engine.create_object_meta(&object("b", 5)).await.unwrap();
+    let mut orphan = object("c", 1);
+    orphan.bucket_name = "orphan".to_string();
+    engine.create_object_meta(&orphan).await.unwrap();
+
+    let stats = |name: &str, usage| BucketStats {
+        name: name.to_string(),
+        usage,
+    };
+    assert_eq!(
+        engine.stats().await.unwrap(),
+        StorageStats {
+            bucket_count: 3,
+            object_count: 3,
+            total_bytes: 16,
+            buckets: vec![
+                stats(BUCKET, usage(2, 15)),
+                stats("empty", usage(0, 0)),
+                stats("orphan", usage(1, 1)),
+            ],
+        }
+    );
+}
+
+#[tokio::test]
+async fn test_mem_stats() {
+    check_stats(&MemMetaEngine::default()).await;
+}
+
+#[tokio::test]
+async fn test_fs_stats() {
+    check_stats(&FsMetaEngine::new(fresh("stats")).unwrap()).await;
+}
+
+#[tokio::test]
+async fn test_source_usage() {
+    let source = MetaSource::new("mem://").unwrap();
+    source.create_object_meta(&object("a", 4)).await.unwrap();
+    assert_eq!(source.bucket_usage(BUCKET).await.unwrap(), usage(1, 4));
+}
+
+#[test]
+fn test_check_quota() {
+    let meta = BucketMeta {
+        name: BUCKET.to_string(),
+        max_bytes: Some(10),
+        max_objects: Some(2),
+        ..BucketMeta::default()
+    };
+    assert!(meta.has_quota());
+    assert!(!BucketMeta::default().has_quota());
+
+    assert!(meta.check_quota(usage(1, 4), None, 6).is_ok());
+    assert!(matches!(
+        meta.check_quota(usage(1, 4), None, 7),
+        Err(EngineError::ByteQuotaExceeded { limit: 10, .. })
+    ));
+    assert!(matches!(
+        meta.check_quota(usage(2, 4), None, 1),
+        Err(EngineError::ObjectQuotaExceeded { limit: 2, .. })
+    ));
+
+    // 替换已有的 object 不增加个数，只计算大小的变化
+    assert!(meta.check_quota(usage(2, 9), Some(5), 6).is_ok());
+    assert!(matches!(
+        meta.check_quota(usage(2, 9), Some(5), 7),
+        Err(EngineError::ByteQuotaExceeded { .. })
+    ));
+
+    // 已经超出配额时，让用量变小的写入仍然被允许
+    assert!(meta.check_quota(usage(3, 20), Some(8), 2).is_ok());
+}
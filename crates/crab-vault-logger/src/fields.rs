@@ -0,0 +1,125 @@
+use std::marker::PhantomData;
+
+use tracing::span;
+use tracing_subscriber::{layer::Context, registry::LookupSpan};
+
+use crate::scrub::Scrubber;
+
+/// 转换成文本的字段，按照记录的顺序排列
+#[derive(Default)]
+pub(crate) struct TextFields {
+    pub fields: Vec<(&'static str, String)>,
+}
+
+/// span 上的字段，`L` 是使用它的日志层，每个日志层在 span 的 extensions 中各存一份
+pub(crate) struct SpanTextFields<L> {
+    fields: TextFields,
+    _layer: PhantomData<fn(L)>,
+}
+
+impl TextFields {
+    /// 同名的字段覆盖掉，保持字段第一次出现时的位置
+    pub fn set(&mut self, name: &'static str, value: String) {
+        match self.fields.iter_mut().find(|(v, _)| *v == name) {
+            Some((_, old)) => *old = value,
+            None => self.fields.push((name, value)),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(v, _)| *v == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn scrub(&mut self, scrubber: &Scrubber) {
+        if scrubber.is_noop() {
+            return;
+        }
+
+        for (name, value) in self.fields.iter_mut() {
+            if let Some(scrubbed) = scrubber.scrub(name, value) {
+                *value = scrubbed;
+            }
+        }
+    }
+}
+
+impl<L: 'static> SpanTextFields<L> {
+    pub fn on_new_span<S>(
+        attrs: &span::Attributes<'_>,
+        id: &span::Id,
+        ctx: Context<'_, S>,
+        scrubber: &Scrubber,
+    ) where
+        S: tracing::Subscriber + for<'lookup> LookupSpan<'lookup>,
+    {
+        let mut fields = TextFields::default();
+        attrs.record(&mut fields);
+        fields.scrub(scrubber);
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(Self {
+                fields,
+                _layer: PhantomData,
+            });
+        }
+    }
+
+    pub fn on_record<S>(
+        id: &span::Id,
+        values: &span::Record<'_>,
+        ctx: Context<'_, S>,
+        scrubber: &Scrubber,
+    ) where
+        S: tracing::Subscriber + for<'lookup> LookupSpan<'lookup>,
+    {
+        let mut recorded = TextFields::default();
+        values.record(&mut recorded);
+        recorded.scrub(scrubber);
+
+        if let Some(span) = ctx.span(id)
+            && let Some(storage) = span.extensions_mut().get_mut::<Self>()
+        {
+            for (name, value) in recorded.fields {
+                storage.fields.set(name, value);
+            }
+        }
+    }
+
+    /// 事件所在的所有 span 上的字段，从最外层开始，内层的同名字段覆盖外层的
+    pub fn collect<S>(event: &tracing::Event<'_>, ctx: &Context<'_, S>) -> TextFields
+    where
+        S: tracing::Subscriber + for<'lookup> LookupSpan<'lookup>,
+    {
+        let mut collected = TextFields::default();
+        if let Some(scope) = ctx.event_scope(event) {
+            for span in scope.from_root() {
+                if let Some(storage) = span.extensions().get::<Self>() {
+                    for (name, value) in &storage.fields.fields {
+                        collected.set(name, value.clone());
+                    }
+                }
+            }
+        }
+        collected
+    }
+}
+
+impl tracing::field::Visit for TextFields {
+    fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+        self.set(field.name(), value.to_string());
+    }
+
+    fn record_error(
+        &mut self,
+        field: &tracing::field::Field,
+        value: &(dyn std::error::Error + 'static),
+    ) {
+        self.set(field.name(), value.to_string());
+    }
+
+    fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+        self.set(field.name(), format!("{value:?}"));
+    }
+}
@@ -0,0 +1,135 @@
+use std::{fmt::Display, str::FromStr};
+
+use clap::ValueEnum;
+
+use crate::LogLevel;
+
+/// ## 按照模块设置的日志级别
+///
+/// 由逗号分隔的 `target=level` 组成，例如 `crab_vault=debug,tower=warn`。与 `tracing_subscriber`
+/// 的 `EnvFilter` 一样，`target` 是事件的 target 的前缀，多条同时匹配时最长的一条生效；
+/// 都不匹配时使用日志层自己的最低级别，所以这里不接受单独的级别
+///
+/// # 示例
+/// ```
+/// # use crab_vault_logger::{LogLevel, filter::TargetFilter};
+/// let filter: TargetFilter = "crab_vault=debug, crab_vault::http=warn".parse().unwrap();
+///
+/// assert!(filter.enabled("crab_vault::engine", LogLevel::Debug, LogLevel::Info));
+/// assert!(!filter.enabled("crab_vault::http::server", LogLevel::Info, LogLevel::Info));
+/// // 没有匹配的规则时使用默认的级别
+/// assert!(!filter.enabled("hyper::proto", LogLevel::Debug, LogLevel::Info));
+///
+/// assert_eq!(filter.to_string(), "crab_vault::http=warn,crab_vault=debug");
+/// assert!("debug".parse::<TargetFilter>().is_err());
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TargetFilter {
+    /// 按照 target 的长度从长到短排列，第一条匹配的就是最长的
+    directives: Vec<Directive>,
+}
+
+/// [`TargetFilter`] 中的一条规则
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Directive {
+    pub target: String,
+    pub level: LogLevel,
+}
+
+/// 无法解析的 [`TargetFilter`]
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+#[error("invalid log filter directive `{directive}`, {reason}")]
+pub struct ParseFilterError {
+    directive: String,
+    reason: &'static str,
+}
+
+impl TargetFilter {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 添加一条规则，target 相同的规则被替换
+    pub fn with_directive(mut self, target: impl Into<String>, level: LogLevel) -> Self {
+        let target = target.into();
+        self.directives.retain(|v| v.target != target);
+        let index = self
+            .directives
+            .partition_point(|v| v.target.len() >= target.len());
+        self.directives.insert(index, Directive { target, level });
+        self
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.directives.is_empty()
+    }
+
+    #[inline]
+    pub fn directives(&self) -> &[Directive] {
+        &self.directives
+    }
+
+    /// 对 `target` 生效的级别，没有匹配的规则时为 [`None`]
+    pub fn level_for(&self, target: &str) -> Option<LogLevel> {
+        self.directives
+            .iter()
+            .find(|v| target.starts_with(&v.target))
+            .map(|v| v.level)
+    }
+
+    /// `target` 中 `level` 级别的事件是否需要输出，没有匹配的规则时与 `default` 比较
+    #[inline]
+    pub fn enabled(&self, target: &str, level: LogLevel, default: LogLevel) -> bool {
+        level >= self.level_for(target).unwrap_or(default)
+    }
+}
+
+impl FromStr for TargetFilter {
+    type Err = ParseFilterError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut filter = Self::new();
+        for directive in s.split(',').map(str::trim).filter(|v| !v.is_empty()) {
+            let err = |reason| ParseFilterError {
+                directive: directive.to_string(),
+                reason,
+            };
+
+            let Some((target, level)) = directive.split_once('=') else {
+                return Err(err(
+                    "expected `target=level`, use `logger.level` for the default level",
+                ));
+            };
+            let target = target.trim();
+            if target.is_empty() {
+                return Err(err("the target is empty"));
+            }
+            let level = <LogLevel as ValueEnum>::from_str(level.trim(), true)
+                .map_err(|_| err("the level must be one of trace, debug, info, warn and error"))?;
+
+            filter = filter.with_directive(target, level);
+        }
+        Ok(filter)
+    }
+}
+
+impl Display for TargetFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (index, directive) in self.directives.iter().enumerate() {
+            if index > 0 {
+                f.write_str(",")?;
+            }
+            write!(f, "{directive}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for Directive {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let level = self.level.to_possible_value().expect("no level is skipped");
+        write!(f, "{}={}", self.target, level.get_name())
+    }
+}
@@ -0,0 +1,185 @@
+//! # 输出到 systemd-journald
+//!
+//! 使用 journald 的原生协议，每个事件是发往 `/run/systemd/journal/socket` 的一个报文，
+//! 字段保持结构化，可以用 `journalctl REQ_ID=...` 这样的条件查询。
+//!
+//! 字段名被转换为 journald 接受的形式：大写，非字母数字的字符替换为 `_`，不能以 `_` 或者数字开头。
+//! 超出 socket 报文大小限制的事件无法发送，原生协议要求这时改用 memfd，这里没有实现。
+
+use std::{
+    io,
+    os::unix::net::UnixDatagram,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use tracing::span;
+use tracing_subscriber::Layer;
+
+use crate::{
+    LogLevel,
+    fields::{SpanTextFields, TextFields},
+    filter::TargetFilter,
+    scrub::Scrubber,
+    syslog::severity,
+};
+
+/// journald 接收日志的 socket
+pub const JOURNALD_SOCKET: &str = "/run/systemd/journal/socket";
+
+pub struct JournaldLogger {
+    socket: UnixDatagram,
+    identifier: String,
+    min_level: LogLevel,
+    filter: TargetFilter,
+    scrubber: Scrubber,
+
+    /// 发送失败只报告一次
+    reported: AtomicBool,
+}
+
+impl<S> Layer<S> for JournaldLogger
+where
+    S: tracing::Subscriber + for<'lookup> tracing_subscriber::registry::LookupSpan<'lookup>,
+{
+    fn on_event(&self, event: &tracing::Event<'_>, ctx: tracing_subscriber::layer::Context<'_, S>) {
+        let meta = event.metadata();
+        let level = LogLevel::from(*meta.level());
+        if !self.filter.enabled(meta.target(), level, self.min_level) {
+            return;
+        }
+
+        let mut fields = SpanTextFields::<Self>::collect(event, &ctx);
+        let mut own = TextFields::default();
+        event.record(&mut own);
+        own.scrub(&self.scrubber);
+        for (name, value) in own.fields {
+            fields.set(name, value);
+        }
+
+        let mut datagram = Vec::with_capacity(512);
+        put(&mut datagram, "PRIORITY", &severity(level).to_string());
+        put(&mut datagram, "SYSLOG_IDENTIFIER", &self.identifier);
+        put(&mut datagram, "TARGET", meta.target());
+        if let Some(file) = meta.file() {
+            put(&mut datagram, "CODE_FILE", file);
+        }
+        if let Some(line) = meta.line() {
+            put(&mut datagram, "CODE_LINE", &line.to_string());
+        }
+        for (name, value) in &fields.fields {
+            put(&mut datagram, &field_name(name), value);
+        }
+
+        if let Err(e) = self.socket.send(&datagram)
+            && !self.reported.swap(true, Ordering::Relaxed)
+        {
+            eprintln!(
+                "Cannot send logs to journald, later failures are not reported, details: {e}"
+            );
+        }
+    }
+
+    fn on_new_span(
+        &self,
+        attrs: &span::Attributes<'_>,
+        id: &span::Id,
+        ctx: tracing_subscriber::layer::Context<'_, S>,
+    ) {
+        SpanTextFields::<Self>::on_new_span(attrs, id, ctx, &self.scrubber);
+    }
+
+    fn on_record(
+        &self,
+        id: &span::Id,
+        values: &span::Record<'_>,
+        ctx: tracing_subscriber::layer::Context<'_, S>,
+    ) {
+        SpanTextFields::<Self>::on_record(id, values, ctx, &self.scrubber);
+    }
+}
+
+impl JournaldLogger {
+    /// 连接到 [`JOURNALD_SOCKET`]，不是由 systemd 管理的系统上会失败
+    pub fn connect(min_level: LogLevel) -> io::Result<Self> {
+        let socket = UnixDatagram::unbound()?;
+        socket.connect(JOURNALD_SOCKET)?;
+
+        Ok(Self {
+            socket,
+            identifier: "crab-vault".into(),
+            min_level,
+            filter: TargetFilter::default(),
+            scrubber: Scrubber::default(),
+            reported: AtomicBool::new(false),
+        })
+    }
+
+    /// `SYSLOG_IDENTIFIER`，也就是 `journalctl -t` 使用的名字
+    pub fn with_identifier(mut self, identifier: impl Into<String>) -> Self {
+        self.identifier = identifier.into();
+        self
+    }
+
+    /// 运行时修改输出的最低级别，参见 `tracing_subscriber::reload`
+    pub fn set_level(&mut self, min_level: LogLevel) {
+        self.min_level = min_level;
+    }
+
+    /// 运行时替换按照模块设置的级别，参见 [`TargetFilter`]
+    pub fn set_filter(&mut self, filter: TargetFilter) {
+        self.filter = filter;
+    }
+
+    /// 匹配 `filter` 的模块使用其中的级别，其他模块仍然使用 `min_level`
+    pub fn with_filter(mut self, filter: TargetFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// 在发送之前，使用 `scrubber` 对字段进行脱敏
+    pub fn with_scrubber(mut self, scrubber: Scrubber) -> Self {
+        self.scrubber = scrubber;
+        self
+    }
+}
+
+/// ## 追加一个字段
+///
+/// 不含换行的值写作 `NAME=value\n`，否则写作 `NAME\n`、小端序的 64 位长度、值本身和 `\n`
+fn put(datagram: &mut Vec<u8>, name: &str, value: &str) {
+    datagram.extend_from_slice(name.as_bytes());
+    if value.contains('\n') {
+        datagram.push(b'\n');
+        datagram.extend_from_slice(&(value.len() as u64).to_le_bytes());
+    } else {
+        datagram.push(b'=');
+    }
+    datagram.extend_from_slice(value.as_bytes());
+    datagram.push(b'\n');
+}
+
+/// ## journald 接受的字段名
+///
+/// # 示例
+/// ```
+/// # use crab_vault_logger::journald::field_name;
+/// assert_eq!(field_name("message"), "MESSAGE");
+/// assert_eq!(field_name("req_id"), "REQ_ID");
+/// assert_eq!(field_name("_private"), "F_PRIVATE");
+/// assert_eq!(field_name("http.status"), "HTTP_STATUS");
+/// ```
+pub fn field_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| match c.is_ascii_alphanumeric() {
+            true => c.to_ascii_uppercase(),
+            false => '_',
+        })
+        .collect();
+
+    // 以 `_` 开头的字段是 journald 自己的，不能由客户端写入
+    match name.chars().next() {
+        Some('_' | '0'..='9') | None => format!("F{name}"),
+        _ => name,
+    }
+}
@@ -1,12 +1,15 @@
 use std::{collections::BTreeMap, fs::File, io::Write, path::Path, sync::Arc};
 
-use chrono::Local;
+use chrono::{DateTime, FixedOffset, Local};
 use serde_json::json;
 use std::fs;
 use tracing::span;
 use tracing_subscriber::Layer;
 
-use crate::LogLevel;
+use crate::{LogLevel, filter::TargetFilter, scrub::Scrubber, writer::NonBlocking};
+
+/// 日志文件的文件名格式，即这个文件开始写入的时间
+pub const DUMP_FILE_NAME_FORMAT: &str = "%Y.%m.%d@%H-%M";
 
 pub struct JsonLogger {
     with_target: bool,
@@ -14,6 +17,20 @@ pub struct JsonLogger {
     with_thread: bool,
     file: Arc<File>,
     min_level: LogLevel,
+
+    /// 匹配的模块使用其中的级别，而不是 `min_level`
+    filter: TargetFilter,
+    scrubber: Scrubber,
+
+    /// 为 [`None`] 时在产生日志的线程中直接写入文件
+    output: Option<NonBlocking>,
+}
+
+/// 在进程退出之前把 [`JsonLogger`] 写出的日志落盘，参见 [`JsonLogger::flusher`]
+#[derive(Clone)]
+pub struct JsonLogFlusher {
+    file: Arc<File>,
+    output: Option<NonBlocking>,
 }
 
 #[derive(Default)]
@@ -21,6 +38,22 @@ struct JsonSpanFieldStorage {
     fields: BTreeMap<&'static str, serde_json::Value>,
 }
 
+/// [`JsonLogger`] 写出的一条日志记录
+pub struct JsonLogRecord<'a> {
+    /// 这条记录在日志文件中的原文，不包含分隔用的 `,`
+    pub raw: &'a str,
+
+    /// 记录的时间，缺失或者无法解析时为 [`None`]
+    pub time: Option<DateTime<FixedOffset>>,
+}
+
+/// 逐条读取 [`JsonLogger`] 写出的日志文件
+///
+/// 遇到无法解析的记录（例如进程崩溃时写了一半的记录）时返回错误，之后不再产生任何记录
+pub struct JsonLogRecords<'a> {
+    rest: &'a str,
+}
+
 struct JsonVisitor<'a> {
     fields: &'a mut BTreeMap<&'static str, serde_json::Value>,
 }
@@ -30,12 +63,15 @@ where
     S: tracing::Subscriber + for<'lookup> tracing_subscriber::registry::LookupSpan<'lookup>,
 {
     fn on_event(&self, event: &tracing::Event<'_>, ctx: tracing_subscriber::layer::Context<'_, S>) {
-        if LogLevel::from(*event.metadata().level()) < self.min_level {
+        let meta = event.metadata();
+        if !self
+            .filter
+            .enabled(meta.target(), LogLevel::from(*meta.level()), self.min_level)
+        {
             return;
         }
 
         let mut fields = BTreeMap::new();
-        let meta = event.metadata();
         fields.insert("level", json!(meta.level().as_str()));
         fields.insert("time", json!(Local::now().to_rfc2822()));
         fields.insert("target", json!(meta.target()));
@@ -74,14 +110,16 @@ where
         }
         let mut visitor = JsonVisitor::new(&mut fields);
         event.record(&mut visitor);
+        self.scrub(&mut fields);
 
         fields.insert("spans", json!(span_info));
 
-        match self
-            .file
-            .clone()
-            .write_all(format!("{},\n", serde_json::to_string_pretty(&fields).unwrap()).as_bytes())
-        {
+        let record = format!("{},\n", serde_json::to_string_pretty(&fields).unwrap());
+        if let Some(output) = &self.output {
+            return output.send(record.into_bytes());
+        }
+
+        match self.file.clone().write_all(record.as_bytes()) {
             Ok(_) => (),
             Err(e) => println!("Cannot write to dump file, details: {e}"),
         }
@@ -95,10 +133,28 @@ where
     ) {
         let mut storage = JsonSpanFieldStorage::new();
         attrs.record(&mut storage);
+        self.scrub(&mut storage.fields);
         if let Some(span) = ctx.span(id) {
             span.extensions_mut().insert(storage);
         }
     }
+
+    fn on_record(
+        &self,
+        id: &span::Id,
+        values: &span::Record<'_>,
+        ctx: tracing_subscriber::layer::Context<'_, S>,
+    ) {
+        let mut recorded = JsonSpanFieldStorage::new();
+        values.record(&mut recorded);
+        self.scrub(&mut recorded.fields);
+
+        if let Some(span) = ctx.span(id)
+            && let Some(storage) = span.extensions_mut().get_mut::<JsonSpanFieldStorage>()
+        {
+            storage.fields.append(&mut recorded.fields);
+        }
+    }
 }
 
 impl JsonLogger {
@@ -107,7 +163,7 @@ impl JsonLogger {
         fs::create_dir_all(&log_path)?;
 
         let file =
-            File::create(log_path.join(format!("{}.json", Local::now().format("%Y.%m.%d@%H-%M"))))?;
+            File::create(log_path.join(format!("{}.json", Local::now().format(DUMP_FILE_NAME_FORMAT))))?;
         let file = Arc::new(file);
         Ok(Self {
             with_file: false,
@@ -115,9 +171,53 @@ impl JsonLogger {
             with_thread: false,
             file,
             min_level,
+            filter: TargetFilter::default(),
+            scrubber: Scrubber::default(),
+            output: None,
         })
     }
 
+    /// 日志文件的落盘句柄，日志层交给 subscriber 之后仍然可以使用
+    pub fn flusher(&self) -> JsonLogFlusher {
+        JsonLogFlusher {
+            file: self.file.clone(),
+            output: self.output.clone(),
+        }
+    }
+
+    /// 在写入日志文件之前，使用 `scrubber` 对字段进行脱敏
+    pub fn with_scrubber(mut self, scrubber: Scrubber) -> Self {
+        self.scrubber = scrubber;
+        self
+    }
+
+    /// 运行时替换按照模块设置的级别，参见 [`TargetFilter`]
+    pub fn set_filter(&mut self, filter: TargetFilter) {
+        self.filter = filter;
+    }
+
+    /// 匹配 `filter` 的模块使用其中的级别，其他模块仍然使用 `min_level`
+    pub fn with_filter(mut self, filter: TargetFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// 日志文件由后台线程写入，缓冲区中最多有 `capacity` 条记录，参见 [`NonBlocking`]
+    pub fn with_async_buffer(mut self, capacity: usize) -> Self {
+        self.output = Some(NonBlocking::new(self.file.clone(), capacity));
+        self
+    }
+
+    fn scrub(&self, fields: &mut BTreeMap<&'static str, serde_json::Value>) {
+        if self.scrubber.is_noop() {
+            return;
+        }
+
+        for (name, value) in fields.iter_mut() {
+            *value = self.scrubber.scrub_json(name, value.take());
+        }
+    }
+
     pub fn with_target(mut self, enabled: bool) -> Self {
         self.with_target = enabled;
         self
@@ -134,6 +234,53 @@ impl JsonLogger {
     }
 }
 
+impl JsonLogFlusher {
+    /// 写出所有缓冲的日志并等待它们落盘
+    pub fn flush(&self) -> std::io::Result<()> {
+        if let Some(output) = &self.output {
+            output.flush()?;
+        }
+        (&*self.file).flush()?;
+        self.file.sync_data()
+    }
+}
+
+impl<'a> JsonLogRecords<'a> {
+    pub fn new(content: &'a str) -> Self {
+        Self { rest: content }
+    }
+}
+
+impl<'a> Iterator for JsonLogRecords<'a> {
+    type Item = Result<JsonLogRecord<'a>, serde_json::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // 每条记录之后都跟着 `,\n`
+        let rest = self
+            .rest
+            .trim_start_matches(|c: char| c.is_whitespace() || c == ',');
+        self.rest = "";
+
+        let mut stream = serde_json::Deserializer::from_str(rest)
+            .into_iter::<serde_json::Map<String, serde_json::Value>>();
+
+        match stream.next()? {
+            Ok(fields) => {
+                let (raw, rest) = rest.split_at(stream.byte_offset());
+                self.rest = rest;
+
+                let time = fields
+                    .get("time")
+                    .and_then(serde_json::Value::as_str)
+                    .and_then(|v| DateTime::parse_from_rfc2822(v).ok());
+
+                Some(Ok(JsonLogRecord { raw, time }))
+            }
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
 impl JsonSpanFieldStorage {
     fn new() -> Self {
         Self {
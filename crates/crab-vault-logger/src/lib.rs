@@ -1,8 +1,19 @@
 use clap::ValueEnum;
 use serde::{Deserialize, Serialize};
 
+mod fields;
+pub mod filter;
 pub mod json;
+#[cfg(unix)]
+pub mod journald;
+#[cfg(feature = "otlp")]
+pub mod otlp;
 pub mod pretty;
+pub mod scrub;
+pub mod syslog;
+pub mod theme;
+pub mod trace_context;
+pub mod writer;
 
 #[derive(Deserialize, Serialize, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug, Default, ValueEnum)]
 pub enum LogLevel {
@@ -31,3 +42,16 @@ impl From<tracing::Level> for LogLevel {
         }
     }
 }
+
+impl From<LogLevel> for tracing::Level {
+    #[inline(always)]
+    fn from(value: LogLevel) -> Self {
+        match value {
+            LogLevel::Trace => Self::TRACE,
+            LogLevel::Debug => Self::DEBUG,
+            LogLevel::Info => Self::INFO,
+            LogLevel::Warn => Self::WARN,
+            LogLevel::Error => Self::ERROR,
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use opentelemetry::{
+    Context,
+    trace::{
+        SpanContext, SpanId, TraceContextExt, TraceFlags, TraceId, TraceState, TracerProvider,
+    },
+};
+use opentelemetry_otlp::{
+    ExporterBuildError, SpanExporter, WithExportConfig, WithTonicConfig,
+    tonic_types::transport::ClientTlsConfig,
+};
+use opentelemetry_sdk::{Resource, trace::SdkTracerProvider};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::{Layer, filter::LevelFilter, registry::LookupSpan};
+
+use crate::{LogLevel, trace_context::TraceParent};
+
+/// 上报的 span 中的 `service.name`
+const SERVICE_NAME: &str = "crab-vault";
+
+/// ## 通过 OTLP/gRPC 导出 span
+///
+/// span 在后台分批发送给 `endpoint`，例如 `http://127.0.0.1:4317`，`https` 时使用内置的根证书。
+/// 进程退出之前需要调用 [`shutdown`](Self::shutdown)，否则最后一批 span 会丢失
+#[derive(Clone)]
+pub struct OtlpExporter {
+    provider: SdkTracerProvider,
+    min_level: LogLevel,
+}
+
+impl OtlpExporter {
+    /// 必须在 tokio 运行时中调用，连接在第一次发送时才建立，所以 `endpoint` 无法连接并不会在这里报错
+    pub fn new(endpoint: &str, min_level: LogLevel) -> Result<Self, ExporterBuildError> {
+        let mut exporter = SpanExporter::builder().with_tonic().with_endpoint(endpoint);
+        if endpoint.starts_with("https://") {
+            exporter = exporter.with_tls_config(ClientTlsConfig::new().with_webpki_roots());
+        }
+
+        let provider = SdkTracerProvider::builder()
+            .with_batch_exporter(exporter.build()?)
+            .with_resource(Resource::builder().with_service_name(SERVICE_NAME).build())
+            .build();
+
+        Ok(Self {
+            provider,
+            min_level,
+        })
+    }
+
+    /// 把 `min_level` 及以上的 span 和其中的事件交给导出器的日志层
+    pub fn layer<S>(&self) -> impl Layer<S> + use<S>
+    where
+        S: tracing::Subscriber + for<'lookup> LookupSpan<'lookup>,
+    {
+        tracing_opentelemetry::layer()
+            .with_tracer(self.provider.tracer(SERVICE_NAME))
+            .with_filter(LevelFilter::from_level(self.min_level.into()))
+    }
+
+    /// 发送所有还没有发送的 span，之后不再导出
+    pub fn shutdown(&self) -> Result<(), String> {
+        self.provider.shutdown().map_err(|e| e.to_string())
+    }
+}
+
+/// 把 `span` 接到调用方的 span 之下，它们在导出之后属于同一条调用链
+///
+/// 需要在 `span` 第一次进入之前调用，之后调用不会产生任何效果
+pub fn set_parent(span: &tracing::Span, parent: &TraceParent) {
+    let context = SpanContext::new(
+        TraceId::from_bytes(parent.trace_id),
+        SpanId::from_bytes(parent.parent_id),
+        TraceFlags::new(parent.flags),
+        true,
+        TraceState::NONE,
+    );
+
+    let _ = span.set_parent(Context::new().with_remote_span_context(context));
+}
@@ -1,13 +1,27 @@
+use std::{fmt::Write, io::IsTerminal};
+
 use chrono::Local;
 
 use crab_vault_utils::ansi::{
-    AnsiColor::{self, *},
-    AnsiString, AnsiStyle, FontStyle,
+    AnsiString, AnsiStyle, AnsiText, ColorSupport, FontStyle, truncate_to_width,
 };
 use tracing::span;
 use tracing_subscriber::Layer;
 
-use crate::LogLevel;
+use crate::{LogLevel, filter::TargetFilter, scrub::Scrubber, theme::Theme, writer::NonBlocking};
+
+/// 向一条日志的缓冲区中追加一行，写入 `String` 不会失败
+macro_rules! outln {
+    ($out:expr, $($arg:tt)*) => {{
+        let _ = writeln!($out, $($arg)*);
+    }};
+}
+
+/// 名称一列至少占据的宽度，较短的名称右对齐
+const KEY_WIDTH: usize = 8;
+
+/// 名称一列最多占据的宽度，更长的名称被截断，避免把值挤到很远的地方
+const MAX_KEY_WIDTH: usize = 24;
 
 pub struct PrettyLogger {
     with_target: bool,
@@ -15,6 +29,17 @@ pub struct PrettyLogger {
     with_file: bool,
     with_thread: bool,
     min_level: LogLevel,
+
+    /// 匹配的模块使用其中的级别，而不是 `min_level`
+    filter: TargetFilter,
+    scrubber: Scrubber,
+    theme: Theme,
+
+    /// 为 [`None`] 时直接写入标准输出
+    output: Option<NonBlocking>,
+
+    /// 开启了 `with_ansi` 时，颜色按照终端的能力降级
+    color_support: ColorSupport,
 }
 
 struct PrettySpanFieldsStorage {
@@ -24,6 +49,7 @@ struct PrettySpanFieldsStorage {
 struct PrettyVisitor<'a> {
     config: &'a PrettyLogger,
     event: &'a tracing::Event<'a>,
+    out: &'a mut String,
 }
 
 impl<S> Layer<S> for PrettyLogger
@@ -32,24 +58,35 @@ where
     S: for<'lookup> tracing_subscriber::registry::LookupSpan<'lookup>,
 {
     fn on_event(&self, event: &tracing::Event<'_>, ctx: tracing_subscriber::layer::Context<'_, S>) {
-        if LogLevel::from(*event.metadata().level()) < self.min_level {
+        let meta = event.metadata();
+        if !self
+            .filter
+            .enabled(meta.target(), LogLevel::from(*meta.level()), self.min_level)
+        {
             return;
         }
 
         let style = self.severity_style(event);
         let prefix = style.decorate("|   ");
         let splitter = style.decorate("`-----------");
-        let style = self.get_style(Some(Magenta), None, Some(FontStyle::new().bold(true)));
-        self.print_level_label(event)
-            .print_target(event, prefix, style)
-            .print_thread(prefix, style)
-            .print_file(event, prefix, style)
-            .print_time(prefix, style)
-            .print_spans(prefix, splitter, event, ctx);
-
-        println!("{splitter}");
-        event.record(&mut PrettyVisitor::new(self, event));
-        println!("{splitter}\n");
+        let style = self.themed(self.theme.meta_style());
+        let mut out = String::with_capacity(1024);
+        self.print_level_label(&mut out, event)
+            .print_target(&mut out, event, prefix, style)
+            .print_thread(&mut out, prefix, style)
+            .print_file(&mut out, event, prefix, style)
+            .print_time(&mut out, prefix, style)
+            .print_spans(&mut out, prefix, splitter, event, ctx);
+
+        outln!(out, "{splitter}");
+        event.record(&mut PrettyVisitor::new(self, event, &mut out));
+        outln!(out, "{splitter}\n");
+
+        // 整条日志一次写出，多个线程的日志不会交错
+        match &self.output {
+            Some(writer) => writer.send(out.into_bytes()),
+            None => print!("{out}"),
+        }
     }
 
     fn on_new_span(
@@ -60,18 +97,51 @@ where
     ) {
         let mut storage = PrettySpanFieldsStorage::new();
         attrs.record(&mut storage);
+        if !self.scrubber.is_noop() {
+            for (name, value) in storage.fields.iter_mut() {
+                *value = self.scrubber.scrub_json(name, value.take());
+            }
+        }
         if let Some(span) = ctx.span(id) {
             span.extensions_mut().insert(storage);
         }
     }
+
+    fn on_record(
+        &self,
+        id: &span::Id,
+        values: &span::Record<'_>,
+        ctx: tracing_subscriber::layer::Context<'_, S>,
+    ) {
+        let mut recorded = PrettySpanFieldsStorage::new();
+        values.record(&mut recorded);
+
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut extensions = span.extensions_mut();
+        let Some(storage) = extensions.get_mut::<PrettySpanFieldsStorage>() else {
+            return;
+        };
+
+        // 同名的字段覆盖掉，保持字段在 span 创建时的顺序
+        for (name, value) in recorded.fields {
+            let value = self.scrubber.scrub_json(name, value);
+            match storage.fields.iter_mut().find(|(v, _)| *v == name) {
+                Some((_, old)) => *old = value,
+                None => storage.fields.push((name, value)),
+            }
+        }
+    }
 }
 
 impl PrettyLogger {
     #[inline(always)]
-    fn print_level_label(&self, event: &tracing::Event) -> &Self {
-        let style = self.severity_label_style(event);
+    fn print_level_label(&self, out: &mut String, event: &tracing::Event) -> &Self {
+        let style = self.themed(self.theme.label_style_of(Self::level_of(event)));
         let prefix = self.severity_style(event).decorate("*--");
-        println!(
+        outln!(
+            out,
             "{prefix}{}{}{}",
             style.decorate("["),
             style.decorate(event.metadata().level().as_str()),
@@ -81,21 +151,29 @@ impl PrettyLogger {
     }
 
     #[inline(always)]
-    fn print_time(&self, prefix: AnsiString, style: AnsiStyle) -> &Self {
-        println!(
-            "{prefix}{:>8}: {}",
-            style.decorate("time"),
+    fn print_time(&self, out: &mut String, prefix: AnsiString, style: AnsiStyle) -> &Self {
+        outln!(
+            out,
+            "{prefix}{}: {}",
+            self.key_column(style, "time"),
             Local::now().to_rfc2822()
         );
         self
     }
 
     #[inline(always)]
-    fn print_target(&self, event: &tracing::Event, prefix: AnsiString, style: AnsiStyle) -> &Self {
+    fn print_target(
+        &self,
+        out: &mut String,
+        event: &tracing::Event,
+        prefix: AnsiString,
+        style: AnsiStyle,
+    ) -> &Self {
         if self.with_target {
-            println!(
-                "{prefix}{:>8}: {}",
-                style.decorate("target"),
+            outln!(
+                out,
+                "{prefix}{}: {}",
+                self.key_column(style, "target"),
                 event.metadata().target()
             );
         }
@@ -103,11 +181,18 @@ impl PrettyLogger {
     }
 
     #[inline(always)]
-    fn print_file(&self, event: &tracing::Event, prefix: AnsiString, style: AnsiStyle) -> &Self {
+    fn print_file(
+        &self,
+        out: &mut String,
+        event: &tracing::Event,
+        prefix: AnsiString,
+        style: AnsiStyle,
+    ) -> &Self {
         if self.with_file {
-            println!(
-                "{prefix}{:>8}: {}:{}",
-                style.decorate("file"),
+            outln!(
+                out,
+                "{prefix}{}: {}:{}",
+                self.key_column(style, "file"),
                 event.metadata().file().unwrap_or("N/A"),
                 event.metadata().line().unwrap_or(u32::MAX)
             );
@@ -116,11 +201,12 @@ impl PrettyLogger {
     }
 
     #[inline(always)]
-    fn print_thread(&self, prefix: AnsiString, style: AnsiStyle) -> &Self {
+    fn print_thread(&self, out: &mut String, prefix: AnsiString, style: AnsiStyle) -> &Self {
         if self.with_thread {
-            println!(
-                "{prefix}{:>8}: {}@{:?}",
-                style.decorate("thread"),
+            outln!(
+                out,
+                "{prefix}{}: {}@{:?}",
+                self.key_column(style, "thread"),
                 std::thread::current().name().unwrap_or("N/A"),
                 std::thread::current().id(),
             );
@@ -131,6 +217,7 @@ impl PrettyLogger {
     #[inline(always)]
     fn print_spans<S>(
         &self,
+        out: &mut String,
         prefix: AnsiString,
         splitter: AnsiString,
         event: &tracing::Event<'_>,
@@ -140,48 +227,50 @@ impl PrettyLogger {
         S: tracing::Subscriber,
         S: for<'lookup> tracing_subscriber::registry::LookupSpan<'lookup>,
     {
-        let inner_splitter = self
-            .get_style(Some(Cyan), None, None)
-            .decorate(splitter.get_content());
-        let inner_prefix = self
-            .get_style(Some(Cyan), None, None)
-            .decorate(prefix.get_content());
+        let span_style = self.themed(self.theme.span_style());
+        let name_style = self.themed(
+            self.theme
+                .span_style()
+                .with_font(FontStyle::new().reverse(true)),
+        );
+        let inner_splitter = span_style.decorate(splitter.get_content());
+        let inner_prefix = span_style.decorate(prefix.get_content());
         if let Some(scope) = ctx.event_scope(event) {
-            println!("{splitter}");
+            outln!(out, "{splitter}");
             for span in scope.from_root() {
                 // span 的名字
-                println!(
+                outln!(
+                    out,
                     "{prefix}{}",
-                    self.get_style(Some(White), Some(Cyan), Some(FontStyle::new().bold(true)))
-                        .decorate(if !span.name().is_empty() {
-                            span.name()
-                        } else {
-                            "[N/A]"
-                        })
+                    name_style.decorate(if !span.name().is_empty() {
+                        span.name()
+                    } else {
+                        "[N/A]"
+                    })
                 );
-                println!(
-                    "{prefix}{inner_prefix}{:>8}: {}",
-                    self.get_style(Some(Cyan), None, Some(FontStyle::new().bold(true)))
-                        .decorate("target"),
+                outln!(
+                    out,
+                    "{prefix}{inner_prefix}{}: {}",
+                    self.key_column(span_style, "target"),
                     span.metadata().target()
                 );
-                println!(
-                    "{prefix}{inner_prefix}{:>8}: {}",
-                    self.get_style(Some(Cyan), None, Some(FontStyle::new().bold(true)))
-                        .decorate("file"),
+                outln!(
+                    out,
+                    "{prefix}{inner_prefix}{}: {}",
+                    self.key_column(span_style, "file"),
                     span.metadata().file().unwrap_or("N/A")
                 );
-                println!("{prefix}{inner_splitter}");
+                outln!(out, "{prefix}{inner_splitter}");
                 if let Some(storage) = span.extensions().get::<PrettySpanFieldsStorage>() {
                     for (k, v) in &storage.fields {
-                        println!(
-                            "{prefix}{inner_prefix}{:>8}: {v}",
-                            self.get_style(Some(Cyan), None, Some(FontStyle::new().bold(true)))
-                                .decorate(k)
+                        outln!(
+                            out,
+                            "{prefix}{inner_prefix}{}: {v}",
+                            self.key_column(span_style, k)
                         )
                     }
                 }
-                println!("{prefix}{inner_splitter}");
+                outln!(out, "{prefix}{inner_splitter}");
             }
         }
 
@@ -190,65 +279,28 @@ impl PrettyLogger {
 
     #[inline(always)]
     fn severity_style(&self, event: &tracing::Event<'_>) -> AnsiStyle {
-        match *event.metadata().level() {
-            tracing::Level::TRACE => {
-                self.get_style(Some(Magenta), None, Some(FontStyle::new().bold(true)))
-            }
-            tracing::Level::DEBUG => {
-                self.get_style(Some(Blue), None, Some(FontStyle::new().bold(true)))
-            }
-            tracing::Level::INFO => self.get_style(Some(Green), None, None),
-            tracing::Level::WARN => self.get_style(Some(Yellow), None, None),
-            tracing::Level::ERROR => self.get_style(Some(Red), None, None),
-        }
+        self.themed(self.theme.style_of(Self::level_of(event)))
     }
 
     #[inline(always)]
-    fn severity_label_style(&self, event: &tracing::Event<'_>) -> AnsiStyle {
-        match *event.metadata().level() {
-            tracing::Level::TRACE => self.get_style(
-                Some(BrightWhite),
-                Some(BrightMagenta),
-                Some(FontStyle::new().bold(true)),
-            ),
-            tracing::Level::DEBUG => self.get_style(
-                Some(BrightWhite),
-                Some(BrightBlue),
-                Some(FontStyle::new().bold(true)),
-            ),
-            tracing::Level::INFO => self.get_style(
-                Some(BrightBlack),
-                Some(BrightGreen),
-                Some(FontStyle::new().bold(true)),
-            ),
-            tracing::Level::WARN => self.get_style(
-                Some(BrightBlack),
-                Some(BrightYellow),
-                Some(FontStyle::new().bold(true)),
-            ),
-            tracing::Level::ERROR => self.get_style(
-                Some(BrightBlack),
-                Some(BrightRed),
-                Some(FontStyle::new().bold(true)),
-            ),
-        }
+    fn level_of(event: &tracing::Event<'_>) -> LogLevel {
+        LogLevel::from(*event.metadata().level())
     }
 
+    /// 名称一列，按照终端中的宽度对齐，而不是按照字符数
     #[inline(always)]
-    fn get_style(
-        &self,
-        fore: Option<AnsiColor>,
-        back: Option<AnsiColor>,
-        font: Option<FontStyle>,
-    ) -> AnsiStyle {
+    fn key_column(&self, style: AnsiStyle, key: &str) -> AnsiText {
+        AnsiText::styled(style, truncate_to_width(key, MAX_KEY_WIDTH)).pad_start(KEY_WIDTH)
+    }
+
+    /// 关闭了 `with_ansi` 时不使用任何样式，否则按照终端的能力降级
+    #[inline(always)]
+    fn themed(&self, style: AnsiStyle) -> AnsiStyle {
         if !self.with_ansi {
             return AnsiStyle::new_vanilla();
         }
 
-        AnsiStyle::new()
-            .with_fore_option(fore)
-            .with_back_option(back)
-            .with_font_option(font)
+        style.downgrade(self.color_support)
     }
 }
 
@@ -260,9 +312,58 @@ impl PrettyLogger {
             with_file: true,
             with_thread: true,
             min_level,
+            filter: TargetFilter::default(),
+            scrubber: Scrubber::default(),
+            theme: Theme::default(),
+            output: None,
+            color_support: ColorSupport::detect(std::io::stdout().is_terminal()),
         }
     }
 
+    /// 运行时修改输出的最低级别，参见 `tracing_subscriber::reload`
+    pub fn set_level(&mut self, min_level: LogLevel) {
+        self.min_level = min_level;
+    }
+
+    /// 运行时替换按照模块设置的级别，参见 [`TargetFilter`]
+    pub fn set_filter(&mut self, filter: TargetFilter) {
+        self.filter = filter;
+    }
+
+    #[inline]
+    pub fn level(&self) -> LogLevel {
+        self.min_level
+    }
+
+    #[inline]
+    pub fn filter(&self) -> &TargetFilter {
+        &self.filter
+    }
+
+    /// 匹配 `filter` 的模块使用其中的级别，其他模块仍然使用 `min_level`
+    pub fn with_filter(mut self, filter: TargetFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// 在输出之前，使用 `scrubber` 对字段进行脱敏
+    pub fn with_scrubber(mut self, scrubber: Scrubber) -> Self {
+        self.scrubber = scrubber;
+        self
+    }
+
+    /// 使用 `theme` 中的配色，没有配置的部分使用内置的配色
+    pub fn with_theme(mut self, theme: Theme) -> Self {
+        self.theme = theme;
+        self
+    }
+
+    /// 日志交给 `writer` 的后台线程写出，而不是在产生日志的线程中写入标准输出
+    pub fn with_non_blocking(mut self, writer: NonBlocking) -> Self {
+        self.output = Some(writer);
+        self
+    }
+
     pub fn with_target(mut self, enabled: bool) -> Self {
         self.with_target = enabled;
         self
@@ -273,6 +374,12 @@ impl PrettyLogger {
         self
     }
 
+    /// 覆盖从环境变量中检测到的终端颜色能力，参见 [`ColorSupport::detect`]
+    pub fn with_color_support(mut self, support: ColorSupport) -> Self {
+        self.color_support = support;
+        self
+    }
+
     pub fn with_file(mut self, enabled: bool) -> Self {
         self.with_file = enabled;
         self
@@ -329,10 +436,11 @@ impl tracing::field::Visit for PrettySpanFieldsStorage {
 }
 
 impl<'a> PrettyVisitor<'a> {
-    fn new(logger: &'a PrettyLogger, event: &'a tracing::Event<'_>) -> Self {
+    fn new(logger: &'a PrettyLogger, event: &'a tracing::Event<'_>, out: &'a mut String) -> Self {
         Self {
             config: logger,
             event,
+            out,
         }
     }
 }
@@ -340,56 +448,67 @@ impl<'a> PrettyVisitor<'a> {
 impl<'a> tracing::field::Visit for PrettyVisitor<'a> {
     fn record_f64(&mut self, field: &tracing::field::Field, value: f64) {
         let prefix = self.config.severity_style(self.event).decorate("|   ");
-        println!(
-            "{prefix}{:>8}: {}",
-            self.config
-                .get_style(Some(Blue), None, Some(FontStyle::new().bold(true)))
-                .decorate(field.name()),
+        outln!(
+            self.out,
+            "{prefix}{}: {}",
+            self.config.key_column(
+                self.config.themed(self.config.theme.field_style()),
+                field.name()
+            ),
             value
         )
     }
 
     fn record_i64(&mut self, field: &tracing::field::Field, value: i64) {
         let prefix = self.config.severity_style(self.event).decorate("|   ");
-        println!(
-            "{prefix}{:>8}: {}",
-            self.config
-                .get_style(Some(Blue), None, Some(FontStyle::new().bold(true)))
-                .decorate(field.name()),
+        outln!(
+            self.out,
+            "{prefix}{}: {}",
+            self.config.key_column(
+                self.config.themed(self.config.theme.field_style()),
+                field.name()
+            ),
             value
         )
     }
 
     fn record_u64(&mut self, field: &tracing::field::Field, value: u64) {
         let prefix = self.config.severity_style(self.event).decorate("|   ");
-        println!(
-            "{prefix}{:>8}: {}",
-            self.config
-                .get_style(Some(Blue), None, Some(FontStyle::new().bold(true)))
-                .decorate(field.name()),
+        outln!(
+            self.out,
+            "{prefix}{}: {}",
+            self.config.key_column(
+                self.config.themed(self.config.theme.field_style()),
+                field.name()
+            ),
             value
         )
     }
 
     fn record_bool(&mut self, field: &tracing::field::Field, value: bool) {
         let prefix = self.config.severity_style(self.event).decorate("|   ");
-        println!(
-            "{prefix}{:>8}: {}",
-            self.config
-                .get_style(Some(Blue), None, Some(FontStyle::new().bold(true)))
-                .decorate(field.name()),
+        outln!(
+            self.out,
+            "{prefix}{}: {}",
+            self.config.key_column(
+                self.config.themed(self.config.theme.field_style()),
+                field.name()
+            ),
             value
         )
     }
 
     fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
         let prefix = self.config.severity_style(self.event).decorate("|   ");
-        println!(
-            "{prefix}{:>8}: {}",
-            self.config
-                .get_style(Some(Blue), None, Some(FontStyle::new().bold(true)))
-                .decorate(field.name()),
-            value
+        let scrubbed = self.config.scrubber.scrub(field.name(), value);
+        outln!(
+            self.out,
+            "{prefix}{}: {}",
+            self.config.key_column(
+                self.config.themed(self.config.theme.field_style()),
+                field.name()
+            ),
+            scrubbed.as_deref().unwrap_or(value)
         )
     }
 
@@ -399,23 +518,29 @@ impl<'a> tracing::field::Visit for PrettyVisitor<'a> {
         value: &(dyn std::error::Error + 'static),
     ) {
         let prefix = self.config.severity_style(self.event).decorate("|   ");
-        println!(
-            "{prefix}{:>8}: {}",
-            self.config
-                .get_style(Some(Blue), None, Some(FontStyle::new().bold(true)))
-                .decorate(field.name()),
+        outln!(
+            self.out,
+            "{prefix}{}: {}",
+            self.config.key_column(
+                self.config.themed(self.config.theme.field_style()),
+                field.name()
+            ),
             value
         )
     }
 
     fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
         let prefix = self.config.severity_style(self.event).decorate("|   ");
-        println!(
-            "{prefix}{:>8}: {:?}",
-            self.config
-                .get_style(Some(Blue), None, Some(FontStyle::new().bold(true)))
-                .decorate(field.name()),
-            value
+        let value = format!("{value:?}");
+        let scrubbed = self.config.scrubber.scrub(field.name(), &value);
+        outln!(
+            self.out,
+            "{prefix}{}: {}",
+            self.config.key_column(
+                self.config.themed(self.config.theme.field_style()),
+                field.name()
+            ),
+            scrubbed.unwrap_or(value)
         );
     }
 }
@@ -0,0 +1,107 @@
+use glob::Pattern;
+use sha2::{Digest, Sha256};
+
+/// 替换被丢弃的字段值
+pub const DROPPED: &str = "[dropped]";
+
+/// 替换被遮盖的 object 名称
+pub const MASKED: &str = "***";
+
+/// 日志字段的脱敏规则，在日志层输出之前作用于每一个字段
+///
+/// 只有以下名字的字段会被处理：
+///
+/// - `uri`、`path`：路径匹配 `mask_object_keys` 时遮盖其中的 object 部分，只保留 bucket
+/// - `user_meta`：开启 `drop_user_meta` 时被丢弃
+/// - `client_ip`：开启 `hash_client_ips` 时被替换为加盐后的哈希
+///
+/// 默认的 [`Scrubber`] 不做任何处理
+#[derive(Clone, Default, Debug)]
+pub struct Scrubber {
+    mask_object_keys: Vec<Pattern>,
+    drop_user_meta: bool,
+    ip_salt: Option<String>,
+}
+
+impl Scrubber {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 遮盖匹配这些模式的 object，模式匹配的是 `/{bucket}/{object}` 形式的路径
+    #[inline]
+    pub fn mask_object_keys(mut self, patterns: Vec<Pattern>) -> Self {
+        self.mask_object_keys = patterns;
+        self
+    }
+
+    /// 丢弃所有的 `user_meta` 字段
+    #[inline]
+    pub fn drop_user_meta(mut self, enabled: bool) -> Self {
+        self.drop_user_meta = enabled;
+        self
+    }
+
+    /// 将 `client_ip` 替换为 `sha256(salt + ip)` 的前 16 个十六进制字符
+    ///
+    /// 同一个 ip 在同一个 salt 下总是得到同样的结果，所以仍然能够关联同一个客户端的请求
+    #[inline]
+    pub fn hash_client_ips(mut self, salt: Option<String>) -> Self {
+        self.ip_salt = salt;
+        self
+    }
+
+    /// 这个 [`Scrubber`] 是否什么都不做
+    #[inline]
+    pub fn is_noop(&self) -> bool {
+        self.mask_object_keys.is_empty() && !self.drop_user_meta && self.ip_salt.is_none()
+    }
+
+    /// 对一个字段进行脱敏，返回 [`None`] 表示这个字段无需改动
+    pub fn scrub(&self, field: &str, value: &str) -> Option<String> {
+        match field {
+            "uri" | "path" => self.mask_path(value),
+            "user_meta" if self.drop_user_meta => Some(DROPPED.to_string()),
+            "client_ip" => self.ip_salt.as_ref().map(|salt| {
+                let digest = Sha256::digest(format!("{salt}{value}"));
+                format!("{digest:x}")[..16].to_string()
+            }),
+            _ => None,
+        }
+    }
+
+    /// 对单独记录的 object 名称进行脱敏，`/{bucket}/{object}` 匹配 `mask_object_keys` 时返回 [`MASKED`]
+    pub fn scrub_object(&self, bucket: &str, object: &str) -> Option<String> {
+        self.mask_path(&format!("/{bucket}/{object}"))
+            .map(|_| MASKED.to_string())
+    }
+
+    /// 对一个 json 形式的字段值进行脱敏，只有字符串会被处理
+    pub fn scrub_json(&self, field: &str, value: serde_json::Value) -> serde_json::Value {
+        match &value {
+            serde_json::Value::String(v) => self
+                .scrub(field, v)
+                .map(serde_json::Value::String)
+                .unwrap_or(value),
+            _ if field == "user_meta" && self.drop_user_meta => {
+                serde_json::Value::String(DROPPED.to_string())
+            }
+            _ => value,
+        }
+    }
+
+    fn mask_path(&self, uri: &str) -> Option<String> {
+        let path = uri.split(['?', '#']).next().unwrap_or_default();
+        let mut segments = path.trim_start_matches('/').splitn(2, '/');
+
+        match (segments.next(), segments.next()) {
+            (Some(bucket), Some(object))
+                if !object.is_empty() && self.mask_object_keys.iter().any(|v| v.matches(path)) =>
+            {
+                Some(format!("/{bucket}/{MASKED}"))
+            }
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,269 @@
+//! # 输出到 syslog
+//!
+//! 每个事件按照 RFC 5424 格式化为一条消息，通过 UDP 或者 unix datagram socket 发送，
+//! 例如本机的 `/dev/log` 或者远程 rsyslog 的 `514` 端口。
+//!
+//! 消息正文是事件的 `message`，之后是事件所在 span 和事件本身的其他字段，形如 `key=value`，
+//! 所以 `req_id` 等请求 span 上的字段也会出现在每一条消息中。
+
+use std::{
+    io,
+    net::UdpSocket,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+#[cfg(unix)]
+use std::os::unix::net::UnixDatagram;
+
+use chrono::{Local, SecondsFormat};
+use serde::{Deserialize, Serialize};
+use tracing::span;
+use tracing_subscriber::Layer;
+
+use crate::{
+    LogLevel,
+    fields::{SpanTextFields, TextFields},
+    filter::TargetFilter,
+    scrub::Scrubber,
+};
+
+/// 一条消息最多的字节数，更长的正文被截断，避免超出 UDP 报文的限制
+pub const MAX_MESSAGE_LEN: usize = 8192;
+
+/// RFC 5424 中的 NILVALUE
+const NIL: &str = "-";
+
+/// syslog 的 facility，决定了消息被 syslog 守护进程归入哪一类
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Facility {
+    User,
+    #[default]
+    Daemon,
+    Auth,
+    Local0,
+    Local1,
+    Local2,
+    Local3,
+    Local4,
+    Local5,
+    Local6,
+    Local7,
+}
+
+pub struct SyslogLogger {
+    transport: Transport,
+    facility: Facility,
+    app_name: String,
+    hostname: String,
+    min_level: LogLevel,
+    filter: TargetFilter,
+    scrubber: Scrubber,
+
+    /// 发送失败只报告一次，syslog 不可用时不至于每个事件都输出一次错误
+    reported: AtomicBool,
+}
+
+enum Transport {
+    Udp(UdpSocket),
+    #[cfg(unix)]
+    Unix(UnixDatagram),
+}
+
+impl<S> Layer<S> for SyslogLogger
+where
+    S: tracing::Subscriber + for<'lookup> tracing_subscriber::registry::LookupSpan<'lookup>,
+{
+    fn on_event(&self, event: &tracing::Event<'_>, ctx: tracing_subscriber::layer::Context<'_, S>) {
+        let meta = event.metadata();
+        let level = LogLevel::from(*meta.level());
+        if !self.filter.enabled(meta.target(), level, self.min_level) {
+            return;
+        }
+
+        let mut fields = SpanTextFields::<Self>::collect(event, &ctx);
+        let mut own = TextFields::default();
+        event.record(&mut own);
+        own.scrub(&self.scrubber);
+        for (name, value) in own.fields {
+            fields.set(name, value);
+        }
+
+        let message = self.format(level, meta.target(), &fields);
+        if let Err(e) = self.transport.send(message.as_bytes())
+            && !self.reported.swap(true, Ordering::Relaxed)
+        {
+            eprintln!("Cannot send logs to syslog, later failures are not reported, details: {e}");
+        }
+    }
+
+    fn on_new_span(
+        &self,
+        attrs: &span::Attributes<'_>,
+        id: &span::Id,
+        ctx: tracing_subscriber::layer::Context<'_, S>,
+    ) {
+        SpanTextFields::<Self>::on_new_span(attrs, id, ctx, &self.scrubber);
+    }
+
+    fn on_record(
+        &self,
+        id: &span::Id,
+        values: &span::Record<'_>,
+        ctx: tracing_subscriber::layer::Context<'_, S>,
+    ) {
+        SpanTextFields::<Self>::on_record(id, values, ctx, &self.scrubber);
+    }
+}
+
+impl SyslogLogger {
+    /// ## 连接到 syslog
+    ///
+    /// `address` 可以是 `udp://host:port`、`unix:///dev/log`，或者直接写 socket 的路径
+    pub fn connect(address: &str, min_level: LogLevel) -> io::Result<Self> {
+        let transport = match address.strip_prefix("udp://") {
+            Some(addr) => {
+                let socket = UdpSocket::bind(("0.0.0.0", 0))?;
+                socket.connect(addr)?;
+                Transport::Udp(socket)
+            }
+            None => Transport::unix(address.strip_prefix("unix://").unwrap_or(address))?,
+        };
+
+        Ok(Self {
+            transport,
+            facility: Facility::default(),
+            app_name: "crab-vault".into(),
+            hostname: hostname(),
+            min_level,
+            filter: TargetFilter::default(),
+            scrubber: Scrubber::default(),
+            reported: AtomicBool::new(false),
+        })
+    }
+
+    pub fn with_facility(mut self, facility: Facility) -> Self {
+        self.facility = facility;
+        self
+    }
+
+    /// 消息中的 APP-NAME，syslog 守护进程通常用它区分不同的程序
+    pub fn with_app_name(mut self, app_name: impl Into<String>) -> Self {
+        self.app_name = app_name.into();
+        self
+    }
+
+    /// 运行时修改输出的最低级别，参见 `tracing_subscriber::reload`
+    pub fn set_level(&mut self, min_level: LogLevel) {
+        self.min_level = min_level;
+    }
+
+    /// 运行时替换按照模块设置的级别，参见 [`TargetFilter`]
+    pub fn set_filter(&mut self, filter: TargetFilter) {
+        self.filter = filter;
+    }
+
+    /// 匹配 `filter` 的模块使用其中的级别，其他模块仍然使用 `min_level`
+    pub fn with_filter(mut self, filter: TargetFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// 在发送之前，使用 `scrubber` 对字段进行脱敏
+    pub fn with_scrubber(mut self, scrubber: Scrubber) -> Self {
+        self.scrubber = scrubber;
+        self
+    }
+
+    /// `<PRI>1 TIMESTAMP HOSTNAME APP-NAME PROCID MSGID STRUCTURED-DATA MSG`
+    fn format(&self, level: LogLevel, target: &str, fields: &TextFields) -> String {
+        let mut message = format!(
+            "<{}>1 {} {} {} {} {NIL} {NIL} ",
+            self.facility.code() * 8 + severity(level),
+            Local::now().to_rfc3339_opts(SecondsFormat::Micros, false),
+            self.hostname,
+            self.app_name,
+            std::process::id(),
+        );
+        let header = message.len();
+
+        message.push_str(fields.get("message").unwrap_or_default());
+        message.push_str(&format!(" target={target}"));
+        for (name, value) in fields.fields.iter().filter(|(v, _)| *v != "message") {
+            message.push_str(&format!(" {name}={value}"));
+        }
+
+        if message.len() > MAX_MESSAGE_LEN.max(header) {
+            let mut end = MAX_MESSAGE_LEN.max(header);
+            while !message.is_char_boundary(end) {
+                end -= 1;
+            }
+            message.truncate(end);
+        }
+        message
+    }
+}
+
+impl Facility {
+    /// RFC 5424 中的编号
+    pub const fn code(self) -> u8 {
+        match self {
+            Facility::User => 1,
+            Facility::Daemon => 3,
+            Facility::Auth => 4,
+            Facility::Local0 => 16,
+            Facility::Local1 => 17,
+            Facility::Local2 => 18,
+            Facility::Local3 => 19,
+            Facility::Local4 => 20,
+            Facility::Local5 => 21,
+            Facility::Local6 => 22,
+            Facility::Local7 => 23,
+        }
+    }
+}
+
+impl Transport {
+    #[cfg(unix)]
+    fn unix(path: &str) -> io::Result<Self> {
+        let socket = UnixDatagram::unbound()?;
+        socket.connect(path)?;
+        Ok(Transport::Unix(socket))
+    }
+
+    #[cfg(not(unix))]
+    fn unix(_: &str) -> io::Result<Self> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "unix sockets are not supported on this platform, use `udp://host:port`",
+        ))
+    }
+
+    fn send(&self, message: &[u8]) -> io::Result<usize> {
+        match self {
+            Transport::Udp(socket) => socket.send(message),
+            #[cfg(unix)]
+            Transport::Unix(socket) => socket.send(message),
+        }
+    }
+}
+
+/// RFC 5424 中的 severity，syslog 没有 trace，与 debug 相同
+pub const fn severity(level: LogLevel) -> u8 {
+    match level {
+        LogLevel::Error => 3,
+        LogLevel::Warn => 4,
+        LogLevel::Info => 6,
+        LogLevel::Debug | LogLevel::Trace => 7,
+    }
+}
+
+/// 消息中的 HOSTNAME，无法读取时为 NILVALUE
+fn hostname() -> String {
+    std::fs::read_to_string("/proc/sys/kernel/hostname")
+        .ok()
+        .or_else(|| std::env::var("HOSTNAME").ok())
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty() && v.is_ascii() && !v.contains(' '))
+        .unwrap_or_else(|| NIL.to_string())
+}
@@ -0,0 +1,183 @@
+use crab_vault_utils::ansi::{AnsiColor, AnsiStyle, FontStyle};
+use serde::{Deserialize, Serialize};
+
+use crate::LogLevel;
+
+/// ## pretty 日志的配色
+///
+/// 每一项都可以省略，省略的项使用内置的配色。颜色的写法参见 [`AnsiColor`] 的 `FromStr` 实现，例如
+///
+/// ```toml
+/// [logger.theme.error]
+/// line = { fore = "#ff5f5f" }
+/// label = { fore = "black", back = "bright-red", bold = true }
+///
+/// [logger.theme]
+/// field = { fore = 75, bold = true }
+/// ```
+#[derive(Deserialize, Serialize, Clone, Default, Debug, PartialEq, Eq)]
+#[serde(deny_unknown_fields, default)]
+pub struct Theme {
+    pub trace: LevelTheme,
+    pub debug: LevelTheme,
+    pub info: LevelTheme,
+    pub warn: LevelTheme,
+    pub error: LevelTheme,
+
+    /// 事件中字段的名称
+    pub field: Option<StyleSpec>,
+
+    /// `target`、`file` 等元信息的名称
+    pub meta: Option<StyleSpec>,
+
+    /// span 的名称、元信息和字段
+    pub span: Option<StyleSpec>,
+}
+
+/// 某一个级别的日志使用的样式
+#[derive(Deserialize, Serialize, Clone, Default, Debug, PartialEq, Eq)]
+#[serde(deny_unknown_fields, default)]
+pub struct LevelTheme {
+    /// 左侧的边框
+    pub line: Option<StyleSpec>,
+
+    /// 开头的 `[LEVEL]` 标签
+    pub label: Option<StyleSpec>,
+}
+
+/// 配置文件中的一个样式
+#[derive(Deserialize, Serialize, Clone, Default, Debug, PartialEq, Eq)]
+#[serde(deny_unknown_fields, default)]
+pub struct StyleSpec {
+    pub fore: Option<AnsiColor>,
+    pub back: Option<AnsiColor>,
+    pub bold: bool,
+    pub dimmed: bool,
+    pub italic: bool,
+    pub underline: bool,
+}
+
+impl Theme {
+    /// ## 某一个级别的日志的边框样式
+    ///
+    /// # 示例
+    /// ```
+    /// # use crab_vault_logger::{LogLevel, theme::{Theme, StyleSpec}};
+    /// # use crab_vault_utils::ansi::AnsiColor;
+    /// let mut theme = Theme::default();
+    /// theme.error.line = Some(StyleSpec::new().fore(AnsiColor::Rgb(255, 95, 95)));
+    ///
+    /// assert_eq!(theme.style_of(LogLevel::Error).to_string(), "\x1B[;38;2;255;95;95m");
+    /// // 没有配置的级别使用内置的配色
+    /// assert_eq!(theme.style_of(LogLevel::Warn).to_string(), "\x1B[;33m");
+    /// ```
+    pub fn style_of(&self, level: LogLevel) -> AnsiStyle {
+        match &self.level(level).line {
+            Some(spec) => spec.to_style(),
+            None => Self::builtin_line(level),
+        }
+    }
+
+    /// 某一个级别的日志的 `[LEVEL]` 标签的样式
+    pub fn label_style_of(&self, level: LogLevel) -> AnsiStyle {
+        match &self.level(level).label {
+            Some(spec) => spec.to_style(),
+            None => Self::builtin_label(level),
+        }
+    }
+
+    pub fn field_style(&self) -> AnsiStyle {
+        Self::or_builtin(&self.field, AnsiColor::Blue)
+    }
+
+    pub fn meta_style(&self) -> AnsiStyle {
+        Self::or_builtin(&self.meta, AnsiColor::Magenta)
+    }
+
+    pub fn span_style(&self) -> AnsiStyle {
+        Self::or_builtin(&self.span, AnsiColor::Cyan)
+    }
+
+    fn level(&self, level: LogLevel) -> &LevelTheme {
+        match level {
+            LogLevel::Trace => &self.trace,
+            LogLevel::Debug => &self.debug,
+            LogLevel::Info => &self.info,
+            LogLevel::Warn => &self.warn,
+            LogLevel::Error => &self.error,
+        }
+    }
+
+    fn or_builtin(spec: &Option<StyleSpec>, fore: AnsiColor) -> AnsiStyle {
+        match spec {
+            Some(spec) => spec.to_style(),
+            None => AnsiStyle::new()
+                .with_fore(fore)
+                .with_font(FontStyle::new().bold(true)),
+        }
+    }
+
+    fn builtin_line(level: LogLevel) -> AnsiStyle {
+        let bold = FontStyle::new().bold(true);
+        match level {
+            LogLevel::Trace => AnsiStyle::new()
+                .with_fore(AnsiColor::Magenta)
+                .with_font(bold),
+            LogLevel::Debug => AnsiStyle::new().with_fore(AnsiColor::Blue).with_font(bold),
+            LogLevel::Info => AnsiStyle::new().with_fore(AnsiColor::Green),
+            LogLevel::Warn => AnsiStyle::new().with_fore(AnsiColor::Yellow),
+            LogLevel::Error => AnsiStyle::new().with_fore(AnsiColor::Red),
+        }
+    }
+
+    fn builtin_label(level: LogLevel) -> AnsiStyle {
+        let (fore, back) = match level {
+            LogLevel::Trace => (AnsiColor::BrightWhite, AnsiColor::BrightMagenta),
+            LogLevel::Debug => (AnsiColor::BrightWhite, AnsiColor::BrightBlue),
+            LogLevel::Info => (AnsiColor::BrightBlack, AnsiColor::BrightGreen),
+            LogLevel::Warn => (AnsiColor::BrightBlack, AnsiColor::BrightYellow),
+            LogLevel::Error => (AnsiColor::BrightBlack, AnsiColor::BrightRed),
+        };
+        AnsiStyle::new()
+            .with_fore(fore)
+            .with_back(back)
+            .with_font(FontStyle::new().bold(true))
+    }
+}
+
+impl StyleSpec {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn fore(mut self, color: AnsiColor) -> Self {
+        self.fore = Some(color);
+        self
+    }
+
+    #[inline]
+    pub fn back(mut self, color: AnsiColor) -> Self {
+        self.back = Some(color);
+        self
+    }
+
+    #[inline]
+    pub fn bold(mut self, enabled: bool) -> Self {
+        self.bold = enabled;
+        self
+    }
+
+    pub fn to_style(&self) -> AnsiStyle {
+        let font = FontStyle::new()
+            .bold(self.bold)
+            .dimmed(self.dimmed)
+            .italic(self.italic)
+            .underline(self.underline);
+        AnsiStyle::new()
+            .with_fore_option(self.fore)
+            .with_back_option(self.back)
+            .with_font(font)
+    }
+}
@@ -0,0 +1,94 @@
+use std::{fmt, str::FromStr};
+
+/// 传递调用链上下文的请求头
+pub const TRACEPARENT: &str = "traceparent";
+
+/// ## W3C Trace Context 中的 `traceparent`
+///
+/// 形如 `00-{trace_id}-{parent_id}-{flags}`，其中 `trace_id` 是 32 个十六进制字符，
+/// `parent_id` 是 16 个，`flags` 是 2 个。全零的 id 以及版本 `ff` 都是不合法的
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct TraceParent {
+    pub trace_id: [u8; 16],
+    pub parent_id: [u8; 8],
+    pub flags: u8,
+}
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+#[error("invalid traceparent `{0}`")]
+pub struct InvalidTraceParent(pub String);
+
+impl TraceParent {
+    /// 调用方是否对这条调用链进行了采样
+    #[inline]
+    pub fn sampled(&self) -> bool {
+        self.flags & 0x01 != 0
+    }
+
+    /// 十六进制的 `trace_id`，用于在日志中关联同一条调用链
+    pub fn trace_id_hex(&self) -> String {
+        hex(&self.trace_id)
+    }
+}
+
+impl FromStr for TraceParent {
+    type Err = InvalidTraceParent;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || InvalidTraceParent(s.to_string());
+        let mut parts = s.trim().split('-');
+        let (Some(version), Some(trace_id), Some(parent_id), Some(flags)) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(invalid());
+        };
+
+        // 更高的版本可以在后面追加字段，版本 00 不允许
+        let version = parse_hex::<1>(version).ok_or_else(invalid)?[0];
+        if version == 0xff || (version == 0 && parts.next().is_some()) {
+            return Err(invalid());
+        }
+
+        let trace_id = parse_hex::<16>(trace_id).ok_or_else(invalid)?;
+        let parent_id = parse_hex::<8>(parent_id).ok_or_else(invalid)?;
+        let flags = parse_hex::<1>(flags).ok_or_else(invalid)?[0];
+        if trace_id == [0; 16] || parent_id == [0; 8] {
+            return Err(invalid());
+        }
+
+        Ok(Self {
+            trace_id,
+            parent_id,
+            flags,
+        })
+    }
+}
+
+impl fmt::Display for TraceParent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "00-{}-{}-{:02x}",
+            hex(&self.trace_id),
+            hex(&self.parent_id),
+            self.flags
+        )
+    }
+}
+
+/// 恰好 `2 * N` 个小写十六进制字符
+fn parse_hex<const N: usize>(s: &str) -> Option<[u8; N]> {
+    if s.len() != 2 * N || !s.bytes().all(|v| matches!(v, b'0'..=b'9' | b'a'..=b'f')) {
+        return None;
+    }
+
+    let mut bytes = [0; N];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&s[2 * i..2 * i + 2], 16).ok()?;
+    }
+    Some(bytes)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|v| format!("{v:02x}")).collect()
+}
@@ -0,0 +1,175 @@
+//! # 非阻塞的日志输出
+//!
+//! 日志层把格式化好的一整条记录交给 [`NonBlocking`]，由后台线程写出，产生日志的线程不会被 IO 阻塞。
+//! 缓冲区是有界的，写满时新的记录被丢弃并计数，而不是让产生日志的线程等待，
+//! 后台线程会在标准错误中报告丢弃的数量。
+//!
+//! ```
+//! # use std::{io::Write, sync::{Arc, Mutex}};
+//! # use crab_vault_logger::writer::NonBlocking;
+//! #[derive(Clone, Default)]
+//! struct Shared(Arc<Mutex<Vec<u8>>>);
+//!
+//! impl Write for Shared {
+//!     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+//!         self.0.lock().unwrap().write(buf)
+//!     }
+//!
+//!     fn flush(&mut self) -> std::io::Result<()> {
+//!         Ok(())
+//!     }
+//! }
+//!
+//! let output = Shared::default();
+//! let writer = NonBlocking::new(output.clone(), 16);
+//! writer.send(b"first\n".to_vec());
+//! writer.send(b"second\n".to_vec());
+//!
+//! // flush 返回时，之前交给它的记录都已经写出
+//! writer.flush().unwrap();
+//! assert_eq!(*output.0.lock().unwrap(), b"first\nsecond\n");
+//! assert_eq!(writer.dropped(), 0);
+//! ```
+
+use std::{
+    io::{self, Write},
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+        mpsc::{self, Receiver, SyncSender, TrySendError},
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+/// 默认的缓冲区大小，单位是记录的条数
+pub const DEFAULT_CAPACITY: usize = 8192;
+
+/// 等待后台线程写出缓冲区的最长时间，避免 writer 卡住时进程无法退出
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 缓冲区已满时重新尝试放入 flush 请求的间隔
+const FLUSH_RETRY_INTERVAL: Duration = Duration::from_millis(1);
+
+/// ## 交给后台线程写出的日志输出
+///
+/// 可以被克隆，所有的克隆共享同一个缓冲区和后台线程，最后一个克隆被 drop 之后后台线程写完剩下的记录并退出
+#[derive(Clone)]
+pub struct NonBlocking {
+    sender: SyncSender<Message>,
+    dropped: Arc<AtomicU64>,
+}
+
+enum Message {
+    Record(Vec<u8>),
+
+    /// 写出并 flush 之前的所有记录之后回复
+    Flush(SyncSender<io::Result<()>>),
+}
+
+struct Worker<W> {
+    writer: W,
+    receiver: Receiver<Message>,
+    dropped: Arc<AtomicU64>,
+
+    /// 已经报告过的丢弃数量
+    reported: u64,
+}
+
+impl NonBlocking {
+    /// 创建缓冲区并启动后台线程，缓冲区中最多有 `capacity` 条记录
+    pub fn new<W: Write + Send + 'static>(writer: W, capacity: usize) -> Self {
+        let (sender, receiver) = mpsc::sync_channel(capacity.max(1));
+        let dropped = Arc::new(AtomicU64::new(0));
+        let worker = Worker {
+            writer,
+            receiver,
+            dropped: dropped.clone(),
+            reported: 0,
+        };
+
+        thread::Builder::new()
+            .name("crab-vault-log".into())
+            .spawn(move || worker.run())
+            .expect("failed to spawn the log writer thread");
+
+        Self { sender, dropped }
+    }
+
+    /// 交给后台线程写出一条记录，缓冲区已满或者后台线程已经退出时丢弃
+    pub fn send(&self, record: Vec<u8>) {
+        if self.sender.try_send(Message::Record(record)).is_err() {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// 到目前为止因为缓冲区已满而丢弃的记录数
+    #[inline]
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// 等待之前交给后台线程的记录全部写出，最多等待几秒，缓冲区一直是满的时也不会超过这个时间
+    pub fn flush(&self) -> io::Result<()> {
+        let stuck = || io::Error::new(io::ErrorKind::TimedOut, "the log writer is stuck");
+        let deadline = Instant::now() + FLUSH_TIMEOUT;
+        let (ack, done) = mpsc::sync_channel(1);
+
+        let mut message = Message::Flush(ack);
+        loop {
+            match self.sender.try_send(message) {
+                Ok(()) => break,
+                Err(TrySendError::Full(v)) if Instant::now() < deadline => {
+                    message = v;
+                    thread::sleep(FLUSH_RETRY_INTERVAL);
+                }
+                Err(TrySendError::Full(_)) => return Err(stuck()),
+                Err(TrySendError::Disconnected(_)) => {
+                    return Err(io::Error::other("the log writer thread has exited"));
+                }
+            }
+        }
+
+        done.recv_timeout(deadline.saturating_duration_since(Instant::now()))
+            .map_err(|_| stuck())?
+    }
+}
+
+impl<W: Write> Worker<W> {
+    fn run(mut self) {
+        while let Ok(message) = self.receiver.recv() {
+            self.handle(message);
+
+            // 缓冲区空了再 flush，日志很多时不必每条都 flush
+            while let Ok(message) = self.receiver.try_recv() {
+                self.handle(message);
+            }
+            let _ = self.writer.flush();
+            self.report_dropped();
+        }
+    }
+
+    fn handle(&mut self, message: Message) {
+        match message {
+            Message::Record(record) => {
+                if let Err(e) = self.writer.write_all(&record) {
+                    eprintln!("Cannot write the log record, details: {e}");
+                }
+            }
+            Message::Flush(ack) => {
+                let _ = ack.send(self.writer.flush());
+            }
+        }
+    }
+
+    fn report_dropped(&mut self) {
+        let dropped = self.dropped.load(Ordering::Relaxed);
+        if dropped > self.reported {
+            eprintln!(
+                "{} log records were dropped because the log buffer is full",
+                dropped - self.reported
+            );
+            self.reported = dropped;
+        }
+    }
+}
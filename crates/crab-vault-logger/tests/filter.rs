@@ -0,0 +1,45 @@
+use crab_vault_logger::{LogLevel, filter::TargetFilter};
+
+#[test]
+fn test_longest_prefix_wins() {
+    let filter: TargetFilter = "crab_vault=debug,crab_vault::http=warn,tower=error"
+        .parse()
+        .unwrap();
+
+    assert_eq!(
+        filter.level_for("crab_vault::http::server"),
+        Some(LogLevel::Warn)
+    );
+    assert_eq!(
+        filter.level_for("crab_vault::engine"),
+        Some(LogLevel::Debug)
+    );
+    assert_eq!(filter.level_for("crab_vault"), Some(LogLevel::Debug));
+    // 与 EnvFilter 一样按照字符串前缀匹配
+    assert_eq!(filter.level_for("tower_http::trace"), Some(LogLevel::Error));
+    assert_eq!(filter.level_for("hyper"), None);
+}
+
+#[test]
+fn test_default_level_applies_without_match() {
+    let filter: TargetFilter = "hyper=error".parse().unwrap();
+
+    assert!(filter.enabled("crab_vault", LogLevel::Info, LogLevel::Info));
+    assert!(!filter.enabled("crab_vault", LogLevel::Debug, LogLevel::Info));
+    assert!(!filter.enabled("hyper::proto", LogLevel::Warn, LogLevel::Trace));
+    assert!(TargetFilter::new().enabled("hyper", LogLevel::Trace, LogLevel::Trace));
+}
+
+#[test]
+fn test_parse() {
+    let filter: TargetFilter = " a=TRACE , b=warn,,a=info ".parse().unwrap();
+    // 同一个 target 后面的规则覆盖前面的
+    assert_eq!(filter.level_for("a"), Some(LogLevel::Info));
+    assert_eq!(filter.directives().len(), 2);
+    assert_eq!(filter.to_string().parse::<TargetFilter>().unwrap(), filter);
+
+    assert!("".parse::<TargetFilter>().unwrap().is_empty());
+    assert!("debug".parse::<TargetFilter>().is_err());
+    assert!("=debug".parse::<TargetFilter>().is_err());
+    assert!("a=verbose".parse::<TargetFilter>().is_err());
+}
@@ -0,0 +1,60 @@
+use crab_vault_logger::json::JsonLogRecords;
+
+const LOG: &str = r#"{
+  "level": "INFO",
+  "message": "first",
+  "time": "Fri, 17 Oct 2025 10:00:00 +0800"
+},
+{
+  "level": "WARN",
+  "message": "no time"
+},
+{
+  "level": "INFO",
+  "message": "last, with a comma",
+  "time": "Fri, 17 Oct 2025 10:05:00 +0800"
+},
+"#;
+
+#[test]
+fn test_read_records() {
+    let records = JsonLogRecords::new(LOG)
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+
+    assert_eq!(records.len(), 3);
+    assert!(records[0].raw.starts_with('{') && records[0].raw.ends_with('}'));
+    assert!(records[0].raw.contains("\"first\""));
+    assert!(records[2].raw.contains("last, with a comma"));
+
+    let times = records
+        .iter()
+        .map(|v| v.time.map(|t| t.to_rfc3339()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        times,
+        [
+            Some("2025-10-17T10:00:00+08:00".to_string()),
+            None,
+            Some("2025-10-17T10:05:00+08:00".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn test_truncated_record_stops_reading() {
+    let truncated = format!("{LOG}{{\n  \"level\": \"IN");
+    let mut records = JsonLogRecords::new(&truncated);
+
+    for _ in 0..3 {
+        assert!(records.next().unwrap().is_ok());
+    }
+    assert!(records.next().unwrap().is_err());
+    assert!(records.next().is_none());
+}
+
+#[test]
+fn test_empty_file() {
+    assert!(JsonLogRecords::new("").next().is_none());
+    assert!(JsonLogRecords::new("\n").next().is_none());
+}
@@ -0,0 +1,69 @@
+use crab_vault_logger::scrub::{DROPPED, MASKED, Scrubber};
+use glob::Pattern;
+use serde_json::json;
+
+fn scrubber() -> Scrubber {
+    Scrubber::new()
+        .mask_object_keys(vec![Pattern::new("/private/*").unwrap()])
+        .drop_user_meta(true)
+        .hash_client_ips(Some("salt".to_string()))
+}
+
+#[test]
+fn test_default_is_noop() {
+    let scrubber = Scrubber::default();
+    assert!(scrubber.is_noop());
+    assert_eq!(scrubber.scrub("uri", "/private/a.txt"), None);
+    assert_eq!(scrubber.scrub("user_meta", "{}"), None);
+    assert_eq!(scrubber.scrub("client_ip", "127.0.0.1"), None);
+}
+
+#[test]
+fn test_mask_object_keys() {
+    let scrubber = scrubber();
+    assert_eq!(
+        scrubber.scrub("uri", "/private/a.txt?x=1"),
+        Some(format!("/private/{MASKED}"))
+    );
+    assert_eq!(
+        scrubber.scrub("path", "/private/a.txt"),
+        Some(format!("/private/{MASKED}"))
+    );
+    assert_eq!(scrubber.scrub("uri", "/public/a.txt"), None);
+    // 只有 bucket 的路径不需要遮盖
+    assert_eq!(scrubber.scrub("uri", "/private"), None);
+    // 其他字段即便看起来像路径也不处理
+    assert_eq!(scrubber.scrub("message", "/private/a.txt"), None);
+}
+
+#[test]
+fn test_scrub_object() {
+    let scrubber = scrubber();
+    assert_eq!(scrubber.scrub_object("private", "a/b.txt").as_deref(), Some(MASKED));
+    assert_eq!(scrubber.scrub_object("public", "a/b.txt"), None);
+    assert_eq!(Scrubber::default().scrub_object("private", "a/b.txt"), None);
+}
+
+#[test]
+fn test_drop_user_meta() {
+    let scrubber = scrubber();
+    assert_eq!(scrubber.scrub("user_meta", "{\"name\":\"alice\"}").as_deref(), Some(DROPPED));
+    assert_eq!(
+        scrubber.scrub_json("user_meta", json!({ "name": "alice" })),
+        json!(DROPPED)
+    );
+}
+
+#[test]
+fn test_hash_client_ips() {
+    let scrubber = scrubber();
+    let hashed = scrubber.scrub("client_ip", "10.0.0.1").unwrap();
+
+    assert_eq!(hashed.len(), 16);
+    assert_ne!(hashed, "10.0.0.1");
+    assert_eq!(scrubber.scrub("client_ip", "10.0.0.1").unwrap(), hashed);
+    assert_ne!(scrubber.scrub("client_ip", "10.0.0.2").unwrap(), hashed);
+
+    let other = Scrubber::new().hash_client_ips(Some("pepper".to_string()));
+    assert_ne!(other.scrub("client_ip", "10.0.0.1").unwrap(), hashed);
+}
@@ -0,0 +1,66 @@
+use std::{net::UdpSocket, time::Duration};
+
+use crab_vault_logger::{
+    LogLevel,
+    syslog::{Facility, SyslogLogger},
+};
+use tracing_subscriber::layer::SubscriberExt;
+
+fn receive(socket: &UdpSocket) -> String {
+    let mut buf = [0; 4096];
+    let len = socket.recv(&mut buf).unwrap();
+    String::from_utf8_lossy(&buf[..len]).into_owned()
+}
+
+#[test]
+fn test_send_over_udp() {
+    let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+    server
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    let address = format!("udp://{}", server.local_addr().unwrap());
+
+    let logger = SyslogLogger::connect(&address, LogLevel::Info)
+        .unwrap()
+        .with_app_name("crab-test");
+    let subscriber = tracing_subscriber::registry().with(logger);
+
+    tracing::subscriber::with_default(subscriber, || {
+        let span = tracing::info_span!("request", req_id = "abc");
+        let _guard = span.enter();
+        tracing::debug!("filtered out");
+        tracing::info!(status = 200, "request finished");
+    });
+
+    let message = receive(&server);
+    // daemon 是 3，info 是 6
+    assert!(message.starts_with("<30>1 "), "{message}");
+    assert!(message.contains(" crab-test "), "{message}");
+    assert!(
+        message.contains("request finished target=syslog req_id=abc status=200"),
+        "{message}"
+    );
+}
+
+#[test]
+fn test_priority() {
+    let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+    server
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    let address = format!("udp://{}", server.local_addr().unwrap());
+
+    let logger = SyslogLogger::connect(&address, LogLevel::Trace)
+        .unwrap()
+        .with_facility(Facility::Local0);
+    let subscriber = tracing_subscriber::registry().with(logger);
+
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::error!("error");
+        tracing::trace!("trace");
+    });
+
+    // local0 是 16，error 是 3，trace 与 debug 同为 7
+    assert!(receive(&server).starts_with("<131>1 "));
+    assert!(receive(&server).starts_with("<135>1 "));
+}
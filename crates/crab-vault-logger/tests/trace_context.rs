@@ -0,0 +1,45 @@
+use crab_vault_logger::trace_context::TraceParent;
+
+const VALID: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+#[test]
+fn test_parse_traceparent() {
+    let parent = VALID.parse::<TraceParent>().unwrap();
+    assert_eq!(parent.trace_id_hex(), "4bf92f3577b34da6a3ce929d0e0e4736");
+    assert_eq!(
+        parent.parent_id,
+        [0x00, 0xf0, 0x67, 0xaa, 0x0b, 0xa9, 0x02, 0xb7]
+    );
+    assert!(parent.sampled());
+    assert_eq!(parent.to_string(), VALID);
+
+    let unsampled = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-00";
+    assert!(!unsampled.parse::<TraceParent>().unwrap().sampled());
+}
+
+#[test]
+fn test_future_versions_may_append_fields() {
+    let parent = "01-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra"
+        .parse::<TraceParent>()
+        .unwrap();
+    // 无论收到的是哪个版本，传递下去的都是自己支持的版本
+    assert_eq!(parent.to_string(), VALID);
+}
+
+#[test]
+fn test_reject_invalid_traceparent() {
+    for invalid in [
+        "",
+        "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7",
+        "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra",
+        "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+        "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+        "00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01",
+        "00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01",
+        "00-4bf92f3577b34da6a3ce929d0e0e473-00f067aa0ba902b7-01",
+        "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-1",
+        "00-4bf92f3577b34da6a3ce929d0e0e473g-00f067aa0ba902b7-01",
+    ] {
+        assert!(invalid.parse::<TraceParent>().is_err(), "{invalid}");
+    }
+}
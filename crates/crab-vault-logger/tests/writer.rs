@@ -0,0 +1,116 @@
+use std::{
+    io::{self, Write},
+    sync::{
+        Arc, Mutex,
+        mpsc::{self, Receiver},
+    },
+    time::{Duration, Instant},
+};
+
+use crab_vault_logger::writer::NonBlocking;
+
+/// 每次写入之前等待一个许可，用来模拟卡住的输出
+struct Gated {
+    permits: Receiver<()>,
+    output: Arc<Mutex<Vec<u8>>>,
+}
+
+impl Write for Gated {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.permits.recv().map_err(io::Error::other)?;
+        self.output.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_records_are_written_in_order() {
+    let output = Arc::new(Mutex::new(Vec::new()));
+    let (permit, permits) = mpsc::channel();
+    let writer = NonBlocking::new(
+        Gated {
+            permits,
+            output: output.clone(),
+        },
+        16,
+    );
+
+    for i in 0..10 {
+        permit.send(()).unwrap();
+        writer.send(format!("{i}\n").into_bytes());
+    }
+    writer.flush().unwrap();
+
+    let expected: String = (0..10).map(|i| format!("{i}\n")).collect();
+    assert_eq!(*output.lock().unwrap(), expected.as_bytes());
+    assert_eq!(writer.dropped(), 0);
+}
+
+#[test]
+fn test_drops_when_full() {
+    let output = Arc::new(Mutex::new(Vec::new()));
+    let (permit, permits) = mpsc::channel();
+    let writer = NonBlocking::new(
+        Gated {
+            permits,
+            output: output.clone(),
+        },
+        2,
+    );
+
+    // 后台线程卡在第一条记录上，缓冲区中最多再放两条
+    writer.send(b"a\n".to_vec());
+    std::thread::sleep(Duration::from_millis(100));
+    for _ in 0..10 {
+        writer.send(b"b\n".to_vec());
+    }
+    assert_eq!(writer.dropped(), 8);
+
+    for _ in 0..3 {
+        permit.send(()).unwrap();
+    }
+    writer.flush().unwrap();
+    assert_eq!(*output.lock().unwrap(), b"a\nb\nb\n");
+}
+
+#[test]
+fn test_flush_times_out_when_stuck() {
+    let (_permit, permits) = mpsc::channel();
+    let writer = NonBlocking::new(
+        Gated {
+            permits,
+            output: Arc::default(),
+        },
+        4,
+    );
+
+    writer.send(b"stuck\n".to_vec());
+    let e = writer.flush().unwrap_err();
+    assert_eq!(e.kind(), io::ErrorKind::TimedOut);
+}
+
+#[test]
+fn test_flush_times_out_when_full() {
+    let (_permit, permits) = mpsc::channel();
+    let writer = NonBlocking::new(
+        Gated {
+            permits,
+            output: Arc::default(),
+        },
+        2,
+    );
+
+    // 后台线程卡住并且缓冲区已满，flush 请求本身放不进去
+    writer.send(b"stuck\n".to_vec());
+    std::thread::sleep(Duration::from_millis(100));
+    writer.send(b"a\n".to_vec());
+    writer.send(b"b\n".to_vec());
+
+    let start = Instant::now();
+    let e = writer.flush().unwrap_err();
+    assert_eq!(e.kind(), io::ErrorKind::TimedOut);
+    assert!(start.elapsed() < Duration::from_secs(10));
+}
@@ -1,7 +1,13 @@
-use std::fmt::Display;
+use std::{fmt::Display, str::FromStr};
 
 use crate::bitmap::Bitmap;
 
+#[cfg(feature = "serde")]
+mod serialize;
+mod text;
+
+pub use text::{AnsiText, ELLIPSIS, char_width, display_width, truncate_to_width};
+
 pub const RESET: &str = "\x1B[0m";
 pub const ESCAPE_BEGIN: &str = "\x1B[";
 pub const ESCAPE_OVER: &str = "m";
@@ -21,9 +27,9 @@ pub struct FontStyle {
     pub options: Bitmap<u16>,
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum AnsiColor {
-    Black = 30,
+    Black,
     Red,
     Green,
     Yellow,
@@ -31,7 +37,7 @@ pub enum AnsiColor {
     Magenta,
     Cyan,
     White,
-    BrightBlack = 90,
+    BrightBlack,
     BrightRed,
     BrightGreen,
     BrightYellow,
@@ -39,8 +45,65 @@ pub enum AnsiColor {
     BrightMagenta,
     BrightCyan,
     BrightWhite,
+
+    /// 256 色调色板中的一个，0 到 15 与上面的 16 种颜色相同
+    Fixed(u8),
+
+    /// 24 位真彩色
+    Rgb(u8, u8, u8),
 }
 
+/// [`AnsiColor`] 的 SGR 参数，例如 `31`、`38;5;208`、`48;2;255;128;0`
+#[derive(Clone, Copy)]
+pub struct ColorCode {
+    color: AnsiColor,
+
+    /// 前景色为 30，背景色为 40
+    base: u8,
+}
+
+/// 无法识别的颜色名称，参见 [`AnsiColor::from_str`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseColorError {
+    input: String,
+}
+
+/// 终端能够显示的颜色，参见 [`ColorSupport::detect`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ColorSupport {
+    /// 不输出任何转义序列
+    None,
+    /// 只有 16 种基本颜色
+    Basic,
+    /// 256 色
+    Fixed,
+    /// 24 位真彩色
+    TrueColor,
+}
+
+/// xterm 中 16 种基本颜色的近似值，用于把其他颜色降级到最接近的一种
+const BASIC_PALETTE: [(u8, u8, u8); 16] = [
+    (0, 0, 0),
+    (205, 0, 0),
+    (0, 205, 0),
+    (205, 205, 0),
+    (0, 0, 238),
+    (205, 0, 205),
+    (0, 205, 205),
+    (229, 229, 229),
+    (127, 127, 127),
+    (255, 0, 0),
+    (0, 255, 0),
+    (255, 255, 0),
+    (92, 92, 255),
+    (255, 0, 255),
+    (0, 255, 255),
+    (255, 255, 255),
+];
+
+/// 256 色中 6x6x6 色块每个分量的取值
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
 #[derive(Clone, Copy, Default)]
 pub struct AnsiStyle {
     fore: Option<AnsiColor>,
@@ -66,12 +129,12 @@ impl Display for AnsiStyle {
                 f.write_fmt(format_args!(";{code}"))?;
             }
 
-            if self.fore.is_some() {
-                f.write_fmt(format_args!(";{}", self.fore.unwrap().into_fore()))?;
+            if let Some(fore) = self.fore {
+                f.write_fmt(format_args!(";{}", fore.into_fore()))?;
             }
 
-            if self.back.is_some() {
-                f.write_fmt(format_args!(";{}", self.back.unwrap().into_back()))?;
+            if let Some(back) = self.back {
+                f.write_fmt(format_args!(";{}", back.into_back()))?;
             }
 
             f.write_str(ESCAPE_OVER)
@@ -104,16 +167,288 @@ impl FontStyle {
 
 impl AnsiColor {
     #[inline(always)]
-    pub fn into_fore(self) -> u8 {
-        self as u8
+    pub fn into_fore(self) -> ColorCode {
+        ColorCode {
+            color: self,
+            base: 30,
+        }
     }
 
     #[inline(always)]
-    pub fn into_back(self) -> u8 {
-        self as u8 + 10
+    pub fn into_back(self) -> ColorCode {
+        ColorCode {
+            color: self,
+            base: 40,
+        }
+    }
+
+    /// 16 种基本颜色的序号，其他颜色为 [`None`]
+    const fn basic_index(self) -> Option<u8> {
+        use AnsiColor::*;
+        let index = match self {
+            Black => 0,
+            Red => 1,
+            Green => 2,
+            Yellow => 3,
+            Blue => 4,
+            Magenta => 5,
+            Cyan => 6,
+            White => 7,
+            BrightBlack => 8,
+            BrightRed => 9,
+            BrightGreen => 10,
+            BrightYellow => 11,
+            BrightBlue => 12,
+            BrightMagenta => 13,
+            BrightCyan => 14,
+            BrightWhite => 15,
+            Fixed(_) | Rgb(..) => return None,
+        };
+        Some(index)
+    }
+
+    const fn from_basic_index(index: u8) -> Self {
+        use AnsiColor::*;
+        match index {
+            0 => Black,
+            1 => Red,
+            2 => Green,
+            3 => Yellow,
+            4 => Blue,
+            5 => Magenta,
+            6 => Cyan,
+            7 => White,
+            8 => BrightBlack,
+            9 => BrightRed,
+            10 => BrightGreen,
+            11 => BrightYellow,
+            12 => BrightBlue,
+            13 => BrightMagenta,
+            14 => BrightCyan,
+            _ => BrightWhite,
+        }
+    }
+
+    /// ## 转换为终端能够显示的颜色
+    ///
+    /// 真彩色降级为最接近的 256 色，256 色降级为最接近的基本颜色，终端不支持颜色时返回 [`None`]
+    ///
+    /// # 示例
+    /// ```
+    /// # use crab_vault_utils::ansi::{AnsiColor, ColorSupport};
+    /// let orange = AnsiColor::Rgb(255, 135, 0);
+    /// assert_eq!(orange.downgrade(ColorSupport::TrueColor), Some(orange));
+    /// assert_eq!(orange.downgrade(ColorSupport::Fixed), Some(AnsiColor::Fixed(208)));
+    /// assert_eq!(orange.downgrade(ColorSupport::Basic), Some(AnsiColor::Yellow));
+    /// assert_eq!(orange.downgrade(ColorSupport::None), None);
+    ///
+    /// // 基本颜色在任何支持颜色的终端上都不变
+    /// assert_eq!(AnsiColor::Red.downgrade(ColorSupport::Basic), Some(AnsiColor::Red));
+    /// ```
+    pub fn downgrade(self, support: ColorSupport) -> Option<Self> {
+        match (support, self) {
+            (ColorSupport::None, _) => None,
+            (ColorSupport::Fixed, AnsiColor::Rgb(r, g, b)) => {
+                Some(AnsiColor::Fixed(rgb_to_fixed(r, g, b)))
+            }
+            (ColorSupport::Basic, AnsiColor::Rgb(r, g, b)) => Some(rgb_to_basic(r, g, b)),
+            (ColorSupport::Basic, AnsiColor::Fixed(n)) => Some(match n {
+                0..16 => Self::from_basic_index(n),
+                _ => {
+                    let (r, g, b) = fixed_to_rgb(n);
+                    rgb_to_basic(r, g, b)
+                }
+            }),
+            (_, color) => Some(color),
+        }
     }
 }
 
+/// 16 种基本颜色的名称，按照 [`AnsiColor::basic_index`] 的顺序
+const BASIC_NAMES: [&str; 16] = [
+    "black",
+    "red",
+    "green",
+    "yellow",
+    "blue",
+    "magenta",
+    "cyan",
+    "white",
+    "bright-black",
+    "bright-red",
+    "bright-green",
+    "bright-yellow",
+    "bright-blue",
+    "bright-magenta",
+    "bright-cyan",
+    "bright-white",
+];
+
+impl FromStr for AnsiColor {
+    type Err = ParseColorError;
+
+    /// ## 从配置文件中的写法解析颜色
+    ///
+    /// - 基本颜色的名称，例如 `red`、`bright-red`，忽略大小写，`-` 也可以写成 `_`
+    /// - 0 到 255 的整数表示 256 色
+    /// - `#rrggbb` 表示真彩色
+    ///
+    /// # 示例
+    /// ```
+    /// # use crab_vault_utils::ansi::AnsiColor;
+    /// assert_eq!("Bright_Red".parse(), Ok(AnsiColor::BrightRed));
+    /// assert_eq!("208".parse(), Ok(AnsiColor::Fixed(208)));
+    /// assert_eq!("#ff8800".parse(), Ok(AnsiColor::Rgb(255, 136, 0)));
+    /// assert!("orange".parse::<AnsiColor>().is_err());
+    ///
+    /// // 与 Display 互为逆操作
+    /// assert_eq!(AnsiColor::Rgb(255, 136, 0).to_string(), "#ff8800");
+    /// ```
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || ParseColorError {
+            input: s.to_string(),
+        };
+        let s = s.trim();
+
+        if let Some(hex) = s.strip_prefix('#') {
+            let channel = |i: usize| {
+                hex.get(i..i + 2)
+                    .and_then(|v| u8::from_str_radix(v, 16).ok())
+                    .ok_or_else(err)
+            };
+            return match hex.len() {
+                6 => Ok(AnsiColor::Rgb(channel(0)?, channel(2)?, channel(4)?)),
+                _ => Err(err()),
+            };
+        }
+
+        if let Ok(n) = s.parse::<u8>() {
+            return Ok(AnsiColor::Fixed(n));
+        }
+
+        let name = s.to_ascii_lowercase().replace('_', "-");
+        BASIC_NAMES
+            .iter()
+            .position(|v| *v == name)
+            .map(|index| Self::from_basic_index(index as u8))
+            .ok_or_else(err)
+    }
+}
+
+impl Display for AnsiColor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            AnsiColor::Fixed(n) => write!(f, "{n}"),
+            AnsiColor::Rgb(r, g, b) => write!(f, "#{r:02x}{g:02x}{b:02x}"),
+            color => f.write_str(BASIC_NAMES[color.basic_index().unwrap_or_default() as usize]),
+        }
+    }
+}
+
+impl Display for ParseColorError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "unknown color `{}`, expected a color name such as `bright-red`, a number in 0..=255 or `#rrggbb`",
+            self.input
+        )
+    }
+}
+
+impl std::error::Error for ParseColorError {}
+
+impl Display for ColorCode {
+    /// # 示例
+    /// ```
+    /// # use crab_vault_utils::ansi::AnsiColor;
+    /// assert_eq!(AnsiColor::Red.into_fore().to_string(), "31");
+    /// assert_eq!(AnsiColor::BrightRed.into_back().to_string(), "101");
+    /// assert_eq!(AnsiColor::Fixed(208).into_fore().to_string(), "38;5;208");
+    /// assert_eq!(AnsiColor::Rgb(1, 2, 3).into_back().to_string(), "48;2;1;2;3");
+    /// ```
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.color {
+            AnsiColor::Fixed(n) => write!(f, "{};5;{n}", self.base + 8),
+            AnsiColor::Rgb(r, g, b) => write!(f, "{};2;{r};{g};{b}", self.base + 8),
+            // 亮色从 90/100 开始
+            color => match color.basic_index().unwrap_or_default() {
+                index @ 0..8 => write!(f, "{}", self.base + index),
+                index => write!(f, "{}", self.base + 60 + index - 8),
+            },
+        }
+    }
+}
+
+impl ColorSupport {
+    /// ## 根据环境变量判断终端能够显示的颜色
+    ///
+    /// - 设置了 `NO_COLOR`，或者输出不是终端，或者 `TERM=dumb` 时不输出颜色
+    /// - `COLORTERM` 为 `truecolor` 或者 `24bit` 时支持真彩色
+    /// - `TERM` 中含有 `256color` 时支持 256 色
+    /// - 其他情况只使用基本颜色
+    pub fn detect(is_terminal: bool) -> Self {
+        let var = |name| std::env::var(name).ok().filter(|v| !v.is_empty());
+        let term = var("TERM");
+
+        if var("NO_COLOR").is_some() || !is_terminal || term.as_deref() == Some("dumb") {
+            return ColorSupport::None;
+        }
+        if var("COLORTERM").is_some_and(|v| v == "truecolor" || v == "24bit") {
+            return ColorSupport::TrueColor;
+        }
+        match term {
+            Some(term) if term.contains("256color") => ColorSupport::Fixed,
+            _ => ColorSupport::Basic,
+        }
+    }
+}
+
+fn rgb_to_fixed(r: u8, g: u8, b: u8) -> u8 {
+    // 灰色使用 232 到 255 的灰阶，比色块中的灰色更细
+    if r == g && g == b {
+        return match r {
+            0..8 => 16,
+            249.. => 231,
+            _ => 232 + ((r as u16 - 8) * 24 / 247) as u8,
+        };
+    }
+
+    let level = |v: u8| {
+        (0..CUBE_LEVELS.len())
+            .min_by_key(|&i| CUBE_LEVELS[i].abs_diff(v))
+            .unwrap_or_default() as u8
+    };
+    16 + 36 * level(r) + 6 * level(g) + level(b)
+}
+
+fn fixed_to_rgb(n: u8) -> (u8, u8, u8) {
+    match n {
+        0..16 => BASIC_PALETTE[n as usize],
+        16..232 => {
+            let n = n - 16;
+            let level = |v: u8| CUBE_LEVELS[v as usize];
+            (level(n / 36), level(n / 6 % 6), level(n % 6))
+        }
+        _ => {
+            let gray = 8 + 10 * (n - 232);
+            (gray, gray, gray)
+        }
+    }
+}
+
+fn rgb_to_basic(r: u8, g: u8, b: u8) -> AnsiColor {
+    let distance = |&(pr, pg, pb): &(u8, u8, u8)| {
+        [(r, pr), (g, pg), (b, pb)]
+            .iter()
+            .map(|&(v, p)| (v.abs_diff(p) as u32).pow(2))
+            .sum::<u32>()
+    };
+    let index = (0..BASIC_PALETTE.len())
+        .min_by_key(|&i| distance(&BASIC_PALETTE[i]))
+        .unwrap_or_default();
+    AnsiColor::from_basic_index(index as u8)
+}
+
 impl FontStyle {
     #[inline]
     pub fn bold(mut self, enabled: bool) -> Self {
@@ -228,6 +563,18 @@ impl AnsiStyle {
         self.fore.is_none() && self.back.is_none()
     }
 
+    /// 把颜色转换为终端能够显示的，参见 [`AnsiColor::downgrade`]，不支持颜色时返回一个不带装饰的样式
+    pub fn downgrade(self, support: ColorSupport) -> Self {
+        match support {
+            ColorSupport::None => Self::new_vanilla(),
+            _ => Self {
+                fore: self.fore.and_then(|v| v.downgrade(support)),
+                back: self.back.and_then(|v| v.downgrade(support)),
+                font: self.font,
+            },
+        }
+    }
+
     #[inline]
     pub const fn decorate<'a>(self, content: &'a str) -> AnsiString<'a> {
         AnsiString {
@@ -0,0 +1,62 @@
+//! # [`AnsiColor`] 的序列化
+//!
+//! 颜色序列化为字符串，写法与 [`AnsiColor::from_str`](std::str::FromStr::from_str) 相同；
+//! 反序列化时也接受整数，所以配置文件中的 `fore = 208` 和 `fore = "208"` 是一样的。
+//!
+//! ```
+//! # use crab_vault_utils::ansi::AnsiColor;
+//! let color: AnsiColor = serde_json::from_str("\"bright-cyan\"").unwrap();
+//! assert_eq!(color, AnsiColor::BrightCyan);
+//!
+//! let color: AnsiColor = serde_json::from_str("208").unwrap();
+//! assert_eq!(serde_json::to_string(&color).unwrap(), "\"208\"");
+//!
+//! assert!(serde_json::from_str::<AnsiColor>("256").is_err());
+//! ```
+
+use std::fmt;
+
+use serde::{
+    Deserialize, Deserializer, Serialize, Serializer,
+    de::{Error, Visitor},
+};
+
+use super::AnsiColor;
+
+struct ColorVisitor;
+
+impl Serialize for AnsiColor {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for AnsiColor {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(ColorVisitor)
+    }
+}
+
+impl Visitor<'_> for ColorVisitor {
+    type Value = AnsiColor;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a color name, a number in 0..=255 or `#rrggbb`")
+    }
+
+    fn visit_str<E: Error>(self, v: &str) -> Result<Self::Value, E> {
+        v.parse().map_err(E::custom)
+    }
+
+    fn visit_u64<E: Error>(self, v: u64) -> Result<Self::Value, E> {
+        u8::try_from(v)
+            .map(AnsiColor::Fixed)
+            .map_err(|_| E::custom(format!("color index {v} is out of range 0..=255")))
+    }
+
+    fn visit_i64<E: Error>(self, v: i64) -> Result<Self::Value, E> {
+        u8::try_from(v)
+            .map(AnsiColor::Fixed)
+            .map_err(|_| E::custom(format!("color index {v} is out of range 0..=255")))
+    }
+}
@@ -0,0 +1,255 @@
+use std::{
+    borrow::Cow,
+    fmt::Display,
+    ops::{Add, AddAssign},
+};
+
+use super::{AnsiString, AnsiStyle, RESET};
+
+/// 截断时用来表示省略的字符
+pub const ELLIPSIS: char = '…';
+
+/// ## 拥有所有权的带样式文本
+///
+/// 由若干段各自带有样式的文本拼接而成，可以先组合好整行再输出，不受 [`AnsiString`] 借用的限制。
+/// 宽度按照终端中占据的列数计算，不包括转义序列，参见 [`display_width`]。
+///
+/// # 示例
+/// ```
+/// # use crab_vault_utils::ansi::{AnsiColor, AnsiStyle, AnsiText};
+/// let red = AnsiStyle::new().with_fore(AnsiColor::Red);
+/// let mut line = AnsiText::styled(red, "ERROR");
+/// line += ": ";
+/// line += AnsiText::plain("disk full");
+///
+/// assert_eq!(line.width(), 16);
+/// assert_eq!(line.plain_text(), "ERROR: disk full");
+/// assert_eq!(line.to_string(), "\x1B[;31mERROR\x1B[0m: disk full");
+/// ```
+#[derive(Clone, Default)]
+pub struct AnsiText {
+    segments: Vec<(AnsiStyle, String)>,
+}
+
+impl AnsiText {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 只有一段的文本
+    pub fn styled(style: AnsiStyle, text: impl Into<String>) -> Self {
+        let mut this = Self::new();
+        this.push(style, text);
+        this
+    }
+
+    /// 不带样式的文本
+    pub fn plain(text: impl Into<String>) -> Self {
+        Self::styled(AnsiStyle::new_vanilla(), text)
+    }
+
+    /// 在末尾追加一段文本，空的文本会被忽略
+    pub fn push(&mut self, style: AnsiStyle, text: impl Into<String>) {
+        let text = text.into();
+        if !text.is_empty() {
+            self.segments.push((style, text));
+        }
+    }
+
+    /// 终端中占据的列数
+    pub fn width(&self) -> usize {
+        self.segments.iter().map(|(_, v)| display_width(v)).sum()
+    }
+
+    /// 去掉所有样式之后的文本
+    pub fn plain_text(&self) -> String {
+        self.segments.iter().map(|(_, v)| v.as_str()).collect()
+    }
+
+    /// ## 截断到不超过 `width` 列
+    ///
+    /// 被截断时最后一列是 [`ELLIPSIS`]，使用被截断的那一段的样式
+    ///
+    /// # 示例
+    /// ```
+    /// # use crab_vault_utils::ansi::AnsiText;
+    /// let text = AnsiText::plain("request") + "_id";
+    /// assert_eq!(text.clone().truncate(10).plain_text(), "request_id");
+    /// assert_eq!(text.truncate(8).plain_text(), "request…");
+    /// ```
+    pub fn truncate(mut self, width: usize) -> Self {
+        if self.width() <= width {
+            return self;
+        }
+
+        let mut remaining = width.saturating_sub(1);
+        let mut kept = Vec::with_capacity(self.segments.len());
+        for (style, text) in self.segments.drain(..) {
+            let segment_width = display_width(&text);
+            if segment_width <= remaining {
+                remaining -= segment_width;
+                kept.push((style, text));
+                continue;
+            }
+
+            let mut cut = take_width(&text, remaining).to_string();
+            if width > 0 {
+                cut.push(ELLIPSIS);
+            }
+            kept.push((style, cut));
+            break;
+        }
+        self.segments = kept;
+        self
+    }
+
+    /// 在开头填充空格到至少 `width` 列，也就是右对齐
+    ///
+    /// # 示例
+    /// ```
+    /// # use crab_vault_utils::ansi::AnsiText;
+    /// // 中文字符占两列
+    /// assert_eq!(AnsiText::plain("对象").pad_start(6).plain_text(), "  对象");
+    /// ```
+    pub fn pad_start(mut self, width: usize) -> Self {
+        let padding = width.saturating_sub(self.width());
+        if padding > 0 {
+            self.segments
+                .insert(0, (AnsiStyle::new_vanilla(), " ".repeat(padding)));
+        }
+        self
+    }
+
+    /// 在末尾填充空格到至少 `width` 列，也就是左对齐
+    pub fn pad_end(mut self, width: usize) -> Self {
+        let padding = width.saturating_sub(self.width());
+        self.push(AnsiStyle::new_vanilla(), " ".repeat(padding));
+        self
+    }
+}
+
+impl Display for AnsiText {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (style, text) in &self.segments {
+            if style.is_vanilla() {
+                f.write_str(text)?;
+            } else {
+                write!(f, "{style}{text}{RESET}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl From<AnsiString<'_>> for AnsiText {
+    fn from(value: AnsiString<'_>) -> Self {
+        match value.is_vanilla {
+            true => Self::plain(value.content),
+            false => Self::styled(value.style, value.content),
+        }
+    }
+}
+
+impl From<&str> for AnsiText {
+    #[inline]
+    fn from(value: &str) -> Self {
+        Self::plain(value)
+    }
+}
+
+impl From<String> for AnsiText {
+    #[inline]
+    fn from(value: String) -> Self {
+        Self::plain(value)
+    }
+}
+
+impl<T: Into<AnsiText>> AddAssign<T> for AnsiText {
+    fn add_assign(&mut self, rhs: T) {
+        self.segments.extend(rhs.into().segments);
+    }
+}
+
+impl<T: Into<AnsiText>> Add<T> for AnsiText {
+    type Output = Self;
+
+    fn add(mut self, rhs: T) -> Self::Output {
+        self += rhs;
+        self
+    }
+}
+
+/// ## 一个字符在终端中占据的列数
+///
+/// 东亚文字、全角符号和大部分 emoji 占两列，组合用的附加符号和零宽字符不占列，控制字符按照不占列处理，
+/// 其他字符占一列。这是常见终端行为的近似，不是完整的 Unicode 宽度表
+pub fn char_width(c: char) -> usize {
+    match c as u32 {
+        0..0x20 | 0x7F..0xA0 => 0,
+        // 组合用附加符号、零宽字符、变体选择符
+        0x0300..0x0370 | 0x200B..0x2010 | 0xFE00..0xFE10 | 0xFE20..0xFE30 => 0,
+        // 谚文字母、中日韩文字、全角符号、emoji
+        0x1100..0x1160
+        | 0x2E80..0x303F
+        | 0x3041..0x4DC0
+        | 0x4E00..0xA4D0
+        | 0xAC00..0xD7A4
+        | 0xF900..0xFB00
+        | 0xFE30..0xFE50
+        | 0xFF00..0xFF61
+        | 0xFFE0..0xFFE7
+        | 0x1F300..0x1F650
+        | 0x1F900..0x1FA00
+        | 0x20000..0x3FFFE => 2,
+        _ => 1,
+    }
+}
+
+/// 一段文本在终端中占据的列数，参见 [`char_width`]
+///
+/// # 示例
+/// ```
+/// # use crab_vault_utils::ansi::display_width;
+/// assert_eq!(display_width("bucket"), 6);
+/// assert_eq!(display_width("桶"), 2);
+/// ```
+pub fn display_width(text: &str) -> usize {
+    text.chars().map(char_width).sum()
+}
+
+/// ## 截断到不超过 `width` 列
+///
+/// 被截断时最后一列是 [`ELLIPSIS`]，没有被截断时不复制
+///
+/// # 示例
+/// ```
+/// # use crab_vault_utils::ansi::truncate_to_width;
+/// assert_eq!(truncate_to_width("thread-1", 8), "thread-1");
+/// assert_eq!(truncate_to_width("tokio-runtime-worker", 8), "tokio-r…");
+/// // 宽字符不会被切开，放不下时宁可少一列
+/// assert_eq!(truncate_to_width("对象存储", 6), "对象…");
+/// ```
+pub fn truncate_to_width(text: &str, width: usize) -> Cow<'_, str> {
+    if display_width(text) <= width {
+        return Cow::Borrowed(text);
+    }
+
+    let mut cut = take_width(text, width.saturating_sub(1)).to_string();
+    if width > 0 {
+        cut.push(ELLIPSIS);
+    }
+    Cow::Owned(cut)
+}
+
+/// `text` 开头不超过 `width` 列的部分
+fn take_width(text: &str, width: usize) -> &str {
+    let mut used = 0;
+    for (index, c) in text.char_indices() {
+        used += char_width(c);
+        if used > width {
+            return &text[..index];
+        }
+    }
+    text
+}
@@ -9,6 +9,7 @@
 //!
 //! - **泛型实现**: 可以使用任何常见的无符号整数作为底层存储。
 //! - **完整的位运算**: 支持 `&`, `|`, `^`, `!` 等所有标准位运算符。
+//! - **更多的位**: [`BitmapArray`] 由多个整数组成，可以超过 128 位，API 与 [`Bitmap`] 相同。
 //! - **迭代器**: 提供 [`PositiveIter`] 和 [`NegativeIter`]，分别用于遍历值为 1 和 0 的位的索引。
 //! - **丰富的 API**: 包含 [`set`](Bitmap::set), [`get`](Bitmap::get), [`count_ones`](Bitmap::count_ones), [`any`](Bitmap::any), [`all`](Bitmap::all), [`none`](Bitmap::none) 等常用方法。
 //!
@@ -49,6 +50,14 @@
 use std::fmt::Debug;
 use std::ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign, Not, Shl, Shr};
 
+mod array;
+#[cfg(feature = "serde")]
+mod serialize;
+
+pub use array::{ArrayIter, BitmapArray};
+#[cfg(feature = "serde")]
+pub use serialize::indices;
+
 pub trait BitStorage:
     Copy
     + Default
@@ -248,6 +257,24 @@ impl<T: BitStorage> From<T> for Bitmap<T> {
     }
 }
 
+impl<T: BitStorage> FromIterator<usize> for Bitmap<T> {
+    /// 把给出的索引全部设置为 1。
+    ///
+    /// # 示例
+    /// ```
+    /// # use crab_vault_utils::bitmap::{Bitmap, BitStorage};
+    /// let bitmap: Bitmap<u8> = [0, 3].into_iter().collect();
+    /// assert_eq!(bitmap, Bitmap::<u8>::from(0b__0000_1001));
+    /// ```
+    fn from_iter<I: IntoIterator<Item = usize>>(iter: I) -> Self {
+        let mut bitmap = Self::new();
+        for idx in iter {
+            bitmap.set(idx, true);
+        }
+        bitmap
+    }
+}
+
 impl<T: BitStorage> Bitmap<T> {
     /// 创建一个所有位都为 0 的空位图。
     ///
@@ -290,6 +317,36 @@ impl<T: BitStorage> Bitmap<T> {
         Self::new_empty()
     }
 
+    /// 把给出的索引设置为 1，其余为 0。
+    ///
+    /// 与 [`FromIterator`] 不同，有索引超出位图的范围（`idx >= T::BITS`）时返回 `None` 而不是 panic，
+    /// 适合用于来自外部的数据。
+    ///
+    /// # 示例
+    /// ```
+    /// # use crab_vault_utils::bitmap::{Bitmap, BitStorage};
+    /// let bitmap = Bitmap::<u8>::from_indices([1, 4]).unwrap();
+    /// assert_eq!(bitmap.to_vec(), vec![1, 4]);
+    ///
+    /// assert_eq!(Bitmap::<u8>::from_indices([8]), None);
+    /// ```
+    pub fn from_indices(indices: impl IntoIterator<Item = usize>) -> Option<Self> {
+        let mut bitmap = Self::new();
+        for idx in indices {
+            if idx >= T::BITS {
+                return None;
+            }
+            bitmap.set(idx, true);
+        }
+        Some(bitmap)
+    }
+
+    /// 所有值为 1 的位的索引，从小到大排列，与 [`from_indices`](Self::from_indices) 互逆。
+    #[inline]
+    pub fn to_vec(&self) -> Vec<usize> {
+        self.iter_ones().collect()
+    }
+
     /// 返回一个迭代器，用于遍历所有值为 1 的位的索引。
     ///
     /// # 示例
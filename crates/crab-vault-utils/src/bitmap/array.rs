@@ -0,0 +1,370 @@
+use std::ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign, Not};
+
+use super::BitStorage;
+
+/// 由 `N` 个 `T` 组成的位图，总共 `N * T::BITS` 位，用于 [`Bitmap`](super::Bitmap) 放不下的掩码。
+///
+/// 第 `i` 位保存在第 `i / T::BITS` 个字的第 `i % T::BITS` 位，API 与 [`Bitmap`](super::Bitmap) 相同。
+///
+/// # 示例
+/// ```
+/// # use crab_vault_utils::bitmap::BitmapArray;
+/// // 4 个 u64，总共 256 位
+/// let mut bitmap = BitmapArray::<4, u64>::new();
+/// bitmap.set(3, true);
+/// bitmap.set(200, true);
+///
+/// assert!(bitmap.get(200));
+/// assert!(!bitmap.get(199));
+///
+/// let positions: Vec<usize> = bitmap.iter_ones().collect();
+/// assert_eq!(positions, vec![3, 200]);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BitmapArray<const N: usize, T: BitStorage> {
+    words: [T; N],
+}
+
+/// 一个迭代器，按照从小到大的顺序遍历 [`BitmapArray`] 中所有值为 1 的位索引。
+///
+/// [`iter_zeros`](BitmapArray::iter_zeros) 返回的是取反之后的位图上的这个迭代器。
+pub struct ArrayIter<const N: usize, T: BitStorage> {
+    words: [T; N],
+    word: usize,
+}
+
+impl<const N: usize, T: BitStorage> Iterator for ArrayIter<N, T> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.word < N {
+            let word = &mut self.words[self.word];
+            if *word != T::from(0) {
+                let pos = word.trailing_zeros() as usize;
+                // 清除刚刚找到的位，以便下一次迭代
+                *word &= !(T::from(1) << pos);
+                return Some(self.word * T::BITS + pos);
+            }
+            self.word += 1;
+        }
+        None
+    }
+}
+
+impl<const N: usize, T: BitStorage> Default for BitmapArray<N, T> {
+    #[inline]
+    fn default() -> Self {
+        Self::new_empty()
+    }
+}
+
+impl<const N: usize, T: BitStorage> From<[T; N]> for BitmapArray<N, T> {
+    #[inline]
+    fn from(words: [T; N]) -> Self {
+        Self { words }
+    }
+}
+
+impl<const N: usize, T: BitStorage> IntoIterator for &BitmapArray<N, T> {
+    type Item = usize;
+    type IntoIter = ArrayIter<N, T>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_ones()
+    }
+}
+
+impl<const N: usize, T: BitStorage> IntoIterator for BitmapArray<N, T> {
+    type Item = usize;
+    type IntoIter = ArrayIter<N, T>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_ones()
+    }
+}
+
+impl<const N: usize, T: BitStorage> FromIterator<usize> for BitmapArray<N, T> {
+    /// 把给出的索引全部设置为 1。
+    ///
+    /// # 示例
+    /// ```
+    /// # use crab_vault_utils::bitmap::BitmapArray;
+    /// let bitmap: BitmapArray<2, u8> = [1, 9, 15].into_iter().collect();
+    /// assert_eq!(bitmap.count_ones(), 3);
+    /// assert_eq!(bitmap.words(), &[0b__0000_0010, 0b__1000_0010]);
+    /// ```
+    fn from_iter<I: IntoIterator<Item = usize>>(iter: I) -> Self {
+        let mut bitmap = Self::new();
+        for idx in iter {
+            bitmap.set(idx, true);
+        }
+        bitmap
+    }
+}
+
+impl<const N: usize, T: BitStorage> BitmapArray<N, T> {
+    /// 位图的总位数
+    pub const BITS: usize = N * T::BITS;
+
+    /// 创建一个所有位都为 0 的空位图。
+    #[inline]
+    pub fn new_empty() -> Self {
+        Self {
+            words: [T::from(0); N],
+        }
+    }
+
+    /// 创建一个所有位都为 1 的全满位图。
+    ///
+    /// # 示例
+    /// ```
+    /// # use crab_vault_utils::bitmap::BitmapArray;
+    /// let bitmap = BitmapArray::<3, u8>::new_full();
+    /// assert!(bitmap.all());
+    /// assert_eq!(bitmap.count_ones(), 24);
+    /// ```
+    #[inline]
+    pub fn new_full() -> Self {
+        Self {
+            words: [!T::from(0); N],
+        }
+    }
+
+    /// 创建一个空的位图，是 `new_empty` 的别名。
+    #[inline]
+    pub fn new() -> Self {
+        Self::new_empty()
+    }
+
+    /// 底层的字，第 0 个字保存最低的 `T::BITS` 位
+    #[inline]
+    pub const fn words(&self) -> &[T; N] {
+        &self.words
+    }
+
+    /// 返回一个迭代器，用于遍历所有值为 1 的位的索引。
+    #[inline]
+    pub const fn iter_ones(&self) -> ArrayIter<N, T> {
+        ArrayIter {
+            words: self.words,
+            word: 0,
+        }
+    }
+
+    /// 返回一个迭代器，用于遍历所有值为 0 的位的索引。
+    ///
+    /// # 示例
+    /// ```
+    /// # use crab_vault_utils::bitmap::BitmapArray;
+    /// let mut bitmap = BitmapArray::<2, u8>::new_full();
+    /// bitmap.set(2, false);
+    /// bitmap.set(12, false);
+    /// let zeros: Vec<usize> = bitmap.iter_zeros().collect();
+    /// assert_eq!(zeros, vec![2, 12]);
+    /// ```
+    #[inline]
+    pub fn iter_zeros(&self) -> ArrayIter<N, T> {
+        (!*self).iter_ones()
+    }
+
+    /// 设置指定索引的位。
+    ///
+    /// `true` 表示设置为 1，`false` 表示设置为 0。
+    ///
+    /// # Panics
+    ///
+    /// 如果 `idx` 超出位图的范围（`idx >= N * T::BITS`），会触发 panic。
+    #[inline]
+    pub fn set(&mut self, idx: usize, set: bool) {
+        let (word, mask) = Self::locate(idx);
+        if set {
+            self.words[word] |= mask;
+        } else {
+            self.words[word] &= !mask;
+        }
+    }
+
+    /// 获取指定索引的位的值。
+    ///
+    /// # Panics
+    ///
+    /// 如果 `idx` 超出位图的范围（`idx >= N * T::BITS`），会触发 panic。
+    #[inline]
+    pub fn get(&self, idx: usize) -> bool {
+        let (word, mask) = Self::locate(idx);
+        (self.words[word] & mask) != T::from(0)
+    }
+
+    /// 检查指定索引的位是否为 1。`get` 的别名。
+    #[inline]
+    pub fn is_one_on(&self, idx: usize) -> bool {
+        self.get(idx)
+    }
+
+    /// 检查指定索引的位是否为 0。
+    #[inline]
+    pub fn is_zero_on(&self, idx: usize) -> bool {
+        !self.get(idx)
+    }
+
+    /// 将两个位图进行合并（并集），等同于 `|` 按位或操作。
+    #[inline]
+    pub fn merge(self, rhs: Self) -> Self {
+        self | rhs
+    }
+
+    /// 计算值为 1 的位的数量。
+    #[inline]
+    pub fn count_ones(&self) -> u32 {
+        self.words.iter().map(|v| v.count_ones()).sum()
+    }
+
+    /// 计算值为 0 的位的数量。
+    #[inline]
+    pub fn count_zeros(&self) -> u32 {
+        Self::BITS as u32 - self.count_ones()
+    }
+
+    /// 检查位图中是否至少有一个位是 1。
+    #[inline]
+    pub fn any(&self) -> bool {
+        self.words.iter().any(|v| *v != T::from(0))
+    }
+
+    /// 检查位图中是否所有位都是 1。
+    #[inline]
+    pub fn all(&self) -> bool {
+        self.words.iter().all(|v| *v == !T::from(0))
+    }
+
+    /// 检查位图中是否所有位都是 0。
+    #[inline]
+    pub fn none(&self) -> bool {
+        !self.any()
+    }
+
+    /// 查找第一个值为 1 的位的索引。
+    ///
+    /// 如果所有位都为 0，则返回 `None`。
+    ///
+    /// # 示例
+    /// ```
+    /// # use crab_vault_utils::bitmap::BitmapArray;
+    /// let mut bitmap = BitmapArray::<2, u64>::new();
+    /// assert_eq!(bitmap.first_one(), None);
+    /// bitmap.set(70, true);
+    /// assert_eq!(bitmap.first_one(), Some(70));
+    /// ```
+    #[inline]
+    pub fn first_one(&self) -> Option<usize> {
+        self.iter_ones().next()
+    }
+
+    /// 第 `idx` 位所在的字和它在字中的掩码
+    #[inline]
+    fn locate(idx: usize) -> (usize, T) {
+        assert!(idx < Self::BITS, "Index out of bounds");
+        (idx / T::BITS, T::from(1) << (idx % T::BITS))
+    }
+
+    #[inline]
+    fn zip_with(mut self, rhs: Self, op: impl Fn(&mut T, T)) -> Self {
+        for (lhs, rhs) in self.words.iter_mut().zip(rhs.words) {
+            op(lhs, rhs);
+        }
+        self
+    }
+}
+
+impl<const N: usize, T: BitStorage> BitAnd for BitmapArray<N, T> {
+    type Output = Self;
+    /// 逐字按位与（&）。
+    ///
+    /// # 示例
+    /// ```
+    /// # use crab_vault_utils::bitmap::BitmapArray;
+    /// let b1 = BitmapArray::<2, u8>::from([0b__0000_1101, 0b__1000_0001]);
+    /// let b2 = BitmapArray::<2, u8>::from([0b__0000_1011, 0b__1000_0000]);
+    /// let expected = BitmapArray::<2, u8>::from([0b__0000_1001, 0b__1000_0000]);
+    /// assert_eq!(b1 & b2, expected);
+    /// ```
+    fn bitand(self, rhs: Self) -> Self::Output {
+        self.zip_with(rhs, |lhs, rhs| *lhs &= rhs)
+    }
+}
+
+impl<const N: usize, T: BitStorage> BitAndAssign for BitmapArray<N, T> {
+    /// 逐字按位与后赋值（&=）。
+    fn bitand_assign(&mut self, rhs: Self) {
+        *self = *self & rhs
+    }
+}
+
+impl<const N: usize, T: BitStorage> BitOr for BitmapArray<N, T> {
+    type Output = Self;
+    /// 逐字按位或（|）。
+    ///
+    /// # 示例
+    /// ```
+    /// # use crab_vault_utils::bitmap::BitmapArray;
+    /// let b1 = BitmapArray::<2, u8>::from([0b__0000_0001, 0b__0000_0000]);
+    /// let b2 = BitmapArray::<2, u8>::from([0b__0000_0000, 0b__1000_0000]);
+    /// let merged: Vec<usize> = (b1 | b2).iter_ones().collect();
+    /// assert_eq!(merged, vec![0, 15]);
+    /// ```
+    fn bitor(self, rhs: Self) -> Self::Output {
+        self.zip_with(rhs, |lhs, rhs| *lhs |= rhs)
+    }
+}
+
+impl<const N: usize, T: BitStorage> BitOrAssign for BitmapArray<N, T> {
+    /// 逐字按位或后赋值（|=）。
+    fn bitor_assign(&mut self, rhs: Self) {
+        *self = *self | rhs
+    }
+}
+
+impl<const N: usize, T: BitStorage> BitXor for BitmapArray<N, T> {
+    type Output = Self;
+    /// 逐字按位异或（^）。
+    ///
+    /// # 示例
+    /// ```
+    /// # use crab_vault_utils::bitmap::BitmapArray;
+    /// let b1 = BitmapArray::<2, u8>::from([0b__0000_1101, 0b__1111_0000]);
+    /// let b2 = BitmapArray::<2, u8>::from([0b__0000_1011, 0b__1111_0000]);
+    /// let expected = BitmapArray::<2, u8>::from([0b__0000_0110, 0b__0000_0000]);
+    /// assert_eq!(b1 ^ b2, expected);
+    /// ```
+    fn bitxor(self, rhs: Self) -> Self::Output {
+        self.zip_with(rhs, |lhs, rhs| *lhs ^= rhs)
+    }
+}
+
+impl<const N: usize, T: BitStorage> BitXorAssign for BitmapArray<N, T> {
+    /// 逐字按位异或后赋值（^=）。
+    fn bitxor_assign(&mut self, rhs: Self) {
+        *self = *self ^ rhs
+    }
+}
+
+impl<const N: usize, T: BitStorage> Not for BitmapArray<N, T> {
+    type Output = Self;
+    /// 逐字按位取反（!）。
+    ///
+    /// # 示例
+    /// ```
+    /// # use crab_vault_utils::bitmap::BitmapArray;
+    /// let b = BitmapArray::<2, u8>::from([0b__1111_0000, 0b__0000_0000]);
+    /// let expected = BitmapArray::<2, u8>::from([0b__0000_1111, 0b__1111_1111]);
+    /// assert_eq!(!b, expected);
+    /// ```
+    fn not(mut self) -> Self::Output {
+        for word in &mut self.words {
+            *word = !*word;
+        }
+        self
+    }
+}
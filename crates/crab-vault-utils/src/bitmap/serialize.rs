@@ -0,0 +1,123 @@
+//! # [`Bitmap`] 的序列化
+//!
+//! 默认序列化为底层的整数，反序列化时同时接受整数和值为 1 的位的索引列表，所以配置文件中可以写
+//! `mask = 5`，也可以写 `mask = [0, 2]`。需要始终输出索引列表时使用 [`indices`]。
+//!
+//! ```
+//! # use crab_vault_utils::bitmap::Bitmap;
+//! let mask: Bitmap<u128> = serde_json::from_str("5").unwrap();
+//! assert_eq!(serde_json::to_string(&mask).unwrap(), "5");
+//!
+//! let same: Bitmap<u128> = serde_json::from_str("[0, 2]").unwrap();
+//! assert_eq!(mask, same);
+//!
+//! // 超出位图范围的索引被拒绝
+//! assert!(serde_json::from_str::<Bitmap<u8>>("[8]").is_err());
+//! ```
+
+use std::{fmt, marker::PhantomData};
+
+use serde::{
+    Deserialize, Deserializer, Serialize, Serializer,
+    de::{Error, IntoDeserializer, SeqAccess, Visitor},
+};
+
+use super::{BitStorage, Bitmap};
+
+/// 接受整数或者索引列表，整数交给 `T` 自己的反序列化检查范围
+struct BitmapVisitor<T>(PhantomData<T>);
+
+impl<T: BitStorage + Serialize> Serialize for Bitmap<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.inner.serialize(serializer)
+    }
+}
+
+impl<'de, T: BitStorage + Deserialize<'de>> Deserialize<'de> for Bitmap<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(BitmapVisitor(PhantomData))
+    }
+}
+
+impl<'de, T: BitStorage + Deserialize<'de>> Visitor<'de> for BitmapVisitor<T> {
+    type Value = Bitmap<T>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            formatter,
+            "an integer or a list of bit indices below {}",
+            T::BITS
+        )
+    }
+
+    fn visit_u64<E: Error>(self, v: u64) -> Result<Self::Value, E> {
+        T::deserialize(v.into_deserializer()).map(Bitmap::from)
+    }
+
+    fn visit_i64<E: Error>(self, v: i64) -> Result<Self::Value, E> {
+        T::deserialize(v.into_deserializer()).map(Bitmap::from)
+    }
+
+    fn visit_u128<E: Error>(self, v: u128) -> Result<Self::Value, E> {
+        T::deserialize(v.into_deserializer()).map(Bitmap::from)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut bitmap = Bitmap::new();
+        while let Some(idx) = seq.next_element::<usize>()? {
+            if idx >= T::BITS {
+                return Err(A::Error::custom(format!(
+                    "bit index {idx} is out of range, the bitmap has {} bits",
+                    T::BITS
+                )));
+            }
+            bitmap.set(idx, true);
+        }
+        Ok(bitmap)
+    }
+}
+
+/// ## 以索引列表的形式序列化
+///
+/// 用于 `#[serde(with = "crab_vault_utils::bitmap::indices")]`，输出值为 1 的位的索引，
+/// 比整数更便于阅读，也不受某些格式对整数大小的限制（例如 TOML 不能表示超过 `i64::MAX` 的整数）。
+/// 反序列化时与 [`Bitmap`] 本身一样接受两种形式。
+///
+/// # 示例
+/// ```
+/// # use crab_vault_utils::bitmap::Bitmap;
+/// #[derive(serde::Serialize, serde::Deserialize)]
+/// struct Features {
+///     #[serde(with = "crab_vault_utils::bitmap::indices")]
+///     enabled: Bitmap<u128>,
+/// }
+///
+/// let features = Features {
+///     enabled: Bitmap::from_indices([1, 100]).unwrap(),
+/// };
+/// let json = serde_json::to_string(&features).unwrap();
+/// assert_eq!(json, r#"{"enabled":[1,100]}"#);
+///
+/// let features: Features = serde_json::from_str(&json).unwrap();
+/// assert!(features.enabled.get(100));
+/// ```
+pub mod indices {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use super::{BitStorage, Bitmap};
+
+    pub fn serialize<T: BitStorage, S: Serializer>(
+        bitmap: &Bitmap<T>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(bitmap.iter_ones())
+    }
+
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<Bitmap<T>, D::Error>
+    where
+        T: BitStorage + Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        Bitmap::deserialize(deserializer)
+    }
+}
@@ -0,0 +1,138 @@
+//! # 边写边计算摘要
+//!
+//! [`HashingWriter`] 包裹一个 writer，写入的每一块数据同时交给摘要算法，写完之后不需要再遍历一次数据。
+//! 它还记录了在摘要上花费的时间，便于和 IO 的耗时区分开。
+//!
+//! 摘要算法只需要实现 [`digest::Update`]，`sha2`、`md-5` 中的算法都可以直接使用。
+//! 启用 `futures` feature 时，它同样是一个 [`AsyncWrite`](futures::AsyncWrite)。
+//!
+//! ## 示例
+//!
+//! ```
+//! # use std::io::Write;
+//! # use crab_vault_utils::hashing::HashingWriter;
+//! use sha2::{Digest, Sha256};
+//!
+//! let mut writer = HashingWriter::new(Vec::new(), Sha256::new());
+//! writer.write_all(b"hello ").unwrap();
+//! writer.write_all(b"world").unwrap();
+//! assert_eq!(writer.written(), 11);
+//!
+//! let (data, hasher) = writer.into_parts();
+//! assert_eq!(data, b"hello world");
+//! assert_eq!(hasher.finalize(), Sha256::digest(b"hello world"));
+//! ```
+
+use std::{
+    io::{self, Write},
+    time::{Duration, Instant},
+};
+
+use digest::Update;
+
+/// 写入 `W` 的同时用 `H` 计算摘要
+///
+/// 只有被 `W` 接受的那部分数据会计入摘要，所以部分写入之后摘要仍然与实际写入的数据一致
+pub struct HashingWriter<W, H> {
+    inner: W,
+    hasher: H,
+    written: u64,
+    cost: Duration,
+}
+
+impl<W, H: Update> HashingWriter<W, H> {
+    pub fn new(inner: W, hasher: H) -> Self {
+        Self {
+            inner,
+            hasher,
+            written: 0,
+            cost: Duration::ZERO,
+        }
+    }
+
+    /// 已经写入的字节数
+    #[inline]
+    pub fn written(&self) -> u64 {
+        self.written
+    }
+
+    /// 到目前为止在计算摘要上花费的时间，不包括写入 `W` 的时间
+    #[inline]
+    pub fn cost(&self) -> Duration {
+        self.cost
+    }
+
+    #[inline]
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// 直接通过它写入的数据不会计入摘要
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// 拆出 writer 和摘要算法，之后由调用者 finalize
+    pub fn into_parts(self) -> (W, H) {
+        (self.inner, self.hasher)
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        let start = Instant::now();
+        self.hasher.update(data);
+        self.cost += start.elapsed();
+        self.written += data.len() as u64;
+    }
+}
+
+impl<W: Write, H: Update> Write for HashingWriter<W, H> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.update(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(feature = "futures")]
+mod async_write {
+    use std::{
+        io,
+        pin::Pin,
+        task::{Context, Poll, ready},
+    };
+
+    use digest::Update;
+    use futures::AsyncWrite;
+
+    use super::HashingWriter;
+
+    impl<W, H> AsyncWrite for HashingWriter<W, H>
+    where
+        W: AsyncWrite + Unpin,
+        H: Update + Unpin,
+    {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            let this = self.get_mut();
+            let written = ready!(Pin::new(&mut this.inner).poll_write(cx, buf))?;
+            this.update(&buf[..written]);
+            Poll::Ready(Ok(written))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+        }
+
+        fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.get_mut().inner).poll_close(cx)
+        }
+    }
+}
@@ -1,2 +1,3 @@
 pub mod bitmap;
-pub mod ansi;
\ No newline at end of file
+pub mod ansi;
+pub mod hashing;
\ No newline at end of file
@@ -1,43 +1,68 @@
+use std::sync::Arc;
+
 use clap::error::ErrorKind;
 use serde::{Deserialize, Serialize};
 
 use crate::{
     app_config::{
+        access_log::{AccessLogConfig, StaticAccessLogConfig},
+        audit::{AuditConfig, StaticAuditConfig},
         auth::{AuthConfig, StaticAuthConfig},
         data::{DataConfig, StaticDataConfig},
+        encryption::{EncryptionConfig, StaticEncryptionConfig},
         logger::{LoggerConfig, StaticLoggerConfig},
         meta::{MetaConfig, StaticMetaConfig},
+        replication::{ReplicationConfig, StaticReplicationConfig},
         server::{ServerConfig, StaticServerConfig},
     },
     cli::run::RunArgs,
     error::fatal::{FatalError, FatalResult, MultiFatalError},
 };
 
+pub mod access_log;
+pub mod audit;
 pub mod auth;
 pub mod data;
+pub mod encryption;
+pub mod live;
 pub mod logger;
 pub mod meta;
+pub mod replication;
 pub mod server;
 pub mod util;
 
+/// 覆盖配置文件的环境变量的前缀，各级字段之间使用 `__` 分隔
+pub const ENV_PREFIX: &str = "CRAB_VAULT";
+
 #[derive(Deserialize, Serialize)]
 #[serde(deny_unknown_fields, default)]
 #[derive(Default, Clone)]
 pub struct StaticAppConfig {
+    pub access_log: StaticAccessLogConfig,
+    pub audit: StaticAuditConfig,
     pub auth: StaticAuthConfig,
     pub data: StaticDataConfig,
+    pub encryption: StaticEncryptionConfig,
     pub logger: StaticLoggerConfig,
     pub meta: StaticMetaConfig,
+    pub replication: StaticReplicationConfig,
     pub server: StaticServerConfig,
 }
 
 #[derive(Clone)]
 pub struct AppConfig {
+    pub access_log: AccessLogConfig,
+    pub audit: AuditConfig,
     pub auth: AuthConfig,
     pub data: DataConfig,
+    pub encryption: EncryptionConfig,
     pub logger: LoggerConfig,
     pub meta: MetaConfig,
+    pub replication: ReplicationConfig,
     pub server: ServerConfig,
+
+    /// 转换之前的配置，其中的密钥和凭证已经被隐去，参见 `GET /admin/config`
+    pub redacted: Arc<StaticAppConfig>,
 }
 
 /// [`ConfigItem`] 表示一个配置项，实现了这个 trait 的结构就是一个配置项
@@ -64,29 +89,42 @@ where
 
 impl StaticAppConfig {
     pub fn from_file(config_path: String) -> Self {
+        Self::try_from_file(&config_path).unwrap_or_else(|e| e.exit_now())
+    }
+
+    /// 覆盖配置文件的环境变量，例如 `CRAB_VAULT__SERVER__PORT=8080` 覆盖 `server.port`
+    ///
+    /// 命令行参数的优先级仍然更高，参见 [`merge_cli`](StaticAppConfig::merge_cli)
+    fn environment() -> config::Environment {
+        config::Environment::with_prefix(ENV_PREFIX)
+            .separator("__")
+            .try_parsing(true)
+    }
+
+    /// 与 [`from_file`](StaticAppConfig::from_file) 相同，但出错时返回错误而不是退出进程
+    pub fn try_from_file(config_path: &str) -> Result<Self, FatalError> {
         config::Config::builder()
             .add_source(
-                config::File::with_name(&config_path)
+                config::File::with_name(config_path)
                     .required(true)
                     .format(config::FileFormat::Toml),
             )
+            .add_source(Self::environment())
             .build()
-            .unwrap_or_else(|_| {
+            .map_err(|_| {
                 FatalError::new(
                     ErrorKind::Io,
                     format!("Cannot read configuration file from {config_path}"),
                     None,
                 )
-                .exit_now()
-            })
+            })?
             .try_deserialize()
-            .unwrap_or_else(|_| {
+            .map_err(|e| {
                 FatalError::new(
                     ErrorKind::Io,
                     format!("Cannot deserialize configuration from file {config_path}"),
-                    None,
+                    Some(format!("{e}, note `{ENV_PREFIX}__*` environment variables also apply")),
                 )
-                .exit_now()
             })
     }
 
@@ -127,39 +165,79 @@ impl StaticAppConfig {
 
         self
     }
+
+    /// 隐去所有的密钥、密码和凭证，可以安全地展示给运维人员
+    pub fn redacted(self) -> Self {
+        Self {
+            audit: self.audit.redacted(),
+            auth: self.auth.redacted(),
+            data: self.data.redacted(),
+            encryption: self.encryption.redacted(),
+            logger: self.logger.redacted(),
+            meta: self.meta.redacted(),
+            replication: self.replication.redacted(),
+            ..self
+        }
+    }
 }
 
 impl ConfigItem for StaticAppConfig {
     type RuntimeConfig = AppConfig;
 
     fn into_runtime(self) -> FatalResult<Self::RuntimeConfig> {
+        let redacted = Arc::new(self.clone().redacted());
         let StaticAppConfig {
+            access_log,
+            audit,
             auth,
             data,
+            encryption,
             logger,
             meta,
+            replication,
             server,
         } = self;
 
         let mut errors = MultiFatalError::new();
 
-        let (auth, data, logger, meta, server) = (
+        let (access_log, audit, auth, data, encryption, logger, meta, replication, server) = (
+            access_log.error_recorded(&mut errors),
+            audit.error_recorded(&mut errors),
             auth.error_recorded(&mut errors),
             data.error_recorded(&mut errors),
+            encryption.error_recorded(&mut errors),
             logger.error_recorded(&mut errors),
             meta.error_recorded(&mut errors),
+            replication.error_recorded(&mut errors),
             server.error_recorded(&mut errors),
         );
 
+        // 复制从事件日志中得知需要复制什么
+        if let (Some(meta), Some(replication)) = (&meta, &replication)
+            && !replication.targets.is_empty()
+            && !meta.events.enabled
+        {
+            errors.push(FatalError::new(
+                ErrorKind::InvalidValue,
+                "`replication.targets` requires `meta.events.enabled = true`".into(),
+                Some("while validating `replication`".into()),
+            ));
+        }
+
         if !errors.is_empty() {
             Err(errors)
         } else {
             Ok(AppConfig {
+                access_log: access_log.unwrap(),
+                audit: audit.unwrap(),
                 auth: auth.unwrap(),
                 data: data.unwrap(),
+                encryption: encryption.unwrap(),
                 logger: logger.unwrap(),
                 meta: meta.unwrap(),
+                replication: replication.unwrap(),
                 server: server.unwrap(),
+                redacted,
             })
         }
     }
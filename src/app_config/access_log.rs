@@ -0,0 +1,120 @@
+use std::{path::PathBuf, time::Duration};
+
+use clap::error::ErrorKind;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_config::ConfigItem,
+    error::fatal::{FatalError, FatalResult, MultiFatalError},
+};
+
+pub type AccessLogConfig = StaticAccessLogConfig;
+
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct StaticAccessLogConfig {
+    /// 访问日志写到哪里，默认不记录
+    pub sink: AccessLogSink,
+
+    /// `sink = "file"` 时日志文件所在的目录
+    pub path: Option<PathBuf>,
+
+    /// 单个日志文件的最大字节数，超过之后换一个新的文件
+    pub max_file_size: u64,
+
+    /// 最多保留多少个日志文件，更早的文件在换新文件时删除，不设置时全部保留
+    pub max_files: Option<usize>,
+
+    /// `sink = "internal_bucket"` 时两次写入之间的最长间隔，单位为秒
+    pub flush_interval: u64,
+
+    /// `sink = "internal_bucket"` 时积累了这么多条记录就立即写入
+    pub max_batch: usize,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum AccessLogSink {
+    /// 不记录访问日志
+    #[default]
+    Off,
+
+    /// 每条记录一行 json，输出到标准输出
+    Stdout,
+
+    /// 每条记录一行 json，写入 `path` 下的文件，按照 `max_file_size` 轮换
+    File,
+
+    /// 分批写入内部 bucket，与其他 object 一样保存在数据和元数据后端中，参见 `data.internal_bucket`
+    InternalBucket,
+}
+
+impl StaticAccessLogConfig {
+    const fn default_max_file_size() -> u64 {
+        64 * 1024 * 1024
+    }
+
+    const fn default_flush_interval() -> u64 {
+        60
+    }
+
+    const fn default_max_batch() -> usize {
+        1000
+    }
+
+    pub fn flush_interval(&self) -> Duration {
+        Duration::from_secs(self.flush_interval)
+    }
+}
+
+impl Default for StaticAccessLogConfig {
+    fn default() -> Self {
+        Self {
+            sink: AccessLogSink::Off,
+            path: None,
+            max_file_size: Self::default_max_file_size(),
+            max_files: None,
+            flush_interval: Self::default_flush_interval(),
+            max_batch: Self::default_max_batch(),
+        }
+    }
+}
+
+impl ConfigItem for StaticAccessLogConfig {
+    type RuntimeConfig = Self;
+
+    fn into_runtime(self) -> FatalResult<Self::RuntimeConfig> {
+        let mut errors = MultiFatalError::new();
+        let mut invalid = |reason: &str| {
+            errors.push(FatalError::new(
+                ErrorKind::InvalidValue,
+                reason.into(),
+                Some("while validating `access_log`".into()),
+            ))
+        };
+
+        match self.sink {
+            AccessLogSink::File if self.path.is_none() => {
+                invalid("`access_log.path` is required when `access_log.sink` is `file`")
+            }
+            AccessLogSink::File if self.max_file_size == 0 => {
+                invalid("`access_log.max_file_size` must be greater than 0")
+            }
+            AccessLogSink::File if self.max_files == Some(0) => {
+                invalid("`access_log.max_files` must be greater than 0")
+            }
+            AccessLogSink::InternalBucket if self.flush_interval == 0 => {
+                invalid("`access_log.flush_interval` must be greater than 0")
+            }
+            AccessLogSink::InternalBucket if self.max_batch == 0 => {
+                invalid("`access_log.max_batch` must be greater than 0")
+            }
+            _ => {}
+        }
+
+        match errors.is_empty() {
+            true => Ok(self),
+            false => Err(errors),
+        }
+    }
+}
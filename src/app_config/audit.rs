@@ -0,0 +1,58 @@
+use jsonwebtoken::{Algorithm, EncodingKey};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_config::{ConfigItem, util::Key},
+    error::fatal::{FatalResult, MultiFatalError},
+};
+
+#[derive(Deserialize, Serialize, Default, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct StaticAuditConfig {
+    /// 为导出的审计日志包签名的密钥，格式与 jwt 的编码密钥相同
+    pub signing_key: Option<Key>,
+}
+
+#[derive(Clone, Default)]
+pub struct AuditConfig {
+    /// 没有配置时无法导出审计日志包
+    pub signing_key: Option<SigningKey>,
+}
+
+#[derive(Clone)]
+pub struct SigningKey {
+    pub kid: String,
+    pub algorithm: Algorithm,
+    pub key: EncodingKey,
+}
+
+impl StaticAuditConfig {
+    pub fn redacted(mut self) -> Self {
+        self.signing_key = self.signing_key.map(Key::redacted);
+        self
+    }
+}
+
+impl ConfigItem for StaticAuditConfig {
+    type RuntimeConfig = AuditConfig;
+
+    fn into_runtime(self) -> FatalResult<Self::RuntimeConfig> {
+        let Some(key) = self.signing_key else {
+            return Ok(AuditConfig::default());
+        };
+
+        let (kid, algorithm, key) = key.build_as_encode_key().map_err(|e| {
+            let mut errors = MultiFatalError::new();
+            errors.push(e.when("while loading the audit signing key".into()));
+            errors
+        })?;
+
+        Ok(AuditConfig {
+            signing_key: Some(SigningKey {
+                kid,
+                algorithm,
+                key,
+            }),
+        })
+    }
+}
@@ -1,6 +1,7 @@
-use std::collections::HashSet;
+use std::{collections::HashSet, time::Duration};
 
-use crab_vault::auth::HttpMethod;
+use crab_vault::auth::{HttpMethod, pattern::GlobLimits};
+use clap::error::ErrorKind;
 use glob::Pattern;
 use serde::{Deserialize, Serialize};
 
@@ -29,6 +30,22 @@ pub struct StaticAuthConfig {
     /// jwt 鉴权相关设置
     #[serde(default)]
     pub jwt_decoder_config: StaticJwtDecoderConfig,
+
+    /// 路径规则以及 token 中的通配模式的复杂度限制
+    #[serde(default)]
+    pub glob_limits: StaticGlobLimits,
+
+    /// 吊销列表文件的路径，不设置时吊销列表只保存在内存中
+    #[serde(default)]
+    pub revocation_list: Option<String>,
+
+    /// 保存运行时修改的路径规则的文件，这个文件存在时以其中的规则代替 `path_rules`
+    #[serde(default)]
+    pub path_rules_file: Option<String>,
+
+    /// 运行时重新加载 jwt 解码密钥
+    #[serde(default)]
+    pub key_reload: StaticKeyReloadConfig,
 }
 
 #[derive(Clone)]
@@ -42,6 +59,40 @@ pub struct AuthConfig {
 
     /// jwt 鉴权相关设置
     pub jwt_decoder_config: JwtDecoderConfig,
+
+    pub glob_limits: GlobLimits,
+
+    pub revocation_list: Option<String>,
+
+    pub path_rules_file: Option<String>,
+
+    pub key_reload: KeyReloadConfig,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct StaticKeyReloadConfig {
+    /// 检查配置文件和密钥文件是否变化的间隔，单位为秒，`0` 表示不重新加载
+    pub interval: u64,
+
+    /// 从配置中移除的密钥在这段时间内仍然被接受，单位为秒，应当不短于令牌的有效期
+    pub grace_period: u64,
+}
+
+#[derive(Clone, Copy)]
+pub struct KeyReloadConfig {
+    pub interval: Option<Duration>,
+    pub grace_period: Duration,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct StaticGlobLimits {
+    /// 一个通配模式的最大字节数
+    pub max_len: usize,
+
+    /// 一个通配模式中最多的通配符个数
+    pub max_wildcards: usize,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -65,6 +116,12 @@ impl StaticAuthConfig {
     fn default_path_rules() -> Vec<StaticPathRule> {
         vec![StaticPathRule::default()]
     }
+
+    pub fn redacted(mut self) -> Self {
+        self.jwt_encoder_config = self.jwt_encoder_config.redacted();
+        self.jwt_decoder_config = self.jwt_decoder_config.redacted();
+        self
+    }
 }
 
 impl ConfigItem for StaticAuthConfig {
@@ -75,13 +132,18 @@ impl ConfigItem for StaticAuthConfig {
             path_rules,
             jwt_encoder_config,
             jwt_decoder_config,
+            glob_limits,
+            revocation_list,
+            path_rules_file,
+            key_reload,
         } = self;
 
         let mut errors = MultiFatalError::new();
+        let glob_limits = glob_limits.into_runtime()?;
 
         let path_rules = path_rules
             .into_iter()
-            .filter_map(|v| match v.into_runtime() {
+            .filter_map(|v| match v.into_runtime_with_limits(&glob_limits) {
                 Ok(v) => Some(v),
                 Err(mut e) => {
                     errors.append(&mut e);
@@ -96,11 +158,18 @@ impl ConfigItem for StaticAuthConfig {
         );
 
         match (jwt_encoder_config, jwt_decoder_config) {
-            (Ok(jwt_encoder_config), Ok(jwt_decoder_config)) => Ok(AuthConfig {
-                path_rules,
-                jwt_encoder_config,
-                jwt_decoder_config,
-            }),
+            (Ok(jwt_encoder_config), Ok(jwt_decoder_config)) if errors.is_empty() => {
+                Ok(AuthConfig {
+                    path_rules,
+                    jwt_encoder_config,
+                    jwt_decoder_config,
+                    glob_limits,
+                    revocation_list,
+                    path_rules_file,
+                    key_reload: key_reload.into_runtime()?,
+                })
+            }
+            (Ok(_), Ok(_)) => Err(errors),
             (Err(mut e), Ok(_)) | (Ok(_), Err(mut e)) => {
                 errors.append(&mut e);
                 Err(errors)
@@ -122,23 +191,88 @@ impl Default for StaticPathRule {
     }
 }
 
+impl Default for StaticKeyReloadConfig {
+    fn default() -> Self {
+        Self {
+            interval: 10,
+            grace_period: 3600,
+        }
+    }
+}
+
+impl ConfigItem for StaticKeyReloadConfig {
+    type RuntimeConfig = KeyReloadConfig;
+
+    fn into_runtime(self) -> FatalResult<Self::RuntimeConfig> {
+        let StaticKeyReloadConfig {
+            interval,
+            grace_period,
+        } = self;
+
+        Ok(KeyReloadConfig {
+            interval: (interval > 0).then(|| Duration::from_secs(interval)),
+            grace_period: Duration::from_secs(grace_period),
+        })
+    }
+}
+
+impl Default for StaticGlobLimits {
+    fn default() -> Self {
+        let GlobLimits {
+            max_len,
+            max_wildcards,
+        } = GlobLimits::default();
+
+        Self {
+            max_len,
+            max_wildcards,
+        }
+    }
+}
+
+impl ConfigItem for StaticGlobLimits {
+    type RuntimeConfig = GlobLimits;
+
+    fn into_runtime(self) -> FatalResult<Self::RuntimeConfig> {
+        let StaticGlobLimits {
+            max_len,
+            max_wildcards,
+        } = self;
+
+        Ok(GlobLimits {
+            max_len,
+            max_wildcards,
+        })
+    }
+}
+
 impl ConfigItem for StaticPathRule {
     type RuntimeConfig = PathRule;
 
     #[inline]
     fn into_runtime(self) -> FatalResult<Self::RuntimeConfig> {
+        self.into_runtime_with_limits(&GlobLimits::default())
+    }
+}
+
+impl StaticPathRule {
+    /// 编译这条规则，通配模式超出 `limits` 的视为配置错误
+    pub fn into_runtime_with_limits(self, limits: &GlobLimits) -> FatalResult<PathRule> {
         let StaticPathRule {
             pattern,
             public_methods,
         } = self;
 
-        let pattern = Pattern::new(&pattern).map_err(|e| {
-            let mut errors = MultiFatalError::new();
-            errors.push(
-                FatalError::from(e).when(format!("while parsing path rule pattern `{pattern}`")),
-            );
-            errors
-        })?;
+        let when = format!("while parsing path rule pattern `{pattern}`");
+        let pattern = limits
+            .check(&pattern)
+            .map_err(|e| FatalError::new(ErrorKind::InvalidValue, e.to_string(), None))
+            .and_then(|_| Pattern::new(&pattern).map_err(FatalError::from))
+            .map_err(|e| {
+                let mut errors = MultiFatalError::new();
+                errors.push(e.when(when));
+                errors
+            })?;
 
         let public_methods = public_methods.into_iter().collect();
 
@@ -150,6 +284,14 @@ impl ConfigItem for StaticPathRule {
 }
 
 impl PathRule {
+    /// 编译之前的形式，用于展示或者保存当前的规则
+    pub fn to_static(&self) -> StaticPathRule {
+        StaticPathRule {
+            pattern: self.pattern.as_str().to_string(),
+            public_methods: self.public_methods.iter().copied().collect(),
+        }
+    }
+
     pub fn approved(&self, path: &str, method: HttpMethod) -> bool {
         self.pattern.matches(path) && self.public_methods.contains(&method)
     }
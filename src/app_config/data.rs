@@ -1,13 +1,212 @@
+use std::path::PathBuf;
+
+use clap::error::ErrorKind;
+use crab_vault::engine::{
+    cache::DEFAULT_MAX_OBJECT_SIZE,
+    compression::Codec,
+    name::{self, NamingRules},
+};
 use serde::{Deserialize, Serialize};
 
-use crate::{app_config::ConfigItem, error::fatal::FatalResult};
+use crate::{
+    app_config::{ConfigItem, util::redact_uri},
+    error::fatal::{FatalError, FatalResult},
+};
 
 pub type DataConfig = StaticDataConfig;
 
 #[derive(Deserialize, Serialize, Clone)]
 #[serde(deny_unknown_fields, default)]
 pub struct StaticDataConfig {
+    /// 本地路径，`s3://bucket?endpoint=...` 形式的 S3 兼容存储，或者 `mem://` 表示只保存在内存中
     pub source: String,
+
+    /// 意图日志所在的本地目录，不设置时不记录，崩溃时可能留下不一致的数据和元数据，参见 `crab-vault fsck`
+    pub journal: Option<PathBuf>,
+
+    /// 在后台定期清理孤立的数据和元数据，参见 `crab-vault gc`
+    pub gc: GcConfig,
+
+    /// 在后台定期删除按照 bucket 的生命周期规则过期的 object
+    pub lifecycle: LifecycleScanConfig,
+
+    /// 在后台定期按照 bucket 的清单配置生成清单
+    pub inventory: InventoryScanConfig,
+
+    /// 删除的 object 先放进回收站，在保留期内可以恢复
+    pub trash: TrashConfig,
+
+    /// 写入时压缩 object 的数据，读取时解压
+    pub compression: CompressionConfig,
+
+    /// 在内存中缓存小 object 的数据，默认不缓存
+    pub cache: DataCacheConfig,
+
+    /// 上传时是否根据数据和扩展名推断 object 的 content type，`off`、`fallback` 或者 `always`
+    pub sniff: ContentSniff,
+
+    /// 保留给服务端内部使用的 bucket，客户端不能访问，参见 `crab_vault::engine::name`
+    pub internal_bucket: String,
+
+    /// 客户端请求中的 bucket 和 object 名称需要满足的规则，`relaxed` 或者 `s3`
+    pub naming: NamingRules,
+}
+
+#[derive(Deserialize, Serialize, Clone, Default)]
+#[serde(deny_unknown_fields, default)]
+pub struct GcConfig {
+    /// 两次扫描之间的间隔，单位为秒，`0` 表示不在后台扫描
+    pub interval: u64,
+
+    /// 是否删除连续两次扫描都发现的内容，否则只输出警告
+    pub delete: bool,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct LifecycleScanConfig {
+    /// 两次扫描之间的间隔，单位为秒，`0` 表示不在后台执行生命周期规则
+    pub interval: u64,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct InventoryScanConfig {
+    /// 两次检查之间的间隔，单位为秒，`0` 表示不在后台生成清单，只能通过 `POST /admin/inventory/{bucket}` 生成
+    pub interval: u64,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct TrashConfig {
+    /// 回收站中的 object 保留的秒数，`0` 表示不使用回收站，删除立即生效
+    pub retention: u64,
+
+    /// 两次清除过期条目之间的间隔，单位为秒，`0` 表示不在后台清除
+    pub interval: u64,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct DataCacheConfig {
+    /// 缓存的数据总共占用的字节数，`0` 表示不缓存
+    pub max_memory: u64,
+
+    /// 只缓存不超过这个字节数的 object
+    pub max_object_size: u64,
+}
+
+/// ## 上传时如何决定 object 的 content type
+///
+/// 先根据数据开头的魔数判断，无法判断时再根据 object 名称的扩展名，两者都无法判断时使用请求中给出的值
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum ContentSniff {
+    /// 总是使用请求中的 `Content-Type`，没有给出时为 `application/octet-stream`
+    #[default]
+    Off,
+
+    /// 只在请求没有给出 `Content-Type`，或者给出的是 `application/octet-stream` 时推断
+    Fallback,
+
+    /// 能够推断时总是替换请求中的 `Content-Type`
+    Always,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct CompressionConfig {
+    /// `"gzip"` 或者 `"zstd"`，不设置时不压缩
+    pub codec: Option<Codec>,
+
+    /// 只压缩这些 bucket 中的 object，为空时不限制
+    pub buckets: Vec<String>,
+
+    /// 只压缩这些类型的 object，可以用 `text/*` 匹配一类，为空时不限制
+    pub content_types: Vec<String>,
+
+    /// 小于这个字节数的 object 不压缩
+    pub min_size: u64,
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        Self {
+            codec: None,
+            buckets: vec![],
+            content_types: vec![],
+            min_size: 1024,
+        }
+    }
+}
+
+impl CompressionConfig {
+    /// 写入 `bucket` 中类型为 `content_type`、大小为 `size` 的 object 时使用的算法
+    pub fn codec_for(&self, bucket: &str, content_type: &str, size: u64) -> Option<Codec> {
+        let matches = size >= self.min_size
+            && (self.buckets.is_empty() || self.buckets.iter().any(|v| v == bucket))
+            && (self.content_types.is_empty()
+                || self
+                    .content_types
+                    .iter()
+                    .any(|v| content_type_matches(v, content_type)));
+        self.codec.filter(|_| matches)
+    }
+}
+
+/// `content_type` 是否属于 `pattern`，`pattern` 可以是 `text/*` 这样的一类
+pub fn content_type_matches(pattern: &str, content_type: &str) -> bool {
+    // 忽略 `; charset=utf-8` 之类的参数
+    let essence = content_type.split(';').next().unwrap_or_default().trim();
+    match pattern.strip_suffix("/*") {
+        Some(prefix) => essence
+            .split_once('/')
+            .is_some_and(|(v, _)| v.eq_ignore_ascii_case(prefix)),
+        None => essence.eq_ignore_ascii_case(pattern),
+    }
+}
+
+impl Default for DataCacheConfig {
+    fn default() -> Self {
+        Self {
+            max_memory: 0,
+            max_object_size: DEFAULT_MAX_OBJECT_SIZE as u64,
+        }
+    }
+}
+
+impl DataCacheConfig {
+    pub fn enabled(&self) -> bool {
+        self.max_memory > 0
+    }
+}
+
+impl Default for LifecycleScanConfig {
+    fn default() -> Self {
+        Self { interval: 3600 }
+    }
+}
+
+impl Default for InventoryScanConfig {
+    fn default() -> Self {
+        Self { interval: 3600 }
+    }
+}
+
+impl Default for TrashConfig {
+    fn default() -> Self {
+        Self {
+            retention: 0,
+            interval: 3600,
+        }
+    }
+}
+
+impl TrashConfig {
+    /// 删除 object 时是否先放进回收站
+    pub fn enabled(&self) -> bool {
+        self.retention > 0
+    }
 }
 
 impl Default for StaticDataConfig {
@@ -20,10 +219,40 @@ impl Default for StaticDataConfig {
                         .into()
                 })
                 .unwrap_or("./data".into()),
+            journal: None,
+            gc: GcConfig::default(),
+            lifecycle: LifecycleScanConfig::default(),
+            inventory: InventoryScanConfig::default(),
+            trash: TrashConfig::default(),
+            compression: CompressionConfig::default(),
+            cache: DataCacheConfig::default(),
+            sniff: ContentSniff::default(),
+            internal_bucket: name::DEFAULT_INTERNAL_BUCKET.into(),
+            naming: NamingRules::default(),
         }
     }
 }
 
+impl StaticDataConfig {
+    pub fn redacted(mut self) -> Self {
+        self.source = redact_uri(&self.source);
+        self
+    }
+
+    /// 把 [`internal_bucket`](Self::internal_bucket) 保留给服务端，必须在打开任何后端之前调用
+    pub fn reserve_internal_bucket(&self) -> Result<(), FatalError> {
+        name::reserve(&self.internal_bucket)
+            .map(|_| ())
+            .map_err(|e| {
+                FatalError::new(
+                    ErrorKind::InvalidValue,
+                    e.to_string(),
+                    Some("while checking `data.internal_bucket`".into()),
+                )
+            })
+    }
+}
+
 impl ConfigItem for StaticDataConfig {
     type RuntimeConfig = Self;
 
@@ -0,0 +1,148 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+};
+
+use base64::{Engine, prelude::BASE64_STANDARD};
+use clap::error::ErrorKind;
+use crab_vault::engine::crypto::{KEY_LEN, KeyRing};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_config::{ConfigItem, util::REDACTED},
+    error::fatal::{FatalError, FatalResult, MultiFatalError},
+};
+
+#[derive(Deserialize, Serialize, Default, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct StaticEncryptionConfig {
+    /// 所有的主密钥，轮换主密钥时旧的主密钥需要保留，直到 `keys rewrap` 完成
+    pub master_keys: Vec<StaticMasterKey>,
+
+    /// 用于包裹新的数据密钥的主密钥 id，不设置时使用最后一个主密钥
+    pub active_key: Option<String>,
+}
+
+/// 主密钥本身由 `key`、`key_file`、`key_env` 中的恰好一个给出
+#[derive(Deserialize, Serialize, Default, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct StaticMasterKey {
+    pub id: String,
+
+    /// 标准 base64 编码的 32 字节密钥
+    pub key: Option<String>,
+
+    /// 内容为 `key` 的文件，首尾的空白会被忽略
+    pub key_file: Option<PathBuf>,
+
+    /// 值为 `key` 的环境变量
+    pub key_env: Option<String>,
+}
+
+/// 没有配置任何主密钥时为 [`None`]，此时不会为 bucket 生成数据密钥
+pub type EncryptionConfig = Option<KeyRing>;
+
+impl StaticEncryptionConfig {
+    /// 只隐去直接写在配置中的密钥，文件路径和环境变量的名称保持不变
+    pub fn redacted(mut self) -> Self {
+        for master_key in &mut self.master_keys {
+            if master_key.key.is_some() {
+                master_key.key = Some(REDACTED.into());
+            }
+        }
+        self
+    }
+}
+
+impl ConfigItem for StaticEncryptionConfig {
+    type RuntimeConfig = EncryptionConfig;
+
+    fn into_runtime(self) -> FatalResult<Self::RuntimeConfig> {
+        let StaticEncryptionConfig {
+            master_keys,
+            active_key,
+        } = self;
+
+        let Some(last) = master_keys.last() else {
+            return Ok(None);
+        };
+
+        let active_key = active_key.unwrap_or_else(|| last.id.clone());
+        let (mut keys, mut errors) = (HashMap::new(), MultiFatalError::new());
+        let mut ids = HashSet::new();
+
+        for master_key in master_keys {
+            let id = master_key.id.clone();
+            // 重复的 id 会让后一个主密钥顶替前一个，用前一个包裹的数据密钥之后就无法解开了
+            if !ids.insert(id.clone()) {
+                errors.push(FatalError::new(
+                    ErrorKind::InvalidValue,
+                    format!("master key id `{id}` is used more than once"),
+                    Some("while building the master key ring".into()),
+                ));
+                continue;
+            }
+
+            match master_key.load().and_then(|v| decode_master_key(&v)) {
+                Ok(key) => {
+                    keys.insert(id, key);
+                }
+                Err(e) => errors.push(e.when(format!("while loading master key `{id}`"))),
+            }
+        }
+
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        KeyRing::new(keys, active_key).map(Some).map_err(|e| {
+            let mut errors = MultiFatalError::new();
+            errors.push(FatalError::new(
+                ErrorKind::InvalidValue,
+                e.to_string(),
+                Some("while building the master key ring".into()),
+            ));
+            errors
+        })
+    }
+}
+
+impl StaticMasterKey {
+    /// 从配置文件、文件或者环境变量中读取 base64 编码的密钥
+    fn load(self) -> Result<String, FatalError> {
+        let invalid =
+            |message: &str| FatalError::new(ErrorKind::InvalidValue, message.into(), None);
+
+        match (self.key, self.key_file, self.key_env) {
+            (Some(key), None, None) => Ok(key),
+            (None, Some(path), None) => std::fs::read_to_string(&path).map_err(|e| {
+                FatalError::new(
+                    ErrorKind::Io,
+                    e.to_string(),
+                    Some(format!("while reading `{}`", path.display())),
+                )
+            }),
+            (None, None, Some(name)) => std::env::var(&name)
+                .map_err(|e| invalid(&format!("cannot read environment variable `{name}`: {e}"))),
+            (None, None, None) => Err(invalid(
+                "one of `key`, `key_file` and `key_env` is required",
+            )),
+            _ => Err(invalid(
+                "only one of `key`, `key_file` and `key_env` can be given",
+            )),
+        }
+    }
+}
+
+fn decode_master_key(key: &str) -> Result<[u8; KEY_LEN], FatalError> {
+    let key = BASE64_STANDARD.decode(key.trim())?;
+    let len = key.len();
+
+    key.try_into().map_err(|_| {
+        FatalError::new(
+            ErrorKind::InvalidValue,
+            format!("a master key must be exactly {KEY_LEN} bytes, got {len} bytes"),
+            None,
+        )
+    })
+}
@@ -0,0 +1,21 @@
+use std::sync::{Arc, RwLock};
+
+/// ## 运行时可以整体替换的配置
+///
+/// 与 `KeyManager`、`PathRuleStore` 一样，每个请求开始时取出当前的值，
+/// 替换不会影响已经取出了旧值的请求
+pub struct Live<T>(RwLock<Arc<T>>);
+
+impl<T> Live<T> {
+    pub fn new(value: T) -> Self {
+        Self(RwLock::new(Arc::new(value)))
+    }
+
+    pub fn current(&self) -> Arc<T> {
+        self.0.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    pub fn replace(&self, value: T) {
+        *self.0.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(value);
+    }
+}
@@ -1,9 +1,19 @@
-use crab_vault::logger::LogLevel;
+use axum::http::Uri;
+use clap::error::ErrorKind;
+use crab_vault::logger::{
+    LogLevel, filter::TargetFilter, scrub::Scrubber, syslog::Facility, theme::Theme,
+    writer::DEFAULT_CAPACITY,
+};
+use glob::Pattern;
 use serde::{Deserialize, Serialize};
 
-use crate::{app_config::ConfigItem, error::fatal::FatalResult};
-
-pub type LoggerConfig = StaticLoggerConfig;
+use crate::{
+    app_config::{
+        ConfigItem,
+        util::{REDACTED, redact_uri},
+    },
+    error::fatal::{FatalError, FatalResult, MultiFatalError},
+};
 
 #[derive(Deserialize, Serialize, Clone)]
 #[serde(deny_unknown_fields, default)]
@@ -11,6 +21,9 @@ pub struct StaticLoggerConfig {
     /// 最低的日志输出等级
     pub level: LogLevel,
 
+    /// 按照模块设置的等级，例如 `crab_vault=debug,tower_http=warn`，没有匹配的模块使用 `level` 和 `dump_level`
+    pub filter: Option<String>,
+
     /// 彩色日志
     pub with_ansi: bool,
 
@@ -29,13 +42,246 @@ pub struct StaticLoggerConfig {
     /// 日志文件的最低输出等级
     #[serde(default)]
     pub dump_level: LogLevel,
+
+    /// 日志脱敏相关设置
+    #[serde(default)]
+    pub scrub: StaticScrubConfig,
+
+    /// 彩色日志的配色，没有配置的部分使用内置的配色
+    pub theme: Theme,
+
+    /// 日志输出到哪些地方，日志文件仍然由 `dump_path` 控制
+    pub outputs: Vec<LogOutput>,
+
+    /// `outputs` 中有 `syslog` 时的设置
+    pub syslog: StaticSyslogConfig,
+
+    /// 日志由后台线程写出，不阻塞产生日志的线程
+    #[serde(rename = "async")]
+    pub r#async: bool,
+
+    /// 开启 `async` 时缓冲区中最多的日志条数，写满之后新的日志被丢弃
+    pub async_buffer: usize,
+
+    /// 通过 OTLP/gRPC 导出 span 的地址，例如 `http://127.0.0.1:4317`，需要启用 `otlp` feature
+    pub otlp_endpoint: Option<String>,
+}
+
+#[derive(Clone)]
+pub struct LoggerConfig {
+    pub level: LogLevel,
+    pub filter: TargetFilter,
+    pub with_ansi: bool,
+    pub with_file: bool,
+    pub with_target: bool,
+    pub with_thread: bool,
+    pub dump_path: Option<String>,
+    pub dump_level: LogLevel,
+    pub scrubber: Scrubber,
+    pub theme: Theme,
+    pub outputs: Vec<LogOutput>,
+    pub syslog: StaticSyslogConfig,
+    /// 开启 `async` 时为缓冲区的大小
+    pub async_buffer: Option<usize>,
+    #[cfg_attr(not(feature = "otlp"), allow(dead_code))]
+    pub otlp_endpoint: Option<String>,
+}
+
+/// 日志的输出目标
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum LogOutput {
+    /// 标准输出中的彩色日志
+    Pretty,
+
+    /// RFC 5424 格式，通过 UDP 或者 unix socket 发送
+    Syslog,
+
+    /// systemd-journald 的原生协议，只支持 unix
+    Journald,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct StaticSyslogConfig {
+    /// `udp://host:port`、`unix:///dev/log` 或者 socket 的路径
+    pub address: String,
+
+    pub facility: Facility,
+
+    /// 消息中的 APP-NAME，同时也是 journald 中的 `SYSLOG_IDENTIFIER`
+    pub app_name: String,
+}
+
+#[derive(Deserialize, Serialize, Clone, Default)]
+#[serde(deny_unknown_fields, default)]
+pub struct StaticScrubConfig {
+    /// 匹配这些通配符的路径，日志中只保留 bucket，object 名称被遮盖
+    pub mask_object_keys: Vec<String>,
+
+    /// 日志中不记录 `user_meta` 的值
+    pub drop_user_meta: bool,
+
+    /// 日志中只记录客户端 ip 的哈希值
+    pub hash_client_ips: bool,
+
+    /// 计算 ip 哈希时使用的盐，不设置时每次启动随机生成，此时重启前后的哈希无法关联
+    pub ip_hash_salt: Option<String>,
+}
+
+impl StaticLoggerConfig {
+    pub fn redacted(mut self) -> Self {
+        self.otlp_endpoint = self.otlp_endpoint.as_deref().map(redact_uri);
+        if self.scrub.ip_hash_salt.is_some() {
+            self.scrub.ip_hash_salt = Some(REDACTED.into());
+        }
+        self
+    }
 }
 
 impl ConfigItem for StaticLoggerConfig {
-    type RuntimeConfig = Self;
+    type RuntimeConfig = LoggerConfig;
+
+    fn into_runtime(self) -> FatalResult<Self::RuntimeConfig> {
+        let StaticLoggerConfig {
+            level,
+            filter,
+            with_ansi,
+            with_file,
+            with_target,
+            with_thread,
+            dump_path,
+            dump_level,
+            scrub,
+            theme,
+            outputs,
+            syslog,
+            r#async,
+            async_buffer,
+            otlp_endpoint,
+        } = self;
+
+        let mut errors = MultiFatalError::new();
+        if r#async && async_buffer == 0 {
+            errors.push(FatalError::new(
+                ErrorKind::InvalidValue,
+                "the log buffer must hold at least one record".into(),
+                Some("while validating `logger.async_buffer`".into()),
+            ));
+        }
+
+        let filter = match filter.as_deref().map(str::parse::<TargetFilter>) {
+            Some(Ok(filter)) => filter,
+            Some(Err(e)) => {
+                errors.push(FatalError::new(
+                    ErrorKind::InvalidValue,
+                    e.to_string(),
+                    Some("while parsing `logger.filter`".into()),
+                ));
+                TargetFilter::default()
+            }
+            None => TargetFilter::default(),
+        };
+
+        if outputs.contains(&LogOutput::Journald) && !cfg!(unix) {
+            errors.push(FatalError::new(
+                ErrorKind::InvalidValue,
+                "journald is only available on unix, remove it from `logger.outputs`".into(),
+                None,
+            ));
+        }
+        if outputs.contains(&LogOutput::Syslog) && syslog.address.trim().is_empty() {
+            errors.push(FatalError::new(
+                ErrorKind::InvalidValue,
+                "the syslog address is empty".into(),
+                Some("while validating `logger.syslog.address`".into()),
+            ));
+        }
+
+        if let Some(Err(reason)) = otlp_endpoint.as_deref().map(validate_otlp_endpoint) {
+            errors.push(FatalError::new(
+                ErrorKind::InvalidValue,
+                reason,
+                Some("while validating `logger.otlp_endpoint`".into()),
+            ));
+        }
+
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        Ok(LoggerConfig {
+            level,
+            filter,
+            with_ansi,
+            with_file,
+            with_target,
+            with_thread,
+            dump_path,
+            dump_level,
+            scrubber: scrub.into_runtime()?,
+            theme,
+            outputs,
+            syslog,
+            async_buffer: r#async.then_some(async_buffer),
+            otlp_endpoint,
+        })
+    }
+}
+
+/// 没有启用 `otlp` feature 时拒绝这个配置，而不是悄悄地不导出
+fn validate_otlp_endpoint(endpoint: &str) -> Result<(), String> {
+    if !cfg!(feature = "otlp") {
+        return Err("this build does not support exporting spans, \
+            rebuild with the `otlp` feature or remove `logger.otlp_endpoint`"
+            .into());
+    }
+
+    let uri = endpoint
+        .parse::<Uri>()
+        .map_err(|e| format!("`{endpoint}` is not a valid uri: {e}"))?;
+    match (uri.scheme_str(), uri.authority()) {
+        (Some("http" | "https"), Some(_)) => Ok(()),
+        _ => Err(format!(
+            "`{endpoint}` must be an absolute http or https uri, such as `http://127.0.0.1:4317`"
+        )),
+    }
+}
+
+impl ConfigItem for StaticScrubConfig {
+    type RuntimeConfig = Scrubber;
 
     fn into_runtime(self) -> FatalResult<Self::RuntimeConfig> {
-        Ok(self)
+        let StaticScrubConfig {
+            mask_object_keys,
+            drop_user_meta,
+            hash_client_ips,
+            ip_hash_salt,
+        } = self;
+
+        let (mut patterns, mut errors) = (vec![], MultiFatalError::new());
+
+        for pattern in mask_object_keys {
+            match Pattern::new(&pattern) {
+                Ok(v) => patterns.push(v),
+                Err(e) => errors.push(
+                    FatalError::from(e)
+                        .when(format!("while parsing log scrub pattern `{pattern}`")),
+                ),
+            }
+        }
+
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        let salt = hash_client_ips
+            .then(|| ip_hash_salt.unwrap_or_else(|| uuid::Uuid::new_v4().to_string()));
+
+        Ok(Scrubber::new()
+            .mask_object_keys(patterns)
+            .drop_user_meta(drop_user_meta)
+            .hash_client_ips(salt))
     }
 }
 
@@ -43,12 +289,30 @@ impl Default for StaticLoggerConfig {
     fn default() -> Self {
         Self {
             level: LogLevel::default(),
+            filter: None,
             dump_path: None,
             dump_level: LogLevel::default(),
             with_ansi: true,
             with_file: true,
             with_target: true,
             with_thread: true,
+            scrub: StaticScrubConfig::default(),
+            theme: Theme::default(),
+            outputs: vec![LogOutput::Pretty],
+            syslog: StaticSyslogConfig::default(),
+            r#async: false,
+            async_buffer: DEFAULT_CAPACITY,
+            otlp_endpoint: None,
+        }
+    }
+}
+
+impl Default for StaticSyslogConfig {
+    fn default() -> Self {
+        Self {
+            address: "unix:///dev/log".into(),
+            facility: Facility::default(),
+            app_name: "crab-vault".into(),
         }
     }
 }
@@ -1,13 +1,69 @@
-use serde::{Deserialize, Serialize};
+use std::time::Duration;
 
-use crate::{app_config::ConfigItem, error::fatal::FatalResult};
+use clap::error::ErrorKind;
+use crab_vault::engine::{PoolConfig, list::OnCorrupt};
+use serde::{Deserialize, Serialize};
 
-pub type MetaConfig = StaticMetaConfig;
+use crate::{
+    app_config::{ConfigItem, util::redact_uri},
+    error::fatal::{FatalError, FatalResult, MultiFatalError},
+};
 
 #[derive(Deserialize, Serialize, Clone)]
 #[serde(deny_unknown_fields, default)]
 pub struct StaticMetaConfig {
+    /// 本地路径，`postgres://...` 这样的数据库连接串，或者 `mem://` 表示只保存在内存中
     pub source: String,
+
+    /// 连接池相关设置，只有数据库类的后端才会使用
+    pub pool: StaticPoolConfig,
+
+    /// 列举时遇到损坏的元数据，`fail` 表示整个列举失败，`skip` 表示跳过并在响应头中报告
+    pub on_corrupt_entry: OnCorrupt,
+
+    /// 变更事件日志，参见 `GET /admin/events`
+    pub events: EventLogConfig,
+}
+
+#[derive(Clone)]
+pub struct MetaConfig {
+    pub source: String,
+    pub pool: PoolConfig,
+    pub on_corrupt_entry: OnCorrupt,
+    pub events: EventLogConfig,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct EventLogConfig {
+    /// 是否在每次修改元数据之后记录一条事件
+    pub enabled: bool,
+
+    /// 最多保留的事件数，更早的事件会被清理
+    pub max_events: u64,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct StaticPoolConfig {
+    /// 连接池中最多保持的连接数
+    pub max_connections: u32,
+
+    /// 连接池中至少保持的连接数
+    pub min_connections: u32,
+
+    /// 获取连接的最长等待时间，单位为秒
+    pub acquire_timeout: u64,
+
+    /// 空闲连接的最长存活时间，单位为秒，不设置表示不关闭空闲连接
+    pub idle_timeout: Option<u64>,
+}
+
+impl StaticMetaConfig {
+    pub fn redacted(mut self) -> Self {
+        self.source = redact_uri(&self.source);
+        self
+    }
 }
 
 impl Default for StaticMetaConfig {
@@ -20,14 +76,95 @@ impl Default for StaticMetaConfig {
                         .into()
                 })
                 .unwrap_or("./data".into()),
+            pool: StaticPoolConfig::default(),
+            on_corrupt_entry: OnCorrupt::default(),
+            events: EventLogConfig::default(),
+        }
+    }
+}
+
+impl Default for EventLogConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_events: 100_000,
+        }
+    }
+}
+
+impl Default for StaticPoolConfig {
+    fn default() -> Self {
+        let PoolConfig {
+            max_connections,
+            min_connections,
+            acquire_timeout,
+            idle_timeout,
+        } = PoolConfig::default();
+
+        Self {
+            max_connections,
+            min_connections,
+            acquire_timeout: acquire_timeout.as_secs(),
+            idle_timeout: idle_timeout.map(|v| v.as_secs()),
         }
     }
 }
 
 impl ConfigItem for StaticMetaConfig {
+    type RuntimeConfig = MetaConfig;
+
+    fn into_runtime(self) -> FatalResult<Self::RuntimeConfig> {
+        let StaticMetaConfig {
+            source,
+            pool,
+            on_corrupt_entry,
+            events,
+        } = self;
+
+        Ok(MetaConfig {
+            source,
+            pool: pool.into_runtime()?,
+            on_corrupt_entry,
+            events: events.into_runtime()?,
+        })
+    }
+}
+
+impl ConfigItem for EventLogConfig {
     type RuntimeConfig = Self;
 
+    /// 清理时总要留下最后一条事件，后端才能从它的序号继续递增
+    fn into_runtime(self) -> FatalResult<Self::RuntimeConfig> {
+        if self.max_events > 0 {
+            return Ok(self);
+        }
+
+        let mut errors = MultiFatalError::new();
+        errors.push(FatalError::new(
+            ErrorKind::InvalidValue,
+            "`meta.events.max_events` must be greater than 0".into(),
+            Some("while validating `meta.events`".into()),
+        ));
+        Err(errors)
+    }
+}
+
+impl ConfigItem for StaticPoolConfig {
+    type RuntimeConfig = PoolConfig;
+
     fn into_runtime(self) -> FatalResult<Self::RuntimeConfig> {
-        Ok(self)
+        let StaticPoolConfig {
+            max_connections,
+            min_connections,
+            acquire_timeout,
+            idle_timeout,
+        } = self;
+
+        Ok(PoolConfig {
+            max_connections,
+            min_connections,
+            acquire_timeout: Duration::from_secs(acquire_timeout),
+            idle_timeout: idle_timeout.map(Duration::from_secs),
+        })
     }
 }
@@ -0,0 +1,288 @@
+use std::{collections::HashSet, path::PathBuf, time::Duration};
+
+use axum::http::Uri;
+use clap::error::ErrorKind;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_config::{ConfigItem, util::REDACTED},
+    error::fatal::{FatalError, FatalResult, MultiFatalError},
+};
+
+/// 规则中表示所有 bucket 的名称
+pub const ALL_BUCKETS: &str = "*";
+
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct StaticReplicationConfig {
+    /// 复制的目标，为空时不复制，否则必须开启 `meta.events`
+    pub targets: Vec<StaticReplicationTarget>,
+
+    /// 哪些 bucket 复制到哪些目标，没有匹配任何规则的 object 不会被复制
+    pub rules: Vec<ReplicationRule>,
+
+    /// 没有新事件时再次读取事件日志的间隔，单位为秒
+    pub poll_interval: u64,
+
+    /// 复制失败之后从 1 秒开始加倍等待，最多等待的秒数
+    pub max_backoff: u64,
+
+    /// 发往目标的单个请求的超时，单位为秒
+    pub timeout: u64,
+}
+
+/// 另一个 crab-vault，令牌由 `token`、`token_file`、`token_env` 中的恰好一个给出
+#[derive(Deserialize, Serialize, Default, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct StaticReplicationTarget {
+    /// 在规则和 `GET /admin/replication/status` 中引用这个目标，只能包含字母、数字、`-` 和 `_`
+    pub name: String,
+
+    /// 目标的地址，例如 `https://backup.example.com/v1`
+    pub endpoint: String,
+
+    /// 目标签发的令牌，需要允许写入和删除被复制的 bucket
+    pub token: Option<String>,
+
+    /// 内容为 `token` 的文件，首尾的空白会被忽略
+    pub token_file: Option<PathBuf>,
+
+    /// 值为 `token` 的环境变量
+    pub token_env: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Default, Clone, Debug)]
+#[serde(deny_unknown_fields, default)]
+pub struct ReplicationRule {
+    /// 被复制的 bucket，[`ALL_BUCKETS`] 表示所有 bucket
+    pub bucket: String,
+
+    /// 只复制名称以此开头的 object，bucket 本身的创建和删除不受影响
+    pub prefix: String,
+
+    /// 复制到哪些目标，为空时复制到所有目标
+    pub targets: Vec<String>,
+
+    /// 写入目标中的这个 bucket，不设置时与原来的 bucket 同名，`bucket = "*"` 时不能设置
+    pub destination_bucket: Option<String>,
+}
+
+#[derive(Clone, Default)]
+pub struct ReplicationConfig {
+    pub targets: Vec<ReplicationTarget>,
+    pub poll_interval: Duration,
+    pub max_backoff: Duration,
+    pub timeout: Duration,
+}
+
+#[derive(Clone)]
+pub struct ReplicationTarget {
+    pub name: String,
+
+    /// 去掉了末尾的 `/`
+    pub endpoint: String,
+    pub token: String,
+
+    /// 作用于这个目标的规则，按照配置中的顺序，第一条匹配的规则生效
+    pub rules: Vec<ReplicationRule>,
+}
+
+impl StaticReplicationConfig {
+    const fn default_poll_interval() -> u64 {
+        1
+    }
+
+    const fn default_max_backoff() -> u64 {
+        60
+    }
+
+    const fn default_timeout() -> u64 {
+        30
+    }
+
+    /// 只隐去直接写在配置中的令牌
+    pub fn redacted(mut self) -> Self {
+        for target in &mut self.targets {
+            if target.token.is_some() {
+                target.token = Some(REDACTED.into());
+            }
+        }
+        self
+    }
+}
+
+impl Default for StaticReplicationConfig {
+    fn default() -> Self {
+        Self {
+            targets: vec![],
+            rules: vec![],
+            poll_interval: Self::default_poll_interval(),
+            max_backoff: Self::default_max_backoff(),
+            timeout: Self::default_timeout(),
+        }
+    }
+}
+
+impl StaticReplicationTarget {
+    fn load_token(&self) -> Result<String, FatalError> {
+        let invalid =
+            |message: &str| FatalError::new(ErrorKind::InvalidValue, message.into(), None);
+
+        match (&self.token, &self.token_file, &self.token_env) {
+            (Some(token), None, None) => Ok(token.clone()),
+            (None, Some(path), None) => std::fs::read_to_string(path)
+                .map(|v| v.trim().to_string())
+                .map_err(|e| {
+                    FatalError::new(
+                        ErrorKind::Io,
+                        e.to_string(),
+                        Some(format!("while reading `{}`", path.display())),
+                    )
+                }),
+            (None, None, Some(name)) => std::env::var(name)
+                .map_err(|e| invalid(&format!("cannot read environment variable `{name}`: {e}"))),
+            (None, None, None) => Err(invalid(
+                "one of `token`, `token_file` and `token_env` is required",
+            )),
+            _ => Err(invalid(
+                "only one of `token`, `token_file` and `token_env` can be given",
+            )),
+        }
+    }
+
+    fn check_endpoint(&self) -> Result<String, FatalError> {
+        let endpoint = self.endpoint.trim_end_matches('/');
+        let valid = endpoint.parse::<Uri>().is_ok_and(|v| {
+            matches!(v.scheme_str(), Some("http" | "https"))
+                && v.authority().is_some()
+                && v.query().is_none()
+        });
+
+        match valid {
+            true => Ok(endpoint.to_string()),
+            false => Err(FatalError::new(
+                ErrorKind::InvalidValue,
+                format!("`{}` is not an http or https URL", self.endpoint),
+                None,
+            )),
+        }
+    }
+}
+
+impl ReplicationRule {
+    pub fn matches_bucket(&self, bucket: &str) -> bool {
+        self.bucket == ALL_BUCKETS || self.bucket == bucket
+    }
+
+    /// 目标中对应的 bucket
+    pub fn destination<'a>(&'a self, bucket: &'a str) -> &'a str {
+        self.destination_bucket.as_deref().unwrap_or(bucket)
+    }
+}
+
+impl ConfigItem for StaticReplicationConfig {
+    type RuntimeConfig = ReplicationConfig;
+
+    fn into_runtime(self) -> FatalResult<Self::RuntimeConfig> {
+        let StaticReplicationConfig {
+            targets,
+            rules,
+            poll_interval,
+            max_backoff,
+            timeout,
+        } = self;
+
+        let mut errors = MultiFatalError::new();
+        let invalid = |message: String| {
+            FatalError::new(
+                ErrorKind::InvalidValue,
+                message,
+                Some("while validating `replication`".into()),
+            )
+        };
+
+        let mut names = HashSet::new();
+        for target in &targets {
+            let valid = !target.name.is_empty()
+                && target
+                    .name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+            if !valid {
+                errors.push(invalid(format!(
+                    "`{}` is not a valid target name",
+                    target.name
+                )));
+            } else if !names.insert(target.name.as_str()) {
+                errors.push(invalid(format!(
+                    "target `{}` is defined twice",
+                    target.name
+                )));
+            }
+        }
+
+        for rule in &rules {
+            if rule.bucket.is_empty() {
+                errors.push(invalid("the `bucket` of a rule cannot be empty".into()));
+            }
+            if rule.bucket == ALL_BUCKETS && rule.destination_bucket.is_some() {
+                errors.push(invalid(
+                    "`destination_bucket` cannot be used with `bucket = \"*\"`".into(),
+                ));
+            }
+            for name in rule.targets.iter().filter(|v| !names.contains(v.as_str())) {
+                errors.push(invalid(format!(
+                    "the rule for `{}` refers to an unknown target `{name}`",
+                    rule.bucket
+                )));
+            }
+        }
+
+        if poll_interval == 0 || max_backoff == 0 || timeout == 0 {
+            errors.push(invalid(
+                "`poll_interval`, `max_backoff` and `timeout` must be greater than 0".into(),
+            ));
+        }
+
+        let mut runtime = vec![];
+        for target in &targets {
+            let rules: Vec<_> = rules
+                .iter()
+                .filter(|v| v.targets.is_empty() || v.targets.contains(&target.name))
+                .cloned()
+                .collect();
+            if rules.is_empty() {
+                errors.push(invalid(format!(
+                    "target `{}` is not used by any rule",
+                    target.name
+                )));
+            }
+
+            let when = format!("while loading replication target `{}`", target.name);
+            match (target.check_endpoint(), target.load_token()) {
+                (Ok(endpoint), Ok(token)) => runtime.push(ReplicationTarget {
+                    name: target.name.clone(),
+                    endpoint,
+                    token,
+                    rules,
+                }),
+                (endpoint, token) => {
+                    for e in [endpoint.err(), token.err()].into_iter().flatten() {
+                        errors.push(e.when(when.clone()));
+                    }
+                }
+            }
+        }
+
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        Ok(ReplicationConfig {
+            targets: runtime,
+            poll_interval: Duration::from_secs(poll_interval),
+            max_backoff: Duration::from_secs(max_backoff),
+            timeout: Duration::from_secs(timeout),
+        })
+    }
+}
@@ -1,27 +1,492 @@
+use std::{path::PathBuf, time::Duration};
+
+use axum::http::{HeaderName, Uri};
+use chrono::{DateTime, Utc};
+use crab_vault::{auth::HttpMethod, engine::compression::Codec};
+use ipnet::IpNet;
 use serde::{Deserialize, Serialize};
 
-use crate::{app_config::ConfigItem, error::fatal::FatalResult};
+use clap::error::ErrorKind;
+
+use crate::{
+    app_config::ConfigItem,
+    error::fatal::{FatalError, FatalResult, MultiFatalError},
+};
 
 pub type ServerConfig = StaticServerConfig;
 
-#[derive(Deserialize, Serialize, Default, Clone)]
+#[derive(Deserialize, Serialize, Clone)]
 #[serde(deny_unknown_fields, default)]
 pub struct StaticServerConfig {
     #[serde(default = "ServerConfig::default_port")]
     pub port: u16,
+
+    /// 管理接口 (`/admin/healthz`、`/admin/readyz`、`/admin/config`) 单独监听的端口，
+    /// 不设置时与其他接口共用 `port`
+    pub admin_port: Option<u16>,
+
+    /// 没有版本前缀的旧路径的处理方式
+    pub versioning: VersioningConfig,
+
+    /// 受信任的反向代理所在的网段，只有来自这些地址的请求才会参考 `Forwarded`、`X-Forwarded-For` 确定客户端地址
+    pub trusted_proxies: Vec<IpNet>,
+
+    /// 请求体的最大大小（字节），令牌中的 `maxSize` 只能在此基础上进一步收紧
+    #[serde(default = "ServerConfig::default_max_body_size")]
+    pub max_body_size: u64,
+
+    /// 等待请求体的下一段数据的最长时间，单位为秒，`0` 表示不限制
+    #[serde(default = "ServerConfig::default_read_timeout")]
+    pub read_timeout: u64,
+
+    /// 客户端不再接收响应之后等待的最长时间，超时后关闭连接，单位为秒，`0` 表示不限制
+    #[serde(default = "ServerConfig::default_write_timeout")]
+    pub write_timeout: u64,
+
+    /// 需要完整缓冲的请求体的缓冲方式
+    pub buffering: BufferingConfig,
+
+    /// 直接提供 HTTPS，没有配置时只提供 HTTP
+    pub tls: Option<TlsConfig>,
+
+    /// 跨域 (CORS) 规则，bucket 可以用自己的规则覆盖它
+    pub cors: CorsConfig,
+
+    /// 请求数和流量的限制，默认不限制
+    pub rate_limit: RateLimitConfig,
+
+    /// 按照 `Accept-Encoding` 压缩响应，默认不压缩
+    pub compression: ResponseCompressionConfig,
+
+    /// 收到 SIGINT 或者 SIGTERM 之后等待正在处理的请求完成的最长时间，单位为秒
+    #[serde(default = "ServerConfig::default_shutdown_timeout")]
+    pub shutdown_timeout: u64,
+
+    /// 配置文件发生变化时自动重新加载其中可以在运行时修改的部分，参见 `POST /admin/reload`
+    #[serde(default = "ServerConfig::default_watch_config")]
+    pub watch_config: bool,
+
+    /// 测试模式，只应该在测试环境中开启
+    pub test_mode: Option<TestModeConfig>,
+}
+
+/// ## 测试模式
+///
+/// 元数据中的时间戳和响应中的 `Date` 头都固定为 `now`，
+/// 相同的请求序列总是得到完全相同的响应，可以直接对响应头和响应体做快照比较。
+/// 令牌的有效期仍然按照系统时间检查
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct TestModeConfig {
+    /// 服务端认为的当前时间
+    pub now: DateTime<Utc>,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct BufferingConfig {
+    /// 请求体超过这个大小（字节）后写入临时文件，而不是继续留在内存中
+    pub memory_threshold: usize,
+
+    /// 已弃用，使用 [`StaticServerConfig::max_body_size`]，两者都设置时取较小的一个
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_body_size: Option<u64>,
+
+    /// 临时文件所在的目录，默认为系统的临时目录
+    pub spill_dir: Option<PathBuf>,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct TlsConfig {
+    /// PEM 格式的证书链，第一个证书是服务端自己的证书
+    pub cert_path: PathBuf,
+
+    /// PEM 格式的私钥，支持 PKCS#1、PKCS#8 和 SEC1
+    pub key_path: PathBuf,
+
+    /// 检查证书和私钥是否变化的间隔，单位为秒，`0` 表示不重新加载
+    #[serde(default = "TlsConfig::default_reload_interval")]
+    pub reload_interval: u64,
+
+    /// 在这个端口上额外监听 HTTP，把所有请求重定向到 HTTPS
+    #[serde(default)]
+    pub redirect_http_port: Option<u16>,
 }
 
+/// ## 跨域 (CORS) 规则
+///
+/// 只有带有 `Origin` 头的请求才会用到这些规则，`PUT /{bucket}?cors` 的请求体使用同样的格式
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+#[serde(deny_unknown_fields, default)]
+pub struct CorsConfig {
+    /// 允许的来源，例如 `https://example.com`，`*` 表示任意来源，为空时不允许任何跨域请求
+    pub allowed_origins: Vec<String>,
+
+    /// 允许的方法，只能是具体的方法
+    pub allowed_methods: Vec<HttpMethod>,
+
+    /// 允许浏览器携带的请求头，`*` 表示任意请求头
+    pub allowed_headers: Vec<String>,
+
+    /// 除了所有 `X-Crab-Vault-*` 响应头之外，额外允许浏览器读取的响应头
+    pub expose_headers: Vec<String>,
+
+    /// 浏览器缓存预检结果的时间，单位为秒
+    pub max_age: u64,
+
+    /// 是否允许浏览器携带 Cookie 等凭据，开启时 `allowed_origins` 不能包含 `*`
+    pub allow_credentials: bool,
+}
+
+/// ## 响应的传输压缩
+///
+/// 与 `data.compression` 无关，只影响传输。已经带有 `Content-Encoding` 或者 `Content-Range` 的响应不会被压缩，
+/// 例如直接返回的压缩保存的 object
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct ResponseCompressionConfig {
+    /// 可以使用的算法，按照客户端的 `Accept-Encoding` 从中选择，为空时不压缩响应
+    pub codecs: Vec<Codec>,
+
+    /// 只压缩这些类型的响应，可以用 `text/*` 匹配一类，为空时不限制。图片、音视频和压缩包总是不会被压缩
+    pub content_types: Vec<String>,
+
+    /// 小于这个字节数的响应不压缩
+    pub min_size: u64,
+}
+
+/// ## 请求数和流量的限制
+///
+/// 三种范围相互独立，请求必须同时满足所有配置了的范围；没有携带有效令牌的请求只受 `global` 限制
+#[derive(Deserialize, Serialize, Default, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct RateLimitConfig {
+    /// 所有请求共享的限制
+    pub global: Option<RateLimit>,
+
+    /// 同一个签发者 (`iss`) 签发的所有令牌共享的限制
+    pub per_issuer: Option<RateLimit>,
+
+    /// 每一个令牌 (`jti`) 单独的限制
+    pub per_token: Option<RateLimit>,
+}
+
+/// ## 一个范围内的限制
+///
+/// 请求数和字节数各是一个令牌桶，桶的容量决定了允许的突发量
+#[derive(Deserialize, Serialize, Clone, Copy, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct RateLimit {
+    /// 每秒允许的请求数，可以是小数，没有配置时不限制
+    #[serde(default)]
+    pub requests_per_second: Option<f64>,
+
+    /// 允许短时间内连续发出的请求数，默认为一秒的请求数
+    #[serde(default)]
+    pub burst: Option<u32>,
+
+    /// 每秒允许的请求体和响应体的总字节数，没有配置时不限制
+    #[serde(default)]
+    pub bytes_per_second: Option<u64>,
+
+    /// 允许短时间内连续传输的字节数，默认为一秒的字节数
+    #[serde(default)]
+    pub bytes_burst: Option<u64>,
+}
+
+#[derive(Deserialize, Serialize, Default, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct VersioningConfig {
+    pub unversioned: UnversionedPolicy,
+
+    /// 旧路径停止服务的时间，只在 [`UnversionedPolicy::Deprecate`] 时生效，
+    /// 之前的响应带有 `Sunset` 头，之后的请求都会被拒绝
+    pub sunset: Option<DateTime<Utc>>,
+}
+
+/// 没有版本前缀的请求的处理方式
+#[derive(Deserialize, Serialize, Default, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum UnversionedPolicy {
+    /// 与带有版本前缀的请求完全相同
+    #[default]
+    Allow,
+
+    /// 正常处理，但响应中带有 `Deprecation`、`Sunset` 以及指向新路径的 `Link` 头
+    Deprecate,
+
+    /// 直接返回 `410 Gone`
+    Reject,
+}
 
 impl StaticServerConfig {
     const fn default_port() -> u16 {
         32767
     }
+
+    const fn default_max_body_size() -> u64 {
+        5 << 30
+    }
+
+    const fn default_read_timeout() -> u64 {
+        60
+    }
+
+    const fn default_write_timeout() -> u64 {
+        60
+    }
+
+    const fn default_shutdown_timeout() -> u64 {
+        30
+    }
+
+    const fn default_watch_config() -> bool {
+        true
+    }
+
+    /// 需要完整读入内存的请求体（例如 JSON）的最大大小，它们无法写入临时文件
+    pub fn max_buffered_body_size(&self) -> usize {
+        usize::try_from(self.max_body_size)
+            .unwrap_or(usize::MAX)
+            .min(self.buffering.memory_threshold)
+    }
+
+    pub fn read_timeout(&self) -> Option<Duration> {
+        (self.read_timeout > 0).then(|| Duration::from_secs(self.read_timeout))
+    }
+
+    pub fn write_timeout(&self) -> Option<Duration> {
+        (self.write_timeout > 0).then(|| Duration::from_secs(self.write_timeout))
+    }
+}
+
+impl TlsConfig {
+    const fn default_reload_interval() -> u64 {
+        10
+    }
+}
+
+impl Default for StaticServerConfig {
+    fn default() -> Self {
+        Self {
+            port: Self::default_port(),
+            admin_port: None,
+            versioning: VersioningConfig::default(),
+            trusted_proxies: vec![],
+            max_body_size: Self::default_max_body_size(),
+            read_timeout: Self::default_read_timeout(),
+            write_timeout: Self::default_write_timeout(),
+            buffering: BufferingConfig::default(),
+            tls: None,
+            cors: CorsConfig::default(),
+            rate_limit: RateLimitConfig::default(),
+            compression: ResponseCompressionConfig::default(),
+            shutdown_timeout: Self::default_shutdown_timeout(),
+            watch_config: Self::default_watch_config(),
+            test_mode: None,
+        }
+    }
+}
+
+impl Default for ResponseCompressionConfig {
+    fn default() -> Self {
+        Self {
+            codecs: vec![],
+            content_types: vec![],
+            min_size: 1024,
+        }
+    }
+}
+
+impl Default for BufferingConfig {
+    fn default() -> Self {
+        Self {
+            memory_threshold: 8 << 20,
+            max_body_size: None,
+            spill_dir: None,
+        }
+    }
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        Self {
+            allowed_origins: vec!["*".into()],
+            allowed_methods: vec![
+                HttpMethod::Get,
+                HttpMethod::Head,
+                HttpMethod::Put,
+                HttpMethod::Post,
+                HttpMethod::Patch,
+                HttpMethod::Delete,
+            ],
+            allowed_headers: vec!["*".into()],
+            expose_headers: vec!["ETag".into()],
+            max_age: 24 * 3600,
+            allow_credentials: false,
+        }
+    }
+}
+
+impl Default for TestModeConfig {
+    fn default() -> Self {
+        Self {
+            now: DateTime::UNIX_EPOCH,
+        }
+    }
+}
+
+impl BufferingConfig {
+    pub fn spill_dir(&self) -> PathBuf {
+        self.spill_dir.clone().unwrap_or_else(std::env::temp_dir)
+    }
+}
+
+impl CorsConfig {
+    /// 检查来源、方法和头部的格式，返回第一个错误
+    pub fn validate(&self) -> Result<(), String> {
+        for origin in &self.allowed_origins {
+            if origin == "*" {
+                if self.allow_credentials {
+                    return Err(
+                        "`*` is not allowed in `allowed_origins` when `allow_credentials` is enabled"
+                            .into(),
+                    );
+                }
+                continue;
+            }
+
+            let valid = origin.parse::<Uri>().is_ok_and(|uri| {
+                matches!(uri.scheme_str(), Some("http" | "https"))
+                    && uri.authority().is_some()
+                    // 没有路径时 `Uri` 也会给出 `/`，只能检查原始的字符串
+                    && uri.path() == "/"
+                    && uri.query().is_none()
+                    && !origin.ends_with('/')
+            });
+            if !valid {
+                return Err(format!(
+                    "`{origin}` is not a valid origin, expected `scheme://host[:port]`"
+                ));
+            }
+        }
+
+        for method in &self.allowed_methods {
+            if matches!(
+                method,
+                HttpMethod::All | HttpMethod::Safe | HttpMethod::Unsafe | HttpMethod::Other
+            ) {
+                return Err(format!("`{}` is not a concrete method", method.as_str()));
+            }
+        }
+
+        for header in self.allowed_headers.iter().filter(|v| *v != "*") {
+            header
+                .parse::<HeaderName>()
+                .map_err(|_| format!("`{header}` is not a valid header name"))?;
+        }
+        for header in &self.expose_headers {
+            header
+                .parse::<HeaderName>()
+                .map_err(|_| format!("`{header}` is not a valid header name"))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl RateLimit {
+    fn validate(&self, scope: &str) -> Result<(), String> {
+        match (self.requests_per_second, self.bytes_per_second) {
+            (None, None) => {
+                return Err(format!(
+                    "`{scope}` must limit `requests_per_second` or `bytes_per_second`"
+                ));
+            }
+            (Some(rps), _) if !(rps.is_finite() && rps > 0.0) => {
+                return Err(format!("`{scope}.requests_per_second` must be positive"));
+            }
+            (_, Some(0)) => return Err(format!("`{scope}.bytes_per_second` must be positive")),
+            _ => {}
+        }
+
+        if self.burst == Some(0) || self.bytes_burst == Some(0) {
+            return Err(format!("the bursts of `{scope}` must be positive"));
+        }
+
+        Ok(())
+    }
+}
+
+impl VersioningConfig {
+    /// 在 `now` 这一时刻实际生效的处理方式，过了 `sunset` 之后 [`Deprecate`](UnversionedPolicy::Deprecate) 变为 [`Reject`](UnversionedPolicy::Reject)
+    pub fn policy_at(&self, now: DateTime<Utc>) -> UnversionedPolicy {
+        match (self.unversioned, self.sunset) {
+            (UnversionedPolicy::Deprecate, Some(sunset)) if now >= sunset => UnversionedPolicy::Reject,
+            (policy, _) => policy,
+        }
+    }
 }
 
 impl ConfigItem for StaticServerConfig {
     type RuntimeConfig = Self;
 
-    fn into_runtime(self) -> FatalResult<Self::RuntimeConfig> {
-        Ok(self)
+    fn into_runtime(mut self) -> FatalResult<Self::RuntimeConfig> {
+        if let Some(max_body_size) = self.buffering.max_body_size.take() {
+            self.max_body_size = self.max_body_size.min(max_body_size);
+        }
+
+        let mut errors = MultiFatalError::new();
+
+        if let Some(tls) = &self.tls
+            && tls.redirect_http_port == Some(self.port)
+        {
+            errors.push(FatalError::new(
+                ErrorKind::InvalidValue,
+                "`server.tls.redirect_http_port` must differ from `server.port`".into(),
+                None,
+            ));
+        }
+
+        if let Some(admin_port) = self.admin_port
+            && (admin_port == self.port
+                || self.tls.as_ref().and_then(|v| v.redirect_http_port) == Some(admin_port))
+        {
+            errors.push(FatalError::new(
+                ErrorKind::InvalidValue,
+                "`server.admin_port` must differ from `server.port` and `server.tls.redirect_http_port`"
+                    .into(),
+                None,
+            ));
+        }
+
+        if let Err(reason) = self.cors.validate() {
+            errors.push(FatalError::new(
+                ErrorKind::InvalidValue,
+                reason,
+                Some("while validating `server.cors`".into()),
+            ));
+        }
+
+        let scopes = [
+            ("global", &self.rate_limit.global),
+            ("per_issuer", &self.rate_limit.per_issuer),
+            ("per_token", &self.rate_limit.per_token),
+        ];
+        for (scope, limit) in scopes {
+            if let Some(Err(reason)) = limit.as_ref().map(|v| v.validate(scope)) {
+                errors.push(FatalError::new(
+                    ErrorKind::InvalidValue,
+                    reason,
+                    Some("while validating `server.rate_limit`".into()),
+                ));
+            }
+        }
+
+        match errors.is_empty() {
+            true => Ok(self),
+            false => Err(errors),
+        }
     }
 }
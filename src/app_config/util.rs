@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::{borrow::Cow, collections::HashMap, path::PathBuf};
 
 use base64::{Engine, prelude::BASE64_STANDARD};
 use chrono::TimeDelta;
@@ -12,6 +12,9 @@ use crate::{
     error::fatal::{FatalError, FatalResult, MultiFatalError},
 };
 
+/// 被隐去的值在 `GET /admin/config` 中显示为 [`REDACTED`]，连接串由 [`redact_uri`] 处理
+pub use crab_vault::engine::registry::{REDACTED, redact_uri};
+
 #[derive(Serialize, Deserialize, Default, Clone)]
 #[serde(deny_unknown_fields, default)]
 pub struct StaticJwtEncoderConfig {
@@ -44,6 +47,12 @@ pub struct StaticJwtDecoderConfig {
 #[derive(Clone)]
 pub struct JwtDecoderConfig {
     pub decoder: JwtDecoder,
+
+    /// 构建 `decoder` 时使用的所有密钥，热加载时用来找出被移除的密钥
+    pub keys: HashMap<(String, String), (Algorithm, DecodingKey)>,
+
+    /// 从文件中读取的那些密钥的路径
+    pub key_files: Vec<PathBuf>,
 }
 
 #[derive(Serialize, Deserialize, Default, Clone)]
@@ -52,8 +61,12 @@ pub struct Key {
     pub form: KeyForm,
     pub kid: String,
 
-    #[serde(alias = "path")]
+    #[serde(alias = "path", default)]
     pub key: String,
+
+    /// 值为内联密钥的环境变量，密钥不必写在配置文件中，只能用于 `der_inline` 和 `pem_inline`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_env: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Copy, Default)]
@@ -66,6 +79,24 @@ pub enum KeyForm {
     PemFile,
 }
 
+impl StaticJwtEncoderConfig {
+    pub fn redacted(mut self) -> Self {
+        self.encoding_keys = self.encoding_keys.into_iter().map(Key::redacted).collect();
+        self
+    }
+}
+
+impl StaticJwtDecoderConfig {
+    pub fn redacted(mut self) -> Self {
+        self.decoding_keys = self
+            .decoding_keys
+            .into_iter()
+            .map(|(issuer, key)| (issuer, key.redacted()))
+            .collect();
+        self
+    }
+}
+
 impl ConfigItem for StaticJwtEncoderConfig {
     type RuntimeConfig = JwtEncoderConfig;
 
@@ -128,13 +159,18 @@ impl ConfigItem for StaticJwtDecoderConfig {
         } = self;
         let (mut keys, mut errors, mut algs, mut issuers) =
             (HashMap::new(), MultiFatalError::new(), vec![], vec![]);
+        let mut key_files = vec![];
 
         for (iss, key) in decoding_keys {
+            if key.form.is_file() {
+                key_files.push(PathBuf::from(&key.key));
+            }
+
             match key.build_as_decode_key() {
                 Ok((kid, alg, key)) => {
                     issuers.push(iss.clone());
                     algs.push(alg);
-                    keys.insert((iss, kid), key);
+                    keys.insert((iss, kid), (alg, key));
                 }
                 Err(e) => {
                     errors.push(e);
@@ -151,10 +187,17 @@ impl ConfigItem for StaticJwtDecoderConfig {
         }
 
         if errors.is_empty() {
+            let mapping = keys
+                .iter()
+                .map(|(id, (_, key))| (id.clone(), key.clone()))
+                .collect();
+
             Ok(JwtDecoderConfig {
-                decoder: JwtDecoder::new(keys, &algs, &issuers, &aud)
+                decoder: JwtDecoder::new(mapping, &algs, &issuers, &aud)
                     .reject_tokens_expiring_in_less_than(reject_tokens_expiring_in_less_than)
                     .leeway(leeway),
+                keys,
+                key_files,
             })
         } else {
             Err(errors)
@@ -163,21 +206,72 @@ impl ConfigItem for StaticJwtDecoderConfig {
 }
 
 impl Key {
+    /// 隐去直接写在配置中的密钥，密钥文件的路径和环境变量的名称保持不变
+    pub fn redacted(mut self) -> Self {
+        if !self.form.is_file() && !self.key.is_empty() {
+            self.key = REDACTED.into();
+        }
+        self
+    }
+
+    /// 直接写在配置中的密钥，或者 `key_env` 指定的环境变量的值
+    fn inline_key(&self) -> Result<Cow<'_, str>, FatalError> {
+        let invalid =
+            |message: String| FatalError::new(ErrorKind::InvalidValue, message, None);
+
+        match (&self.key_env, self.key.is_empty()) {
+            (None, false) => Ok(Cow::Borrowed(&self.key)),
+            (None, true) => Err(invalid(format!(
+                "one of `key` and `key_env` is required for the key `{}`",
+                self.kid
+            ))),
+            (Some(name), true) => std::env::var(name).map(Cow::Owned).map_err(|e| {
+                invalid(format!("cannot read environment variable `{name}`: {e}"))
+            }),
+            (Some(_), false) => Err(invalid(format!(
+                "only one of `key` and `key_env` can be given for the key `{}`",
+                self.kid
+            ))),
+        }
+    }
+
+    /// 出现在日志中的密钥的简短描述，不会泄露整个密钥
+    fn hint(&self) -> String {
+        match &self.key_env {
+            Some(name) => format!("${name}"),
+            None => self
+                .key
+                .get(0..4)
+                .map(|val| format!("{val}..."))
+                .unwrap_or(self.key.clone()),
+        }
+    }
+
     fn get_key(&self) -> Result<Vec<u8>, FatalError> {
+        if self.form.is_file() && self.key_env.is_some() {
+            return Err(FatalError::new(
+                ErrorKind::InvalidValue,
+                format!(
+                    "`key_env` of the key `{}` can only be used with `der_inline` and `pem_inline`",
+                    self.kid
+                ),
+                None,
+            ));
+        }
+
         let res = match self.form {
-            KeyForm::DerInline => BASE64_STANDARD.decode(self.key.clone()).map_err(|e| {
-                FatalError::from(e).when(format!(
-                    "while decoding the secrete key `{}` into binary, note this should be encoded in standard base64",
-                    self.key
-                        .get(0..4)
-                        .map(|val| format!("{val}..."))
-                        .unwrap_or(self.key.clone())
-                ))
-            })?,
+            KeyForm::DerInline => BASE64_STANDARD
+                .decode(self.inline_key()?.as_bytes())
+                .map_err(|e| {
+                    FatalError::from(e).when(format!(
+                        "while decoding the secrete key `{}` into binary, note this should be encoded in standard base64",
+                        self.hint()
+                    ))
+                })?,
             KeyForm::DerFile => std::fs::read(&self.key).map_err(|e| {
                 FatalError::from(e).when(format!("while reading the der key from {}", self.key))
             })?,
-            KeyForm::PemInline => self.key.clone().into_bytes(),
+            KeyForm::PemInline => self.inline_key()?.into_owned().into_bytes(),
             KeyForm::PemFile => std::fs::read(&self.key).map_err(|e| {
                 FatalError::from(e).when(format!("while reading the pem key from {}", self.key))
             })?,
@@ -186,17 +280,16 @@ impl Key {
         if res.len() < 32 {
             tracing::warn!(
                 "the secret key `{}` is too short to prevent brute cracking",
-                self.key
-                    .get(0..4)
-                    .map(|val| format!("{val}..."))
-                    .unwrap_or(self.key.clone())
+                self.hint()
             )
         }
 
         Ok(res)
     }
 
-    fn build_as_encode_key(&self) -> Result<(String, Algorithm, EncodingKey), FatalError> {
+    pub(super) fn build_as_encode_key(
+        &self,
+    ) -> Result<(String, Algorithm, EncodingKey), FatalError> {
         if self.form.is_der() {
             let build_from_der = match self.algorithm {
                 Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512 => EncodingKey::from_secret,
@@ -316,4 +409,9 @@ impl KeyForm {
     fn is_pem(&self) -> bool {
         matches!(self, KeyForm::PemInline | KeyForm::PemFile)
     }
+
+    #[inline]
+    fn is_file(&self) -> bool {
+        matches!(self, KeyForm::DerFile | KeyForm::PemFile)
+    }
 }
@@ -1,5 +1,19 @@
+mod audit;
+mod backup;
+mod config;
+mod demo;
+mod fanout;
+mod fsck;
+mod gc;
 mod jwt;
+mod keys;
+#[cfg(feature = "s3")]
+mod migrate;
+mod presign;
+mod remote;
 pub mod run;
+mod token;
+mod tui;
 
 use clap::{
     ColorChoice, Parser, Subcommand,
@@ -46,12 +60,142 @@ pub enum CliCommand {
 
     #[command(subcommand, about = "JWT management commands")]
     Jwt(jwt::Command),
+
+    #[command(subcommand, about = "Encryption key management commands")]
+    Keys(keys::Command),
+
+    #[command(about = "Re-wrap all data keys with the active master key")]
+    #[command(
+        long_about = r#"The same as `keys rewrap`: after a new master key is added and made active, re-wrap the data key of every bucket and every encrypted object with it. Object data is not re-encrypted, so the old master key can be removed once this is done."#
+    )]
+    RotateMasterKey(keys::RewrapArgs),
+
+    #[command(about = "Generate a time-limited presigned URL for an object")]
+    #[command(
+        long_about = r#"Generate a time-limited presigned URL for an object, anyone holding this URL can perform the signed method on this very object without an Authorization header."#
+    )]
+    Presign(presign::PresignArgs),
+
+    #[command(subcommand, about = "Audit log management commands")]
+    Audit(audit::Command),
+
+    #[command(subcommand, about = "Mint and inspect access tokens")]
+    #[command(
+        long_about = r#"Mint tokens with fine-grained permissions, or inspect a token and validate it against the keys and revocation list in the configuration file."#
+    )]
+    Token(token::Command),
+
+    #[command(about = "Check that object data and metadata agree")]
+    #[command(
+        long_about = r#"Replay the unfinished entries in the journal, then check every object's metadata against its data. Nothing is changed unless --repair is given, which should only be used while the server is stopped."#
+    )]
+    Fsck(fsck::FsckArgs),
+
+    #[command(about = "Find data without metadata, metadata without data and empty buckets")]
+    #[command(
+        long_about = r#"Scan the data and meta sources for objects that have data but no metadata, metadata but no data, and bucket directories with neither objects nor metadata. The result is printed as JSON. Nothing is deleted unless --delete is given; objects with unfinished journal entries are left to fsck."#
+    )]
+    Gc(gc::GcArgs),
+
+    #[command(about = "Convert flat buckets in a local data directory to the fan-out layout")]
+    #[command(
+        long_about = r#"Move the objects of flat buckets into hashed sub-directories, so that a bucket with many objects does not put them all in one directory. Buckets that are already converted are left as they are. The server must not be running; if interrupted, run it again to finish."#
+    )]
+    Fanout(fanout::FanOutArgs),
+
+    #[command(about = "Write a consistent snapshot of object data and metadata")]
+    #[command(
+        long_about = r#"Write every bucket's metadata and every object's metadata together with its stored data into a zstd compressed tar archive. The server may keep running: an object whose data does not match its metadata is read again, and skipped and reported if it still does not match. Objects are stored as they are kept, so restoring encrypted objects needs the same master keys."#
+    )]
+    Backup(backup::BackupArgs),
+
+    #[command(about = "Restore buckets and objects from a snapshot written by backup")]
+    #[command(
+        long_about = r#"Recreate the buckets in a snapshot written by `crab-vault backup`, writing each object's data before its metadata. Buckets that already exist are refused unless --overwrite is given. The server should not be running."#
+    )]
+    Restore(backup::RestoreArgs),
+
+    #[cfg(feature = "s3")]
+    #[command(about = "Copy objects from an S3 bucket into a bucket")]
+    #[command(
+        long_about = r#"Copy the objects of an S3 compatible bucket, such as one on MinIO, into a bucket, which is created when missing. Object keys become object names, `x-amz-meta-*` metadata becomes the user metadata, and the data is compressed and encrypted as configured. With --checkpoint, an interrupted import continues where it stopped."#
+    )]
+    Import(migrate::ImportArgs),
+
+    #[cfg(feature = "s3")]
+    #[command(about = "Copy the objects of a bucket into an S3 bucket")]
+    #[command(
+        long_about = r#"Copy the objects of a bucket into an S3 compatible bucket, such as one on MinIO. String values in the user metadata become `x-amz-meta-*` metadata, other values are written as JSON. Expired objects and objects encrypted with a customer-provided key are skipped. With --checkpoint, an interrupted export continues where it stopped."#
+    )]
+    Export(migrate::ExportArgs),
+
+    #[command(about = "Copy a file to an object, or an object to a file")]
+    #[command(
+        long_about = r#"Copy between a local file and an object on a running server. If SOURCE is an existing local file, or `-` for the standard input, it is uploaded to DESTINATION, which is `<bucket>/<object>`; when DESTINATION is only a bucket or ends with `/`, the file name is appended. Otherwise SOURCE is an object, downloaded to the file or directory DESTINATION, or to the standard output with `-`."#
+    )]
+    Cp(remote::CpArgs),
+
+    #[command(about = "List buckets, or the objects in a bucket")]
+    #[command(
+        long_about = r#"List the buckets on a running server, or the objects in `<bucket>/<prefix>`. Objects are grouped by `/` like directories unless --recursive is given."#
+    )]
+    Ls(remote::LsArgs),
+
+    #[command(about = "Delete objects or empty buckets")]
+    #[command(
+        long_about = r#"Delete objects or empty buckets on a running server. With --recursive, every object under the prefix is deleted, and a target without an object deletes the whole bucket with its objects."#
+    )]
+    Rm(remote::RmArgs),
+
+    #[command(about = "Show the metadata of a bucket or an object")]
+    #[command(
+        long_about = r#"Print the metadata of `<bucket>` or `<bucket>/<object>` on a running server as JSON."#
+    )]
+    Stat(remote::StatArgs),
+
+    #[command(about = "Browse a running server in an interactive terminal interface")]
+    #[command(
+        long_about = r#"Browse the buckets and objects on a running server, view their metadata and delete objects, watch the access log as it is written, and inspect tokens. The server is reached like with `ls`; the access log can only be watched on the same host when `access_log.sink` is `file`."#
+    )]
+    Tui(tui::TuiArgs),
+
+    #[command(about = "Run a throwaway server with sample data and a ready-to-use token")]
+    #[command(
+        long_about = r#"Start a server that keeps everything in memory, signs tokens with a key generated for this run and has a sample bucket with a few objects. No configuration file is read. A token with full access and curl commands to try are printed on startup."#
+    )]
+    Demo(demo::DemoArgs),
+
+    #[command(subcommand, about = "Configuration file management commands")]
+    #[command(
+        long_about = r#"Validate a configuration file without starting the server, so that a missing key file or a malformed path rule is caught before deploying, or write a commented configuration file to start from."#
+    )]
+    Config(config::Command),
 }
 
 /// 这是 [`Cli`] 的简短表现，用于判断将要执行那些操作而不获取对应的值
 pub enum Action {
     Run,
     Jwt,
+    Keys,
+    Presign,
+    Audit,
+    Token,
+    Fsck,
+    Gc,
+    Fanout,
+    Backup,
+    Restore,
+    #[cfg(feature = "s3")]
+    Import,
+    #[cfg(feature = "s3")]
+    Export,
+    Cp,
+    Ls,
+    Rm,
+    Stat,
+    Tui,
+    Demo,
+    Config,
 }
 
 impl CliCommand {
@@ -59,6 +203,26 @@ impl CliCommand {
         match self {
             CliCommand::Run(_) => Action::Run,
             CliCommand::Jwt(_) => Action::Jwt,
+            CliCommand::Keys(_) | CliCommand::RotateMasterKey(_) => Action::Keys,
+            CliCommand::Presign(_) => Action::Presign,
+            CliCommand::Audit(_) => Action::Audit,
+            CliCommand::Token(_) => Action::Token,
+            CliCommand::Fsck(_) => Action::Fsck,
+            CliCommand::Gc(_) => Action::Gc,
+            CliCommand::Fanout(_) => Action::Fanout,
+            CliCommand::Backup(_) => Action::Backup,
+            CliCommand::Restore(_) => Action::Restore,
+            #[cfg(feature = "s3")]
+            CliCommand::Import(_) => Action::Import,
+            #[cfg(feature = "s3")]
+            CliCommand::Export(_) => Action::Export,
+            CliCommand::Cp(_) => Action::Cp,
+            CliCommand::Ls(_) => Action::Ls,
+            CliCommand::Rm(_) => Action::Rm,
+            CliCommand::Stat(_) => Action::Stat,
+            CliCommand::Tui(_) => Action::Tui,
+            CliCommand::Demo(_) => Action::Demo,
+            CliCommand::Config(_) => Action::Config,
         }
     }
 }
@@ -66,7 +230,32 @@ impl CliCommand {
 pub async fn run() {
     let cli = Cli::parse();
     match cli.action() {
-        Action::Jwt | Action::Run => {
+        Action::Jwt
+        | Action::Keys
+        | Action::Presign
+        | Action::Audit
+        | Action::Token
+        | Action::Fsck
+        | Action::Gc
+        | Action::Fanout
+        | Action::Backup
+        | Action::Restore
+        | Action::Cp
+        | Action::Ls
+        | Action::Rm
+        | Action::Stat
+        | Action::Tui
+        | Action::Demo
+        | Action::Config
+        | Action::Run => {
+            let Cli {
+                subcommand,
+                config_path,
+            } = cli;
+            exec(subcommand, config_path).await
+        }
+        #[cfg(feature = "s3")]
+        Action::Import | Action::Export => {
             let Cli {
                 subcommand,
                 config_path,
@@ -87,6 +276,29 @@ async fn exec(subcommand: CliCommand, config_path: Option<String>) {
 
     match subcommand {
         CliCommand::Jwt(command) => jwt::exec(command, config_path),
+        CliCommand::Keys(command) => keys::exec(command, config_path).await,
+        CliCommand::RotateMasterKey(args) => {
+            keys::exec(keys::Command::Rewrap(args), config_path).await
+        }
+        CliCommand::Presign(args) => presign::exec(args, config_path),
+        CliCommand::Audit(command) => audit::exec(command, config_path),
+        CliCommand::Token(command) => token::exec(command, config_path),
+        CliCommand::Fsck(args) => fsck::exec(args, config_path).await,
+        CliCommand::Gc(args) => gc::exec(args, config_path).await,
+        CliCommand::Fanout(args) => fanout::exec(args, config_path).await,
+        CliCommand::Backup(args) => backup::backup(args, config_path).await,
+        CliCommand::Restore(args) => backup::restore(args, config_path).await,
+        #[cfg(feature = "s3")]
+        CliCommand::Import(args) => migrate::import(args, config_path).await,
+        #[cfg(feature = "s3")]
+        CliCommand::Export(args) => migrate::export(args, config_path).await,
+        CliCommand::Cp(args) => remote::cp(args, config_path).await,
+        CliCommand::Ls(args) => remote::ls(args, config_path).await,
+        CliCommand::Rm(args) => remote::rm(args, config_path).await,
+        CliCommand::Stat(args) => remote::stat(args, config_path).await,
+        CliCommand::Tui(args) => tui::exec(args, config_path).await,
+        CliCommand::Demo(args) => demo::exec(args).await,
+        CliCommand::Config(command) => config::exec(command, config_path),
         CliCommand::Run(arg) => crate::http::server::run(config_path, arg).await,
     }
 }
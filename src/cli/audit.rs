@@ -0,0 +1,282 @@
+use std::{
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use base64::{Engine, prelude::BASE64_STANDARD};
+use chrono::{DateTime, FixedOffset, Local, NaiveDateTime, TimeZone, Utc};
+use clap::{Args, Subcommand, error::ErrorKind};
+use crab_vault::logger::json::{DUMP_FILE_NAME_FORMAT, JsonLogRecords};
+use flate2::{Compression, write::GzEncoder};
+use jsonwebtoken::Algorithm;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    app_config::{self, ConfigItem, audit::SigningKey},
+    error::fatal::FatalError,
+};
+
+#[derive(Subcommand, Clone)]
+pub enum Command {
+    /// Export the dumped logs within a time range as a tar.gz bundle with a detached signature
+    #[command(name = "export")]
+    Export(ExportArgs),
+}
+
+/// 'export' 命令的参数
+#[derive(Args, Clone)]
+pub struct ExportArgs {
+    /// Export records logged at or after this moment, in RFC 3339, e.g. `2025-10-01T00:00:00+08:00`
+    #[arg(long)]
+    pub since: DateTime<FixedOffset>,
+
+    /// Export records logged before this moment, in RFC 3339
+    #[arg(long)]
+    pub until: DateTime<FixedOffset>,
+
+    /// Where to write the bundle, the signature is written next to it with an extra `.sig` suffix
+    #[arg(long, short)]
+    pub output: PathBuf,
+}
+
+/// 包中的 `manifest.json`
+#[derive(Serialize)]
+struct Manifest {
+    since: DateTime<FixedOffset>,
+    until: DateTime<FixedOffset>,
+    generated_at: DateTime<Utc>,
+    files: Vec<ManifestFile>,
+}
+
+#[derive(Serialize)]
+struct ManifestFile {
+    /// 在包中的路径
+    name: String,
+
+    /// 导出的记录条数
+    records: usize,
+
+    /// 因为没有时间而无法判断是否在范围内，没有被导出的记录条数
+    untimed: usize,
+
+    /// 源文件的末尾有无法解析的记录，通常是进程异常退出时写了一半的记录
+    truncated: bool,
+
+    /// 标准 base64 编码的 SHA-256
+    sha256: String,
+}
+
+/// 与包分开存放的签名文件
+#[derive(Serialize)]
+struct Signature {
+    kid: String,
+    algorithm: Algorithm,
+
+    /// 整个包的 SHA-256，标准 base64 编码，仅供快速比对
+    sha256: String,
+
+    /// 对整个包的签名，与 JWS 的签名部分编码相同
+    signature: String,
+}
+
+pub fn exec(cmd: Command, config_path: String) {
+    let config = app_config::StaticAppConfig::from_file(config_path)
+        .into_runtime()
+        .map_err(|e| e.exit_now())
+        .unwrap();
+
+    match cmd {
+        Command::Export(args) => export(
+            args,
+            config.audit.signing_key,
+            config.logger.dump_path.as_deref(),
+        ),
+    }
+    .map_err(|e| e.exit_now())
+    .unwrap()
+}
+
+fn export(
+    args: ExportArgs,
+    signing_key: Option<SigningKey>,
+    dump_path: Option<&str>,
+) -> Result<(), FatalError> {
+    let invalid = |msg: String, when: &str| {
+        FatalError::new(ErrorKind::InvalidValue, msg, Some(when.to_string()))
+    };
+
+    let ExportArgs {
+        since,
+        until,
+        output,
+    } = args;
+
+    let signing_key = signing_key.ok_or_else(|| {
+        invalid(
+            "no audit signing key configured, cannot sign the bundle".into(),
+            "while reading the `audit` section",
+        )
+    })?;
+    let dump_path = dump_path.ok_or_else(|| {
+        invalid(
+            "logs are not dumped to files, nothing to export".into(),
+            "while reading the `logger` section",
+        )
+    })?;
+
+    if since >= until {
+        return Err(invalid(
+            format!("`{since}` is not earlier than `{until}`"),
+            "while checking the time range",
+        ));
+    }
+
+    let generated_at = Utc::now();
+    let mut manifest = Manifest {
+        since,
+        until,
+        generated_at,
+        files: vec![],
+    };
+
+    let mut bundle = tar::Builder::new(GzEncoder::new(vec![], Compression::default()));
+
+    for path in dump_files(Path::new(dump_path), until)? {
+        let when = || format!("while reading the log file {}", path.display());
+        let content = fs::read_to_string(&path).map_err(|e| io_error(e, when()))?;
+
+        let (mut exported, mut untimed, mut truncated) = (String::new(), 0, false);
+        let mut records = 0;
+
+        for record in JsonLogRecords::new(&content) {
+            let Ok(record) = record else {
+                truncated = true;
+                break;
+            };
+
+            match record.time {
+                Some(time) if since <= time && time < until => {
+                    exported.push_str(record.raw);
+                    exported.push_str(",\n");
+                    records += 1;
+                }
+                Some(_) => {}
+                None => untimed += 1,
+            }
+        }
+
+        if records == 0 {
+            continue;
+        }
+
+        let name = format!(
+            "logs/{}",
+            path.file_name().unwrap_or_default().to_string_lossy()
+        );
+        append(&mut bundle, &name, exported.as_bytes(), generated_at)?;
+
+        manifest.files.push(ManifestFile {
+            name,
+            records,
+            untimed,
+            truncated,
+            sha256: BASE64_STANDARD.encode(Sha256::digest(exported.as_bytes())),
+        });
+    }
+
+    let manifest_json = serde_json::to_vec_pretty(&manifest)?;
+    append(&mut bundle, "manifest.json", &manifest_json, generated_at)?;
+
+    let bundle = bundle
+        .into_inner()
+        .and_then(|v| v.finish())
+        .map_err(|e| io_error(e, "while compressing the bundle".into()))?;
+
+    let signature = Signature {
+        kid: signing_key.kid,
+        algorithm: signing_key.algorithm,
+        sha256: BASE64_STANDARD.encode(Sha256::digest(&bundle)),
+        signature: jsonwebtoken::crypto::sign(&bundle, &signing_key.key, signing_key.algorithm)
+            .map_err(|e| {
+                FatalError::new(
+                    ErrorKind::Io,
+                    e.to_string(),
+                    Some("while signing the bundle".into()),
+                )
+            })?,
+    };
+
+    let mut signature_path = output.clone().into_os_string();
+    signature_path.push(".sig");
+    let signature_path = PathBuf::from(signature_path);
+
+    // 证据包不应该悄悄覆盖已有的文件
+    write_new(&output, &bundle)?;
+    write_new(&signature_path, &serde_json::to_vec_pretty(&signature)?)?;
+
+    let records: usize = manifest.files.iter().map(|v| v.records).sum();
+    println!(
+        "exported {records} records from {} log files into {}, signature written to {}",
+        manifest.files.len(),
+        output.display(),
+        signature_path.display()
+    );
+
+    Ok(())
+}
+
+/// 按文件名排序的所有日志文件，跳过那些在 `until` 之后才开始写入的文件
+fn dump_files(dump_path: &Path, until: DateTime<FixedOffset>) -> Result<Vec<PathBuf>, FatalError> {
+    let when = || format!("while listing log files in {}", dump_path.display());
+
+    let mut files = vec![];
+    for entry in fs::read_dir(dump_path).map_err(|e| io_error(e, when()))? {
+        let path = entry.map_err(|e| io_error(e, when()))?.path();
+        if path.extension().is_none_or(|v| v != "json") || !path.is_file() {
+            continue;
+        }
+
+        let started_at = path
+            .file_stem()
+            .and_then(|v| v.to_str())
+            .and_then(|v| NaiveDateTime::parse_from_str(v, DUMP_FILE_NAME_FORMAT).ok())
+            .and_then(|v| Local.from_local_datetime(&v).earliest());
+
+        if started_at.is_some_and(|v| v >= until) {
+            continue;
+        }
+
+        files.push(path);
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+pub(super) fn append<W: Write>(
+    bundle: &mut tar::Builder<W>,
+    name: &str,
+    data: &[u8],
+    mtime: DateTime<Utc>,
+) -> Result<(), FatalError> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(data.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(mtime.timestamp().max(0) as u64);
+
+    bundle
+        .append_data(&mut header, name, data)
+        .map_err(|e| io_error(e, format!("while adding {name} to the bundle")))
+}
+
+pub(super) fn io_error(e: std::io::Error, when: String) -> FatalError {
+    FatalError::new(ErrorKind::Io, e.to_string(), Some(when))
+}
+
+fn write_new(path: &Path, data: &[u8]) -> Result<(), FatalError> {
+    File::create_new(path)
+        .and_then(|mut v| v.write_all(data))
+        .map_err(|e| io_error(e, format!("while writing {}", path.display())))
+}
@@ -0,0 +1,487 @@
+use std::{
+    collections::HashSet,
+    fs::{self, File},
+    io::{Read, Write},
+    path::PathBuf,
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use clap::{Args, error::ErrorKind};
+use crab_vault::engine::{
+    BucketMeta, DataEngine, DataSource, MetaEngine, MetaSource, ObjectMeta,
+    error::{EngineError, EngineResult},
+    journal::{self, Intent, Journal},
+    list::ListObjectsQuery,
+    name,
+};
+use futures::TryStreamExt;
+use percent_encoding::utf8_percent_encode;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_config::{self, AppConfig, ConfigItem},
+    cli::{
+        audit::{append, io_error},
+        fsck::{engine_error, open_sources},
+    },
+    error::fatal::FatalError,
+    http::PATH_SEGMENT,
+};
+
+/// 快照格式的版本，格式不兼容地改变时增加
+const FORMAT_VERSION: u32 = 1;
+
+/// 快照中的 `manifest.json`，写在最后
+const MANIFEST: &str = "manifest.json";
+
+/// 数据与元数据对不上时最多读取的次数
+const READ_ATTEMPTS: u32 = 5;
+
+/// 两次读取之间的等待，让正在进行的写入有机会完成
+const RETRY_DELAY: Duration = Duration::from_millis(200);
+
+/// 'backup' 和 'restore' 共用的 bucket 过滤条件
+#[derive(Args, Clone)]
+pub struct BucketFilter {
+    /// Only this bucket, can be given more than once; all buckets when not given
+    #[arg(long, value_name = "BUCKET")]
+    pub include: Vec<String>,
+
+    /// Leave out this bucket, can be given more than once
+    #[arg(long, value_name = "BUCKET")]
+    pub exclude: Vec<String>,
+}
+
+/// 'backup' 命令的参数
+#[derive(Args, Clone)]
+pub struct BackupArgs {
+    /// Where to write the snapshot, a zstd compressed tar archive; an existing file is not overwritten
+    #[arg(long, short)]
+    pub output: PathBuf,
+
+    #[command(flatten)]
+    pub filter: BucketFilter,
+}
+
+/// 'restore' 命令的参数
+#[derive(Args, Clone)]
+pub struct RestoreArgs {
+    /// A snapshot written by `crab-vault backup`
+    #[arg(long, short)]
+    pub input: PathBuf,
+
+    #[command(flatten)]
+    pub filter: BucketFilter,
+
+    /// Restore into buckets that already exist, replacing objects with the same name
+    #[arg(long)]
+    pub overwrite: bool,
+}
+
+/// 快照的目录
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct Manifest {
+    version: u32,
+    created_at: DateTime<Utc>,
+    buckets: Vec<String>,
+    objects: usize,
+
+    /// 所有 object 保存的数据的字节数，即加密、压缩之后的大小
+    bytes: u64,
+
+    /// 没有被备份的 object
+    skipped: Vec<Skipped>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct Skipped {
+    bucket: String,
+    object: String,
+    reason: String,
+}
+
+/// 一个 object 在某一时刻的状态
+enum Pair {
+    /// 数据就是元数据描述的那份数据
+    Consistent(Box<ObjectMeta>, Vec<u8>),
+
+    /// 在备份的过程中被删除了
+    Gone,
+
+    /// 多次读取之后数据与元数据仍然对不上
+    Torn(&'static str),
+}
+
+impl BucketFilter {
+    fn matches(&self, bucket: &str) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|v| v == bucket))
+            && !self.exclude.iter().any(|v| v == bucket)
+    }
+}
+
+pub async fn backup(args: BackupArgs, config_path: String) {
+    let config = app_config::StaticAppConfig::from_file(config_path)
+        .into_runtime()
+        .map_err(|e| e.exit_now())
+        .unwrap();
+
+    let (data_src, meta_src) = open_sources(&config);
+    let journal = open_journal(&config).await;
+
+    let BackupArgs { output, filter } = args;
+    let file = File::create_new(&output)
+        .map_err(|e| io_error(e, format!("while creating {}", output.display())).exit_now())
+        .unwrap();
+
+    let written = async {
+        let encoder = zstd::Encoder::new(file, 0)
+            .map_err(|e| io_error(e, "while starting compression".into()))?;
+        let (manifest, encoder) =
+            write_snapshot(encoder, &filter, &data_src, &meta_src, journal.as_ref()).await?;
+        encoder
+            .finish()
+            .and_then(|v| v.sync_all())
+            .map_err(|e| io_error(e, format!("while writing {}", output.display())))?;
+        Ok::<_, FatalError>(manifest)
+    }
+    .await;
+
+    // 不完整的快照不应该留下来被误用
+    let manifest = written
+        .inspect_err(|_| {
+            let _ = fs::remove_file(&output);
+        })
+        .map_err(|e| e.exit_now())
+        .unwrap();
+
+    for skipped in &manifest.skipped {
+        println!("/{}/{}: {}", skipped.bucket, skipped.object, skipped.reason);
+    }
+    eprintln!(
+        "{} bucket(s) and {} object(s) ({} bytes) written to {}, {} skipped.",
+        manifest.buckets.len(),
+        manifest.objects,
+        manifest.bytes,
+        output.display(),
+        manifest.skipped.len()
+    );
+}
+
+pub async fn restore(args: RestoreArgs, config_path: String) {
+    let config = app_config::StaticAppConfig::from_file(config_path)
+        .into_runtime()
+        .map_err(|e| e.exit_now())
+        .unwrap();
+
+    let (data_src, meta_src) = open_sources(&config);
+    let journal = open_journal(&config).await;
+
+    let (buckets, objects) = restore_snapshot(&args, &data_src, &meta_src, journal.as_ref())
+        .await
+        .map_err(|e| e.exit_now())
+        .unwrap();
+
+    eprintln!(
+        "{buckets} bucket(s) and {objects} object(s) restored from {}.",
+        args.input.display()
+    );
+}
+
+pub(super) async fn open_journal(config: &AppConfig) -> Option<Journal> {
+    let dir = config.data.journal.as_ref()?;
+    Some(
+        Journal::open(dir)
+            .await
+            .map_err(|e| engine_error(e, "while opening the journal".into()).exit_now())
+            .unwrap(),
+    )
+}
+
+/// 依次写入每个 bucket 的元数据，以及其中每个 object 的元数据和紧跟着的数据，最后写入 [`Manifest`]
+async fn write_snapshot<W: Write>(
+    out: W,
+    filter: &BucketFilter,
+    data_src: &DataSource,
+    meta_src: &MetaSource,
+    journal: Option<&Journal>,
+) -> Result<(Manifest, W), FatalError> {
+    let created_at = Utc::now();
+    let mut snapshot = tar::Builder::new(out);
+    let mut manifest = Manifest {
+        version: FORMAT_VERSION,
+        created_at,
+        buckets: vec![],
+        objects: 0,
+        bytes: 0,
+        skipped: vec![],
+    };
+
+    let buckets = meta_src
+        .list_buckets_meta()
+        .await
+        .map_err(|e| engine_error(e, "while listing buckets".into()))?;
+
+    // 内部 bucket 中是服务端自己的状态，恢复到另一个实例中没有意义
+    let buckets = buckets
+        .iter()
+        .filter(|v| !name::is_internal_bucket(&v.name) && filter.matches(&v.name));
+
+    for bucket in buckets {
+        let when = || format!("while backing up bucket `{}`", bucket.name);
+        append(
+            &mut snapshot,
+            &bucket_entry(&bucket.name),
+            &serde_json::to_vec(bucket)?,
+            created_at,
+        )?;
+        manifest.buckets.push(bucket.name.clone());
+
+        let objects: Vec<String> = meta_src
+            .stream_objects_meta(&bucket.name, &ListObjectsQuery::default())
+            .map_ok(|v| v.object_name)
+            .try_collect()
+            .await
+            .map_err(|e| engine_error(e, when()))?;
+
+        for object in objects {
+            let pair = read_pair(data_src, meta_src, journal, &bucket.name, &object)
+                .await
+                .map_err(|e| engine_error(e, when()))?;
+
+            match pair {
+                Pair::Consistent(meta, stored) => {
+                    let entry = object_entry(&bucket.name, &object);
+                    append(
+                        &mut snapshot,
+                        &format!("{entry}.json"),
+                        &serde_json::to_vec(&meta)?,
+                        created_at,
+                    )?;
+                    append(&mut snapshot, &format!("{entry}.data"), &stored, created_at)?;
+                    manifest.objects += 1;
+                    manifest.bytes += stored.len() as u64;
+                }
+                Pair::Gone => {}
+                Pair::Torn(reason) => manifest.skipped.push(Skipped {
+                    bucket: bucket.name.clone(),
+                    object,
+                    reason: reason.into(),
+                }),
+            }
+        }
+    }
+
+    append(
+        &mut snapshot,
+        MANIFEST,
+        &serde_json::to_vec_pretty(&manifest)?,
+        created_at,
+    )?;
+    let out = snapshot
+        .into_inner()
+        .map_err(|e| io_error(e, "while finishing the snapshot".into()))?;
+
+    Ok((manifest, out))
+}
+
+/// 读出一个 object 的元数据和数据，只有数据与元数据一致时才算读到了
+///
+/// 上传先写数据再写元数据，服务运行时可能读到一半的写入，所以对不上时稍后重新读取
+async fn read_pair(
+    data_src: &DataSource,
+    meta_src: &MetaSource,
+    journal: Option<&Journal>,
+    bucket: &str,
+    object: &str,
+) -> EngineResult<Pair> {
+    for attempt in 0..READ_ATTEMPTS {
+        if attempt > 0 {
+            tokio::time::sleep(RETRY_DELAY).await;
+        }
+
+        let meta = match meta_src.read_object_meta(bucket, object).await {
+            Ok(meta) => meta,
+            Err(EngineError::ObjectMetaNotFound { .. }) => return Ok(Pair::Gone),
+            Err(e) => return Err(e),
+        };
+        match data_src.read_object(bucket, object).await {
+            Ok(stored) if journal::data_matches(&meta, &stored) => {
+                return Ok(Pair::Consistent(Box::new(meta), stored));
+            }
+            Ok(_)
+            | Err(EngineError::ObjectNotFound { .. } | EngineError::BucketNotFound { .. }) => {}
+            Err(e) => return Err(e),
+        }
+    }
+
+    // 意图日志中还有记录说明写入还没有完成，否则是需要 fsck 修复的不一致
+    let in_progress = match journal {
+        Some(journal) => journal
+            .pending()
+            .await?
+            .iter()
+            .any(|(_, v)| v.touches(bucket, object)),
+        None => false,
+    };
+    Ok(Pair::Torn(match in_progress {
+        true => "a write is still in progress",
+        false => "data and metadata disagree, see `crab-vault fsck`",
+    }))
+}
+
+/// 按照快照中的顺序写回，返回恢复的 bucket 和 object 的个数
+async fn restore_snapshot(
+    args: &RestoreArgs,
+    data_src: &DataSource,
+    meta_src: &MetaSource,
+    journal: Option<&Journal>,
+) -> Result<(usize, usize), FatalError> {
+    let invalid =
+        |msg: String, when: String| FatalError::new(ErrorKind::InvalidValue, msg, Some(when));
+    let input = args.input.display();
+
+    let file =
+        File::open(&args.input).map_err(|e| io_error(e, format!("while opening {input}")))?;
+    let decoder =
+        zstd::Decoder::new(file).map_err(|e| io_error(e, "while starting decompression".into()))?;
+    let mut archive = tar::Archive::new(decoder);
+    let entries = archive
+        .entries()
+        .map_err(|e| io_error(e, format!("while reading {input}")))?;
+
+    let mut restored = HashSet::new();
+    let mut objects = 0;
+    let mut manifest = None;
+
+    // 上一个 object 元数据的条目名称，以及需要恢复时它的元数据
+    let mut pending: Option<(String, Option<ObjectMeta>)> = None;
+
+    for entry in entries {
+        let mut entry = entry.map_err(|e| io_error(e, format!("while reading {input}")))?;
+        let path = entry
+            .path()
+            .map_err(|e| io_error(e, format!("while reading {input}")))?
+            .to_string_lossy()
+            .into_owned();
+        let when = || format!("while restoring `{path}` from {input}");
+
+        let mut content = vec![];
+        entry
+            .read_to_end(&mut content)
+            .map_err(|e| io_error(e, when()))?;
+
+        if path == MANIFEST {
+            let read: Manifest = serde_json::from_slice(&content)?;
+            if read.version != FORMAT_VERSION {
+                return Err(invalid(
+                    format!("snapshot format version {} is not supported", read.version),
+                    when(),
+                ));
+            }
+            manifest = Some(read);
+        } else if path.starts_with("buckets/") {
+            let bucket: BucketMeta = serde_json::from_slice(&content)?;
+            if name::is_internal_bucket(&bucket.name) || !args.filter.matches(&bucket.name) {
+                continue;
+            }
+
+            let exists = match meta_src.read_bucket_meta(&bucket.name).await {
+                Ok(_) => true,
+                Err(EngineError::BucketMetaNotFound { .. }) => false,
+                Err(e) => return Err(engine_error(e, when())),
+            };
+            if exists && !args.overwrite {
+                return Err(invalid(
+                    format!(
+                        "bucket `{}` already exists, pass --overwrite to restore into it",
+                        bucket.name
+                    ),
+                    when(),
+                ));
+            }
+
+            data_src
+                .create_bucket(&bucket.name)
+                .await
+                .map_err(|e| engine_error(e, when()))?;
+            meta_src
+                .create_bucket_meta(&bucket)
+                .await
+                .map_err(|e| engine_error(e, when()))?;
+            restored.insert(bucket.name);
+        } else if let Some(entry) = path.strip_suffix(".json") {
+            let meta: ObjectMeta = serde_json::from_slice(&content)?;
+            let wanted = restored.contains(&meta.bucket_name);
+            pending = Some((entry.to_string(), wanted.then_some(meta)));
+        } else if let Some(entry) = path.strip_suffix(".data") {
+            let meta = match pending.take() {
+                Some((name, meta)) if name == entry => meta,
+                _ => return Err(invalid("the object has no metadata".into(), when())),
+            };
+            let Some(meta) = meta else {
+                continue;
+            };
+            if !journal::data_matches(&meta, &content) {
+                return Err(invalid(
+                    "the data does not match its metadata, the snapshot is corrupted".into(),
+                    when(),
+                ));
+            }
+
+            write_object(data_src, meta_src, journal, &meta, &content)
+                .await
+                .map_err(|e| engine_error(e, when()))?;
+            objects += 1;
+        } else {
+            return Err(invalid("unknown entry".into(), when()));
+        }
+    }
+
+    if manifest.is_none() {
+        return Err(invalid(
+            "no manifest found, the snapshot is incomplete".into(),
+            format!("while reading {input}"),
+        ));
+    }
+
+    Ok((restored.len(), objects))
+}
+
+/// 与上传一样先写数据再写元数据，配置了意图日志时中途崩溃可以由 fsck 完成
+pub(super) async fn write_object(
+    data_src: &DataSource,
+    meta_src: &MetaSource,
+    journal: Option<&Journal>,
+    meta: &ObjectMeta,
+    stored: &[u8],
+) -> EngineResult<()> {
+    let id = match journal {
+        Some(journal) => Some(journal.begin(&Intent::put(meta)).await?),
+        None => None,
+    };
+
+    data_src
+        .create_object(&meta.bucket_name, &meta.object_name, stored)
+        .await?;
+    meta_src.create_object_meta(meta).await?;
+
+    if let (Some(journal), Some(id)) = (journal, id) {
+        journal.finish(id).await?;
+    }
+    Ok(())
+}
+
+fn bucket_entry(bucket: &str) -> String {
+    format!("buckets/{bucket}.json")
+}
+
+/// object 的元数据和数据分别是加上 `.json` 和 `.data` 后缀的条目
+fn object_entry(bucket: &str, object: &str) -> String {
+    format!(
+        "objects/{bucket}/{}",
+        utf8_percent_encode(object, PATH_SEGMENT)
+    )
+}
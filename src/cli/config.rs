@@ -0,0 +1,219 @@
+mod edit;
+mod schema;
+
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::PathBuf,
+};
+
+use base64::{Engine, prelude::BASE64_STANDARD};
+use clap::{Args, Subcommand, error::ErrorKind};
+use toml_edit::{DocumentMut, Item};
+
+use crate::{
+    app_config::{ConfigItem, StaticAppConfig},
+    cli::config::{edit::KeyPath, schema::Schema},
+    error::fatal::{FatalError, MultiFatalError},
+    http::server,
+};
+
+/// `config init` 写入的配置文件，`{{jwt_key}}` 会被替换为随机生成的密钥
+const TEMPLATE: &str = include_str!("config/crab-vault.toml");
+
+#[derive(Subcommand, Clone)]
+pub enum Command {
+    /// Load the configuration file and build everything the server would, without starting it
+    #[command(name = "validate")]
+    Validate,
+
+    /// Write a commented configuration file with a freshly generated JWT key
+    #[command(name = "init")]
+    Init(InitArgs),
+
+    /// Print a value in the configuration file, or the whole file if no key is given
+    #[command(name = "show")]
+    Show(ShowArgs),
+
+    /// Set a value in the configuration file, keeping its comments and layout
+    #[command(name = "set")]
+    Set(SetArgs),
+
+    /// Remove a value from the configuration file so that its default is used again
+    #[command(name = "unset")]
+    Unset(UnsetArgs),
+}
+
+/// 'init' 命令的参数
+#[derive(Args, Clone)]
+pub struct InitArgs {
+    /// Where to write the configuration file
+    #[arg(long, short, default_value = "crab-vault.toml")]
+    pub output: PathBuf,
+
+    /// Overwrite the file if it already exists
+    #[arg(long)]
+    pub force: bool,
+}
+
+/// 'show' 命令的参数
+#[derive(Args, Clone)]
+pub struct ShowArgs {
+    /// Dotted key with optional indices, e.g. `server.port` or `auth.path_rules[0]`
+    pub key: Option<String>,
+}
+
+/// 'set' 命令的参数
+#[derive(Args, Clone)]
+pub struct SetArgs {
+    /// Dotted key with optional indices, e.g. `server.port` or `auth.path_rules[0].pattern`,
+    /// an index equal to the length of the array appends to it
+    pub key: String,
+
+    /// A TOML or JSON literal such as `8080`, `true`, `["GET", "HEAD"]` or `{ pattern = "*" }`,
+    /// anything else is taken as a string, so quote it (`'"true"'`) to set a string that looks like a literal
+    pub value: String,
+}
+
+/// 'unset' 命令的参数
+#[derive(Args, Clone)]
+pub struct UnsetArgs {
+    /// Dotted key with optional indices, e.g. `server.admin_port` or `auth.path_rules[1]`
+    pub key: String,
+}
+
+pub fn exec(cmd: Command, config_path: String) {
+    let result = match cmd {
+        Command::Validate => return validate(config_path).map_err(|e| e.exit_now()).unwrap(),
+        Command::Init(args) => init(args),
+        Command::Show(args) => show(args, config_path),
+        Command::Set(args) => set(args, config_path),
+        Command::Unset(args) => unset(args, config_path),
+    };
+    result.map_err(|e| e.exit_now()).unwrap()
+}
+
+/// 与启动服务时一样读取配置文件和环境变量，报告所有的错误，但不会打开数据和元数据后端
+fn validate(config_path: String) -> Result<(), MultiFatalError> {
+    let config = StaticAppConfig::try_from_file(&config_path).map_err(|e| {
+        let mut errors = MultiFatalError::new();
+        errors.push(e);
+        errors
+    })?;
+
+    let mut errors = MultiFatalError::new();
+    let _ = config.clone().error_recorded(&mut errors);
+    if let Err(mut e) = server::check(&config) {
+        errors.append(&mut e);
+    }
+
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    println!("{config_path} is valid");
+    Ok(())
+}
+
+fn init(InitArgs { output, force }: InitArgs) -> Result<(), FatalError> {
+    let when = || format!("while writing {}", output.display());
+    let key = BASE64_STANDARD.encode(rand::random::<[u8; 32]>());
+
+    let mut file = match force {
+        true => OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&output),
+        false => OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&output),
+    }
+    .map_err(|e| match e.kind() {
+        io::ErrorKind::AlreadyExists => FatalError::new(
+            ErrorKind::Io,
+            format!(
+                "{} already exists, use --force to overwrite it",
+                output.display()
+            ),
+            None,
+        ),
+        _ => FatalError::from(e).when(when()),
+    })?;
+
+    file.write_all(TEMPLATE.replace("{{jwt_key}}", &key).as_bytes())
+        .map_err(|e| FatalError::from(e).when(when()))?;
+
+    println!("{}", output.display());
+    Ok(())
+}
+
+fn show(ShowArgs { key }: ShowArgs, config_path: String) -> Result<(), FatalError> {
+    let document = read_document(&config_path, false)?;
+    let Some(key) = key else {
+        print!("{document}");
+        return Ok(());
+    };
+
+    let path = KeyPath::parse(&key)?;
+    match path.get(document.as_item()) {
+        Some(Item::Value(value)) => println!("{}", value.to_string().trim()),
+        Some(item) => print!("{item}"),
+        None => {
+            return Err(FatalError::new(
+                ErrorKind::InvalidValue,
+                format!("`{path}` is not set in {config_path}"),
+                None,
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn set(SetArgs { key, value }: SetArgs, config_path: String) -> Result<(), FatalError> {
+    let mut document = read_document(&config_path, true)?;
+    let (path, value) = (KeyPath::parse(&key)?, edit::parse_value(&value)?);
+    Schema::of_config().check(&path, &value)?;
+    path.set(document.as_item_mut(), value)?;
+    write_document(&document, &config_path)
+}
+
+fn unset(UnsetArgs { key }: UnsetArgs, config_path: String) -> Result<(), FatalError> {
+    let mut document = read_document(&config_path, false)?;
+    let path = KeyPath::parse(&key)?;
+    path.unset(document.as_item_mut())?;
+    write_document(&document, &config_path)
+}
+
+/// `missing_ok` 为 `true` 时，文件不存在视为空的配置文件
+fn read_document(config_path: &str, missing_ok: bool) -> Result<DocumentMut, FatalError> {
+    let content = match fs::read_to_string(config_path) {
+        Ok(content) => content,
+        Err(e) if missing_ok && e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(FatalError::from(e).when(format!("while reading {config_path}"))),
+    };
+
+    content
+        .parse::<DocumentMut>()
+        .map_err(|e| FatalError::from(e).when(format!("while parsing {config_path}")))
+}
+
+/// 修改之后的配置文件仍然能被解析时才会写入，[`Schema`] 不知道结构的那些配置项也要经过这里的检查
+fn write_document(document: &DocumentMut, config_path: &str) -> Result<(), FatalError> {
+    let content = document.to_string();
+    config::Config::builder()
+        .add_source(config::File::from_str(&content, config::FileFormat::Toml))
+        .build()
+        .and_then(|v| v.try_deserialize::<StaticAppConfig>())
+        .map_err(|e| {
+            FatalError::new(
+                ErrorKind::InvalidValue,
+                e.to_string(),
+                Some(format!("{config_path} is left unchanged")),
+            )
+        })?;
+
+    fs::write(config_path, content)
+        .map_err(|e| FatalError::from(e).when(format!("while writing {config_path}")))
+}
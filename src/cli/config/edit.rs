@@ -0,0 +1,316 @@
+use std::fmt;
+
+use clap::error::ErrorKind;
+use toml_edit::{Array, InlineTable, Item, Table, Value};
+
+use crate::error::fatal::FatalError;
+
+/// 路径中的一段，`auth.path_rules[0].pattern` 由 `auth`、`path_rules`、`0`、`pattern` 四段组成
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+/// 配置项的路径，例如 `server.port` 和 `auth.path_rules[0].pattern`
+#[derive(Clone, Debug)]
+pub struct KeyPath(Vec<Segment>);
+
+impl KeyPath {
+    pub fn parse(path: &str) -> Result<Self, FatalError> {
+        let malformed = || invalid(format!("`{path}` is not a valid key path"));
+
+        let mut segments = vec![];
+        for part in path.split('.') {
+            let (key, mut indices) = part.split_at(part.find('[').unwrap_or(part.len()));
+            if key.is_empty() {
+                return Err(malformed());
+            }
+            segments.push(Segment::Key(key.into()));
+
+            while !indices.is_empty() {
+                let (index, rest) = indices
+                    .strip_prefix('[')
+                    .and_then(|v| v.split_once(']'))
+                    .ok_or_else(malformed)?;
+                segments.push(Segment::Index(index.parse().map_err(|_| malformed())?));
+                indices = rest;
+            }
+        }
+
+        Ok(Self(segments))
+    }
+
+    pub fn segments(&self) -> &[Segment] {
+        &self.0
+    }
+
+    /// 读取这个路径上的值
+    pub fn get<'a>(&self, root: &'a Item) -> Option<&'a Item> {
+        self.0
+            .iter()
+            .try_fold(root, |item, segment| match segment {
+                Segment::Key(key) => item.get(key.as_str()),
+                Segment::Index(index) => item.get(*index),
+            })
+            .filter(|v| !v.is_none())
+    }
+
+    /// 写入这个路径上的值，缺少的表会被创建；下标等于数组的长度时追加到数组的末尾
+    pub fn set(&self, root: &mut Item, value: Value) -> Result<(), FatalError> {
+        let (last, parent) = self.split_last();
+        let parent = self.walk_mut(root, parent.len(), true)?;
+
+        match (parent, last) {
+            (Item::Table(table), Segment::Key(key)) => {
+                table.insert(key, Item::Value(value));
+            }
+            (Item::Value(Value::InlineTable(table)), Segment::Key(key)) => {
+                table.insert(key, value);
+            }
+            (Item::Value(Value::Array(array)), Segment::Index(index)) => match *index {
+                i if i < array.len() => {
+                    array.replace(i, value);
+                }
+                i if i == array.len() => append(array, value),
+                _ => return Err(self.out_of_range(array.len())),
+            },
+            (Item::ArrayOfTables(array), Segment::Index(index)) => {
+                let Value::InlineTable(table) = value else {
+                    return Err(invalid(format!(
+                        "`{self}` is an element of an array of tables, the value must be a table"
+                    )));
+                };
+                match *index {
+                    i if i < array.len() => {
+                        *array.get_mut(i).expect("checked above") = table.into_table();
+                    }
+                    i if i == array.len() => array.push(table.into_table()),
+                    _ => return Err(self.out_of_range(array.len())),
+                }
+            }
+            _ => return Err(self.mismatched()),
+        }
+
+        Ok(())
+    }
+
+    /// 删除这个路径上的值
+    pub fn unset(&self, root: &mut Item) -> Result<(), FatalError> {
+        let (last, parent) = self.split_last();
+        let parent = self.walk_mut(root, parent.len(), false)?;
+
+        let removed = match (parent, last) {
+            (Item::Table(table), Segment::Key(key)) => table.remove(key).is_some(),
+            (Item::Value(Value::InlineTable(table)), Segment::Key(key)) => {
+                table.remove(key).is_some()
+            }
+            (Item::Value(Value::Array(array)), Segment::Index(index)) => {
+                let found = *index < array.len();
+                if found {
+                    remove(array, *index);
+                }
+                found
+            }
+            (Item::ArrayOfTables(array), Segment::Index(index)) => {
+                let found = *index < array.len();
+                if found {
+                    array.remove(*index);
+                }
+                found
+            }
+            _ => return Err(self.mismatched()),
+        };
+
+        match removed {
+            true => Ok(()),
+            false => Err(self.not_found()),
+        }
+    }
+
+    fn split_last(&self) -> (&Segment, &[Segment]) {
+        self.0.split_last().expect("a key path is never empty")
+    }
+
+    /// 沿着路径的前 `depth` 段向下查找，`create` 为 `true` 时创建缺少的表和数组
+    fn walk_mut<'a>(
+        &self,
+        mut item: &'a mut Item,
+        depth: usize,
+        create: bool,
+    ) -> Result<&'a mut Item, FatalError> {
+        for (segment, next) in self.0[..depth].iter().zip(&self.0[1..]) {
+            if create && let Segment::Key(key) = segment {
+                create_child(item, key, matches!(next, Segment::Index(_)));
+            }
+
+            item = match segment {
+                Segment::Key(key) => item.get_mut(key.as_str()),
+                Segment::Index(index) => item.get_mut(*index),
+            }
+            .filter(|v| !v.is_none())
+            .ok_or_else(|| self.not_found())?;
+        }
+
+        Ok(item)
+    }
+
+    fn not_found(&self) -> FatalError {
+        invalid(format!("`{self}` does not exist"))
+    }
+
+    fn mismatched(&self) -> FatalError {
+        invalid(format!(
+            "`{self}` does not match the configuration file, a key must follow a table and an index must follow an array"
+        ))
+    }
+
+    fn out_of_range(&self, len: usize) -> FatalError {
+        invalid(format!(
+            "`{self}` is out of range, the array has {len} elements, use index {len} to append"
+        ))
+    }
+}
+
+impl fmt::Display for KeyPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, segment) in self.0.iter().enumerate() {
+            match segment {
+                Segment::Key(key) if i == 0 => write!(f, "{key}")?,
+                Segment::Key(key) => write!(f, ".{key}")?,
+                Segment::Index(index) => write!(f, "[{index}]")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl From<&[Segment]> for KeyPath {
+    fn from(segments: &[Segment]) -> Self {
+        Self(segments.to_vec())
+    }
+}
+
+/// ## 解析命令行中的值
+///
+/// 依次尝试 TOML 字面量（`8080`、`true`、`["GET", "HEAD"]`、`{ pattern = "*" }`）和 JSON，
+/// 都不是时作为字符串，所以 `info` 和 `images/*` 不需要加引号，而 `"true"` 需要
+pub fn parse_value(raw: &str) -> Result<Value, FatalError> {
+    if let Ok(value) = raw.parse::<Value>() {
+        return Ok(value);
+    }
+
+    let trimmed = raw.trim_start();
+    if trimmed.starts_with('[') || trimmed.starts_with('{') {
+        let json = serde_json::from_str(raw).map_err(|e| {
+            invalid(format!(
+                "`{raw}` is neither a TOML nor a JSON array or table, details: {e}"
+            ))
+        })?;
+        return from_json(json);
+    }
+
+    Ok(Value::from(raw))
+}
+
+fn from_json(value: serde_json::Value) -> Result<Value, FatalError> {
+    use serde_json::Value as Json;
+
+    Ok(match value {
+        Json::Null => {
+            return Err(invalid(
+                "TOML has no `null`, use `config unset` to remove a value".into(),
+            ));
+        }
+        Json::Bool(v) => v.into(),
+        Json::Number(v) => match v.as_i64() {
+            Some(v) => v.into(),
+            None => v.as_f64().unwrap_or(f64::MAX).into(),
+        },
+        Json::String(v) => v.into(),
+        Json::Array(values) => Value::Array(
+            values
+                .into_iter()
+                .map(from_json)
+                .collect::<Result<Array, _>>()?,
+        ),
+        Json::Object(entries) => {
+            let mut table = InlineTable::new();
+            for (key, value) in entries {
+                table.insert(key, from_json(value)?);
+            }
+            Value::InlineTable(table)
+        }
+    })
+}
+
+/// 追加到数组的末尾，沿用最后一个元素的换行和缩进
+fn append(array: &mut Array, mut value: Value) {
+    let Some(last) = array.len().checked_sub(1).and_then(|i| array.get_mut(i)) else {
+        array.push(value);
+        return;
+    };
+
+    let prefix = match last.decor().prefix().and_then(|v| v.as_str()) {
+        Some(prefix) if prefix.contains('\n') => prefix.to_string(),
+        _ => " ".to_string(),
+    };
+    let suffix = last.decor().suffix().cloned();
+    last.decor_mut().set_suffix("");
+
+    value.decor_mut().set_prefix(prefix);
+    if let Some(suffix) = suffix {
+        value.decor_mut().set_suffix(suffix);
+    }
+    array.push_formatted(value);
+}
+
+/// 从数组中删除，第一个元素前面和最后一个元素后面的空白交给新的第一个和最后一个元素
+fn remove(array: &mut Array, index: usize) {
+    let removed = array.remove(index);
+    let decor = removed.decor();
+
+    if index == 0
+        && let (Some(first), Some(prefix)) = (array.get_mut(0), decor.prefix())
+    {
+        first.decor_mut().set_prefix(prefix.clone());
+    }
+    if index == array.len()
+        && let (Some(last), Some(suffix)) = (
+            index.checked_sub(1).and_then(|i| array.get_mut(i)),
+            decor.suffix(),
+        )
+    {
+        last.decor_mut().set_suffix(suffix.clone());
+    }
+}
+
+/// 在 `item` 中创建名为 `key` 的空表或者空数组，已经存在时什么也不做
+fn create_child(item: &mut Item, key: &str, array: bool) {
+    let child = || match array {
+        true => Value::Array(Array::new()),
+        false => Value::InlineTable(InlineTable::new()),
+    };
+
+    match item {
+        Item::Table(table) if !table.contains_key(key) => {
+            let child = match array {
+                true => Item::Value(child()),
+                false => {
+                    let mut child = Table::new();
+                    child.set_implicit(true);
+                    Item::Table(child)
+                }
+            };
+            table.insert(key, child);
+        }
+        Item::Value(Value::InlineTable(table)) if !table.contains_key(key) => {
+            table.insert(key, child());
+        }
+        _ => {}
+    }
+}
+
+fn invalid(message: String) -> FatalError {
+    FatalError::new(ErrorKind::InvalidValue, message, None)
+}
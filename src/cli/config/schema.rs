@@ -0,0 +1,178 @@
+use std::{collections::BTreeMap, fmt};
+
+use clap::error::ErrorKind;
+use toml_edit::Value;
+
+use crate::{
+    app_config::StaticAppConfig,
+    cli::config::edit::{KeyPath, Segment},
+    error::fatal::FatalError,
+};
+
+/// ## 配置项的结构
+///
+/// 由序列化之后的 [`StaticAppConfig::default()`] 得到，新增的配置项和配置块不需要在这里登记。
+/// 默认值为 `None` 的配置项和默认为空的数组无法知道里面是什么，它们是 [`Schema::Any`]，
+/// 交给写入前对整个文件的解析去检查
+#[derive(Debug)]
+pub enum Schema {
+    Any,
+    Bool,
+    Integer,
+    Float,
+    String,
+    Array(Box<Schema>),
+    Table(BTreeMap<String, Schema>),
+}
+
+impl Schema {
+    pub fn of_config() -> Self {
+        let value = serde_json::to_value(StaticAppConfig::default())
+            .expect("the default configuration is always serializable");
+        Self::from_json(value)
+    }
+
+    fn from_json(value: serde_json::Value) -> Self {
+        use serde_json::Value as Json;
+
+        match value {
+            Json::Null => Self::Any,
+            Json::Bool(_) => Self::Bool,
+            Json::Number(v) if v.is_f64() => Self::Float,
+            Json::Number(_) => Self::Integer,
+            Json::String(_) => Self::String,
+            Json::Array(values) => Self::Array(Box::new(
+                values.into_iter().next().map_or(Self::Any, Self::from_json),
+            )),
+            Json::Object(entries) => Self::Table(
+                entries
+                    .into_iter()
+                    .map(|(key, value)| (key, Self::from_json(value)))
+                    .collect(),
+            ),
+        }
+    }
+
+    /// 检查 `path` 是否是有效的配置项，以及 `value` 的类型是否与它相符
+    pub fn check(&self, path: &KeyPath, value: &Value) -> Result<(), FatalError> {
+        let mut schema = self;
+        for (depth, segment) in path.segments().iter().enumerate() {
+            let walked = || KeyPath::from(&path.segments()[..depth]).to_string();
+            schema = match (schema, segment) {
+                (Self::Any, _) => return Ok(()),
+                (Self::Table(fields), Segment::Key(key)) => match fields.get(key) {
+                    Some(schema) => schema,
+                    None => return Err(unknown_key(&walked(), key, fields)),
+                },
+                (Self::Array(element), Segment::Index(_)) => element,
+                (schema, _) => {
+                    return Err(invalid(format!(
+                        "`{}` is {schema}, `{path}` does not exist",
+                        walked()
+                    )));
+                }
+            };
+        }
+
+        schema.check_value(&path.to_string(), value)
+    }
+
+    fn check_value(&self, path: &str, value: &Value) -> Result<(), FatalError> {
+        match (self, value) {
+            (Self::Any, _)
+            | (Self::Bool, Value::Boolean(_))
+            | (Self::Integer, Value::Integer(_))
+            | (Self::Float, Value::Float(_) | Value::Integer(_))
+            | (Self::String, Value::String(_)) => Ok(()),
+            (Self::Array(element), Value::Array(values)) => values
+                .iter()
+                .enumerate()
+                .try_for_each(|(i, value)| element.check_value(&format!("{path}[{i}]"), value)),
+            (Self::Table(fields), Value::InlineTable(table)) => {
+                table.iter().try_for_each(|(key, value)| match fields.get(key) {
+                    Some(schema) => schema.check_value(&format!("{path}.{key}"), value),
+                    None => Err(unknown_key(path, key, fields)),
+                })
+            }
+            (schema, _) => Err(invalid(format!("`{path}` expects {schema}"))),
+        }
+    }
+}
+
+impl fmt::Display for Schema {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Any => "a value",
+            Self::Bool => "a boolean",
+            Self::Integer => "an integer",
+            Self::Float => "a number",
+            Self::String => "a string",
+            Self::Array(_) => "an array",
+            Self::Table(_) => "a table",
+        })
+    }
+}
+
+/// 报告拼错的配置项，同时列出这一层所有有效的配置项和它们的类型
+fn unknown_key(parent: &str, key: &str, fields: &BTreeMap<String, Schema>) -> FatalError {
+    let (name, under) = match parent.is_empty() {
+        true => (key.to_string(), "at the top level".to_string()),
+        false => (format!("{parent}.{key}"), format!("under `{parent}`")),
+    };
+    let valid = fields
+        .iter()
+        .map(|(key, schema)| format!("`{key}` ({schema})"))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    FatalError::new(
+        ErrorKind::InvalidValue,
+        format!("`{name}` is not a configuration key"),
+        Some(format!("valid keys {under} are {valid}")),
+    )
+}
+
+fn invalid(message: String) -> FatalError {
+    FatalError::new(ErrorKind::InvalidValue, message, None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::config::edit::parse_value;
+
+    fn check(path: &str, value: &str) -> Result<(), String> {
+        let (path, value) = (KeyPath::parse(path).unwrap(), parse_value(value).unwrap());
+        Schema::of_config()
+            .check(&path, &value)
+            .map_err(FatalError::into_message)
+    }
+
+    #[test]
+    fn test_check() {
+        check("server.port", "8080").unwrap();
+        check("logger.level", "debug").unwrap();
+        check("server.cors.allowed_methods", r#"["GET", "HEAD"]"#).unwrap();
+        check("server.cors.allowed_methods[6]", "OPTIONS").unwrap();
+        check("server.cors", "{ max_age = 60, allow_credentials = true }").unwrap();
+
+        // 默认为空或者为 `None` 的配置项不知道结构，交给整个文件的解析
+        check("auth.path_rules[0]", r#"{ pattern = "*" }"#).unwrap();
+        check("server.rate_limit.global", "{ requests = 10 }").unwrap();
+
+        let e = check("server.prot", "8080").unwrap_err();
+        assert!(e.contains("`server.prot` is not a configuration key"), "{e}");
+        assert!(e.contains("`port` (an integer)"), "{e}");
+        let e = check("sever", "{}").unwrap_err();
+        assert!(e.contains("at the top level"), "{e}");
+        let e = check("server.cors", "{ max_agee = 60 }").unwrap_err();
+        assert!(e.contains("`server.cors.max_agee`"), "{e}");
+
+        let e = check("server.port", "eighty").unwrap_err();
+        assert!(e.contains("`server.port` expects an integer"), "{e}");
+        let e = check("server.cors.allowed_methods", "[1]").unwrap_err();
+        assert!(e.contains("`server.cors.allowed_methods[0]` expects a string"), "{e}");
+        let e = check("server.port[0]", "1").unwrap_err();
+        assert!(e.contains("`server.port` is an integer"), "{e}");
+    }
+}
@@ -0,0 +1,193 @@
+use base64::{Engine, prelude::BASE64_STANDARD};
+use chrono::{DateTime, Duration};
+use clap::{Args, error::ErrorKind};
+use crab_vault::{
+    auth::{Jwt, Permission, PermissionSet},
+    engine::{BucketMeta, DataEngine, DataSource, MetaEngine, MetaSource, ObjectMeta},
+};
+
+use crate::{
+    app_config::{AppConfig, ConfigItem, StaticAppConfig},
+    cli::presign::parse_duration,
+    error::fatal::FatalError,
+    http::{api::API_VERSION_PREFIX, server},
+    logger,
+};
+
+/// 演示用的令牌的签发者，也是唯一被接受的签发者
+const ISSUER: &str = "crab-vault-demo";
+
+const KID: &str = "demo";
+
+const BUCKET: &str = "demo";
+
+/// 预先放入 [`BUCKET`] 中的 object，依次是名称、content type 和内容
+const OBJECTS: [(&str, &str, &str); 3] = [
+    ("hello.txt", "text/plain", "Hello from Crab Vault!\n"),
+    (
+        "notes/todo.md",
+        "text/markdown",
+        "# TODO\n\n- try the demo\n",
+    ),
+    ("config.json", "application/json", "{\"demo\": true}\n"),
+];
+
+/// 'demo' 命令的参数
+#[derive(Args, Clone)]
+pub struct DemoArgs {
+    /// Listening port number of the demo server
+    #[arg(long, short = 'p', default_value_t = 32767)]
+    pub port: u16,
+
+    /// How long the printed token stays valid, e.g. `30m`, `1h`, `7d`
+    #[arg(long, value_parser = parse_duration, default_value = "1d")]
+    pub expires_in: Duration,
+}
+
+/// 不读取配置文件，所有的内容都保存在内存中，签名密钥在每次启动时随机生成
+pub async fn exec(args: DemoArgs) {
+    let config = demo_config(args.port)
+        .map_err(|e| e.exit_now())
+        .unwrap()
+        .into_runtime()
+        .map_err(|e| e.exit_now())
+        .unwrap();
+
+    logger::init(config.logger.clone());
+
+    let data_src = DataSource::new("mem://").unwrap();
+    let meta_src = MetaSource::new("mem://").unwrap();
+    populate(&data_src, &meta_src)
+        .await
+        .map_err(|e| e.exit_now())
+        .unwrap();
+
+    let (token, expires_at) = issue(&config, args.expires_in)
+        .map_err(|e| e.exit_now())
+        .unwrap();
+    print_usage(args.port, &token, expires_at);
+
+    server::serve(config, None, data_src, meta_src, None).await
+}
+
+fn demo_config(port: u16) -> Result<StaticAppConfig, FatalError> {
+    let key = BASE64_STANDARD.encode(rand::random::<[u8; 32]>());
+    let key =
+        format!(r#"{{algorithm = "HS256", form = "der_inline", kid = "{KID}", key = "{key}"}}"#);
+    let toml = format!(
+        r#"
+        [server]
+        port = {port}
+
+        [data]
+        source = "mem://"
+
+        [meta]
+        source = "mem://"
+
+        [auth.jwt_encoder_config]
+        encoding_keys = [{key}]
+        issue_as = "{ISSUER}"
+        audience = ["{ISSUER}"]
+
+        [auth.jwt_decoder_config]
+        decoding_keys = [["{ISSUER}", {key}]]
+        audience = ["{ISSUER}"]
+
+        [auth.key_reload]
+        interval = 0
+        "#
+    );
+
+    config::Config::builder()
+        .add_source(config::File::from_str(&toml, config::FileFormat::Toml))
+        .build()
+        .and_then(|v| v.try_deserialize())
+        .map_err(|e| {
+            FatalError::new(
+                ErrorKind::InvalidValue,
+                e.to_string(),
+                Some("while building the demo configuration".into()),
+            )
+        })
+}
+
+/// 创建 [`BUCKET`] 和其中的 [`OBJECTS`]
+async fn populate(data_src: &DataSource, meta_src: &MetaSource) -> Result<(), FatalError> {
+    let when = || Some("while creating the sample bucket".to_string());
+    let error = |e: crab_vault::engine::error::EngineError| {
+        FatalError::new(ErrorKind::Io, e.to_string(), when())
+    };
+
+    let bucket = BucketMeta::builder().name(BUCKET).build().map_err(error)?;
+    data_src.create_bucket(BUCKET).await.map_err(error)?;
+    meta_src.create_bucket_meta(&bucket).await.map_err(error)?;
+
+    for (object, content_type, body) in OBJECTS {
+        let meta = ObjectMeta::builder()
+            .bucket_name(BUCKET)
+            .object_name(object)
+            .content_type(content_type)
+            .data(body.as_bytes())
+            .build()
+            .map_err(error)?;
+        data_src
+            .create_object(BUCKET, object, body.as_bytes())
+            .await
+            .map_err(error)?;
+        meta_src.create_object_meta(&meta).await.map_err(error)?;
+    }
+
+    Ok(())
+}
+
+/// 签发一个可以访问所有内容的令牌，同时返回它的过期时间
+fn issue(config: &AppConfig, expires_in: Duration) -> Result<(String, String), FatalError> {
+    let claims = Jwt::new(
+        ISSUER,
+        &[ISSUER],
+        PermissionSet::from(Permission::new_root()),
+    )
+    .expires_in(expires_in);
+
+    let token = config
+        .auth
+        .jwt_encoder_config
+        .encoder
+        .encode(&claims, KID)
+        .map_err(|e| FatalError::new(ErrorKind::Io, format!("JWT encoding failed: {e}"), None))?;
+    let expires_at = DateTime::from_timestamp(claims.exp, 0)
+        .map_or_else(|| claims.exp.to_string(), |v| v.to_rfc3339());
+
+    Ok((token, expires_at))
+}
+
+fn print_usage(port: u16, token: &str, expires_at: String) {
+    let base = format!("http://127.0.0.1:{port}{API_VERSION_PREFIX}");
+    let auth = r#"-H "Authorization: Bearer $TOKEN""#;
+    let objects = OBJECTS.map(|(v, _, _)| v).join(", ");
+
+    println!(
+        r#"
+Crab Vault demo, everything is kept in memory and lost on exit.
+
+Server:  {base}
+Bucket:  `{BUCKET}` with {objects}
+Token:   full access until {expires_at}, signed with a key generated for this run
+
+export TOKEN='{token}'
+
+# list the buckets and the objects in `{BUCKET}`
+curl {auth} {base}/
+curl {auth} {base}/{BUCKET}
+
+# download and inspect an object
+curl {auth} {base}/{BUCKET}/hello.txt
+curl -I {auth} {base}/{BUCKET}/hello.txt
+
+# upload, then delete an object
+curl -X PUT {auth} -H "Content-Type: text/plain" --data-binary 'hi there' {base}/{BUCKET}/new.txt
+curl -X DELETE {auth} -H "Content-Type: text/plain" -H "Content-Length: 0" {base}/{BUCKET}/new.txt
+"#
+    );
+}
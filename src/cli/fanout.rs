@@ -0,0 +1,68 @@
+use std::collections::BTreeMap;
+
+use clap::{Args, error::ErrorKind};
+use crab_vault::engine::{
+    DataEngine,
+    fs::FsDataEngine,
+    registry::{DEFAULT_SCHEME, split_scheme},
+};
+
+use crate::{
+    app_config::{self, ConfigItem},
+    cli::fsck::engine_error,
+    error::fatal::FatalError,
+};
+
+/// 'fanout' 命令的参数
+#[derive(Args, Clone)]
+pub struct FanOutArgs {
+    /// Buckets to convert, all buckets when none is given
+    pub buckets: Vec<String>,
+}
+
+pub async fn exec(args: FanOutArgs, config_path: String) {
+    let config = app_config::StaticAppConfig::from_file(config_path)
+        .into_runtime()
+        .map_err(|e| e.exit_now())
+        .unwrap();
+
+    config
+        .data
+        .reserve_internal_bucket()
+        .map_err(|e| e.exit_now())
+        .unwrap();
+
+    let (scheme, path) = split_scheme(&config.data.source);
+    if scheme != DEFAULT_SCHEME {
+        FatalError::new(
+            ErrorKind::InvalidValue,
+            format!("`{}` is not a local directory", config.data.source),
+            Some("only a local data source has a fan-out layout".into()),
+        )
+        .exit_now()
+    }
+
+    let engine = FsDataEngine::from_uri(path)
+        .map_err(|e| engine_error(e, "while opening the data source".into()).exit_now())
+        .unwrap();
+    let buckets = match args.buckets.is_empty() {
+        true => engine
+            .list_buckets()
+            .await
+            .map_err(|e| engine_error(e, "while listing buckets".into()).exit_now())
+            .unwrap(),
+        false => args.buckets,
+    };
+
+    // 每个 bucket 中移动了多少个文件，已经转换过的是 0
+    let mut moved = BTreeMap::new();
+    for bucket in buckets {
+        let count = engine
+            .fan_out_bucket(&bucket)
+            .await
+            .map_err(|e| engine_error(e, format!("while converting bucket `{bucket}`")).exit_now())
+            .unwrap();
+        moved.insert(bucket, count);
+    }
+    println!("{}", serde_json::to_string_pretty(&moved).unwrap());
+}
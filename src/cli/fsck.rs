@@ -0,0 +1,140 @@
+use std::sync::Arc;
+
+use clap::{Args, error::ErrorKind};
+use crab_vault::engine::{
+    DataEngine, DataSource, MetaEngine, MetaSource,
+    error::EngineError,
+    journal::{self, Journal, Report},
+    list::ListObjectsQuery,
+};
+use futures::TryStreamExt;
+
+use crate::{
+    app_config::{self, ConfigItem},
+    error::fatal::FatalError,
+};
+
+/// 'fsck' 命令的参数
+#[derive(Args, Clone)]
+pub struct FsckArgs {
+    /// Fix what is found instead of only reporting it, the server must not be running
+    #[arg(long)]
+    pub repair: bool,
+
+    /// Only replay the journal, without checking every object against its data
+    #[arg(long)]
+    pub journal_only: bool,
+}
+
+pub async fn exec(args: FsckArgs, config_path: String) {
+    let config = app_config::StaticAppConfig::from_file(config_path)
+        .into_runtime()
+        .map_err(|e| e.exit_now())
+        .unwrap();
+
+    let (data_src, meta_src) = open_sources(&config);
+
+    let mut reports = vec![];
+    if let Some(dir) = &config.data.journal {
+        let journal = Journal::open(dir)
+            .await
+            .map_err(|e| engine_error(e, "while opening the journal".into()).exit_now())
+            .unwrap();
+        reports.extend(
+            journal::replay(&journal, &data_src, meta_src.as_ref(), args.repair)
+                .await
+                .map_err(|e| engine_error(e, "while replaying the journal".into()).exit_now())
+                .unwrap(),
+        );
+    }
+
+    // 重放之后再检查，已经修复的 object 不会重复报告
+    if !args.journal_only {
+        reports.extend(
+            scan(&data_src, &meta_src, args.repair)
+                .await
+                .map_err(|e| e.exit_now())
+                .unwrap(),
+        );
+    }
+
+    let found = reports.iter().filter(|v| v.finding.needs_repair());
+    let (mut inconsistent, mut repaired) = (0usize, 0usize);
+    for report in found {
+        println!(
+            "/{}/{}: {}{}",
+            report.bucket,
+            report.object,
+            report.finding.as_str(),
+            if report.repaired { " (repaired)" } else { "" }
+        );
+        inconsistent += 1;
+        repaired += report.repaired as usize;
+    }
+
+    eprintln!(
+        "{} object(s) checked, {} inconsistent, {} repaired.",
+        reports.len(),
+        inconsistent,
+        repaired
+    );
+}
+
+/// 逐个检查每个 bucket 中的每个 object 的元数据和数据，需要读出全部数据
+///
+/// 只能发现有元数据的 object 的问题，没有记录在意图日志中的孤立数据不会被发现
+async fn scan(
+    data_src: &DataSource,
+    meta_src: &MetaSource,
+    repair: bool,
+) -> Result<Vec<Report>, FatalError> {
+    let buckets = meta_src
+        .list_buckets_meta()
+        .await
+        .map_err(|e| engine_error(e, "while listing buckets".into()))?;
+
+    let mut reports = vec![];
+    for bucket in buckets {
+        let when = || format!("while checking bucket `{}`", bucket.name);
+
+        // 边列举边修复可能会影响列举本身，所以先收集所有 object 的名称
+        let objects: Vec<String> = meta_src
+            .stream_objects_meta(&bucket.name, &ListObjectsQuery::default())
+            .map_ok(|v| v.object_name)
+            .try_collect()
+            .await
+            .map_err(|e| engine_error(e, when()))?;
+
+        for object in objects {
+            let report =
+                journal::reconcile(data_src, meta_src, &bucket.name, &object, None, repair)
+                    .await
+                    .map_err(|e| engine_error(e, when()))?;
+            reports.push(report);
+        }
+    }
+
+    Ok(reports)
+}
+
+/// 按照配置打开数据和元数据，失败时直接退出，数据的覆盖和删除同样检查保留
+pub(super) fn open_sources(config: &app_config::AppConfig) -> (DataSource, Arc<MetaSource>) {
+    config
+        .data
+        .reserve_internal_bucket()
+        .map_err(|e| e.exit_now())
+        .unwrap();
+    let data_src = DataSource::new(&config.data.source)
+        .map_err(|e| engine_error(e, "while opening the data source".into()).exit_now())
+        .unwrap();
+    let meta_src = MetaSource::with_pool_config(&config.meta.source, &config.meta.pool)
+        .map_err(|e| engine_error(e, "while opening the meta source".into()).exit_now())
+        .unwrap()
+        .on_corrupt(config.meta.on_corrupt_entry);
+    let meta_src = Arc::new(meta_src);
+    (data_src.guard_retention(meta_src.clone()), meta_src)
+}
+
+pub(super) fn engine_error(e: EngineError, when: String) -> FatalError {
+    FatalError::new(ErrorKind::Io, e.to_string(), Some(when))
+}
@@ -0,0 +1,109 @@
+use std::path::Path;
+
+use clap::Args;
+use crab_vault::engine::{
+    error::EngineResult,
+    fs::FsDataEngine,
+    gc::{self, Garbage},
+    journal::{Intent, Journal},
+    registry::{DEFAULT_SCHEME, split_scheme},
+};
+use serde::Serialize;
+
+use crate::{
+    app_config::{self, ConfigItem},
+    cli::fsck::{engine_error, open_sources},
+};
+
+/// 'gc' 命令的参数
+#[derive(Args, Clone)]
+pub struct GcArgs {
+    /// Delete what is found instead of only reporting it
+    #[arg(long)]
+    pub delete: bool,
+}
+
+/// 输出到标准输出的 JSON
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct Output {
+    #[serde(flatten)]
+    garbage: Garbage,
+
+    /// 去重目录中没有任何 object 引用的 blob 的摘要，数据不在本地目录中时总是为空
+    orphan_blobs: Vec<String>,
+    deleted: bool,
+}
+
+pub async fn exec(args: GcArgs, config_path: String) {
+    let config = app_config::StaticAppConfig::from_file(config_path)
+        .into_runtime()
+        .map_err(|e| e.exit_now())
+        .unwrap();
+
+    let (data_src, meta_src) = open_sources(&config);
+
+    let mut garbage = gc::scan(&data_src, meta_src.as_ref())
+        .await
+        .map_err(|e| engine_error(e, "while scanning for garbage".into()).exit_now())
+        .unwrap();
+
+    // 没有完成的修改交给 `fsck` 处理
+    if let Some(dir) = &config.data.journal {
+        let pending = pending_intents(dir)
+            .await
+            .map_err(|e| engine_error(e, "while reading the journal".into()).exit_now())
+            .unwrap();
+        garbage.exclude(&pending);
+    }
+
+    if args.delete {
+        gc::collect(&data_src, meta_src.as_ref(), &garbage)
+            .await
+            .map_err(|e| engine_error(e, "while deleting garbage".into()).exit_now())
+            .unwrap();
+    }
+
+    // 先删除孤立的数据，它们引用的 blob 在这一次就能被找到
+    let orphan_blobs = match local_engine(&config.data.source) {
+        Some(engine) => collect_blobs(&engine, args.delete)
+            .await
+            .map_err(|e| engine_error(e, "while pruning orphan blobs".into()).exit_now())
+            .unwrap(),
+        None => vec![],
+    };
+
+    let output = Output {
+        garbage,
+        orphan_blobs,
+        deleted: args.delete,
+    };
+    println!("{}", serde_json::to_string_pretty(&output).unwrap());
+}
+
+/// 数据在本地目录中时打开它，以便检查去重目录
+fn local_engine(source: &str) -> Option<FsDataEngine> {
+    let (scheme, path) = split_scheme(source);
+    (scheme == DEFAULT_SCHEME)
+        .then(|| FsDataEngine::from_uri(path))
+        .and_then(Result::ok)
+}
+
+/// 找出没有任何 object 引用的 blob，`delete` 时删除它们
+async fn collect_blobs(engine: &FsDataEngine, delete: bool) -> EngineResult<Vec<String>> {
+    let orphans = engine.orphan_blobs().await?;
+    if delete {
+        engine.prune_blobs(&orphans).await?;
+    }
+    Ok(orphans)
+}
+
+async fn pending_intents(dir: &Path) -> EngineResult<Vec<Intent>> {
+    let journal = Journal::open(dir).await?;
+    Ok(journal
+        .pending()
+        .await?
+        .into_iter()
+        .map(|(_, v)| v)
+        .collect())
+}
@@ -1,6 +1,6 @@
 use crate::app_config::{self, AppConfig, ConfigItem};
 use crate::error::fatal::FatalError;
-use crab_vault::auth::{HttpMethod, Jwt, JwtDecoder, Permission};
+use crab_vault::auth::{HttpMethod, Jwt, JwtDecoder, Permission, PermissionSet};
 
 use chrono::Duration;
 use clap::error::ErrorKind;
@@ -77,23 +77,22 @@ fn generate_jwt(args: GenerateArgs, config: AppConfig) -> Result<(), FatalError>
     let jwt_encoder_config = &config.auth.jwt_encoder_config;
     let jwt_encoder = &config.auth.jwt_encoder_config.encoder;
 
-    let iss = if args.issue_as.is_some() {
-        args.issue_as.unwrap()
-    } else {
-        jwt_encoder_config.issue_as.to_string()
+    let iss = match args.issue_as {
+        Some(iss) => iss,
+        None => jwt_encoder_config.issue_as.to_string(),
     };
 
-    let aud = if args.audiences.is_some() {
-        args.audiences.unwrap()
-    } else {
-        jwt_encoder_config.audience.to_vec()
+    let aud = match args.audiences {
+        Some(aud) => aud,
+        None => jwt_encoder_config.audience.to_vec(),
     };
 
     let payload = Permission::new_minimum()
         .permit_method(args.operations)
         .permit_resource_pattern(args.resource_pattern)
         .restrict_maximum_size_option(args.max_size)
-        .permit_content_type(args.allowed_content_type);
+        .permit_content_type(args.allowed_content_type)
+        .permit_list(true);
 
     let claims = Jwt::new(iss, &aud, payload)
         .expires_in(Duration::seconds(
@@ -140,7 +139,7 @@ fn verify_jwt(config: AppConfig) -> Result<(), FatalError> {
     let pretty_json = serde_json::to_string_pretty(&decoded).map_err(FatalError::from)?;
 
     // 验证
-    match jwt_decoder.decode::<Permission>(token) {
+    match jwt_decoder.decode::<PermissionSet>(token) {
         Ok(_) => eprintln!("Token verified successfully. Payload (Claims):\n"),
         Err(e) => eprintln!("Token invalid because of {e}. Payload (Claims):\n"),
     }
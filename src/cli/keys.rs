@@ -0,0 +1,190 @@
+use clap::{Args, Subcommand, error::ErrorKind};
+use crab_vault::engine::{DataEngine, DataSource, MetaEngine, MetaSource, crypto::KeyRing};
+
+use crate::{
+    app_config::{self, ConfigItem},
+    cli::fsck::{engine_error, open_sources},
+    error::fatal::FatalError,
+};
+
+#[derive(Subcommand, Clone)]
+pub enum Command {
+    /// Re-wrap every bucket and object data key with the active master key, object data is left untouched
+    #[command(name = "rewrap")]
+    Rewrap(RewrapArgs),
+}
+
+/// 'rewrap' 命令的参数
+#[derive(Args, Clone)]
+pub struct RewrapArgs {
+    /// Only report which buckets would be re-wrapped, without writing anything
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+pub async fn exec(cmd: Command, config_path: String) {
+    let config = app_config::StaticAppConfig::from_file(config_path)
+        .into_runtime()
+        .map_err(|e| e.exit_now())
+        .unwrap();
+
+    let (data_src, meta_src) = open_sources(&config);
+
+    match cmd {
+        Command::Rewrap(args) => rewrap(args, config.encryption, &data_src, &meta_src).await,
+    }
+    .map_err(|e| e.exit_now())
+    .unwrap()
+}
+
+async fn rewrap(
+    args: RewrapArgs,
+    key_ring: app_config::encryption::EncryptionConfig,
+    data_src: &DataSource,
+    meta_src: &MetaSource,
+) -> Result<(), FatalError> {
+    let key_ring = key_ring.ok_or_else(|| {
+        FatalError::new(
+            ErrorKind::InvalidValue,
+            "no master key configured, nothing to rewrap".into(),
+            Some("while reading the `encryption` section".into()),
+        )
+    })?;
+
+    let buckets = meta_src
+        .list_buckets_meta()
+        .await
+        .map_err(|e| engine_error(e, "while listing buckets".into()))?;
+
+    let (mut rewrapped, mut skipped) = (0usize, 0usize);
+
+    for mut bucket in buckets {
+        let Some(data_key) = &bucket.data_key else {
+            skipped += 1;
+            continue;
+        };
+
+        let Some(new_key) = key_ring
+            .rewrap(data_key)
+            .map_err(|e| engine_error(e, format!("while rewrapping bucket `{}`", bucket.name)))?
+        else {
+            skipped += 1;
+            continue;
+        };
+
+        println!(
+            "{}: {} -> {}",
+            bucket.name,
+            data_key.master_key_id,
+            key_ring.active_key_id()
+        );
+
+        if !args.dry_run {
+            bucket.data_key = Some(new_key);
+            meta_src
+                .create_bucket_meta(&bucket)
+                .await
+                .map_err(|e| engine_error(e, format!("while saving bucket `{}`", bucket.name)))?;
+        }
+
+        rewrapped += 1;
+    }
+
+    eprintln!(
+        "{} bucket(s) {}, {} bucket(s) skipped.",
+        rewrapped,
+        if args.dry_run { "to rewrap" } else { "rewrapped" },
+        skipped
+    );
+
+    let (rewrapped, skipped) = rewrap_objects(&args, &key_ring, data_src, meta_src).await?;
+    eprintln!(
+        "{} object(s) {}, {} object(s) skipped.",
+        rewrapped,
+        if args.dry_run { "to rewrap" } else { "rewrapped" },
+        skipped
+    );
+
+    Ok(())
+}
+
+/// 改为由当前主密钥包裹每个加密过的 object 的数据密钥，返回处理和跳过的 object 的个数
+///
+/// 写回之前重新读取元数据，期间被覆盖或者删除的 object 会被跳过
+async fn rewrap_objects(
+    args: &RewrapArgs,
+    key_ring: &KeyRing,
+    data_src: &DataSource,
+    meta_src: &MetaSource,
+) -> Result<(usize, usize), FatalError> {
+    let buckets = data_src
+        .list_buckets()
+        .await
+        .map_err(|e| engine_error(e, "while listing buckets".into()))?;
+
+    let (mut rewrapped, mut skipped) = (0usize, 0usize);
+
+    for bucket in buckets {
+        let objects = meta_src
+            .list_objects_meta(&bucket)
+            .await
+            .map_err(|e| engine_error(e, format!("while listing objects in `{bucket}`")))?;
+
+        for object in objects {
+            let path = format!("{}/{}", object.bucket_name, object.object_name);
+            let when = || format!("while rewrapping object `{path}`");
+
+            let Some(mut encryption) = object
+                .encryption()
+                .map_err(|e| engine_error(e, when()))?
+            else {
+                skipped += 1;
+                continue;
+            };
+            // 客户密钥包裹的数据密钥与主密钥无关，服务端也无法解开
+            if encryption.customer_key_sha256.is_some() {
+                skipped += 1;
+                continue;
+            }
+            let Some(new_key) = key_ring
+                .rewrap(&encryption.data_key)
+                .map_err(|e| engine_error(e, when()))?
+            else {
+                skipped += 1;
+                continue;
+            };
+
+            println!(
+                "{}: {} -> {}",
+                path,
+                encryption.data_key.master_key_id,
+                key_ring.active_key_id()
+            );
+
+            if !args.dry_run {
+                let mut current = match meta_src
+                    .read_object_meta(&object.bucket_name, &object.object_name)
+                    .await
+                {
+                    Ok(current) if current == object => current,
+                    _ => {
+                        skipped += 1;
+                        continue;
+                    }
+                };
+                encryption.data_key = new_key;
+                current
+                    .set_encryption(Some(&encryption))
+                    .map_err(|e| engine_error(e, when()))?;
+                meta_src
+                    .create_object_meta(&current)
+                    .await
+                    .map_err(|e| engine_error(e, format!("while saving object `{path}`")))?;
+            }
+
+            rewrapped += 1;
+        }
+    }
+
+    Ok((rewrapped, skipped))
+}
@@ -0,0 +1,490 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use clap::{Args, error::ErrorKind};
+use crab_vault::engine::{
+    BucketMeta, DataEngine, DataSource, MetaEngine, MetaSource, ObjectMeta,
+    builder::DEFAULT_CONTENT_TYPE,
+    clock,
+    crypto::{KeyRing, is_reserved_meta_key},
+    error::{EngineError, EngineResult},
+    journal::Journal,
+    list::ListObjectsQuery,
+    name,
+    s3::{S3Bucket, S3Object},
+};
+use futures::{StreamExt, TryStreamExt, stream};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    app_config::{self, ConfigItem, data::CompressionConfig},
+    cli::{
+        audit::io_error,
+        backup::{open_journal, write_object},
+        fsck::{engine_error, open_sources},
+    },
+    error::fatal::FatalError,
+    http::{read_original, seal_original},
+};
+
+/// 每复制这么多个 object 保存一次进度
+const CHECKPOINT_EVERY: usize = 100;
+
+/// 'import' 和 'export' 共用的参数
+#[derive(Args, Clone)]
+pub struct TransferArgs {
+    /// Only copy objects whose name starts with this
+    #[arg(long, default_value = "")]
+    pub prefix: String,
+
+    /// How many objects are copied at the same time
+    #[arg(long, default_value_t = 8, value_parser = clap::value_parser!(u16).range(1..))]
+    pub concurrency: u16,
+
+    /// Record the progress in this file, and continue from where it stopped when run again
+    #[arg(long)]
+    pub checkpoint: Option<PathBuf>,
+}
+
+/// 'import' 命令的参数
+#[derive(Args, Clone)]
+pub struct ImportArgs {
+    /// The S3 bucket to copy from, e.g. `s3://photos?endpoint=http://minio:9000`
+    pub source: String,
+
+    /// The bucket to copy into, created when missing; the same name as the S3 bucket when not given
+    #[arg(long)]
+    pub bucket: Option<String>,
+
+    #[command(flatten)]
+    pub transfer: TransferArgs,
+}
+
+/// 'export' 命令的参数
+#[derive(Args, Clone)]
+pub struct ExportArgs {
+    /// The bucket to copy from
+    pub bucket: String,
+
+    /// The S3 bucket to copy into, e.g. `s3://photos?endpoint=http://minio:9000`
+    pub destination: String,
+
+    #[command(flatten)]
+    pub transfer: TransferArgs,
+}
+
+/// `--checkpoint` 文件的内容
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+#[serde(rename_all = "kebab-case")]
+struct Checkpoint {
+    /// 来源和目标，不包括 uri 中的密钥等参数
+    from: String,
+    to: String,
+    prefix: String,
+
+    /// 这个 object 和排在它之前的所有 object 都已经复制完成
+    done_through: Option<String>,
+}
+
+/// 本地的数据和元数据，以及写入时按照配置进行的压缩和加密
+struct Local {
+    data_src: DataSource,
+    meta_src: Arc<MetaSource>,
+    journal: Option<Journal>,
+    compression: CompressionConfig,
+    key_ring: Option<Arc<KeyRing>>,
+}
+
+/// 一个 object 的复制结果
+enum Outcome {
+    Copied,
+    Skipped(String),
+}
+
+#[derive(Default)]
+struct Summary {
+    copied: usize,
+    skipped: usize,
+    failed: usize,
+}
+
+pub async fn import(args: ImportArgs, config_path: String) {
+    let ImportArgs {
+        source,
+        bucket,
+        transfer,
+    } = args;
+
+    let local = Local::open(config_path).await;
+    let source = S3Bucket::new(&source)
+        .map_err(|e| engine_error(e, "while opening the S3 bucket".into()).exit_now())
+        .unwrap();
+    let bucket = bucket.unwrap_or_else(|| source.name().to_string());
+
+    let imported = async {
+        local.ensure_bucket(&bucket).await?;
+
+        let mut checkpoint = Checkpoint::load(
+            transfer.checkpoint.as_deref(),
+            format!("s3://{}", source.name()),
+            bucket.clone(),
+            &transfer.prefix,
+        )?;
+        let objects = source
+            .list_objects(&transfer.prefix, checkpoint.done_through.as_deref())
+            .await
+            .map_err(|e| engine_error(e, "while listing the S3 bucket".into()))?;
+
+        copy_all(objects, &transfer, &mut checkpoint, |key| {
+            import_object(&local, &source, &bucket, key)
+        })
+        .await
+    }
+    .await;
+
+    report(imported.map_err(|e| e.exit_now()).unwrap())
+}
+
+pub async fn export(args: ExportArgs, config_path: String) {
+    let ExportArgs {
+        bucket,
+        destination,
+        transfer,
+    } = args;
+
+    let local = Local::open(config_path).await;
+    let destination = S3Bucket::new(&destination)
+        .map_err(|e| engine_error(e, "while opening the S3 bucket".into()).exit_now())
+        .unwrap();
+
+    let exported = async {
+        let mut checkpoint = Checkpoint::load(
+            transfer.checkpoint.as_deref(),
+            bucket.clone(),
+            format!("s3://{}", destination.name()),
+            &transfer.prefix,
+        )?;
+        let objects = local
+            .list_objects(
+                &bucket,
+                &transfer.prefix,
+                checkpoint.done_through.as_deref(),
+            )
+            .await
+            .map_err(|e| engine_error(e, format!("while listing bucket `{bucket}`")))?;
+
+        copy_all(objects, &transfer, &mut checkpoint, |object| {
+            export_object(&local, &destination, &bucket, object)
+        })
+        .await
+    }
+    .await;
+
+    report(exported.map_err(|e| e.exit_now()).unwrap())
+}
+
+fn report(summary: Summary) {
+    eprintln!(
+        "{} object(s) copied, {} skipped, {} failed.",
+        summary.copied, summary.skipped, summary.failed
+    );
+
+    if summary.failed > 0 {
+        FatalError::new(
+            ErrorKind::Io,
+            format!("{} object(s) could not be copied", summary.failed),
+            Some("run the same command again to retry them".into()),
+        )
+        .exit_now()
+    }
+}
+
+/// 按照名称的顺序复制 `objects`，同时最多进行 `concurrency` 个
+///
+/// 结果按照名称的顺序处理，所以进度总是连续的；出现失败之后进度不再前进，再次运行时从失败的 object 开始
+async fn copy_all<F, Fut>(
+    objects: Vec<String>,
+    args: &TransferArgs,
+    checkpoint: &mut Checkpoint,
+    copy: F,
+) -> Result<Summary, FatalError>
+where
+    F: Fn(String) -> Fut,
+    Fut: Future<Output = EngineResult<Outcome>>,
+{
+    let mut results = stream::iter(objects)
+        .map(|name| {
+            let copied = copy(name.clone());
+            async move { (name, copied.await) }
+        })
+        .buffered(args.concurrency as usize);
+
+    let mut summary = Summary::default();
+    while let Some((name, result)) = results.next().await {
+        match result {
+            Ok(Outcome::Copied) => summary.copied += 1,
+            Ok(Outcome::Skipped(reason)) => {
+                println!("{name}: skipped, {reason}");
+                summary.skipped += 1;
+            }
+            Err(e) => {
+                println!("{name}: {e}");
+                summary.failed += 1;
+            }
+        }
+
+        if summary.failed == 0 {
+            checkpoint.done_through = Some(name);
+            if (summary.copied + summary.skipped) % CHECKPOINT_EVERY == 0 {
+                checkpoint.save(args.checkpoint.as_deref())?;
+            }
+        }
+    }
+
+    checkpoint.save(args.checkpoint.as_deref())?;
+    Ok(summary)
+}
+
+async fn import_object(
+    local: &Local,
+    source: &S3Bucket,
+    bucket: &str,
+    key: String,
+) -> EngineResult<Outcome> {
+    if let Err(e) = name::validate_object_name(&key) {
+        return Ok(Outcome::Skipped(e.to_string()));
+    }
+
+    let object = match source.get_object(&key).await {
+        Ok(object) => object,
+        Err(EngineError::ObjectNotFound { .. }) => {
+            return Ok(Outcome::Skipped("deleted while copying".into()));
+        }
+        Err(e) => return Err(e),
+    };
+
+    // `x-amz-meta-*` 的每一项成为用户元数据中的一个字符串，服务端保留的键不能从外部写入
+    let user_meta = object
+        .metadata
+        .into_iter()
+        .filter(|(k, _)| !is_reserved_meta_key(k))
+        .map(|(k, v)| (k, Value::String(v)))
+        .collect();
+
+    let mut meta = ObjectMeta::builder()
+        .bucket_name(bucket)
+        .object_name(&key)
+        .data(&object.data)
+        .content_type(
+            object
+                .content_type
+                .unwrap_or_else(|| DEFAULT_CONTENT_TYPE.into()),
+        )
+        .user_meta(Value::Object(user_meta))
+        .build()?;
+
+    let stored = seal_original(
+        &local.compression,
+        local.key_ring.as_ref(),
+        &mut meta,
+        object.data,
+    )
+    .await?;
+    write_object(
+        &local.data_src,
+        &local.meta_src,
+        local.journal.as_ref(),
+        &meta,
+        &stored,
+    )
+    .await?;
+
+    Ok(Outcome::Copied)
+}
+
+async fn export_object(
+    local: &Local,
+    destination: &S3Bucket,
+    bucket: &str,
+    object: String,
+) -> EngineResult<Outcome> {
+    let meta = match local.meta_src.read_object_meta(bucket, &object).await {
+        Ok(meta) => meta,
+        Err(EngineError::ObjectMetaNotFound { .. }) => {
+            return Ok(Outcome::Skipped("deleted while copying".into()));
+        }
+        Err(e) => return Err(e),
+    };
+    if meta.is_expired(clock::now()) {
+        return Ok(Outcome::Skipped("expired".into()));
+    }
+    if meta.check_customer_key(None).is_err() {
+        return Ok(Outcome::Skipped(
+            "encrypted with a customer-provided key".into(),
+        ));
+    }
+
+    let data = read_original(&local.data_src, local.key_ring.as_ref(), &meta).await?;
+
+    // 用户元数据中的字符串原样成为 `x-amz-meta-*`，其他的值写为 JSON
+    let meta = meta.hide_reserved_meta();
+    let metadata = match meta.user_meta {
+        Value::Object(map) => map
+            .into_iter()
+            .map(|(k, v)| match v {
+                Value::String(v) => (k, v),
+                v => (k, v.to_string()),
+            })
+            .collect(),
+        _ => Default::default(),
+    };
+
+    destination
+        .put_object(
+            &object,
+            S3Object {
+                data,
+                content_type: Some(meta.content_type),
+                metadata,
+            },
+        )
+        .await?;
+
+    Ok(Outcome::Copied)
+}
+
+impl Local {
+    async fn open(config_path: String) -> Self {
+        let config = app_config::StaticAppConfig::from_file(config_path)
+            .into_runtime()
+            .map_err(|e| e.exit_now())
+            .unwrap();
+
+        name::set_rules(config.data.naming);
+        let (data_src, meta_src) = open_sources(&config);
+        let journal = open_journal(&config).await;
+
+        Self {
+            data_src,
+            meta_src,
+            journal,
+            compression: config.data.compression,
+            key_ring: config.encryption.map(Arc::new),
+        }
+    }
+
+    /// 与创建 bucket 的接口一样，配置了主密钥时为新的 bucket 生成数据密钥
+    async fn ensure_bucket(&self, bucket: &str) -> Result<(), FatalError> {
+        let when = || format!("while creating bucket `{bucket}`");
+
+        name::validate_bucket_name(bucket).map_err(|e| engine_error(e, when()))?;
+        match self.meta_src.read_bucket_meta(bucket).await {
+            Ok(_) => return Ok(()),
+            Err(EngineError::BucketMetaNotFound { .. }) => {}
+            Err(e) => return Err(engine_error(e, when())),
+        }
+
+        let mut meta = BucketMeta::new(bucket.to_string(), Value::Object(Default::default()));
+        if let Some(key_ring) = &self.key_ring {
+            meta.data_key = Some(
+                key_ring
+                    .generate_wrapped()
+                    .map_err(|e| engine_error(e, when()))?,
+            );
+        }
+
+        self.data_src
+            .create_bucket(bucket)
+            .await
+            .map_err(|e| engine_error(e, when()))?;
+        self.meta_src
+            .create_bucket_meta(&meta)
+            .await
+            .map_err(|e| engine_error(e, when()))
+    }
+
+    /// 按照名称排序的 object，只包括排在 `after` 之后的
+    async fn list_objects(
+        &self,
+        bucket: &str,
+        prefix: &str,
+        after: Option<&str>,
+    ) -> EngineResult<Vec<String>> {
+        if name::is_internal_bucket(bucket) {
+            return Err(EngineError::BucketNotFound {
+                bucket: bucket.to_string(),
+            });
+        }
+
+        let query = ListObjectsQuery {
+            prefix: Some(prefix.to_string()),
+            ..Default::default()
+        };
+        let mut objects: Vec<String> = self
+            .meta_src
+            .stream_objects_meta(bucket, &query)
+            .map_ok(|v| v.object_name)
+            .try_collect()
+            .await?;
+
+        objects.retain(|v| after.is_none_or(|after| v.as_str() > after));
+        objects.sort();
+        Ok(objects)
+    }
+}
+
+impl Checkpoint {
+    /// 读出上次的进度，没有指定文件或者文件不存在时从头开始
+    fn load(
+        path: Option<&Path>,
+        from: String,
+        to: String,
+        prefix: &str,
+    ) -> Result<Self, FatalError> {
+        let fresh = Self {
+            from,
+            to,
+            prefix: prefix.to_string(),
+            done_through: None,
+        };
+        let Some(path) = path.filter(|v| v.exists()) else {
+            return Ok(fresh);
+        };
+
+        let when = || format!("while reading the checkpoint {}", path.display());
+        let content = fs::read(path).map_err(|e| io_error(e, when()))?;
+        let loaded: Checkpoint = serde_json::from_slice(&content)?;
+
+        let same_copy =
+            loaded.from == fresh.from && loaded.to == fresh.to && loaded.prefix == fresh.prefix;
+        if !same_copy {
+            return Err(FatalError::new(
+                ErrorKind::InvalidValue,
+                format!(
+                    "the checkpoint is for copying `{}` from `{}` to `{}`",
+                    loaded.prefix, loaded.from, loaded.to
+                ),
+                Some(when()),
+            ));
+        }
+
+        Ok(loaded)
+    }
+
+    /// 先写入临时文件再替换，中途退出不会留下写了一半的进度
+    fn save(&self, path: Option<&Path>) -> Result<(), FatalError> {
+        let Some(path) = path else {
+            return Ok(());
+        };
+
+        let mut temp = path.as_os_str().to_owned();
+        temp.push(".tmp");
+        fs::write(&temp, serde_json::to_vec_pretty(self)?)
+            .and_then(|_| fs::rename(&temp, path))
+            .map_err(|e| io_error(e, format!("while saving the checkpoint {}", path.display())))
+    }
+}
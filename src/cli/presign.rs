@@ -0,0 +1,152 @@
+use crate::app_config::{self, AppConfig, ConfigItem};
+use crate::error::fatal::FatalError;
+use crate::http::api::API_VERSION_PREFIX;
+use crab_vault::auth::{HttpMethod, Jwt, PRESIGN_QUERY_KEY, Permission};
+
+use chrono::Duration;
+use clap::error::ErrorKind;
+use clap::{Args, ValueEnum};
+
+/// 预签名 URL 最长的有效期
+const MAX_EXPIRES: Duration = Duration::days(7);
+
+/// 'presign' 命令的参数
+#[derive(Args, Clone)]
+pub struct PresignArgs {
+    /// The object to presign, in the form of `<bucket>/<object>`
+    pub target: String,
+
+    /// How long the URL stays valid, e.g. `90s`, `30m`, `1h`, `7d` (at most 7 days)
+    #[arg(long, default_value = "1h", value_parser = parse_duration)]
+    pub expires: Duration,
+
+    /// The HTTP method this URL is signed for
+    #[arg(long, value_enum, default_value = "get")]
+    pub method: PresignMethod,
+
+    /// The base URL of the server, defaults to `http://localhost:<server.port>`
+    #[arg(long)]
+    pub base_url: Option<String>,
+}
+
+#[derive(ValueEnum, Clone, Copy)]
+pub enum PresignMethod {
+    Get,
+    Put,
+}
+
+impl From<PresignMethod> for HttpMethod {
+    fn from(value: PresignMethod) -> Self {
+        match value {
+            PresignMethod::Get => HttpMethod::Get,
+            PresignMethod::Put => HttpMethod::Put,
+        }
+    }
+}
+
+pub fn exec(args: PresignArgs, config_path: String) {
+    let config = app_config::StaticAppConfig::from_file(config_path)
+        .into_runtime()
+        .map_err(|e| e.exit_now())
+        .unwrap();
+
+    presign(args, config).map_err(|e| e.exit_now()).unwrap()
+}
+
+fn presign(args: PresignArgs, config: AppConfig) -> Result<(), FatalError> {
+    let invalid = |msg: String| FatalError::new(ErrorKind::InvalidValue, msg, None);
+
+    let PresignArgs {
+        target,
+        expires,
+        method,
+        base_url,
+    } = args;
+
+    match target.split_once('/') {
+        Some((bucket, object)) if !bucket.is_empty() && !object.is_empty() => {}
+        _ => {
+            return Err(invalid(format!(
+                "`{target}` is not in the form of `<bucket>/<object>`"
+            )));
+        }
+    }
+
+    if expires <= Duration::zero() || expires > MAX_EXPIRES {
+        return Err(invalid(format!(
+            "expires should be positive and no longer than {} days",
+            MAX_EXPIRES.num_days()
+        )));
+    }
+
+    // 令牌中的路径需要与服务端看到的请求路径完全一致，所以在这里就完成编码
+    // 服务端鉴权时看到的路径不包含版本前缀，所以令牌中也不包含
+    let path = format!("/{}", encode_path(&target));
+    let payload = Permission::new_presigned(method.into(), &path);
+
+    // 服务端会丢弃超出复杂度限制的模式，这样签发出来的 URL 是无法使用的
+    if let Some(pattern) = &payload.resource_pattern
+        && let Err(e) = config.auth.glob_limits.check(pattern)
+    {
+        return Err(invalid(format!(
+            "`{target}` cannot be presigned, because {e}"
+        )));
+    }
+
+    let encoder_config = &config.auth.jwt_encoder_config;
+    let claims =
+        Jwt::new(&encoder_config.issue_as, &encoder_config.audience, payload).expires_in(expires);
+
+    let token = encoder_config
+        .encoder
+        .encode_randomly(&claims)
+        .map_err(|e| FatalError::new(ErrorKind::Io, format!("JWT encoding failed: {e}"), None))?;
+
+    let base_url = base_url.unwrap_or_else(|| format!("http://localhost:{}", config.server.port));
+
+    println!(
+        "{}{API_VERSION_PREFIX}{path}?{PRESIGN_QUERY_KEY}={token}",
+        base_url.trim_end_matches('/')
+    );
+    Ok(())
+}
+
+/// 解析形如 `90s`、`30m`、`1h`、`7d` 的时长，没有单位时视为秒
+pub(super) fn parse_duration(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let (number, unit) = value.split_at(
+        value
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(value.len()),
+    );
+
+    let number: i64 = number
+        .parse()
+        .map_err(|_| format!("`{value}` is not a valid duration"))?;
+
+    let duration = match unit {
+        "" | "s" => Duration::try_seconds(number),
+        "m" => Duration::try_minutes(number),
+        "h" => Duration::try_hours(number),
+        "d" => Duration::try_days(number),
+        _ => {
+            return Err(format!(
+                "unknown duration unit `{unit}`, expected one of s, m, h, d"
+            ));
+        }
+    };
+
+    duration.ok_or_else(|| format!("`{value}` is out of range"))
+}
+
+/// 对路径做百分号编码，保留 `/` 和 RFC 3986 中的非保留字符
+fn encode_path(path: &str) -> String {
+    path.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{b:02X}"),
+        })
+        .collect()
+}
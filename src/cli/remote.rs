@@ -0,0 +1,443 @@
+use std::path::{Path, PathBuf};
+
+use clap::{Args, error::ErrorKind};
+use crab_vault::{
+    auth::PermissionSet,
+    client::{Client, ListObjectsOptions, ObjectInfo, PutOptions, TokenSigner, error::ClientError},
+};
+use futures::{StreamExt, TryStreamExt, stream};
+use serde_json::Value;
+use tokio::io::{AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::{
+    app_config::{self, ConfigItem},
+    cli::audit::io_error,
+    error::fatal::FatalError,
+    http::api::API_VERSION_PREFIX,
+};
+
+/// `rm --recursive` 同时删除的 object 数
+const DELETE_CONCURRENCY: usize = 8;
+
+/// 远程命令共用的参数
+#[derive(Args, Clone)]
+pub struct RemoteArgs {
+    /// The base URL of the server, defaults to `http://localhost:<server.port>/v1`
+    #[arg(long, env = "CRAB_VAULT_ENDPOINT")]
+    pub endpoint: Option<String>,
+
+    /// The token to send; when not given, a token with full access is signed with the keys in the configuration file
+    #[arg(long, env = "CRAB_VAULT_TOKEN", hide_env_values = true)]
+    pub token: Option<String>,
+}
+
+/// 'cp' 命令的参数
+#[derive(Args, Clone)]
+pub struct CpArgs {
+    /// A local file, `-` for the standard input, or `<bucket>/<object>`
+    pub source: String,
+
+    /// `<bucket>/<object>`, a local file or directory, or `-` for the standard output
+    pub destination: String,
+
+    /// The content type of the uploaded object
+    #[arg(long)]
+    pub content_type: Option<String>,
+
+    /// The user metadata of the uploaded object, a JSON object
+    #[arg(long, value_parser = parse_user_meta)]
+    pub user_meta: Option<Value>,
+
+    #[command(flatten)]
+    pub remote: RemoteArgs,
+}
+
+/// 'ls' 命令的参数
+#[derive(Args, Clone)]
+pub struct LsArgs {
+    /// `<bucket>` or `<bucket>/<prefix>`; the buckets are listed when not given
+    pub target: Option<String>,
+
+    /// List every object under the prefix instead of grouping them by `/`
+    #[arg(long, short)]
+    pub recursive: bool,
+
+    #[command(flatten)]
+    pub remote: RemoteArgs,
+}
+
+/// 'rm' 命令的参数
+#[derive(Args, Clone)]
+pub struct RmArgs {
+    /// `<bucket>/<object>` to delete an object, or `<bucket>` to delete an empty bucket
+    #[arg(required = true)]
+    pub targets: Vec<String>,
+
+    /// Delete every object whose name starts with `<object>`; with only `<bucket>`, delete all of its objects and then the bucket
+    #[arg(long, short)]
+    pub recursive: bool,
+
+    #[command(flatten)]
+    pub remote: RemoteArgs,
+}
+
+/// 'stat' 命令的参数
+#[derive(Args, Clone)]
+pub struct StatArgs {
+    /// `<bucket>` or `<bucket>/<object>`
+    pub target: String,
+
+    #[command(flatten)]
+    pub remote: RemoteArgs,
+}
+
+pub async fn cp(args: CpArgs, config_path: String) {
+    let client = connect(&args.remote, config_path);
+    let result = match args.source == "-" || Path::new(&args.source).is_file() {
+        true => upload(&client, &args).await,
+        false => download(&client, &args).await,
+    };
+    result.map_err(|e| e.exit_now()).unwrap()
+}
+
+pub async fn ls(args: LsArgs, config_path: String) {
+    let client = connect(&args.remote, config_path);
+    let result = match &args.target {
+        Some(target) => list_objects(&client, target, args.recursive).await,
+        None => list_buckets(&client).await,
+    };
+    result.map_err(|e| e.exit_now()).unwrap()
+}
+
+pub async fn rm(args: RmArgs, config_path: String) {
+    let client = connect(&args.remote, config_path);
+    for target in &args.targets {
+        remove(&client, target, args.recursive)
+            .await
+            .map_err(|e| e.exit_now())
+            .unwrap()
+    }
+}
+
+pub async fn stat(args: StatArgs, config_path: String) {
+    let client = connect(&args.remote, config_path);
+    let when = format!("while reading the metadata of `{}`", args.target);
+    let info = match split_target(&args.target) {
+        (bucket, Some(object)) => client
+            .head_object(bucket, object)
+            .await
+            .map(|v| serde_json::to_string_pretty(&v)),
+        (bucket, None) => client
+            .head_bucket(bucket)
+            .await
+            .map(|v| serde_json::to_string_pretty(&v)),
+    };
+
+    match info {
+        Ok(Ok(json)) => println!("{json}"),
+        Ok(Err(e)) => FatalError::new(ErrorKind::Io, e.to_string(), Some(when)).exit_now(),
+        Err(e) => client_error(e, when).exit_now(),
+    }
+}
+
+/// 命令行和环境变量中没有给出的地址和令牌从配置文件中得到
+pub(super) fn connect(remote: &RemoteArgs, config_path: String) -> Client {
+    let builder = match (&remote.endpoint, &remote.token) {
+        (Some(endpoint), Some(token)) => Client::builder(endpoint).token(token),
+        (endpoint, token) => {
+            let config = app_config::StaticAppConfig::from_file(config_path)
+                .into_runtime()
+                .map_err(|e| e.exit_now())
+                .unwrap();
+
+            let endpoint = endpoint.clone().unwrap_or_else(|| {
+                let scheme = match config.server.tls {
+                    Some(_) => "https",
+                    None => "http",
+                };
+                let port = config.server.port;
+                format!("{scheme}://localhost:{port}{API_VERSION_PREFIX}")
+            });
+
+            let builder = Client::builder(endpoint);
+            match token {
+                Some(token) => builder.token(token),
+                None => {
+                    let encoder_config = config.auth.jwt_encoder_config;
+                    let signer = TokenSigner::new(
+                        encoder_config.encoder,
+                        encoder_config.issue_as,
+                        &encoder_config.audience,
+                        PermissionSet::new_root(),
+                    )
+                    .lifetime(encoder_config.expires_in);
+                    builder.signer(signer)
+                }
+            }
+        }
+    };
+
+    builder
+        .build()
+        .map_err(|e| client_error(e, "while connecting to the server".into()).exit_now())
+        .unwrap()
+}
+
+async fn upload(client: &Client, args: &CpArgs) -> Result<(), FatalError> {
+    let CpArgs {
+        source,
+        destination,
+        content_type,
+        user_meta,
+        ..
+    } = args;
+
+    // 目标只给出了 bucket 或者以 `/` 结尾时，沿用本地文件的名称
+    let (bucket, object) = split_target(destination);
+    let file_name = Path::new(source).file_name().and_then(|v| v.to_str());
+    let object = match (object, file_name) {
+        (Some(object), _) if !object.ends_with('/') => object.to_string(),
+        (object, Some(file_name)) if source != "-" => {
+            format!("{}{file_name}", object.unwrap_or_default())
+        }
+        _ => {
+            return Err(invalid(format!(
+                "`{destination}` is not in the form of `<bucket>/<object>`"
+            )));
+        }
+    };
+
+    let data = match source.as_str() {
+        "-" => {
+            let mut data = vec![];
+            tokio::io::stdin()
+                .read_to_end(&mut data)
+                .await
+                .map(|_| data)
+        }
+        path => tokio::fs::read(path).await,
+    }
+    .map_err(|e| io_error(e, format!("while reading `{source}`")))?;
+    let size = data.len();
+
+    let options = PutOptions {
+        content_type: content_type.clone(),
+        user_meta: user_meta.clone(),
+        ..Default::default()
+    };
+    client
+        .put_object(bucket, &object, data, &options)
+        .await
+        .map_err(|e| client_error(e, format!("while uploading to `{bucket}/{object}`")))?;
+
+    eprintln!("{source} -> {bucket}/{object} ({size} bytes)");
+    Ok(())
+}
+
+async fn download(client: &Client, args: &CpArgs) -> Result<(), FatalError> {
+    let CpArgs {
+        source,
+        destination,
+        ..
+    } = args;
+
+    let (bucket, Some(object)) = split_target(source) else {
+        return Err(invalid(format!(
+            "`{source}` is neither a local file nor in the form of `<bucket>/<object>`"
+        )));
+    };
+    let when = format!("while downloading `{source}`");
+    let mut stream = client
+        .get_object_stream(bucket, object)
+        .await
+        .map_err(|e| client_error(e, when.clone()))?;
+
+    if destination == "-" {
+        let mut stdout = tokio::io::stdout();
+        write_all(&mut stream, &mut stdout, &when).await?;
+        return stdout
+            .flush()
+            .await
+            .map_err(|e| io_error(e, "while writing to the standard output".into()));
+    }
+
+    // 目标是已经存在的目录时，沿用 object 名称的最后一段
+    let mut path = PathBuf::from(destination);
+    if path.is_dir() {
+        path.push(object.rsplit('/').next().unwrap_or(object));
+    }
+    let mut file = tokio::fs::File::create(&path)
+        .await
+        .map_err(|e| io_error(e, format!("while creating `{}`", path.display())))?;
+
+    // 没有完整下载的文件没有意义，直接删除
+    let written = write_all(&mut stream, &mut file, &when).await;
+    let written = match written {
+        Ok(size) => file
+            .sync_all()
+            .await
+            .map(|_| size)
+            .map_err(|e| io_error(e, format!("while writing `{}`", path.display()))),
+        Err(e) => Err(e),
+    };
+    match written {
+        Ok(size) => {
+            eprintln!("{source} -> {} ({size} bytes)", path.display());
+            Ok(())
+        }
+        Err(e) => {
+            let _ = tokio::fs::remove_file(&path).await;
+            Err(e)
+        }
+    }
+}
+
+async fn list_buckets(client: &Client) -> Result<(), FatalError> {
+    let buckets = client
+        .list_buckets(None)
+        .await
+        .map_err(|e| client_error(e, "while listing buckets".into()))?;
+
+    for bucket in buckets {
+        println!(
+            "{}  {}",
+            bucket.created_at.format("%Y-%m-%d %H:%M:%S"),
+            bucket.name
+        );
+    }
+    Ok(())
+}
+
+async fn list_objects(client: &Client, target: &str, recursive: bool) -> Result<(), FatalError> {
+    let (bucket, prefix) = split_target(target);
+    let options = ListObjectsOptions {
+        prefix: prefix.map(ToString::to_string),
+        delimiter: (!recursive).then(|| "/".to_string()),
+        ..Default::default()
+    };
+
+    let when = format!("while listing `{target}`");
+    let (objects, prefixes) = list_all(client, bucket, options)
+        .await
+        .map_err(|e| client_error(e, when))?;
+
+    for prefix in prefixes {
+        println!("{:>19}  {:>12}  {prefix}", "", "PRE");
+    }
+    for object in objects {
+        println!(
+            "{}  {:>12}  {}",
+            object.updated_at.format("%Y-%m-%d %H:%M:%S"),
+            object.size,
+            object.object_name
+        );
+    }
+    Ok(())
+}
+
+async fn remove(client: &Client, target: &str, recursive: bool) -> Result<(), FatalError> {
+    let when = format!("while deleting `{target}`");
+    let (bucket, object) = split_target(target);
+
+    if !recursive {
+        match object {
+            Some(object) => client.delete_object(bucket, object).await,
+            None => client.delete_bucket(bucket).await,
+        }
+        .map_err(|e| client_error(e, when))?;
+        eprintln!("deleted {target}");
+        return Ok(());
+    }
+
+    let options = ListObjectsOptions {
+        prefix: object.map(ToString::to_string),
+        ..Default::default()
+    };
+    let (objects, _) = list_all(client, bucket, options)
+        .await
+        .map_err(|e| client_error(e, when.clone()))?;
+
+    let deleted = objects.len();
+    stream::iter(objects)
+        .map(|v| async move {
+            client
+                .delete_object(bucket, &v.object_name)
+                .await
+                .map_err(|e| {
+                    client_error(e, format!("while deleting `{bucket}/{}`", v.object_name))
+                })
+        })
+        .buffer_unordered(DELETE_CONCURRENCY)
+        .try_collect::<()>()
+        .await?;
+
+    if object.is_none() {
+        client
+            .delete_bucket(bucket)
+            .await
+            .map_err(|e| client_error(e, when))?;
+    }
+    eprintln!("deleted {deleted} object(s) under {target}");
+    Ok(())
+}
+
+/// 列出所有的页，返回 object 和公共前缀
+async fn list_all(
+    client: &Client,
+    bucket: &str,
+    mut options: ListObjectsOptions,
+) -> Result<(Vec<ObjectInfo>, Vec<String>), ClientError> {
+    let (mut objects, mut prefixes) = (vec![], vec![]);
+    loop {
+        let page = client.list_objects(bucket, &options).await?;
+        objects.extend(page.objects);
+        prefixes.extend(page.common_prefixes);
+        match page.next_continuation_token {
+            Some(token) if page.is_truncated => options.continuation_token = Some(token),
+            _ => return Ok((objects, prefixes)),
+        }
+    }
+}
+
+async fn write_all<W: AsyncWrite + Unpin>(
+    stream: &mut (impl futures::Stream<Item = Result<bytes::Bytes, ClientError>> + Unpin),
+    writer: &mut W,
+    when: &str,
+) -> Result<u64, FatalError> {
+    let mut size = 0;
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(|e| client_error(e, when.to_string()))?;
+        writer
+            .write_all(&chunk)
+            .await
+            .map_err(|e| io_error(e, when.to_string()))?;
+        size += chunk.len() as u64;
+    }
+    Ok(size)
+}
+
+/// `<bucket>/<object>` 分成 bucket 和 object，没有 object 或者 object 为空时返回 `None`
+fn split_target(target: &str) -> (&str, Option<&str>) {
+    let target = target.trim_start_matches('/');
+    match target.split_once('/') {
+        Some((bucket, "")) => (bucket, None),
+        Some((bucket, object)) => (bucket, Some(object)),
+        None => (target, None),
+    }
+}
+
+fn parse_user_meta(value: &str) -> Result<Value, String> {
+    match serde_json::from_str(value) {
+        Ok(value @ Value::Object(_)) => Ok(value),
+        Ok(_) => Err("the user metadata should be a JSON object".into()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn invalid(message: String) -> FatalError {
+    FatalError::new(ErrorKind::InvalidValue, message, None)
+}
+
+fn client_error(e: ClientError, when: String) -> FatalError {
+    FatalError::new(ErrorKind::Io, e.to_string(), Some(when))
+}
@@ -1,7 +1,7 @@
 use clap::Args;
 use crab_vault::logger::LogLevel;
 
-#[derive(Args)]
+#[derive(Args, Clone)]
 pub struct RunArgs {
     /// Listening port number of server.
     #[arg(long = "port", short = 'p')]
@@ -0,0 +1,325 @@
+use std::{
+    io::{self, Read},
+    sync::Arc,
+};
+
+use chrono::{DateTime, Duration};
+use clap::{Args, Subcommand, error::ErrorKind};
+use crab_vault::auth::{
+    Capability, HttpMethod, Jwt, JwtDecoder, MAX_PERMISSIONS, Permission, PermissionSet,
+    revocation::FileRevocationStore,
+};
+use serde_json::json;
+
+use crate::{
+    app_config::{self, AppConfig, ConfigItem},
+    cli::presign::parse_duration,
+    error::fatal::FatalError,
+};
+
+#[derive(Subcommand, Clone)]
+pub enum Command {
+    /// Mint a token signed with the keys in the configuration file
+    #[command(name = "issue")]
+    Issue(Box<IssueArgs>),
+
+    /// Print the header and claims of a token, then validate it against the configured keys
+    #[command(name = "inspect")]
+    Inspect(InspectArgs),
+}
+
+/// 'token issue' 命令的参数
+#[derive(Args, Clone)]
+pub struct IssueArgs {
+    /// Allowed HTTP methods, repeatable or comma-separated (e.g. `--method get --method head`)
+    #[arg(long, value_delimiter = ',', default_value = "all")]
+    pub method: Vec<HttpMethod>,
+
+    /// Resource pattern (UNIX shell wildcard), a leading `/` is added when missing (e.g. `images/*`)
+    #[arg(long, default_value = "*")]
+    pub resource: String,
+
+    /// The max size of a request body, e.g. `512`, `64KiB`, `10MB`, unlimited if not provided
+    #[arg(long, value_parser = parse_size)]
+    pub max_size: Option<usize>,
+
+    /// Allowed content types (UNIX shell wildcard), repeatable or comma-separated
+    #[arg(long, value_delimiter = ',', default_value = "*")]
+    pub content_type: Vec<String>,
+
+    /// How long the token stays valid, e.g. `90s`, `30m`, `1h`, `7d`, defaults to `auth.jwt_encoder_config.expires_in`
+    #[arg(long, value_parser = parse_duration)]
+    pub expires_in: Option<Duration>,
+
+    /// How long until the token becomes valid, defaults to `auth.jwt_encoder_config.not_valid_in`
+    #[arg(long, value_parser = parse_duration)]
+    pub not_before: Option<Duration>,
+
+    /// The issuer of this token, defaults to `auth.jwt_encoder_config.issue_as`
+    #[arg(long)]
+    pub issuer: Option<String>,
+
+    /// The audiences of this token, comma-separated, defaults to `auth.jwt_encoder_config.audience`
+    #[arg(long, value_delimiter = ',')]
+    pub audience: Option<Vec<String>>,
+
+    /// Methods denied even when `--method` allows them, repeatable or comma-separated,
+    /// restricted to `--deny-resource` if it is given
+    #[arg(long, value_delimiter = ',')]
+    pub deny_method: Vec<HttpMethod>,
+
+    /// Resource pattern denied even when `--resource` matches it (e.g. `*/secrets/*`),
+    /// restricted to `--deny-method` if it is given
+    #[arg(long)]
+    pub deny_resource: Option<String>,
+
+    /// Forbid listing objects in buckets, the token can still access objects whose names are known
+    #[arg(long)]
+    pub no_list: bool,
+
+    /// Only allow objects carrying this tag, `KEY=PATTERN` where the value is matched as a UNIX shell wildcard,
+    /// repeatable (e.g. `--tag team=infra --tag stage=prod-*`)
+    #[arg(long, value_parser = parse_tag)]
+    pub tag: Vec<(String, String)>,
+
+    /// Grant operations beyond the allowed methods, repeatable or comma-separated
+    /// (e.g. `--capability force-delete` with `--method delete` to delete non-empty buckets)
+    #[arg(long, value_delimiter = ',')]
+    pub capability: Vec<Capability>,
+
+    /// Sign with this key, a random configured key is used if not provided
+    #[arg(long)]
+    pub kid: Option<String>,
+
+    /// Grant the permissions in this JSON file (a permission object or an array of them) instead of
+    /// the ones described by the other permission flags
+    #[arg(long, conflicts_with_all = [
+        "method", "resource", "max_size", "content_type", "deny_method", "deny_resource", "no_list",
+        "tag", "capability",
+    ])]
+    pub policy: Option<String>,
+}
+
+/// 'token inspect' 命令的参数
+#[derive(Args, Clone)]
+pub struct InspectArgs {
+    /// The token to inspect, read from standard input if not provided or `-`
+    pub jwt: Option<String>,
+}
+
+pub fn exec(cmd: Command, config_path: String) {
+    let config = app_config::StaticAppConfig::from_file(config_path)
+        .into_runtime()
+        .map_err(|e| e.exit_now())
+        .unwrap();
+
+    match cmd {
+        Command::Issue(args) => issue(*args, config),
+        Command::Inspect(args) => inspect(args, config),
+    }
+    .map_err(|e| e.exit_now())
+    .unwrap()
+}
+
+fn issue(args: IssueArgs, config: AppConfig) -> Result<(), FatalError> {
+    let invalid = |msg: String| FatalError::new(ErrorKind::InvalidValue, msg, None);
+    let encoder_config = &config.auth.jwt_encoder_config;
+
+    let payload = match &args.policy {
+        Some(path) => {
+            let policy = std::fs::read_to_string(path)?;
+            serde_json::from_str::<PermissionSet>(&policy)
+                .map_err(|e| invalid(format!("`{path}` is not a valid policy: {e}")))?
+        }
+        None => Permission::new_minimum()
+            .permit_method(args.method)
+            .permit_resource_pattern(args.resource)
+            .restrict_maximum_size_option(args.max_size)
+            .permit_content_type(args.content_type)
+            .deny_method(args.deny_method)
+            .deny_resource_pattern_option(args.deny_resource)
+            .permit_list(!args.no_list)
+            .require_tags(args.tag.into_iter().collect())
+            .permit_capability(args.capability)
+            .into(),
+    };
+
+    if payload.permissions.is_empty() || payload.permissions.len() > MAX_PERMISSIONS {
+        return Err(invalid(format!(
+            "a token carries 1 to {MAX_PERMISSIONS} permissions, got {}",
+            payload.permissions.len()
+        )));
+    }
+
+    let payload = payload
+        .permissions
+        .into_iter()
+        .map(|permission| {
+            // 服务端鉴权时看到的路径总是以 `/` 开头
+            let absolute = |v: String| match v.starts_with(['/', '*']) {
+                true => v,
+                false => format!("/{v}"),
+            };
+            let resource = permission.resource_pattern.clone().map(absolute);
+            let denied = permission.denied_resource_pattern.clone().map(absolute);
+
+            // 服务端会忽略超出复杂度限制的模式，这样签发出来的令牌什么都访问不了；
+            // 禁止规则中的这种模式则会禁止所有路径
+            for pattern in resource
+                .iter()
+                .chain(&denied)
+                .chain(&permission.allowed_content_types)
+                .chain(permission.tags.values())
+            {
+                config
+                    .auth
+                    .glob_limits
+                    .check(pattern)
+                    .map_err(|e| invalid(format!("`{pattern}` cannot be used, because {e}")))?;
+            }
+
+            Ok(permission
+                .permit_resource_pattern_option(resource)
+                .deny_resource_pattern_option(denied))
+        })
+        .collect::<Result<PermissionSet, FatalError>>()?;
+
+    let issuer = args
+        .issuer
+        .unwrap_or_else(|| encoder_config.issue_as.clone());
+    let audience = args
+        .audience
+        .unwrap_or_else(|| encoder_config.audience.to_vec());
+
+    let claims = Jwt::new(issuer, &audience, payload)
+        .expires_in(args.expires_in.unwrap_or(encoder_config.expires_in))
+        .not_valid_in(args.not_before.unwrap_or(encoder_config.not_valid_in));
+
+    let token = match &args.kid {
+        Some(kid) => encoder_config.encoder.encode(&claims, kid),
+        None => encoder_config.encoder.encode_randomly(&claims),
+    }
+    .map_err(|e| FatalError::new(ErrorKind::Io, format!("JWT encoding failed: {e}"), None))?;
+
+    eprintln!(
+        "Token {} expires at {}",
+        claims.jti,
+        format_timestamp(claims.exp)
+    );
+    println!("{token}");
+    Ok(())
+}
+
+fn inspect(args: InspectArgs, config: AppConfig) -> Result<(), FatalError> {
+    let token = match args.jwt {
+        Some(token) if token != "-" => token,
+        _ => {
+            let mut token = String::new();
+            io::stdin().read_to_string(&mut token)?;
+            token
+        }
+    };
+
+    let token = token.trim();
+    if token.is_empty() {
+        return Err(FatalError::new(
+            ErrorKind::InvalidValue,
+            "No token to inspect.".to_string(),
+            None,
+        ));
+    }
+
+    let header = jsonwebtoken::decode_header(token).map_err(|e| {
+        FatalError::new(
+            ErrorKind::InvalidValue,
+            format!("Malformed token header: {e}"),
+            None,
+        )
+    })?;
+    let claims = JwtDecoder::decode_unchecked(token).map_err(FatalError::from)?;
+
+    let pretty_json = serde_json::to_string_pretty(&json!({ "header": header, "claims": claims }))
+        .map_err(FatalError::from)?;
+    println!("{pretty_json}");
+
+    for (name, claim) in [
+        ("issued at", "iat"),
+        ("not before", "nbf"),
+        ("expires at", "exp"),
+    ] {
+        if let Some(timestamp) = claims.get(claim).and_then(|v| v.as_i64()) {
+            eprintln!("{name:>10}: {}", format_timestamp(timestamp));
+        }
+    }
+
+    match decoder(&config)?.decode::<PermissionSet>(token) {
+        Ok(_) => {
+            eprintln!("Token is valid.");
+            Ok(())
+        }
+        Err(e) => Err(FatalError::new(
+            ErrorKind::InvalidValue,
+            format!("Token is invalid because of {e}"),
+            None,
+        )),
+    }
+}
+
+/// 与服务端一样检查签名、时间、签发者、受众以及吊销列表的解码器
+pub(super) fn decoder(config: &AppConfig) -> Result<JwtDecoder, FatalError> {
+    let decoder = config.auth.jwt_decoder_config.decoder.clone();
+    match &config.auth.revocation_list {
+        Some(path) => Ok(decoder.revocation_store(Arc::new(
+            FileRevocationStore::open(path).map_err(FatalError::from)?,
+        ))),
+        None => Ok(decoder),
+    }
+}
+
+pub(super) fn format_timestamp(timestamp: i64) -> String {
+    match DateTime::from_timestamp(timestamp, 0) {
+        Some(time) => time.to_rfc3339(),
+        None => timestamp.to_string(),
+    }
+}
+
+/// 解析形如 `512`、`64KiB`、`10MB` 的大小，没有单位时视为字节，单位不区分大小写
+fn parse_tag(value: &str) -> Result<(String, String), String> {
+    value
+        .split_once('=')
+        .filter(|(key, _)| !key.is_empty())
+        .map(|(key, pattern)| (key.to_string(), pattern.to_string()))
+        .ok_or_else(|| format!("`{value}` should be `KEY=PATTERN`"))
+}
+
+fn parse_size(value: &str) -> Result<usize, String> {
+    let value = value.trim();
+    let (number, unit) = value.split_at(
+        value
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(value.len()),
+    );
+
+    let number: usize = number
+        .parse()
+        .map_err(|_| format!("`{value}` is not a valid size"))?;
+
+    let multiplier: usize = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "kb" => 1000,
+        "mb" => 1000 * 1000,
+        "gb" => 1000 * 1000 * 1000,
+        "kib" => 1 << 10,
+        "mib" => 1 << 20,
+        "gib" => 1 << 30,
+        _ => {
+            return Err(format!(
+                "unknown size unit `{unit}`, expected one of B, KB, MB, GB, KiB, MiB, GiB"
+            ));
+        }
+    };
+
+    number
+        .checked_mul(multiplier)
+        .ok_or_else(|| format!("`{value}` is out of range"))
+}
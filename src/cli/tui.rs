@@ -0,0 +1,650 @@
+use std::{
+    collections::VecDeque,
+    fs::{self, File},
+    io::{self, Read, Seek, SeekFrom},
+    path::PathBuf,
+    time::Duration,
+};
+
+use clap::Args;
+use crab_vault::{
+    auth::{JwtDecoder, PermissionSet},
+    client::{BucketInfo, Client, ListObjectsOptions, ObjectInfo},
+};
+use ratatui::{
+    DefaultTerminal, Frame,
+    crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    layout::{Constraint, Layout},
+    style::{Color, Modifier, Style, Stylize},
+    text::{Line, Span},
+    widgets::{Block, List, ListItem, ListState, Paragraph, Tabs, Wrap},
+};
+use serde_json::json;
+use tokio::sync::mpsc;
+
+use crate::{
+    app_config::{self, AppConfig, ConfigItem, access_log::AccessLogSink},
+    cli::{
+        audit::io_error,
+        remote::{self, RemoteArgs},
+        token::{decoder, format_timestamp},
+    },
+    http::access_log::AccessRecord,
+};
+
+/// 每次列出的 object 数，选中最后一个时再读取下一页
+const PAGE_SIZE: u32 = 200;
+
+/// 最多保留的访问记录数
+const MAX_LOG_RECORDS: usize = 1000;
+
+/// 检查访问日志文件有没有新内容的间隔
+const LOG_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// 读取终端事件的线程检查界面是否已经退出的间隔
+const EVENT_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// 令牌输入框的高度，令牌通常需要折成几行才能显示完整
+const TOKEN_INPUT_HEIGHT: u16 = 6;
+
+/// 'tui' 命令的参数
+#[derive(Args, Clone)]
+pub struct TuiArgs {
+    #[command(flatten)]
+    pub remote: RemoteArgs,
+}
+
+pub async fn exec(args: TuiArgs, config_path: String) {
+    let client = remote::connect(&args.remote, config_path.clone());
+
+    // 只使用 `--endpoint` 和 `--token` 时没有配置文件也可以浏览，只是看不到访问日志，也无法校验令牌
+    let config = app_config::StaticAppConfig::from_file(config_path)
+        .into_runtime()
+        .ok();
+    let mut app = App::new(client, config.as_ref());
+    app.load_buckets().await;
+
+    let when = "while drawing the terminal interface";
+    let terminal = match ratatui::try_init() {
+        Ok(terminal) => terminal,
+        Err(e) => {
+            ratatui::restore();
+            io_error(e, when.into()).exit_now()
+        }
+    };
+    let result = app.run(terminal).await;
+    ratatui::restore();
+    result
+        .map_err(|e| io_error(e, when.into()).exit_now())
+        .unwrap()
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Tab {
+    Buckets,
+    Logs,
+    Token,
+}
+
+impl Tab {
+    const ALL: [Tab; 3] = [Tab::Buckets, Tab::Logs, Tab::Token];
+
+    fn title(self) -> &'static str {
+        match self {
+            Tab::Buckets => "Buckets",
+            Tab::Logs => "Access log",
+            Tab::Token => "Token",
+        }
+    }
+
+    fn help(self) -> &'static str {
+        match self {
+            Tab::Buckets => {
+                "↑↓ select  enter open  esc back  d delete  r refresh  tab switch  q quit"
+            }
+            Tab::Logs => "↑↓ scroll  end follow  tab switch  q quit",
+            Tab::Token => "paste a token  enter inspect  esc clear  tab switch  ctrl-c quit",
+        }
+    }
+}
+
+struct App {
+    client: Client,
+    tab: Tab,
+    buckets: Vec<BucketInfo>,
+    bucket_list: ListState,
+
+    /// 正在浏览的 bucket
+    opened: Option<OpenedBucket>,
+
+    /// 等待按下 `y` 确认删除的 object
+    confirm: Option<(String, String)>,
+    logs: LogTail,
+    token: TokenPane,
+
+    /// 最近一次操作的结果，显示在底部
+    status: String,
+    quit: bool,
+}
+
+struct OpenedBucket {
+    name: String,
+    objects: Vec<ObjectInfo>,
+    list: ListState,
+
+    /// 还有下一页时为下一页的令牌
+    next: Option<String>,
+}
+
+/// 跟随 `access_log.path` 下最新的日志文件，与 `tail -f` 一样
+struct LogTail {
+    /// 访问日志没有写入文件时为 [`None`]
+    dir: Option<PathBuf>,
+
+    /// 正在读取的文件和已经读取的字节数，只读取到最后一个完整的行
+    file: Option<(PathBuf, u64)>,
+    records: VecDeque<AccessRecord>,
+    list: ListState,
+
+    /// 是否总是选中最新的记录
+    follow: bool,
+    error: Option<String>,
+}
+
+struct TokenPane {
+    input: String,
+    output: Vec<String>,
+
+    /// 没有读到配置文件时无法校验签名
+    decoder: Option<JwtDecoder>,
+}
+
+impl App {
+    fn new(client: Client, config: Option<&AppConfig>) -> Self {
+        let dir = config
+            .filter(|v| v.access_log.sink == AccessLogSink::File)
+            .and_then(|v| v.access_log.path.clone());
+
+        Self {
+            client,
+            tab: Tab::Buckets,
+            buckets: vec![],
+            bucket_list: ListState::default(),
+            opened: None,
+            confirm: None,
+            logs: LogTail {
+                dir,
+                file: None,
+                records: VecDeque::new(),
+                list: ListState::default(),
+                follow: true,
+                error: None,
+            },
+            token: TokenPane {
+                input: String::new(),
+                output: vec![],
+                decoder: config.and_then(|v| decoder(v).ok()),
+            },
+            status: String::new(),
+            quit: false,
+        }
+    }
+
+    async fn run(&mut self, mut terminal: DefaultTerminal) -> io::Result<()> {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        tokio::task::spawn_blocking(move || read_events(tx));
+
+        let mut ticker = tokio::time::interval(LOG_POLL_INTERVAL);
+        while !self.quit {
+            terminal.draw(|frame| self.draw(frame))?;
+            tokio::select! {
+                event = rx.recv() => match event {
+                    Some(Ok(Event::Key(key))) if key.kind == KeyEventKind::Press => self.on_key(key).await,
+                    Some(Ok(_)) => {}
+                    Some(Err(e)) => return Err(e),
+                    None => break,
+                },
+                _ = ticker.tick() => self.logs.poll(),
+            }
+        }
+        Ok(())
+    }
+
+    async fn on_key(&mut self, key: KeyEvent) {
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            self.quit = true;
+            return;
+        }
+        if key.code == KeyCode::Tab {
+            let next = Tab::ALL.iter().position(|v| *v == self.tab).unwrap_or(0) + 1;
+            self.tab = Tab::ALL[next % Tab::ALL.len()];
+            return;
+        }
+
+        match self.tab {
+            // 输入令牌时 `q` 是令牌的一部分
+            Tab::Token => self.token.on_key(key),
+            _ if key.code == KeyCode::Char('q') => self.quit = true,
+            Tab::Buckets => self.on_bucket_key(key).await,
+            Tab::Logs => self.logs.on_key(key),
+        }
+    }
+
+    async fn on_bucket_key(&mut self, key: KeyEvent) {
+        if let Some((bucket, object)) = self.confirm.take() {
+            if key.code == KeyCode::Char('y') {
+                self.delete_object(bucket, object).await;
+            } else {
+                self.status = "Cancelled.".into();
+            }
+            return;
+        }
+
+        match (key.code, &mut self.opened) {
+            (KeyCode::Up | KeyCode::Char('k'), None) => self.bucket_list.select_previous(),
+            (KeyCode::Down | KeyCode::Char('j'), None) => self.bucket_list.select_next(),
+            (KeyCode::Enter, None) => self.open_bucket().await,
+            (KeyCode::Char('r'), None) => self.load_buckets().await,
+            (KeyCode::Up | KeyCode::Char('k'), Some(opened)) => opened.list.select_previous(),
+            (KeyCode::Down | KeyCode::Char('j'), Some(opened)) => {
+                opened.list.select_next();
+                if opened.list.selected() >= Some(opened.objects.len().saturating_sub(1)) {
+                    self.load_objects().await;
+                }
+            }
+            (KeyCode::Esc | KeyCode::Backspace, Some(_)) => self.opened = None,
+            (KeyCode::Char('r'), Some(opened)) => {
+                (opened.objects, opened.next) = (vec![], None);
+                opened.list.select(None);
+                self.load_objects().await;
+            }
+            (KeyCode::Char('d'), Some(opened)) => {
+                if let Some(object) = opened.list.selected().and_then(|v| opened.objects.get(v)) {
+                    self.status = format!(
+                        "Delete {}/{}? (y/n)",
+                        object.bucket_name, object.object_name
+                    );
+                    self.confirm = Some((object.bucket_name.clone(), object.object_name.clone()));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    async fn load_buckets(&mut self) {
+        match self.client.list_buckets(None).await {
+            Ok(buckets) => {
+                self.status = format!("{} bucket(s)", buckets.len());
+                self.bucket_list.select((!buckets.is_empty()).then_some(0));
+                self.buckets = buckets;
+            }
+            Err(e) => self.status = format!("Cannot list the buckets: {e}"),
+        }
+    }
+
+    /// 打开选中的 bucket，同时读取它的用量
+    async fn open_bucket(&mut self) {
+        let Some(index) = self.bucket_list.selected() else {
+            return;
+        };
+        let Some(bucket) = self.buckets.get_mut(index) else {
+            return;
+        };
+        if let Ok(info) = self.client.head_bucket(&bucket.name).await {
+            *bucket = info;
+        }
+
+        self.opened = Some(OpenedBucket {
+            name: bucket.name.clone(),
+            objects: vec![],
+            list: ListState::default(),
+            next: None,
+        });
+        self.load_objects().await;
+    }
+
+    /// 读取下一页 object，已经读完时什么也不做
+    async fn load_objects(&mut self) {
+        let Some(opened) = &mut self.opened else {
+            return;
+        };
+        if !opened.objects.is_empty() && opened.next.is_none() {
+            return;
+        }
+
+        let options = ListObjectsOptions {
+            max_keys: Some(PAGE_SIZE),
+            continuation_token: opened.next.take(),
+            ..Default::default()
+        };
+        match self.client.list_objects(&opened.name, &options).await {
+            Ok(page) => {
+                opened.objects.extend(page.objects);
+                opened.next = page.next_continuation_token.filter(|_| page.is_truncated);
+                if opened.list.selected().is_none() && !opened.objects.is_empty() {
+                    opened.list.select(Some(0));
+                }
+                self.status = format!(
+                    "{}{} object(s) in {}",
+                    opened.objects.len(),
+                    if opened.next.is_some() { "+" } else { "" },
+                    opened.name
+                );
+            }
+            Err(e) => self.status = format!("Cannot list the objects in {}: {e}", opened.name),
+        }
+    }
+
+    async fn delete_object(&mut self, bucket: String, object: String) {
+        if let Err(e) = self.client.delete_object(&bucket, &object).await {
+            self.status = format!("Cannot delete {bucket}/{object}: {e}");
+            return;
+        }
+
+        self.status = format!("Deleted {bucket}/{object}");
+        if let Some(opened) = &mut self.opened {
+            opened.objects.retain(|v| v.object_name != object);
+            if opened.list.selected() >= Some(opened.objects.len()) {
+                opened.list.select(opened.objects.len().checked_sub(1));
+            }
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [tabs, body, status, help] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Fill(1),
+            Constraint::Length(1),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        let selected = Tab::ALL.iter().position(|v| *v == self.tab).unwrap_or(0);
+        frame.render_widget(
+            Tabs::new(Tab::ALL.map(Tab::title))
+                .select(selected)
+                .highlight_style(Style::new().fg(Color::Green).add_modifier(Modifier::BOLD)),
+            tabs,
+        );
+        frame.render_widget(Line::raw(self.status.as_str()), status);
+        frame.render_widget(Line::raw(self.tab.help()).style(Style::new().dim()), help);
+
+        match self.tab {
+            Tab::Buckets => self.draw_buckets(frame, body),
+            Tab::Logs => self.logs.draw(frame, body),
+            Tab::Token => self.token.draw(frame, body),
+        }
+    }
+
+    fn draw_buckets(&mut self, frame: &mut Frame, area: ratatui::layout::Rect) {
+        let [list, detail] =
+            Layout::horizontal([Constraint::Percentage(40), Constraint::Fill(1)]).areas(area);
+
+        let (title, items, state, selected) = match &mut self.opened {
+            Some(opened) => {
+                let items: Vec<_> = opened
+                    .objects
+                    .iter()
+                    .map(|v| ListItem::new(v.object_name.as_str()))
+                    .collect();
+                let selected = opened
+                    .list
+                    .selected()
+                    .and_then(|v| opened.objects.get(v))
+                    .map(serde_json::to_string_pretty);
+                (opened.name.clone(), items, &mut opened.list, selected)
+            }
+            None => {
+                let items: Vec<_> = self
+                    .buckets
+                    .iter()
+                    .map(|v| ListItem::new(v.name.as_str()))
+                    .collect();
+                let selected = self
+                    .bucket_list
+                    .selected()
+                    .and_then(|v| self.buckets.get(v))
+                    .map(serde_json::to_string_pretty);
+                (
+                    "Buckets".to_string(),
+                    items,
+                    &mut self.bucket_list,
+                    selected,
+                )
+            }
+        };
+
+        frame.render_stateful_widget(
+            List::new(items)
+                .block(Block::bordered().title(title))
+                .highlight_style(Style::new().reversed()),
+            list,
+            state,
+        );
+        frame.render_widget(
+            Paragraph::new(selected.and_then(Result::ok).unwrap_or_default())
+                .block(Block::bordered().title("Metadata"))
+                .wrap(Wrap { trim: false }),
+            detail,
+        );
+    }
+}
+
+impl LogTail {
+    fn on_key(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Up | KeyCode::Char('k') => {
+                self.follow = false;
+                self.list.select_previous();
+            }
+            KeyCode::Down | KeyCode::Char('j') => self.list.select_next(),
+            KeyCode::End | KeyCode::Char('f') => self.follow = true,
+            _ => {}
+        }
+    }
+
+    /// 读取最新的日志文件中新写入的行，出现更新的文件时从头读取它
+    fn poll(&mut self) {
+        let Some(dir) = &self.dir else {
+            return;
+        };
+
+        // 文件名是开始写入的时间，按名称排序即可
+        let latest = fs::read_dir(dir).map(|entries| {
+            entries
+                .filter_map(|v| v.ok().map(|v| v.path()))
+                .filter(|v| v.extension().is_some_and(|v| v == "jsonl"))
+                .max()
+        });
+        let path = match latest {
+            Ok(Some(path)) => path,
+            Ok(None) => return,
+            Err(e) => {
+                self.error = Some(format!("Cannot list {}: {e}", dir.display()));
+                return;
+            }
+        };
+
+        let offset = match &self.file {
+            Some((current, offset)) if *current == path => *offset,
+            _ => 0,
+        };
+        match read_lines(&path, offset) {
+            Ok((lines, read)) => {
+                for line in lines.split(|v| *v == b'\n').filter(|v| !v.is_empty()) {
+                    if let Ok(record) = serde_json::from_slice(line) {
+                        self.records.push_back(record);
+                    }
+                }
+                while self.records.len() > MAX_LOG_RECORDS {
+                    self.records.pop_front();
+                }
+                self.file = Some((path, offset + read));
+                self.error = None;
+            }
+            Err(e) => self.error = Some(format!("Cannot read {}: {e}", path.display())),
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame, area: ratatui::layout::Rect) {
+        let block = Block::bordered().title("Access log");
+        let message = match (&self.dir, &self.error) {
+            (None, _) => Some(
+                "Live request logs are read from the files written with `access_log.sink = \"file\"`, which is not configured.".to_string(),
+            ),
+            (_, Some(error)) => Some(error.clone()),
+            _ => None,
+        };
+        if let Some(message) = message {
+            frame.render_widget(
+                Paragraph::new(message)
+                    .block(block)
+                    .wrap(Wrap { trim: false }),
+                area,
+            );
+            return;
+        }
+
+        if self.follow {
+            self.list.select(self.records.len().checked_sub(1));
+        }
+        let items: Vec<_> = self.records.iter().map(log_line).collect();
+        frame.render_stateful_widget(
+            List::new(items)
+                .block(block)
+                .highlight_style(Style::new().reversed()),
+            area,
+            &mut self.list,
+        );
+    }
+}
+
+impl TokenPane {
+    fn on_key(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Char(c) => self.input.push(c),
+            KeyCode::Backspace => {
+                self.input.pop();
+            }
+            KeyCode::Esc => {
+                self.input.clear();
+                self.output.clear();
+            }
+            KeyCode::Enter => self.output = self.inspect(),
+            _ => {}
+        }
+    }
+
+    /// 与 `token inspect` 相同：先不经校验地解码，再使用配置文件中的密钥和吊销列表校验
+    fn inspect(&self) -> Vec<String> {
+        let token = self.input.trim();
+        let header = match jsonwebtoken::decode_header(token) {
+            Ok(header) => header,
+            Err(e) => return vec![format!("Malformed token header: {e}")],
+        };
+        let claims = match JwtDecoder::decode_unchecked(token) {
+            Ok(claims) => claims,
+            Err(e) => return vec![format!("Malformed token claims: {e}")],
+        };
+
+        let pretty_json =
+            serde_json::to_string_pretty(&json!({ "header": header, "claims": claims }))
+                .unwrap_or_default();
+        let mut output: Vec<_> = pretty_json.lines().map(String::from).collect();
+        output.push(String::new());
+        for (name, claim) in [
+            ("issued at", "iat"),
+            ("not before", "nbf"),
+            ("expires at", "exp"),
+        ] {
+            if let Some(timestamp) = claims.get(claim).and_then(|v| v.as_i64()) {
+                output.push(format!("{name:>10}: {}", format_timestamp(timestamp)));
+            }
+        }
+
+        output.push(match &self.decoder {
+            Some(decoder) => match decoder.decode::<PermissionSet>(token) {
+                Ok(_) => "Token is valid.".into(),
+                Err(e) => format!("Token is invalid because of {e}"),
+            },
+            None => {
+                "The configuration file cannot be read, so the signature is not checked.".into()
+            }
+        });
+        output
+    }
+
+    fn draw(&self, frame: &mut Frame, area: ratatui::layout::Rect) {
+        let [input, output] =
+            Layout::vertical([Constraint::Length(TOKEN_INPUT_HEIGHT), Constraint::Fill(1)])
+                .areas(area);
+
+        frame.render_widget(
+            Paragraph::new(self.input.as_str())
+                .block(Block::bordered().title("Token"))
+                .wrap(Wrap { trim: false }),
+            input,
+        );
+        frame.render_widget(
+            Paragraph::new(
+                self.output
+                    .iter()
+                    .map(|v| Line::raw(v.as_str()))
+                    .collect::<Vec<_>>(),
+            )
+            .block(Block::bordered().title("Header and claims"))
+            .wrap(Wrap { trim: false }),
+            output,
+        );
+    }
+}
+
+/// 在后台线程中读取终端事件，界面退出之后 `tx` 被关闭，线程随之结束
+fn read_events(tx: mpsc::UnboundedSender<io::Result<Event>>) {
+    loop {
+        let event = match event::poll(EVENT_POLL_INTERVAL) {
+            Ok(true) => event::read(),
+            Ok(false) if tx.is_closed() => return,
+            Ok(false) => continue,
+            Err(e) => Err(e),
+        };
+        if tx.send(event).is_err() {
+            return;
+        }
+    }
+}
+
+/// 从 `offset` 读取到最后一个完整的行，返回读到的行和它们的字节数
+fn read_lines(path: &PathBuf, offset: u64) -> io::Result<(Vec<u8>, u64)> {
+    let mut file = File::open(path)?;
+    file.seek(SeekFrom::Start(offset))?;
+    let mut data = vec![];
+    file.read_to_end(&mut data)?;
+
+    let complete = data.iter().rposition(|v| *v == b'\n').map_or(0, |v| v + 1);
+    data.truncate(complete);
+    Ok((data, complete as u64))
+}
+
+fn log_line(record: &AccessRecord) -> ListItem<'_> {
+    let color = match record.status {
+        500.. => Color::Red,
+        400.. => Color::Yellow,
+        _ => Color::Green,
+    };
+
+    ListItem::new(Line::from(vec![
+        Span::raw(format!("{} ", record.time.format("%H:%M:%S"))),
+        Span::styled(format!("{} ", record.status), Style::new().fg(color)),
+        Span::raw(format!("{:<6} {} ", record.method, record.path)),
+        Span::styled(
+            format!(
+                "{}ms {}B {}",
+                record.latency_ms,
+                record.bytes_out,
+                record.issuer.as_deref().unwrap_or("-")
+            ),
+            Style::new().dim(),
+        ),
+    ]))
+}
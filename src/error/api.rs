@@ -3,42 +3,184 @@ use axum::{
     response::{IntoResponse, Response},
 };
 use serde::Serialize;
+use thiserror::Error;
 
-#[derive(Serialize)]
+use crate::error::context::RequestError;
+
+#[derive(Serialize, Error, Debug)]
 #[serde(rename_all = "camelCase", untagged)]
 pub enum ApiError {
+    #[error(transparent)]
     Client(ClientError),
+    #[error(transparent)]
     Server(ServerError),
 }
 
 #[non_exhaustive]
-#[derive(Serialize)]
+#[derive(Serialize, Error, Debug)]
 #[serde(rename_all = "camelCase", tag = "code")]
 pub enum ClientError {
     /// 没有 content type 这个头部
+    #[error("missing the content type header")]
     MissingContentType,
 
-    /// content type 这个头部的值没有通过 [`Permission`](crab_vault::auth::Permission) 校验
+    /// content type 这个头部的值，或者推断出的类型没有通过 [`Permission`](crab_vault::auth::Permission) 校验
+    #[error("the content type is not allowed")]
     InvalidContentType,
 
     /// 没有 content length 这个头部
+    #[error("missing the content length header")]
     MissingContentLength,
 
-    /// 报文部分太大了
+    /// 报文部分太大了，超出了 `server.max_body_size` 或者令牌中的 `maxSize`
+    #[error("the request body is too large")]
     BodyTooLarge,
 
+    /// 超过 `server.read_timeout` 仍然没有收到请求体的下一段数据
+    #[error("timed out while receiving the request body")]
+    RequestTimeout,
+
+    /// 超出了 `server.rate_limit` 中 `scope` 范围的限制，响应中的 `Retry-After` 是需要等待的秒数
+    #[error("too many requests, limited by the {scope} rate limit")]
+    TooManyRequests { scope: &'static str },
+
+    /// 接收请求体的过程中连接出错，没有收到完整的请求体
+    #[error("the connection failed before the request body was received")]
+    IncompleteBody,
+
+    /// `header` 中声明的请求体摘要无法解析
+    #[error("cannot parse the digest in `{header}`")]
+    InvalidDigest { header: &'static str },
+
+    /// 收到的请求体与 `header` 中声明的摘要不一致，请求体在传输中被损坏了
+    #[error("the request body does not match the digest in `{header}`")]
+    BadDigest { header: &'static str },
+
     /// uri 错误
+    #[error("the uri is invalid")]
     UriInvalid,
 
     /// 值解析出错，比如 content length 应该是一个数字，但是你传了一个字符串
+    #[error("cannot parse a header value")]
     ValueParsingError,
 
     /// HTTP 规定头部中不允许有除了 **可见** ASCII 之外的字符，如果有，就会产生这个错误
+    #[error("a header contains non-visible ascii characters")]
     HeaderWithOpaqueBytes,
 
     /// base64 解码错误
+    #[error("cannot decode a base64 value")]
     Base64DecodeError,
 
+    /// `X-Crab-Vault-Copy-Source` 不是 `/{bucket}/{object}` 的形式
+    #[error("the copy source must be `/{{bucket}}/{{object}}`")]
+    InvalidCopySource,
+
+    /// `X-Crab-Vault-Metadata-Directive` 既不是 `COPY` 也不是 `REPLACE`
+    #[error("the metadata directive must be `COPY` or `REPLACE`")]
+    InvalidMetadataDirective,
+
+    /// `If-None-Match` 只支持 `*`，`If-Match` 只支持单个 etag，两者不能同时出现，也不能用于服务端复制
+    #[error("the precondition is not supported")]
+    UnsupportedPrecondition,
+
+    /// 配额头部 `header` 的值既不是非负整数也不是 `none`
+    #[error("the quota in `{header}` must be a non-negative integer or `none`")]
+    InvalidQuota { header: &'static str },
+
+    /// `X-Crab-Vault-Expires-At` 或者 `X-Crab-Vault-Ttl` 无法解析、已经过去，或者同时给出了两者
+    #[error("invalid expiry: {reason}")]
+    InvalidExpiry { reason: &'static str },
+
+    /// `X-Crab-Vault-Sse-Key` 和 `X-Crab-Vault-Sse-Key-Md5` 没有同时给出、无法解析，或者摘要不一致
+    #[error("invalid customer-provided key: {reason}")]
+    InvalidCustomerKey { reason: &'static str },
+
+    /// user meta 中包含保留给服务端的键，参见 `crab_vault::engine::crypto::RESERVED_META_PREFIX`
+    #[error("the user meta key `{key}` is reserved")]
+    ReservedMetaKey { key: String },
+
+    /// `Content-Range` 无法解析、与请求体的长度不一致，或者与 `?append` 同时给出
+    #[error("invalid content range: {reason}")]
+    InvalidContentRange { reason: &'static str },
+
+    /// object 压缩或者加密过，不能修改其中的一部分，只能整体替换
+    #[error("the object cannot be patched: {reason}")]
+    ObjectNotPatchable { reason: &'static str },
+
+    /// 表单上传的请求体无法解析，或者缺少 `key`、`file` 字段
+    #[error("invalid form: {reason}")]
+    InvalidForm { reason: &'static str },
+
+    /// 批量操作中包含的 object 过多
+    #[error("too many objects, at most {max} are allowed")]
+    TooManyObjects { max: usize },
+
+    /// 没有版本前缀的路径已经停止服务，`successor` 是对应的新路径
+    #[error("this path is no longer served, use `{successor}` instead")]
+    UnversionedPath { successor: String },
+
+    /// bucket 没有设置策略
+    #[error("the bucket has no policy")]
+    NoBucketPolicy,
+
+    /// bucket 策略能够解析，但是没有通过校验，例如语句过多或者模式过于复杂
+    #[error("invalid bucket policy: {reason}")]
+    InvalidBucketPolicy { reason: String },
+
+    /// bucket 没有设置生命周期规则
+    #[error("the bucket has no lifecycle configuration")]
+    NoLifecycleConfig,
+
+    /// 生命周期规则能够解析，但是没有意义，例如没有规则或者过期天数为 0
+    #[error("invalid lifecycle configuration: {reason}")]
+    InvalidLifecycle { reason: String },
+
+    /// bucket 没有设置清单
+    #[error("the bucket has no inventory configuration")]
+    NoInventoryConfig,
+
+    /// 清单配置能够解析，但是目标 bucket 的名称或者清单的名称不合法
+    #[error("invalid inventory configuration: {reason}")]
+    InvalidInventory { reason: String },
+
+    /// object 的标签能够解析，但是数量或者长度超出了限制
+    #[error("invalid tagging: {reason}")]
+    InvalidTagging { reason: String },
+
+    /// object 的保留设置能够解析，但是保留期已经过去了
+    #[error("invalid retention: {reason}")]
+    InvalidRetention { reason: String },
+
+    /// bucket 没有设置自己的跨域规则
+    #[error("the bucket has no cors configuration")]
+    NoCorsConfig,
+
+    /// 跨域规则能够解析，但是其中的来源、方法或者头部无效
+    #[error("invalid cors configuration: {reason}")]
+    InvalidCorsConfig { reason: String },
+
+    /// 路径规则能够解析，但是无法编译，例如通配模式错误或者过于复杂
+    #[error("invalid path rule: {reason}")]
+    InvalidPathRule { reason: String },
+
+    /// 重新加载时配置文件无法读取或者没有通过校验，这时继续使用原来的配置
+    #[error("invalid configuration: {reason}")]
+    InvalidConfig { reason: String },
+
+    /// `POST /admin/log-level` 中的 `filter` 无法解析，或者没有给出任何修改
+    #[error("invalid log filter: {reason}")]
+    InvalidLogFilter { reason: String },
+
+    /// 服务不是从配置文件启动的，例如 `crab-vault demo`，没有可以重新加载的配置
+    #[error("the server is not started from a configuration file")]
+    NoConfigFile,
+
+    /// 没有开启 `meta.events`，没有可以读取的变更事件
+    #[error("the event log is not enabled")]
+    NoEventLog,
+
+    #[error("invalid json ({kind}) at line {line}, column {col}")]
     JsonError {
         kind: &'static str,
         line: usize,
@@ -47,9 +189,10 @@ pub enum ClientError {
 }
 
 #[non_exhaustive]
-#[derive(Serialize)]
+#[derive(Serialize, Error, Debug)]
 #[serde(rename_all = "camelCase", tag = "code")]
 pub enum ServerError {
+    #[error("internal server error")]
     Internal,
 }
 
@@ -59,9 +202,24 @@ impl ClientError {
             ClientError::MissingContentType
             | ClientError::InvalidContentType
             | ClientError::MissingContentLength
-            | ClientError::BodyTooLarge
             | ClientError::HeaderWithOpaqueBytes
             | ClientError::Base64DecodeError
+            | ClientError::InvalidCopySource
+            | ClientError::InvalidMetadataDirective
+            | ClientError::UnsupportedPrecondition
+            | ClientError::InvalidQuota { header: _ }
+            | ClientError::InvalidExpiry { reason: _ }
+            | ClientError::ReservedMetaKey { key: _ }
+            | ClientError::TooManyObjects { max: _ }
+            | ClientError::InvalidBucketPolicy { reason: _ }
+            | ClientError::InvalidLifecycle { reason: _ }
+            | ClientError::InvalidInventory { reason: _ }
+            | ClientError::InvalidTagging { reason: _ }
+            | ClientError::InvalidRetention { reason: _ }
+            | ClientError::InvalidCorsConfig { reason: _ }
+            | ClientError::InvalidPathRule { reason: _ }
+            | ClientError::InvalidConfig { reason: _ }
+            | ClientError::InvalidLogFilter { reason: _ }
             | ClientError::ValueParsingError
             | ClientError::JsonError {
                 kind: _,
@@ -69,23 +227,114 @@ impl ClientError {
                 line: _,
             } => StatusCode::UNPROCESSABLE_ENTITY,
 
-            ClientError::UriInvalid => StatusCode::NOT_FOUND,
+            ClientError::UriInvalid
+            | ClientError::NoBucketPolicy
+            | ClientError::NoLifecycleConfig
+            | ClientError::NoInventoryConfig
+            | ClientError::NoCorsConfig
+            | ClientError::NoConfigFile
+            | ClientError::NoEventLog => StatusCode::NOT_FOUND,
+
+            ClientError::IncompleteBody
+            | ClientError::InvalidDigest { header: _ }
+            | ClientError::BadDigest { header: _ }
+            | ClientError::InvalidCustomerKey { reason: _ }
+            | ClientError::InvalidContentRange { reason: _ }
+            | ClientError::InvalidForm { reason: _ } => StatusCode::BAD_REQUEST,
+
+            ClientError::ObjectNotPatchable { reason: _ } => StatusCode::CONFLICT,
+
+            ClientError::BodyTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+
+            ClientError::RequestTimeout => StatusCode::REQUEST_TIMEOUT,
+
+            ClientError::TooManyRequests { scope: _ } => StatusCode::TOO_MANY_REQUESTS,
+
+            ClientError::UnversionedPath { successor: _ } => StatusCode::GONE,
+        }
+    }
+
+    /// 错误响应中的 `code`，客户端依靠它区分错误，一经发布就不再修改
+    pub const fn error_code(&self) -> &'static str {
+        match self {
+            ClientError::MissingContentType => "missingContentType",
+            ClientError::InvalidContentType => "invalidContentType",
+            ClientError::MissingContentLength => "missingContentLength",
+            ClientError::BodyTooLarge => "bodyTooLarge",
+            ClientError::RequestTimeout => "requestTimeout",
+            ClientError::TooManyRequests { .. } => "tooManyRequests",
+            ClientError::IncompleteBody => "incompleteBody",
+            ClientError::InvalidDigest { .. } => "invalidDigest",
+            ClientError::BadDigest { .. } => "badDigest",
+            ClientError::UriInvalid => "uriInvalid",
+            ClientError::ValueParsingError => "valueParsingError",
+            ClientError::HeaderWithOpaqueBytes => "headerWithOpaqueBytes",
+            ClientError::Base64DecodeError => "base64DecodeError",
+            ClientError::InvalidCopySource => "invalidCopySource",
+            ClientError::InvalidMetadataDirective => "invalidMetadataDirective",
+            ClientError::UnsupportedPrecondition => "unsupportedPrecondition",
+            ClientError::InvalidQuota { .. } => "invalidQuota",
+            ClientError::InvalidExpiry { .. } => "invalidExpiry",
+            ClientError::InvalidCustomerKey { .. } => "invalidCustomerKey",
+            ClientError::ReservedMetaKey { .. } => "reservedMetaKey",
+            ClientError::InvalidContentRange { .. } => "invalidContentRange",
+            ClientError::ObjectNotPatchable { .. } => "objectNotPatchable",
+            ClientError::InvalidForm { .. } => "invalidForm",
+            ClientError::TooManyObjects { .. } => "tooManyObjects",
+            ClientError::UnversionedPath { .. } => "unversionedPath",
+            ClientError::NoBucketPolicy => "noBucketPolicy",
+            ClientError::InvalidBucketPolicy { .. } => "invalidBucketPolicy",
+            ClientError::NoLifecycleConfig => "noLifecycleConfig",
+            ClientError::InvalidLifecycle { .. } => "invalidLifecycle",
+            ClientError::NoInventoryConfig => "noInventoryConfig",
+            ClientError::InvalidInventory { .. } => "invalidInventory",
+            ClientError::InvalidTagging { .. } => "invalidTagging",
+            ClientError::InvalidRetention { .. } => "invalidRetention",
+            ClientError::NoCorsConfig => "noCorsConfig",
+            ClientError::InvalidCorsConfig { .. } => "invalidCorsConfig",
+            ClientError::InvalidPathRule { .. } => "invalidPathRule",
+            ClientError::InvalidConfig { .. } => "invalidConfig",
+            ClientError::InvalidLogFilter { .. } => "invalidLogFilter",
+            ClientError::NoConfigFile => "noConfigFile",
+            ClientError::NoEventLog => "noEventLog",
+            ClientError::JsonError { .. } => "jsonError",
+        }
+    }
+}
+
+impl ApiError {
+    pub fn code(&self) -> StatusCode {
+        match self {
+            ApiError::Client(e) => e.code(),
+            ApiError::Server(e) => e.code(),
+        }
+    }
+
+    pub const fn error_code(&self) -> &'static str {
+        match self {
+            ApiError::Client(e) => e.error_code(),
+            ApiError::Server(e) => e.error_code(),
         }
     }
 }
 
 impl ServerError {
     pub fn code(&self) -> StatusCode {
-        StatusCode::NOT_FOUND
+        match self {
+            ServerError::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    pub const fn error_code(&self) -> &'static str {
+        match self {
+            ServerError::Internal => "internal",
+        }
     }
 }
 
 impl IntoResponse for ApiError {
     fn into_response(self) -> axum::response::Response {
-        match self {
-            ApiError::Client(e) => (e.code(), axum::Json(e)).into_response(),
-            ApiError::Server(e) => (e.code(), axum::Json(e)).into_response(),
-        }
+        RequestError::Api(self).into_response()
     }
 }
 
@@ -97,8 +346,16 @@ impl From<ApiError> for Response {
 }
 
 impl From<axum::extract::rejection::BytesRejection> for ApiError {
-    fn from(_: axum::extract::rejection::BytesRejection) -> Self {
-        Self::Client(ClientError::BodyTooLarge)
+    fn from(e: axum::extract::rejection::BytesRejection) -> Self {
+        use axum::extract::rejection::{BytesRejection, FailedToBufferBody};
+
+        match e {
+            // 超出了 `DefaultBodyLimit`，即需要完整读入内存的请求体的限制
+            BytesRejection::FailedToBufferBody(FailedToBufferBody::LengthLimitError(_)) => {
+                Self::Client(ClientError::BodyTooLarge)
+            }
+            e => Self::Client(crate::http::body_error(&e)),
+        }
     }
 }
 
@@ -128,3 +385,230 @@ impl From<serde_json::Error> for ApiError {
         Self::Client(ClientError::JsonError { kind, line, col })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 每一个变体与它的 `code` 和状态码，这里的值是对客户端的承诺，修改之前请确认是有意为之
+    fn all() -> Vec<(ApiError, &'static str, StatusCode)> {
+        use ClientError::*;
+        use StatusCode as S;
+        let reason = || "reason".to_string();
+        let client = vec![
+            (
+                MissingContentType,
+                "missingContentType",
+                S::UNPROCESSABLE_ENTITY,
+            ),
+            (
+                InvalidContentType,
+                "invalidContentType",
+                S::UNPROCESSABLE_ENTITY,
+            ),
+            (
+                MissingContentLength,
+                "missingContentLength",
+                S::UNPROCESSABLE_ENTITY,
+            ),
+            (BodyTooLarge, "bodyTooLarge", S::PAYLOAD_TOO_LARGE),
+            (RequestTimeout, "requestTimeout", S::REQUEST_TIMEOUT),
+            (
+                TooManyRequests { scope: "token" },
+                "tooManyRequests",
+                S::TOO_MANY_REQUESTS,
+            ),
+            (IncompleteBody, "incompleteBody", S::BAD_REQUEST),
+            (
+                InvalidDigest {
+                    header: "Content-MD5",
+                },
+                "invalidDigest",
+                S::BAD_REQUEST,
+            ),
+            (
+                BadDigest {
+                    header: "Content-MD5",
+                },
+                "badDigest",
+                S::BAD_REQUEST,
+            ),
+            (UriInvalid, "uriInvalid", S::NOT_FOUND),
+            (
+                ValueParsingError,
+                "valueParsingError",
+                S::UNPROCESSABLE_ENTITY,
+            ),
+            (
+                HeaderWithOpaqueBytes,
+                "headerWithOpaqueBytes",
+                S::UNPROCESSABLE_ENTITY,
+            ),
+            (
+                Base64DecodeError,
+                "base64DecodeError",
+                S::UNPROCESSABLE_ENTITY,
+            ),
+            (
+                InvalidCopySource,
+                "invalidCopySource",
+                S::UNPROCESSABLE_ENTITY,
+            ),
+            (
+                InvalidMetadataDirective,
+                "invalidMetadataDirective",
+                S::UNPROCESSABLE_ENTITY,
+            ),
+            (
+                UnsupportedPrecondition,
+                "unsupportedPrecondition",
+                S::UNPROCESSABLE_ENTITY,
+            ),
+            (
+                InvalidQuota {
+                    header: "X-Crab-Vault-Max-Bytes",
+                },
+                "invalidQuota",
+                S::UNPROCESSABLE_ENTITY,
+            ),
+            (
+                InvalidExpiry {
+                    reason: "in the past",
+                },
+                "invalidExpiry",
+                S::UNPROCESSABLE_ENTITY,
+            ),
+            (
+                InvalidCustomerKey { reason: "bad md5" },
+                "invalidCustomerKey",
+                S::BAD_REQUEST,
+            ),
+            (
+                ReservedMetaKey {
+                    key: "x-crab-vault-sse".into(),
+                },
+                "reservedMetaKey",
+                S::UNPROCESSABLE_ENTITY,
+            ),
+            (
+                InvalidContentRange {
+                    reason: "bad range",
+                },
+                "invalidContentRange",
+                S::BAD_REQUEST,
+            ),
+            (
+                ObjectNotPatchable {
+                    reason: "compressed",
+                },
+                "objectNotPatchable",
+                S::CONFLICT,
+            ),
+            (
+                InvalidForm { reason: "no key" },
+                "invalidForm",
+                S::BAD_REQUEST,
+            ),
+            (
+                TooManyObjects { max: 1000 },
+                "tooManyObjects",
+                S::UNPROCESSABLE_ENTITY,
+            ),
+            (
+                UnversionedPath {
+                    successor: "/v1/photos".into(),
+                },
+                "unversionedPath",
+                S::GONE,
+            ),
+            (NoBucketPolicy, "noBucketPolicy", S::NOT_FOUND),
+            (
+                InvalidBucketPolicy { reason: reason() },
+                "invalidBucketPolicy",
+                S::UNPROCESSABLE_ENTITY,
+            ),
+            (NoLifecycleConfig, "noLifecycleConfig", S::NOT_FOUND),
+            (
+                InvalidLifecycle { reason: reason() },
+                "invalidLifecycle",
+                S::UNPROCESSABLE_ENTITY,
+            ),
+            (NoInventoryConfig, "noInventoryConfig", S::NOT_FOUND),
+            (
+                InvalidInventory { reason: reason() },
+                "invalidInventory",
+                S::UNPROCESSABLE_ENTITY,
+            ),
+            (
+                InvalidTagging { reason: reason() },
+                "invalidTagging",
+                S::UNPROCESSABLE_ENTITY,
+            ),
+            (
+                InvalidRetention { reason: reason() },
+                "invalidRetention",
+                S::UNPROCESSABLE_ENTITY,
+            ),
+            (NoCorsConfig, "noCorsConfig", S::NOT_FOUND),
+            (
+                InvalidCorsConfig { reason: reason() },
+                "invalidCorsConfig",
+                S::UNPROCESSABLE_ENTITY,
+            ),
+            (
+                InvalidPathRule { reason: reason() },
+                "invalidPathRule",
+                S::UNPROCESSABLE_ENTITY,
+            ),
+            (
+                InvalidConfig { reason: reason() },
+                "invalidConfig",
+                S::UNPROCESSABLE_ENTITY,
+            ),
+            (
+                InvalidLogFilter { reason: reason() },
+                "invalidLogFilter",
+                S::UNPROCESSABLE_ENTITY,
+            ),
+            (NoConfigFile, "noConfigFile", S::NOT_FOUND),
+            (NoEventLog, "noEventLog", S::NOT_FOUND),
+            (
+                JsonError {
+                    kind: "syntax",
+                    line: 1,
+                    col: 2,
+                },
+                "jsonError",
+                S::UNPROCESSABLE_ENTITY,
+            ),
+        ];
+
+        let mut errors: Vec<_> = client
+            .into_iter()
+            .map(|(error, code, status)| (ApiError::Client(error), code, status))
+            .collect();
+        errors.push((
+            ApiError::Server(ServerError::Internal),
+            "internal",
+            S::INTERNAL_SERVER_ERROR,
+        ));
+        errors
+    }
+
+    #[test]
+    fn test_error_codes_are_stable() {
+        let errors = all();
+        for (error, code, status) in &errors {
+            assert_eq!(error.error_code(), *code, "{error}");
+            assert_eq!(error.code(), *status, "{error}");
+            // 序列化出的 `code` 与之相同，重命名变体时这里会失败
+            let value = serde_json::to_value(error).unwrap();
+            assert_eq!(value["code"], *code, "{error}");
+        }
+
+        let mut codes: Vec<_> = errors.iter().map(|(_, code, _)| *code).collect();
+        codes.sort_unstable();
+        codes.dedup();
+        assert_eq!(codes.len(), errors.len());
+    }
+}
@@ -0,0 +1,230 @@
+//! # 带有上下文的请求错误
+//!
+//! handler 中的每一个错误都要通过 [`Context::context`] 附加上正在执行的操作以及涉及的 bucket 和 object，
+//! 这样响应和日志中总能看出是哪一步失败了。[`ContextError`] 故意没有实现来自底层错误的 `From`，
+//! 漏掉上下文的 `?` 无法通过编译。
+//!
+//! 所有的错误响应都由这里的 [`ErrorBody`] 生成，格式参见 `docs/错误处理.md`。
+
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use crab_vault::{auth::error::AuthError, engine::error::EngineError};
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::{error::api::ApiError, http::current_request_id};
+
+/// 出错时正在执行的操作，以及它涉及的 bucket 和 object
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ErrorContext {
+    pub operation: &'static str,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bucket: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub object: Option<String>,
+}
+
+impl ErrorContext {
+    pub fn new(operation: &'static str) -> Self {
+        Self {
+            operation,
+            bucket: None,
+            object: None,
+        }
+    }
+
+    pub fn bucket(mut self, bucket: impl Into<String>) -> Self {
+        self.bucket = Some(bucket.into());
+        self
+    }
+
+    pub fn object(mut self, object: impl Into<String>) -> Self {
+        self.object = Some(object.into());
+        self
+    }
+
+    /// `/{bucket}` 或者 `/{bucket}/{object}`
+    fn resource(&self) -> Option<String> {
+        let bucket = self.bucket.as_deref()?;
+        match self.object.as_deref() {
+            Some(object) => Some(format!("/{bucket}/{object}")),
+            None => Some(format!("/{bucket}")),
+        }
+    }
+}
+
+/// 处理请求时可能遇到的各类错误
+pub enum RequestError {
+    Engine(EngineError),
+    Api(ApiError),
+    Auth(AuthError),
+}
+
+impl RequestError {
+    pub fn code(&self) -> StatusCode {
+        match self {
+            RequestError::Engine(e) => e.status_code(),
+            RequestError::Api(e) => e.code(),
+            RequestError::Auth(e) => e.status_code(),
+        }
+    }
+
+    /// 写入日志的错误码，鉴权错误的细节只出现在日志中
+    pub fn error_code(&self) -> &'static str {
+        match self {
+            RequestError::Engine(e) => e.error_code(),
+            RequestError::Api(e) => e.error_code(),
+            RequestError::Auth(e) => e.error_code(),
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            RequestError::Engine(e) => e.to_string(),
+            RequestError::Api(e) => e.to_string(),
+            RequestError::Auth(e) => e.to_string(),
+        }
+    }
+
+    /// 错误响应的响应体，批量操作中单个 object 的错误也使用它
+    pub fn body(&self, context: Option<ErrorContext>) -> ErrorBody {
+        let (code, message, details) = match self {
+            RequestError::Engine(e) => (e.error_code(), e.to_string(), details(e)),
+            RequestError::Api(e) => (e.error_code(), e.to_string(), details(e)),
+            // 与鉴权中间件一样，不向客户端透露鉴权失败的细节
+            RequestError::Auth(AuthError::InsufficientPermissions) => (
+                "insufficientPermissions",
+                "insufficient permissions for this operation".into(),
+                Map::new(),
+            ),
+            RequestError::Auth(_) => (
+                "unauthenticated",
+                "the token is missing or invalid".into(),
+                Map::new(),
+            ),
+        };
+
+        let resource = match self {
+            RequestError::Engine(e) => e.resource(),
+            _ => None,
+        }
+        .or_else(|| context.as_ref().and_then(ErrorContext::resource));
+
+        ErrorBody {
+            code,
+            message,
+            resource,
+            request_id: current_request_id(),
+            context,
+            details,
+        }
+    }
+}
+
+/// ## 错误响应的格式
+///
+/// `code` 是稳定的错误码，`resource` 是出错的 bucket 或者 object，`request_id` 与日志中的 `req_id` 相同。
+/// 之后是错误本身带有的字段，例如 `bucketNotFound` 中的 `bucket`
+#[derive(Serialize)]
+pub struct ErrorBody {
+    pub code: &'static str,
+    pub message: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resource: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub context: Option<ErrorContext>,
+
+    #[serde(flatten)]
+    pub details: Map<String, Value>,
+}
+
+/// 错误序列化之后除了 `code` 之外的字段，`code` 以 `error_code` 为准
+fn details(error: &impl Serialize) -> Map<String, Value> {
+    let mut details = match serde_json::to_value(error) {
+        Ok(Value::Object(details)) => details,
+        _ => Map::new(),
+    };
+    details.remove("code");
+    details
+}
+
+impl From<EngineError> for RequestError {
+    fn from(value: EngineError) -> Self {
+        Self::Engine(value)
+    }
+}
+
+impl From<ApiError> for RequestError {
+    fn from(value: ApiError) -> Self {
+        Self::Api(value)
+    }
+}
+
+impl From<AuthError> for RequestError {
+    fn from(value: AuthError) -> Self {
+        Self::Auth(value)
+    }
+}
+
+/// 没有上下文的错误，例如在到达 handler 之前就被中间件拒绝的请求
+impl IntoResponse for RequestError {
+    fn into_response(self) -> Response {
+        (self.code(), axum::Json(self.body(None))).into_response()
+    }
+}
+
+impl From<RequestError> for Response {
+    #[inline(always)]
+    fn from(value: RequestError) -> Self {
+        value.into_response()
+    }
+}
+
+/// 附加了 [`ErrorContext`] 的 [`RequestError`]
+pub struct ContextError {
+    pub context: ErrorContext,
+    pub error: RequestError,
+}
+
+pub type HandlerResult<T> = Result<T, ContextError>;
+
+pub trait Context<T> {
+    /// 为错误附加上下文，成功时原样返回
+    fn context(self, context: &ErrorContext) -> HandlerResult<T>;
+}
+
+impl<T, E: Into<RequestError>> Context<T> for Result<T, E> {
+    fn context(self, context: &ErrorContext) -> HandlerResult<T> {
+        self.map_err(|e| ContextError {
+            context: context.clone(),
+            error: e.into(),
+        })
+    }
+}
+
+impl IntoResponse for ContextError {
+    fn into_response(self) -> Response {
+        let Self { context, error } = self;
+        let code = error.code();
+
+        let (bucket, object) = (context.bucket.as_deref(), context.object.as_deref());
+        let (error_code, msg) = (error.error_code(), error.describe());
+        if code.is_server_error() {
+            tracing::error!(operation = context.operation, bucket, object, %code, error_code, "{msg}");
+        } else {
+            tracing::warn!(operation = context.operation, bucket, object, %code, error_code, "{msg}");
+        }
+
+        (code, axum::Json(error.body(Some(context)))).into_response()
+    }
+}
@@ -41,12 +41,15 @@ impl MultiFatalError {
     }
 
     pub fn exit_now(self) -> ! {
+        Cli::command().error(ErrorKind::Io, self.into_message()).exit()
+    }
+
+    pub fn into_message(self) -> String {
         let mut final_message = "".to_string();
         for e in self.errors {
             final_message.push_str(&format!("\n\n{}", e.into_message()));
         }
-
-        Cli::command().error(ErrorKind::Io, final_message).exit()
+        final_message
     }
 
     #[inline]
@@ -201,12 +204,18 @@ impl From<AuthError> for FatalError {
 
 impl From<serde_json::Error> for FatalError {
     fn from(value: serde_json::Error) -> Self {
-        match value.classify() {
-            serde_json::error::Category::Io => todo!(),
-            serde_json::error::Category::Syntax => todo!(),
-            serde_json::error::Category::Data => todo!(),
-            serde_json::error::Category::Eof => todo!(),
-        }
+        let kind = match value.classify() {
+            serde_json::error::Category::Io => "io error",
+            serde_json::error::Category::Syntax => "syntax error",
+            serde_json::error::Category::Data => "invalid data",
+            serde_json::error::Category::Eof => "unexpected end of input",
+        };
+
+        Self::new(
+            ErrorKind::InvalidValue,
+            format!("cannot handle the json, {kind}, details: {value}"),
+            None,
+        )
     }
 }
 
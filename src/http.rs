@@ -1,11 +1,72 @@
 use axum::http::HeaderName;
+use percent_encoding::{AsciiSet, CONTROLS};
 
+pub mod access_log;
 pub mod api;
+mod conn;
+mod digest;
 mod extractor;
+mod gc;
+mod inventory;
+mod key_manager;
+mod lifecycle;
 mod middleware;
+mod path_rules;
+mod reload;
+mod replication;
 pub mod server;
+mod shutdown;
+mod tls;
+mod trash;
+
+#[cfg(feature = "s3")]
+pub(crate) use api::{read_original, seal_original};
+pub(crate) use middleware::{limits::body_error, request_id::current_request_id};
+
+/// object 名称放入路径时需要编码的字符，与浏览器编码路径时相同，另外加上 `%`
+pub(crate) const PATH_SEGMENT: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
 
 const X_CRAB_VAULT_USER_META: HeaderName = HeaderName::from_static("x-crab-vault-user-meta");
 const X_CRAB_VAULT_CREATED_AT: HeaderName = HeaderName::from_static("x-crab-vault-created-at");
 const X_CRAB_VAULT_BUCKET_NAME: HeaderName = HeaderName::from_static("x-crab-vault-bucket-name");
-const X_CRAB_VAULT_OBJECT_NAME: HeaderName = HeaderName::from_static("x-crab-vault-object-name");
\ No newline at end of file
+const X_CRAB_VAULT_OBJECT_NAME: HeaderName = HeaderName::from_static("x-crab-vault-object-name");
+const X_CRAB_VAULT_COPY_SOURCE: HeaderName = HeaderName::from_static("x-crab-vault-copy-source");
+const X_CRAB_VAULT_METADATA_DIRECTIVE: HeaderName =
+    HeaderName::from_static("x-crab-vault-metadata-directive");
+const X_CRAB_VAULT_SKIPPED_COUNT: HeaderName =
+    HeaderName::from_static("x-crab-vault-skipped-count");
+const X_CRAB_VAULT_SKIPPED_ENTRIES: HeaderName =
+    HeaderName::from_static("x-crab-vault-skipped-entries");
+const X_CRAB_VAULT_BUCKET_COUNT: HeaderName =
+    HeaderName::from_static("x-crab-vault-bucket-count");
+const X_CRAB_VAULT_IS_TRUNCATED: HeaderName =
+    HeaderName::from_static("x-crab-vault-is-truncated");
+const X_CRAB_VAULT_NEXT_CONTINUATION_TOKEN: HeaderName =
+    HeaderName::from_static("x-crab-vault-next-continuation-token");
+const X_CRAB_VAULT_CONTENT_SHA256: HeaderName =
+    HeaderName::from_static("x-crab-vault-content-sha256");
+const X_CRAB_VAULT_MAX_BYTES: HeaderName = HeaderName::from_static("x-crab-vault-max-bytes");
+const X_CRAB_VAULT_MAX_OBJECTS: HeaderName = HeaderName::from_static("x-crab-vault-max-objects");
+const X_CRAB_VAULT_OBJECT_COUNT: HeaderName =
+    HeaderName::from_static("x-crab-vault-object-count");
+const X_CRAB_VAULT_TOTAL_BYTES: HeaderName = HeaderName::from_static("x-crab-vault-total-bytes");
+const X_CRAB_VAULT_EXPIRES_AT: HeaderName = HeaderName::from_static("x-crab-vault-expires-at");
+const X_CRAB_VAULT_TAGGING_COUNT: HeaderName =
+    HeaderName::from_static("x-crab-vault-tagging-count");
+const X_CRAB_VAULT_RETAIN_UNTIL: HeaderName =
+    HeaderName::from_static("x-crab-vault-retain-until");
+const X_CRAB_VAULT_LEGAL_HOLD: HeaderName = HeaderName::from_static("x-crab-vault-legal-hold");
+const X_CRAB_VAULT_TTL: HeaderName = HeaderName::from_static("x-crab-vault-ttl");
+const X_CRAB_VAULT_SSE_KEY: HeaderName = HeaderName::from_static("x-crab-vault-sse-key");
+const X_CRAB_VAULT_SSE_KEY_MD5: HeaderName = HeaderName::from_static("x-crab-vault-sse-key-md5");
+const CONTENT_MD5: HeaderName = HeaderName::from_static("content-md5");
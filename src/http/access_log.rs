@@ -0,0 +1,346 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use chrono::{DateTime, Utc};
+use crab_vault::engine::{
+    DataEngine, DataSource, MetaEngine, MetaSource, ObjectMeta, clock,
+    error::{EngineError, EngineResult},
+    name,
+};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    sync::{mpsc, oneshot},
+    task::JoinHandle,
+};
+use uuid::Uuid;
+
+use crate::app_config::access_log::{AccessLogConfig, AccessLogSink};
+
+/// 内部 bucket 中保存访问日志的前缀
+const OBJECT_PREFIX: &str = "access-log";
+
+/// 写入内部 bucket 的访问日志的类型，每行一条记录
+const CONTENT_TYPE: &str = "application/x-ndjson";
+
+/// 日志文件的文件名格式，即这个文件开始写入的时间
+const FILE_NAME_FORMAT: &str = "%Y.%m.%d@%H-%M-%S";
+
+/// ## 一个请求的访问记录
+///
+/// 响应体发送完成，或者连接在发送途中断开时才会产生，所以 `latency_ms` 包括发送响应体的时间
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AccessRecord {
+    /// 请求到达的时间
+    pub time: DateTime<Utc>,
+    pub method: String,
+
+    /// 客户端请求的路径和查询参数，预签名 URL 中的令牌已经被隐去
+    pub path: String,
+    pub status: u16,
+
+    /// 实际收到的请求体的字节数
+    pub bytes_in: u64,
+
+    /// 实际发出的响应体的字节数
+    pub bytes_out: u64,
+    pub latency_ms: u64,
+
+    /// 请求携带了有效的令牌时，令牌的签发者和 ID
+    pub issuer: Option<String>,
+    pub jti: Option<Uuid>,
+    pub remote_addr: Option<String>,
+
+    /// 与响应头 `X-Request-Id` 和服务端日志中的 `req_id` 相同
+    pub request_id: Option<String>,
+
+    /// 响应体是否完整地发送了，`false` 表示客户端在接收途中断开了连接
+    pub completed: bool,
+}
+
+/// 把访问记录交给后台的写入任务，参见 [`AccessLog::spawn`]
+#[derive(Clone)]
+pub struct AccessLog(mpsc::UnboundedSender<AccessRecord>);
+
+/// 后台的写入任务，参见 [`AccessLogWriter::finish`]
+pub struct AccessLogWriter {
+    task: JoinHandle<()>,
+    stop: oneshot::Sender<()>,
+}
+
+enum Sink {
+    Stdout,
+    File(RotatingFile),
+    InternalBucket {
+        data_src: Arc<DataSource>,
+        meta_src: Arc<MetaSource>,
+        pending: Vec<u8>,
+        count: usize,
+    },
+}
+
+/// 按照大小轮换的日志文件
+struct RotatingFile {
+    dir: PathBuf,
+    file: File,
+
+    /// 当前文件的名称，新文件的名称总是排在它之后
+    name: String,
+    size: u64,
+    max_size: u64,
+    max_files: Option<usize>,
+}
+
+impl AccessLog {
+    /// 在后台开始写入访问日志，`sink` 为 `off` 时返回 [`None`]
+    ///
+    /// 写入失败时只输出错误日志，不影响请求的处理
+    pub fn spawn(
+        config: &AccessLogConfig,
+        data_src: Arc<DataSource>,
+        meta_src: Arc<MetaSource>,
+    ) -> io::Result<Option<(Self, AccessLogWriter)>> {
+        let sink = match config.sink {
+            AccessLogSink::Off => return Ok(None),
+            AccessLogSink::Stdout => Sink::Stdout,
+            AccessLogSink::File => Sink::File(RotatingFile::open(
+                config.path.clone().unwrap_or_default(),
+                config.max_file_size,
+                config.max_files,
+            )?),
+            AccessLogSink::InternalBucket => Sink::InternalBucket {
+                data_src,
+                meta_src,
+                pending: vec![],
+                count: 0,
+            },
+        };
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        let (stop, stopped) = oneshot::channel();
+        let task = tokio::spawn(sink.run(rx, stopped, config.max_batch, config.flush_interval()));
+        Ok(Some((Self(tx), AccessLogWriter { task, stop })))
+    }
+
+    /// 写入任务已经退出时丢弃这条记录
+    pub fn record(&self, record: AccessRecord) {
+        let _ = self.0.send(record);
+    }
+}
+
+impl AccessLogWriter {
+    /// 不再接受新的记录，等待已经收到的记录写完
+    ///
+    /// 停止服务时超时的请求仍然可能持有 [`AccessLog`]，所以不能等待它们全部被丢弃
+    pub async fn finish(self) {
+        let _ = self.stop.send(());
+        if let Err(e) = self.task.await {
+            tracing::error!("The access log writer stopped unexpectedly: {e}");
+        }
+    }
+}
+
+impl Sink {
+    async fn run(
+        mut self,
+        mut rx: mpsc::UnboundedReceiver<AccessRecord>,
+        mut stop: oneshot::Receiver<()>,
+        max_batch: usize,
+        flush_interval: std::time::Duration,
+    ) {
+        let mut ticker = tokio::time::interval(flush_interval);
+        let mut records = Vec::with_capacity(max_batch);
+        let mut stopping = false;
+
+        // 关闭之后 `rx` 仍然会交出已经收到的记录，之后才返回 `0`
+        loop {
+            tokio::select! {
+                received = rx.recv_many(&mut records, max_batch) => {
+                    if received == 0 {
+                        break;
+                    }
+                    self.write(&records, max_batch).await;
+                    records.clear();
+                }
+                _ = &mut stop, if !stopping => {
+                    stopping = true;
+                    rx.close();
+                }
+                _ = ticker.tick() => self.flush().await,
+            }
+        }
+
+        self.flush().await;
+    }
+
+    async fn write(&mut self, records: &[AccessRecord], max_batch: usize) {
+        let mut lines = vec![];
+        for record in records {
+            // 记录中只有字符串、数字和时间，不会序列化失败
+            let _ = serde_json::to_writer(&mut lines, record);
+            lines.push(b'\n');
+        }
+
+        match self {
+            Sink::Stdout => {
+                let mut stdout = io::stdout().lock();
+                if let Err(e) = stdout.write_all(&lines).and_then(|_| stdout.flush()) {
+                    tracing::error!("Cannot write the access log to stdout! Details: {e}");
+                }
+            }
+            Sink::File(file) => {
+                if let Err(e) = file.write(&lines) {
+                    tracing::error!("Cannot write the access log file! Details: {e}");
+                }
+            }
+            Sink::InternalBucket { pending, count, .. } => {
+                pending.extend_from_slice(&lines);
+                *count += records.len();
+                if *count >= max_batch {
+                    self.flush().await;
+                }
+            }
+        }
+    }
+
+    /// 把积累的记录写入内部 bucket，失败时保留这些记录，下次再试
+    async fn flush(&mut self) {
+        let Sink::InternalBucket {
+            data_src,
+            meta_src,
+            pending,
+            count,
+        } = self
+        else {
+            return;
+        };
+        if pending.is_empty() {
+            return;
+        }
+
+        match store(data_src, meta_src, pending).await {
+            Ok(object) => {
+                tracing::debug!(object, records = *count, "access log stored");
+                pending.clear();
+                *count = 0;
+            }
+            Err(e) => tracing::error!(
+                records = *count,
+                "Cannot store the access log in the internal bucket! Details: {e}"
+            ),
+        }
+    }
+}
+
+/// 把 `data` 作为一个新的 object 写入内部 bucket，返回 object 的名称
+async fn store(data_src: &DataSource, meta_src: &MetaSource, data: &[u8]) -> EngineResult<String> {
+    let bucket = name::internal_bucket();
+    let now = clock::now();
+    let object = format!(
+        "{OBJECT_PREFIX}/{}/{}-{}.jsonl",
+        now.format("%Y/%m/%d"),
+        now.format("%H-%M-%S"),
+        Uuid::new_v4()
+    );
+
+    match data_src.create_object(bucket, &object, data).await {
+        Err(EngineError::BucketNotFound { .. }) => {
+            data_src.create_bucket(bucket).await?;
+            data_src.create_object(bucket, &object, data).await?;
+        }
+        other => other?,
+    }
+
+    let meta = ObjectMeta::builder()
+        .bucket_name(bucket)
+        .object_name(&object)
+        .data(data)
+        .content_type(CONTENT_TYPE)
+        .build()?;
+    meta_src.create_object_meta(&meta).await?;
+
+    Ok(object)
+}
+
+impl RotatingFile {
+    fn open(dir: PathBuf, max_size: u64, max_files: Option<usize>) -> io::Result<Self> {
+        fs::create_dir_all(&dir)?;
+        let (file, name) = create_file(&dir, None)?;
+
+        let rotating = Self {
+            dir,
+            file,
+            name,
+            size: 0,
+            max_size,
+            max_files,
+        };
+        rotating.prune();
+        Ok(rotating)
+    }
+
+    /// 写入之后超过大小限制时先换一个新的文件，一批记录总是写在同一个文件中
+    fn write(&mut self, lines: &[u8]) -> io::Result<()> {
+        if self.size > 0 && self.size + lines.len() as u64 > self.max_size {
+            (self.file, self.name) = create_file(&self.dir, Some(&self.name))?;
+            self.size = 0;
+            self.prune();
+        }
+
+        self.file.write_all(lines)?;
+        self.size += lines.len() as u64;
+        Ok(())
+    }
+
+    /// 删除超出 `max_files` 的最早的文件，文件名是开始写入的时间和序号，所以按名称排序即可
+    fn prune(&self) {
+        let Some(max_files) = self.max_files else {
+            return;
+        };
+
+        let mut files = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries
+                .filter_map(|v| v.ok().map(|v| v.path()))
+                .filter(|v| v.extension().is_some_and(|v| v == "jsonl"))
+                .collect::<Vec<_>>(),
+            Err(e) => {
+                tracing::error!("Cannot list the access log files! Details: {e}");
+                return;
+            }
+        };
+        files.sort();
+
+        for file in &files[..files.len().saturating_sub(max_files)] {
+            if let Err(e) = fs::remove_file(file) {
+                tracing::error!("Cannot remove {}! Details: {e}", file.display());
+            }
+        }
+    }
+}
+
+/// 以当前时间命名的新文件，同一秒内轮换多次时使用下一个序号
+///
+/// 名称必须排在 `after` 之后，否则同一秒内被删除的文件的序号会被重新使用，新文件反而先被删除
+fn create_file(dir: &Path, after: Option<&str>) -> io::Result<(File, String)> {
+    let stem = clock::now().format(FILE_NAME_FORMAT).to_string();
+
+    for seq in 0.. {
+        let name = format!("{stem}-{seq:03}.jsonl");
+        if after.is_some_and(|v| name.as_str() <= v) {
+            continue;
+        }
+        match OpenOptions::new()
+            .append(true)
+            .create_new(true)
+            .open(dir.join(&name))
+        {
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            other => return other.map(|file| (file, name)),
+        }
+    }
+
+    unreachable!("there are always unused sequence numbers")
+}
@@ -1,56 +1,475 @@
 use std::sync::Arc;
 
-use axum::{routing::MethodRouter, Router};
-use crab_vault_auth::JwtDecoder;
+use axum::{
+    extract::{DefaultBodyLimit, FromRef},
+    http::HeaderMap,
+    routing::MethodRouter,
+    Router,
+};
+use crab_vault_auth::{pattern::GlobLimits, revocation::RevocationStore};
+use tokio::sync::Mutex;
 
-use crate::{app_config::auth::PathRule, http::middleware::auth::AuthLayer};
+use crate::{
+    app_config::{
+        StaticAppConfig,
+        data::{CompressionConfig, ContentSniff, TrashConfig},
+        live::Live,
+        server::{BufferingConfig, ResponseCompressionConfig, VersioningConfig},
+    },
+    http::{
+        extractor::condition::WriteCondition,
+        key_manager::KeyManager,
+        middleware::{
+            auth::AuthLayer,
+            compression::response_compression_layer,
+            cors::{CorsLayer, CorsRules},
+            limits::{RequestLimits, RequestLimitsLayer},
+            rate_limit::{RateLimitLayer, RateLimiter},
+            version::UnversionedLayer,
+        },
+        path_rules::PathRuleStore,
+        reload::ConfigReloader,
+        replication::Replication,
+    },
+};
 
-use crab_vault::engine::{DataSource, MetaSource};
+use crab_vault::engine::{
+    DataSource, MetaEngine, MetaSource, ObjectMeta,
+    crypto::KeyRing,
+    error::EngineResult,
+    journal::{Intent, IntentId, Journal},
+};
 
+mod admin;
+mod batch;
+mod compression;
+mod cors;
+mod form;
 mod handler;
+mod inventory;
+mod lifecycle;
+mod lock;
+mod patch;
+mod payload;
+mod policy;
+mod purge;
+mod quota;
 mod response;
+mod retention;
+mod sniff;
+mod summary;
+mod tagging;
+mod trash;
 mod util;
+mod xml;
+
+use lock::{KeyedLocks, LockGuards, LockMode};
+
+pub(crate) use inventory::write_inventory;
+pub(crate) use payload::read_original;
+#[cfg(feature = "s3")]
+pub(crate) use payload::seal_original;
+
+/// 当前版本的 API 的路径前缀
+pub const API_VERSION_PREFIX: &str = "/v1";
+
+/// 吊销令牌的接口，它的路径与 `admin` 这个 bucket 中的 object 相同，bucket 策略不会作用于它
+pub const REVOKE_TOKEN_PATH: &str = "/admin/tokens/revoke";
+
+/// 读取、替换路径规则的接口，与 [`REVOKE_TOKEN_PATH`] 一样不受 bucket 策略和路径规则本身的约束
+pub const PATH_RULES_PATH: &str = "/admin/path-rules";
+
+/// 所有 bucket 的用量统计，与 [`REVOKE_TOKEN_PATH`] 一样不受 bucket 策略的约束
+pub const STATS_PATH: &str = "/admin/stats";
+
+/// 读取变更事件，与 [`REVOKE_TOKEN_PATH`] 一样需要令牌明确地允许
+pub const EVENTS_PATH: &str = "/admin/events";
+
+/// 隐去密钥之后的配置，与 [`REVOKE_TOKEN_PATH`] 一样需要令牌明确地允许
+pub const CONFIG_PATH: &str = "/admin/config";
+
+/// 立即重新加载配置文件，与 [`REVOKE_TOKEN_PATH`] 一样需要令牌明确地允许
+pub const RELOAD_PATH: &str = "/admin/reload";
+
+/// 修改控制台日志的级别，与 [`REVOKE_TOKEN_PATH`] 一样需要令牌明确地允许
+pub const LOG_LEVEL_PATH: &str = "/admin/log-level";
+
+/// `POST /admin/inventory/{bucket}` 立即生成一个 bucket 的清单，与 [`REVOKE_TOKEN_PATH`] 一样需要令牌明确地允许
+pub const INVENTORY_PATH: &str = "/admin/inventory";
+
+/// 复制到各个目标的进度，与 [`REVOKE_TOKEN_PATH`] 一样需要令牌明确地允许
+pub const REPLICATION_STATUS_PATH: &str = "/admin/replication/status";
+
+/// 存活探针，不需要令牌
+pub const HEALTHZ_PATH: &str = "/admin/healthz";
+
+/// 就绪探针，检查数据和元数据后端是否可以访问，不需要令牌
+pub const READYZ_PATH: &str = "/admin/readyz";
+
+/// 管理接口总是要求令牌明确地允许对它执行这个方法
+pub fn is_admin_path(path: &str) -> bool {
+    path == REVOKE_TOKEN_PATH
+        || path == PATH_RULES_PATH
+        || path == STATS_PATH
+        || path == EVENTS_PATH
+        || path == CONFIG_PATH
+        || path == RELOAD_PATH
+        || path == LOG_LEVEL_PATH
+        || path == REPLICATION_STATUS_PATH
+        || path
+            .strip_prefix(INVENTORY_PATH)
+            .is_some_and(|v| v.starts_with('/'))
+}
+
+/// 读写 bucket 策略的请求使用的查询参数，参见 `PUT /{bucket}?policy`
+pub const POLICY_QUERY_KEY: &str = "policy";
+
+/// 读写生命周期规则的请求使用的查询参数，参见 `PUT /{bucket}?lifecycle`
+pub const LIFECYCLE_QUERY_KEY: &str = "lifecycle";
+
+/// 读写清单配置的请求使用的查询参数，参见 `PUT /{bucket}?inventory`
+pub const INVENTORY_QUERY_KEY: &str = "inventory";
+
+/// 读写 bucket 自己的跨域规则的请求使用的查询参数，参见 `PUT /{bucket}?cors`
+pub const CORS_QUERY_KEY: &str = "cors";
+
+/// 读写 object 标签的请求使用的查询参数，参见 `PUT /{bucket}/{object}?tagging`
+pub const TAGGING_QUERY_KEY: &str = "tagging";
+
+/// 读写 object 的保留设置的请求使用的查询参数，参见 `PUT /{bucket}/{object}?retention`
+pub const RETENTION_QUERY_KEY: &str = "retention";
+
+/// 删除 bucket 时连同其中所有的 object 一起删除的查询参数，参见 `DELETE /{bucket}?force=true`
+pub const FORCE_QUERY_KEY: &str = "force";
+
+/// 列出 bucket 的回收站的查询参数，参见 `GET /{bucket}?trash`
+pub const TRASH_QUERY_KEY: &str = "trash";
+
+/// 从回收站恢复 object 的查询参数，参见 `POST /{bucket}/{object}?restore`
+pub const RESTORE_QUERY_KEY: &str = "restore";
+
+/// bucket 的用户元数据中这个字段为 `true` 时，任何人都可以读取、列出这个 bucket 中的 object
+///
+/// 它相当于 bucket 策略中的一条允许语句，策略中的禁止语句仍然优先
+pub const PUBLIC_READ_META_KEY: &str = "public_read";
+
+/// `POST /{bucket}` 是不是浏览器的表单上传，而不是 `?delete` 批量删除，参见 `form::FormUpload`
+pub fn is_form_upload(query: Option<&str>, headers: &HeaderMap) -> bool {
+    !batch::is_delete_query(query) && form::is_form(headers)
+}
+
+/// 查询字符串中是否有 `force=true`，`force` 的其他值都不算
+pub fn is_force_delete(query: Option<&str>) -> bool {
+    query.is_some_and(|v| {
+        v.split('&')
+            .any(|pair| pair.split_once('=') == Some((FORCE_QUERY_KEY, "true")))
+    })
+}
+
+/// 查询字符串中是否有名为 `key` 的参数，它的值会被忽略
+pub fn has_query_key(query: Option<&str>, key: &str) -> bool {
+    query.is_some_and(|v| {
+        v.split('&')
+            .any(|pair| pair.split_once('=').map_or(pair, |(name, _)| name) == key)
+    })
+}
 
 #[derive(Clone)]
 pub struct ApiState {
     data_src: Arc<DataSource>,
     meta_src: Arc<MetaSource>,
+    key_ring: Option<Arc<KeyRing>>,
+    revocations: Arc<dyn RevocationStore>,
+    path_rules: Arc<PathRuleStore>,
+    buffering: Arc<BufferingConfig>,
+    compression: Arc<CompressionConfig>,
+    sniff: ContentSniff,
+    trash: Arc<TrashConfig>,
+    journal: Option<Arc<Journal>>,
+
+    /// 这个进程中对 bucket 和 object 的读写锁，多个实例之间不共享
+    locks: Arc<KeyedLocks>,
 }
 
 impl ApiState {
-    pub fn new(data_src: DataSource, meta_src: MetaSource) -> Self {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        data_src: DataSource,
+        meta_src: Arc<MetaSource>,
+        key_ring: Option<KeyRing>,
+        revocations: Arc<dyn RevocationStore>,
+        path_rules: PathRuleStore,
+        buffering: BufferingConfig,
+        compression: CompressionConfig,
+        sniff: ContentSniff,
+        trash: TrashConfig,
+        journal: Option<Journal>,
+    ) -> Self {
         Self {
             data_src: Arc::new(data_src),
-            meta_src: Arc::new(meta_src),
+            meta_src,
+            key_ring: key_ring.map(Arc::new),
+            revocations,
+            path_rules: Arc::new(path_rules),
+            buffering: Arc::new(buffering),
+            compression: Arc::new(compression),
+            sniff,
+            trash: Arc::new(trash),
+            journal: journal.map(Arc::new),
+            locks: Arc::new(KeyedLocks::new()),
         }
     }
+
+    /// 读取 object 的元数据和数据期间持有，保证读到的两者来自同一次写入
+    async fn lock_read(&self, bucket: &str, object: &str) -> LockGuards {
+        let key = KeyedLocks::object_key(bucket, object);
+        self.locks.lock(vec![(key, LockMode::Read)]).await
+    }
+
+    /// 修改 object 期间持有，同一个 object 的修改依次进行，bucket 在此期间不会被删除
+    ///
+    /// 多个实例共用后端时由 [`create_object_meta_if`](MetaEngine::create_object_meta_if) 保证带有 `If-Match`
+    /// 的写入不会覆盖其他实例的元数据，但是失败的一方可能已经写入了数据，配置了意图日志时由重放修复
+    async fn lock_write<'a>(
+        &self,
+        bucket: &str,
+        objects: impl IntoIterator<Item = &'a str>,
+    ) -> LockGuards {
+        let objects = objects
+            .into_iter()
+            .map(|object| (KeyedLocks::object_key(bucket, object), LockMode::Write));
+        let keys = std::iter::once((KeyedLocks::bucket_key(bucket), LockMode::Read))
+            .chain(objects)
+            .collect();
+        self.locks.lock(keys).await
+    }
+
+    /// 复制 object 期间持有，源 object 只会被读取
+    async fn lock_copy(
+        &self,
+        (src_bucket, src): (&str, &str),
+        (bucket, object): (&str, &str),
+    ) -> LockGuards {
+        self.locks
+            .lock(vec![
+                (KeyedLocks::object_key(src_bucket, src), LockMode::Read),
+                (KeyedLocks::bucket_key(bucket), LockMode::Read),
+                (KeyedLocks::object_key(bucket, object), LockMode::Write),
+            ])
+            .await
+    }
+
+    /// 修改或者删除 bucket 期间持有，等待所有正在修改其中 object 的请求完成
+    async fn lock_bucket(&self, bucket: &str) -> LockGuards {
+        let key = KeyedLocks::bucket_key(bucket);
+        self.locks.lock(vec![(key, LockMode::Write)]).await
+    }
+
+    /// 检查配额到写入完成期间持有，同一个 bucket 中受配额限制的写入依次进行
+    ///
+    /// 调用者必须已经持有要写入的 object 的锁，并且持有这个锁期间不再等待其他锁，所以不会死锁
+    async fn lock_quota(&self, bucket: &str) -> LockGuards {
+        let key = KeyedLocks::quota_key(bucket);
+        self.locks.lock(vec![(key, LockMode::Write)]).await
+    }
+
+    /// 带有 `If-Match` 时只在 etag 仍然没有改变时写入元数据
+    async fn write_meta(&self, meta: &ObjectMeta, condition: &WriteCondition) -> EngineResult<()> {
+        match condition.expected_etag() {
+            Some(etag) => self.meta_src.create_object_meta_if(meta, etag).await,
+            None => self.meta_src.create_object_meta(meta).await,
+        }
+    }
+
+    /// 配置了意图日志时，在修改数据和元数据之前记录下要做什么
+    async fn begin(&self, intent: Intent) -> EngineResult<Option<IntentId>> {
+        match &self.journal {
+            Some(journal) => journal.begin(&intent).await.map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// 数据和元数据都修改完成后删除 [`begin`](Self::begin) 的记录
+    ///
+    /// 中途失败时不调用，留下的记录在下次启动时由 [`replay`](crab_vault::engine::journal::replay) 处理
+    async fn finish(&self, id: Option<IntentId>) -> EngineResult<()> {
+        match (&self.journal, id) {
+            (Some(journal), Some(id)) => journal.finish(id).await,
+            _ => Ok(()),
+        }
+    }
+}
+
+/// [`build_admin_router`] 的状态，与 [`ApiState`] 共用同一组后端
+#[derive(Clone)]
+pub struct AdminState {
+    data_src: Arc<DataSource>,
+    meta_src: Arc<MetaSource>,
+
+    /// 隐去了密钥的配置，重新加载之后随之更新
+    config: Arc<Live<StaticAppConfig>>,
+
+    /// 不是从配置文件启动时为 [`None`]
+    reloader: Option<Arc<Mutex<ConfigReloader>>>,
+
+    /// 没有配置复制目标时其中没有任何目标
+    replication: Arc<Replication>,
+}
+
+impl AdminState {
+    pub fn new(
+        data_src: Arc<DataSource>,
+        meta_src: Arc<MetaSource>,
+        config: Arc<Live<StaticAppConfig>>,
+        reloader: Option<Arc<Mutex<ConfigReloader>>>,
+        replication: Arc<Replication>,
+    ) -> Self {
+        Self {
+            data_src,
+            meta_src,
+            config,
+            reloader,
+            replication,
+        }
+    }
+}
+
+impl FromRef<ApiState> for Arc<BufferingConfig> {
+    fn from_ref(state: &ApiState) -> Self {
+        state.buffering.clone()
+    }
+}
+
+impl FromRef<ApiState> for Arc<PathRuleStore> {
+    fn from_ref(state: &ApiState) -> Self {
+        state.path_rules.clone()
+    }
+}
+
+impl FromRef<ApiState> for Arc<DataSource> {
+    fn from_ref(state: &ApiState) -> Self {
+        state.data_src.clone()
+    }
+}
+
+impl FromRef<ApiState> for Option<Arc<Journal>> {
+    fn from_ref(state: &ApiState) -> Self {
+        state.journal.clone()
+    }
+}
+
+impl FromRef<ApiState> for Option<Arc<KeyRing>> {
+    fn from_ref(state: &ApiState) -> Self {
+        state.key_ring.clone()
+    }
 }
 
-pub async fn build_router(decoder: JwtDecoder, path_rules: Vec<PathRule>) -> Router<ApiState> {
+impl FromRef<ApiState> for Arc<MetaSource> {
+    fn from_ref(state: &ApiState) -> Self {
+        state.meta_src.clone()
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn build_router(
+    meta_src: Arc<MetaSource>,
+    keys: Arc<KeyManager>,
+    path_rules: Arc<PathRuleStore>,
+    glob_limits: GlobLimits,
+    versioning: VersioningConfig,
+    limits: RequestLimits,
+    cors: Arc<Live<CorsRules>>,
+    rate_limiter: Arc<RateLimiter>,
+    sniff: ContentSniff,
+    compression: ResponseCompressionConfig,
+) -> Router<ApiState> {
     use self::handler::*;
 
+    // 带有 `?tagging` 的 PUT、GET、DELETE 读写 object 的标签，带有 `?retention` 的 PUT、GET 读写 object 的保留，
+    // 参见 handler 中的 `*_or_tagging`
+    // 带有 `?restore` 的 POST 从回收站恢复 object
     let object_router = MethodRouter::new()
-        .put(upload_object)
-        .get(get_object)
+        .put(upload_object_or_tagging)
+        .get(get_object_or_tagging)
         .head(head_object)
-        .patch(patch_object_meta)
-        .delete(delete_object);
+        .patch(patch_object)
+        .post(restore_object)
+        .delete(delete_object_or_tagging);
 
+    // 带有 `?policy` 的 PUT、GET、DELETE 读写 bucket 策略，带有 `?lifecycle`、`?inventory`、`?cors` 时
+    // 读写生命周期规则、清单配置和跨域规则，
+    // 参见 handler 中的 `*_or_policy`
+    // 带有 `?summary` 的 GET 返回一个前缀的概况，带有 `?usage` 的 GET 返回 bucket 的用量和配额，
+    // 带有 `?trash` 的 GET 列出回收站
+    // POST 带有 `?delete` 时批量删除，请求体是 `multipart/form-data` 时是表单上传
     let bucket_router = MethodRouter::new()
-        .put(create_bucket)
+        .put(create_bucket_or_policy)
         .patch(patch_bucket_meta)
-        .delete(delete_bucket)
-        .get(list_objects_meta)
+        .delete(delete_bucket_or_policy)
+        .post(delete_objects_or_upload)
+        .get(list_objects_or_policy)
         .head(head_bucket);
 
     let health = MethodRouter::new()
         .get(health)
         .head(health);
 
-    Router::new()
-        .route("/", axum::routing::get(list_buckets_meta))
+    let api = Router::new()
+        .route("/", MethodRouter::new().get(list_buckets_meta).head(list_buckets_meta))
+        // 静态路由优先于下面的通配路由，所以 `admin` 这个 bucket 中与管理接口同名的 object 无法通过这些方法访问
+        .route(REVOKE_TOKEN_PATH, axum::routing::post(revoke_token))
+        .route(PATH_RULES_PATH, axum::routing::get(list_path_rules).put(replace_path_rules))
+        .route(STATS_PATH, axum::routing::get(storage_stats))
+        .route(EVENTS_PATH, axum::routing::get(list_events))
+        .route(
+            &format!("{INVENTORY_PATH}/{{bucket_name}}"),
+            axum::routing::post(run_inventory),
+        )
         .route("/{bucket_name}", bucket_router)
         .route("/{bucket_name}/{*object_name}", object_router)
-        .layer(AuthLayer::new(decoder, path_rules))
+        // 按照令牌限制时需要鉴权得出的签发者和令牌 ID
+        .layer(RateLimitLayer::per_identity(rate_limiter.clone()))
+        .layer(
+            AuthLayer::new(keys, path_rules, glob_limits, meta_src.clone()).content_sniff(sniff),
+        )
+        // 被鉴权拒绝的请求同样计入 `global`，否则大量无效的令牌可以绕过它
+        .layer(RateLimitLayer::global(rate_limiter))
+        // 预检请求不携带令牌，必须在鉴权之前应答
+        .layer(CorsLayer::new(cors, meta_src))
+        // 错误响应同样可以压缩，但它们通常小于 `min_size`
+        .layer(response_compression_layer(&compression));
+
+    // nest 会去掉路径中的版本前缀，所以鉴权时看到的路径与旧路径相同，已有的令牌和路径规则不需要修改
+    // 静态的前缀优先于通配路由，名为 `v1` 的 bucket 只能通过 `/v1/v1/...` 访问
+    // 健康检查不受版本策略影响，避免旧的探针在旧路径停止服务后失效
+    Router::new()
+        .nest(API_VERSION_PREFIX, api.clone().route("/health", health.clone()))
+        .merge(api.layer(UnversionedLayer::new(versioning)))
         .route("/health", health)
+        // 替换 axum 默认的 2 MiB 限制，超出时返回 413
+        .layer(DefaultBodyLimit::max(limits.max_buffered_body_size))
+        .layer(RequestLimitsLayer::new(limits))
+}
+
+/// ## 探针和配置查询
+///
+/// 没有配置 `server.admin_port` 时与 [`build_router`] 合并，否则单独监听。
+/// 路径没有版本前缀，也不受版本策略的影响
+pub fn build_admin_router(
+    meta_src: Arc<MetaSource>,
+    keys: Arc<KeyManager>,
+    path_rules: Arc<PathRuleStore>,
+    glob_limits: GlobLimits,
+) -> Router<AdminState> {
+    use self::handler::*;
+
+    Router::new()
+        .route(CONFIG_PATH, axum::routing::get(dump_config))
+        .route(RELOAD_PATH, axum::routing::post(reload_config))
+        .route(LOG_LEVEL_PATH, axum::routing::post(set_log_level))
+        .route(REPLICATION_STATUS_PATH, axum::routing::get(replication_status))
+        .layer(AuthLayer::new(keys, path_rules, glob_limits, meta_src))
+        // 之后添加的路由不经过鉴权，负载均衡器的探针不需要令牌
+        .route(HEALTHZ_PATH, MethodRouter::new().get(health).head(health))
+        .route(READYZ_PATH, axum::routing::get(readiness))
 }
@@ -0,0 +1,68 @@
+use std::time::Duration;
+
+use crab_vault::{engine::events::Event, logger::LogLevel};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// `GET /admin/events` 没有新事件时最多等待的秒数
+pub(super) const MAX_EVENTS_WAIT: u64 = 30;
+
+/// 等待新事件时重新读取的间隔，共享数据库的其他实例写入的事件也能被发现
+pub(super) const EVENTS_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// `POST /admin/tokens/revoke` 的请求体
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub(super) struct RevokeTokenRequest {
+    pub jti: Uuid,
+
+    /// 令牌的过期时间，Unix 时间戳，之后这条吊销记录会被清理，不提供时永远保留
+    #[serde(default)]
+    pub expires_at: Option<i64>,
+}
+
+/// `POST /admin/log-level` 的请求体和响应体
+///
+/// 请求中省略的项保持不变，响应中是修改之后控制台日志正在使用的级别
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub(super) struct LogLevelBody {
+    pub level: Option<LogLevel>,
+
+    /// 与 `logger.filter` 的写法相同，空字符串表示清除所有按照模块设置的级别
+    pub filter: Option<String>,
+}
+
+/// `GET /admin/readyz` 的响应体，后端出错时带有错误信息
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub(super) struct Readiness {
+    pub ready: bool,
+    pub data: Option<String>,
+    pub meta: Option<String>,
+}
+
+/// `GET /admin/events` 的查询参数
+#[derive(Deserialize)]
+pub(super) struct EventsQuery {
+    /// 只返回序号大于它的事件，通常是上一次响应中的 `last-seq`
+    #[serde(default)]
+    pub since: u64,
+
+    /// 最多返回的事件数，不超过 [`MAX_EVENTS_PER_PAGE`](crab_vault::engine::events::MAX_EVENTS_PER_PAGE)
+    pub limit: Option<usize>,
+
+    /// 没有新事件时最多等待的秒数，不超过 [`MAX_EVENTS_WAIT`]
+    #[serde(default)]
+    pub wait: u64,
+}
+
+/// `GET /admin/events` 的响应体
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub(super) struct EventPage {
+    pub events: Vec<Event>,
+
+    /// 最后一个事件的序号，没有事件时等于请求中的 `since`，下一次请求从这里继续
+    pub last_seq: u64,
+}
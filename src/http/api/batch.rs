@@ -0,0 +1,108 @@
+use crab_vault::{
+    auth::{HttpMethod, error::AuthError, policy::PolicyEngine},
+    engine::{error::EngineResult, name},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::{
+        api::{ApiError, ClientError},
+        context::{ErrorBody, RequestError},
+    },
+    http::api::has_query_key,
+};
+
+/// 一次批量删除最多包含的 object 个数
+pub(super) const MAX_DELETE_OBJECTS: usize = 1000;
+
+/// `POST /{bucket}?delete` 的请求体
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub(super) struct DeleteObjectsRequest {
+    pub objects: Vec<String>,
+}
+
+/// `POST /{bucket}?delete` 的响应体，每个 object 要么出现在 `deleted` 中，要么出现在 `errors` 中
+#[derive(Serialize, Default)]
+#[serde(rename_all = "kebab-case")]
+pub(super) struct DeleteObjectsResult {
+    pub deleted: Vec<String>,
+    pub errors: Vec<DeleteObjectError>,
+}
+
+/// 单个 object 的错误，除了 `object` 之外与对应的错误响应体相同
+#[derive(Serialize)]
+pub(super) struct DeleteObjectError {
+    object: String,
+
+    #[serde(flatten)]
+    error: ErrorBody,
+}
+
+/// 查询字符串中是否有 `delete` 参数，它的值会被忽略
+pub(super) fn is_delete_query(query: Option<&str>) -> bool {
+    has_query_key(query, "delete")
+}
+
+impl DeleteObjectsRequest {
+    pub fn from_body(body: &[u8]) -> Result<Self, ApiError> {
+        let request: Self = serde_json::from_slice(body)?;
+
+        if request.objects.len() > MAX_DELETE_OBJECTS {
+            return Err(ApiError::Client(ClientError::TooManyObjects {
+                max: MAX_DELETE_OBJECTS,
+            }));
+        }
+
+        Ok(request)
+    }
+}
+
+impl DeleteObjectsResult {
+    /// 检查每个 object 的名称和删除权限，返回需要交给引擎删除的那些 object
+    ///
+    /// bucket 级别的请求在鉴权中间件中不会检查路径，所以需要在这里逐个检查，bucket 策略同样适用
+    pub fn admit(
+        &mut self,
+        bucket_name: &str,
+        objects: Vec<String>,
+        permission: &PolicyEngine,
+    ) -> Vec<String> {
+        objects
+            .into_iter()
+            .filter(|object| {
+                if let Err(e) = name::validate_object_name(object) {
+                    self.fail(object.clone(), e.into());
+                    false
+                } else if !permission.allows(HttpMethod::Delete, &format!("/{bucket_name}/{object}")) {
+                    self.fail(object.clone(), AuthError::InsufficientPermissions.into());
+                    false
+                } else {
+                    true
+                }
+            })
+            .collect()
+    }
+
+    /// 按照引擎返回的结果记录每个 object，返回删除成功的那些 object
+    pub fn record(&mut self, objects: Vec<String>, results: Vec<EngineResult<()>>) -> Vec<String> {
+        objects
+            .into_iter()
+            .zip(results)
+            .filter_map(|(object, result)| match result {
+                Ok(()) => Some(object),
+                Err(e) => {
+                    self.fail(object, e.into());
+                    None
+                }
+            })
+            .collect()
+    }
+
+    fn fail(&mut self, object: String, error: RequestError) {
+        let mut error = error.body(None);
+        // 错误本身的 `object` 与外面的相同，留下来会出现重复的键
+        error.details.remove("object");
+        self.errors.push(DeleteObjectError { object, error });
+    }
+}
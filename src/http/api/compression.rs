@@ -0,0 +1,59 @@
+use axum::http::{HeaderMap, header::ACCEPT_ENCODING};
+use bytes::Bytes;
+use crab_vault::engine::{
+    ObjectMeta,
+    compression::{Codec, Compression},
+    error::{EngineError, EngineResult},
+};
+
+use crate::{app_config::data::CompressionConfig, http::extractor::spool::SpooledBody};
+
+/// ## 按照配置压缩请求体，返回压缩信息和实际要保存的数据
+///
+/// 不需要压缩、压缩之后没有变小，或者请求体已经写入了临时文件时返回 [`None`]，这时保存原始数据
+pub(super) async fn encode(
+    config: &CompressionConfig,
+    meta: &ObjectMeta,
+    body: &SpooledBody,
+) -> EngineResult<Option<(Compression, Bytes)>> {
+    let codec = match config.codec_for(&meta.bucket_name, &meta.content_type, meta.size) {
+        Some(codec) if body.spilled().is_none() => codec,
+        _ => return Ok(None),
+    };
+
+    let data = body.to_bytes().await?;
+    let encoded = tokio::task::spawn_blocking(move || Compression::apply(codec, &data))
+        .await
+        .map_err(|e| EngineError::Compression(e.to_string()))??;
+
+    Ok(encoded.map(|(compression, data)| (compression, Bytes::from(data))))
+}
+
+/// 把读出的数据还原为原始数据
+pub(super) async fn decode(meta: &ObjectMeta, data: Vec<u8>) -> EngineResult<Vec<u8>> {
+    if meta.compression.is_none() {
+        return Ok(data);
+    }
+
+    let meta = meta.clone();
+    tokio::task::spawn_blocking(move || meta.decode(data))
+        .await
+        .map_err(|e| EngineError::Compression(e.to_string()))?
+}
+
+/// 客户端是否在 `Accept-Encoding` 中接受 `codec`，`q=0` 表示明确拒绝
+pub(super) fn accepts(headers: &HeaderMap, codec: Codec) -> bool {
+    headers
+        .get_all(ACCEPT_ENCODING)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .any(|v| {
+            let mut params = v.split(';').map(str::trim);
+            let token = params.next().unwrap_or_default();
+            let refused = params
+                .filter_map(|v| v.strip_prefix("q="))
+                .any(|v| v.parse::<f32>().is_ok_and(|q| q == 0.0));
+            token.eq_ignore_ascii_case(codec.token()) && !refused
+        })
+}
@@ -0,0 +1,18 @@
+use serde_json::Value;
+
+use crate::{
+    app_config::server::CorsConfig,
+    error::api::{ApiError, ClientError},
+};
+
+/// 解析并校验 `PUT /{bucket}?cors` 的请求体，格式与配置文件中的 `server.cors` 相同
+///
+/// 保存的是重新序列化之后的结果，省略的字段会被补上默认值
+pub(super) fn from_body(body: &[u8]) -> Result<Value, ApiError> {
+    let cors: CorsConfig = serde_json::from_slice(body)?;
+
+    cors.validate()
+        .map_err(|reason| ApiError::Client(ClientError::InvalidCorsConfig { reason }))?;
+
+    Ok(serde_json::to_value(cors)?)
+}
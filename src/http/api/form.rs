@@ -0,0 +1,295 @@
+use std::collections::HashMap;
+
+use axum::http::{HeaderMap, HeaderValue, StatusCode, Uri, header::CONTENT_TYPE};
+use bytes::Bytes;
+use percent_encoding::{NON_ALPHANUMERIC, utf8_percent_encode};
+
+use crate::{
+    error::api::{ApiError, ClientError},
+    http::PATH_SEGMENT,
+};
+
+/// 文件之前最多的字段数，文件之后的字段会被忽略
+const MAX_FIELDS: usize = 32;
+
+/// 保存文件内容的字段
+const FILE_FIELD: &str = "file";
+
+/// `key` 中的这个占位符会被替换为上传的文件名
+const FILENAME_PLACEHOLDER: &str = "${filename}";
+
+/// ## `POST /{bucket}` 的 `multipart/form-data` 请求体
+///
+/// 字段的名称不区分大小写：
+///
+/// - `key`：object 的名称，其中的 `${filename}` 会被替换为上传的文件名
+/// - `token`：令牌，请求中没有 Authorization 头和预签名的令牌时使用
+/// - `Content-Type`：object 的内容类型，没有时使用文件自己的类型，都没有时按照 `data.sniff` 决定
+/// - `success_action_redirect`：上传成功后重定向到的地址
+/// - `success_action_status`：没有重定向时的状态码，`200`、`201` 或者 `204`
+/// - `file`：文件的内容，必须是最后一个字段
+pub(super) struct FormUpload {
+    pub key: String,
+    pub token: Option<String>,
+    pub content_type: Option<String>,
+    pub redirect: Option<String>,
+    pub status: StatusCode,
+    pub file: Bytes,
+}
+
+/// 表单中的一个字段
+struct Part {
+    name: String,
+    filename: Option<String>,
+    content_type: Option<String>,
+    data: Bytes,
+}
+
+impl FormUpload {
+    pub fn parse(headers: &HeaderMap, body: &Bytes) -> Result<Self, ApiError> {
+        let boundary = headers
+            .get(CONTENT_TYPE)
+            .map(HeaderValue::to_str)
+            .transpose()?
+            .and_then(boundary)
+            .ok_or(invalid(
+                "the request should be `multipart/form-data` with a boundary",
+            ))?;
+
+        let mut parts = PartReader::new(body, &boundary)?;
+        let mut fields = HashMap::new();
+        let file = loop {
+            let part = parts
+                .next_part()?
+                .ok_or(invalid("the form has no `file` field"))?;
+            if part.name.eq_ignore_ascii_case(FILE_FIELD) {
+                break part;
+            }
+            if fields.len() == MAX_FIELDS {
+                return Err(invalid("the form has too many fields"));
+            }
+
+            let value = String::from_utf8(part.data.to_vec())
+                .map_err(|_| invalid("form fields should be UTF-8"))?;
+            fields.insert(part.name.to_ascii_lowercase(), value);
+        };
+
+        let filename = file.filename.as_deref().map(basename).unwrap_or_default();
+        let key = fields
+            .remove("key")
+            .ok_or(invalid("the form has no `key` field"))?
+            .replace(FILENAME_PLACEHOLDER, filename);
+
+        let redirect = fields.remove("success_action_redirect");
+        if redirect.as_deref().is_some_and(|v| !redirectable(v)) {
+            return Err(invalid(
+                "`success_action_redirect` should be an absolute http or https URL",
+            ));
+        }
+
+        // 与 S3 一样，不支持的状态码按照没有给出处理
+        let status = match fields.remove("success_action_status").as_deref() {
+            Some("200") => StatusCode::OK,
+            Some("204") => StatusCode::NO_CONTENT,
+            _ => StatusCode::CREATED,
+        };
+
+        let content_type = fields.remove("content-type").or(file.content_type);
+
+        Ok(Self {
+            key,
+            token: fields.remove("token").filter(|v| !v.is_empty()),
+            content_type,
+            redirect,
+            status,
+            file: file.data,
+        })
+    }
+
+    /// 鉴权时使用的路径，与直接 `PUT` 这个 object 时的路径相同
+    pub fn path(&self, bucket: &str) -> String {
+        format!(
+            "/{}/{}",
+            utf8_percent_encode(bucket, PATH_SEGMENT),
+            utf8_percent_encode(&self.key, PATH_SEGMENT)
+        )
+    }
+
+    /// 重定向的地址，查询参数中附带 `bucket`、`key` 和 `etag`
+    pub fn redirect_location(&self, bucket: &str, etag: &str) -> Option<String> {
+        let redirect = self.redirect.as_deref()?;
+        let separator = if redirect.contains('?') { '&' } else { '?' };
+        let encode = |v| utf8_percent_encode(v, NON_ALPHANUMERIC);
+
+        Some(format!(
+            "{redirect}{separator}bucket={}&key={}&etag={}",
+            encode(bucket),
+            encode(&self.key),
+            encode(etag)
+        ))
+    }
+}
+
+/// 按照分隔符逐个读出表单中的字段
+struct PartReader<'a> {
+    body: &'a Bytes,
+    delimiter: Vec<u8>,
+
+    /// 下一个字段的起始位置，紧跟在分隔符之后
+    pos: usize,
+}
+
+impl<'a> PartReader<'a> {
+    fn new(body: &'a Bytes, boundary: &str) -> Result<Self, ApiError> {
+        let delimiter = [b"--", boundary.as_bytes()].concat();
+        // 第一个分隔符之前的内容会被忽略
+        let start = find(body, &delimiter, 0).ok_or(malformed())?;
+
+        Ok(Self {
+            body,
+            pos: start + delimiter.len(),
+            delimiter,
+        })
+    }
+
+    /// 读出下一个字段，遇到结束的分隔符时返回 [`None`]
+    fn next_part(&mut self) -> Result<Option<Part>, ApiError> {
+        let rest = &self.body[self.pos..];
+        if rest.starts_with(b"--") {
+            return Ok(None);
+        }
+
+        // 分隔符之后可以有空白
+        let padding = rest
+            .iter()
+            .take_while(|v| **v == b' ' || **v == b'\t')
+            .count();
+        if !rest[padding..].starts_with(b"\r\n") {
+            return Err(malformed());
+        }
+
+        let headers_start = self.pos + padding + 2;
+        let headers_end = find(self.body, b"\r\n\r\n", headers_start).ok_or(malformed())?;
+        let data_start = headers_end + 4;
+        let closing = [b"\r\n", self.delimiter.as_slice()].concat();
+        let data_end = find(self.body, &closing, data_start).ok_or(malformed())?;
+        self.pos = data_end + closing.len();
+
+        let headers = std::str::from_utf8(&self.body[headers_start..headers_end])
+            .map_err(|_| invalid("the headers of a form field should be UTF-8"))?;
+        let (mut disposition, mut content_type) = (None, None);
+        for line in headers.split("\r\n") {
+            let (name, value) = line.split_once(':').ok_or(malformed())?;
+            if name.trim().eq_ignore_ascii_case("content-disposition") {
+                disposition = Some(value.trim());
+            } else if name.trim().eq_ignore_ascii_case("content-type") {
+                content_type = Some(value.trim().to_string());
+            }
+        }
+
+        let params = parameters(disposition.ok_or(malformed())?);
+        let param = |name: &str| {
+            params
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.clone())
+        };
+
+        Ok(Some(Part {
+            name: param("name").ok_or(malformed())?,
+            filename: param("filename"),
+            content_type,
+            data: self.body.slice(data_start..data_end),
+        }))
+    }
+}
+
+/// `multipart/form-data; boundary=...` 中的分隔符，不是表单时返回 [`None`]
+fn boundary(content_type: &str) -> Option<String> {
+    let mime = content_type.split(';').next()?.trim();
+    if !mime.eq_ignore_ascii_case("multipart/form-data") {
+        return None;
+    }
+
+    parameters(content_type)
+        .into_iter()
+        .find(|(key, _)| key == "boundary")
+        .map(|(_, value)| value)
+        .filter(|v| (1..=70).contains(&v.len()))
+}
+
+/// 请求体是不是一个表单，不检查其中的分隔符
+pub(super) fn is_form(headers: &HeaderMap) -> bool {
+    headers
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.split(';').next())
+        .is_some_and(|v| v.trim().eq_ignore_ascii_case("multipart/form-data"))
+}
+
+/// 头部中第一个 `;` 之后的参数，参数名转换为小写，值可以是带有转义的引号字符串
+fn parameters(value: &str) -> Vec<(String, String)> {
+    let mut params = vec![];
+    let mut rest = value;
+
+    while let Some(i) = rest.find(';') {
+        let Some((name, tail)) = rest[i + 1..].split_once('=') else {
+            break;
+        };
+
+        let (value, tail) = match tail.trim_start().strip_prefix('"') {
+            Some(quoted) => {
+                let (mut value, mut end) = (String::new(), quoted.len());
+                let mut chars = quoted.char_indices();
+                while let Some((i, c)) = chars.next() {
+                    match c {
+                        '\\' => value.extend(chars.next().map(|(_, c)| c)),
+                        '"' => {
+                            end = i + 1;
+                            break;
+                        }
+                        c => value.push(c),
+                    }
+                }
+                (value, &quoted[end..])
+            }
+            None => {
+                let end = tail.find(';').unwrap_or(tail.len());
+                (tail[..end].trim().to_string(), &tail[end..])
+            }
+        };
+
+        params.push((name.trim().to_ascii_lowercase(), value));
+        rest = tail;
+    }
+
+    params
+}
+
+/// 浏览器可能给出完整的路径，只保留最后一段
+fn basename(filename: &str) -> &str {
+    filename.rsplit(['/', '\\']).next().unwrap_or(filename)
+}
+
+fn redirectable(url: &str) -> bool {
+    HeaderValue::from_str(url).is_ok()
+        && url
+            .parse::<Uri>()
+            .is_ok_and(|v| matches!(v.scheme_str(), Some("http" | "https")) && v.host().is_some())
+}
+
+fn find(haystack: &[u8], needle: &[u8], from: usize) -> Option<usize> {
+    haystack
+        .get(from..)?
+        .windows(needle.len())
+        .position(|v| v == needle)
+        .map(|v| v + from)
+}
+
+fn malformed() -> ApiError {
+    invalid("the form is malformed")
+}
+
+fn invalid(reason: &'static str) -> ApiError {
+    ApiError::Client(ClientError::InvalidForm { reason })
+}
@@ -1,38 +1,117 @@
+use std::future::ready;
+
 use axum::{
     debug_handler,
-    extract::{Path, State},
-    http::StatusCode,
+    Extension,
+    extract::{Query, RawQuery, Request, State, rejection::BytesRejection},
+    handler::Handler,
+    http::{
+        HeaderMap, StatusCode, Uri,
+        header::{ETAG, LOCATION},
+    },
     response::{IntoResponse, Response},
 };
+use bytes::Bytes;
 use crab_vault_engine::error::EngineError;
+use futures::TryStreamExt;
 
-use crate::http::{
-    api::{
-        ApiState,
-        response::{BucketResponse, ObjectResponse},
-        util::merge_json_object,
+use crate::{
+    app_config::{
+        auth::{PathRule, StaticPathRule},
+        data::ContentSniff,
+    },
+    error::{
+        api::{ApiError, ClientError},
+        context::{Context, ErrorContext, HandlerResult},
     },
-    extractor::{
-        auth::RestrictedBytes,
-        meta::{BuckeMetaExtractor, ObjectMetaExtractor},
+    http::{
+        api::{
+            AdminState, ApiState, CORS_QUERY_KEY, INVENTORY_QUERY_KEY, LIFECYCLE_QUERY_KEY,
+            POLICY_QUERY_KEY, RESTORE_QUERY_KEY, RETENTION_QUERY_KEY, TAGGING_QUERY_KEY,
+            TRASH_QUERY_KEY,
+            admin::{
+                EVENTS_POLL_INTERVAL, EventPage, EventsQuery, LogLevelBody, MAX_EVENTS_WAIT,
+                Readiness, RevokeTokenRequest,
+            },
+            batch::{self, DeleteObjectsRequest, DeleteObjectsResult},
+            compression, cors,
+            form::FormUpload,
+            has_query_key, inventory, is_force_delete, is_form_upload, lifecycle,
+            patch::{self, ContentPatch},
+            payload, policy,
+            purge::{self, PURGE_CHUNK},
+            quota::{self, USAGE_QUERY_KEY, UsageReport},
+            response::{
+                BucketResponse, NdjsonResponse, ObjectResponse, bucket_page_headers,
+                skipped_entries_headers,
+            },
+            retention, sniff,
+            summary::{PrefixAccess, PrefixSummary, SUMMARY_QUERY_KEY},
+            tagging,
+            trash::{self, RestoreQuery},
+            util::{ResponseFormat, merge_json_object},
+            xml,
+        },
+        extractor::{
+            auth::{PermissionExtractor, RestrictedBytes},
+            condition::WriteCondition,
+            copy::{CopyExtractor, CopySource, MetadataDirective},
+            meta::{BuckeMetaExtractor, ObjectMetaExtractor},
+            path::{BucketPath, ObjectPath},
+            spool::SpooledBody,
+            sse::CustomerKeyExtractor,
+        },
+        middleware::{
+            auth::{ApprovedByPathRule, FormUploadAuth},
+            rate_limit::FormRateLimit,
+        },
     },
+    logger,
 };
 
-use crab_vault::engine::{error::EngineResult, *};
+use crab_vault::engine::{journal::Intent, *};
+use crab_vault::logger::filter::TargetFilter;
 
 // --- Bucket Handlers ---
 #[debug_handler]
 pub(super) async fn create_bucket(
     State(state): State<ApiState>,
     meta: BuckeMetaExtractor,
-) -> EngineResult<StatusCode> {
-    let meta = meta.into_meta();
+) -> HandlerResult<StatusCode> {
+    let cx = ErrorContext::new("createBucket").bucket(&meta.name);
+    let (max_bytes, max_objects) = (meta.max_bytes, meta.max_objects);
+    let mut meta = meta.into_meta().context(&cx)?;
+
+    let _lock = state.lock_bucket(&meta.name).await;
+    let existing = match state.meta_src.read_bucket_meta(&meta.name).await {
+        Ok(existing) => Some(existing),
+        Err(EngineError::BucketMetaNotFound { .. }) => None,
+        Err(e) => return Err(e).context(&cx),
+    };
 
-    tracing::info!("{:?}", meta);
+    // 重复创建时保留原有的策略、生命周期规则、清单配置、跨域规则和没有重新给出的配额，以及原有的数据密钥，否则已经加密的 object 将无法解密
+    let mut data_key = None;
+    if let Some(existing) = existing {
+        meta.policy = existing.policy;
+        meta.lifecycle = existing.lifecycle;
+        meta.inventory = existing.inventory;
+        meta.cors = existing.cors;
+        meta.max_bytes = max_bytes.unwrap_or(existing.max_bytes);
+        meta.max_objects = max_objects.unwrap_or(existing.max_objects);
+        data_key = existing.data_key;
+    }
+    if let Some(key_ring) = &state.key_ring {
+        meta.data_key = match data_key {
+            Some(data_key) => Some(data_key),
+            None => Some(key_ring.generate_wrapped().context(&cx)?),
+        };
+    }
+
+    tracing::info!("{:?}", meta.name);
 
     // 操作是幂等的，所以我们不关心它们是否已经存在
-    state.data_src.create_bucket(&meta.name).await?;
-    state.meta_src.create_bucket_meta(&meta).await?;
+    state.data_src.create_bucket(&meta.name).await.context(&cx)?;
+    state.meta_src.create_bucket_meta(&meta).await.context(&cx)?;
 
     Ok(StatusCode::CREATED)
 }
@@ -40,10 +119,82 @@ pub(super) async fn create_bucket(
 #[debug_handler]
 pub(super) async fn delete_bucket(
     State(state): State<ApiState>,
-    Path(bucket_name): Path<String>,
-) -> EngineResult<StatusCode> {
-    state.data_src.delete_bucket(&bucket_name).await?;
-    state.meta_src.delete_bucket_meta(&bucket_name).await?;
+    BucketPath(bucket_name): BucketPath,
+) -> HandlerResult<StatusCode> {
+    let cx = ErrorContext::new("deleteBucket").bucket(&bucket_name);
+    let _lock = state.lock_bucket(&bucket_name).await;
+    state.data_src.delete_bucket(&bucket_name).await.context(&cx)?;
+    state
+        .meta_src
+        .delete_bucket_meta(&bucket_name)
+        .await
+        .context(&cx)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// `DELETE /{bucket}?force=true`，先删除其中所有的 object 和它们的元数据，再删除 bucket 本身
+///
+/// 令牌必须授予 [`ForceDelete`](crab_vault::auth::Capability::ForceDelete)，并且允许删除其中的每一个 object，
+/// 有任何一个不允许时什么都不删除。删除期间持有 bucket 的锁，其中的 object 不会被修改。
+///
+/// 删除不是原子的：object 分批删除，中途失败时已经删除的不会恢复，
+/// 留下的意图日志会在下次启动时删除剩下的 object，bucket 本身需要重新删除
+#[debug_handler]
+pub(super) async fn force_delete_bucket(
+    State(state): State<ApiState>,
+    BucketPath(bucket_name): BucketPath,
+    PermissionExtractor(permission): PermissionExtractor,
+) -> HandlerResult<StatusCode> {
+    let cx = ErrorContext::new("forceDeleteBucket").bucket(&bucket_name);
+    purge::admit_bucket(&permission, &bucket_name).context(&cx)?;
+    let _lock = state.lock_bucket(&bucket_name).await;
+    state
+        .meta_src
+        .read_bucket_meta(&bucket_name)
+        .await
+        .context(&cx)?;
+
+    // 有任何一个 object 无法删除时整个 bucket 都保持不变
+    let now = clock::now();
+    let query = list::ListObjectsQuery::default();
+    let mut objects = Vec::new();
+    let mut stream = state.meta_src.stream_objects_meta(&bucket_name, &query);
+    while let Some(meta) = stream.try_next().await.context(&cx)? {
+        purge::admit(&permission, &meta).context(&cx.clone().object(&meta.object_name))?;
+        meta.check_retention(now).context(&cx)?;
+        objects.push(meta.object_name);
+    }
+    drop(stream);
+
+    let total = objects.len();
+    tracing::info!(bucket = bucket_name, total, "force deleting bucket");
+    let intent = state
+        .begin(Intent::delete(&bucket_name, &objects))
+        .await
+        .context(&cx)?;
+    let mut deleted = 0;
+    for chunk in objects.chunks(PURGE_CHUNK) {
+        let results = state.data_src.delete_objects(&bucket_name, chunk).await;
+        purge::first_error(results).context(&cx)?;
+        let results = state
+            .meta_src
+            .delete_objects_meta(&bucket_name, chunk)
+            .await;
+        purge::first_error(results).context(&cx)?;
+
+        deleted += chunk.len();
+        tracing::info!(bucket = bucket_name, deleted, total, "objects deleted");
+    }
+
+    state.data_src.delete_bucket(&bucket_name).await.context(&cx)?;
+    state
+        .meta_src
+        .delete_bucket_meta(&bucket_name)
+        .await
+        .context(&cx)?;
+    state.finish(intent).await.context(&cx)?;
+    tracing::info!(bucket = bucket_name, total, "bucket force deleted");
 
     Ok(StatusCode::NO_CONTENT)
 }
@@ -51,65 +202,363 @@ pub(super) async fn delete_bucket(
 #[debug_handler]
 pub(super) async fn head_bucket(
     State(state): State<ApiState>,
-    Path(bucket_name): Path<String>,
-) -> EngineResult<Response> {
-    let meta = state.meta_src.read_bucket_meta(&bucket_name).await?;
+    BucketPath(bucket_name): BucketPath,
+) -> HandlerResult<Response> {
+    let cx = ErrorContext::new("headBucket").bucket(&bucket_name);
+    let meta = state
+        .meta_src
+        .read_bucket_meta(&bucket_name)
+        .await
+        .context(&cx)?;
+    let usage = state
+        .meta_src
+        .bucket_usage(&bucket_name)
+        .await
+        .context(&cx)?;
 
-    Ok(BucketResponse::new(meta).into_response())
+    Ok(BucketResponse::new(meta).with_usage(usage).into_response())
 }
 
 #[debug_handler]
 pub(super) async fn patch_bucket_meta(
     State(state): State<ApiState>,
     new: BuckeMetaExtractor,
-) -> EngineResult<StatusCode> {
-    let mut old_meta = state.meta_src.read_bucket_meta(&new.name).await?;
-    old_meta.user_meta = merge_json_object(new.user_meta, old_meta.user_meta)?;
-    state.meta_src.create_bucket_meta(&old_meta).await?;
-    state.meta_src.touch_bucket(&new.name).await?;
+) -> HandlerResult<StatusCode> {
+    let cx = ErrorContext::new("patchBucketMeta").bucket(&new.name);
+    let _lock = state.lock_bucket(&new.name).await;
+    let mut old_meta = state
+        .meta_src
+        .read_bucket_meta(&new.name)
+        .await
+        .context(&cx)?;
+    new.apply_quota(&mut old_meta);
+    old_meta.user_meta = merge_json_object(new.user_meta, old_meta.user_meta).context(&cx)?;
+    state
+        .meta_src
+        .create_bucket_meta(&old_meta)
+        .await
+        .context(&cx)?;
+    state.meta_src.touch_bucket(&new.name).await.context(&cx)?;
 
     Ok(StatusCode::OK)
 }
 
 #[debug_handler]
-pub(super) async fn list_buckets_meta(State(state): State<ApiState>) -> EngineResult<Response> {
-    let res = state.meta_src.list_buckets_meta().await?;
-    let res = res.into_iter().map(BucketResponse::new).collect::<Vec<_>>();
+pub(super) async fn list_buckets_meta(
+    State(state): State<ApiState>,
+    Query(query): Query<list::ListBucketsQuery>,
+    headers: HeaderMap,
+) -> HandlerResult<Response> {
+    let cx = ErrorContext::new("listBuckets");
+    let format = ResponseFormat::negotiate(&headers);
+    // ndjson 不分页也不排序，逐条返回所有满足前缀条件的 bucket
+    if format == ResponseFormat::Ndjson {
+        let (sender, response) = NdjsonResponse::channel();
+        tokio::spawn(async move {
+            let stream = state
+                .meta_src
+                .stream_buckets_meta()
+                .try_filter(|v| ready(query.matches(v)));
+            sender.send_all(stream.map_ok(BucketResponse::new)).await;
+        });
+        return Ok(response.into_response());
+    }
+
+    // 内部的 bucket 对客户端不可见，`query` 会过滤掉它
+    let page = state
+        .meta_src
+        .list_buckets_meta_page(&query)
+        .await
+        .context(&cx)?;
+    let headers = bucket_page_headers(&page);
+    if format == ResponseFormat::Xml {
+        return Ok((headers, xml::list_buckets(&page)).into_response());
+    }
+    let res = page
+        .buckets
+        .into_iter()
+        .map(BucketResponse::new)
+        .collect::<Vec<_>>();
 
-    Ok((StatusCode::OK, axum::Json(res)).into_response())
+    Ok((StatusCode::OK, headers, axum::Json(res)).into_response())
 }
 
 // --- Object Handlers ---
 
 #[debug_handler]
+#[allow(clippy::too_many_arguments)]
 pub(super) async fn upload_object(
     State(state): State<ApiState>,
-    meta: ObjectMetaExtractor,
-    RestrictedBytes(data): RestrictedBytes,
-) -> EngineResult<StatusCode> {
+    mut meta: ObjectMetaExtractor,
+    CopyExtractor(copy): CopyExtractor,
+    CustomerKeyExtractor(customer_key): CustomerKeyExtractor,
+    condition: WriteCondition,
+    PermissionExtractor(permission): PermissionExtractor,
+    uri: Uri,
+    body: SpooledBody,
+) -> HandlerResult<StatusCode> {
+    // 带有 X-Crab-Vault-Copy-Source 时是服务端复制，请求体会被忽略
+    if let Some((source, directive)) = copy {
+        let cx = ErrorContext::new("copyObject")
+            .bucket(&meta.bucket_name)
+            .object(&meta.object_name);
+        if condition != WriteCondition::Always {
+            return Err(ApiError::Client(ClientError::UnsupportedPrecondition)).context(&cx);
+        }
+        let _lock = state
+            .lock_copy(
+                (&source.bucket_name, &source.object_name),
+                (&meta.bucket_name, &meta.object_name),
+            )
+            .await;
+        return copy_object(state, meta, source, directive, customer_key.as_ref())
+            .await
+            .context(&cx);
+    }
+
+    let cx = ErrorContext::new("uploadObject")
+        .bucket(&meta.bucket_name)
+        .object(&meta.object_name);
+
+    let _lock = state
+        .lock_write(&meta.bucket_name, [meta.object_name.as_str()])
+        .await;
+    retention::check(&state.meta_src, &meta.bucket_name, &meta.object_name)
+        .await
+        .context(&cx)?;
+
+    // 带有 If-Match 时先比较 etag，避免明知会失败还覆盖数据
+    if condition.expected_etag().is_some() {
+        let current = state
+            .meta_src
+            .read_object_meta(&meta.bucket_name, &meta.object_name)
+            .await;
+        condition.check(current).context(&cx)?;
+    }
+
     // 1. 检查 bucket 是否存在
-    tracing::warn!("{}{}", &meta.bucket_name, &meta.object_name);
+    tracing::warn!(path = format!("/{}/{}", &meta.bucket_name, &meta.object_name));
 
-    // 2. 从提取器和数据中创建完整的元数据
-    let meta = meta.into_meta(&data);
+    // 2. 按照配置推断 content type，再从提取器和数据中创建完整的元数据，检查写入之后是否超出 bucket 的配额
+    if state.sniff != ContentSniff::Off {
+        let head = body.head(sniff::SNIFF_LEN).await.context(&cx)?;
+        let declared = meta.content_type.as_deref();
+        let resolved = sniff::resolve(state.sniff, declared, &meta.object_name, &head);
+        sniff::admit(&permission, uri.path(), body.len(), declared, &resolved).context(&cx)?;
+        meta.content_type = Some(resolved);
+    }
+    let mut meta = meta.into_meta(&body).context(&cx)?;
+    let _quota = quota::check(&state, &meta).await.context(&cx)?;
 
-    // 3. 原子地写入数据和元数据
-    match state
-        .data_src
-        .create_object(&meta.bucket_name, &meta.object_name, &data)
-        .await {
-            Ok(_) => {},
-            Err(e) => {
-                if let EngineError::BucketNotFound { bucket: _ } = e {
-                    state.data_src.create_bucket(&meta.bucket_name).await?;
-                    state.data_src.create_object(&meta.bucket_name, &meta.object_name, &data).await?;
-                } else {
-                    return Err(e)
-                }
-            },
+    // 3. 按照配置压缩、加密数据，元数据中的大小和 etag 仍然是原始数据的
+    let stored = payload::prepare(&state, &mut meta, body, customer_key.as_ref())
+        .await
+        .context(&cx)?;
+
+    // 4. 原子地写入数据和元数据
+    store_object(&state, &meta, &stored, &condition)
+        .await
+        .context(&cx)?;
+
+    Ok(StatusCode::CREATED)
+}
+
+/// 写入按照配置处理过的请求体和元数据，bucket 的数据还不存在时先创建它
+///
+/// 带有 If-None-Match: * 时由引擎保证检查和写入是原子的，并发的写入者中只有一个能够成功；
+/// 带有 If-Match 时由引擎保证比较 etag 和写入元数据是原子的
+/// 写入了临时文件的请求体交给引擎直接从文件复制，只有 If-None-Match 需要把它读回内存
+pub(super) async fn store_object(
+    state: &ApiState,
+    meta: &ObjectMeta,
+    stored: &SpooledBody,
+    condition: &WriteCondition,
+) -> crab_vault::engine::error::EngineResult<()> {
+    let create = || async {
+        match (condition, stored.spilled()) {
+            (WriteCondition::Always | WriteCondition::IfMatch(_), Some(path)) => {
+                state
+                    .data_src
+                    .create_object_from_file(&meta.bucket_name, &meta.object_name, path)
+                    .await
+            }
+            (WriteCondition::Always | WriteCondition::IfMatch(_), None) => {
+                state
+                    .data_src
+                    .create_object(&meta.bucket_name, &meta.object_name, &stored.to_bytes().await?)
+                    .await
+            }
+            (WriteCondition::IfAbsent, _) => {
+                state
+                    .data_src
+                    .create_object_if_absent(
+                        &meta.bucket_name,
+                        &meta.object_name,
+                        &stored.to_bytes().await?,
+                    )
+                    .await
+            }
         }
+    };
 
-    state.meta_src.create_object_meta(&meta).await?;
+    let intent = state.begin(Intent::put(meta)).await?;
+    match create().await {
+        Err(EngineError::BucketNotFound { bucket: _ }) => {
+            state.data_src.create_bucket(&meta.bucket_name).await?;
+            create().await?;
+        }
+        other => other?,
+    }
+
+    state.write_meta(meta, condition).await?;
+    state.finish(intent).await
+}
+
+/// ## 浏览器的表单上传
+///
+/// object 的名称、令牌和文件都在 `multipart/form-data` 的请求体中，参见 [`FormUpload`]。
+/// 整个表单需要读入内存，所以文件不能超过 `server.buffering.memory_threshold`
+///
+/// 鉴权与 `PUT /{bucket}/{key}` 相同，参见 [`FormUploadAuth`]，通过之后按照令牌的身份计入签发者和令牌的限流
+#[debug_handler]
+pub(super) async fn upload_form(
+    State(state): State<ApiState>,
+    BucketPath(bucket_name): BucketPath,
+    Extension(auth): Extension<FormUploadAuth>,
+    rate_limit: Option<Extension<FormRateLimit>>,
+    headers: HeaderMap,
+    body: Result<Bytes, BytesRejection>,
+) -> HandlerResult<Response> {
+    let cx = ErrorContext::new("uploadForm").bucket(&bucket_name);
+    let body = body.map_err(ApiError::from).context(&cx)?;
+    let form = FormUpload::parse(&headers, &body).context(&cx)?;
+
+    let cx = cx.object(&form.key);
+    name::validate_object_name(&form.key).context(&cx)?;
+    logger::record_target(&bucket_name, Some(&form.key));
+
+    // 表单已经在内存中，所以总是在鉴权之前决定 content type，鉴权按照最终的类型检查
+    let head = &form.file[..form.file.len().min(sniff::SNIFF_LEN)];
+    let content_type = sniff::resolve(state.sniff, form.content_type.as_deref(), &form.key, head);
+    let identity = auth
+        .authorize(
+            form.token.as_deref(),
+            &form.path(&bucket_name),
+            form.file.len(),
+            &content_type,
+        )
+        .await
+        .context(&cx)?;
+    if let (Some(identity), Some(Extension(rate_limit))) = (&identity, &rate_limit)
+        && let Some(response) = rate_limit.acquire(identity, body.len() as u64)
+    {
+        return Ok(response);
+    }
+
+    let body = SpooledBody::from_bytes(form.file.clone());
+    let mut meta = ObjectMeta::builder()
+        .bucket_name(bucket_name.clone())
+        .object_name(form.key.clone())
+        .content_type(content_type)
+        .size(body.len())
+        .etag(body.etag())
+        .build()
+        .context(&cx)?;
+
+    let _lock = state.lock_write(&bucket_name, [form.key.as_str()]).await;
+    retention::check(&state.meta_src, &bucket_name, &form.key)
+        .await
+        .context(&cx)?;
+    let _quota = quota::check(&state, &meta).await.context(&cx)?;
+    let etag = meta.etag.clone();
+    let stored = payload::prepare(&state, &mut meta, body, None)
+        .await
+        .context(&cx)?;
+    store_object(&state, &meta, &stored, &WriteCondition::Always)
+        .await
+        .context(&cx)?;
+
+    let mut response = match form.redirect_location(&bucket_name, &etag) {
+        Some(location) => {
+            (StatusCode::SEE_OTHER, [(LOCATION, location), (ETAG, etag)]).into_response()
+        }
+        None => (form.status, [(ETAG, etag)]).into_response(),
+    };
+    // 令牌来自请求体时鉴权中间件不知道它的身份，由这里交给访问日志
+    if let Some(identity) = identity {
+        response.extensions_mut().insert(identity);
+    }
+    Ok(response)
+}
+
+/// 在服务端把 `source` 复制到 `meta` 指定的位置，数据由 [`DataEngine::copy_object`] 直接复制
+///
+/// 压缩、加密过的数据原样复制，目标 object 继承源的压缩信息和加密信息。
+/// 源 object 以客户密钥加密时请求中必须给出同一个密钥，目标 object 仍然由这个密钥加密
+///
+/// 与源 object 有关的错误本身就带有源的 bucket 和 object，所以由调用者统一附加目标的上下文
+async fn copy_object(
+    state: ApiState,
+    meta: ObjectMetaExtractor,
+    source: CopySource,
+    directive: MetadataDirective,
+    customer_key: Option<&crypto::CustomerKey>,
+) -> crab_vault::engine::error::EngineResult<StatusCode> {
+    let src_meta = state
+        .meta_src
+        .read_object_meta(&source.bucket_name, &source.object_name)
+        .await?;
+    src_meta.check_expiry(clock::now())?;
+    src_meta.check_customer_key(customer_key)?;
+    let encryption = src_meta.encryption()?;
+
+    // 标签与用户元数据一样，替换时使用这个请求中的，也就是没有标签
+    let (content_type, user_meta, tags) = match directive {
+        MetadataDirective::Copy => (src_meta.content_type, src_meta.user_meta, src_meta.tags),
+        MetadataDirective::Replace => (
+            meta.content_type
+                .unwrap_or_else(|| builder::DEFAULT_CONTENT_TYPE.to_string()),
+            meta.user_meta,
+            Default::default(),
+        ),
+    };
+
+    let mut dst_meta = ObjectMeta::builder()
+        .bucket_name(meta.bucket_name)
+        .object_name(meta.object_name)
+        .size(src_meta.size)
+        .etag(src_meta.etag)
+        .content_type(content_type)
+        .user_meta(user_meta)
+        .tags(tags)
+        .expires_at(meta.expires_at)
+        .compression(src_meta.compression)
+        .build()?;
+    // 替换了 user meta 时也要保留数据密钥，否则复制出的数据无法解密
+    dst_meta.set_encryption(encryption.as_ref())?;
+    retention::check(&state.meta_src, &dst_meta.bucket_name, &dst_meta.object_name).await?;
+    let _quota = quota::check(&state, &dst_meta).await?;
+
+    let copy = || {
+        state.data_src.copy_object(
+            &source.bucket_name,
+            &source.object_name,
+            &dst_meta.bucket_name,
+            &dst_meta.object_name,
+        )
+    };
+
+    let intent = state.begin(Intent::put(&dst_meta)).await?;
+    match copy().await {
+        Err(EngineError::BucketNotFound { bucket: _ }) => {
+            state.data_src.create_bucket(&dst_meta.bucket_name).await?;
+            copy().await?;
+        }
+        other => other?,
+    }
+
+    state.meta_src.create_object_meta(&dst_meta).await?;
+    state.finish(intent).await?;
 
     Ok(StatusCode::CREATED)
 }
@@ -117,52 +566,212 @@ pub(super) async fn upload_object(
 #[debug_handler]
 pub(super) async fn get_object(
     State(state): State<ApiState>,
-    Path((bucket_name, object_name)): Path<(String, String)>,
-) -> EngineResult<ObjectResponse> {
+    ObjectPath(bucket_name, object_name): ObjectPath,
+    CustomerKeyExtractor(customer_key): CustomerKeyExtractor,
+    headers: HeaderMap,
+) -> HandlerResult<ObjectResponse> {
+    let cx = ErrorContext::new("getObject")
+        .bucket(&bucket_name)
+        .object(&object_name);
+    let lock = state.lock_read(&bucket_name, &object_name).await;
     let meta = state
         .meta_src
         .read_object_meta(&bucket_name, &object_name)
-        .await?;
+        .await
+        .context(&cx)?;
+    meta.check_expiry(clock::now()).context(&cx)?;
+    meta.check_customer_key(customer_key.as_ref()).context(&cx)?;
+
+    // 客户端接受保存时使用的压缩算法时直接返回压缩过的数据，省去解压
+    let encoded = meta
+        .compression
+        .filter(|v| compression::accepts(&headers, v.codec));
+
+    // 不需要解密、解压时，数据不经过内存，直接从后端逐块发送给客户端
+    if meta.encryption().context(&cx)?.is_none()
+        && (meta.compression.is_none() || encoded.is_some())
+    {
+        let reader = state
+            .data_src
+            .read_object_reader(&bucket_name, &object_name)
+            .await
+            .context(&cx)?;
+        let len = encoded.map_or(meta.size, |v| v.stored_size);
+        let response = ObjectResponse::reader(meta, lock.hold_while_reading(reader), len);
+        return Ok(match encoded {
+            Some(compression) => response.encoded(compression.codec),
+            None => response,
+        });
+    }
 
     let data = state
         .data_src
         .read_object(&bucket_name, &object_name)
-        .await?;
+        .await
+        .context(&cx)?;
+    // 通过了上面的检查，给出了客户密钥时 object 一定是由它加密的
+    let key_ring = match &customer_key {
+        Some(customer_key) => Some(customer_key.key_ring()),
+        None => state.key_ring.as_ref(),
+    };
+    let data = payload::decrypt(key_ring, &meta, data)
+        .await
+        .context(&cx)?;
+
+    if let Some(compression) = encoded {
+        return Ok(ObjectResponse::new(meta, data).encoded(compression.codec));
+    }
 
+    let data = compression::decode(&meta, data).await.context(&cx)?;
     Ok(ObjectResponse::new(meta, data))
 }
 
 #[debug_handler]
 pub(super) async fn head_object(
     State(state): State<ApiState>,
-    Path((bucket_name, object_name)): Path<(String, String)>,
-) -> EngineResult<ObjectResponse> {
+    ObjectPath(bucket_name, object_name): ObjectPath,
+    CustomerKeyExtractor(customer_key): CustomerKeyExtractor,
+) -> HandlerResult<ObjectResponse> {
+    let cx = ErrorContext::new("headObject")
+        .bucket(&bucket_name)
+        .object(&object_name);
     let meta = state
         .meta_src
         .read_object_meta(&bucket_name, &object_name)
-        .await?;
+        .await
+        .context(&cx)?;
+    meta.check_expiry(clock::now()).context(&cx)?;
+    meta.check_customer_key(customer_key.as_ref()).context(&cx)?;
 
     Ok(ObjectResponse::meta_only(meta))
 }
 
+/// `PATCH /{bucket}/{object}`，带有 `?append` 或者 `Content-Range` 时修改 object 的内容，否则修改元数据
+pub(super) async fn patch_object(State(state): State<ApiState>, req: Request) -> Response {
+    if ContentPatch::requested(req.uri().query(), req.headers()) {
+        patch_object_content.call(req, state).await
+    } else {
+        patch_object_meta.call(req, state).await
+    }
+}
+
+/// 追加到 object 的末尾，或者覆盖其中的一段，其余的数据和元数据保持不变
+///
+/// 写入之后读出整个 object 重新计算 etag，同一个 object 上并发的修改依次进行但是先后顺序不确定，
+/// 需要在已知的内容上修改时带上 `If-Match`，etag 已经改变的修改会被拒绝
+#[debug_handler]
+pub(super) async fn patch_object_content(
+    State(state): State<ApiState>,
+    ObjectPath(bucket_name, object_name): ObjectPath,
+    RawQuery(query): RawQuery,
+    condition: WriteCondition,
+    headers: HeaderMap,
+    body: SpooledBody,
+) -> HandlerResult<Response> {
+    let cx = ErrorContext::new("patchObjectContent")
+        .bucket(&bucket_name)
+        .object(&object_name);
+    let patch = ContentPatch::from_request(query.as_deref(), &headers).context(&cx)?;
+    if condition == WriteCondition::IfAbsent {
+        return Err(ApiError::Client(ClientError::UnsupportedPrecondition)).context(&cx);
+    }
+
+    let _lock = state.lock_write(&bucket_name, [object_name.as_str()]).await;
+    let read = state
+        .meta_src
+        .read_object_meta(&bucket_name, &object_name)
+        .await;
+    let mut meta = condition.check(read).context(&cx)?;
+    meta.check_expiry(clock::now()).context(&cx)?;
+    meta.check_retention(clock::now()).context(&cx)?;
+    patch::check_patchable(&meta).context(&cx)?;
+
+    let data = body.to_bytes().await.context(&cx)?;
+    let offset = patch
+        .offset(meta.size, data.len() as u64)
+        .context(&cx)?;
+
+    // 按照写入之后的大小检查配额
+    let mut patched = meta.clone();
+    patched.size = meta.size.max(offset + data.len() as u64);
+    let _quota = quota::check(&state, &patched).await.context(&cx)?;
+
+    // 新的 etag 要等到写入之后才知道，所以记录的是原来的元数据，
+    // 中途失败时重放会发现元数据与数据不符，并根据数据更新元数据
+    let intent = state.begin(Intent::put(&meta)).await.context(&cx)?;
+    match patch {
+        ContentPatch::Append => state
+            .data_src
+            .append_object(&bucket_name, &object_name, &data)
+            .await
+            .map(|_| ()),
+        ContentPatch::Range { .. } => {
+            state
+                .data_src
+                .write_object_at(&bucket_name, &object_name, offset, &data)
+                .await
+        }
+    }
+    .context(&cx)?;
+
+    let reader = state
+        .data_src
+        .read_object_reader(&bucket_name, &object_name)
+        .await
+        .context(&cx)?;
+    (meta.size, meta.etag) = patch::digest(reader, &format!("/{bucket_name}/{object_name}"))
+        .await
+        .context(&cx)?;
+    state.write_meta(&meta, &condition).await.context(&cx)?;
+    state
+        .meta_src
+        .touch_object(&bucket_name, &object_name)
+        .await
+        .context(&cx)?;
+    state.finish(intent).await.context(&cx)?;
+
+    Ok((StatusCode::NO_CONTENT, [(ETAG, meta.etag)]).into_response())
+}
+
+/// 元数据的修改不改变 etag，所以 `If-Match` 只能排除同时修改了内容的写入者
 #[debug_handler]
 pub(super) async fn patch_object_meta(
     State(state): State<ApiState>,
-    Path((bucket_name, object_name)): Path<(String, String)>,
+    ObjectPath(bucket_name, object_name): ObjectPath,
+    condition: WriteCondition,
     new_meta: ObjectMetaExtractor,
-) -> EngineResult<StatusCode> {
-    let mut old_meta = state
+) -> HandlerResult<StatusCode> {
+    let cx = ErrorContext::new("patchObjectMeta")
+        .bucket(&bucket_name)
+        .object(&object_name);
+    if condition == WriteCondition::IfAbsent {
+        return Err(ApiError::Client(ClientError::UnsupportedPrecondition)).context(&cx);
+    }
+
+    let _lock = state.lock_write(&bucket_name, [object_name.as_str()]).await;
+    let read = state
         .meta_src
         .read_object_meta(&bucket_name, &object_name)
-        .await?;
+        .await;
+    let mut old_meta = condition.check(read).context(&cx)?;
+    old_meta.check_expiry(clock::now()).context(&cx)?;
 
-    old_meta.user_meta = merge_json_object(new_meta.user_meta, old_meta.user_meta)?;
+    old_meta.user_meta =
+        merge_json_object(new_meta.user_meta, old_meta.user_meta).context(&cx)?;
+    // 给出了过期时间时替换原有的，这样可以延长或者提前 object 的过期
+    if new_meta.expires_at.is_some() {
+        old_meta.expires_at = new_meta.expires_at;
+    }
 
-    state.meta_src.create_object_meta(&old_meta).await?;
+    state
+        .write_meta(&old_meta, &condition)
+        .await
+        .context(&cx)?;
     state
         .meta_src
         .touch_object(&bucket_name, &object_name)
-        .await?;
+        .await
+        .context(&cx)?;
 
     Ok(StatusCode::OK)
 }
@@ -170,33 +779,978 @@ pub(super) async fn patch_object_meta(
 #[debug_handler]
 pub(super) async fn delete_object(
     State(state): State<ApiState>,
-    Path((bucket_name, object_name)): Path<(String, String)>,
-) -> EngineResult<StatusCode> {
+    ObjectPath(bucket_name, object_name): ObjectPath,
+) -> HandlerResult<StatusCode> {
+    let cx = ErrorContext::new("deleteObject")
+        .bucket(&bucket_name)
+        .object(&object_name);
+    let _lock = state.lock_write(&bucket_name, [object_name.as_str()]).await;
+    retention::check(&state.meta_src, &bucket_name, &object_name)
+        .await
+        .context(&cx)?;
+    trash::keep(&state, &bucket_name, &object_name)
+        .await
+        .context(&cx)?;
+
     // 原子地删除数据和元数据
+    let objects = std::slice::from_ref(&object_name);
+    let intent = state
+        .begin(Intent::delete(&bucket_name, objects))
+        .await
+        .context(&cx)?;
     state
         .data_src
         .delete_object(&bucket_name, &object_name)
-        .await?;
+        .await
+        .context(&cx)?;
 
     state
         .meta_src
         .delete_object_meta(&bucket_name, &object_name)
-        .await?;
+        .await
+        .context(&cx)?;
+    state.finish(intent).await.context(&cx)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// `POST /{bucket}/{object}?restore[&id=...]`，把回收站中的 object 恢复到原来的位置，参见 `GET /{bucket}?trash`
+#[debug_handler]
+pub(super) async fn restore_object(
+    State(state): State<ApiState>,
+    ObjectPath(bucket_name, object_name): ObjectPath,
+    RawQuery(raw_query): RawQuery,
+    Query(query): Query<RestoreQuery>,
+) -> HandlerResult<Response> {
+    let cx = ErrorContext::new("restoreObject")
+        .bucket(&bucket_name)
+        .object(&object_name);
+    if !has_query_key(raw_query.as_deref(), RESTORE_QUERY_KEY) {
+        return Err(ApiError::Client(ClientError::UriInvalid)).context(&cx);
+    }
+
+    let _lock = state.lock_write(&bucket_name, [object_name.as_str()]).await;
+    let meta = trash::restore(&state, &bucket_name, &object_name, query.id.as_deref())
+        .await
+        .context(&cx)?;
+
+    Ok((StatusCode::OK, [(ETAG, meta.etag)]).into_response())
+}
+
+// --- Object Tagging Handlers ---
+
+/// `PUT /{bucket}/{object}`，带有 `?tagging` 时替换 object 的标签，带有 `?retention` 时设置 object 的保留，
+/// 否则上传 object
+pub(super) async fn upload_object_or_tagging(State(state): State<ApiState>, req: Request) -> Response {
+    if has_query_key(req.uri().query(), TAGGING_QUERY_KEY) {
+        put_object_tagging.call(req, state).await
+    } else if has_query_key(req.uri().query(), RETENTION_QUERY_KEY) {
+        put_object_retention.call(req, state).await
+    } else {
+        upload_object.call(req, state).await
+    }
+}
+
+/// `GET /{bucket}/{object}`，带有 `?tagging` 时返回 object 的标签，带有 `?retention` 时返回 object 的保留，
+/// 否则读取 object
+pub(super) async fn get_object_or_tagging(State(state): State<ApiState>, req: Request) -> Response {
+    if has_query_key(req.uri().query(), TAGGING_QUERY_KEY) {
+        get_object_tagging.call(req, state).await
+    } else if has_query_key(req.uri().query(), RETENTION_QUERY_KEY) {
+        get_object_retention.call(req, state).await
+    } else {
+        get_object.call(req, state).await
+    }
+}
+
+/// `DELETE /{bucket}/{object}`，带有 `?tagging` 时删除 object 的所有标签，否则删除 object
+pub(super) async fn delete_object_or_tagging(State(state): State<ApiState>, req: Request) -> Response {
+    if has_query_key(req.uri().query(), TAGGING_QUERY_KEY) {
+        delete_object_tagging.call(req, state).await
+    } else {
+        delete_object.call(req, state).await
+    }
+}
+
+/// 替换 object 的所有标签，不会改变 `updated_at`
+#[debug_handler]
+pub(super) async fn put_object_tagging(
+    State(state): State<ApiState>,
+    ObjectPath(bucket_name, object_name): ObjectPath,
+    RestrictedBytes(body): RestrictedBytes,
+) -> HandlerResult<StatusCode> {
+    let cx = ErrorContext::new("putObjectTagging")
+        .bucket(&bucket_name)
+        .object(&object_name);
+    let tagging = tagging::from_body(&body).context(&cx)?;
+
+    let _lock = state.lock_write(&bucket_name, [object_name.as_str()]).await;
+    let mut meta = state
+        .meta_src
+        .read_object_meta(&bucket_name, &object_name)
+        .await
+        .context(&cx)?;
+    meta.check_expiry(clock::now()).context(&cx)?;
+    meta.tags = tagging.tags;
+    state.meta_src.create_object_meta(&meta).await.context(&cx)?;
 
     Ok(StatusCode::NO_CONTENT)
 }
 
+#[debug_handler]
+pub(super) async fn get_object_tagging(
+    State(state): State<ApiState>,
+    ObjectPath(bucket_name, object_name): ObjectPath,
+) -> HandlerResult<Response> {
+    let cx = ErrorContext::new("getObjectTagging")
+        .bucket(&bucket_name)
+        .object(&object_name);
+    let meta = state
+        .meta_src
+        .read_object_meta(&bucket_name, &object_name)
+        .await
+        .context(&cx)?;
+    meta.check_expiry(clock::now()).context(&cx)?;
+
+    let tagging = crab_vault::engine::tagging::Tagging { tags: meta.tags };
+    Ok((StatusCode::OK, axum::Json(tagging)).into_response())
+}
+
+/// 删除 object 的所有标签，没有标签时什么都不做
+#[debug_handler]
+pub(super) async fn delete_object_tagging(
+    State(state): State<ApiState>,
+    ObjectPath(bucket_name, object_name): ObjectPath,
+) -> HandlerResult<StatusCode> {
+    let cx = ErrorContext::new("deleteObjectTagging")
+        .bucket(&bucket_name)
+        .object(&object_name);
+    let _lock = state.lock_write(&bucket_name, [object_name.as_str()]).await;
+    let mut meta = state
+        .meta_src
+        .read_object_meta(&bucket_name, &object_name)
+        .await
+        .context(&cx)?;
+    meta.check_expiry(clock::now()).context(&cx)?;
+
+    if !meta.tags.is_empty() {
+        meta.tags.clear();
+        state.meta_src.create_object_meta(&meta).await.context(&cx)?;
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// --- Object Retention Handlers ---
+
+/// 替换 object 的保留设置，保留期只能延长，解除合法保留需要令牌授予 [`LegalHold`](crab_vault::auth::Capability::LegalHold)
+#[debug_handler]
+pub(super) async fn put_object_retention(
+    State(state): State<ApiState>,
+    ObjectPath(bucket_name, object_name): ObjectPath,
+    PermissionExtractor(permission): PermissionExtractor,
+    RestrictedBytes(body): RestrictedBytes,
+) -> HandlerResult<StatusCode> {
+    let cx = ErrorContext::new("putObjectRetention")
+        .bucket(&bucket_name)
+        .object(&object_name);
+    let now = clock::now();
+    let new = retention::from_body(&body, now).context(&cx)?;
+
+    let _lock = state.lock_write(&bucket_name, [object_name.as_str()]).await;
+    let mut meta = state
+        .meta_src
+        .read_object_meta(&bucket_name, &object_name)
+        .await
+        .context(&cx)?;
+    meta.check_expiry(now).context(&cx)?;
+
+    if meta.retention.shortened_by(&new, now) {
+        return Err(EngineError::ObjectLocked {
+            bucket: bucket_name,
+            object: object_name,
+        })
+        .context(&cx);
+    }
+    retention::admit_release(&permission, &bucket_name, &object_name, &meta.retention, &new)
+        .context(&cx)?;
+    meta.retention = new;
+    state.meta_src.create_object_meta(&meta).await.context(&cx)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[debug_handler]
+pub(super) async fn get_object_retention(
+    State(state): State<ApiState>,
+    ObjectPath(bucket_name, object_name): ObjectPath,
+) -> HandlerResult<Response> {
+    let cx = ErrorContext::new("getObjectRetention")
+        .bucket(&bucket_name)
+        .object(&object_name);
+    let meta = state
+        .meta_src
+        .read_object_meta(&bucket_name, &object_name)
+        .await
+        .context(&cx)?;
+
+    Ok((StatusCode::OK, axum::Json(meta.retention)).into_response())
+}
+
+/// `POST /{bucket}`，请求体是 `multipart/form-data` 时是表单上传，否则是批量删除
+pub(super) async fn delete_objects_or_upload(State(state): State<ApiState>, req: Request) -> Response {
+    if is_form_upload(req.uri().query(), req.headers()) {
+        upload_form.call(req, state).await
+    } else {
+        delete_objects.call(req, state).await
+    }
+}
+
+/// 批量删除一个 bucket 下的 object，需要带有 `?delete`
+#[debug_handler]
+pub(super) async fn delete_objects(
+    State(state): State<ApiState>,
+    BucketPath(bucket_name): BucketPath,
+    RawQuery(query): RawQuery,
+    PermissionExtractor(permission): PermissionExtractor,
+    RestrictedBytes(body): RestrictedBytes,
+) -> HandlerResult<Response> {
+    let cx = ErrorContext::new("deleteObjects").bucket(&bucket_name);
+    if !batch::is_delete_query(query.as_deref()) {
+        return Err(ApiError::Client(ClientError::UriInvalid)).context(&cx);
+    }
+
+    let request = DeleteObjectsRequest::from_body(&body).context(&cx)?;
+    let mut result = DeleteObjectsResult::default();
+    let objects = result.admit(&bucket_name, request.objects, &permission);
+
+    // 保留中的 object 不会被删除；开启了回收站时，没能放进回收站的 object 也不会被删除
+    let _lock = state
+        .lock_write(&bucket_name, objects.iter().map(String::as_str))
+        .await;
+    let results = retention::check_all(&state.meta_src, &bucket_name, &objects).await;
+    let objects = result.record(objects, results);
+    let results = trash::keep_all(&state, &bucket_name, &objects).await;
+    let objects = result.record(objects, results);
+
+    // 与单个删除一样，先删除数据，数据删除成功后再删除元数据
+    // 有任何一个删除失败时都留下记录，下次启动时完成剩下的删除
+    let denied = result.errors.len();
+    let intent = state
+        .begin(Intent::delete(&bucket_name, &objects))
+        .await
+        .context(&cx)?;
+    let results = state.data_src.delete_objects(&bucket_name, &objects).await;
+    let objects = result.record(objects, results);
+
+    let results = state
+        .meta_src
+        .delete_objects_meta(&bucket_name, &objects)
+        .await;
+    result.deleted = result.record(objects, results);
+    if result.errors.len() == denied {
+        state.finish(intent).await.context(&cx)?;
+    }
+
+    Ok((StatusCode::OK, axum::Json(result)).into_response())
+}
+
 #[debug_handler]
 pub(super) async fn list_objects_meta(
     State(state): State<ApiState>,
-    Path(bucket_name): Path<String>,
-) -> EngineResult<Response> {
-    let res = state.meta_src.list_objects_meta(&bucket_name).await?;
+    BucketPath(bucket_name): BucketPath,
+    Query(query): Query<list::ListObjectsQuery>,
+    headers: HeaderMap,
+) -> HandlerResult<Response> {
+    let cx = ErrorContext::new("listObjects").bucket(&bucket_name);
+    let format = ResponseFormat::negotiate(&headers);
+    // ndjson 不分页，逐条返回所有满足前缀和时间条件的 object
+    if format == ResponseFormat::Ndjson {
+        let (sender, response) = NdjsonResponse::channel();
+        tokio::spawn(async move {
+            let stream = state
+                .meta_src
+                .stream_objects_meta(&bucket_name, &query)
+                .map_ok(ObjectMeta::hide_reserved_meta);
+            sender.send_all(stream).await;
+        });
+        return Ok(response.into_response());
+    }
+
+    let mut res = state
+        .meta_src
+        .list_objects_meta_page(&bucket_name, &query)
+        .await
+        .context(&cx)?;
+    res.objects = res
+        .objects
+        .into_iter()
+        .map(ObjectMeta::hide_reserved_meta)
+        .collect();
 
-    Ok((StatusCode::OK, axum::Json(res)).into_response())
+    let headers = skipped_entries_headers(&res.skipped);
+    if format == ResponseFormat::Xml {
+        return Ok((headers, xml::list_objects(&bucket_name, &query, &res)).into_response());
+    }
+    Ok((StatusCode::OK, headers, axum::Json(res)).into_response())
+}
+
+/// `GET /{bucket}?summary&prefix=...`，一次返回某个前缀下的 object 个数、总大小、修改时间的范围，
+/// 以及请求携带的令牌对这个前缀的权限。与列出 object 一样需要列出这个前缀的权限
+///
+/// 只使用 `prefix` 参数，其他列出 object 的参数都会被忽略
+#[debug_handler]
+pub(super) async fn summarize_prefix(
+    State(state): State<ApiState>,
+    BucketPath(bucket_name): BucketPath,
+    Query(query): Query<list::ListObjectsQuery>,
+    PermissionExtractor(permission): PermissionExtractor,
+    approved: Option<Extension<ApprovedByPathRule>>,
+) -> HandlerResult<Response> {
+    let cx = ErrorContext::new("summarizePrefix").bucket(&bucket_name);
+    state
+        .meta_src
+        .read_bucket_meta(&bucket_name)
+        .await
+        .context(&cx)?;
+
+    let prefix = query.prefix.unwrap_or_default();
+    let mut summary = PrefixSummary::new(&bucket_name, &prefix);
+
+    let query = list::ListObjectsQuery {
+        prefix: Some(prefix),
+        ..Default::default()
+    };
+    let mut stream = state.meta_src.stream_objects_meta(&bucket_name, &query);
+    while let Some(meta) = stream.try_next().await.context(&cx)? {
+        summary.add(&meta);
+    }
+
+    if permission.authenticated() && approved.is_none() {
+        summary.access = Some(PrefixAccess::of(&permission, &bucket_name, &summary.prefix));
+    }
+
+    Ok((StatusCode::OK, axum::Json(summary)).into_response())
+}
+
+/// `GET /{bucket}?usage`，返回 bucket 中 object 的个数、总大小，以及设置了的配额
+#[debug_handler]
+pub(super) async fn bucket_usage(
+    State(state): State<ApiState>,
+    BucketPath(bucket_name): BucketPath,
+) -> HandlerResult<Response> {
+    let cx = ErrorContext::new("bucketUsage").bucket(&bucket_name);
+    let meta = state
+        .meta_src
+        .read_bucket_meta(&bucket_name)
+        .await
+        .context(&cx)?;
+    let usage = state
+        .meta_src
+        .bucket_usage(&bucket_name)
+        .await
+        .context(&cx)?;
+
+    Ok((StatusCode::OK, axum::Json(UsageReport::new(meta, usage))).into_response())
+}
+
+/// `GET /{bucket}?trash`，列出 bucket 的回收站中的 object，最近删除的在前
+#[debug_handler]
+pub(super) async fn list_trash(
+    State(state): State<ApiState>,
+    BucketPath(bucket_name): BucketPath,
+) -> HandlerResult<Response> {
+    let cx = ErrorContext::new("listTrash").bucket(&bucket_name);
+    let list = trash::list(&state, &bucket_name).await.context(&cx)?;
+
+    Ok((StatusCode::OK, axum::Json(list)).into_response())
+}
+
+// --- Bucket Policy Handlers ---
+
+/// `PUT /{bucket}`，带有 `?policy` 时设置 bucket 策略，带有 `?lifecycle` 时设置生命周期规则，
+/// 带有 `?inventory` 时设置清单，带有 `?cors` 时设置跨域规则，否则创建 bucket
+pub(super) async fn create_bucket_or_policy(
+    State(state): State<ApiState>,
+    req: Request,
+) -> Response {
+    let query = req.uri().query();
+    if has_query_key(query, POLICY_QUERY_KEY) {
+        put_bucket_policy.call(req, state).await
+    } else if has_query_key(query, LIFECYCLE_QUERY_KEY) {
+        put_bucket_lifecycle.call(req, state).await
+    } else if has_query_key(query, INVENTORY_QUERY_KEY) {
+        put_bucket_inventory.call(req, state).await
+    } else if has_query_key(query, CORS_QUERY_KEY) {
+        put_bucket_cors.call(req, state).await
+    } else {
+        create_bucket.call(req, state).await
+    }
+}
+
+/// `GET /{bucket}`，带有 `?policy` 时返回 bucket 策略，带有 `?lifecycle` 时返回生命周期规则，带有 `?inventory` 时返回清单配置，
+/// 带有 `?cors` 时返回跨域规则，
+/// 带有 `?summary` 时返回前缀的概况，带有 `?usage` 时返回 bucket 的用量，带有 `?trash` 时列出回收站，否则列出 object
+pub(super) async fn list_objects_or_policy(
+    State(state): State<ApiState>,
+    req: Request,
+) -> Response {
+    let query = req.uri().query();
+    if has_query_key(query, POLICY_QUERY_KEY) {
+        get_bucket_policy.call(req, state).await
+    } else if has_query_key(query, LIFECYCLE_QUERY_KEY) {
+        get_bucket_lifecycle.call(req, state).await
+    } else if has_query_key(query, INVENTORY_QUERY_KEY) {
+        get_bucket_inventory.call(req, state).await
+    } else if has_query_key(query, CORS_QUERY_KEY) {
+        get_bucket_cors.call(req, state).await
+    } else if has_query_key(query, SUMMARY_QUERY_KEY) {
+        summarize_prefix.call(req, state).await
+    } else if has_query_key(query, USAGE_QUERY_KEY) {
+        bucket_usage.call(req, state).await
+    } else if has_query_key(query, TRASH_QUERY_KEY) {
+        list_trash.call(req, state).await
+    } else {
+        list_objects_meta.call(req, state).await
+    }
+}
+
+/// `DELETE /{bucket}`，带有 `?policy` 时删除 bucket 策略，带有 `?lifecycle` 时删除生命周期规则，
+/// 带有 `?inventory` 时删除清单配置，带有 `?cors` 时删除跨域规则，带有 `?force=true` 时连同其中的 object 一起删除 bucket，否则删除 bucket
+pub(super) async fn delete_bucket_or_policy(
+    State(state): State<ApiState>,
+    req: Request,
+) -> Response {
+    let query = req.uri().query();
+    if has_query_key(query, POLICY_QUERY_KEY) {
+        delete_bucket_policy.call(req, state).await
+    } else if has_query_key(query, LIFECYCLE_QUERY_KEY) {
+        delete_bucket_lifecycle.call(req, state).await
+    } else if has_query_key(query, INVENTORY_QUERY_KEY) {
+        delete_bucket_inventory.call(req, state).await
+    } else if has_query_key(query, CORS_QUERY_KEY) {
+        delete_bucket_cors.call(req, state).await
+    } else if is_force_delete(query) {
+        force_delete_bucket.call(req, state).await
+    } else {
+        delete_bucket.call(req, state).await
+    }
+}
+
+#[debug_handler]
+pub(super) async fn put_bucket_policy(
+    State(state): State<ApiState>,
+    BucketPath(bucket_name): BucketPath,
+    RestrictedBytes(body): RestrictedBytes,
+) -> HandlerResult<StatusCode> {
+    let cx = ErrorContext::new("putBucketPolicy").bucket(&bucket_name);
+    let _lock = state.lock_bucket(&bucket_name).await;
+    let policy = policy::from_body(&body).context(&cx)?;
+
+    let mut meta = state
+        .meta_src
+        .read_bucket_meta(&bucket_name)
+        .await
+        .context(&cx)?;
+    meta.policy = Some(policy);
+    state.meta_src.create_bucket_meta(&meta).await.context(&cx)?;
+    state.meta_src.touch_bucket(&bucket_name).await.context(&cx)?;
+    tracing::info!(bucket = bucket_name, "bucket policy updated");
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[debug_handler]
+pub(super) async fn get_bucket_policy(
+    State(state): State<ApiState>,
+    BucketPath(bucket_name): BucketPath,
+) -> HandlerResult<Response> {
+    let cx = ErrorContext::new("getBucketPolicy").bucket(&bucket_name);
+    let policy = state
+        .meta_src
+        .read_bucket_meta(&bucket_name)
+        .await
+        .context(&cx)?
+        .policy
+        .ok_or(ApiError::Client(ClientError::NoBucketPolicy))
+        .context(&cx)?;
+
+    Ok((StatusCode::OK, axum::Json(policy)).into_response())
+}
+
+/// 删除 bucket 策略，没有设置策略时什么都不做
+#[debug_handler]
+pub(super) async fn delete_bucket_policy(
+    State(state): State<ApiState>,
+    BucketPath(bucket_name): BucketPath,
+) -> HandlerResult<StatusCode> {
+    let cx = ErrorContext::new("deleteBucketPolicy").bucket(&bucket_name);
+    let _lock = state.lock_bucket(&bucket_name).await;
+    let mut meta = state
+        .meta_src
+        .read_bucket_meta(&bucket_name)
+        .await
+        .context(&cx)?;
+
+    if meta.policy.take().is_some() {
+        state.meta_src.create_bucket_meta(&meta).await.context(&cx)?;
+        state.meta_src.touch_bucket(&bucket_name).await.context(&cx)?;
+        tracing::info!(bucket = bucket_name, "bucket policy deleted");
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// --- Bucket Lifecycle Handlers ---
+
+#[debug_handler]
+pub(super) async fn put_bucket_lifecycle(
+    State(state): State<ApiState>,
+    BucketPath(bucket_name): BucketPath,
+    RestrictedBytes(body): RestrictedBytes,
+) -> HandlerResult<StatusCode> {
+    let cx = ErrorContext::new("putBucketLifecycle").bucket(&bucket_name);
+    let _lock = state.lock_bucket(&bucket_name).await;
+    let lifecycle = lifecycle::from_body(&body).context(&cx)?;
+
+    let mut meta = state
+        .meta_src
+        .read_bucket_meta(&bucket_name)
+        .await
+        .context(&cx)?;
+    meta.lifecycle = Some(lifecycle);
+    state.meta_src.create_bucket_meta(&meta).await.context(&cx)?;
+    state.meta_src.touch_bucket(&bucket_name).await.context(&cx)?;
+    tracing::info!(bucket = bucket_name, "bucket lifecycle updated");
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[debug_handler]
+pub(super) async fn get_bucket_lifecycle(
+    State(state): State<ApiState>,
+    BucketPath(bucket_name): BucketPath,
+) -> HandlerResult<Response> {
+    let cx = ErrorContext::new("getBucketLifecycle").bucket(&bucket_name);
+    let lifecycle = state
+        .meta_src
+        .read_bucket_meta(&bucket_name)
+        .await
+        .context(&cx)?
+        .lifecycle
+        .ok_or(ApiError::Client(ClientError::NoLifecycleConfig))
+        .context(&cx)?;
+
+    Ok((StatusCode::OK, axum::Json(lifecycle)).into_response())
+}
+
+/// 删除生命周期规则，没有设置时什么都不做
+#[debug_handler]
+pub(super) async fn delete_bucket_lifecycle(
+    State(state): State<ApiState>,
+    BucketPath(bucket_name): BucketPath,
+) -> HandlerResult<StatusCode> {
+    let cx = ErrorContext::new("deleteBucketLifecycle").bucket(&bucket_name);
+    let _lock = state.lock_bucket(&bucket_name).await;
+    let mut meta = state
+        .meta_src
+        .read_bucket_meta(&bucket_name)
+        .await
+        .context(&cx)?;
+
+    if meta.lifecycle.take().is_some() {
+        state.meta_src.create_bucket_meta(&meta).await.context(&cx)?;
+        state.meta_src.touch_bucket(&bucket_name).await.context(&cx)?;
+        tracing::info!(bucket = bucket_name, "bucket lifecycle deleted");
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// --- Bucket Inventory Handlers ---
+
+#[debug_handler]
+pub(super) async fn put_bucket_inventory(
+    State(state): State<ApiState>,
+    BucketPath(bucket_name): BucketPath,
+    PermissionExtractor(permission): PermissionExtractor,
+    RestrictedBytes(body): RestrictedBytes,
+) -> HandlerResult<StatusCode> {
+    let cx = ErrorContext::new("putBucketInventory").bucket(&bucket_name);
+    let _lock = state.lock_bucket(&bucket_name).await;
+    let inventory = inventory::from_body(&body, &bucket_name).context(&cx)?;
+    inventory::admit(&permission, &inventory).context(&cx)?;
+
+    let mut meta = state
+        .meta_src
+        .read_bucket_meta(&bucket_name)
+        .await
+        .context(&cx)?;
+    meta.inventory = Some(inventory);
+    state.meta_src.create_bucket_meta(&meta).await.context(&cx)?;
+    state.meta_src.touch_bucket(&bucket_name).await.context(&cx)?;
+    tracing::info!(bucket = bucket_name, "bucket inventory updated");
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[debug_handler]
+pub(super) async fn get_bucket_inventory(
+    State(state): State<ApiState>,
+    BucketPath(bucket_name): BucketPath,
+) -> HandlerResult<Response> {
+    let cx = ErrorContext::new("getBucketInventory").bucket(&bucket_name);
+    let inventory = state
+        .meta_src
+        .read_bucket_meta(&bucket_name)
+        .await
+        .context(&cx)?
+        .inventory
+        .ok_or(ApiError::Client(ClientError::NoInventoryConfig))
+        .context(&cx)?;
+
+    Ok((StatusCode::OK, axum::Json(inventory)).into_response())
+}
+
+/// 删除清单配置，已经生成的清单不受影响，没有设置时什么都不做
+#[debug_handler]
+pub(super) async fn delete_bucket_inventory(
+    State(state): State<ApiState>,
+    BucketPath(bucket_name): BucketPath,
+) -> HandlerResult<StatusCode> {
+    let cx = ErrorContext::new("deleteBucketInventory").bucket(&bucket_name);
+    let _lock = state.lock_bucket(&bucket_name).await;
+    let mut meta = state
+        .meta_src
+        .read_bucket_meta(&bucket_name)
+        .await
+        .context(&cx)?;
+
+    if meta.inventory.take().is_some() {
+        state.meta_src.create_bucket_meta(&meta).await.context(&cx)?;
+        state.meta_src.touch_bucket(&bucket_name).await.context(&cx)?;
+        tracing::info!(bucket = bucket_name, "bucket inventory deleted");
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// `POST /admin/inventory/{bucket}`，不论距离上一次生成过了多久，立即按照 bucket 的清单配置生成一次
+#[debug_handler]
+pub(super) async fn run_inventory(
+    State(state): State<ApiState>,
+    BucketPath(bucket_name): BucketPath,
+) -> HandlerResult<Response> {
+    let cx = ErrorContext::new("runInventory").bucket(&bucket_name);
+    let config = state
+        .meta_src
+        .read_bucket_meta(&bucket_name)
+        .await
+        .context(&cx)?
+        .inventory
+        .ok_or(ApiError::Client(ClientError::NoInventoryConfig))
+        .context(&cx)?;
+
+    let report = inventory::write_inventory(&state, &bucket_name, &config)
+        .await
+        .context(&cx)?;
+    tracing::info!(
+        bucket = bucket_name,
+        object = report.object,
+        objects = report.objects,
+        "inventory generated"
+    );
+
+    Ok((StatusCode::OK, axum::Json(report)).into_response())
+}
+
+// --- Bucket CORS Handlers ---
+
+#[debug_handler]
+pub(super) async fn put_bucket_cors(
+    State(state): State<ApiState>,
+    BucketPath(bucket_name): BucketPath,
+    RestrictedBytes(body): RestrictedBytes,
+) -> HandlerResult<StatusCode> {
+    let cx = ErrorContext::new("putBucketCors").bucket(&bucket_name);
+    let _lock = state.lock_bucket(&bucket_name).await;
+    let cors = cors::from_body(&body).context(&cx)?;
+
+    let mut meta = state
+        .meta_src
+        .read_bucket_meta(&bucket_name)
+        .await
+        .context(&cx)?;
+    meta.cors = Some(cors);
+    state.meta_src.create_bucket_meta(&meta).await.context(&cx)?;
+    state.meta_src.touch_bucket(&bucket_name).await.context(&cx)?;
+    tracing::info!(bucket = bucket_name, "bucket cors rules updated");
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[debug_handler]
+pub(super) async fn get_bucket_cors(
+    State(state): State<ApiState>,
+    BucketPath(bucket_name): BucketPath,
+) -> HandlerResult<Response> {
+    let cx = ErrorContext::new("getBucketCors").bucket(&bucket_name);
+    let cors = state
+        .meta_src
+        .read_bucket_meta(&bucket_name)
+        .await
+        .context(&cx)?
+        .cors
+        .ok_or(ApiError::Client(ClientError::NoCorsConfig))
+        .context(&cx)?;
+
+    Ok((StatusCode::OK, axum::Json(cors)).into_response())
+}
+
+/// 删除跨域规则，之后这个 bucket 使用 `server.cors`，没有设置时什么都不做
+#[debug_handler]
+pub(super) async fn delete_bucket_cors(
+    State(state): State<ApiState>,
+    BucketPath(bucket_name): BucketPath,
+) -> HandlerResult<StatusCode> {
+    let cx = ErrorContext::new("deleteBucketCors").bucket(&bucket_name);
+    let _lock = state.lock_bucket(&bucket_name).await;
+    let mut meta = state
+        .meta_src
+        .read_bucket_meta(&bucket_name)
+        .await
+        .context(&cx)?;
+
+    if meta.cors.take().is_some() {
+        state.meta_src.create_bucket_meta(&meta).await.context(&cx)?;
+        state.meta_src.touch_bucket(&bucket_name).await.context(&cx)?;
+        tracing::info!(bucket = bucket_name, "bucket cors rules deleted");
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// --- Admin Handlers ---
+
+/// 吊销一个令牌，能否访问这个接口与其他接口一样由令牌中的方法和路径决定
+#[debug_handler]
+pub(super) async fn revoke_token(
+    State(state): State<ApiState>,
+    RestrictedBytes(body): RestrictedBytes,
+) -> HandlerResult<StatusCode> {
+    let cx = ErrorContext::new("revokeToken");
+    let RevokeTokenRequest { jti, expires_at } = serde_json::from_slice(&body)
+        .map_err(ApiError::from)
+        .context(&cx)?;
+
+    state
+        .revocations
+        .revoke(jti, expires_at.unwrap_or(i64::MAX))
+        .context(&cx)?;
+    tracing::info!(%jti, "token revoked");
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// 当前的路径规则，包括通过 `PUT /admin/path-rules` 修改之后的结果
+#[debug_handler]
+pub(super) async fn list_path_rules(State(state): State<ApiState>) -> Response {
+    let rules = state
+        .path_rules
+        .current()
+        .iter()
+        .map(PathRule::to_static)
+        .collect::<Vec<_>>();
+
+    (StatusCode::OK, axum::Json(rules)).into_response()
+}
+
+/// 整体替换路径规则，之后的请求立即使用新的规则，不需要重启服务
+#[debug_handler]
+pub(super) async fn replace_path_rules(
+    State(state): State<ApiState>,
+    RestrictedBytes(body): RestrictedBytes,
+) -> HandlerResult<StatusCode> {
+    let cx = ErrorContext::new("replacePathRules");
+    let rules: Vec<StaticPathRule> = serde_json::from_slice(&body)
+        .map_err(ApiError::from)
+        .context(&cx)?;
+
+    let count = rules.len();
+    state.path_rules.replace(rules).context(&cx)?;
+    tracing::info!(count, "path rules replaced");
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// ## 读取变更事件
+///
+/// 没有新事件时每隔 [`EVENTS_POLL_INTERVAL`] 重新读取一次，直到出现新事件或者等待了 `wait` 秒。
+/// 内部 bucket 的事件不会返回，但是 `last-seq` 会越过它们
+#[debug_handler]
+pub(super) async fn list_events(
+    State(state): State<ApiState>,
+    Query(query): Query<EventsQuery>,
+) -> HandlerResult<Response> {
+    let cx = ErrorContext::new("listEvents");
+    if !state.meta_src.records_events() {
+        return Err(ApiError::Client(ClientError::NoEventLog)).context(&cx);
+    }
+
+    let limit = query
+        .limit
+        .unwrap_or(events::MAX_EVENTS_PER_PAGE)
+        .clamp(1, events::MAX_EVENTS_PER_PAGE);
+    let deadline =
+        tokio::time::Instant::now() + std::time::Duration::from_secs(query.wait.min(MAX_EVENTS_WAIT));
+
+    let mut last_seq = query.since;
+    let events = loop {
+        let mut events = state
+            .meta_src
+            .list_events(last_seq, limit)
+            .await
+            .context(&cx)?;
+        let exhausted = events.is_empty();
+        last_seq = events.last().map_or(last_seq, |v| v.seq);
+        events.retain(|v| !name::is_internal_bucket(&v.bucket));
+
+        let now = tokio::time::Instant::now();
+        if !events.is_empty() || now >= deadline {
+            break events;
+        }
+        // 读到的都是内部 bucket 的事件时立即继续读取
+        if exhausted {
+            tokio::time::sleep(EVENTS_POLL_INTERVAL.min(deadline - now)).await;
+        }
+    };
+
+    Ok((StatusCode::OK, axum::Json(EventPage { events, last_seq })).into_response())
+}
+
+/// 所有 bucket 的对象个数和总大小，内部的 bucket 与列出 bucket 时一样不可见
+#[debug_handler]
+pub(super) async fn storage_stats(State(state): State<ApiState>) -> HandlerResult<Response> {
+    let cx = ErrorContext::new("storageStats");
+    let stats = state.meta_src.stats().await.context(&cx)?;
+    let buckets = stats
+        .buckets
+        .into_iter()
+        .filter(|v| !name::is_internal_bucket(&v.name))
+        .collect();
+
+    Ok((StatusCode::OK, axum::Json(usage::StorageStats::new(buckets))).into_response())
 }
 
 #[debug_handler]
 pub(super) async fn health() -> Response {
     StatusCode::NO_CONTENT.into_response()
 }
+
+/// 读取内部 bucket 中一个不存在的 object，后端可以访问时只会得到 `NotFound`
+#[debug_handler]
+pub(super) async fn readiness(State(state): State<AdminState>) -> Response {
+    let bucket = name::internal_bucket();
+    let data = match state.data_src.read_object(bucket, ".readyz").await {
+        Ok(_)
+        | Err(EngineError::BucketNotFound { .. })
+        | Err(EngineError::ObjectNotFound { .. }) => None,
+        Err(e) => Some(e.to_string()),
+    };
+    let meta = match state.meta_src.read_bucket_meta(bucket).await {
+        Ok(_) | Err(EngineError::BucketMetaNotFound { .. }) => None,
+        Err(e) => Some(e.to_string()),
+    };
+
+    let ready = data.is_none() && meta.is_none();
+    let status = match ready {
+        true => StatusCode::OK,
+        false => {
+            tracing::warn!(data, meta, "the server is not ready");
+            StatusCode::SERVICE_UNAVAILABLE
+        }
+    };
+
+    (status, axum::Json(Readiness { ready, data, meta })).into_response()
+}
+
+/// 正在使用的配置，包括命令行参数的覆盖和重新加载的结果，密钥、密码和凭证已经被隐去
+#[debug_handler]
+pub(super) async fn dump_config(State(state): State<AdminState>) -> Response {
+    let config = state.config.current();
+    (StatusCode::OK, axum::Json(config.as_ref())).into_response()
+}
+
+/// 立即重新加载配置文件，配置文件无效时不做任何修改
+#[debug_handler]
+pub(super) async fn reload_config(
+    State(state): State<AdminState>,
+) -> HandlerResult<Response> {
+    let cx = ErrorContext::new("reloadConfig");
+    let Some(reloader) = &state.reloader else {
+        return Err(ApiError::Client(ClientError::NoConfigFile)).context(&cx);
+    };
+
+    let report = reloader
+        .lock()
+        .await
+        .reload()
+        .map_err(|reason| ApiError::Client(ClientError::InvalidConfig { reason }))
+        .context(&cx)?;
+
+    Ok((StatusCode::OK, axum::Json(report)).into_response())
+}
+
+/// ## 修改所有日志输出的级别
+///
+/// 与重新加载配置文件中的 `logger.level`、`logger.filter` 效果相同，但不修改配置文件，
+/// 所以配置文件中的这两项再次变化之前一直有效。日志文件仍然使用 `logger.dump_level`，导出的 span 不受影响
+#[debug_handler]
+pub(super) async fn set_log_level(
+    RestrictedBytes(body): RestrictedBytes,
+) -> HandlerResult<Response> {
+    let cx = ErrorContext::new("setLogLevel");
+    let LogLevelBody { level, filter } = serde_json::from_slice(&body)
+        .map_err(ApiError::from)
+        .context(&cx)?;
+    let invalid = |reason: String| ApiError::Client(ClientError::InvalidLogFilter { reason });
+
+    if level.is_none() && filter.is_none() {
+        return Err(invalid(
+            "expected at least one of `level` and `filter`".into(),
+        ))
+        .context(&cx);
+    }
+    // 全部解析成功之后再修改，避免只修改了一半
+    let filter = filter
+        .map(|v| v.parse::<TargetFilter>())
+        .transpose()
+        .map_err(|e| invalid(e.to_string()))
+        .context(&cx)?;
+
+    if let Some(level) = level {
+        logger::set_level(level).map_err(invalid).context(&cx)?;
+    }
+    if let Some(filter) = filter {
+        logger::set_filter(filter).map_err(invalid).context(&cx)?;
+    }
+
+    let (level, filter) = logger::current_filter().unzip();
+    tracing::info!(?level, filter = ?filter.as_ref().map(ToString::to_string), "log level changed");
+    let body = LogLevelBody {
+        level,
+        filter: filter.map(|v| v.to_string()),
+    };
+    Ok((StatusCode::OK, axum::Json(body)).into_response())
+}
+
+/// 复制到各个目标的进度，没有配置复制时 `targets` 为空
+#[debug_handler]
+pub(super) async fn replication_status(
+    State(state): State<AdminState>,
+) -> HandlerResult<Response> {
+    let cx = ErrorContext::new("replicationStatus");
+    let status = state.replication.status().await.context(&cx)?;
+
+    Ok((StatusCode::OK, axum::Json(status)).into_response())
+}
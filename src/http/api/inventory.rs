@@ -0,0 +1,93 @@
+use bytes::Bytes;
+use crab_vault::{
+    auth::{HttpMethod, error::AuthError, policy::PolicyEngine},
+    engine::{
+        MetaEngine, ObjectMeta, clock,
+        error::EngineResult,
+        inventory::{self, InventoryConfig},
+    },
+};
+use serde::Serialize;
+
+use crate::{
+    error::api::{ApiError, ClientError},
+    http::{
+        api::{ApiState, handler, payload, quota, retention},
+        extractor::{condition::WriteCondition, spool::SpooledBody},
+    },
+};
+
+/// 生成一次清单的结果，也是 `POST /admin/inventory/{bucket}` 的响应体
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct InventoryReport {
+    pub bucket: String,
+    pub destination: String,
+
+    /// 清单在目标 bucket 中的名称
+    pub object: String,
+    pub objects: u64,
+    pub bytes: u64,
+}
+
+/// 解析并校验 `PUT /{bucket}?inventory` 的请求体
+pub(super) fn from_body(body: &[u8], bucket: &str) -> Result<InventoryConfig, ApiError> {
+    let inventory: InventoryConfig = serde_json::from_slice(body)?;
+
+    inventory
+        .validate(bucket)
+        .map_err(|reason| ApiError::Client(ClientError::InvalidInventory { reason }))?;
+
+    Ok(inventory)
+}
+
+/// 后台任务会以 `config` 的名义写入目标 bucket，所以设置之前检查令牌是否允许写入清单所在的目录
+///
+/// 令牌本身必须允许 `PUT` `/{destination}/{destination-prefix}*`，bucket 策略中的允许语句不算，禁止语句仍然生效
+pub(super) fn admit(permission: &PolicyEngine, config: &InventoryConfig) -> Result<(), AuthError> {
+    let path = format!("/{}/{}*", config.destination, config.destination_prefix);
+    let allowed = !permission.denies(HttpMethod::Put, &path)
+        && permission.permissions().allows(HttpMethod::Put, &path);
+    match allowed {
+        true => Ok(()),
+        false => Err(AuthError::InsufficientPermissions),
+    }
+}
+
+/// ## 生成 `bucket` 的清单并写入目标 bucket
+///
+/// 清单与上传的 object 一样按照配置压缩、加密，并受目标 bucket 的配额和保留限制。
+/// 目标 bucket 必须已经存在，这里不会创建它
+pub(crate) async fn write_inventory(
+    state: &ApiState,
+    bucket: &str,
+    config: &InventoryConfig,
+) -> EngineResult<InventoryReport> {
+    state.meta_src.read_bucket_meta(&config.destination).await?;
+    let inventory = inventory::generate(state.meta_src.as_ref(), bucket, config).await?;
+    let object = config.object_name(bucket, clock::now());
+
+    let _lock = state
+        .lock_write(&config.destination, [object.as_str()])
+        .await;
+    let data = Bytes::from(inventory.data);
+    let mut meta = ObjectMeta::builder()
+        .bucket_name(&config.destination)
+        .object_name(&object)
+        .data(&data)
+        .content_type(config.format.content_type())
+        .build()?;
+    retention::check(&state.meta_src, &config.destination, &object).await?;
+    let _quota = quota::check(state, &meta).await?;
+
+    let stored = payload::prepare(state, &mut meta, SpooledBody::from_bytes(data), None).await?;
+    handler::store_object(state, &meta, &stored, &WriteCondition::Always).await?;
+
+    Ok(InventoryReport {
+        bucket: bucket.to_string(),
+        destination: config.destination.clone(),
+        object,
+        objects: inventory.objects,
+        bytes: inventory.bytes,
+    })
+}
@@ -0,0 +1,14 @@
+use crab_vault::engine::lifecycle::LifecycleConfig;
+
+use crate::error::api::{ApiError, ClientError};
+
+/// 解析并校验 `PUT /{bucket}?lifecycle` 的请求体
+pub(super) fn from_body(body: &[u8]) -> Result<LifecycleConfig, ApiError> {
+    let lifecycle: LifecycleConfig = serde_json::from_slice(body)?;
+
+    lifecycle
+        .validate()
+        .map_err(|reason| ApiError::Client(ClientError::InvalidLifecycle { reason }))?;
+
+    Ok(lifecycle)
+}
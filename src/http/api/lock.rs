@@ -0,0 +1,162 @@
+use std::{
+    collections::HashMap,
+    hash::{BuildHasher, RandomState},
+    io,
+    pin::Pin,
+    sync::{Arc, Mutex, Weak},
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use crab_vault::engine::ObjectReader;
+use futures::AsyncRead;
+use tokio::sync::{OwnedRwLockReadGuard, OwnedRwLockWriteGuard, RwLock, oneshot};
+
+/// 分片的数量，同一个分片中的 key 在查找锁时互相等待，但是不会互相阻塞读写
+const SHARD_COUNT: usize = 64;
+
+/// 分片中的条目超过这个数量时才清理已经没有人持有的锁
+const MIN_PRUNE_LEN: usize = 64;
+
+/// 响应体最多持有读锁的时间，慢速或者停滞的客户端不能无限期地阻塞对同一个 object 的修改
+const READ_HOLD_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// 加锁的方式，同一个 key 同时要求两种方式时按照 [`Write`](LockMode::Write) 处理
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(super) enum LockMode {
+    Read,
+    Write,
+}
+
+/// 按照 key 区分的读写锁
+///
+/// 锁只在有人持有或者等待时存在，释放之后由分片在之后的插入中清理
+pub(super) struct KeyedLocks {
+    hasher: RandomState,
+    shards: Box<[Mutex<Shard>]>,
+}
+
+struct Shard {
+    locks: HashMap<String, Weak<RwLock<()>>>,
+    prune_at: usize,
+}
+
+/// [`KeyedLocks::lock`] 持有的所有锁，drop 时全部释放
+pub(super) struct LockGuards {
+    _read: Vec<OwnedRwLockReadGuard<()>>,
+    _write: Vec<OwnedRwLockWriteGuard<()>>,
+}
+
+impl KeyedLocks {
+    pub fn new() -> Self {
+        let shards = (0..SHARD_COUNT)
+            .map(|_| {
+                Mutex::new(Shard {
+                    locks: HashMap::new(),
+                    prune_at: MIN_PRUNE_LEN,
+                })
+            })
+            .collect();
+
+        Self {
+            hasher: RandomState::new(),
+            shards,
+        }
+    }
+
+    /// bucket 本身使用的 key
+    pub fn bucket_key(bucket: &str) -> String {
+        bucket.to_string()
+    }
+
+    /// object 使用的 key，bucket 的名称中不能有 `/`，所以不会与 [`bucket_key`](Self::bucket_key) 相同
+    pub fn object_key(bucket: &str, object: &str) -> String {
+        format!("{bucket}/{object}")
+    }
+
+    /// 写入设置了配额的 bucket 时使用的 key，bucket 的名称中不能有 `?`，所以不会与其他 key 相同
+    pub fn quota_key(bucket: &str) -> String {
+        format!("{bucket}?quota")
+    }
+
+    /// 按照 key 的顺序依次加锁，所有请求都按照同样的顺序加锁，所以不会死锁
+    pub async fn lock(&self, mut keys: Vec<(String, LockMode)>) -> LockGuards {
+        // 同一个 key 排序后相邻，Write 排在 Read 之前，只保留第一个
+        keys.sort_by(|(a, a_mode), (b, b_mode)| a.cmp(b).then(b_mode.cmp(a_mode)));
+        keys.dedup_by(|(later, _), (kept, _)| later == kept);
+
+        let mut guards = LockGuards {
+            _read: Vec::new(),
+            _write: Vec::new(),
+        };
+        for (key, mode) in keys {
+            let lock = self.get(key);
+            match mode {
+                LockMode::Read => guards._read.push(lock.read_owned().await),
+                LockMode::Write => guards._write.push(lock.write_owned().await),
+            }
+        }
+        guards
+    }
+
+    fn get(&self, key: String) -> Arc<RwLock<()>> {
+        let index = self.hasher.hash_one(&key) as usize % self.shards.len();
+        let mut shard = self.shards[index]
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        if let Some(lock) = shard.locks.get(&key).and_then(Weak::upgrade) {
+            return lock;
+        }
+
+        if shard.locks.len() >= shard.prune_at {
+            shard.locks.retain(|_, lock| lock.strong_count() > 0);
+            shard.prune_at = (shard.locks.len() * 2).max(MIN_PRUNE_LEN);
+        }
+
+        let lock = Arc::new(RwLock::new(()));
+        shard.locks.insert(key, Arc::downgrade(&lock));
+        lock
+    }
+}
+
+impl LockGuards {
+    /// 把锁交给 `reader`，直到读完、被丢弃或者超过 [`READ_HOLD_TIMEOUT`] 时释放
+    ///
+    /// 超时之后剩下的数据不再受锁的保护，这期间 object 被修改时客户端收到的数据可能与 `ETag` 不符
+    pub(super) fn hold_while_reading(self, reader: ObjectReader) -> ObjectReader {
+        let (done, finished) = oneshot::channel::<()>();
+        // 停滞的客户端不会再读取响应体，所以由单独的任务计时
+        tokio::spawn(async move {
+            let _guards = self;
+            let _ = tokio::time::timeout(READ_HOLD_TIMEOUT, finished).await;
+        });
+
+        Box::pin(LockedReader {
+            reader,
+            done: Some(done),
+        })
+    }
+}
+
+/// 读完或者被丢弃时丢弃 `done`，持有锁的任务随之结束
+struct LockedReader {
+    reader: ObjectReader,
+    done: Option<oneshot::Sender<()>>,
+}
+
+impl AsyncRead for LockedReader {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let poll = self.reader.as_mut().poll_read(cx, buf);
+        if let Poll::Ready(Ok(0)) = poll
+            && !buf.is_empty()
+        {
+            self.done = None;
+        }
+        poll
+    }
+}
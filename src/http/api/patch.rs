@@ -0,0 +1,154 @@
+use axum::http::{HeaderMap, header::CONTENT_RANGE};
+use crab_vault::{
+    engine::{
+        ObjectMeta, ObjectReader,
+        builder::EtagHasher,
+        error::{EngineError, EngineResult},
+    },
+    utils::hashing::HashingWriter,
+};
+
+use crate::{
+    error::api::{ApiError, ClientError},
+    http::api::has_query_key,
+};
+
+/// 追加到 object 末尾的请求使用的查询参数，参见 `PATCH /{bucket}/{object}?append`
+pub(super) const APPEND_QUERY_KEY: &str = "append";
+
+/// `PATCH /{bucket}/{object}` 修改 object 内容的方式
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(super) enum ContentPatch {
+    /// `?append`，把请求体追加到 object 的末尾
+    Append,
+
+    /// `Content-Range: bytes {first}-{last}/{length}`，用请求体覆盖 `first` 到 `last` 的字节，两端都包含在内
+    Range { first: u64, last: u64 },
+}
+
+impl ContentPatch {
+    /// 请求是修改 object 的内容，而不是修改元数据
+    pub fn requested(query: Option<&str>, headers: &HeaderMap) -> bool {
+        has_query_key(query, APPEND_QUERY_KEY) || headers.contains_key(CONTENT_RANGE)
+    }
+
+    pub fn from_request(query: Option<&str>, headers: &HeaderMap) -> Result<Self, ApiError> {
+        let range = headers.get(CONTENT_RANGE);
+        match (has_query_key(query, APPEND_QUERY_KEY), range) {
+            (true, None) => Ok(Self::Append),
+            (false, Some(range)) => parse_content_range(range.to_str()?).map_err(invalid_range),
+            (true, Some(_)) => Err(invalid_range(
+                "`?append` and `Content-Range` cannot be used together",
+            )),
+            (false, None) => Err(invalid_range(
+                "neither `?append` nor `Content-Range` is given",
+            )),
+        }
+    }
+
+    /// 请求体在 object 中的起始位置，`size` 是 object 现在的大小
+    ///
+    /// 只能覆盖已有的数据或者紧接着末尾写入，不能在末尾之后留下空洞；返回 `Ok` 时写入之后的末尾不会溢出
+    pub fn offset(&self, size: u64, body_len: u64) -> Result<u64, ApiError> {
+        let offset = match *self {
+            Self::Append => size,
+            Self::Range { first, .. } if first > size => {
+                return Err(invalid_range(
+                    "the range starts beyond the end of the object",
+                ));
+            }
+            Self::Range { first, last } if (last - first).checked_add(1) == Some(body_len) => first,
+            Self::Range { .. } => {
+                return Err(invalid_range(
+                    "the range does not match the length of the request body",
+                ));
+            }
+        };
+
+        match offset.checked_add(body_len) {
+            Some(_) => Ok(offset),
+            None => Err(invalid_range(
+                "the range ends beyond the largest object size",
+            )),
+        }
+    }
+}
+
+/// 逐块读出修改之后的数据，返回它的大小和 etag，不会把整个 object 读入内存
+pub(super) async fn digest(mut reader: ObjectReader, path: &str) -> EngineResult<(u64, String)> {
+    let mut writer = HashingWriter::new(futures::io::sink(), EtagHasher::new());
+    futures::io::copy(&mut reader, &mut writer)
+        .await
+        .map_err(|error| EngineError::Io {
+            error,
+            path: path.to_string(),
+        })?;
+    let len = writer.written();
+    let (_, hasher) = writer.into_parts();
+    Ok((len, hasher.finish()))
+}
+
+/// 压缩或者加密过的 object 无法按照原始数据的位置修改
+pub(super) fn check_patchable(meta: &ObjectMeta) -> Result<(), ApiError> {
+    let reason = if meta.compression.is_some() {
+        "the object is stored compressed"
+    } else if !matches!(meta.encryption(), Ok(None)) {
+        "the object is stored encrypted"
+    } else {
+        return Ok(());
+    };
+    Err(ApiError::Client(ClientError::ObjectNotPatchable { reason }))
+}
+
+/// 解析 `bytes {first}-{last}/{length}`，`length` 可以是 `*`
+fn parse_content_range(value: &str) -> Result<ContentPatch, &'static str> {
+    const MALFORMED: &str = "expected `bytes {first}-{last}/{length}`, `{length}` can be `*`";
+
+    let (range, length) = value
+        .trim()
+        .strip_prefix("bytes ")
+        .and_then(|v| v.split_once('/'))
+        .ok_or(MALFORMED)?;
+    let (first, last) = range.split_once('-').ok_or(MALFORMED)?;
+    let (first, last) = match (first.parse::<u64>(), last.parse::<u64>()) {
+        (Ok(first), Ok(last)) => (first, last),
+        _ => return Err(MALFORMED),
+    };
+
+    if last < first {
+        return Err("the last byte position is before the first");
+    }
+    match length {
+        "*" => {}
+        length => match length.parse::<u64>() {
+            Ok(length) if last < length => {}
+            Ok(_) => return Err("the range ends beyond the complete length"),
+            Err(_) => return Err(MALFORMED),
+        },
+    }
+
+    Ok(ContentPatch::Range { first, last })
+}
+
+fn invalid_range(reason: &'static str) -> ApiError {
+    ApiError::Client(ClientError::InvalidContentRange { reason })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ContentPatch;
+
+    #[test]
+    fn test_offset() {
+        let range = |first, last| ContentPatch::Range { first, last };
+        assert_eq!(ContentPatch::Append.offset(5, 3).unwrap(), 5);
+        assert_eq!(range(2, 4).offset(5, 3).unwrap(), 2);
+        assert_eq!(range(5, 7).offset(5, 3).unwrap(), 5);
+
+        // 长度不符、在末尾之后留下空洞、或者溢出
+        assert!(range(2, 4).offset(5, 2).is_err());
+        assert!(range(6, 8).offset(5, 3).is_err());
+        assert!(range(0, u64::MAX).offset(5, 0).is_err());
+        assert!(ContentPatch::Append.offset(u64::MAX, 1).is_err());
+    }
+}
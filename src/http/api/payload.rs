@@ -0,0 +1,112 @@
+use std::sync::Arc;
+
+use bytes::Bytes;
+use crab_vault::engine::{
+    DataEngine, DataSource, ObjectMeta,
+    crypto::{CustomerKey, KeyRing},
+    error::{EngineError, EngineResult},
+};
+
+use crate::{
+    app_config::data::CompressionConfig,
+    http::{
+        api::{ApiState, compression},
+        extractor::spool::SpooledBody,
+    },
+};
+
+/// ## 按照配置压缩、加密请求体，返回实际写入数据后端的内容
+///
+/// 压缩信息和加密信息记录在 `meta` 中，`size` 和 `etag` 仍然是请求体本身的。配置了主密钥时总是加密，
+/// 请求中给出了客户密钥时由它代替主密钥
+pub(super) async fn prepare(
+    state: &ApiState,
+    meta: &mut ObjectMeta,
+    body: SpooledBody,
+    customer_key: Option<&CustomerKey>,
+) -> EngineResult<SpooledBody> {
+    seal(
+        &state.compression,
+        state.key_ring.as_ref(),
+        meta,
+        body,
+        customer_key,
+    )
+    .await
+}
+
+/// 按照配置压缩、加密服务端自己写入的数据，是 [`read_original`] 的逆操作，例如从其他服务导入 object
+#[cfg_attr(not(feature = "s3"), allow(dead_code))]
+pub(crate) async fn seal_original(
+    compression: &CompressionConfig,
+    key_ring: Option<&Arc<KeyRing>>,
+    meta: &mut ObjectMeta,
+    data: Vec<u8>,
+) -> EngineResult<Vec<u8>> {
+    let body = SpooledBody::from_bytes(Bytes::from(data));
+    let sealed = seal(compression, key_ring, meta, body, None).await?;
+    Ok(sealed.to_bytes().await?.to_vec())
+}
+
+async fn seal(
+    compression: &CompressionConfig,
+    key_ring: Option<&Arc<KeyRing>>,
+    meta: &mut ObjectMeta,
+    body: SpooledBody,
+    customer_key: Option<&CustomerKey>,
+) -> EngineResult<SpooledBody> {
+    let body = match compression::encode(compression, meta, &body).await? {
+        Some((compression, data)) => {
+            meta.compression = Some(compression);
+            SpooledBody::from_bytes(data)
+        }
+        None => body,
+    };
+
+    let key_ring = match customer_key {
+        Some(customer_key) => customer_key.key_ring(),
+        None => match key_ring {
+            Some(key_ring) => key_ring,
+            None => return Ok(body),
+        },
+    };
+    let (mut encryption, sealed) = body.encrypt(key_ring).await?;
+    if let Some(customer_key) = customer_key {
+        customer_key.mark(&mut encryption);
+    }
+    meta.set_encryption(Some(&encryption))?;
+    Ok(sealed)
+}
+
+/// 读出 object 并还原为上传时的内容，供服务端自己使用，例如复制到其他实例
+///
+/// 用客户密钥加密的 object 无法还原，调用之前需要排除
+pub(crate) async fn read_original(
+    data_src: &DataSource,
+    key_ring: Option<&Arc<KeyRing>>,
+    meta: &ObjectMeta,
+) -> EngineResult<Vec<u8>> {
+    let data = data_src
+        .read_object(&meta.bucket_name, &meta.object_name)
+        .await?;
+    let data = decrypt(key_ring, meta, data).await?;
+    compression::decode(meta, data).await
+}
+
+/// 解密从数据后端读出的内容，没有加密时原样返回，压缩过的数据仍然是压缩的
+pub(super) async fn decrypt(
+    key_ring: Option<&Arc<KeyRing>>,
+    meta: &ObjectMeta,
+    data: Vec<u8>,
+) -> EngineResult<Vec<u8>> {
+    let Some(encryption) = meta.encryption()? else {
+        return Ok(data);
+    };
+    let key_ring = key_ring.cloned().ok_or_else(|| {
+        EngineError::Encryption("the object is encrypted, but no master key is configured".into())
+    })?;
+
+    tokio::task::spawn_blocking(move || key_ring.open(&encryption, &data))
+        .await
+        .map_err(|e| EngineError::Encryption(e.to_string()))?
+}
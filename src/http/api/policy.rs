@@ -0,0 +1,20 @@
+use crab_vault::auth::policy::BucketPolicy;
+use serde_json::Value;
+use validator::Validate;
+
+use crate::error::api::{ApiError, ClientError};
+
+/// 解析并校验 `PUT /{bucket}?policy` 的请求体，返回需要保存到 bucket 元数据中的策略
+///
+/// 保存的是重新序列化之后的结果，省略的字段会被补上默认值
+pub(super) fn from_body(body: &[u8]) -> Result<Value, ApiError> {
+    let policy: BucketPolicy = serde_json::from_slice(body)?;
+
+    policy.validate().map_err(|e| {
+        ApiError::Client(ClientError::InvalidBucketPolicy {
+            reason: e.to_string(),
+        })
+    })?;
+
+    Ok(serde_json::to_value(policy)?)
+}
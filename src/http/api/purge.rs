@@ -0,0 +1,53 @@
+use crab_vault::{
+    auth::{Capability, HttpMethod, error::AuthError, policy::PolicyEngine},
+    engine::{ObjectMeta, error::EngineResult},
+};
+
+use crate::http::api::batch::MAX_DELETE_OBJECTS;
+
+/// 强制删除 bucket 时每一批删除的 object 个数，每删除一批记录一次进度
+pub(super) const PURGE_CHUNK: usize = MAX_DELETE_OBJECTS;
+
+/// 令牌本身必须有一条权限允许 `DELETE` 这个 bucket 并且授予了 [`Capability::ForceDelete`]，
+/// 普通的删除权限不足以删除整个 bucket。bucket 策略中的禁止语句仍然生效
+pub(super) fn admit_bucket(permission: &PolicyEngine, bucket: &str) -> Result<(), AuthError> {
+    let path = format!("/{bucket}");
+    let allowed = !permission.denies(HttpMethod::Delete, &path)
+        && permission.permissions().allows_capability(
+            Capability::ForceDelete,
+            HttpMethod::Delete,
+            &path,
+        );
+    match allowed {
+        true => Ok(()),
+        false => Err(AuthError::InsufficientPermissions),
+    }
+}
+
+/// 令牌本身必须允许删除 `meta` 对应的 object，bucket 策略中的允许语句不算，禁止语句仍然生效
+///
+/// 带有标签条件的权限按照这个 object 的标签判断
+pub(super) fn admit(permission: &PolicyEngine, meta: &ObjectMeta) -> Result<(), AuthError> {
+    let path = format!("/{}/{}", meta.bucket_name, meta.object_name);
+    if permission.denies(HttpMethod::Delete, &path) {
+        return Err(AuthError::InsufficientPermissions);
+    }
+
+    let allowed = match permission.permissions().needs_tags() {
+        true => {
+            let mut permissions = permission.permissions().clone();
+            permissions.resolve_tags(&meta.tags);
+            permissions.allows(HttpMethod::Delete, &path)
+        }
+        false => permission.permissions().allows(HttpMethod::Delete, &path),
+    };
+    match allowed {
+        true => Ok(()),
+        false => Err(AuthError::InsufficientPermissions),
+    }
+}
+
+/// 一批删除中的第一个错误
+pub(super) fn first_error(results: Vec<EngineResult<()>>) -> EngineResult<()> {
+    results.into_iter().collect()
+}
@@ -0,0 +1,68 @@
+use crab_vault::engine::{
+    BucketMeta, MetaEngine, ObjectMeta,
+    error::{EngineError, EngineResult},
+    usage::BucketUsage,
+};
+use serde::Serialize;
+
+use crate::http::api::{ApiState, lock::LockGuards};
+
+/// 读取 bucket 用量的请求使用的查询参数，参见 `GET /{bucket}?usage`
+pub(super) const USAGE_QUERY_KEY: &str = "usage";
+
+/// `GET /{bucket}?usage` 的响应体
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub(super) struct UsageReport {
+    pub bucket: String,
+
+    #[serde(flatten)]
+    pub usage: BucketUsage,
+
+    /// 没有设置时不出现
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_bytes: Option<u64>,
+
+    /// 没有设置时不出现
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_objects: Option<u64>,
+}
+
+impl UsageReport {
+    pub fn new(meta: BucketMeta, usage: BucketUsage) -> Self {
+        Self {
+            bucket: meta.name,
+            usage,
+            max_bytes: meta.max_bytes,
+            max_objects: meta.max_objects,
+        }
+    }
+}
+
+/// ## 写入 `meta` 之前检查目标 bucket 的配额，bucket 没有元数据或者没有设置配额时不检查
+///
+/// 设置了配额时返回的锁必须持有到写入完成，这样同一个 bucket 中并发的写入不会一起超出配额。
+/// 替换已有的 object 时只计算大小的变化，调用者需要持有这个 object 的写锁
+pub(super) async fn check(state: &ApiState, meta: &ObjectMeta) -> EngineResult<Option<LockGuards>> {
+    let meta_src = &state.meta_src;
+    // 修改配额需要 bucket 的写锁，调用者持有 object 的写锁时它不会改变
+    let bucket = match meta_src.read_bucket_meta(&meta.bucket_name).await {
+        Ok(bucket) if bucket.has_quota() => bucket,
+        Ok(_) | Err(EngineError::BucketMetaNotFound { .. }) => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    let lock = state.lock_quota(&meta.bucket_name).await;
+
+    let old = match meta_src
+        .read_object_meta(&meta.bucket_name, &meta.object_name)
+        .await
+    {
+        Ok(old) => Some(old.size),
+        Err(EngineError::ObjectMetaNotFound { .. }) => None,
+        Err(e) => return Err(e),
+    };
+
+    let usage = meta_src.bucket_usage(&meta.bucket_name).await?;
+    bucket.check_quota(usage, old, meta.size)?;
+    Ok(Some(lock))
+}
@@ -1,45 +1,119 @@
+use std::pin::pin;
+
 use axum::{
+    body::Body,
     http::{
         HeaderMap, HeaderValue, StatusCode,
-        header::{self, CONTENT_TYPE, ETAG, LAST_MODIFIED},
+        header::{self, CONTENT_ENCODING, CONTENT_TYPE, ETAG, LAST_MODIFIED, VARY},
     },
     response::{IntoResponse, Response},
 };
 use base64::{Engine, prelude::BASE64_STANDARD};
-use crab_vault::engine::{BucketMeta, ObjectMeta};
+use bytes::Bytes;
+use crab_vault::engine::{
+    BucketMeta, ObjectMeta, ObjectReader, compression::Codec, error::EngineResult,
+    list::BucketPage, usage::BucketUsage,
+};
+use futures::{SinkExt, Stream, StreamExt, channel::mpsc};
 use serde::Serialize;
+use tokio_util::{compat::FuturesAsyncReadCompatExt, io::ReaderStream};
 
 use crate::http::{
-    X_CRAB_VAULT_BUCKET_NAME, X_CRAB_VAULT_CREATED_AT, X_CRAB_VAULT_OBJECT_NAME,
-    X_CRAB_VAULT_USER_META,
+    X_CRAB_VAULT_BUCKET_COUNT, X_CRAB_VAULT_BUCKET_NAME, X_CRAB_VAULT_CREATED_AT,
+    X_CRAB_VAULT_EXPIRES_AT, X_CRAB_VAULT_IS_TRUNCATED, X_CRAB_VAULT_LEGAL_HOLD,
+    X_CRAB_VAULT_MAX_BYTES, X_CRAB_VAULT_MAX_OBJECTS, X_CRAB_VAULT_NEXT_CONTINUATION_TOKEN,
+    X_CRAB_VAULT_OBJECT_COUNT, X_CRAB_VAULT_OBJECT_NAME, X_CRAB_VAULT_RETAIN_UNTIL,
+    X_CRAB_VAULT_SKIPPED_COUNT, X_CRAB_VAULT_SKIPPED_ENTRIES, X_CRAB_VAULT_TAGGING_COUNT,
+    X_CRAB_VAULT_TOTAL_BYTES, X_CRAB_VAULT_USER_META,
 };
 
 /// 一个自定义的响应类型，它将元数据放入 Headers，数据放入 Body。
 pub struct ObjectResponse {
     meta: ObjectMeta,
-    data: Option<Vec<u8>>, // Optional, because HEAD requests have no body
+    body: ObjectBody,
+
+    /// `body` 是以这个算法压缩过的数据，参见 [`encoded`](Self::encoded)
+    encoding: Option<Codec>,
+}
+
+enum ObjectBody {
+    /// `HEAD` 请求没有响应体
+    None,
+    Bytes(Vec<u8>),
+
+    /// 从 reader 中逐块读出，长度事先已知
+    Reader(ObjectReader, u64),
 }
 
 #[derive(Serialize)]
 pub struct BucketResponse {
     meta: BucketMeta,
+
+    /// 只在 `HEAD /{bucket}` 的响应头中出现
+    #[serde(skip)]
+    usage: Option<BucketUsage>,
+}
+
+/// ndjson 的媒体类型
+pub const APPLICATION_NDJSON: &str = "application/x-ndjson";
+
+/// 响应头中最多列出的被跳过的条目数，数量本身不受限制
+const MAX_REPORTED_SKIPPED: usize = 100;
+
+/// 生产者最多领先于客户端的行数，超过后等待客户端读取
+const NDJSON_BUFFER: usize = 64;
+
+/// 逐行输出元数据的响应，每一行是一个 JSON 对象
+///
+/// 响应体由 [`NdjsonSender`] 在另一个任务中写入，客户端断开后写入会自动停止
+pub struct NdjsonResponse {
+    rx: mpsc::Receiver<EngineResult<Bytes>>,
+}
+
+pub struct NdjsonSender {
+    tx: mpsc::Sender<EngineResult<Bytes>>,
 }
 
 impl ObjectResponse {
+    /// user meta 中保留给服务端的键不会出现在响应中
     pub fn new(meta: ObjectMeta, data: Vec<u8>) -> Self {
         Self {
-            meta,
-            data: Some(data),
+            meta: meta.hide_reserved_meta(),
+            body: ObjectBody::Bytes(data),
+            encoding: None,
         }
     }
     pub fn meta_only(meta: ObjectMeta) -> Self {
-        Self { meta, data: None }
+        Self {
+            meta: meta.hide_reserved_meta(),
+            body: ObjectBody::None,
+            encoding: None,
+        }
+    }
+
+    /// 响应体从 `reader` 中逐块读出，不会一次性放入内存，`len` 是 reader 中数据的字节数
+    pub fn reader(meta: ObjectMeta, reader: ObjectReader, len: u64) -> Self {
+        Self {
+            meta: meta.hide_reserved_meta(),
+            body: ObjectBody::Reader(reader, len),
+            encoding: None,
+        }
+    }
+
+    /// 直接返回保存的压缩数据，由客户端解压
+    pub fn encoded(mut self, codec: Codec) -> Self {
+        self.encoding = Some(codec);
+        self
     }
 }
 
 impl IntoResponse for ObjectResponse {
     fn into_response(self) -> Response {
-        let Self { meta, data } = self;
+        let Self {
+            meta,
+            body,
+            encoding,
+        } = self;
         let ObjectMeta {
             object_name,
             bucket_name,
@@ -49,12 +123,14 @@ impl IntoResponse for ObjectResponse {
             user_meta,
             created_at,
             updated_at,
+            expires_at,
+            compression,
+            tags,
+            retention,
         } = meta;
 
         let mut headers = HeaderMap::new();
 
-        headers.insert(LAST_MODIFIED, HeaderValue::from(size));
-
         HeaderValue::from_str(&content_type)
             .ok()
             .and_then(|content_type| headers.insert(CONTENT_TYPE, content_type));
@@ -71,6 +147,10 @@ impl IntoResponse for ObjectResponse {
             .ok()
             .and_then(|created_at| headers.insert(X_CRAB_VAULT_CREATED_AT, created_at));
 
+        expires_at
+            .and_then(|v| HeaderValue::from_str(&v.to_rfc2822()).ok())
+            .and_then(|expires_at| headers.insert(X_CRAB_VAULT_EXPIRES_AT, expires_at));
+
         HeaderValue::from_str(&object_name)
             .ok()
             .and_then(|object_name| headers.insert(X_CRAB_VAULT_OBJECT_NAME, object_name));
@@ -79,29 +159,72 @@ impl IntoResponse for ObjectResponse {
             .ok()
             .and_then(|bucket_name| headers.insert(X_CRAB_VAULT_BUCKET_NAME, bucket_name));
 
+        // 标签可能很长，响应头中只给出数量，完整的标签通过 `?tagging` 读取
+        if !tags.is_empty() {
+            headers.insert(X_CRAB_VAULT_TAGGING_COUNT, HeaderValue::from(tags.len()));
+        }
+
+        retention
+            .retain_until
+            .and_then(|v| HeaderValue::from_str(&v.to_rfc2822()).ok())
+            .and_then(|retain_until| headers.insert(X_CRAB_VAULT_RETAIN_UNTIL, retain_until));
+
+        if retention.legal_hold {
+            headers.insert(X_CRAB_VAULT_LEGAL_HOLD, HeaderValue::from_static("on"));
+        }
+
         let mut headers = append_user_mata_to_headers(user_meta, headers);
 
-        let body = data.unwrap_or_default();
-        headers.insert(header::CONTENT_LENGTH, HeaderValue::from(body.len()));
+        // 压缩保存的 object 的响应体取决于 Accept-Encoding
+        if compression.is_some() {
+            headers.insert(VARY, HeaderValue::from_static("accept-encoding"));
+        }
+        if let Some(codec) = encoding {
+            headers.insert(CONTENT_ENCODING, HeaderValue::from_static(codec.token()));
+        }
+
+        // `HEAD` 的响应没有响应体，但长度仍然是 object 的大小
+        let (length, body) = match body {
+            ObjectBody::None => (size, Body::empty()),
+            ObjectBody::Bytes(data) => (data.len() as u64, Body::from(data)),
+            ObjectBody::Reader(reader, len) => {
+                (len, Body::from_stream(ReaderStream::new(reader.compat())))
+            }
+        };
+        headers.insert(header::CONTENT_LENGTH, HeaderValue::from(length));
 
         (StatusCode::OK, headers, body).into_response()
     }
 }
 
 impl BucketResponse {
-    pub fn new(meta: BucketMeta) -> Self {
-        Self { meta }
+    pub fn new(mut meta: BucketMeta) -> Self {
+        // 数据密钥虽然被包裹过，但仍然不应该出现在响应中；策略、生命周期规则、清单配置和跨域规则只能通过各自的查询参数读取
+        meta.data_key = None;
+        meta.policy = None;
+        meta.lifecycle = None;
+        meta.inventory = None;
+        meta.cors = None;
+        Self { meta, usage: None }
+    }
+
+    pub fn with_usage(mut self, usage: BucketUsage) -> Self {
+        self.usage = Some(usage);
+        self
     }
 }
 
 impl IntoResponse for BucketResponse {
     fn into_response(self) -> Response {
-        let BucketResponse { meta } = self;
+        let BucketResponse { meta, usage } = self;
         let BucketMeta {
             name,
             user_meta,
+            max_bytes,
+            max_objects,
             created_at,
             updated_at,
+            ..
         } = meta;
 
         let mut headers = HeaderMap::new();
@@ -118,12 +241,63 @@ impl IntoResponse for BucketResponse {
             .ok()
             .and_then(|created_at| headers.insert(X_CRAB_VAULT_CREATED_AT, created_at));
 
+        // 配额只在设置了时出现，用量只在统计过时出现
+        let numbers = [
+            (X_CRAB_VAULT_MAX_BYTES, max_bytes),
+            (X_CRAB_VAULT_MAX_OBJECTS, max_objects),
+            (X_CRAB_VAULT_OBJECT_COUNT, usage.map(|v| v.object_count)),
+            (X_CRAB_VAULT_TOTAL_BYTES, usage.map(|v| v.total_bytes)),
+        ];
+        for (name, value) in numbers {
+            if let Some(value) = value {
+                headers.insert(name, HeaderValue::from(value));
+            }
+        }
+
         let headers = append_user_mata_to_headers(user_meta, headers);
 
         (StatusCode::OK, headers).into_response()
     }
 }
 
+impl NdjsonResponse {
+    pub fn channel() -> (NdjsonSender, Self) {
+        let (tx, rx) = mpsc::channel(NDJSON_BUFFER);
+        (NdjsonSender { tx }, Self { rx })
+    }
+}
+
+impl NdjsonSender {
+    /// 把流中的元数据逐条写入响应体，遇到错误时中断响应
+    pub async fn send_all<T: Serialize>(mut self, stream: impl Stream<Item = EngineResult<T>>) {
+        let mut stream = pin!(stream);
+
+        while let Some(item) = stream.next().await {
+            let line = item.and_then(|v| {
+                let mut line = serde_json::to_vec(&v)?;
+                line.push(b'\n');
+                Ok(Bytes::from(line))
+            });
+            let failed = line.is_err();
+
+            if self.tx.send(line).await.is_err() || failed {
+                break;
+            }
+        }
+    }
+}
+
+impl IntoResponse for NdjsonResponse {
+    fn into_response(self) -> Response {
+        (
+            StatusCode::OK,
+            [(CONTENT_TYPE, APPLICATION_NDJSON)],
+            Body::from_stream(self.rx),
+        )
+            .into_response()
+    }
+}
+
 pub fn append_user_mata_to_headers(value: serde_json::Value, mut headers: HeaderMap) -> HeaderMap {
     if let Ok(value_json_string) = serde_json::to_string(&value)
         && let Ok(header_value) = HeaderValue::from_str(&BASE64_STANDARD.encode(value_json_string))
@@ -133,3 +307,48 @@ pub fn append_user_mata_to_headers(value: serde_json::Value, mut headers: Header
 
     headers
 }
+
+/// 报告列举时因为元数据损坏而被跳过的条目，没有跳过任何条目时返回空的 [`HeaderMap`]
+///
+/// 条目名称与 user meta 一样以 base64 编码的 JSON 数组放在响应头中
+pub fn skipped_entries_headers(skipped: &[String]) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    if skipped.is_empty() {
+        return headers;
+    }
+
+    tracing::warn!(
+        count = skipped.len(),
+        entries = ?skipped,
+        "skipped corrupt metadata entries while listing"
+    );
+
+    headers.insert(X_CRAB_VAULT_SKIPPED_COUNT, HeaderValue::from(skipped.len()));
+
+    let reported = &skipped[..skipped.len().min(MAX_REPORTED_SKIPPED)];
+    if let Ok(value_json_string) = serde_json::to_string(reported)
+        && let Ok(header_value) = HeaderValue::from_str(&BASE64_STANDARD.encode(value_json_string))
+    {
+        headers.insert(X_CRAB_VAULT_SKIPPED_ENTRIES, header_value);
+    }
+
+    headers
+}
+
+/// `GET /` 和 `HEAD /` 的响应头：满足前缀条件的 bucket 总数、是否还有下一页，以及下一页的令牌
+///
+/// 响应体仍然是 bucket 的列表，不分页的旧客户端不受影响
+pub fn bucket_page_headers(page: &BucketPage) -> HeaderMap {
+    let mut headers = skipped_entries_headers(&page.skipped);
+    headers.insert(X_CRAB_VAULT_BUCKET_COUNT, HeaderValue::from(page.total));
+    headers.insert(
+        X_CRAB_VAULT_IS_TRUNCATED,
+        HeaderValue::from_static(if page.is_truncated { "true" } else { "false" }),
+    );
+    if let Some(token) = &page.next_continuation_token
+        && let Ok(value) = HeaderValue::from_str(token)
+    {
+        headers.insert(X_CRAB_VAULT_NEXT_CONTINUATION_TOKEN, value);
+    }
+    headers
+}
@@ -0,0 +1,69 @@
+use chrono::{DateTime, Utc};
+use crab_vault::{
+    auth::{Capability, HttpMethod, error::AuthError, policy::PolicyEngine},
+    engine::{
+        MetaSource, clock,
+        error::EngineResult,
+        retention::{self, ObjectRetention},
+    },
+};
+
+use crate::error::api::{ApiError, ClientError};
+
+/// 解析并校验 `PUT /{bucket}/{object}?retention` 的请求体
+pub(super) fn from_body(body: &[u8], now: DateTime<Utc>) -> Result<ObjectRetention, ApiError> {
+    let retention: ObjectRetention = serde_json::from_slice(body)?;
+
+    retention
+        .validate(now)
+        .map_err(|reason| ApiError::Client(ClientError::InvalidRetention { reason }))?;
+
+    Ok(retention)
+}
+
+/// 把保留从 `current` 替换为 `new` 会解除合法保留时，检查请求携带的令牌是否允许这样做
+///
+/// 令牌本身必须有一条权限允许 `PUT` 这个 object 并且授予了 [`Capability::LegalHold`]，
+/// bucket 策略中的允许语句不算，禁止语句仍然生效
+pub(super) fn admit_release(
+    permission: &PolicyEngine,
+    bucket: &str,
+    object: &str,
+    current: &ObjectRetention,
+    new: &ObjectRetention,
+) -> Result<(), AuthError> {
+    if !current.released_by(new) {
+        return Ok(());
+    }
+
+    let path = format!("/{bucket}/{object}");
+    let allowed = !permission.denies(HttpMethod::Put, &path)
+        && permission.permissions().allows_capability(
+            Capability::LegalHold,
+            HttpMethod::Put,
+            &path,
+        );
+    match allowed {
+        true => Ok(()),
+        false => Err(AuthError::InsufficientPermissions),
+    }
+}
+
+/// 删除或者覆盖 object 之前检查它现在的保留，调用者需要持有这个 object 的写锁
+pub(super) async fn check(meta_src: &MetaSource, bucket: &str, object: &str) -> EngineResult<()> {
+    retention::check(meta_src, bucket, object, clock::now()).await
+}
+
+/// 对每个 object 调用 [`check`]，结果与 `objects` 一一对应
+pub(super) async fn check_all(
+    meta_src: &MetaSource,
+    bucket: &str,
+    objects: &[String],
+) -> Vec<EngineResult<()>> {
+    let now = clock::now();
+    let mut results = Vec::with_capacity(objects.len());
+    for object in objects {
+        results.push(retention::check(meta_src, bucket, object, now).await);
+    }
+    results
+}
@@ -0,0 +1,62 @@
+use crab_vault::{
+    auth::{HttpMethod, policy::PolicyEngine},
+    engine::builder::DEFAULT_CONTENT_TYPE,
+};
+
+use crate::{
+    app_config::data::ContentSniff,
+    error::api::{ApiError, ClientError},
+};
+
+/// 推断 content type 时最多读取的请求体字节数，足够识别常见格式的魔数
+pub(super) const SNIFF_LEN: usize = 8192;
+
+/// ## 按照 `mode` 决定 object 的 content type
+///
+/// `declared` 是请求中给出的 `Content-Type`，`head` 是数据的开头，参见 [`SNIFF_LEN`]
+pub(super) fn resolve(
+    mode: ContentSniff,
+    declared: Option<&str>,
+    object_name: &str,
+    head: &[u8],
+) -> String {
+    let sniffed = || {
+        infer::get(head)
+            .map(|v| v.mime_type())
+            .or_else(|| mime_guess::from_path(object_name).first_raw())
+    };
+
+    let resolved = match mode {
+        ContentSniff::Off => declared,
+        ContentSniff::Fallback => declared
+            .filter(|v| *v != DEFAULT_CONTENT_TYPE)
+            .or_else(sniffed)
+            .or(declared),
+        ContentSniff::Always => sniffed().or(declared),
+    };
+    resolved.unwrap_or(DEFAULT_CONTENT_TYPE).to_string()
+}
+
+/// 推断出的 content type 与请求中给出的不同时，检查令牌是否允许以这个类型上传
+///
+/// 中间件只检查了请求头中的 `Content-Type`，所以这里与中间件一样，要求同一条权限同时允许请求体的大小和推断出的类型
+pub(super) fn admit(
+    permission: &PolicyEngine,
+    path: &str,
+    size: u64,
+    declared: Option<&str>,
+    resolved: &str,
+) -> Result<(), ApiError> {
+    if declared == Some(resolved) {
+        return Ok(());
+    }
+
+    let size = usize::try_from(size).unwrap_or(usize::MAX);
+    match permission
+        .matching(HttpMethod::Put, path)
+        .any(|v| v.check_size(size) && v.check_content_type(resolved))
+    {
+        true => Ok(()),
+        false => Err(ApiError::Client(ClientError::InvalidContentType)),
+    }
+}
@@ -0,0 +1,72 @@
+use chrono::{DateTime, Utc};
+use crab_vault::{
+    auth::{HttpMethod, policy::PolicyEngine},
+    engine::ObjectMeta,
+};
+use serde::Serialize;
+
+/// 读取前缀概况的请求使用的查询参数，参见 `GET /{bucket}?summary`
+pub(super) const SUMMARY_QUERY_KEY: &str = "summary";
+
+/// `GET /{bucket}?summary&prefix=...` 的响应体
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub(super) struct PrefixSummary {
+    pub bucket: String,
+    pub prefix: String,
+    pub object_count: u64,
+    pub total_bytes: u64,
+
+    /// 最早修改的 object 的 `updated_at`，没有 object 时不出现
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub oldest: Option<DateTime<Utc>>,
+
+    /// 最近修改的 object 的 `updated_at`，没有 object 时不出现
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub newest: Option<DateTime<Utc>>,
+
+    /// 请求携带的令牌对这个前缀能做什么，没有令牌时不出现
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub access: Option<PrefixAccess>,
+}
+
+/// 与鉴权中间件使用同样的判断，前缀本身当作路径参与匹配
+#[derive(Serialize)]
+pub(super) struct PrefixAccess {
+    pub read: bool,
+    pub write: bool,
+    pub list: bool,
+}
+
+impl PrefixSummary {
+    pub fn new(bucket: &str, prefix: &str) -> Self {
+        Self {
+            bucket: bucket.to_string(),
+            prefix: prefix.to_string(),
+            object_count: 0,
+            total_bytes: 0,
+            oldest: None,
+            newest: None,
+            access: None,
+        }
+    }
+
+    pub fn add(&mut self, meta: &ObjectMeta) {
+        self.object_count += 1;
+        self.total_bytes += meta.size;
+        self.oldest = Some(self.oldest.map_or(meta.updated_at, |v| v.min(meta.updated_at)));
+        self.newest = Some(self.newest.map_or(meta.updated_at, |v| v.max(meta.updated_at)));
+    }
+}
+
+impl PrefixAccess {
+    pub fn of(engine: &PolicyEngine, bucket: &str, prefix: &str) -> Self {
+        let bucket = format!("/{bucket}");
+        let path = format!("{bucket}/{prefix}");
+        Self {
+            read: engine.allows(HttpMethod::Get, &path),
+            write: engine.allows(HttpMethod::Put, &path),
+            list: engine.allows_listing(&bucket, &path),
+        }
+    }
+}
@@ -0,0 +1,14 @@
+use crab_vault::engine::tagging::Tagging;
+
+use crate::error::api::{ApiError, ClientError};
+
+/// 解析并校验 `PUT /{bucket}/{object}?tagging` 的请求体
+pub(super) fn from_body(body: &[u8]) -> Result<Tagging, ApiError> {
+    let tagging: Tagging = serde_json::from_slice(body)?;
+
+    tagging
+        .validate()
+        .map_err(|reason| ApiError::Client(ClientError::InvalidTagging { reason }))?;
+
+    Ok(tagging)
+}
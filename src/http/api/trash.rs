@@ -0,0 +1,101 @@
+use crab_vault::engine::{
+    MetaEngine, ObjectMeta, clock,
+    error::{EngineError, EngineResult},
+    journal::Intent,
+    trash::{self, TrashEntry},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::http::api::{ApiState, quota, retention};
+
+/// `GET /{bucket}?trash` 的响应体
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub(super) struct TrashList {
+    pub bucket: String,
+
+    /// 条目在回收站中保留的秒数，`0` 表示没有开启回收站，已有的条目不会被清除
+    pub retention: u64,
+
+    /// 最近删除的在前
+    pub entries: Vec<TrashEntry>,
+}
+
+/// `POST /{bucket}/{object}?restore` 的查询参数
+#[derive(Deserialize)]
+pub(super) struct RestoreQuery {
+    /// 恢复哪一次删除，即 [`TrashEntry::id`]，不给出时恢复最近的一次
+    pub id: Option<String>,
+}
+
+/// 开启了回收站时把即将删除的 object 复制到回收站，调用者需要持有这个 object 的写锁
+///
+/// object 不存在时什么都不做，由之后的删除照常报告
+pub(super) async fn keep(state: &ApiState, bucket: &str, object: &str) -> EngineResult<()> {
+    if !state.trash.enabled() {
+        return Ok(());
+    }
+
+    let meta = match state.meta_src.read_object_meta(bucket, object).await {
+        Ok(meta) => meta,
+        Err(EngineError::ObjectMetaNotFound { .. }) => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    let now = clock::now();
+    match trash::put(state.data_src.as_ref(), state.meta_src.as_ref(), &meta, now).await {
+        Ok(_) | Err(EngineError::ObjectNotFound { .. }) => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+/// 对每个 object 调用 [`keep`]，结果与 `objects` 一一对应
+pub(super) async fn keep_all(
+    state: &ApiState,
+    bucket: &str,
+    objects: &[String],
+) -> Vec<EngineResult<()>> {
+    let mut results = Vec::with_capacity(objects.len());
+    for object in objects {
+        results.push(keep(state, bucket, object).await);
+    }
+    results
+}
+
+/// 列出 bucket 的回收站，bucket 必须存在
+pub(super) async fn list(state: &ApiState, bucket: &str) -> EngineResult<TrashList> {
+    state.meta_src.read_bucket_meta(bucket).await?;
+
+    Ok(TrashList {
+        bucket: bucket.to_string(),
+        retention: state.trash.retention,
+        entries: trash::list(state.meta_src.as_ref(), bucket).await?,
+    })
+}
+
+/// 把回收站中的条目恢复到原来的位置，覆盖同名的 object，返回恢复之后的元数据
+///
+/// 与上传一样检查配额和被覆盖的 object 的保留，并记录在意图日志中。调用者需要持有这个 object 的写锁
+pub(super) async fn restore(
+    state: &ApiState,
+    bucket: &str,
+    object: &str,
+    id: Option<&str>,
+) -> EngineResult<ObjectMeta> {
+    retention::check(&state.meta_src, bucket, object).await?;
+
+    let entry = trash::find(state.meta_src.as_ref(), bucket, object, id).await?;
+    let meta = trash::read(state.meta_src.as_ref(), bucket, &entry).await?;
+    let _quota = quota::check(state, &meta).await?;
+
+    let intent = state.begin(Intent::put(&meta)).await?;
+    trash::restore(
+        state.data_src.as_ref(),
+        state.meta_src.as_ref(),
+        &entry,
+        &meta,
+    )
+    .await?;
+    state.finish(intent).await?;
+
+    Ok(meta)
+}
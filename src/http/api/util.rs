@@ -1,5 +1,8 @@
+use axum::http::{HeaderMap, header::ACCEPT};
 use crab_vault::engine::error::{EngineError, EngineResult};
 
+use crate::http::api::{response::APPLICATION_NDJSON, xml::APPLICATION_XML};
+
 pub fn merge_json_object(
     new: serde_json::Value,
     old: serde_json::Value,
@@ -32,3 +35,50 @@ pub fn merge_json_object(
 
     Ok(Value::Object(old))
 }
+
+/// ## 列表响应的格式，由请求的 `Accept` 决定
+///
+/// 按照 `q` 值选择服务端支持的格式，`q` 相同时先出现的优先；没有 `Accept`、只有 `*/*`
+/// 或者没有支持的格式时返回 JSON，与以前的客户端保持兼容
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ResponseFormat {
+    Json,
+
+    /// 与 S3 的 `ListObjectsV2`、`ListBuckets` 相同的 XML，参见 [`xml`](super::xml)
+    Xml,
+
+    /// 每一行是一条元数据，边读取边返回，不分页，参见 [`NdjsonResponse`](super::response::NdjsonResponse)
+    Ndjson,
+}
+
+impl ResponseFormat {
+    pub fn negotiate(headers: &HeaderMap) -> Self {
+        let mut chosen = (Self::Json, 0.0);
+
+        let ranges = headers
+            .get_all(ACCEPT)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','));
+        for range in ranges {
+            let mut parts = range.split(';');
+            let format = match parts.next().unwrap_or_default().trim() {
+                v if v.eq_ignore_ascii_case(APPLICATION_NDJSON) => Self::Ndjson,
+                v if v.eq_ignore_ascii_case(APPLICATION_XML) => Self::Xml,
+                v if v.eq_ignore_ascii_case("text/xml") => Self::Xml,
+                v if v.eq_ignore_ascii_case("application/json") => Self::Json,
+                _ => continue,
+            };
+            // 无法解析的 `q` 按照 1 处理，`q=0` 表示明确拒绝
+            let q = parts
+                .filter_map(|v| v.trim().strip_prefix("q="))
+                .find_map(|v| v.trim().parse::<f32>().ok())
+                .unwrap_or(1.0);
+            if q > chosen.1 {
+                chosen = (format, q);
+            }
+        }
+
+        chosen.0
+    }
+}
@@ -0,0 +1,137 @@
+//! # S3 风格的 XML 列表
+//!
+//! 只用于 `Accept: application/xml` 的列表请求，元素与 S3 的 `ListObjectsV2`、`ListBuckets` 的响应相同，
+//! 所以按照 S3 解析列表的工具可以直接使用。用户元数据、标签等 S3 的列表中没有的字段不会出现
+
+use std::fmt::Write;
+
+use axum::{
+    http::{StatusCode, header::CONTENT_TYPE},
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, SecondsFormat, Utc};
+use crab_vault::engine::list::{BucketPage, ListObjectsQuery, MAX_KEYS, ObjectPage};
+
+/// XML 的媒体类型
+pub const APPLICATION_XML: &str = "application/xml";
+
+const S3_NAMESPACE: &str = "http://s3.amazonaws.com/doc/2006-03-01/";
+
+/// 已经生成的 XML 文档
+pub struct XmlResponse(String);
+
+/// 逐个写入元素，元素的文本会被转义
+struct XmlWriter(String);
+
+impl XmlWriter {
+    fn new(root: &str) -> Self {
+        let mut xml = String::from(r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+        let _ = write!(xml, r#"<{root} xmlns="{S3_NAMESPACE}">"#);
+        Self(xml)
+    }
+
+    fn open(&mut self, name: &str) -> &mut Self {
+        let _ = write!(self.0, "<{name}>");
+        self
+    }
+
+    fn close(&mut self, name: &str) -> &mut Self {
+        let _ = write!(self.0, "</{name}>");
+        self
+    }
+
+    fn element(&mut self, name: &str, text: impl ToString) -> &mut Self {
+        self.open(name);
+        escape_into(&mut self.0, &text.to_string());
+        self.close(name)
+    }
+
+    /// 值为 [`None`] 时不写入这个元素
+    fn optional(&mut self, name: &str, text: Option<impl ToString>) -> &mut Self {
+        if let Some(text) = text {
+            self.element(name, text);
+        }
+        self
+    }
+
+    fn finish(mut self, root: &str) -> XmlResponse {
+        self.close(root);
+        XmlResponse(self.0)
+    }
+}
+
+fn escape_into(xml: &mut String, text: &str) {
+    for c in text.chars() {
+        match c {
+            '&' => xml.push_str("&amp;"),
+            '<' => xml.push_str("&lt;"),
+            '>' => xml.push_str("&gt;"),
+            '"' => xml.push_str("&quot;"),
+            '\'' => xml.push_str("&apos;"),
+            c => xml.push(c),
+        }
+    }
+}
+
+fn timestamp(time: &DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+/// `GET /{bucket}` 的一页，对应 S3 的 `ListBucketResult`
+pub fn list_objects(bucket: &str, query: &ListObjectsQuery, page: &ObjectPage) -> XmlResponse {
+    const ROOT: &str = "ListBucketResult";
+
+    let mut xml = XmlWriter::new(ROOT);
+    xml.element("Name", bucket)
+        .element("Prefix", query.prefix.as_deref().unwrap_or_default())
+        .optional("Delimiter", query.delimiter.as_deref())
+        .element("MaxKeys", query.max_keys.unwrap_or(MAX_KEYS).min(MAX_KEYS))
+        .element("KeyCount", page.objects.len() + page.common_prefixes.len())
+        .element("IsTruncated", page.is_truncated)
+        .optional("ContinuationToken", query.continuation_token.as_deref())
+        .optional(
+            "NextContinuationToken",
+            page.next_continuation_token.as_deref(),
+        );
+
+    for meta in &page.objects {
+        xml.open("Contents")
+            .element("Key", &meta.object_name)
+            .element("LastModified", timestamp(&meta.updated_at))
+            .element("ETag", &meta.etag)
+            .element("Size", meta.size)
+            .element("StorageClass", "STANDARD")
+            .close("Contents");
+    }
+    for prefix in &page.common_prefixes {
+        xml.open("CommonPrefixes")
+            .element("Prefix", prefix)
+            .close("CommonPrefixes");
+    }
+
+    xml.finish(ROOT)
+}
+
+/// `GET /` 的一页，对应 S3 的 `ListAllMyBucketsResult`
+pub fn list_buckets(page: &BucketPage) -> XmlResponse {
+    const ROOT: &str = "ListAllMyBucketsResult";
+
+    let mut xml = XmlWriter::new(ROOT);
+    xml.open("Buckets");
+    for meta in &page.buckets {
+        xml.open("Bucket")
+            .element("Name", &meta.name)
+            .element("CreationDate", timestamp(&meta.created_at))
+            .close("Bucket");
+    }
+    xml.close("Buckets")
+        .optional("ContinuationToken", page.next_continuation_token.as_deref());
+
+    xml.finish(ROOT)
+}
+
+impl IntoResponse for XmlResponse {
+    fn into_response(self) -> Response {
+        (StatusCode::OK, [(CONTENT_TYPE, APPLICATION_XML)], self.0).into_response()
+    }
+}
@@ -0,0 +1,188 @@
+//! # 请求体的摘要
+//!
+//! 接收请求体的同时逐块计算 etag 和客户端声明的摘要，不需要在接收完之后再遍历一次数据。
+//! etag 就是 sha256 摘要，所以声明了 sha256 时不会重复计算
+
+use std::time::{Duration, Instant};
+
+use axum::http::{HeaderMap, HeaderName};
+use base64::{Engine, prelude::BASE64_STANDARD};
+use crab_vault::engine::builder::EtagHasher;
+use md5::{Digest, Md5};
+
+use crate::{
+    error::api::{ApiError, ClientError},
+    http::{CONTENT_MD5, X_CRAB_VAULT_CONTENT_SHA256},
+};
+
+/// 客户端可以声明的摘要算法
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DigestAlgorithm {
+    Sha256,
+    Md5,
+}
+
+/// 逐块计算一种摘要
+enum Hasher {
+    Md5(Md5),
+}
+
+/// 逐块计算请求体的 etag，同时校验客户端声明的摘要
+#[derive(Default)]
+pub struct BodyHasher {
+    etag: EtagHasher,
+
+    /// etag 以外需要计算的摘要
+    others: Vec<(DigestAlgorithm, Hasher)>,
+
+    /// 客户端声明的摘要
+    expected: Vec<(DigestAlgorithm, Vec<u8>)>,
+
+    /// 在计算摘要上花费的时间
+    cost: Duration,
+}
+
+impl DigestAlgorithm {
+    /// 声明这种摘要的请求头，也用在错误信息中
+    pub const fn header(self) -> &'static str {
+        match self {
+            DigestAlgorithm::Sha256 => "X-Crab-Vault-Content-Sha256",
+            DigestAlgorithm::Md5 => "Content-MD5",
+        }
+    }
+
+    fn header_name(self) -> HeaderName {
+        match self {
+            DigestAlgorithm::Sha256 => X_CRAB_VAULT_CONTENT_SHA256,
+            DigestAlgorithm::Md5 => CONTENT_MD5,
+        }
+    }
+
+    /// 摘要的字节数
+    pub const fn len(self) -> usize {
+        match self {
+            DigestAlgorithm::Sha256 => 32,
+            DigestAlgorithm::Md5 => 16,
+        }
+    }
+
+    /// 接受标准 base64 编码或者十六进制编码，`Content-MD5` 通常是前者，`sha256sum` 的输出是后者
+    pub fn decode(self, value: &str) -> Result<Vec<u8>, ApiError> {
+        let value = value.trim();
+        let digest = match value.len() == self.len() * 2 {
+            true => decode_hex(value),
+            false => BASE64_STANDARD.decode(value).ok(),
+        };
+
+        match digest {
+            Some(digest) if digest.len() == self.len() => Ok(digest),
+            _ => Err(ApiError::Client(ClientError::InvalidDigest {
+                header: self.header(),
+            })),
+        }
+    }
+}
+
+impl Hasher {
+    /// sha256 由 etag 的计算覆盖，不需要单独的 hasher
+    fn new(algorithm: DigestAlgorithm) -> Option<Self> {
+        match algorithm {
+            DigestAlgorithm::Sha256 => None,
+            DigestAlgorithm::Md5 => Some(Hasher::Md5(Md5::new())),
+        }
+    }
+
+    fn update(&mut self, chunk: &[u8]) {
+        match self {
+            Hasher::Md5(md5) => md5.update(chunk),
+        }
+    }
+
+    fn finish(self) -> Vec<u8> {
+        match self {
+            Hasher::Md5(md5) => md5.finalize().to_vec(),
+        }
+    }
+}
+
+impl BodyHasher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 按照请求头中声明的摘要创建，格式不正确时拒绝
+    pub fn from_headers(headers: &HeaderMap) -> Result<Self, ApiError> {
+        let mut hasher = Self::new();
+        for algorithm in [DigestAlgorithm::Md5, DigestAlgorithm::Sha256] {
+            let Some(value) = headers.get(algorithm.header_name()) else {
+                continue;
+            };
+            let value = value.to_str().map_err(|_| {
+                ApiError::Client(ClientError::InvalidDigest {
+                    header: algorithm.header(),
+                })
+            })?;
+            hasher = hasher.expect(algorithm, algorithm.decode(value)?);
+        }
+        Ok(hasher)
+    }
+
+    /// 要求请求体的 `algorithm` 摘要为 `digest`，在 [`finish`](Self::finish) 时校验
+    pub fn expect(mut self, algorithm: DigestAlgorithm, digest: Vec<u8>) -> Self {
+        if !self.others.iter().any(|(v, _)| *v == algorithm)
+            && let Some(hasher) = Hasher::new(algorithm)
+        {
+            self.others.push((algorithm, hasher));
+        }
+        self.expected.push((algorithm, digest));
+        self
+    }
+
+    pub fn update(&mut self, chunk: &[u8]) {
+        let start = Instant::now();
+        self.etag.update(chunk);
+        for (_, hasher) in &mut self.others {
+            hasher.update(chunk);
+        }
+        self.cost += start.elapsed();
+    }
+
+    /// 到目前为止在计算摘要上花费的时间，不包括接收请求体的时间
+    pub fn cost(&self) -> Duration {
+        self.cost
+    }
+
+    /// 校验所有声明的摘要，一致时返回 etag
+    pub fn finish(self) -> Result<String, ApiError> {
+        let etag = self.etag.finish();
+        let computed: Vec<_> = self
+            .others
+            .into_iter()
+            .map(|(algorithm, hasher)| (algorithm, hasher.finish()))
+            .collect();
+
+        for (algorithm, expected) in self.expected {
+            // etag 就是 sha256 摘要的标准 base64 编码
+            let matched = match algorithm {
+                DigestAlgorithm::Sha256 => BASE64_STANDARD.encode(&expected) == etag,
+                _ => computed
+                    .iter()
+                    .any(|(v, digest)| *v == algorithm && *digest == expected),
+            };
+            if !matched {
+                return Err(ApiError::Client(ClientError::BadDigest {
+                    header: algorithm.header(),
+                }));
+            }
+        }
+
+        Ok(etag)
+    }
+}
+
+fn decode_hex(value: &str) -> Option<Vec<u8>> {
+    (0..value.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(value.get(i..i + 2)?, 16).ok())
+        .collect()
+}
@@ -1,2 +1,7 @@
 pub(super) mod auth;
-pub(super) mod meta;
\ No newline at end of file
+pub(super) mod condition;
+pub(super) mod copy;
+pub(super) mod meta;
+pub(super) mod path;
+pub(super) mod spool;
+pub(super) mod sse;
\ No newline at end of file
@@ -4,26 +4,29 @@ use axum::{
     response::{IntoResponse, Response},
 };
 use bytes::Bytes;
-use crab_vault::auth::{Permission, error::AuthError};
+use crab_vault::auth::{HttpMethod, error::AuthError, policy::PolicyEngine};
 
-use crate::error::api::{ApiError, ClientError};
+use crate::error::{
+    api::{ApiError, ClientError},
+    context::RequestError,
+};
 
-#[allow(dead_code)]
-pub struct PermissionExtractor(pub Permission);
+/// 鉴权中间件放入请求的 extensions 中的 [`PolicyEngine`]
+pub struct PermissionExtractor(pub PolicyEngine);
 
 impl<S> FromRequestParts<S> for PermissionExtractor
 where
     S: Send + Sync,
 {
-    type Rejection = AuthError;
+    type Rejection = RequestError;
 
     async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
         parts
             .extensions
-            .get::<Permission>()
+            .get::<PolicyEngine>()
             .cloned()
             .map(PermissionExtractor)
-            .ok_or(AuthError::InvalidToken)
+            .ok_or(AuthError::InvalidToken.into())
     }
 }
 
@@ -36,7 +39,7 @@ where
     type Rejection = Response; // 发生错误时直接返回 Response
 
     async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
-        let permission = match req.extensions().get::<Permission>() {
+        let permission = match req.extensions().get::<PolicyEngine>() {
             Some(p) => p,
             // 如果没有找到权限，这是一个服务器内部错误。
             // 意味着这个提取器被用在了没有被 AuthMiddleware 保护的路由上。
@@ -44,6 +47,7 @@ where
             None => unreachable!(),
         }
         .clone();
+        let (method, path) = (HttpMethod::from(req.method()), req.uri().path().to_string());
 
         let body_bytes = match Bytes::from_request(req, state).await {
             Ok(bytes) => bytes,
@@ -53,7 +57,7 @@ where
             }
         };
 
-        if !permission.compile().check_size(body_bytes.len()) {
+        if !permission.check_size(method, &path, body_bytes.len()) {
             return Err(ApiError::Client(ClientError::BodyTooLarge).into_response());
         }
 
@@ -0,0 +1,93 @@
+use axum::{
+    extract::FromRequestParts,
+    http::{
+        HeaderMap,
+        header::{IF_MATCH, IF_NONE_MATCH},
+        request::Parts,
+    },
+};
+use crab_vault::engine::{
+    ObjectMeta,
+    error::{EngineError, EngineResult},
+};
+
+use crate::error::api::{ApiError, ClientError};
+
+/// 写入 object 的前置条件，来自 `If-None-Match` 或者 `If-Match`
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum WriteCondition {
+    /// 没有条件，已经存在的 object 会被覆盖
+    #[default]
+    Always,
+
+    /// `If-None-Match: *`，只在 object 不存在时写入
+    IfAbsent,
+
+    /// `If-Match: <etag>`，只在 object 存在并且 etag 相同时写入
+    IfMatch(String),
+}
+
+impl WriteCondition {
+    /// `If-None-Match` 只支持 `*`，`If-Match` 只支持单个 etag，两者不能同时出现
+    pub fn from_headers(headers: &HeaderMap) -> Result<Self, ApiError> {
+        let unsupported = || ApiError::Client(ClientError::UnsupportedPrecondition);
+
+        match (headers.get(IF_NONE_MATCH), headers.get(IF_MATCH)) {
+            (None, None) => Ok(Self::Always),
+            (Some(value), None) => match value.to_str()?.trim() {
+                "*" => Ok(Self::IfAbsent),
+                _ => Err(unsupported()),
+            },
+            (None, Some(value)) => {
+                // etag 可以带引号，也可以不带
+                let etag = value.to_str()?.trim().trim_matches('"');
+                match etag.is_empty() || etag == "*" || etag.contains(',') {
+                    true => Err(unsupported()),
+                    false => Ok(Self::IfMatch(etag.to_string())),
+                }
+            }
+            (Some(_), Some(_)) => Err(unsupported()),
+        }
+    }
+
+    /// `If-Match` 中的 etag
+    pub fn expected_etag(&self) -> Option<&str> {
+        match self {
+            Self::IfMatch(etag) => Some(etag),
+            _ => None,
+        }
+    }
+
+    /// 检查读出的已有元数据，满足条件时原样返回
+    ///
+    /// `If-Match` 中的 etag 与已有的 object 不同，或者 object 不存在时返回
+    /// [`EtagMismatch`](EngineError::EtagMismatch)，其他条件在这里总是满足
+    pub fn check(&self, current: EngineResult<ObjectMeta>) -> EngineResult<ObjectMeta> {
+        let Some(expected) = self.expected_etag() else {
+            return current;
+        };
+
+        match current {
+            Ok(current) if current.etag == expected => Ok(current),
+            Ok(current) => Err(EngineError::EtagMismatch {
+                bucket: current.bucket_name,
+                object: current.object_name,
+            }),
+            Err(EngineError::ObjectMetaNotFound { bucket, object }) => {
+                Err(EngineError::EtagMismatch { bucket, object })
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
+impl<S> FromRequestParts<S> for WriteCondition
+where
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Self::from_headers(&parts.headers)
+    }
+}
@@ -0,0 +1,97 @@
+use axum::{
+    extract::FromRequestParts,
+    http::{HeaderMap, request::Parts},
+};
+use crab_vault::engine::name;
+
+use crate::{
+    error::api::{ApiError, ClientError},
+    http::{X_CRAB_VAULT_COPY_SOURCE, X_CRAB_VAULT_METADATA_DIRECTIVE},
+};
+
+/// 服务端复制的源 object，来自 `X-Crab-Vault-Copy-Source: /{bucket}/{object}`
+#[derive(Debug)]
+pub struct CopySource {
+    pub bucket_name: String,
+    pub object_name: String,
+}
+
+/// 复制时如何处理元数据，来自 `X-Crab-Vault-Metadata-Directive`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MetadataDirective {
+    /// 沿用源 object 的 content type 和用户元数据
+    #[default]
+    Copy,
+
+    /// 使用这个请求中的 content type 和用户元数据
+    Replace,
+}
+
+/// 如果请求是一个服务端复制，提取出源 object 和元数据的处理方式
+pub struct CopyExtractor(pub Option<(CopySource, MetadataDirective)>);
+
+impl CopySource {
+    /// 从请求头中解析源 object，没有这个头部时返回 [`None`]
+    ///
+    /// bucket 和 object 的名称必须合法，参见 [`name`]
+    pub fn from_headers(headers: &HeaderMap) -> Result<Option<Self>, ApiError> {
+        let Some(value) = headers.get(X_CRAB_VAULT_COPY_SOURCE) else {
+            return Ok(None);
+        };
+
+        let invalid = || ApiError::Client(ClientError::InvalidCopySource);
+
+        let (bucket_name, object_name) = value
+            .to_str()?
+            .strip_prefix('/')
+            .and_then(|v| v.split_once('/'))
+            .ok_or_else(invalid)?;
+
+        if name::validate_bucket_name(bucket_name).is_err()
+            || name::validate_object_name(object_name).is_err()
+        {
+            return Err(invalid());
+        }
+
+        Ok(Some(Self {
+            bucket_name: bucket_name.to_string(),
+            object_name: object_name.to_string(),
+        }))
+    }
+
+    /// 源 object 的请求路径，用于权限校验
+    pub fn path(&self) -> String {
+        format!("/{}/{}", self.bucket_name, self.object_name)
+    }
+}
+
+impl MetadataDirective {
+    pub fn from_headers(headers: &HeaderMap) -> Result<Self, ApiError> {
+        let Some(value) = headers.get(X_CRAB_VAULT_METADATA_DIRECTIVE) else {
+            return Ok(Self::default());
+        };
+
+        match value.to_str()? {
+            v if v.eq_ignore_ascii_case("COPY") => Ok(Self::Copy),
+            v if v.eq_ignore_ascii_case("REPLACE") => Ok(Self::Replace),
+            _ => Err(ApiError::Client(ClientError::InvalidMetadataDirective)),
+        }
+    }
+}
+
+impl<S> FromRequestParts<S> for CopyExtractor
+where
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let Some(source) = CopySource::from_headers(&parts.headers)? else {
+            return Ok(Self(None));
+        };
+
+        let directive = MetadataDirective::from_headers(&parts.headers)?;
+
+        Ok(Self(Some((source, directive))))
+    }
+}
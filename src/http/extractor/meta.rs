@@ -1,18 +1,26 @@
 use axum::{
     extract::FromRequestParts,
-    http::{header, request::Parts},
+    http::{HeaderName, header, request::Parts},
+    response::{IntoResponse, Response},
 };
 use base64::{Engine, prelude::BASE64_STANDARD};
-use bytes::Bytes;
-use chrono::Utc;
-use crab_vault::engine::ObjectMeta;
-use crab_vault_engine::BucketMeta;
+use chrono::{DateTime, Duration, Utc};
+use crab_vault::engine::{
+    BucketMeta, ObjectMeta, builder::DEFAULT_CONTENT_TYPE, clock, crypto, error::EngineResult, name,
+};
+use percent_encoding::percent_decode_str;
 use serde_json::{Value, json};
-use sha2::{Digest, Sha256};
 
 use crate::{
-    error::api::{ApiError, ClientError},
-    http::X_CRAB_VAULT_USER_META,
+    error::{
+        api::{ApiError, ClientError},
+        context::RequestError,
+    },
+    http::{
+        X_CRAB_VAULT_EXPIRES_AT, X_CRAB_VAULT_MAX_BYTES, X_CRAB_VAULT_MAX_OBJECTS,
+        X_CRAB_VAULT_TTL, X_CRAB_VAULT_USER_META, extractor::spool::SpooledBody,
+    },
+    logger,
 };
 
 /// 从请求头中提取元数据，用于创建新的 ObjectMeta。
@@ -20,60 +28,63 @@ use crate::{
 pub struct ObjectMetaExtractor {
     pub bucket_name: String,
     pub object_name: String,
-    pub content_type: String,
+
+    /// 请求中没有 `Content-Type` 时为 [`None`]，写入时使用 `application/octet-stream`
+    pub content_type: Option<String>,
     pub user_meta: Value,
+
+    /// `X-Crab-Vault-Expires-At` 或者 `X-Crab-Vault-Ttl`，参见 [`expires_at_of`]
+    pub expires_at: Option<DateTime<Utc>>,
 }
 
 pub struct BuckeMetaExtractor {
     pub name: String,
     pub user_meta: Value,
+
+    /// `X-Crab-Vault-Max-Bytes`，参见 [`quota_of`]
+    pub max_bytes: Option<Option<u64>>,
+
+    /// `X-Crab-Vault-Max-Objects`，参见 [`quota_of`]
+    pub max_objects: Option<Option<u64>>,
 }
 
 impl<S> FromRequestParts<S> for ObjectMetaExtractor
 where
     S: Send + Sync,
 {
-    type Rejection = ApiError;
+    type Rejection = Response;
 
     async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
-        // 从路径中获取 bucket 和 object 名称
-        let path_params: Vec<_> = parts
+        // 从路径中获取 bucket 和 object 名称，object 名称末尾的 `/` 是名称的一部分，参见目录标记
+        // 与其他接口使用的 `Path` 一样先解码，否则上传的 `a%20b` 无法通过 `a%20b` 读出
+        let (bucket_name, object_name) = parts
             .uri
             .path()
-            .split('/')
-            .filter(|s| !s.is_empty())
-            .collect();
-
-        if path_params.len() < 2 {
-            return Err(ApiError::Client(ClientError::UriInvalid));
-        }
-
-        let bucket_name = path_params[0].to_string();
-        let object_name = path_params[1..].join("/");
+            .trim_start_matches('/')
+            .split_once('/')
+            .filter(|(bucket, object)| !bucket.is_empty() && !object.is_empty())
+            .and_then(|(bucket, object)| Some((decode(bucket)?, decode(object)?)))
+            .ok_or(ApiError::Client(ClientError::UriInvalid).into_response())?;
+        name::validate_bucket_name(&bucket_name).map_err(RequestError::from)?;
+        name::validate_object_name(&object_name).map_err(RequestError::from)?;
+        logger::record_target(&bucket_name, Some(&object_name));
 
+        // 按理说 AuthMiddleware 会拦截没有携带 content type 的请求，除非开启了推断，参见 `data.sniff`
         let content_type = parts
             .headers
             .get(header::CONTENT_TYPE)
             .and_then(|v| v.to_str().ok())
-            // octet-stream 是默认值，如果没有提供 content type
-            // 按理说 AuthMiddleware 会拦截没有携带 content type 的请求
-            .unwrap_or("application/octet-stream")
-            .to_string();
-
-        let user_meta = match parts.headers.get(X_CRAB_VAULT_USER_META) {
-            Some(header_value) => {
-                let raw_value = header_value.to_str()?;
-                let decoded = BASE64_STANDARD.decode(raw_value)?;
-                serde_json::from_slice(&decoded)?
-            }
-            None => json!({}),
-        };
+            .map(str::to_string);
+
+        let user_meta = user_meta_of(parts).map_err(IntoResponse::into_response)?;
+        let expires_at = expires_at_of(parts).map_err(IntoResponse::into_response)?;
 
         Ok(Self {
             bucket_name,
             object_name,
             content_type,
             user_meta,
+            expires_at,
         })
     }
 }
@@ -82,7 +93,7 @@ impl<S> FromRequestParts<S> for BuckeMetaExtractor
 where
     S: Send + Sync,
 {
-    type Rejection = ApiError;
+    type Rejection = Response;
 
     async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
         let name = parts
@@ -90,41 +101,166 @@ where
             .path()
             .split('/')
             .find(|s| !s.is_empty())
-            .ok_or(ApiError::Client(ClientError::UriInvalid))?
-            .to_string();
-
-        let user_meta = match parts.headers.get(X_CRAB_VAULT_USER_META) {
-            Some(header_value) => {
-                let raw_value = header_value.to_str()?;
-                let decoded = BASE64_STANDARD.decode(raw_value)?;
-                serde_json::from_slice(&decoded)?
-            }
-            None => json!({}),
-        };
-
-        Ok(Self { name, user_meta })
+            .and_then(decode)
+            .ok_or(ApiError::Client(ClientError::UriInvalid).into_response())?;
+        name::validate_bucket_name(&name).map_err(RequestError::from)?;
+        logger::record_target(&name, None);
+
+        let user_meta = user_meta_of(parts).map_err(IntoResponse::into_response)?;
+        let max_bytes = quota_of(parts, X_CRAB_VAULT_MAX_BYTES, "X-Crab-Vault-Max-Bytes")
+            .map_err(IntoResponse::into_response)?;
+        let max_objects = quota_of(parts, X_CRAB_VAULT_MAX_OBJECTS, "X-Crab-Vault-Max-Objects")
+            .map_err(IntoResponse::into_response)?;
+
+        Ok(Self {
+            name,
+            user_meta,
+            max_bytes,
+            max_objects,
+        })
+    }
+}
+
+/// 百分号解码路径中的一段，结果不是合法的 UTF-8 时返回 [`None`]
+fn decode(segment: &str) -> Option<String> {
+    percent_decode_str(segment)
+        .decode_utf8()
+        .ok()
+        .map(|v| v.into_owned())
+}
+
+/// `X-Crab-Vault-User-Meta` 中 base64 编码的 JSON，没有这个头部时为空对象
+///
+/// 以 [`crypto::RESERVED_META_PREFIX`] 开头的键保留给服务端，客户端不能设置
+fn user_meta_of(parts: &Parts) -> Result<Value, ApiError> {
+    let user_meta: Value = match parts.headers.get(X_CRAB_VAULT_USER_META) {
+        Some(header_value) => {
+            let raw_value = header_value.to_str()?;
+            let decoded = BASE64_STANDARD.decode(raw_value)?;
+            serde_json::from_slice(&decoded)?
+        }
+        None => json!({}),
+    };
+
+    if let Some(key) = user_meta
+        .as_object()
+        .and_then(|v| v.keys().find(|key| crypto::is_reserved_meta_key(key)))
+    {
+        return Err(ApiError::Client(ClientError::ReservedMetaKey { key: key.clone() }));
+    }
+
+    Ok(user_meta)
+}
+
+/// 配额头部，没有这个头部时为 [`None`]，值为 `none` 时为 `Some(None)`，表示取消这个配额
+fn quota_of(
+    parts: &Parts,
+    header: HeaderName,
+    display: &'static str,
+) -> Result<Option<Option<u64>>, ApiError> {
+    let Some(value) = parts.headers.get(header) else {
+        return Ok(None);
+    };
+
+    match value.to_str()?.trim() {
+        v if v.eq_ignore_ascii_case("none") => Ok(Some(None)),
+        v => v
+            .parse()
+            .map(|v| Some(Some(v)))
+            .map_err(|_| ApiError::Client(ClientError::InvalidQuota { header: display })),
+    }
+}
+
+/// ## object 的过期时间，两个头部都没有时为 [`None`]
+///
+/// - `X-Crab-Vault-Expires-At`：RFC 3339 或者 RFC 2822 格式的时间
+/// - `X-Crab-Vault-Ttl`：从现在开始的秒数，必须大于 0
+///
+/// 两者不能同时给出，过期时间也不能早于现在
+fn expires_at_of(parts: &Parts) -> Result<Option<DateTime<Utc>>, ApiError> {
+    let invalid = |reason| ApiError::Client(ClientError::InvalidExpiry { reason });
+    let now = clock::now();
+
+    let expires_at = match (
+        parts.headers.get(X_CRAB_VAULT_EXPIRES_AT),
+        parts.headers.get(X_CRAB_VAULT_TTL),
+    ) {
+        (None, None) => return Ok(None),
+        (Some(_), Some(_)) => {
+            return Err(invalid(
+                "`X-Crab-Vault-Expires-At` and `X-Crab-Vault-Ttl` are mutually exclusive",
+            ));
+        }
+        (Some(value), None) => {
+            let value = value.to_str()?.trim();
+            DateTime::parse_from_rfc3339(value)
+                .or_else(|_| DateTime::parse_from_rfc2822(value))
+                .map_err(|_| invalid("`X-Crab-Vault-Expires-At` should be an RFC 3339 time"))?
+                .to_utc()
+        }
+        (None, Some(value)) => {
+            let ttl = value
+                .to_str()?
+                .trim()
+                .parse::<i64>()
+                .ok()
+                .filter(|v| *v > 0)
+                .and_then(Duration::try_seconds)
+                .ok_or(invalid("`X-Crab-Vault-Ttl` should be a positive number of seconds"))?;
+            now.checked_add_signed(ttl)
+                .ok_or(invalid("`X-Crab-Vault-Ttl` is too large"))?
+        }
+    };
+
+    match expires_at > now {
+        true => Ok(Some(expires_at)),
+        false => Err(invalid("the expiry time has already passed")),
     }
 }
 
 impl ObjectMetaExtractor {
     /// 结合请求体数据，最终生成完整的 [`ObjectMeta`]
-    pub fn into_meta(self, data: &Bytes) -> ObjectMeta {
-        ObjectMeta {
-            object_name: self.object_name,
-            bucket_name: self.bucket_name,
-            size: data.len() as u64,
-            content_type: self.content_type,
-            etag: BASE64_STANDARD.encode(Sha256::digest(data)),
-            created_at: Utc::now(),
-            updated_at: Utc::now(),
-            user_meta: self.user_meta,
-        }
+    /// 大小和 etag 在接收请求体时已经计算好了
+    pub fn into_meta(self, body: &SpooledBody) -> EngineResult<ObjectMeta> {
+        ObjectMeta::builder()
+            .bucket_name(self.bucket_name)
+            .object_name(self.object_name)
+            .content_type(
+                self.content_type
+                    .unwrap_or_else(|| DEFAULT_CONTENT_TYPE.to_string()),
+            )
+            .user_meta(self.user_meta)
+            .expires_at(self.expires_at)
+            .size(body.len())
+            .etag(body.etag())
+            .build()
     }
 }
 
 impl BuckeMetaExtractor {
-    pub fn into_meta(self) -> BucketMeta {
-        let Self { name, user_meta } = self;
-        BucketMeta::new(name, user_meta)
+    /// 请求中没有给出的配额为空
+    pub fn into_meta(self) -> EngineResult<BucketMeta> {
+        let Self {
+            name,
+            user_meta,
+            max_bytes,
+            max_objects,
+        } = self;
+        BucketMeta::builder()
+            .name(name)
+            .user_meta(user_meta)
+            .max_bytes(max_bytes.flatten())
+            .max_objects(max_objects.flatten())
+            .build()
+    }
+
+    /// 把请求中给出的配额写入 `meta`，没有给出的保持不变
+    pub fn apply_quota(&self, meta: &mut BucketMeta) {
+        if let Some(max_bytes) = self.max_bytes {
+            meta.max_bytes = max_bytes;
+        }
+        if let Some(max_objects) = self.max_objects {
+            meta.max_objects = max_objects;
+        }
     }
 }
@@ -0,0 +1,52 @@
+use axum::{
+    extract::{FromRequestParts, Path},
+    http::request::Parts,
+    response::{IntoResponse, Response},
+};
+use crab_vault::engine::name;
+
+use crate::{error::context::RequestError, logger};
+
+/// 路径中的 bucket 名称，不合法时拒绝，参见 [`name`]
+pub struct BucketPath(pub String);
+
+/// 路径中的 bucket 和 object 名称，不合法时拒绝，参见 [`name`]
+///
+/// 与 [`Path`] 一样，二者都是百分号解码之后的值，所以 `%2e%2e` 同样会被拒绝
+pub struct ObjectPath(pub String, pub String);
+
+impl<S> FromRequestParts<S> for BucketPath
+where
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Path(bucket_name) = Path::<String>::from_request_parts(parts, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+
+        name::validate_bucket_name(&bucket_name).map_err(RequestError::from)?;
+        logger::record_target(&bucket_name, None);
+        Ok(Self(bucket_name))
+    }
+}
+
+impl<S> FromRequestParts<S> for ObjectPath
+where
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Path((bucket_name, object_name)) =
+            Path::<(String, String)>::from_request_parts(parts, state)
+                .await
+                .map_err(IntoResponse::into_response)?;
+
+        name::validate_bucket_name(&bucket_name).map_err(RequestError::from)?;
+        name::validate_object_name(&object_name).map_err(RequestError::from)?;
+        logger::record_target(&bucket_name, Some(&object_name));
+        Ok(Self(bucket_name, object_name))
+    }
+}
@@ -0,0 +1,336 @@
+use std::{
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use axum::{
+    extract::{FromRef, FromRequest, Request},
+    http::header::CONTENT_LENGTH,
+    response::{IntoResponse, Response},
+};
+use bytes::{Bytes, BytesMut};
+use crab_vault::{
+    auth::{HttpMethod, policy::PolicyEngine},
+    engine::{
+        builder::{EtagHasher, compute_etag},
+        crypto::{
+            KeyRing, ObjectEncryption,
+            stream::{SEGMENT_LEN, StreamEncryptor},
+        },
+        error::EngineError,
+    },
+    utils::hashing::HashingWriter,
+};
+use futures::TryStreamExt;
+use tokio::{
+    fs,
+    io::{AsyncReadExt, AsyncWriteExt},
+};
+
+use crate::{
+    app_config::server::BufferingConfig,
+    error::{
+        api::{ApiError, ClientError},
+        context::RequestError,
+    },
+    http::{body_error, digest::BodyHasher},
+};
+
+/// 完整缓冲的请求体，超过 [`BufferingConfig::memory_threshold`] 后写入临时文件
+///
+/// 接收的同时计算大小和 etag，并且在超出令牌或者配置中的大小限制时立即拒绝，不会先读完整个请求体。
+/// 请求头中声明了摘要时同时计算并校验，不一致时拒绝，参见 [`BodyHasher`]
+pub struct SpooledBody {
+    len: u64,
+    etag: String,
+    storage: Storage,
+}
+
+enum Storage {
+    Memory(Bytes),
+    File(SpillFile),
+}
+
+/// 临时文件，drop 时删除
+struct SpillFile(PathBuf);
+
+impl SpooledBody {
+    /// 已经在内存中的数据，例如压缩之后的请求体
+    pub fn from_bytes(data: Bytes) -> Self {
+        Self {
+            len: data.len() as u64,
+            etag: compute_etag(&data),
+            storage: Storage::Memory(data),
+        }
+    }
+
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn etag(&self) -> &str {
+        &self.etag
+    }
+
+    /// 请求体超过阈值时，保存它的临时文件
+    pub fn spilled(&self) -> Option<&Path> {
+        match &self.storage {
+            Storage::Memory(_) => None,
+            Storage::File(file) => Some(&file.0),
+        }
+    }
+
+    /// 完整的请求体，写入了临时文件时需要把它读回内存，只应当用于无法直接使用临时文件的场合
+    pub async fn to_bytes(&self) -> Result<Bytes, EngineError> {
+        match &self.storage {
+            Storage::Memory(data) => Ok(data.clone()),
+            Storage::File(file) => fs::read(&file.0)
+                .await
+                .map(Bytes::from)
+                .map_err(|e| spill_error(e, file)),
+        }
+    }
+
+    /// 请求体开头的至多 `len` 个字节，写入了临时文件时只读取这一部分
+    pub async fn head(&self, len: usize) -> Result<Bytes, EngineError> {
+        match &self.storage {
+            Storage::Memory(data) => Ok(data.slice(..data.len().min(len))),
+            Storage::File(file) => {
+                let mut buffer = Vec::with_capacity(len);
+                fs::File::open(&file.0)
+                    .await
+                    .map_err(|e| spill_error(e, file))?
+                    .take(len as u64)
+                    .read_to_end(&mut buffer)
+                    .await
+                    .map_err(|e| spill_error(e, file))?;
+                Ok(Bytes::from(buffer))
+            }
+        }
+    }
+
+    /// ## 使用新的数据密钥加密，返回加密信息和密文
+    ///
+    /// 写入了临时文件的请求体被逐段加密到另一个临时文件中，不需要读回内存
+    pub async fn encrypt(
+        &self,
+        key_ring: &Arc<KeyRing>,
+    ) -> Result<(ObjectEncryption, SpooledBody), EngineError> {
+        let join_error = |e: tokio::task::JoinError| EngineError::Encryption(e.to_string());
+
+        match &self.storage {
+            Storage::Memory(data) => {
+                let (key_ring, data) = (key_ring.clone(), data.clone());
+                let (encryption, ciphertext) =
+                    tokio::task::spawn_blocking(move || key_ring.seal(&data))
+                        .await
+                        .map_err(join_error)??;
+                Ok((encryption, Self::from_bytes(Bytes::from(ciphertext))))
+            }
+            Storage::File(file) => {
+                let sealed = SpillFile(file.0.with_extension("sealed"));
+                let (data_key, encryptor) = key_ring.encryptor()?;
+                let (src, dst) = (file.0.clone(), sealed.0.clone());
+                let (len, etag) =
+                    tokio::task::spawn_blocking(move || encrypt_file(encryptor, &src, &dst))
+                        .await
+                        .map_err(join_error)??;
+
+                let encryption = ObjectEncryption {
+                    data_key,
+                    stored_etag: etag.clone(),
+                    customer_key_sha256: None,
+                };
+                let sealed = SpooledBody {
+                    len,
+                    etag,
+                    storage: Storage::File(sealed),
+                };
+                Ok((encryption, sealed))
+            }
+        }
+    }
+}
+
+/// 把 `src` 逐段加密到 `dst`，返回密文的长度和 etag
+fn encrypt_file(
+    mut encryptor: StreamEncryptor,
+    src: &Path,
+    dst: &Path,
+) -> Result<(u64, String), EngineError> {
+    let io_error = |error, path: &Path| EngineError::Io {
+        error,
+        path: path.to_string_lossy().to_string(),
+    };
+
+    let mut reader = std::fs::File::open(src).map_err(|e| io_error(e, src))?;
+    let writer = std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(dst)
+        .map_err(|e| io_error(e, dst))?;
+    let mut writer = HashingWriter::new(io::BufWriter::new(writer), EtagHasher::new());
+    let mut emit =
+        |ciphertext: Vec<u8>| writer.write_all(&ciphertext).map_err(|e| io_error(e, dst));
+
+    let mut buffer = vec![0; SEGMENT_LEN];
+    loop {
+        match reader.read(&mut buffer).map_err(|e| io_error(e, src))? {
+            0 => break,
+            read => emit(encryptor.update(&buffer[..read])?)?,
+        }
+    }
+    emit(encryptor.finish()?)?;
+
+    writer.flush().map_err(|e| io_error(e, dst))?;
+    let len = writer.written();
+    let (_, hasher) = writer.into_parts();
+    Ok((len, hasher.finish()))
+}
+
+impl Drop for SpillFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+impl<S> FromRequest<S> for SpooledBody
+where
+    S: Send + Sync,
+    Arc<BufferingConfig>: FromRef<S>,
+{
+    type Rejection = Response;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let config = Arc::<BufferingConfig>::from_ref(state);
+
+        // 与 RestrictedBytes 一样，这个提取器只能用在被 AuthMiddleware 保护的路由上
+        let permission = match req.extensions().get::<PolicyEngine>() {
+            Some(p) => p.clone(),
+            None => unreachable!(),
+        };
+        let method = HttpMethod::from(req.method());
+        let path = req.uri().path().to_string();
+        let too_large = || ApiError::Client(ClientError::BodyTooLarge).into_response();
+        // `server.max_body_size` 已经由 RequestLimitsLayer 检查过了，这里只需要检查令牌的限制
+        let allowed = |len: u64| {
+            permission.check_size(method, &path, usize::try_from(len).unwrap_or(usize::MAX))
+        };
+
+        // 声明的长度已经超出限制时不必读取请求体
+        let declared = req
+            .headers()
+            .get(CONTENT_LENGTH)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<u64>().ok());
+        if declared.is_some_and(|len| !allowed(len)) {
+            return Err(too_large());
+        }
+        let mut hasher =
+            BodyHasher::from_headers(req.headers()).map_err(IntoResponse::into_response)?;
+
+        let mut spooler = Spooler::new(&config);
+        let mut stream = req.into_body().into_data_stream();
+        while let Some(chunk) = stream
+            .try_next()
+            .await
+            .map_err(|e| ApiError::Client(body_error(&e)).into_response())?
+        {
+            if !allowed(spooler.len + chunk.len() as u64) {
+                return Err(too_large());
+            }
+            hasher.update(&chunk);
+            spooler.push(chunk).await.map_err(RequestError::from)?;
+        }
+
+        // 与通配匹配的耗时一样记录在请求的 span 上
+        tracing::Span::current().record("hash_us", hasher.cost().as_micros() as u64);
+
+        // 在 handler 写入数据和元数据之前校验，不一致时临时文件随 spooler 一起被删除
+        let etag = hasher.finish().map_err(IntoResponse::into_response)?;
+        spooler
+            .finish(etag)
+            .await
+            .map_err(|e| RequestError::from(e).into_response())
+    }
+}
+
+/// 逐块接收请求体，[`SpooledBody`] 的构造过程
+struct Spooler<'a> {
+    config: &'a BufferingConfig,
+    len: u64,
+    memory: BytesMut,
+    file: Option<(fs::File, SpillFile)>,
+}
+
+impl<'a> Spooler<'a> {
+    fn new(config: &'a BufferingConfig) -> Self {
+        Self {
+            config,
+            len: 0,
+            memory: BytesMut::new(),
+            file: None,
+        }
+    }
+
+    async fn push(&mut self, chunk: Bytes) -> Result<(), EngineError> {
+        self.len += chunk.len() as u64;
+
+        if self.file.is_none() && self.memory.len() + chunk.len() <= self.config.memory_threshold {
+            self.memory.extend_from_slice(&chunk);
+            return Ok(());
+        }
+
+        let (file, spill) = match &mut self.file {
+            Some(file) => file,
+            None => {
+                let spill = SpillFile(
+                    self.config
+                        .spill_dir()
+                        .join(format!("crab-vault-spool-{}", uuid::Uuid::new_v4())),
+                );
+                let file = fs::OpenOptions::new()
+                    .write(true)
+                    .create_new(true)
+                    .open(&spill.0)
+                    .await
+                    .map_err(|e| spill_error(e, &spill))?;
+                self.file.insert((file, spill))
+            }
+        };
+
+        if !self.memory.is_empty() {
+            let buffered = self.memory.split().freeze();
+            file.write_all(&buffered)
+                .await
+                .map_err(|e| spill_error(e, spill))?;
+        }
+        file.write_all(&chunk)
+            .await
+            .map_err(|e| spill_error(e, spill))
+    }
+
+    async fn finish(self, etag: String) -> Result<SpooledBody, EngineError> {
+        let storage = match self.file {
+            Some((mut file, spill)) => {
+                file.flush().await.map_err(|e| spill_error(e, &spill))?;
+                Storage::File(spill)
+            }
+            None => Storage::Memory(self.memory.freeze()),
+        };
+
+        Ok(SpooledBody {
+            len: self.len,
+            etag,
+            storage,
+        })
+    }
+}
+
+fn spill_error(error: io::Error, spill: &SpillFile) -> EngineError {
+    EngineError::Io {
+        error,
+        path: spill.0.to_string_lossy().to_string(),
+    }
+}
@@ -0,0 +1,58 @@
+use axum::{
+    extract::FromRequestParts,
+    http::{HeaderMap, request::Parts},
+};
+use base64::{Engine, prelude::BASE64_STANDARD};
+use crab_vault::engine::crypto::{CustomerKey, KEY_LEN};
+use md5::{Digest, Md5};
+
+use crate::{
+    error::api::{ApiError, ClientError},
+    http::{X_CRAB_VAULT_SSE_KEY, X_CRAB_VAULT_SSE_KEY_MD5},
+};
+
+/// 客户在请求中提供的密钥，来自 `X-Crab-Vault-Sse-Key` 和 `X-Crab-Vault-Sse-Key-Md5`
+pub struct CustomerKeyExtractor(pub Option<CustomerKey>);
+
+impl CustomerKeyExtractor {
+    /// 两个头部必须同时给出，密钥是 base64 编码的 32 字节，摘要是 base64 编码的密钥的 md5
+    pub fn from_headers(headers: &HeaderMap) -> Result<Self, ApiError> {
+        let invalid = |reason| ApiError::Client(ClientError::InvalidCustomerKey { reason });
+
+        let (key, md5) = match (
+            headers.get(X_CRAB_VAULT_SSE_KEY),
+            headers.get(X_CRAB_VAULT_SSE_KEY_MD5),
+        ) {
+            (None, None) => return Ok(Self(None)),
+            (Some(key), Some(md5)) => (key.to_str()?, md5.to_str()?),
+            _ => return Err(invalid("the key and its md5 must be given together")),
+        };
+
+        let key = BASE64_STANDARD
+            .decode(key.trim())
+            .map_err(|_| invalid("the key is not valid base64"))?;
+        let key: [u8; KEY_LEN] = key
+            .try_into()
+            .map_err(|_| invalid("the key must be 256 bits long"))?;
+        let md5 = BASE64_STANDARD
+            .decode(md5.trim())
+            .map_err(|_| invalid("the md5 of the key is not valid base64"))?;
+
+        if Md5::digest(key).as_slice() != md5.as_slice() {
+            return Err(invalid("the md5 does not match the key"));
+        }
+
+        Ok(Self(Some(CustomerKey::new(key))))
+    }
+}
+
+impl<S> FromRequestParts<S> for CustomerKeyExtractor
+where
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Self::from_headers(&parts.headers)
+    }
+}
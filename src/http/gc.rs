@@ -0,0 +1,94 @@
+use std::{sync::Arc, time::Duration};
+
+use crab_vault::engine::{
+    DataSource, MetaSource,
+    error::EngineResult,
+    gc::{self, Garbage},
+    journal::Journal,
+};
+
+use crate::app_config::data::GcConfig;
+
+/// 在后台定期执行 `crab-vault gc`
+///
+/// 服务运行时扫描到的孤立数据可能只是还没有写入元数据的上传，所以只删除连续两次扫描都发现的内容
+pub struct GcTask {
+    data_src: Arc<DataSource>,
+    meta_src: Arc<MetaSource>,
+    journal: Option<Arc<Journal>>,
+    config: GcConfig,
+    last: Garbage,
+}
+
+impl GcTask {
+    pub fn new(
+        data_src: Arc<DataSource>,
+        meta_src: Arc<MetaSource>,
+        journal: Option<Arc<Journal>>,
+        config: GcConfig,
+    ) -> Self {
+        Self {
+            data_src,
+            meta_src,
+            journal,
+            config,
+            last: Garbage::default(),
+        }
+    }
+
+    /// 按照配置的间隔扫描，没有配置间隔时什么都不做
+    pub fn spawn(mut self) {
+        if self.config.interval == 0 {
+            return;
+        }
+
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(Duration::from_secs(self.config.interval));
+            ticker.tick().await;
+
+            loop {
+                ticker.tick().await;
+                if let Err(e) = self.tick().await {
+                    tracing::warn!(error = %e, "garbage collection failed");
+                }
+            }
+        });
+    }
+
+    async fn tick(&mut self) -> EngineResult<()> {
+        let mut garbage = gc::scan(self.data_src.as_ref(), self.meta_src.as_ref()).await?;
+        if let Some(journal) = &self.journal {
+            let pending: Vec<_> = journal
+                .pending()
+                .await?
+                .into_iter()
+                .map(|(_, v)| v)
+                .collect();
+            garbage.exclude(&pending);
+        }
+
+        let confirmed = garbage.intersect(&self.last);
+        self.last = garbage;
+        if confirmed.is_empty() {
+            return Ok(());
+        }
+
+        for v in &confirmed.orphan_data {
+            tracing::warn!("/{}/{} has data but no metadata", v.bucket, v.object);
+        }
+        for v in &confirmed.orphan_meta {
+            tracing::warn!("/{}/{} has metadata but no data", v.bucket, v.object);
+        }
+        for bucket in &confirmed.empty_buckets {
+            tracing::warn!("/{bucket} is an empty bucket without metadata");
+        }
+
+        if self.config.delete {
+            gc::collect(self.data_src.as_ref(), self.meta_src.as_ref(), &confirmed).await?;
+            tracing::info!("garbage collected");
+            // 已经删除的内容不应该在下一次被再次确认
+            self.last = Garbage::default();
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,85 @@
+use std::{sync::Arc, time::Duration};
+
+use axum::extract::FromRef;
+use crab_vault::engine::{
+    MetaEngine, MetaSource, clock,
+    error::EngineResult,
+    inventory::{self, InventoryConfig},
+    name,
+};
+
+use crate::{
+    app_config::data::InventoryScanConfig,
+    http::api::{ApiState, write_inventory},
+};
+
+/// 在后台定期按照 bucket 的清单配置生成清单，参见 `PUT /{bucket}?inventory`
+///
+/// 上一次生成的时间取自目标 bucket 中已有的清单，所以重启服务或者多个实例共用后端时不会重复生成
+pub struct InventoryTask {
+    state: ApiState,
+    meta_src: Arc<MetaSource>,
+    config: InventoryScanConfig,
+}
+
+impl InventoryTask {
+    pub fn new(state: ApiState, config: InventoryScanConfig) -> Self {
+        Self {
+            meta_src: FromRef::from_ref(&state),
+            state,
+            config,
+        }
+    }
+
+    /// 按照配置的间隔检查，间隔为 `0` 时什么都不做
+    pub fn spawn(self) {
+        if self.config.interval == 0 {
+            return;
+        }
+
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(Duration::from_secs(self.config.interval));
+
+            loop {
+                ticker.tick().await;
+                if let Err(e) = self.tick().await {
+                    tracing::warn!(error = %e, "inventory scan failed");
+                }
+            }
+        });
+    }
+
+    async fn tick(&self) -> EngineResult<()> {
+        let buckets = self.meta_src.list_buckets_meta().await?;
+
+        // 一个 bucket 失败时继续处理其他 bucket
+        for bucket in buckets
+            .iter()
+            .filter(|v| !name::is_internal_bucket(&v.name))
+        {
+            let Some(config) = &bucket.inventory else {
+                continue;
+            };
+            if let Err(e) = self.generate(&bucket.name, config).await {
+                tracing::warn!(bucket = bucket.name, error = %e, "failed to generate inventory");
+            }
+        }
+        Ok(())
+    }
+
+    async fn generate(&self, bucket: &str, config: &InventoryConfig) -> EngineResult<()> {
+        let last = inventory::last_generated(self.meta_src.as_ref(), bucket, config).await?;
+        if !config.is_due(last, clock::now()) {
+            return Ok(());
+        }
+
+        let report = write_inventory(&self.state, bucket, config).await?;
+        tracing::info!(
+            bucket,
+            object = report.object,
+            objects = report.objects,
+            "inventory generated"
+        );
+        Ok(())
+    }
+}
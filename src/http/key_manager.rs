@@ -0,0 +1,187 @@
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{Arc, RwLock},
+    time::{Instant, SystemTime},
+};
+
+use crab_vault::auth::{JwtDecoder, revocation::RevocationStore};
+use jsonwebtoken::{Algorithm, DecodingKey};
+
+use crate::app_config::{
+    ConfigItem, StaticAppConfig, auth::KeyReloadConfig, util::JwtDecoderConfig,
+};
+
+type KeyId = (String, String);
+
+/// 运行时可以整体替换的 [`JwtDecoder`]
+///
+/// 每个请求开始时取出当前的解码器，替换不会影响已经开始鉴权的请求
+pub struct KeyManager {
+    decoder: RwLock<Arc<JwtDecoder>>,
+}
+
+/// 定期检查配置文件以及其中引用的密钥文件，发生变化时重新构建解码器
+///
+/// 从配置中移除的密钥在宽限期内仍然被接受，这样轮换密钥时已经签发的令牌不会立即失效
+pub struct KeyReloader {
+    manager: Arc<KeyManager>,
+    config_path: String,
+    reload: KeyReloadConfig,
+    revocations: Arc<dyn RevocationStore>,
+
+    /// 最近一次成功加载的配置
+    current: JwtDecoderConfig,
+
+    /// 已经从配置中移除，但仍在宽限期内的密钥
+    retired: HashMap<KeyId, (Algorithm, DecodingKey, Instant)>,
+
+    /// 需要检查的文件以及上一次看到的修改时间
+    watched: HashMap<PathBuf, Option<SystemTime>>,
+}
+
+impl KeyManager {
+    pub fn new(decoder: JwtDecoder) -> Self {
+        Self {
+            decoder: RwLock::new(Arc::new(decoder)),
+        }
+    }
+
+    /// 当前使用的解码器
+    pub fn decoder(&self) -> Arc<JwtDecoder> {
+        self.decoder
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    fn replace(&self, decoder: JwtDecoder) {
+        *self.decoder.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(decoder);
+    }
+}
+
+impl KeyReloader {
+    pub fn new(
+        manager: Arc<KeyManager>,
+        config_path: String,
+        reload: KeyReloadConfig,
+        revocations: Arc<dyn RevocationStore>,
+        current: JwtDecoderConfig,
+    ) -> Self {
+        let mut reloader = Self {
+            manager,
+            config_path,
+            reload,
+            revocations,
+            current,
+            retired: HashMap::new(),
+            watched: HashMap::new(),
+        };
+        reloader.watched = reloader.modified_times();
+        reloader
+    }
+
+    /// 在后台按照配置的间隔检查，没有配置间隔时什么都不做
+    pub fn spawn(mut self) {
+        let Some(interval) = self.reload.interval else {
+            return;
+        };
+
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.tick().await;
+
+            loop {
+                ticker.tick().await;
+                self.tick(Instant::now());
+            }
+        });
+    }
+
+    fn tick(&mut self, now: Instant) {
+        let mut changed = false;
+
+        let watched = self.modified_times();
+        if watched != self.watched {
+            // 无论加载是否成功都记下这次的修改时间，避免同一个错误的配置反复输出警告
+            self.watched = watched;
+            changed = self.reload(now);
+        }
+
+        let retired = self.retired.len();
+        self.retired.retain(|_, (_, _, deadline)| *deadline > now);
+        if self.retired.len() != retired {
+            tracing::info!(
+                expired = retired - self.retired.len(),
+                "retired jwt decoding keys are no longer accepted"
+            );
+            changed = true;
+        }
+
+        if changed {
+            self.manager.replace(self.build());
+        }
+    }
+
+    /// 重新读取配置，返回解码器是否需要重新构建
+    fn reload(&mut self, now: Instant) -> bool {
+        let loaded = StaticAppConfig::try_from_file(&self.config_path)
+            .map_err(|e| e.into_message())
+            .and_then(|v| {
+                v.auth
+                    .jwt_decoder_config
+                    .into_runtime()
+                    .map_err(|e| e.into_message())
+            });
+
+        let loaded = match loaded {
+            Ok(loaded) => loaded,
+            Err(e) => {
+                tracing::warn!(error = %e, "failed to reload jwt decoding keys, keep using the old ones");
+                return false;
+            }
+        };
+
+        let deadline = now + self.reload.grace_period;
+        for (id, (alg, key)) in &self.current.keys {
+            if !loaded.keys.contains_key(id) {
+                self.retired
+                    .insert(id.clone(), (*alg, key.clone(), deadline));
+            }
+        }
+        self.retired.retain(|id, _| !loaded.keys.contains_key(id));
+
+        tracing::info!(
+            keys = loaded.keys.len(),
+            retired = self.retired.len(),
+            "jwt decoding keys reloaded"
+        );
+
+        self.current = loaded;
+        // 新的配置可能引用了不同的密钥文件
+        self.watched = self.modified_times();
+        true
+    }
+
+    fn build(&self) -> JwtDecoder {
+        self.retired
+            .iter()
+            .fold(
+                self.current.decoder.clone(),
+                |decoder, ((iss, kid), (alg, key, _))| {
+                    decoder.accept_key(iss, kid, *alg, key.clone())
+                },
+            )
+            .revocation_store(self.revocations.clone())
+    }
+
+    fn modified_times(&self) -> HashMap<PathBuf, Option<SystemTime>> {
+        std::iter::once(PathBuf::from(&self.config_path))
+            .chain(self.current.key_files.iter().cloned())
+            .map(|path| {
+                let modified = std::fs::metadata(&path).and_then(|v| v.modified()).ok();
+                (path, modified)
+            })
+            .collect()
+    }
+}
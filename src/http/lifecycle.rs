@@ -0,0 +1,122 @@
+use std::{sync::Arc, time::Duration};
+
+use crab_vault::engine::{
+    DataEngine, DataSource, MetaEngine, MetaSource, clock,
+    error::{EngineError, EngineResult},
+    journal::{Intent, Journal},
+    lifecycle, name,
+};
+
+use crate::app_config::data::LifecycleScanConfig;
+
+/// 在后台定期删除按照生命周期规则过期的 object，参见 `PUT /{bucket}?lifecycle`，
+/// 以及超过了自己的过期时间的 object，参见 `X-Crab-Vault-Expires-At`
+///
+/// 与 `DELETE /{bucket}/{object}` 一样先删除数据再删除元数据，并记录在意图日志中
+pub struct LifecycleTask {
+    data_src: Arc<DataSource>,
+    meta_src: Arc<MetaSource>,
+    journal: Option<Arc<Journal>>,
+    config: LifecycleScanConfig,
+}
+
+impl LifecycleTask {
+    pub fn new(
+        data_src: Arc<DataSource>,
+        meta_src: Arc<MetaSource>,
+        journal: Option<Arc<Journal>>,
+        config: LifecycleScanConfig,
+    ) -> Self {
+        Self {
+            data_src,
+            meta_src,
+            journal,
+            config,
+        }
+    }
+
+    /// 按照配置的间隔扫描，间隔为 `0` 时什么都不做
+    pub fn spawn(self) {
+        if self.config.interval == 0 {
+            return;
+        }
+
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(Duration::from_secs(self.config.interval));
+
+            loop {
+                ticker.tick().await;
+                if let Err(e) = self.tick().await {
+                    tracing::warn!(error = %e, "lifecycle scan failed");
+                }
+            }
+        });
+    }
+
+    /// 每个有数据的 bucket 都要扫描，因为设置了过期时间的 object 可能在没有元数据的 bucket 中
+    async fn tick(&self) -> EngineResult<()> {
+        let buckets = self.data_src.list_buckets().await?;
+
+        // 一个 bucket 失败时继续处理其他 bucket
+        for bucket in buckets.iter().filter(|v| !name::is_internal_bucket(v)) {
+            if let Err(e) = self.expire(bucket).await {
+                tracing::warn!(bucket, error = %e, "lifecycle scan failed");
+            }
+        }
+        Ok(())
+    }
+
+    async fn expire(&self, bucket: &str) -> EngineResult<()> {
+        let lifecycle = match self.meta_src.read_bucket_meta(bucket).await {
+            Ok(meta) => meta.lifecycle,
+            Err(EngineError::BucketMetaNotFound { .. }) => None,
+            Err(e) => return Err(e),
+        };
+        let now = clock::now();
+        let expired =
+            lifecycle::scan(self.meta_src.as_ref(), bucket, lifecycle.as_ref(), now).await?;
+        if expired.is_empty() {
+            return Ok(());
+        }
+
+        let objects: Vec<_> = expired.iter().map(|v| v.object.clone()).collect();
+        let intent = match &self.journal {
+            Some(journal) => Some(journal.begin(&Intent::delete(bucket, &objects)).await?),
+            None => None,
+        };
+
+        let data = self.data_src.delete_objects(bucket, &objects).await;
+        let deleted: Vec<_> = expired
+            .iter()
+            .zip(data)
+            .filter_map(|(v, result)| match result {
+                Ok(()) => Some(v),
+                Err(e) => {
+                    tracing::warn!(bucket, object = v.object, error = %e, "failed to expire object");
+                    None
+                }
+            })
+            .collect();
+
+        let names: Vec<_> = deleted.iter().map(|v| v.object.clone()).collect();
+        let meta = self.meta_src.delete_objects_meta(bucket, &names).await;
+        let mut failed = deleted.len() < expired.len();
+        for (v, result) in deleted.into_iter().zip(meta) {
+            match result {
+                Ok(()) => {
+                    tracing::info!(bucket, object = v.object, rule = v.rule, "object expired")
+                }
+                Err(e) => {
+                    failed = true;
+                    tracing::warn!(bucket, object = v.object, error = %e, "failed to expire object");
+                }
+            }
+        }
+
+        // 有删除失败时留下记录，下次启动时完成剩下的删除
+        if let (Some(journal), Some(id), false) = (&self.journal, intent, failed) {
+            journal.finish(id).await?;
+        }
+        Ok(())
+    }
+}
@@ -1 +1,10 @@
-pub(super) mod auth;
\ No newline at end of file
+pub(super) mod access_log;
+pub(super) mod auth;
+pub(super) mod client_ip;
+pub(super) mod compression;
+pub(super) mod cors;
+pub(super) mod limits;
+pub(super) mod rate_limit;
+pub(super) mod request_id;
+pub(super) mod trailing_slash;
+pub(super) mod version;
@@ -0,0 +1,239 @@
+use std::{
+    convert::Infallible,
+    pin::Pin,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    task::{Context, Poll},
+    time::Instant,
+};
+
+use axum::{
+    body::{Body, Bytes},
+    http::Request,
+    response::Response,
+};
+use chrono::{DateTime, Utc};
+use crab_vault::engine::clock;
+use http_body::{Frame, SizeHint};
+use tower::{Layer, Service};
+
+use crate::http::{
+    access_log::{AccessLog, AccessRecord},
+    middleware::{
+        auth::{TokenIdentity, redact_presigned_token},
+        client_ip::ClientIp,
+        request_id::RequestId,
+    },
+};
+
+/// ## 为每个请求产生一条访问记录
+///
+/// 必须位于 [`ClientIpLayer`](super::client_ip::ClientIpLayer) 和
+/// [`RequestIdLayer`](super::request_id::RequestIdLayer) 之内，鉴权之外，
+/// 令牌的身份由鉴权中间件放在响应中。记录在响应体发送完成或者被丢弃时交给 [`AccessLog`]
+#[derive(Clone)]
+pub struct AccessLogMiddleware<Inner> {
+    inner: Inner,
+    log: Option<AccessLog>,
+}
+
+#[derive(Clone)]
+pub struct AccessLogLayer(Option<AccessLog>);
+
+/// 统计实际收到的请求体的字节数
+struct CountedBody {
+    inner: Body,
+    received: Arc<AtomicU64>,
+}
+
+/// 统计实际发出的响应体的字节数，被丢弃时写出访问记录
+struct LoggedBody {
+    inner: Body,
+    sent: u64,
+    completed: bool,
+    pending: Option<PendingRecord>,
+}
+
+/// 响应体发送完成之前已经知道的部分
+struct PendingRecord {
+    log: AccessLog,
+    time: DateTime<Utc>,
+    start: Instant,
+    method: String,
+    path: String,
+    status: u16,
+    received: Arc<AtomicU64>,
+    identity: Option<TokenIdentity>,
+    remote_addr: Option<String>,
+    request_id: Option<String>,
+}
+
+impl<Inner> Service<Request<Body>> for AccessLogMiddleware<Inner>
+where
+    Inner: Service<Request<Body>, Response = Response, Error = Infallible> + Send + Clone + 'static,
+    Inner::Future: 'static + Send,
+{
+    type Response = Response;
+    type Error = Infallible;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<Body>) -> Self::Future {
+        let cloned = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, cloned);
+        let Some(log) = self.log.clone() else {
+            return Box::pin(inner.call(req));
+        };
+
+        let time = clock::now();
+        let start = Instant::now();
+        let method = req.method().to_string();
+        let path = redact_presigned_token(req.uri());
+        let remote_addr = req
+            .extensions()
+            .get::<ClientIp>()
+            .map(|ClientIp(ip)| ip.to_string());
+        let request_id = req
+            .extensions()
+            .get::<RequestId>()
+            .map(|RequestId(id)| id.clone());
+
+        let received = Arc::new(AtomicU64::new(0));
+        let req = req.map(|inner| {
+            Body::new(CountedBody {
+                inner,
+                received: received.clone(),
+            })
+        });
+
+        Box::pin(async move {
+            let response = inner.call(req).await?;
+
+            let pending = PendingRecord {
+                log,
+                time,
+                start,
+                method,
+                path,
+                status: response.status().as_u16(),
+                received,
+                identity: response.extensions().get::<TokenIdentity>().cloned(),
+                remote_addr,
+                request_id,
+            };
+            Ok(response.map(|inner| {
+                Body::new(LoggedBody {
+                    inner,
+                    sent: 0,
+                    completed: false,
+                    pending: Some(pending),
+                })
+            }))
+        })
+    }
+}
+
+impl<Inner> Layer<Inner> for AccessLogLayer {
+    type Service = AccessLogMiddleware<Inner>;
+
+    fn layer(&self, inner: Inner) -> Self::Service {
+        AccessLogMiddleware {
+            inner,
+            log: self.0.clone(),
+        }
+    }
+}
+
+impl AccessLogLayer {
+    /// `log` 为 [`None`] 时不记录
+    pub fn new(log: Option<AccessLog>) -> Self {
+        Self(log)
+    }
+}
+
+impl http_body::Body for CountedBody {
+    type Data = Bytes;
+    type Error = axum::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let poll = Pin::new(&mut self.inner).poll_frame(cx);
+        if let Poll::Ready(Some(Ok(frame))) = &poll
+            && let Some(data) = frame.data_ref()
+        {
+            self.received
+                .fetch_add(data.len() as u64, Ordering::Relaxed);
+        }
+        poll
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+impl http_body::Body for LoggedBody {
+    type Data = Bytes;
+    type Error = axum::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let poll = Pin::new(&mut self.inner).poll_frame(cx);
+        match &poll {
+            Poll::Ready(Some(Ok(frame))) => {
+                self.sent += frame.data_ref().map_or(0, |v| v.len() as u64);
+            }
+            Poll::Ready(None) => self.completed = true,
+            _ => {}
+        }
+        poll
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+impl Drop for LoggedBody {
+    fn drop(&mut self) {
+        let Some(pending) = self.pending.take() else {
+            return;
+        };
+
+        // 空的响应体在发送之前就已经结束了，不会被读取
+        let completed = self.completed || http_body::Body::is_end_stream(&self.inner);
+        let (issuer, jti) = pending.identity.map(|v| (v.issuer, v.jti)).unzip();
+
+        pending.log.record(AccessRecord {
+            time: pending.time,
+            method: pending.method,
+            path: pending.path,
+            status: pending.status,
+            bytes_in: pending.received.load(Ordering::Relaxed),
+            bytes_out: self.sent,
+            latency_ms: pending.start.elapsed().as_millis() as u64,
+            issuer,
+            jti,
+            remote_addr: pending.remote_addr,
+            request_id: pending.request_id,
+            completed,
+        });
+    }
+}
@@ -3,32 +3,158 @@ use std::{
     pin::Pin,
     sync::Arc,
     task::{Context, Poll},
+    time::{Duration, Instant},
 };
 
 use axum::{
+    extract::Query,
     http::{
-        HeaderMap,
+        HeaderMap, Uri,
         header::{AUTHORIZATION, CONTENT_LENGTH, CONTENT_TYPE},
     },
     response::{IntoResponse, Response},
 };
-use crab_vault::auth::{HttpMethod, Jwt, JwtDecoder, Permission, error::AuthError};
+use crab_vault::{
+    auth::{
+        CompiledPermissionSet, HttpMethod, Jwt, JwtDecoder, PRESIGN_QUERY_KEY, PermissionSet,
+        error::AuthError,
+        pattern::GlobLimits,
+        policy::{BucketPolicy, CompiledBucketPolicy, PolicyEngine, PolicyStatement, Principal},
+    },
+    engine::{
+        MetaEngine, MetaSource, error::EngineError, list::ListObjectsQuery, name, tagging::Tags,
+    },
+};
+use base64::{Engine, prelude::BASE64_STANDARD};
+use percent_encoding::percent_decode_str;
+use serde_json::Value;
 use tower::{Layer, Service};
+use uuid::Uuid;
 
 use crate::{
-    app_config::auth::PathRule,
+    app_config::{auth::PathRule, data::ContentSniff},
     error::{
         api::{ApiError, ClientError},
+        context::RequestError,
+    },
+    http::{
+        X_CRAB_VAULT_COPY_SOURCE, X_CRAB_VAULT_USER_META,
+        api::{
+            CORS_QUERY_KEY, INVENTORY_PATH, INVENTORY_QUERY_KEY, LIFECYCLE_QUERY_KEY,
+            POLICY_QUERY_KEY, PUBLIC_READ_META_KEY, RESTORE_QUERY_KEY, RETENTION_QUERY_KEY,
+            TAGGING_QUERY_KEY, TRASH_QUERY_KEY, has_query_key, is_admin_path, is_force_delete,
+            is_form_upload,
+        },
+        extractor::copy::CopySource,
+        key_manager::KeyManager,
+        path_rules::PathRuleStore,
     },
 };
 
+/// 请求被路径规则公开时没有检查令牌，放入请求中的 [`PolicyEngine`] 只是一个允许所有操作的占位，
+/// 需要知道令牌实际权限的 handler 用这个标记区分
+#[derive(Clone, Copy)]
+pub struct ApprovedByPathRule;
+
+/// 通过验证的令牌的签发者和 ID，请求携带了有效的令牌时放入请求和响应中
+#[derive(Clone)]
+pub struct TokenIdentity {
+    pub issuer: String,
+    pub jti: Uuid,
+}
+
+/// ## 表单上传的鉴权
+///
+/// 中间件只能取出请求头中的令牌和 bucket 策略，由 handler 解析表单之后按照 `PUT /{bucket}/{key}` 检查，
+/// 文件的大小和内容类型同样要满足令牌或者策略中的限制。路径规则也按照这个路径匹配
+///
+/// 请求头和预签名 URL 中都没有令牌时使用表单中的令牌
+#[derive(Clone)]
+pub struct FormUploadAuth {
+    token: Option<String>,
+    decoder: Arc<JwtDecoder>,
+    glob_limits: GlobLimits,
+    rules: Arc<Vec<PathRule>>,
+    policy: Option<Arc<CompiledBucketPolicy>>,
+    meta_src: Arc<MetaSource>,
+}
+
+impl FormUploadAuth {
+    /// 通过时返回令牌的身份，没有使用令牌时为 [`None`]
+    pub async fn authorize(
+        &self,
+        form_token: Option<&str>,
+        path: &str,
+        size: usize,
+        content_type: &str,
+    ) -> Result<Option<TokenIdentity>, RequestError> {
+        let mut match_cost = Duration::ZERO;
+        let result = self
+            .check(form_token, path, size, content_type, &mut match_cost)
+            .await;
+        record_match_cost(match_cost);
+
+        if let Ok(Some(identity)) = &result {
+            tracing::Span::current().record("jti", identity.jti.to_string());
+        }
+        result
+    }
+
+    async fn check(
+        &self,
+        form_token: Option<&str>,
+        path: &str,
+        size: usize,
+        content_type: &str,
+        match_cost: &mut Duration,
+    ) -> Result<Option<TokenIdentity>, RequestError> {
+        if approved(&self.rules, path, HttpMethod::Put, match_cost).await {
+            return Ok(None);
+        }
+
+        let (permissions, identity) = match self.token.as_deref().or(form_token) {
+            Some(token) => {
+                let jwt: Jwt<PermissionSet> = self.decoder.decode(token)?;
+                let identity = TokenIdentity {
+                    issuer: jwt.iss,
+                    jti: jwt.jti,
+                };
+                (Some(jwt.load.compile_with_limits(&self.glob_limits)), Some(identity))
+            }
+            None => (None, None),
+        };
+
+        let mut engine = PolicyEngine::new(permissions, self.policy.clone());
+        resolve_object_tags(&mut engine, &self.meta_src, path).await?;
+        let start = Instant::now();
+        let denied = engine.denies(HttpMethod::Put, path);
+        let anonymous_denied = !engine.authenticated() && !engine.allows(HttpMethod::Put, path);
+        *match_cost += start.elapsed();
+        if denied {
+            return Err(AuthError::InsufficientPermissions.into());
+        }
+        if anonymous_denied {
+            return Err(AuthError::MissingAuthHeader.into());
+        }
+
+        check_body(&engine, HttpMethod::Put, path, size, Some(Ok(content_type)), match_cost)?;
+        Ok(identity)
+    }
+}
+
 #[derive(Clone)]
 pub struct AuthMiddleware<Inner> {
     inner: Inner,
-    jwt_config: Arc<JwtDecoder>,
-    path_rules: Arc<Vec<PathRule>>,
+    keys: Arc<KeyManager>,
+    path_rules: Arc<PathRuleStore>,
+    glob_limits: GlobLimits,
+    meta_src: Arc<MetaSource>,
+    sniff: ContentSniff,
 }
 
+/// 单个请求的通配匹配耗时超过这个值时输出警告
+const SLOW_GLOB_MATCH: Duration = Duration::from_millis(1);
+
 // 在 Inner 是一个 Service 的情况下，可以为 AuthMiddleware<Inner> 实现 Service
 // 这个 AuthMiddleware 和 Inner 使用同样的请求参数，axum::http::Request<ReqBody>
 impl<Inner, ReqBody> Service<axum::http::Request<ReqBody>> for AuthMiddleware<Inner>
@@ -50,8 +176,12 @@ where
     fn call(&mut self, mut req: axum::http::Request<ReqBody>) -> Self::Future {
         let cloned = self.inner.clone();
         let mut inner = std::mem::replace(&mut self.inner, cloned);
-        let jwt_config = self.jwt_config.clone();
-        let path_rules = self.path_rules.clone();
+        // 密钥可能在运行时被替换，每个请求只取一次当前的解码器
+        let jwt_config = self.keys.decoder();
+        let path_rules = self.path_rules.current();
+        let glob_limits = self.glob_limits;
+        let meta_src = self.meta_src.clone();
+        let sniff = self.sniff;
 
         Box::pin(async move {
             let call_inner_with_req = |req| async move {
@@ -61,39 +191,137 @@ where
                 }
             };
 
-            if approved(&path_rules, req.uri().path(), req.method().into()).await {
-                req.extensions_mut().insert(Permission::new_root());
+            let mut match_cost = Duration::ZERO;
+            let mut identity = None;
+
+            // 服务端复制时还需要源 object 的读权限，否则只有写权限的令牌就能复制出任意 object
+            if req.method() == axum::http::Method::PUT
+                && let Err(e) = authorize_copy_source(
+                    req.headers(),
+                    req.uri().query(),
+                    &path_rules,
+                    &jwt_config,
+                    &glob_limits,
+                    &meta_src,
+                    &mut match_cost,
+                )
+                .await
+            {
+                record_match_cost(match_cost);
+                return Ok(e);
+            }
+
+            // 表单上传的令牌和 object 的名称都在请求体中，只能交给 handler 鉴权，参见 FormUploadAuth
+            if is_form_upload_request(&req) {
+                let token = match extract_token(req.headers(), req.uri().query()) {
+                    Ok((token, _)) => Some(token.to_string()),
+                    Err(AuthError::MissingAuthHeader) => None,
+                    Err(e) => return Ok(RequestError::from(e).into_response()),
+                };
+                let policy = match load_policy(&meta_src, req.uri().path(), &glob_limits).await {
+                    Ok(policy) => policy,
+                    Err(e) => return Ok(e),
+                };
+                req.extensions_mut().insert(FormUploadAuth {
+                    token,
+                    decoder: jwt_config,
+                    glob_limits,
+                    rules: path_rules,
+                    policy,
+                    meta_src,
+                });
                 return call_inner_with_req(req).await;
             }
 
-            match extract_and_validate_token(
+            // 管理接口不能通过路径规则公开，否则任何人都可以修改路径规则本身
+            if !is_admin_path(req.uri().path())
+                && approved(&path_rules, req.uri().path(), req.method().into(), &mut match_cost)
+                    .await
+            {
+                record_match_cost(match_cost);
+                req.extensions_mut().insert(PolicyEngine::new_root());
+                req.extensions_mut().insert(ApprovedByPathRule);
+                return call_inner_with_req(req).await;
+            }
+
+            let policy = match policy_applies(req.uri().path(), req.uri().query()) {
+                true => match load_policy(&meta_src, req.uri().path(), &glob_limits).await {
+                    Ok(policy) => policy,
+                    Err(e) => return Ok(e),
+                },
+                false => None,
+            };
+
+            let result = extract_and_validate_token(
                 req.headers(),
                 req.method().into(),
                 req.uri().path(),
+                req.uri().query(),
                 &jwt_config,
+                &glob_limits,
+                policy,
+                &meta_src,
+                sniff,
+                &mut match_cost,
+                &mut identity,
             )
-            .await
-            {
-                Ok(permission) => {
-                    req.extensions_mut().insert(permission);
-                    call_inner_with_req(req).await
+            .await;
+            let listing = result.as_ref().map_or(Ok(()), |engine| {
+                authorize_listing(engine, req.method().into(), req.uri(), &mut match_cost)
+            });
+            record_match_cost(match_cost);
+
+            if let Some(identity) = &identity {
+                tracing::Span::current().record("jti", identity.jti.to_string());
+            }
+
+            let mut response = match (result, listing) {
+                (Ok(engine), Ok(())) => {
+                    req.extensions_mut().insert(engine);
+                    if let Some(identity) = identity.clone() {
+                        req.extensions_mut().insert(identity);
+                    }
+                    call_inner_with_req(req).await?
                 }
-                Err(e) => Ok(e),
+                (Ok(_), Err(e)) => RequestError::from(e).into_response(),
+                (Err(e), _) => e,
+            };
+
+            // 访问日志位于鉴权之外，只能从响应中取得令牌的身份，被拒绝的请求同样需要记录
+            if let Some(identity) = identity {
+                response.extensions_mut().insert(identity);
             }
+            Ok(response)
         })
     }
 }
 
 #[derive(Clone)]
-pub struct AuthLayer(Arc<JwtDecoder>, Arc<Vec<PathRule>>);
+pub struct AuthLayer(
+    Arc<KeyManager>,
+    Arc<PathRuleStore>,
+    GlobLimits,
+    Arc<MetaSource>,
+    ContentSniff,
+);
 
 impl AuthLayer {
-    /// 此函数将在堆上创建一个 [`JwtConfig`] 结构作为这个中间件的配置
-    pub fn new(decoder: JwtDecoder, path_rules: Vec<PathRule>) -> Self {
-        Self(
-            Arc::new(decoder),
-            Arc::new(path_rules),
-        )
+    /// 解码器由 [`KeyManager`] 持有，这样密钥重新加载之后新的请求立即使用新的密钥
+    ///
+    /// bucket 策略保存在 bucket 的元数据中，每个请求都从 `meta_src` 读取，修改之后立即生效，路径规则同理
+    pub fn new(
+        keys: Arc<KeyManager>,
+        path_rules: Arc<PathRuleStore>,
+        glob_limits: GlobLimits,
+        meta_src: Arc<MetaSource>,
+    ) -> Self {
+        Self(keys, path_rules, glob_limits, meta_src, ContentSniff::Off)
+    }
+
+    /// 开启了推断时，上传 object 可以不带有 `Content-Type`，推断出的类型由 handler 检查
+    pub fn content_sniff(mut self, sniff: ContentSniff) -> Self {
+        self.4 = sniff;
+        self
     }
 }
 
@@ -101,76 +329,486 @@ impl<Inner> Layer<Inner> for AuthLayer {
     type Service = AuthMiddleware<Inner>;
 
     fn layer(&self, inner: Inner) -> Self::Service {
-        let Self(jwt_config, path_rules) = self.clone();
+        let Self(keys, path_rules, glob_limits, meta_src, sniff) = self.clone();
 
         AuthMiddleware {
             inner,
-            jwt_config,
+            keys,
             path_rules,
+            glob_limits,
+            meta_src,
+            sniff,
         }
     }
 }
 
-/// 提取并验证JWT令牌
+/// 提取并验证JWT令牌，再与 bucket 策略合并
+///
+/// 令牌优先从 Authorization 头中提取，没有这个头时再尝试预签名 URL 中的查询参数。
+/// 路径所在的 bucket 设置了策略时，没有令牌的请求也可能被策略允许
+///
+/// 令牌中有多条权限时，请求必须被其中的某一条完整地允许；策略中的禁止语句优先于令牌中的权限。
+/// 权限带有标签条件时从 `meta_src` 读取 object 的标签
+///
+/// 令牌通过验证时把它的签发者和 ID 写入 `identity`
+#[allow(clippy::too_many_arguments)]
 async fn extract_and_validate_token(
     headers: &HeaderMap,
     method: HttpMethod,
     path: &str,
+    query: Option<&str>,
     decoder: &JwtDecoder,
-) -> Result<Permission, Response> {
-    // 1. 提取Authorization头
-    let auth_header = headers
-        .get(AUTHORIZATION)
-        .ok_or(AuthError::MissingAuthHeader)?
-        .to_str()
-        .map_err(|_| AuthError::InvalidAuthFormat)?;
-
-    // 2. 验证Bearer格式并提取令牌
-    let token = auth_header
-        .strip_prefix("Bearer ")
-        .ok_or(AuthError::InvalidAuthFormat)?;
+    glob_limits: &GlobLimits,
+    policy: Option<Arc<CompiledBucketPolicy>>,
+    meta_src: &MetaSource,
+    sniff: ContentSniff,
+    match_cost: &mut Duration,
+    identity: &mut Option<TokenIdentity>,
+) -> Result<PolicyEngine, Response> {
+    // 1. 提取Authorization头，或者预签名 URL 中的令牌
+    let token = match extract_token(headers, query) {
+        Ok(token) => Some(token),
+        Err(AuthError::MissingAuthHeader) if policy.is_some() => None,
+        Err(e) => return Err(RequestError::from(e).into_response()),
+    };
 
     // 3. 解码并验证JWT
-    let jwt: Jwt<Permission> = decoder.decode(token)?;
+    let mut check_token = false;
+    let permissions = match token {
+        Some((token, presigned)) => {
+            let jwt: Jwt<PermissionSet> = decoder.decode(token).map_err(RequestError::from)?;
+            *identity = Some(TokenIdentity {
+                issuer: jwt.iss,
+                jti: jwt.jti,
+            });
+            let perm = jwt.load.compile_with_limits(glob_limits);
+
+            // 预签名 URL 可能被转发给任何人，即使是只读的方法也必须检查方法和路径；
+            // 读写 bucket 策略、修改公开读取标记都是修改这个 bucket 的访问控制，与管理接口一样需要令牌明确地允许；
+            // 生命周期规则会删除 object，清单会把所有 object 的列表写入另一个 bucket，
+            // 跨域规则决定哪些网页可以访问这个 bucket，同样如此；
+            // object 的标签可能是访问权限的条件；强制删除 bucket 会删除其中所有的 object；
+            // 回收站中是已经删除的 object，恢复会覆盖同名的 object；保留设置决定 object 能否被删除
+            check_token = presigned
+                || has_query_key(query, POLICY_QUERY_KEY)
+                || has_query_key(query, LIFECYCLE_QUERY_KEY)
+                || has_query_key(query, INVENTORY_QUERY_KEY)
+                || has_query_key(query, CORS_QUERY_KEY)
+                || has_query_key(query, TAGGING_QUERY_KEY)
+                || has_query_key(query, TRASH_QUERY_KEY)
+                || has_query_key(query, RESTORE_QUERY_KEY)
+                || has_query_key(query, RETENTION_QUERY_KEY)
+                || is_admin_path(path)
+                || (method == HttpMethod::Delete && is_force_delete(query))
+                || (!method.safe() && changes_public_read(headers, path));
+            Some(perm)
+        }
+        None => None,
+    };
+
+    let mut engine = PolicyEngine::new(permissions, policy);
+    resolve_object_tags(&mut engine, meta_src, path)
+        .await
+        .map_err(RequestError::from)?;
+    if check_token {
+        check_method_and_path(engine.permissions(), method, path, match_cost)
+            .map_err(RequestError::from)?;
+    }
+
+    let start = Instant::now();
+    let denied = engine.denies(method, path);
+    let anonymous_denied = !engine.authenticated() && !engine.allows(method, path);
+    *match_cost += start.elapsed();
+    if denied {
+        return Err(RequestError::from(AuthError::InsufficientPermissions).into_response());
+    }
+    if anonymous_denied {
+        return Err(RequestError::from(AuthError::MissingAuthHeader).into_response());
+    }
 
-    if path.split('/').filter(|v| !v.is_empty()).count() <= 1 || method.safe() {
-        return Ok(jwt.load);
+    // 从回收站恢复 object、立即生成清单都没有请求体
+    if path.split('/').filter(|v| !v.is_empty()).count() <= 1
+        || method.safe()
+        || (method == HttpMethod::Post && has_query_key(query, RESTORE_QUERY_KEY))
+        || (method == HttpMethod::Post && is_admin_path(path) && path.starts_with(INVENTORY_PATH))
+    {
+        return Ok(engine);
     }
 
     // 4. 检查 content-length，如果没过这个要求，那更是演都不演了
     // 当然，如果访问的是一个 bucket (只有一个) 那就不用检查
     // 或者说请求方法是只读的，这个只读的方法对 body 的长度没有要求
-    let content_length = headers
+    let content_length: usize = headers
         .get(CONTENT_LENGTH)
         .ok_or(ApiError::Client(ClientError::MissingContentLength))?
         .to_str()
         .map_err(|_| ApiError::Client(ClientError::HeaderWithOpaqueBytes))?
         .parse()
         .map_err(|_| ApiError::Client(ClientError::ValueParsingError))?;
+    let content_type = headers
+        .get(CONTENT_TYPE)
+        .ok_or(ApiError::Client(ClientError::MissingContentType))
+        .and_then(|v| {
+            v.to_str()
+                .map_err(|_| ApiError::Client(ClientError::InvalidContentType))
+        });
+    // 开启了推断时，没有 Content-Type 的上传交给 handler 按照推断出的类型检查，参见 `sniff::admit`
+    let content_type = match content_type {
+        Err(ApiError::Client(ClientError::MissingContentType))
+            if sniff != ContentSniff::Off && is_plain_upload(method, query, headers) =>
+        {
+            None
+        }
+        other => Some(other),
+    };
 
-    let perm = jwt.load.clone().compile();
-    if !perm.check_size(content_length) {
-        return Err(ApiError::Client(ClientError::BodyTooLarge).into());
-    }
+    check_body(&engine, method, path, content_length, content_type, match_cost)
+        .map_err(IntoResponse::into_response)?;
+    Ok(engine)
+}
 
-    // 5. 检查资源路径匹配和请求方法
-    if !perm.can_perform_method(method) || !perm.can_access(path) {
+/// 允许这个方法和路径的权限中，必须有一条同时允许请求体的大小和 content type
+///
+/// 请求头中的 content type 无法使用时，先报告权限和大小的问题
+fn check_body(
+    engine: &PolicyEngine,
+    method: HttpMethod,
+    path: &str,
+    content_length: usize,
+    content_type: Option<Result<&str, ApiError>>,
+    match_cost: &mut Duration,
+) -> Result<(), RequestError> {
+    // 5. 检查资源路径匹配和请求方法，只有允许这个方法和路径的那些权限参与后面的检查
+    let start = Instant::now();
+    let matching = engine.matching(method, path).collect::<Vec<_>>();
+    *match_cost += start.elapsed();
+    if matching.is_empty() {
         return Err(AuthError::InsufficientPermissions.into());
     }
 
-    // 6. 检查 content-type
-    let content_type = headers
-        .get(CONTENT_TYPE)
-        .ok_or(ApiError::Client(ClientError::MissingContentType))?
-        .to_str()
-        .map_err(|_| ApiError::Client(ClientError::InvalidContentType))?;
-    if !perm.check_content_type(content_type) {
+    let sized = matching
+        .into_iter()
+        .filter(|v| v.check_size(content_length))
+        .collect::<Vec<_>>();
+    if sized.is_empty() {
+        return Err(ApiError::Client(ClientError::BodyTooLarge).into());
+    }
+
+    // 6. 检查 content-type，没有给出时由 handler 检查
+    let Some(content_type) = content_type else {
+        return Ok(());
+    };
+    let content_type = content_type?;
+    let start = Instant::now();
+    let allowed = sized.iter().any(|v| v.check_content_type(content_type));
+    *match_cost += start.elapsed();
+    if !allowed {
         return Err(ApiError::Client(ClientError::InvalidContentType).into());
     }
 
-    Ok(jwt.load)
+    Ok(())
 }
 
-async fn approved(rules: &[PathRule], path: &str, method: HttpMethod) -> bool {
-    rules.iter().any(|v| v.approved(path, method))
+/// 返回请求携带的令牌，以及这个令牌是否来自预签名 URL
+fn extract_token<'a>(
+    headers: &'a HeaderMap,
+    query: Option<&'a str>,
+) -> Result<(&'a str, bool), AuthError> {
+    match headers.get(AUTHORIZATION) {
+        Some(auth_header) => {
+            let auth_header = auth_header
+                .to_str()
+                .map_err(|_| AuthError::InvalidAuthFormat)?;
+
+            // 2. 验证Bearer格式并提取令牌
+            let token = auth_header
+                .strip_prefix("Bearer ")
+                .ok_or(AuthError::InvalidAuthFormat)?;
+            Ok((token, false))
+        }
+        None => Ok((
+            presigned_token(query).ok_or(AuthError::MissingAuthHeader)?,
+            true,
+        )),
+    }
+}
+
+/// 检查是否能够读取 `X-Crab-Vault-Copy-Source` 指向的 object，不是服务端复制时直接通过
+///
+/// 与普通的只读请求不同，这里总是检查令牌中的方法和路径，源 object 所在的 bucket 的策略同样适用
+async fn authorize_copy_source(
+    headers: &HeaderMap,
+    query: Option<&str>,
+    rules: &[PathRule],
+    decoder: &JwtDecoder,
+    glob_limits: &GlobLimits,
+    meta_src: &MetaSource,
+    match_cost: &mut Duration,
+) -> Result<(), Response> {
+    let Some(source) = CopySource::from_headers(headers)? else {
+        return Ok(());
+    };
+    let path = source.path();
+
+    if approved(rules, &path, HttpMethod::Get, match_cost).await {
+        return Ok(());
+    }
+
+    let policy = load_policy(meta_src, &path, glob_limits).await?;
+    let permissions = match extract_token(headers, query) {
+        Ok((token, _)) => {
+            let jwt: Jwt<PermissionSet> = decoder.decode(token).map_err(RequestError::from)?;
+            Some(jwt.load.compile_with_limits(glob_limits))
+        }
+        Err(AuthError::MissingAuthHeader) if policy.is_some() => None,
+        Err(e) => return Err(RequestError::from(e).into_response()),
+    };
+
+    let mut engine = PolicyEngine::new(permissions, policy);
+    if engine.needs_object_tags() {
+        engine.resolve_tags(
+            &object_tags(meta_src, &source.bucket_name, &source.object_name)
+                .await
+                .map_err(RequestError::from)?,
+        );
+    }
+    let start = Instant::now();
+    let allowed = engine.allows(HttpMethod::Get, &path);
+    *match_cost += start.elapsed();
+    if !allowed {
+        return Err(RequestError::from(AuthError::InsufficientPermissions).into_response());
+    }
+
+    Ok(())
+}
+
+/// 请求路径指向一个 object、并且令牌或者策略带有标签条件时，读取这个 object 的标签判断这些条件
+///
+/// bucket 级别的请求没有标签，带有标签条件的权限不允许它们，参见 [`PolicyEngine::resolve_tags`]
+async fn resolve_object_tags(
+    engine: &mut PolicyEngine,
+    meta_src: &MetaSource,
+    path: &str,
+) -> Result<(), EngineError> {
+    if !engine.needs_object_tags() {
+        return Ok(());
+    }
+    let Some((bucket, object)) = path.strip_prefix('/').and_then(|v| v.split_once('/')) else {
+        return Ok(());
+    };
+
+    let bucket = percent_decode_str(bucket).decode_utf8_lossy();
+    let object = percent_decode_str(object).decode_utf8_lossy();
+    engine.resolve_tags(&object_tags(meta_src, &bucket, &object).await?);
+    Ok(())
+}
+
+/// object 现有的标签，还不存在的 object 没有标签；名称不合法的请求会被 handler 拒绝，这里同样按照没有标签处理
+async fn object_tags(
+    meta_src: &MetaSource,
+    bucket: &str,
+    object: &str,
+) -> Result<Tags, EngineError> {
+    if name::validate_bucket_name(bucket).is_err() || name::validate_object_name(object).is_err() {
+        return Ok(Tags::new());
+    }
+
+    match meta_src.read_object_meta(bucket, object).await {
+        Ok(meta) => Ok(meta.tags),
+        Err(
+            EngineError::ObjectMetaNotFound { .. }
+            | EngineError::BucketMetaNotFound { .. }
+            | EngineError::BucketNotFound { .. },
+        ) => Ok(Tags::new()),
+        Err(e) => Err(e),
+    }
+}
+
+/// 列出 object 会暴露 object 的名称，与读取已知名称的 object 分开授权，参见 [`PolicyEngine::allows_listing`]
+///
+/// 列出时使用的前缀也参与检查，这样只能读取某个前缀的令牌仍然可以列出这个前缀下的 object
+fn authorize_listing(
+    engine: &PolicyEngine,
+    method: HttpMethod,
+    uri: &Uri,
+    match_cost: &mut Duration,
+) -> Result<(), AuthError> {
+    let path = uri.path();
+    if method != HttpMethod::Get
+        || path.split('/').filter(|v| !v.is_empty()).count() != 1
+        || has_query_key(uri.query(), POLICY_QUERY_KEY)
+    {
+        return Ok(());
+    }
+
+    // 无法解析的查询参数会在 handler 中被拒绝，这里按照没有前缀处理
+    let prefix = Query::<ListObjectsQuery>::try_from_uri(uri)
+        .ok()
+        .and_then(|Query(query)| query.prefix)
+        .unwrap_or_default();
+
+    let start = Instant::now();
+    let allowed = engine.allows_listing(path, &format!("{path}/{prefix}"));
+    *match_cost += start.elapsed();
+    if !allowed {
+        return Err(AuthError::InsufficientPermissions);
+    }
+
+    Ok(())
+}
+
+/// 除了列出所有 bucket 和管理接口，其他请求都访问某一个 bucket，这个 bucket 的策略作用于它们
+///
+/// 读写策略本身的请求不受策略约束，否则一份错误的策略可能把所有人都挡在外面
+fn policy_applies(path: &str, query: Option<&str>) -> bool {
+    path.split('/').any(|v| !v.is_empty())
+        && !is_admin_path(path)
+        && !has_query_key(query, POLICY_QUERY_KEY)
+}
+
+/// 读取 `path` 所在的 bucket 的策略，bucket 不存在、没有设置策略也不是公开读取的时候返回 [`None`]
+///
+/// 用户元数据中的 [`PUBLIC_READ_META_KEY`] 被视为一条额外的允许语句，参见 [`public_read`]
+///
+/// 已经保存的策略都经过了校验，无法解析说明元数据被破坏了，这时拒绝所有请求而不是忽略这份策略
+async fn load_policy(
+    meta_src: &MetaSource,
+    path: &str,
+    glob_limits: &GlobLimits,
+) -> Result<Option<Arc<CompiledBucketPolicy>>, Response> {
+    let Some(segment) = path.split('/').find(|v| !v.is_empty()) else {
+        return Ok(None);
+    };
+    let bucket = percent_decode_str(segment).decode_utf8_lossy();
+
+    let (policy, public) = match meta_src.read_bucket_meta(&bucket).await {
+        Ok(meta) => {
+            let public = meta.user_meta.get(PUBLIC_READ_META_KEY) == Some(&Value::Bool(true));
+            (meta.policy, public)
+        }
+        Err(EngineError::BucketMetaNotFound { .. }) => (None, false),
+        Err(e) => return Err(RequestError::from(e).into_response()),
+    };
+
+    let policy = match policy.map(serde_json::from_value::<BucketPolicy>) {
+        Some(Ok(policy)) => Some(policy.compile_with_limits(segment, glob_limits)),
+        Some(Err(e)) => {
+            tracing::error!(bucket = %bucket, "the policy of this bucket is corrupted: {e}");
+            return Err(RequestError::from(AuthError::InsufficientPermissions).into_response());
+        }
+        None => None,
+    };
+    let public = public.then(|| public_read().compile_with_limits(segment, glob_limits));
+
+    Ok(match (policy, public) {
+        (Some(policy), Some(public)) => Some(Arc::new(policy.merge(public))),
+        (Some(policy), None) | (None, Some(policy)) => Some(Arc::new(policy)),
+        (None, None) => None,
+    })
+}
+
+/// 公开读取的 bucket 允许任何人读取其中的 object，以及列出这个 bucket
+fn public_read() -> BucketPolicy {
+    BucketPolicy::new().statement(
+        PolicyStatement::allow(Principal::Anyone)
+            .methods(vec![HttpMethod::Get, HttpMethod::Head])
+            .resources(vec![String::new(), "*".to_string()]),
+    )
+}
+
+/// 修改 bucket 的公开读取标记同样是修改访问控制，需要令牌明确地允许
+///
+/// 只要请求头中的用户元数据提到了 [`PUBLIC_READ_META_KEY`] 就算作修改，无法解析的请求头交给 handler 拒绝
+fn changes_public_read(headers: &HeaderMap, path: &str) -> bool {
+    if path.split('/').filter(|v| !v.is_empty()).count() != 1 {
+        return false;
+    }
+
+    headers
+        .get(X_CRAB_VAULT_USER_META)
+        .and_then(|v| BASE64_STANDARD.decode(v.as_bytes()).ok())
+        .and_then(|v| serde_json::from_slice::<Value>(&v).ok())
+        .is_some_and(|v| v.get(PUBLIC_READ_META_KEY).is_some())
+}
+
+fn check_method_and_path(
+    perm: &CompiledPermissionSet,
+    method: HttpMethod,
+    path: &str,
+    match_cost: &mut Duration,
+) -> Result<(), AuthError> {
+    let start = Instant::now();
+    let allowed = perm.allows(method, path);
+    *match_cost += start.elapsed();
+    if !allowed {
+        return Err(AuthError::InsufficientPermissions);
+    }
+    Ok(())
+}
+
+/// 从查询字符串中取出预签名 URL 携带的令牌，令牌是 base64url 编码的，不需要解码百分号
+fn presigned_token(query: Option<&str>) -> Option<&str> {
+    query?
+        .split('&')
+        .filter_map(|v| v.split_once('='))
+        .find(|(key, _)| *key == PRESIGN_QUERY_KEY)
+        .map(|(_, token)| token)
+        .filter(|v| !v.is_empty())
+}
+
+/// 把 uri 中预签名 URL 携带的令牌替换掉，避免令牌出现在日志中
+pub fn redact_presigned_token(uri: &Uri) -> String {
+    let Some(query) = uri.query() else {
+        return uri.to_string();
+    };
+
+    let query = query
+        .split('&')
+        .map(|v| match v.split_once('=') {
+            Some((key, _)) if key == PRESIGN_QUERY_KEY => format!("{key}=[REDACTED]"),
+            _ => v.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("&");
+
+    format!("{}?{query}", uri.path())
+}
+
+async fn approved(
+    rules: &[PathRule],
+    path: &str,
+    method: HttpMethod,
+    match_cost: &mut Duration,
+) -> bool {
+    let start = Instant::now();
+    let approved = rules.iter().any(|v| v.approved(path, method));
+    *match_cost += start.elapsed();
+    approved
+}
+
+/// `POST /{bucket}` 的表单上传，参见 [`is_form_upload`]
+fn is_form_upload_request<B>(req: &axum::http::Request<B>) -> bool {
+    req.method() == axum::http::Method::POST
+        && req.uri().path().split('/').filter(|v| !v.is_empty()).count() == 1
+        && is_form_upload(req.uri().query(), req.headers())
+}
+
+/// `PUT /{bucket}/{object}` 上传 object 的数据，不是读写标签、保留或者服务端复制，调用者已经确认路径中有 object
+fn is_plain_upload(method: HttpMethod, query: Option<&str>, headers: &HeaderMap) -> bool {
+    method == HttpMethod::Put
+        && !has_query_key(query, TAGGING_QUERY_KEY)
+        && !has_query_key(query, RETENTION_QUERY_KEY)
+        && !headers.contains_key(X_CRAB_VAULT_COPY_SOURCE)
+}
+
+/// 将这个请求在通配匹配上花费的时间记录到请求的 span 上
+fn record_match_cost(cost: Duration) {
+    tracing::Span::current().record("glob_match_us", cost.as_micros() as u64);
+
+    if cost > SLOW_GLOB_MATCH {
+        tracing::warn!(
+            glob_match_us = cost.as_micros() as u64,
+            "glob matching took too long, consider simplifying path rules or token patterns"
+        );
+    }
 }
@@ -0,0 +1,159 @@
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use axum::{
+    extract::ConnectInfo,
+    http::{HeaderMap, HeaderName, header::FORWARDED},
+};
+use ipnet::IpNet;
+use tower::{Layer, Service};
+
+const X_FORWARDED_FOR: HeaderName = HeaderName::from_static("x-forwarded-for");
+
+/// 发起请求的客户端的地址，由 [`ClientIpLayer`] 放入请求的 extensions 中
+///
+/// 只有直接相连的对端是受信任的代理时才会参考 `Forwarded`、`X-Forwarded-For`，否则就是对端的地址
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ClientIp(pub IpAddr);
+
+/// 受信任的反向代理所在的网段
+#[derive(Clone, Default)]
+pub struct TrustedProxies(Arc<Vec<IpNet>>);
+
+impl TrustedProxies {
+    pub fn new(networks: Vec<IpNet>) -> Self {
+        Self(Arc::new(networks))
+    }
+
+    pub fn contains(&self, ip: &IpAddr) -> bool {
+        let ip = ip.to_canonical();
+        self.0.iter().any(|net| net.contains(&ip))
+    }
+
+    /// 从直接相连的对端 `peer` 开始，沿着转发链从右向左跳过受信任的代理，第一个不受信任的地址就是客户端
+    ///
+    /// 同时存在两种头部时只使用标准的 `Forwarded`。链中出现无法解析的地址（例如 `unknown` 或者混淆过的标识）时，
+    /// 它左边的内容都不可信，此时以最后一个经过检查的代理作为客户端
+    pub fn resolve(&self, peer: IpAddr, headers: &HeaderMap) -> IpAddr {
+        let mut client = peer.to_canonical();
+        if !self.contains(&client) {
+            return client;
+        }
+
+        let hops = match headers.contains_key(FORWARDED) {
+            true => forwarded_for(headers),
+            false => x_forwarded_for(headers),
+        };
+
+        for hop in hops.iter().rev() {
+            match hop {
+                Some(ip) => client = ip.to_canonical(),
+                None => break,
+            }
+            if !self.contains(&client) {
+                break;
+            }
+        }
+
+        client
+    }
+}
+
+/// `X-Forwarded-For: client, proxy1, proxy2`，多个同名头部按照出现的顺序拼接
+fn x_forwarded_for(headers: &HeaderMap) -> Vec<Option<IpAddr>> {
+    headers
+        .get_all(X_FORWARDED_FOR)
+        .iter()
+        .flat_map(|value| value.to_str().unwrap_or_default().split(','))
+        .map(parse_node)
+        .collect()
+}
+
+/// `Forwarded: for=192.0.2.60;proto=http, for="[2001:db8::1]:4711"`，参见 RFC 7239
+fn forwarded_for(headers: &HeaderMap) -> Vec<Option<IpAddr>> {
+    headers
+        .get_all(FORWARDED)
+        .iter()
+        .flat_map(|value| value.to_str().unwrap_or_default().split(','))
+        .map(|element| {
+            element
+                .split(';')
+                .filter_map(|pair| pair.split_once('='))
+                .find(|(key, _)| key.trim().eq_ignore_ascii_case("for"))
+                .and_then(|(_, node)| parse_node(node))
+        })
+        .collect()
+}
+
+/// 解析转发链中的一个节点，允许带有引号、方括号和端口
+fn parse_node(node: &str) -> Option<IpAddr> {
+    let node = node.trim().trim_matches('"');
+
+    if let Ok(ip) = node.parse() {
+        return Some(ip);
+    }
+    if let Ok(addr) = node.parse::<SocketAddr>() {
+        return Some(addr.ip());
+    }
+
+    node.strip_prefix('[')
+        .and_then(|v| v.strip_suffix(']'))
+        .and_then(|v| v.parse().ok())
+}
+
+/// 在进入其他中间件之前计算 [`ClientIp`]，需要使用 `into_make_service_with_connect_info` 启动服务
+#[derive(Clone)]
+pub struct ClientIpLayer(TrustedProxies);
+
+#[derive(Clone)]
+pub struct ClientIpMiddleware<Inner> {
+    inner: Inner,
+    proxies: TrustedProxies,
+}
+
+impl ClientIpLayer {
+    pub fn new(proxies: TrustedProxies) -> Self {
+        Self(proxies)
+    }
+}
+
+impl<Inner> Layer<Inner> for ClientIpLayer {
+    type Service = ClientIpMiddleware<Inner>;
+
+    fn layer(&self, inner: Inner) -> Self::Service {
+        ClientIpMiddleware {
+            inner,
+            proxies: self.0.clone(),
+        }
+    }
+}
+
+impl<Inner, ReqBody> Service<axum::http::Request<ReqBody>> for ClientIpMiddleware<Inner>
+where
+    Inner: Service<axum::http::Request<ReqBody>>,
+{
+    type Response = Inner::Response;
+    type Error = Inner::Error;
+    type Future = Inner::Future;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: axum::http::Request<ReqBody>) -> Self::Future {
+        let peer = req
+            .extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip());
+
+        if let Some(peer) = peer {
+            let client = self.proxies.resolve(peer, req.headers());
+            req.extensions_mut().insert(ClientIp(client));
+        }
+
+        self.inner.call(req)
+    }
+}
@@ -0,0 +1,94 @@
+use axum::http::{
+    Response,
+    header::{CONTENT_LENGTH, CONTENT_TYPE},
+};
+use crab_vault::engine::compression::Codec;
+use http_body::Body;
+use tower_http::compression::{CompressionLayer, Predicate};
+
+use crate::app_config::{data::content_type_matches, server::ResponseCompressionConfig};
+
+/// 已经压缩过的类型，再压缩一次几乎不会变小
+const COMPRESSED_TYPES: [&str; 13] = [
+    "image/*",
+    "video/*",
+    "audio/*",
+    "font/woff",
+    "font/woff2",
+    "application/zip",
+    "application/gzip",
+    "application/x-gzip",
+    "application/zstd",
+    "application/x-bzip2",
+    "application/x-xz",
+    "application/x-7z-compressed",
+    "application/vnd.rar",
+];
+
+/// ## 决定响应是否需要压缩
+///
+/// 没有 `Content-Type` 的响应不压缩。`Content-Encoding` 和 `Content-Range` 由 [`CompressionLayer`] 自己检查，
+/// 带有这两个头的响应总是原样返回
+#[derive(Clone)]
+pub struct CompressWhen {
+    content_types: Vec<String>,
+    min_size: u64,
+}
+
+impl CompressWhen {
+    fn new(config: &ResponseCompressionConfig) -> Self {
+        Self {
+            content_types: config.content_types.clone(),
+            min_size: config.min_size,
+        }
+    }
+}
+
+impl Predicate for CompressWhen {
+    fn should_compress<B>(&self, response: &Response<B>) -> bool
+    where
+        B: Body,
+    {
+        let Some(content_type) = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+        else {
+            return false;
+        };
+
+        // SVG 虽然是图片，但它是文本
+        let compressed = !content_type_matches("image/svg+xml", content_type)
+            && COMPRESSED_TYPES
+                .iter()
+                .any(|v| content_type_matches(v, content_type));
+        let allowed = self.content_types.is_empty()
+            || self
+                .content_types
+                .iter()
+                .any(|v| content_type_matches(v, content_type));
+
+        // 流式的响应没有确定的长度，总是压缩
+        let size = response.body().size_hint().exact().or_else(|| {
+            response
+                .headers()
+                .get(CONTENT_LENGTH)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.parse().ok())
+        });
+
+        !compressed && allowed && size.is_none_or(|v| v >= self.min_size)
+    }
+}
+
+/// 按照配置构造压缩层，没有配置算法时它不会修改任何响应
+pub fn response_compression_layer(
+    config: &ResponseCompressionConfig,
+) -> CompressionLayer<CompressWhen> {
+    let enabled = |codec| config.codecs.contains(&codec);
+
+    CompressionLayer::new()
+        .gzip(enabled(Codec::Gzip))
+        .zstd(enabled(Codec::Zstd))
+        .compress_when(CompressWhen::new(config))
+}
@@ -0,0 +1,301 @@
+use std::{
+    convert::Infallible,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use axum::{
+    body::Body,
+    http::{
+        HeaderMap, HeaderName, HeaderValue, Method, Request, StatusCode,
+        header::{
+            ACCESS_CONTROL_ALLOW_CREDENTIALS, ACCESS_CONTROL_ALLOW_HEADERS,
+            ACCESS_CONTROL_ALLOW_METHODS, ACCESS_CONTROL_ALLOW_ORIGIN,
+            ACCESS_CONTROL_EXPOSE_HEADERS, ACCESS_CONTROL_MAX_AGE, ACCESS_CONTROL_REQUEST_HEADERS,
+            ACCESS_CONTROL_REQUEST_METHOD, ORIGIN, VARY,
+        },
+    },
+    response::{IntoResponse, Response},
+};
+use crab_vault::engine::{MetaEngine, MetaSource, error::EngineError};
+use percent_encoding::percent_decode_str;
+use serde_json::Value;
+use tower::{Layer, Service};
+
+use crate::{
+    app_config::{live::Live, server::CorsConfig},
+    error::context::RequestError,
+    http::{api::is_admin_path, middleware::request_id::X_REQUEST_ID},
+};
+
+/// 所有以此开头的响应头都允许浏览器读取，包括用户元数据
+const EXPOSED_PREFIX: &str = "x-crab-vault-";
+
+/// ## 处理跨域请求
+///
+/// 预检请求直接在这里应答，不会经过鉴权；其他带有 `Origin` 的请求在响应中加上允许跨域的头部。
+/// 请求访问的 bucket 设置了自己的规则时使用 bucket 的规则，否则使用 `server.cors`
+#[derive(Clone)]
+pub struct CorsMiddleware<Inner> {
+    inner: Inner,
+    rules: Arc<Live<CorsRules>>,
+    meta_src: Arc<MetaSource>,
+}
+
+#[derive(Clone)]
+pub struct CorsLayer(Arc<Live<CorsRules>>, Arc<MetaSource>);
+
+/// 解析之后的 [`CorsConfig`]
+pub struct CorsRules {
+    any_origin: bool,
+    origins: Vec<String>,
+    methods: Vec<Method>,
+    any_header: bool,
+    headers: Vec<HeaderName>,
+    expose_headers: Vec<HeaderName>,
+    max_age: u64,
+    allow_credentials: bool,
+}
+
+impl<Inner> Service<Request<Body>> for CorsMiddleware<Inner>
+where
+    Inner: Service<Request<Body>, Response = Response, Error = Infallible> + Send + Clone + 'static,
+    Inner::Future: 'static + Send,
+{
+    type Response = Response;
+    type Error = Infallible;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<Body>) -> Self::Future {
+        let cloned = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, cloned);
+        let global = self.rules.current();
+        let meta_src = self.meta_src.clone();
+
+        Box::pin(async move {
+            // 不是来自浏览器的跨域请求，不需要读取 bucket 的规则
+            let Some(origin) = req.headers().get(ORIGIN).cloned() else {
+                return inner.call(req).await;
+            };
+
+            let rules = match load_rules(&meta_src, req.uri().path(), global).await {
+                Ok(rules) => rules,
+                Err(e) => return Ok(e),
+            };
+
+            if req.method() == Method::OPTIONS
+                && req.headers().contains_key(ACCESS_CONTROL_REQUEST_METHOD)
+            {
+                return Ok(preflight(rules.as_deref(), &origin, req.headers()));
+            }
+
+            let mut response = inner.call(req).await?;
+            let headers = response.headers_mut();
+            headers.append(VARY, HeaderValue::from_static("origin"));
+            if let Some(rules) = rules
+                && rules.allows_origin(&origin)
+            {
+                rules.allow(&origin, headers);
+                if let Some(exposed) = rules.exposed(headers) {
+                    headers.insert(ACCESS_CONTROL_EXPOSE_HEADERS, exposed);
+                }
+            }
+
+            Ok(response)
+        })
+    }
+}
+
+impl<Inner> Layer<Inner> for CorsLayer {
+    type Service = CorsMiddleware<Inner>;
+
+    fn layer(&self, inner: Inner) -> Self::Service {
+        let Self(rules, meta_src) = self.clone();
+
+        CorsMiddleware {
+            inner,
+            rules,
+            meta_src,
+        }
+    }
+}
+
+impl CorsLayer {
+    /// bucket 的规则与 bucket 策略一样保存在元数据中，每个跨域请求都从 `meta_src` 读取，
+    /// `rules` 在重新加载配置时被替换
+    pub fn new(rules: Arc<Live<CorsRules>>, meta_src: Arc<MetaSource>) -> Self {
+        Self(rules, meta_src)
+    }
+}
+
+impl CorsRules {
+    pub fn new(config: &CorsConfig) -> Result<Self, String> {
+        config.validate()?;
+
+        let parse = |v: &String| v.parse::<HeaderName>().ok();
+        Ok(Self {
+            any_origin: config.allowed_origins.iter().any(|v| v == "*"),
+            origins: config.allowed_origins.clone(),
+            methods: config
+                .allowed_methods
+                .iter()
+                .filter_map(|v| Method::from_bytes(v.as_str().as_bytes()).ok())
+                .collect(),
+            any_header: config.allowed_headers.iter().any(|v| v == "*"),
+            headers: config.allowed_headers.iter().filter_map(parse).collect(),
+            expose_headers: config.expose_headers.iter().filter_map(parse).collect(),
+            max_age: config.max_age,
+            allow_credentials: config.allow_credentials,
+        })
+    }
+
+    /// 读取 bucket 元数据中保存的规则，它们在保存之前都经过了校验
+    fn from_value(value: Value) -> Result<Self, String> {
+        let config: CorsConfig = serde_json::from_value(value).map_err(|e| e.to_string())?;
+        Self::new(&config)
+    }
+
+    fn allows_origin(&self, origin: &HeaderValue) -> bool {
+        let Ok(origin) = origin.to_str() else {
+            return false;
+        };
+
+        self.any_origin || self.origins.iter().any(|v| v.eq_ignore_ascii_case(origin))
+    }
+
+    /// 预检请求中的方法和所有请求头都被允许时返回 `true`
+    fn allows_preflight(&self, headers: &HeaderMap) -> bool {
+        let method = headers
+            .get(ACCESS_CONTROL_REQUEST_METHOD)
+            .and_then(|v| Method::from_bytes(v.as_bytes()).ok());
+        if !method.is_some_and(|v| self.methods.contains(&v)) {
+            return false;
+        }
+
+        self.any_header
+            || requested_headers(headers).all(|v| {
+                v.parse::<HeaderName>()
+                    .is_ok_and(|v| self.headers.contains(&v))
+            })
+    }
+
+    /// 加上允许 `origin` 读取响应的头部，预检请求和实际的请求都需要
+    fn allow(&self, origin: &HeaderValue, headers: &mut HeaderMap) {
+        let allowed = match self.any_origin && !self.allow_credentials {
+            true => HeaderValue::from_static("*"),
+            false => origin.clone(),
+        };
+        headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, allowed);
+
+        if self.allow_credentials {
+            headers.insert(
+                ACCESS_CONTROL_ALLOW_CREDENTIALS,
+                HeaderValue::from_static("true"),
+            );
+        }
+    }
+
+    /// 允许浏览器读取的响应头：配置中的，`X-Request-Id`，以及这个响应中所有的 `X-Crab-Vault-*`
+    ///
+    /// `X-Request-Id` 在这一层之外才加入响应，所以总是列出
+    fn exposed(&self, headers: &HeaderMap) -> Option<HeaderValue> {
+        let request_id = X_REQUEST_ID;
+        let mut exposed: Vec<&str> = self.expose_headers.iter().map(|v| v.as_str()).collect();
+        if !exposed.contains(&request_id.as_str()) {
+            exposed.push(request_id.as_str());
+        }
+        for name in headers.keys() {
+            if name.as_str().starts_with(EXPOSED_PREFIX) && !exposed.contains(&name.as_str()) {
+                exposed.push(name.as_str());
+            }
+        }
+
+        HeaderValue::from_str(&exposed.join(", ")).ok()
+    }
+}
+
+/// 应答预检请求，不被允许时响应中没有任何允许跨域的头部，浏览器会因此拒绝实际的请求
+fn preflight(rules: Option<&CorsRules>, origin: &HeaderValue, request: &HeaderMap) -> Response {
+    let mut response = StatusCode::NO_CONTENT.into_response();
+    let headers = response.headers_mut();
+    headers.insert(
+        VARY,
+        HeaderValue::from_static(
+            "origin, access-control-request-method, access-control-request-headers",
+        ),
+    );
+
+    let Some(rules) = rules.filter(|v| v.allows_origin(origin) && v.allows_preflight(request))
+    else {
+        return response;
+    };
+
+    rules.allow(origin, headers);
+
+    let methods = rules.methods.iter().map(|v| v.as_str()).collect::<Vec<_>>();
+    if let Ok(methods) = HeaderValue::from_str(&methods.join(", ")) {
+        headers.insert(ACCESS_CONTROL_ALLOW_METHODS, methods);
+    }
+
+    // 允许任意请求头时原样返回浏览器请求的那些，`*` 在携带凭据时不被当作通配符
+    let allowed_headers = match rules.any_header {
+        true => request.get(ACCESS_CONTROL_REQUEST_HEADERS).cloned(),
+        false => {
+            let names = rules.headers.iter().map(|v| v.as_str()).collect::<Vec<_>>();
+            HeaderValue::from_str(&names.join(", ")).ok()
+        }
+    };
+    if let Some(allowed_headers) = allowed_headers.filter(|v| !v.is_empty()) {
+        headers.insert(ACCESS_CONTROL_ALLOW_HEADERS, allowed_headers);
+    }
+
+    headers.insert(ACCESS_CONTROL_MAX_AGE, HeaderValue::from(rules.max_age));
+
+    response
+}
+
+/// `Access-Control-Request-Headers` 中以逗号分隔的请求头名称
+fn requested_headers(headers: &HeaderMap) -> impl Iterator<Item = &str> {
+    headers
+        .get_all(ACCESS_CONTROL_REQUEST_HEADERS)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+}
+
+/// 找到 `path` 所在的 bucket 的规则，bucket 不存在或者没有设置规则时使用 `global`
+///
+/// 已经保存的规则无法解析说明元数据被破坏了，这时不允许任何跨域请求，而不是退回到 `global`
+async fn load_rules(
+    meta_src: &MetaSource,
+    path: &str,
+    global: Arc<CorsRules>,
+) -> Result<Option<Arc<CorsRules>>, Response> {
+    let segment = path.split('/').find(|v| !v.is_empty());
+    let Some(segment) = segment.filter(|_| !is_admin_path(path)) else {
+        return Ok(Some(global));
+    };
+    let bucket = percent_decode_str(segment).decode_utf8_lossy();
+
+    let cors = match meta_src.read_bucket_meta(&bucket).await {
+        Ok(meta) => meta.cors,
+        Err(EngineError::BucketMetaNotFound { .. }) => None,
+        Err(e) => return Err(RequestError::from(e).into_response()),
+    };
+
+    Ok(match cors.map(CorsRules::from_value) {
+        Some(Ok(rules)) => Some(Arc::new(rules)),
+        Some(Err(e)) => {
+            tracing::error!(bucket = %bucket, "the cors rules of this bucket are corrupted: {e}");
+            None
+        }
+        None => Some(global),
+    })
+}
@@ -0,0 +1,152 @@
+use std::{
+    convert::Infallible,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use axum::{
+    body::{Body, Bytes},
+    http::{Request, header::CONTENT_LENGTH},
+    response::{IntoResponse, Response},
+};
+use futures::StreamExt;
+use tower::{Layer, Service};
+
+use crate::error::api::{ApiError, ClientError};
+
+/// ## 限制请求体的大小和接收速度
+///
+/// 声明的 `Content-Length` 超出限制时直接拒绝，否则在接收过程中检查，
+/// 超出限制或者等待下一段数据超时时请求体产生 [`BodyLimitError`]，由提取器转换为对应的错误响应
+#[derive(Clone)]
+pub struct RequestLimitsMiddleware<Inner> {
+    inner: Inner,
+    limits: RequestLimits,
+}
+
+#[derive(Clone)]
+pub struct RequestLimitsLayer(RequestLimits);
+
+#[derive(Clone, Copy)]
+pub struct RequestLimits {
+    /// 请求体的最大大小（字节）
+    pub max_body_size: u64,
+
+    /// 等待请求体的下一段数据的最长时间
+    pub read_timeout: Option<Duration>,
+
+    /// 需要完整读入内存的请求体的最大大小（字节），由 [`DefaultBodyLimit`](axum::extract::DefaultBodyLimit) 检查
+    pub max_buffered_body_size: usize,
+}
+
+/// 被 [`RequestLimitsMiddleware`] 中断的请求体产生的错误
+#[derive(Debug, thiserror::Error)]
+pub enum BodyLimitError {
+    #[error("the request body is larger than {0} bytes")]
+    TooLarge(u64),
+
+    #[error("no data of the request body received within {0:?}")]
+    TimedOut(Duration),
+
+    #[error(transparent)]
+    Transport(axum::Error),
+}
+
+impl<Inner> Service<Request<Body>> for RequestLimitsMiddleware<Inner>
+where
+    Inner: Service<Request<Body>, Response = Response, Error = Infallible> + Send + Clone + 'static,
+    Inner::Future: 'static + Send,
+{
+    type Response = Response;
+    type Error = Infallible;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<Body>) -> Self::Future {
+        let cloned = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, cloned);
+        let limits = self.limits;
+
+        let declared = req
+            .headers()
+            .get(CONTENT_LENGTH)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<u64>().ok());
+        if declared.is_some_and(|len| len > limits.max_body_size) {
+            return Box::pin(async {
+                Ok(ApiError::Client(ClientError::BodyTooLarge).into_response())
+            });
+        }
+
+        let req = req.map(|body| limits.wrap(body));
+        Box::pin(async move { inner.call(req).await })
+    }
+}
+
+impl<Inner> Layer<Inner> for RequestLimitsLayer {
+    type Service = RequestLimitsMiddleware<Inner>;
+
+    fn layer(&self, inner: Inner) -> Self::Service {
+        RequestLimitsMiddleware {
+            inner,
+            limits: self.0,
+        }
+    }
+}
+
+impl RequestLimitsLayer {
+    pub fn new(limits: RequestLimits) -> Self {
+        Self(limits)
+    }
+}
+
+impl RequestLimits {
+    /// 在接收 `body` 的过程中检查大小和等待时间，出错之后不再产生任何数据
+    fn wrap(self, body: Body) -> Body {
+        let stream = body.into_data_stream();
+
+        Body::from_stream(futures::stream::unfold(
+            Some((stream, 0u64)),
+            move |state| async move {
+                let (mut stream, received) = state?;
+
+                let next = match self.read_timeout {
+                    Some(timeout) => match tokio::time::timeout(timeout, stream.next()).await {
+                        Ok(next) => next,
+                        Err(_) => return Some((Err(BodyLimitError::TimedOut(timeout)), None)),
+                    },
+                    None => stream.next().await,
+                };
+
+                match next? {
+                    Ok(chunk) => {
+                        let received = received + chunk.len() as u64;
+                        if received > self.max_body_size {
+                            return Some((Err(BodyLimitError::TooLarge(self.max_body_size)), None));
+                        }
+                        Some((Ok::<Bytes, _>(chunk), Some((stream, received))))
+                    }
+                    Err(e) => Some((Err(BodyLimitError::Transport(e)), None)),
+                }
+            },
+        ))
+    }
+}
+
+/// 接收请求体失败时的错误，被 [`RequestLimitsMiddleware`] 中断时是对应的错误，否则是 [`ClientError::IncompleteBody`]
+pub fn body_error(error: &(dyn std::error::Error + 'static)) -> ClientError {
+    let mut source = Some(error);
+    while let Some(error) = source {
+        match error.downcast_ref::<BodyLimitError>() {
+            Some(BodyLimitError::TooLarge(_)) => return ClientError::BodyTooLarge,
+            Some(BodyLimitError::TimedOut(_)) => return ClientError::RequestTimeout,
+            _ => source = error.source(),
+        }
+    }
+
+    ClientError::IncompleteBody
+}
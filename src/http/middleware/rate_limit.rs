@@ -0,0 +1,650 @@
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use axum::{
+    body::{Body, Bytes, HttpBody},
+    http::{
+        HeaderMap, HeaderValue, Method, Request,
+        header::{CONTENT_LENGTH, RETRY_AFTER},
+    },
+    response::{IntoResponse, Response},
+};
+use http_body::{Frame, SizeHint};
+use tower::{Layer, Service};
+use uuid::Uuid;
+
+use crate::{
+    app_config::server::{RateLimit, RateLimitConfig},
+    error::api::{ApiError, ClientError},
+    http::middleware::auth::{FormUploadAuth, TokenIdentity},
+};
+
+/// 签发者或者令牌的条目超过这个数量时，清理已经回满的条目，它们与从未出现过没有区别
+const PRUNE_THRESHOLD: usize = 4096;
+
+/// ## 限制请求数和流量
+///
+/// 请求到达时检查所有适用的范围，任何一个范围的令牌不足时返回 `429 Too Many Requests`，
+/// 其中的 `Retry-After` 是令牌回满到足够处理这个请求所需的秒数。
+///
+/// 长度已知的请求体在开始处理之前计入，长度已知的响应体在处理完成之后计入，长度未知的请求体和响应体在读取时逐块计入。
+/// 流量可以暂时透支，透支的部分还清之前新的请求都会被拒绝
+///
+/// `global` 在鉴权之前检查，这样被鉴权拒绝的请求同样计入；签发者和令牌在鉴权之后检查，参见 [`RateLimitLayer`]
+#[derive(Clone)]
+pub struct RateLimitMiddleware<Inner> {
+    inner: Inner,
+    limiter: Arc<RateLimiter>,
+    scope: Scope,
+}
+
+#[derive(Clone)]
+pub struct RateLimitLayer {
+    limiter: Arc<RateLimiter>,
+    scope: Scope,
+}
+
+/// 一个 [`RateLimitMiddleware`] 检查的范围
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Scope {
+    /// `global`，所有请求都计入
+    Global,
+
+    /// `issuer` 和 `token`，只有携带了有效令牌的请求计入
+    Identity,
+}
+
+/// ## 表单上传的签发者和令牌限制
+///
+/// 表单中的令牌要到 handler 中才能验证，鉴权之后的 [`RateLimitMiddleware`] 把它放入表单上传的请求中，
+/// 由 handler 按照 [`FormUploadAuth::authorize`] 得出的身份计入
+#[derive(Clone)]
+pub struct FormRateLimit(Arc<RateLimiter>);
+
+/// 所有范围的令牌桶
+pub struct RateLimiter {
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    limits: Limits,
+    global: Buckets,
+    issuers: HashMap<String, Buckets>,
+    tokens: HashMap<Uuid, Buckets>,
+}
+
+/// 解析之后的 [`RateLimitConfig`]
+#[derive(Clone, Copy, Default)]
+struct Limits {
+    global: Option<Limit>,
+    per_issuer: Option<Limit>,
+    per_token: Option<Limit>,
+}
+
+/// 解析之后的 [`RateLimit`]
+#[derive(Clone, Copy)]
+struct Limit {
+    requests: Option<Rate>,
+    bytes: Option<Rate>,
+}
+
+#[derive(Clone, Copy)]
+struct Rate {
+    per_second: f64,
+    burst: f64,
+}
+
+/// 一个范围内的两个令牌桶，[`None`] 表示还没有用过，相当于满的桶
+#[derive(Default)]
+struct Buckets {
+    requests: Option<Bucket>,
+    bytes: Option<Bucket>,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// 长度未知的请求体或者响应体，每读出一块就计入流量
+struct ChargedBody {
+    inner: Body,
+    limiter: Arc<RateLimiter>,
+    identity: Option<TokenIdentity>,
+}
+
+impl<Inner> Service<Request<Body>> for RateLimitMiddleware<Inner>
+where
+    Inner: Service<Request<Body>, Response = Response, Error = Infallible> + Send + Clone + 'static,
+    Inner::Future: 'static + Send,
+{
+    type Response = Response;
+    type Error = Infallible;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<Body>) -> Self::Future {
+        let cloned = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, cloned);
+        let limiter = self.limiter.clone();
+        let scope = self.scope;
+
+        Box::pin(async move {
+            let mut req = req;
+            let identity = match scope {
+                Scope::Global => None,
+                Scope::Identity => match req.extensions().get::<TokenIdentity>().cloned() {
+                    Some(identity) => Some(identity),
+                    None => {
+                        if req.extensions().get::<FormUploadAuth>().is_some() {
+                            req.extensions_mut().insert(FormRateLimit(limiter));
+                        }
+                        return inner.call(req).await;
+                    }
+                },
+            };
+            let head = req.method() == Method::HEAD;
+
+            let request_bytes =
+                content_length(req.headers()).or_else(|| req.body().size_hint().exact());
+            let acquired = limiter.acquire(
+                identity.as_ref(),
+                request_bytes.unwrap_or(0),
+                Instant::now(),
+            );
+            if let Err((scope, wait)) = acquired {
+                return Ok(too_many_requests(scope, wait));
+            }
+
+            // 例如 `Transfer-Encoding: chunked` 的上传，不能让它绕过流量限制
+            let req = match request_bytes {
+                Some(_) => req,
+                None => req.map(|inner| ChargedBody::wrap(inner, &limiter, &identity)),
+            };
+
+            let response = inner.call(req).await?;
+            // HEAD 的响应中的 `Content-Length` 是 GET 时的长度，实际上没有响应体
+            if head {
+                return Ok(response);
+            }
+            let response_bytes =
+                content_length(response.headers()).or_else(|| response.body().size_hint().exact());
+            match response_bytes {
+                Some(bytes) => limiter.charge(identity.as_ref(), bytes, Instant::now()),
+                None => {
+                    return Ok(response.map(|inner| ChargedBody::wrap(inner, &limiter, &identity)));
+                }
+            }
+
+            Ok(response)
+        })
+    }
+}
+
+impl<Inner> Layer<Inner> for RateLimitLayer {
+    type Service = RateLimitMiddleware<Inner>;
+
+    fn layer(&self, inner: Inner) -> Self::Service {
+        RateLimitMiddleware {
+            inner,
+            limiter: self.limiter.clone(),
+            scope: self.scope,
+        }
+    }
+}
+
+impl RateLimitLayer {
+    /// 只检查 `global`，必须位于鉴权之前，否则大量无效的令牌可以绕过它
+    pub fn global(limiter: Arc<RateLimiter>) -> Self {
+        Self {
+            limiter,
+            scope: Scope::Global,
+        }
+    }
+
+    /// 只检查 `issuer` 和 `token`，必须位于鉴权之后，这样才能从请求中取出 [`TokenIdentity`]
+    pub fn per_identity(limiter: Arc<RateLimiter>) -> Self {
+        Self {
+            limiter,
+            scope: Scope::Identity,
+        }
+    }
+}
+
+impl FormRateLimit {
+    /// 按照 `identity` 占用一个请求并计入 `bytes`，超出限制时返回应当直接交给客户端的 `429` 响应
+    pub fn acquire(&self, identity: &TokenIdentity, bytes: u64) -> Option<Response> {
+        self.0
+            .acquire(Some(identity), bytes, Instant::now())
+            .err()
+            .map(|(scope, wait)| too_many_requests(scope, wait))
+    }
+}
+
+impl RateLimiter {
+    pub fn new(config: &RateLimitConfig) -> Self {
+        Self {
+            state: Mutex::new(State {
+                limits: Limits::from(config),
+                ..State::default()
+            }),
+        }
+    }
+
+    /// 使用新的限制，所有的令牌桶都重新从满的状态开始
+    pub fn replace(&self, config: &RateLimitConfig) {
+        *self.state.lock().unwrap_or_else(|e| e.into_inner()) = State {
+            limits: Limits::from(config),
+            ..State::default()
+        };
+    }
+
+    /// 检查所有适用的范围，都允许时占用一个请求并计入 `bytes`，否则返回需要等待最久的范围和等待的时间
+    ///
+    /// 没有 `identity` 时只有 `global`，否则只有 `issuer` 和 `token`，参见 [`Scope`]
+    fn acquire(
+        &self,
+        identity: Option<&TokenIdentity>,
+        bytes: u64,
+        now: Instant,
+    ) -> Result<(), (&'static str, Duration)> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if state.limits.is_disabled() {
+            return Ok(());
+        }
+        state.prune(now);
+
+        let mut scopes = state.scopes(identity);
+        let exceeded = scopes
+            .iter_mut()
+            .filter_map(|(scope, limit, buckets)| Some((*scope, buckets.wait(limit, now)?)))
+            .max_by_key(|(_, wait)| *wait);
+        if let Some(exceeded) = exceeded {
+            return Err(exceeded);
+        }
+
+        for (_, _, buckets) in scopes {
+            buckets.take(1.0, bytes as f64);
+        }
+        Ok(())
+    }
+
+    /// 计入请求之后传输的字节数，可能使流量透支
+    fn charge(&self, identity: Option<&TokenIdentity>, bytes: u64, now: Instant) {
+        if bytes == 0 {
+            return;
+        }
+
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        for (_, limit, buckets) in state.scopes(identity) {
+            buckets.refill(&limit, now);
+            buckets.take(0.0, bytes as f64);
+        }
+    }
+}
+
+impl State {
+    /// 这个请求适用的范围以及对应的令牌桶，没有 `identity` 时是 `global`，否则是签发者和令牌
+    fn scopes(
+        &mut self,
+        identity: Option<&TokenIdentity>,
+    ) -> Vec<(&'static str, Limit, &mut Buckets)> {
+        let mut scopes = vec![];
+
+        match identity {
+            None => {
+                if let Some(limit) = self.limits.global {
+                    scopes.push(("global", limit, &mut self.global));
+                }
+            }
+            Some(identity) => {
+                if let Some(limit) = self.limits.per_issuer {
+                    let buckets = self.issuers.entry(identity.issuer.clone()).or_default();
+                    scopes.push(("issuer", limit, buckets));
+                }
+                if let Some(limit) = self.limits.per_token {
+                    let buckets = self.tokens.entry(identity.jti).or_default();
+                    scopes.push(("token", limit, buckets));
+                }
+            }
+        }
+
+        scopes
+    }
+
+    fn prune(&mut self, now: Instant) {
+        if let Some(limit) = self.limits.per_issuer
+            && self.issuers.len() > PRUNE_THRESHOLD
+        {
+            self.issuers.retain(|_, v| !v.is_full(&limit, now));
+        }
+        if let Some(limit) = self.limits.per_token
+            && self.tokens.len() > PRUNE_THRESHOLD
+        {
+            self.tokens.retain(|_, v| !v.is_full(&limit, now));
+        }
+    }
+}
+
+impl Limits {
+    fn is_disabled(&self) -> bool {
+        self.global.is_none() && self.per_issuer.is_none() && self.per_token.is_none()
+    }
+}
+
+impl From<&RateLimitConfig> for Limits {
+    fn from(value: &RateLimitConfig) -> Self {
+        Self {
+            global: value.global.as_ref().map(Limit::from),
+            per_issuer: value.per_issuer.as_ref().map(Limit::from),
+            per_token: value.per_token.as_ref().map(Limit::from),
+        }
+    }
+}
+
+impl From<&RateLimit> for Limit {
+    fn from(value: &RateLimit) -> Self {
+        let requests = value.requests_per_second.map(|per_second| Rate {
+            per_second,
+            burst: value.burst.map_or(per_second.ceil(), f64::from),
+        });
+        let bytes = value.bytes_per_second.map(|per_second| Rate {
+            per_second: per_second as f64,
+            burst: value.bytes_burst.unwrap_or(per_second) as f64,
+        });
+
+        Self { requests, bytes }
+    }
+}
+
+impl Buckets {
+    /// 回满之后仍然需要等待时返回等待的时间：请求数至少要有一个令牌，流量不能处于透支状态
+    fn wait(&mut self, limit: &Limit, now: Instant) -> Option<Duration> {
+        self.refill(limit, now);
+
+        let requests = self
+            .requests
+            .as_ref()
+            .zip(limit.requests)
+            .map(|(bucket, rate)| (1.0 - bucket.tokens) / rate.per_second);
+        let bytes = self
+            .bytes
+            .as_ref()
+            .zip(limit.bytes)
+            .map(|(bucket, rate)| -bucket.tokens / rate.per_second);
+
+        let wait = requests.into_iter().chain(bytes).fold(0.0, f64::max);
+        (wait > 0.0).then(|| Duration::from_secs_f64(wait))
+    }
+
+    fn refill(&mut self, limit: &Limit, now: Instant) {
+        for (bucket, rate) in [
+            (&mut self.requests, limit.requests),
+            (&mut self.bytes, limit.bytes),
+        ] {
+            if let Some(rate) = rate {
+                bucket
+                    .get_or_insert_with(|| Bucket::full(rate, now))
+                    .refill(rate, now);
+            }
+        }
+    }
+
+    fn take(&mut self, requests: f64, bytes: f64) {
+        if let Some(bucket) = &mut self.requests {
+            bucket.tokens -= requests;
+        }
+        if let Some(bucket) = &mut self.bytes {
+            bucket.tokens -= bytes;
+        }
+    }
+
+    fn is_full(&self, limit: &Limit, now: Instant) -> bool {
+        let full = |bucket: &Option<Bucket>, rate: Option<Rate>| match (bucket, rate) {
+            (Some(bucket), Some(rate)) => bucket.peek(rate, now) >= rate.burst,
+            _ => true,
+        };
+
+        full(&self.requests, limit.requests) && full(&self.bytes, limit.bytes)
+    }
+}
+
+impl Bucket {
+    fn full(rate: Rate, now: Instant) -> Self {
+        Self {
+            tokens: rate.burst,
+            updated: now,
+        }
+    }
+
+    /// 到 `now` 为止回满之后的令牌数
+    fn peek(&self, rate: Rate, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        (self.tokens + elapsed * rate.per_second).min(rate.burst)
+    }
+
+    fn refill(&mut self, rate: Rate, now: Instant) {
+        self.tokens = self.peek(rate, now);
+        self.updated = now;
+    }
+}
+
+impl ChargedBody {
+    fn wrap(inner: Body, limiter: &Arc<RateLimiter>, identity: &Option<TokenIdentity>) -> Body {
+        Body::new(Self {
+            inner,
+            limiter: limiter.clone(),
+            identity: identity.clone(),
+        })
+    }
+}
+
+impl http_body::Body for ChargedBody {
+    type Data = Bytes;
+    type Error = axum::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let poll = Pin::new(&mut self.inner).poll_frame(cx);
+        if let Poll::Ready(Some(Ok(frame))) = &poll
+            && let Some(data) = frame.data_ref()
+        {
+            self.limiter
+                .charge(self.identity.as_ref(), data.len() as u64, Instant::now());
+        }
+        poll
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+fn too_many_requests(scope: &'static str, wait: Duration) -> Response {
+    let retry_after = wait.as_secs_f64().ceil().max(1.0) as u64;
+    tracing::debug!(scope, retry_after, "request rate limited");
+
+    let error = ApiError::Client(ClientError::TooManyRequests { scope });
+    let retry_after = [(RETRY_AFTER, HeaderValue::from(retry_after))];
+    (retry_after, error).into_response()
+}
+
+fn content_length(headers: &HeaderMap) -> Option<u64> {
+    headers
+        .get(CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rate(requests_per_second: Option<f64>, bytes_per_second: Option<u64>) -> Option<RateLimit> {
+        Some(RateLimit {
+            requests_per_second,
+            burst: None,
+            bytes_per_second,
+            bytes_burst: None,
+        })
+    }
+
+    fn identity(issuer: &str, jti: u128) -> TokenIdentity {
+        TokenIdentity {
+            issuer: issuer.into(),
+            jti: Uuid::from_u128(jti),
+        }
+    }
+
+    fn secs(secs: f64) -> Duration {
+        Duration::from_secs_f64(secs)
+    }
+
+    #[test]
+    fn test_requests_refill() {
+        let limiter = RateLimiter::new(&RateLimitConfig {
+            global: rate(Some(2.0), None),
+            ..Default::default()
+        });
+        let now = Instant::now();
+
+        assert!(limiter.acquire(None, 0, now).is_ok());
+        assert!(limiter.acquire(None, 0, now).is_ok());
+        assert_eq!(limiter.acquire(None, 0, now), Err(("global", secs(0.5))));
+        // 被拒绝的请求不占用令牌
+        assert!(limiter.acquire(None, 0, now + secs(0.5)).is_ok());
+        assert!(limiter.acquire(None, 0, now + secs(0.5)).is_err());
+        // 回满之后不会超过桶的容量
+        let later = now + secs(60.0);
+        assert!(limiter.acquire(None, 0, later).is_ok());
+        assert!(limiter.acquire(None, 0, later).is_ok());
+        assert!(limiter.acquire(None, 0, later).is_err());
+    }
+
+    #[test]
+    fn test_bytes_overdraft() {
+        let limiter = RateLimiter::new(&RateLimitConfig {
+            global: rate(None, Some(100)),
+            ..Default::default()
+        });
+        let now = Instant::now();
+
+        // 请求体计入之后透支也能通过，之后的请求要等透支的部分还清
+        assert!(limiter.acquire(None, 150, now).is_ok());
+        assert_eq!(limiter.acquire(None, 0, now), Err(("global", secs(0.5))));
+
+        limiter.charge(None, 150, now + secs(0.5));
+        assert_eq!(
+            limiter.acquire(None, 0, now + secs(0.5)),
+            Err(("global", secs(1.5)))
+        );
+        assert!(limiter.acquire(None, 0, now + secs(2.0)).is_ok());
+    }
+
+    #[test]
+    fn test_scopes() {
+        let limiter = RateLimiter::new(&RateLimitConfig {
+            global: rate(Some(10.0), None),
+            per_issuer: rate(Some(2.0), None),
+            per_token: rate(Some(1.0), None),
+        });
+        let now = Instant::now();
+        let (a, b, c) = (identity("a", 1), identity("a", 2), identity("b", 3));
+
+        assert!(limiter.acquire(Some(&a), 0, now).is_ok());
+        // 需要等待最久的范围
+        assert_eq!(limiter.acquire(Some(&a), 0, now), Err(("token", secs(1.0))));
+        assert!(limiter.acquire(Some(&b), 0, now).is_ok());
+        assert_eq!(
+            limiter.acquire(Some(&identity("a", 4)), 0, now),
+            Err(("issuer", secs(0.5)))
+        );
+        assert!(limiter.acquire(Some(&c), 0, now).is_ok());
+
+        // `global` 在鉴权之前单独检查，上面的请求没有计入
+        for _ in 0..10 {
+            assert!(limiter.acquire(None, 0, now).is_ok());
+        }
+        assert_eq!(limiter.acquire(None, 0, now), Err(("global", secs(0.1))));
+    }
+
+    #[test]
+    fn test_charge_scopes() {
+        let limiter = RateLimiter::new(&RateLimitConfig {
+            per_token: rate(None, Some(100)),
+            ..Default::default()
+        });
+        let now = Instant::now();
+        let (a, b) = (identity("a", 1), identity("a", 2));
+
+        limiter.charge(Some(&a), 300, now);
+        assert_eq!(limiter.acquire(Some(&a), 0, now), Err(("token", secs(2.0))));
+        assert!(limiter.acquire(Some(&b), 0, now).is_ok());
+        // 没有令牌的请求不受任何限制
+        limiter.charge(None, 1000, now);
+        assert!(limiter.acquire(None, 0, now).is_ok());
+    }
+
+    #[test]
+    fn test_prune() {
+        let limiter = RateLimiter::new(&RateLimitConfig {
+            per_token: rate(Some(1.0), None),
+            ..Default::default()
+        });
+        let tokens = || limiter.state.lock().unwrap().tokens.len();
+        let now = Instant::now();
+
+        for jti in 0..=PRUNE_THRESHOLD as u128 {
+            assert!(limiter.acquire(Some(&identity("a", jti)), 0, now).is_ok());
+        }
+        assert_eq!(tokens(), PRUNE_THRESHOLD + 1);
+
+        // 还没有回满的条目不能清理，否则会重置它们的限制
+        let next = identity("a", u128::MAX);
+        assert!(limiter.acquire(Some(&next), 0, now + secs(0.5)).is_ok());
+        assert_eq!(tokens(), PRUNE_THRESHOLD + 2);
+
+        let last = identity("a", u128::MAX - 1);
+        assert!(limiter.acquire(Some(&last), 0, now + secs(1.0)).is_ok());
+        assert_eq!(tokens(), 2);
+    }
+
+    #[test]
+    fn test_replace() {
+        let limiter = RateLimiter::new(&RateLimitConfig {
+            global: rate(Some(1.0), None),
+            ..Default::default()
+        });
+        let now = Instant::now();
+
+        assert!(limiter.acquire(None, 0, now).is_ok());
+        assert!(limiter.acquire(None, 0, now).is_err());
+
+        limiter.replace(&RateLimitConfig {
+            global: rate(Some(1.0), None),
+            ..Default::default()
+        });
+        assert!(limiter.acquire(None, 0, now).is_ok());
+
+        limiter.replace(&RateLimitConfig::default());
+        for _ in 0..10 {
+            assert!(limiter.acquire(None, 0, now).is_ok());
+        }
+    }
+}
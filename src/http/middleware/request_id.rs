@@ -0,0 +1,101 @@
+use std::{
+    convert::Infallible,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use axum::{
+    body::Body,
+    http::{HeaderName, HeaderValue, Request},
+    response::Response,
+};
+use tower::{Layer, Service};
+
+tokio::task_local! {
+    static REQUEST_ID: RequestId;
+}
+
+/// 请求和响应中携带请求 ID 的头部
+pub const X_REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
+
+/// 客户端提供的请求 ID 的最大长度，更长的会被替换
+const MAX_LEN: usize = 128;
+
+/// 请求的 `req_id`，由 [`RequestIdLayer`] 放入请求的 extensions 中，日志、访问记录和错误响应使用同一个值
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RequestId(pub String);
+
+impl RequestId {
+    fn generate() -> Self {
+        Self(uuid::Uuid::new_v4().to_string())
+    }
+
+    /// 沿用客户端或者上游代理给出的 `X-Request-Id`，只接受不含空白的可见 ASCII 字符
+    fn from_header(value: &HeaderValue) -> Option<Self> {
+        let value = value.to_str().ok()?;
+        let valid = !value.is_empty()
+            && value.len() <= MAX_LEN
+            && value.bytes().all(|b| b.is_ascii_graphic());
+        valid.then(|| Self(value.to_string()))
+    }
+}
+
+/// 正在处理的请求的 `req_id`，不在 [`RequestIdLayer`] 之内时为 [`None`]
+///
+/// 只在处理请求的任务中可以读取，`tokio::spawn` 出去的任务读不到
+pub fn current_request_id() -> Option<String> {
+    REQUEST_ID.try_with(|RequestId(id)| id.clone()).ok()
+}
+
+/// ## 为每个请求确定 [`RequestId`]
+///
+/// 请求带有合法的 `X-Request-Id` 时沿用它，否则生成一个 uuid，并在响应中用同一个头部返回。
+/// 需要在 `TraceLayer` 和 [`AccessLogLayer`](super::access_log::AccessLogLayer) 之外，它们才能记录它
+#[derive(Clone, Default)]
+pub struct RequestIdLayer;
+
+#[derive(Clone)]
+pub struct RequestIdMiddleware<Inner> {
+    inner: Inner,
+}
+
+impl<Inner> Layer<Inner> for RequestIdLayer {
+    type Service = RequestIdMiddleware<Inner>;
+
+    fn layer(&self, inner: Inner) -> Self::Service {
+        RequestIdMiddleware { inner }
+    }
+}
+
+impl<Inner> Service<Request<Body>> for RequestIdMiddleware<Inner>
+where
+    Inner: Service<Request<Body>, Response = Response, Error = Infallible>,
+    Inner::Future: 'static + Send,
+{
+    type Response = Response;
+    type Error = Infallible;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: Request<Body>) -> Self::Future {
+        let id = req
+            .headers()
+            .get(X_REQUEST_ID)
+            .and_then(RequestId::from_header)
+            .unwrap_or_else(RequestId::generate);
+        let header = HeaderValue::from_str(&id.0).ok();
+        req.extensions_mut().insert(id.clone());
+
+        let future = REQUEST_ID.scope(id, self.inner.call(req));
+        Box::pin(async move {
+            let mut response = future.await?;
+            if let Some(header) = header {
+                response.headers_mut().insert(X_REQUEST_ID, header);
+            }
+            Ok(response)
+        })
+    }
+}
@@ -0,0 +1,76 @@
+use std::task::{Context, Poll};
+
+use axum::http::{Request, Uri, uri::PathAndQuery};
+use tower::{Layer, Service};
+
+use crate::http::api::{API_VERSION_PREFIX, is_admin_path};
+
+/// 去掉路径末尾多余的 `/`，但保留 object 名称末尾的 `/`
+///
+/// 以 `/` 结尾的 object 是目录标记，`/bucket/folder/` 与 `/bucket/folder` 是两个不同的 object。
+/// bucket、管理接口等其他路径的行为与 `NormalizePathLayer::trim_trailing_slash` 相同
+#[derive(Clone)]
+pub struct TrailingSlashMiddleware<Inner> {
+    inner: Inner,
+}
+
+#[derive(Clone, Default)]
+pub struct TrailingSlashLayer;
+
+impl<Inner, ReqBody> Service<Request<ReqBody>> for TrailingSlashMiddleware<Inner>
+where
+    Inner: Service<Request<ReqBody>>,
+{
+    type Response = Inner::Response;
+    type Error = Inner::Error;
+    type Future = Inner::Future;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: Request<ReqBody>) -> Self::Future {
+        if let Some(path) = normalize(req.uri().path()) {
+            set_path(req.uri_mut(), &path);
+        }
+        self.inner.call(req)
+    }
+}
+
+impl<Inner> Layer<Inner> for TrailingSlashLayer {
+    type Service = TrailingSlashMiddleware<Inner>;
+
+    fn layer(&self, inner: Inner) -> Self::Service {
+        TrailingSlashMiddleware { inner }
+    }
+}
+
+/// 规范化后的路径，不需要修改时返回 [`None`]
+fn normalize(path: &str) -> Option<String> {
+    let trimmed = path.trim_matches('/');
+    let unversioned = trimmed
+        .strip_prefix(&API_VERSION_PREFIX[1..])
+        .and_then(|v| v.strip_prefix('/'))
+        .unwrap_or(trimmed);
+
+    let is_object = unversioned.contains('/') && !is_admin_path(&format!("/{unversioned}"));
+    let normalized = match is_object {
+        true => format!("/{}", path.trim_start_matches('/')),
+        false => format!("/{trimmed}"),
+    };
+
+    (normalized != path).then_some(normalized)
+}
+
+fn set_path(uri: &mut Uri, path: &str) {
+    let path_and_query = match uri.query() {
+        Some(query) => format!("{path}?{query}"),
+        None => path.to_string(),
+    };
+
+    let mut parts = uri.clone().into_parts();
+    parts.path_and_query = PathAndQuery::try_from(path_and_query).ok();
+    if let Ok(new_uri) = Uri::from_parts(parts) {
+        *uri = new_uri;
+    }
+}
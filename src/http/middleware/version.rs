@@ -0,0 +1,113 @@
+use std::{
+    convert::Infallible,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use axum::{
+    http::{HeaderName, HeaderValue, header::LINK},
+    response::{IntoResponse, Response},
+};
+use crab_vault::engine::clock;
+use tower::{Layer, Service};
+
+use crate::{
+    app_config::server::{UnversionedPolicy, VersioningConfig},
+    error::api::{ApiError, ClientError},
+    http::api::API_VERSION_PREFIX,
+};
+
+const DEPRECATION: HeaderName = HeaderName::from_static("deprecation");
+const SUNSET: HeaderName = HeaderName::from_static("sunset");
+
+/// 只用于没有版本前缀的路由，按照 [`VersioningConfig`] 放行、标记为弃用或者拒绝请求
+#[derive(Clone)]
+pub struct UnversionedMiddleware<Inner> {
+    inner: Inner,
+    config: Arc<VersioningConfig>,
+}
+
+#[derive(Clone)]
+pub struct UnversionedLayer(Arc<VersioningConfig>);
+
+impl<Inner, ReqBody> Service<axum::http::Request<ReqBody>> for UnversionedMiddleware<Inner>
+where
+    Inner: Service<axum::http::Request<ReqBody>> + Send + Clone + 'static,
+    ReqBody: 'static + Send,
+    Inner::Error: std::error::Error,
+    Inner::Response: IntoResponse,
+    Inner::Future: 'static + Send,
+{
+    type Response = Response;
+    type Error = Infallible;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(|_| unreachable!())
+    }
+
+    fn call(&mut self, req: axum::http::Request<ReqBody>) -> Self::Future {
+        let cloned = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, cloned);
+        let config = self.config.clone();
+
+        let successor = match req.uri().path_and_query() {
+            Some(v) => format!("{API_VERSION_PREFIX}{v}"),
+            None => API_VERSION_PREFIX.to_string(),
+        };
+
+        Box::pin(async move {
+            let policy = config.policy_at(clock::now());
+
+            if policy == UnversionedPolicy::Reject {
+                return Ok(
+                    ApiError::Client(ClientError::UnversionedPath { successor }).into_response()
+                );
+            }
+
+            let mut response = match inner.call(req).await {
+                Ok(val) => val.into_response(),
+                Err(_) => unreachable!(),
+            };
+
+            if policy == UnversionedPolicy::Deprecate {
+                let headers = response.headers_mut();
+                headers.insert(DEPRECATION, HeaderValue::from_static("true"));
+
+                if let Some(sunset) = config.sunset
+                    && let Ok(value) = HeaderValue::from_str(
+                        &sunset.format("%a, %d %b %Y %H:%M:%S GMT").to_string(),
+                    )
+                {
+                    headers.insert(SUNSET, value);
+                }
+
+                if let Ok(value) =
+                    HeaderValue::from_str(&format!("<{successor}>; rel=\"successor-version\""))
+                {
+                    headers.append(LINK, value);
+                }
+            }
+
+            Ok(response)
+        })
+    }
+}
+
+impl UnversionedLayer {
+    pub fn new(config: VersioningConfig) -> Self {
+        Self(Arc::new(config))
+    }
+}
+
+impl<Inner> Layer<Inner> for UnversionedLayer {
+    type Service = UnversionedMiddleware<Inner>;
+
+    fn layer(&self, inner: Inner) -> Self::Service {
+        UnversionedMiddleware {
+            inner,
+            config: self.0.clone(),
+        }
+    }
+}
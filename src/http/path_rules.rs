@@ -0,0 +1,137 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+};
+
+use clap::error::ErrorKind;
+use crab_vault::auth::{error::AuthError, pattern::GlobLimits};
+
+use crate::{
+    app_config::auth::{PathRule, StaticPathRule},
+    error::{
+        api::{ApiError, ClientError},
+        context::RequestError,
+        fatal::{FatalError, FatalResult, MultiFatalError},
+    },
+};
+
+/// ## 运行时可以修改的路径规则
+///
+/// 启动时使用配置文件中的 `path_rules`，设置了 `path_rules_file` 并且这个文件存在时以文件中的规则为准。
+/// 通过 `PUT /admin/path-rules` 整体替换，与 [`KeyManager`](super::key_manager::KeyManager) 一样，
+/// 每个请求开始时取出当前的规则，替换不会影响已经开始鉴权的请求
+pub struct PathRuleStore {
+    rules: RwLock<Arc<Vec<PathRule>>>,
+    limits: GlobLimits,
+    file: Option<PathBuf>,
+}
+
+impl PathRuleStore {
+    pub fn open(rules: Vec<PathRule>, limits: GlobLimits, file: Option<&str>) -> FatalResult<Self> {
+        let file = file.map(PathBuf::from);
+
+        let rules = match &file {
+            Some(path) => match std::fs::read(path) {
+                Ok(data) => {
+                    let when = format!("while parsing path rules in {}", path.display());
+                    let rules = serde_json::from_slice::<Vec<StaticPathRule>>(&data)
+                        .map_err(|e| FatalError::from(e).when(when.clone()))
+                        .map_err(|e| {
+                            let mut errors = MultiFatalError::new();
+                            errors.push(e);
+                            errors
+                        })?;
+                    compile(rules, &limits)?
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => rules,
+                Err(e) => {
+                    let mut errors = MultiFatalError::new();
+                    errors.push(FatalError::new(
+                        ErrorKind::Io,
+                        e.to_string(),
+                        Some(format!("while reading {}", path.display())),
+                    ));
+                    return Err(errors);
+                }
+            },
+            None => rules,
+        };
+
+        Ok(Self {
+            rules: RwLock::new(Arc::new(rules)),
+            limits,
+            file,
+        })
+    }
+
+    /// 当前使用的规则
+    pub fn current(&self) -> Arc<Vec<PathRule>> {
+        self.rules.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// 整体替换当前的规则，任意一条规则无法编译时不做任何修改
+    ///
+    /// 设置了 `path_rules_file` 时先写入文件，写入失败时同样不做修改
+    pub fn replace(&self, rules: Vec<StaticPathRule>) -> Result<(), RequestError> {
+        let compiled = compile(rules.clone(), &self.limits).map_err(|e| {
+            ApiError::Client(ClientError::InvalidPathRule {
+                reason: e.into_message().trim().to_string(),
+            })
+        })?;
+
+        let mut current = self.rules.write().unwrap_or_else(|e| e.into_inner());
+        if let Some(path) = &self.file {
+            persist(path, &rules)?;
+        }
+        *current = Arc::new(compiled);
+
+        Ok(())
+    }
+
+    /// 配置文件中的 `path_rules` 发生变化时使用新的规则，返回是否替换了
+    ///
+    /// `path_rules_file` 存在时规则以这个文件为准，配置文件中的规则不会生效
+    pub fn reload(&self, rules: Vec<PathRule>) -> bool {
+        if self.file.as_ref().is_some_and(|v| v.exists()) {
+            return false;
+        }
+
+        *self.rules.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(rules);
+        true
+    }
+}
+
+fn compile(rules: Vec<StaticPathRule>, limits: &GlobLimits) -> FatalResult<Vec<PathRule>> {
+    let mut errors = MultiFatalError::new();
+    let rules = rules
+        .into_iter()
+        .filter_map(|v| match v.into_runtime_with_limits(limits) {
+            Ok(v) => Some(v),
+            Err(mut e) => {
+                errors.append(&mut e);
+                None
+            }
+        })
+        .collect();
+
+    match errors.is_empty() {
+        true => Ok(rules),
+        false => Err(errors),
+    }
+}
+
+/// 先写入临时文件再重命名，与吊销列表相同
+fn persist(path: &Path, rules: &[StaticPathRule]) -> Result<(), AuthError> {
+    let io_error = |e: std::io::Error| {
+        AuthError::InternalError(format!("{e} while writing {}", path.display()))
+    };
+    let data =
+        serde_json::to_vec_pretty(rules).map_err(|e| AuthError::InternalError(e.to_string()))?;
+
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+
+    std::fs::write(&tmp_path, data)
+        .and_then(|_| std::fs::rename(&tmp_path, path))
+        .map_err(io_error)
+}
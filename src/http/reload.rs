@@ -0,0 +1,231 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use notify::{EventKind, RecursiveMode, Watcher};
+use serde::Serialize;
+use serde_json::Value;
+use tokio::sync::{Mutex, mpsc};
+
+use crate::{
+    app_config::{ConfigItem, StaticAppConfig, live::Live},
+    cli::run::RunArgs,
+    http::{
+        middleware::{cors::CorsRules, rate_limit::RateLimiter},
+        path_rules::PathRuleStore,
+    },
+    logger,
+};
+
+/// 保存文件时通常会连续产生多个事件，安静这么久之后才重新加载
+const DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// 服务启动时读取的配置文件
+pub struct ConfigFile {
+    pub path: String,
+
+    /// 命令行参数覆盖配置文件中的值，重新加载时同样如此
+    pub args: RunArgs,
+
+    /// 合并了命令行参数之后的配置
+    pub loaded: StaticAppConfig,
+}
+
+/// ## 重新加载配置文件
+///
+/// 整个配置文件都通过校验之后才会生效。`logger.level`、`logger.filter`、`auth.path_rules`、`server.rate_limit`、
+/// `server.cors` 立即生效，开启了密钥热加载时解码密钥由 `KeyReloader` 负责，其他配置项的变化要等到重启
+pub struct ConfigReloader {
+    path: String,
+    args: RunArgs,
+
+    /// 正在使用的配置，没有生效的变化不会记在这里，所以每次重新加载都会再次报告它们
+    current: StaticAppConfig,
+
+    /// 隐去了密钥的 `current`，参见 `GET /admin/config`
+    effective: Arc<Live<StaticAppConfig>>,
+    path_rules: Arc<PathRuleStore>,
+    rate_limiter: Arc<RateLimiter>,
+    cors: Arc<Live<CorsRules>>,
+}
+
+/// `POST /admin/reload` 的响应体
+#[derive(Serialize, Default)]
+#[serde(rename_all = "kebab-case")]
+pub struct ReloadReport {
+    /// 这次重新加载之后已经生效的配置项
+    pub applied: Vec<&'static str>,
+
+    /// 与正在使用的配置不同，但需要重启才能生效的配置项
+    pub restart_required: Vec<String>,
+}
+
+impl ConfigReloader {
+    pub fn new(
+        file: ConfigFile,
+        effective: Arc<Live<StaticAppConfig>>,
+        path_rules: Arc<PathRuleStore>,
+        rate_limiter: Arc<RateLimiter>,
+        cors: Arc<Live<CorsRules>>,
+    ) -> Self {
+        Self {
+            path: file.path,
+            args: file.args,
+            current: file.loaded,
+            effective,
+            path_rules,
+            rate_limiter,
+            cors,
+        }
+    }
+
+    /// 重新读取配置文件并应用其中的变化，失败时继续使用原来的配置
+    pub fn reload(&mut self) -> Result<ReloadReport, String> {
+        let result = self.try_reload();
+        match &result {
+            Ok(report) => {
+                if !report.applied.is_empty() {
+                    tracing::info!(applied = ?report.applied, "configuration reloaded");
+                }
+                if !report.restart_required.is_empty() {
+                    tracing::warn!(
+                        changed = ?report.restart_required,
+                        "some configuration changes take effect only after a restart"
+                    );
+                }
+            }
+            Err(e) => {
+                tracing::warn!(error = %e, "failed to reload the configuration, keep using the old one")
+            }
+        }
+        result
+    }
+
+    fn try_reload(&mut self) -> Result<ReloadReport, String> {
+        let loaded = StaticAppConfig::try_from_file(&self.path)
+            .map_err(|e| e.into_message().trim().to_string())?
+            .merge_cli(self.args.clone());
+        let runtime = loaded
+            .clone()
+            .into_runtime()
+            .map_err(|e| e.into_message().trim().to_string())?;
+        let cors = CorsRules::new(&runtime.server.cors)?;
+
+        let mut report = ReloadReport::default();
+        let current = &mut self.current;
+
+        if current.logger.level != loaded.logger.level {
+            logger::set_level(runtime.logger.level)?;
+            current.logger.level = loaded.logger.level;
+            report.applied.push("logger.level");
+        }
+
+        if current.logger.filter != loaded.logger.filter {
+            logger::set_filter(runtime.logger.filter)?;
+            current.logger.filter = loaded.logger.filter.clone();
+            report.applied.push("logger.filter");
+        }
+
+        if differs(&current.auth.path_rules, &loaded.auth.path_rules) {
+            match self.path_rules.reload(runtime.auth.path_rules) {
+                true => report.applied.push("auth.path_rules"),
+                false => tracing::warn!(
+                    "`auth.path_rules` is ignored because `auth.path_rules_file` exists"
+                ),
+            }
+            current.auth.path_rules = loaded.auth.path_rules.clone();
+        }
+
+        if differs(&current.server.rate_limit, &loaded.server.rate_limit) {
+            self.rate_limiter.replace(&runtime.server.rate_limit);
+            current.server.rate_limit = loaded.server.rate_limit.clone();
+            report.applied.push("server.rate_limit");
+        }
+
+        if differs(&current.server.cors, &loaded.server.cors) {
+            self.cors.replace(cors);
+            current.server.cors = loaded.server.cors.clone();
+            report.applied.push("server.cors");
+        }
+
+        if runtime.auth.key_reload.interval.is_some() {
+            current.auth.jwt_decoder_config = loaded.auth.jwt_decoder_config.clone();
+        }
+
+        report.restart_required = changed_fields(current, &loaded);
+        self.effective.replace(current.clone().redacted());
+        Ok(report)
+    }
+}
+
+/// 在后台监听配置文件，发生变化时重新加载
+///
+/// 编辑器通常先写入临时文件再重命名，原来的文件已经不存在了，所以监听的是配置文件所在的目录
+pub fn spawn_watcher(reloader: Arc<Mutex<ConfigReloader>>, path: &Path) -> notify::Result<()> {
+    let name = path.file_name().map(ToOwned::to_owned);
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |event| {
+        let _ = tx.send(event);
+    })?;
+    watcher.watch(&dir, RecursiveMode::NonRecursive)?;
+
+    tokio::spawn(async move {
+        // 丢弃之后不再产生事件
+        let _watcher = watcher;
+
+        while let Some(event) = rx.recv().await {
+            let event: notify::Event = match event {
+                Ok(event) => event,
+                Err(e) => {
+                    tracing::warn!("Cannot watch the configuration file! Details: {e}");
+                    continue;
+                }
+            };
+            if matches!(event.kind, EventKind::Access(_))
+                || !event.paths.iter().any(|v| v.file_name() == name.as_deref())
+            {
+                continue;
+            }
+
+            while let Ok(Some(_)) = tokio::time::timeout(DEBOUNCE, rx.recv()).await {}
+            let _ = reloader.lock().await.reload();
+        }
+    });
+
+    Ok(())
+}
+
+/// 配置项没有实现 [`PartialEq`]，比较序列化之后的结果
+fn differs<T: Serialize>(lhs: &T, rhs: &T) -> bool {
+    serde_json::to_value(lhs).ok() != serde_json::to_value(rhs).ok()
+}
+
+/// `old` 与 `new` 中不同的配置项，形如 `server.port`
+fn changed_fields(old: &StaticAppConfig, new: &StaticAppConfig) -> Vec<String> {
+    let (Ok(Value::Object(old)), Ok(Value::Object(new))) =
+        (serde_json::to_value(old), serde_json::to_value(new))
+    else {
+        return vec![];
+    };
+
+    let mut changed = vec![];
+    for (section, value) in &new {
+        match (old.get(section), value) {
+            (Some(Value::Object(old)), Value::Object(new)) => changed.extend(
+                new.iter()
+                    .filter(|(key, value)| old.get(*key) != Some(value))
+                    .map(|(key, _)| format!("{section}.{key}")),
+            ),
+            (old, new) if old != Some(new) => changed.push(section.clone()),
+            _ => {}
+        }
+    }
+    changed
+}
@@ -0,0 +1,585 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use axum::http::{
+    HeaderMap, HeaderValue, Method, Request, StatusCode,
+    header::{AUTHORIZATION, CONTENT_LENGTH, CONTENT_TYPE},
+};
+use base64::{Engine, prelude::BASE64_STANDARD};
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use clap::error::ErrorKind;
+use crab_vault::engine::{
+    DataEngine, DataSource, MetaEngine, MetaSource,
+    builder::DEFAULT_CONTENT_TYPE,
+    clock,
+    crypto::KeyRing,
+    error::{EngineError, EngineResult},
+    events::{Event, EventOp},
+    name,
+    tagging::Tagging,
+};
+use http_body_util::{BodyExt, Full};
+use hyper_rustls::{HttpsConnector, HttpsConnectorBuilder};
+use hyper_util::{
+    client::legacy::{Client, connect::HttpConnector},
+    rt::TokioExecutor,
+};
+use percent_encoding::utf8_percent_encode;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    app_config::replication::{ReplicationConfig, ReplicationRule, ReplicationTarget},
+    error::fatal::FatalError,
+    http::{
+        PATH_SEGMENT, X_CRAB_VAULT_EXPIRES_AT, X_CRAB_VAULT_MAX_BYTES, X_CRAB_VAULT_MAX_OBJECTS,
+        X_CRAB_VAULT_USER_META,
+        api::{self, TAGGING_QUERY_KEY},
+    },
+};
+
+/// 复制进度保存在内部 bucket 中的这个前缀下，每个目标一个 object
+///
+/// 只写数据不写元数据，否则保存进度本身又会产生一条需要复制的事件
+const CURSOR_PREFIX: &str = "replication";
+
+/// 一次从事件日志读取的事件数
+const BATCH_SIZE: usize = 100;
+
+/// 错误信息中最多保留的目标响应体的字节数
+const MAX_ERROR_BODY: usize = 256;
+
+type HttpClient = Client<HttpsConnector<HttpConnector>, Full<Bytes>>;
+
+/// ## 异步复制到其他 crab-vault
+///
+/// 每个目标一个后台任务，按照序号依次读取变更事件，把匹配规则的修改通过目标的 HTTP 接口重放一遍。
+/// 写入 object 时复制的是读取时的最新内容，所以落后的目标最终与源保持一致。
+///
+/// 一个事件复制失败时按照 `max_backoff` 退避重试，不会跳过它；目标明确拒绝的请求无法通过重试解决，
+/// 记录错误之后跳过
+pub struct Replication {
+    meta_src: Arc<MetaSource>,
+    targets: Vec<Arc<TargetState>>,
+}
+
+struct TargetState {
+    target: ReplicationTarget,
+    progress: Mutex<Progress>,
+}
+
+#[derive(Default, Clone)]
+struct Progress {
+    replicated_seq: u64,
+    last_success_at: Option<DateTime<Utc>>,
+    last_error: Option<String>,
+    last_error_at: Option<DateTime<Utc>>,
+    skipped: u64,
+}
+
+/// `GET /admin/replication/status` 的响应体
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ReplicationStatus {
+    /// 事件日志中最后一个事件的序号
+    pub latest_seq: u64,
+    pub targets: Vec<TargetStatus>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct TargetStatus {
+    pub name: String,
+    pub endpoint: String,
+
+    /// 这个序号以及之前的事件都已经处理完
+    pub replicated_seq: u64,
+
+    /// 还没有处理的、匹配这个目标的规则的事件数
+    pub lag: u64,
+
+    /// 其中最早的事件发生的时间
+    pub oldest_pending_at: Option<DateTime<Utc>>,
+    pub last_success_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    pub last_error_at: Option<DateTime<Utc>>,
+
+    /// 被目标拒绝而跳过的事件数
+    pub skipped: u64,
+}
+
+/// 保存在内部 bucket 中的复制进度
+#[derive(Serialize, Deserialize)]
+struct Cursor {
+    seq: u64,
+}
+
+/// 复制一个事件失败的原因
+enum Failure {
+    /// 网络错误、目标暂时不可用或者令牌失效，重试可能成功
+    Retry(String),
+
+    /// 目标拒绝了这个请求，或者源中的 object 无法复制，重试也不会成功
+    Skip(String),
+}
+
+struct Worker {
+    state: Arc<TargetState>,
+    client: HttpClient,
+    data_src: Arc<DataSource>,
+    meta_src: Arc<MetaSource>,
+    key_ring: Option<Arc<KeyRing>>,
+    poll_interval: Duration,
+    max_backoff: Duration,
+    timeout: Duration,
+}
+
+impl Replication {
+    /// 启动每个目标的后台任务，没有配置目标时什么都不做
+    pub fn spawn(
+        config: ReplicationConfig,
+        data_src: Arc<DataSource>,
+        meta_src: Arc<MetaSource>,
+        key_ring: Option<Arc<KeyRing>>,
+    ) -> Result<Self, FatalError> {
+        let targets: Vec<_> = config
+            .targets
+            .into_iter()
+            .map(|target| {
+                Arc::new(TargetState {
+                    target,
+                    progress: Mutex::default(),
+                })
+            })
+            .collect();
+        if targets.is_empty() {
+            return Ok(Self { meta_src, targets });
+        }
+
+        // 与 `server.tls` 使用相同的加密库，证书来自系统，或者 `SSL_CERT_FILE` 指定的文件
+        let connector = HttpsConnectorBuilder::new()
+            .with_provider_and_native_roots(rustls::crypto::aws_lc_rs::default_provider())
+            .map_err(|e| {
+                FatalError::new(
+                    ErrorKind::Io,
+                    e.to_string(),
+                    Some("while loading the root certificates for replication".into()),
+                )
+            })?
+            .https_or_http()
+            .enable_http1()
+            .build();
+        let client = Client::builder(TokioExecutor::new()).build(connector);
+
+        for state in &targets {
+            let worker = Worker {
+                state: state.clone(),
+                client: client.clone(),
+                data_src: data_src.clone(),
+                meta_src: meta_src.clone(),
+                key_ring: key_ring.clone(),
+                poll_interval: config.poll_interval,
+                max_backoff: config.max_backoff,
+                timeout: config.timeout,
+            };
+            tokio::spawn(worker.run());
+        }
+
+        Ok(Self { meta_src, targets })
+    }
+
+    /// 读出所有目标还没有处理的事件，计算每个目标落后的程度
+    pub async fn status(&self) -> EngineResult<ReplicationStatus> {
+        let progress: Vec<_> = self
+            .targets
+            .iter()
+            .map(|v| v.progress.lock().unwrap_or_else(|e| e.into_inner()).clone())
+            .collect();
+        let since = progress.iter().map(|v| v.replicated_seq).min();
+
+        // 事件日志的长度受 `meta.events.max_events` 限制，可以一次读完
+        let pending = match since {
+            Some(since) => self.meta_src.list_events(since, usize::MAX).await?,
+            None => vec![],
+        };
+        let latest_seq = pending.last().map(|v| v.seq).or(since).unwrap_or_default();
+
+        let targets = self
+            .targets
+            .iter()
+            .zip(progress)
+            .map(|(state, progress)| {
+                let mut pending = pending
+                    .iter()
+                    .filter(|v| v.seq > progress.replicated_seq)
+                    .filter(|v| rule_for(&state.target.rules, v).is_some());
+                let oldest_pending_at = pending.next().map(|v| v.time);
+
+                TargetStatus {
+                    name: state.target.name.clone(),
+                    endpoint: state.target.endpoint.clone(),
+                    replicated_seq: progress.replicated_seq,
+                    lag: oldest_pending_at.map_or(0, |_| 1 + pending.count() as u64),
+                    oldest_pending_at,
+                    last_success_at: progress.last_success_at,
+                    last_error: progress.last_error,
+                    last_error_at: progress.last_error_at,
+                    skipped: progress.skipped,
+                }
+            })
+            .collect();
+
+        Ok(ReplicationStatus {
+            latest_seq,
+            targets,
+        })
+    }
+}
+
+/// 第一条匹配这个事件的规则，内部 bucket 的事件不会被复制
+fn rule_for<'a>(rules: &'a [ReplicationRule], event: &Event) -> Option<&'a ReplicationRule> {
+    if name::is_internal_bucket(&event.bucket) {
+        return None;
+    }
+
+    rules.iter().find(|rule| {
+        rule.matches_bucket(&event.bucket)
+            && event
+                .object
+                .as_ref()
+                .is_none_or(|v| v.starts_with(&rule.prefix))
+    })
+}
+
+impl Worker {
+    async fn run(self) {
+        let name = self.state.target.name.clone();
+        let mut cursor = self.with_retry(|| self.load_cursor()).await;
+        self.progress().replicated_seq = cursor;
+        tracing::info!(target_name = name, seq = cursor, "replication started");
+
+        loop {
+            let events = self
+                .with_retry(|| self.meta_src.list_events(cursor, BATCH_SIZE))
+                .await;
+            let Some(first) = events.first() else {
+                tokio::time::sleep(self.poll_interval).await;
+                continue;
+            };
+
+            if first.seq > cursor + 1 {
+                tracing::warn!(
+                    target_name = name,
+                    "events {} to {} were trimmed before being replicated, the target may miss some changes",
+                    cursor + 1,
+                    first.seq - 1
+                );
+            }
+
+            for event in &events {
+                self.replicate_until_done(event).await;
+                cursor = event.seq;
+                self.progress().replicated_seq = cursor;
+            }
+
+            if let Err(e) = self.save_cursor(cursor).await {
+                tracing::warn!(target_name = name, error = %e, "cannot save the replication progress");
+            }
+        }
+    }
+
+    fn progress(&self) -> std::sync::MutexGuard<'_, Progress> {
+        self.state
+            .progress
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// 读取源中的事件日志或者进度失败时，等待之后重试，直到成功
+    async fn with_retry<T, F, Fut>(&self, f: F) -> T
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = EngineResult<T>>,
+    {
+        let mut backoff = Duration::from_secs(1);
+        loop {
+            match f().await {
+                Ok(v) => return v,
+                Err(e) => {
+                    self.record_error(e.to_string());
+                    tokio::time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(self.max_backoff);
+                }
+            }
+        }
+    }
+
+    async fn replicate_until_done(&self, event: &Event) {
+        let Some(rule) = rule_for(&self.state.target.rules, event) else {
+            return;
+        };
+
+        let mut backoff = Duration::from_secs(1);
+        loop {
+            match self.replicate(event, rule.destination(&event.bucket)).await {
+                Ok(()) => {
+                    self.progress().last_success_at = Some(clock::now());
+                    return;
+                }
+                Err(Failure::Skip(reason)) => {
+                    tracing::warn!(
+                        target_name = self.state.target.name,
+                        seq = event.seq,
+                        op = %event.op,
+                        bucket = event.bucket,
+                        object = event.object,
+                        "skipped an event that cannot be replicated: {reason}"
+                    );
+                    self.record_error(reason);
+                    self.progress().skipped += 1;
+                    return;
+                }
+                Err(Failure::Retry(reason)) => {
+                    tracing::debug!(
+                        target_name = self.state.target.name,
+                        seq = event.seq,
+                        "replication failed, retrying in {backoff:?}: {reason}"
+                    );
+                    self.record_error(reason);
+                    tokio::time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(self.max_backoff);
+                }
+            }
+        }
+    }
+
+    fn record_error(&self, error: String) {
+        let mut progress = self.progress();
+        progress.last_error = Some(error);
+        progress.last_error_at = Some(clock::now());
+    }
+
+    /// 把这个事件对应的修改写入目标中的 `bucket`
+    async fn replicate(&self, event: &Event, bucket: &str) -> Result<(), Failure> {
+        let bucket_path = format!("/{}", utf8_percent_encode(bucket, PATH_SEGMENT));
+
+        match (event.op, &event.object) {
+            (EventOp::PutBucket, _) => self.put_bucket(&event.bucket, &bucket_path).await,
+            // 目标中还有不在复制范围内的 object 时无法删除
+            (EventOp::DeleteBucket, _) => {
+                self.send(
+                    Method::DELETE,
+                    &bucket_path,
+                    HeaderMap::new(),
+                    Bytes::new(),
+                    &[StatusCode::NOT_FOUND],
+                )
+                .await
+            }
+            (EventOp::PutObject, Some(object)) => {
+                self.put_object(&event.bucket, object, &bucket_path).await
+            }
+            (EventOp::DeleteObject, Some(object)) => {
+                let path = format!(
+                    "{bucket_path}/{}",
+                    utf8_percent_encode(object, PATH_SEGMENT)
+                );
+                self.send(
+                    Method::DELETE,
+                    &path,
+                    HeaderMap::new(),
+                    Bytes::new(),
+                    &[StatusCode::NOT_FOUND],
+                )
+                .await
+            }
+            (_, None) => Err(Failure::Skip("the event has no object".into())),
+        }
+    }
+
+    /// 复制 bucket 的用户元数据和配额，策略、生命周期规则和跨域规则由目标自己管理
+    async fn put_bucket(&self, bucket: &str, path: &str) -> Result<(), Failure> {
+        let meta = match self.meta_src.read_bucket_meta(bucket).await {
+            Ok(meta) => meta,
+            // 之后一定还有一条删除的事件
+            Err(EngineError::BucketMetaNotFound { .. }) => return Ok(()),
+            Err(e) => return Err(Failure::Retry(e.to_string())),
+        };
+
+        let mut headers = HeaderMap::new();
+        insert_user_meta(&mut headers, &meta.user_meta);
+        for (header, quota) in [
+            (X_CRAB_VAULT_MAX_BYTES, meta.max_bytes),
+            (X_CRAB_VAULT_MAX_OBJECTS, meta.max_objects),
+        ] {
+            let value = quota.map_or(HeaderValue::from_static("none"), HeaderValue::from);
+            headers.insert(header, value);
+        }
+
+        self.send(Method::PUT, path, headers, Bytes::new(), &[])
+            .await
+    }
+
+    /// 复制 object 当前的内容、元数据和标签
+    async fn put_object(
+        &self,
+        bucket: &str,
+        object: &str,
+        bucket_path: &str,
+    ) -> Result<(), Failure> {
+        let meta = match self.meta_src.read_object_meta(bucket, object).await {
+            Ok(meta) => meta,
+            Err(EngineError::ObjectMetaNotFound { .. }) => return Ok(()),
+            Err(e) => return Err(Failure::Retry(e.to_string())),
+        };
+        // 过期的 object 很快会被删除，删除同样会被复制
+        if meta.is_expired(clock::now()) {
+            return Ok(());
+        }
+        if meta.check_customer_key(None).is_err() {
+            return Err(Failure::Skip(
+                "the object is encrypted with a customer-provided key".into(),
+            ));
+        }
+
+        let data = match api::read_original(&self.data_src, self.key_ring.as_ref(), &meta).await {
+            Ok(data) => data,
+            Err(EngineError::ObjectNotFound { .. }) => return Ok(()),
+            Err(e) => return Err(Failure::Retry(e.to_string())),
+        };
+
+        let mut headers = HeaderMap::new();
+        if let Ok(content_type) = HeaderValue::from_str(&meta.content_type) {
+            headers.insert(CONTENT_TYPE, content_type);
+        }
+        let meta = meta.hide_reserved_meta();
+        insert_user_meta(&mut headers, &meta.user_meta);
+        if let Some(expires_at) = meta.expires_at
+            && let Ok(value) = HeaderValue::from_str(&expires_at.to_rfc3339())
+        {
+            headers.insert(X_CRAB_VAULT_EXPIRES_AT, value);
+        }
+
+        let path = format!(
+            "{bucket_path}/{}",
+            utf8_percent_encode(object, PATH_SEGMENT)
+        );
+        self.send(Method::PUT, &path, headers, data.into(), &[])
+            .await?;
+
+        // 重新上传的 object 没有标签
+        if meta.tags.is_empty() {
+            return Ok(());
+        }
+        let tagging = serde_json::to_vec(&Tagging { tags: meta.tags })
+            .map_err(|e| Failure::Skip(e.to_string()))?;
+        let path = format!("{path}?{TAGGING_QUERY_KEY}");
+        let headers =
+            HeaderMap::from_iter([(CONTENT_TYPE, HeaderValue::from_static("application/json"))]);
+        self.send(Method::PUT, &path, headers, tagging.into(), &[])
+            .await
+    }
+
+    /// 发送请求，成功或者目标返回了 `ok` 中的状态码时视为完成
+    ///
+    /// 目标拒绝的请求直接跳过；网络错误、超时、鉴权失败、限流和服务端错误可能在修改配置或者等待之后恢复，所以重试
+    async fn send(
+        &self,
+        method: Method,
+        path: &str,
+        headers: HeaderMap,
+        body: Bytes,
+        ok: &[StatusCode],
+    ) -> Result<(), Failure> {
+        let ReplicationTarget {
+            endpoint, token, ..
+        } = &self.state.target;
+
+        let mut request = Request::builder()
+            .method(method.clone())
+            .uri(format!("{endpoint}{path}"))
+            .header(AUTHORIZATION, format!("Bearer {token}"))
+            // 目标在鉴权时要求修改 object 的请求都带有这两个头部，没有请求体的 `DELETE` 也不例外
+            .header(CONTENT_LENGTH, body.len())
+            .header(CONTENT_TYPE, DEFAULT_CONTENT_TYPE)
+            .body(Full::new(body))
+            .map_err(|e| Failure::Skip(e.to_string()))?;
+        request.headers_mut().extend(headers);
+
+        let response = async {
+            let response = self
+                .client
+                .request(request)
+                .await
+                .map_err(|e| e.to_string())?;
+            let status = response.status();
+            let body = response
+                .into_body()
+                .collect()
+                .await
+                .map_err(|e| e.to_string())?;
+            Ok::<_, String>((status, body.to_bytes()))
+        };
+        let (status, body) = match tokio::time::timeout(self.timeout, response).await {
+            Ok(Ok(response)) => response,
+            Ok(Err(e)) => return Err(Failure::Retry(format!("{method} {path}: {e}"))),
+            Err(_) => return Err(Failure::Retry(format!("{method} {path}: timed out"))),
+        };
+        if status.is_success() || ok.contains(&status) {
+            return Ok(());
+        }
+
+        let body = String::from_utf8_lossy(&body[..body.len().min(MAX_ERROR_BODY)]);
+        let description = format!("{method} {path} returned {status}: {}", body.trim());
+        match status {
+            StatusCode::UNAUTHORIZED
+            | StatusCode::FORBIDDEN
+            | StatusCode::REQUEST_TIMEOUT
+            | StatusCode::TOO_MANY_REQUESTS => Err(Failure::Retry(description)),
+            status if status.is_server_error() => Err(Failure::Retry(description)),
+            _ => Err(Failure::Skip(description)),
+        }
+    }
+
+    async fn load_cursor(&self) -> EngineResult<u64> {
+        let object = self.cursor_object();
+        match self
+            .data_src
+            .read_object(name::internal_bucket(), &object)
+            .await
+        {
+            Ok(data) => Ok(serde_json::from_slice::<Cursor>(&data)?.seq),
+            Err(EngineError::ObjectNotFound { .. } | EngineError::BucketNotFound { .. }) => Ok(0),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn save_cursor(&self, seq: u64) -> EngineResult<()> {
+        let (bucket, object) = (name::internal_bucket(), self.cursor_object());
+        let data = serde_json::to_vec(&Cursor { seq })?;
+
+        match self.data_src.create_object(bucket, &object, &data).await {
+            Err(EngineError::BucketNotFound { .. }) => {
+                self.data_src.create_bucket(bucket).await?;
+                self.data_src.create_object(bucket, &object, &data).await
+            }
+            other => other,
+        }
+    }
+
+    fn cursor_object(&self) -> String {
+        format!("{CURSOR_PREFIX}/{}.json", self.state.target.name)
+    }
+}
+
+/// 与响应中一样，用户元数据是 base64 编码的 JSON
+fn insert_user_meta(headers: &mut HeaderMap, user_meta: &Value) {
+    if let Ok(json) = serde_json::to_string(user_meta)
+        && let Ok(value) = HeaderValue::from_str(&BASE64_STANDARD.encode(json))
+    {
+        headers.insert(X_CRAB_VAULT_USER_META, value);
+    }
+}
@@ -1,74 +1,490 @@
-use std::{net::Ipv4Addr, time::Duration};
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
 
-use axum::extract::Request;
-use base64::{Engine, prelude::BASE64_STANDARD};
-use crab_vault::engine::{DataEngine, DataSource, MetaEngine, MetaSource};
+use axum::{
+    Router, ServiceExt,
+    extract::{FromRef, Request},
+    http::{HeaderValue, header::DATE},
+    response::Response,
+    serve::ListenerExt,
+};
+use clap::error::ErrorKind;
+use crab_vault::{
+    auth::revocation::{FileRevocationStore, MemoryRevocationStore, RevocationStore},
+    engine::{
+        DataEngine, DataSource, MetaSource,
+        clock::{self, ManualClock},
+        error::EngineError,
+        journal::{self, Journal},
+        name,
+    },
+    logger::trace_context::{TRACEPARENT, TraceParent},
+};
+use tokio::sync::Mutex;
+use tower::Layer;
 use tower_http::{
-    cors::{self, CorsLayer},
-    normalize_path::NormalizePathLayer,
+    set_header::SetResponseHeaderLayer,
     trace::{DefaultOnRequest, DefaultOnResponse, TraceLayer},
 };
 
 use crate::{
-    app_config::{self, ConfigItem},
+    app_config::{self, AppConfig, ConfigItem, StaticAppConfig, live::Live},
+    error::fatal::{FatalError, FatalResult, MultiFatalError},
     cli::run::RunArgs,
-    http::api::{self, ApiState},
+    http::{
+        access_log::AccessLog,
+        api::{self, AdminState, ApiState},
+        conn::WriteTimeoutListener,
+        gc::GcTask,
+        inventory::InventoryTask,
+        lifecycle::LifecycleTask,
+        key_manager::{KeyManager, KeyReloader},
+        path_rules::PathRuleStore,
+        reload::{self, ConfigFile, ConfigReloader},
+        replication::Replication,
+        shutdown::Shutdown,
+        tls::{self, CertReloader, CertStore, TlsListener},
+        trash::TrashTask,
+        middleware::{
+            access_log::AccessLogLayer,
+            auth::redact_presigned_token,
+            client_ip::{ClientIp, ClientIpLayer, TrustedProxies},
+            cors::CorsRules,
+            limits::RequestLimits,
+            rate_limit::RateLimiter,
+            request_id::{RequestId, RequestIdLayer},
+            trailing_slash::TrailingSlashLayer,
+        },
+    },
     logger,
 };
 
 pub async fn run(config_path: String, args: RunArgs) {
-    let config = app_config::StaticAppConfig::from_file(config_path)
-        .merge_cli(args)
+    let loaded = app_config::StaticAppConfig::from_file(config_path.clone()).merge_cli(args.clone());
+    let config = loaded
+        .clone()
         .into_runtime()
         .map_err(|e| e.exit_now())
         .unwrap();
 
-    logger::init(config.logger);
+    logger::init(config.logger.clone());
+
+    // 必须在打开任何后端之前安装，之后写入的时间戳才都来自固定的时间
+    if let Some(test_mode) = &config.server.test_mode {
+        clock::install(Arc::new(ManualClock::new(test_mode.now)));
+        tracing::warn!(
+            "Test mode is enabled, the server clock is frozen at {}",
+            test_mode.now
+        );
+    }
+
+    config
+        .data
+        .reserve_internal_bucket()
+        .map_err(|e| e.exit_now())
+        .unwrap();
+    name::set_rules(config.data.naming);
+
+    // 目录结构不符合预期时给出修复建议，参见 `crab_vault::engine::layout`
+    let data_src = DataSource::new(&config.data.source)
+        .map_err(|e| open_error(e, "while opening the data source"))
+        .unwrap();
+    let mut meta_src = MetaSource::with_pool_config(&config.meta.source, &config.meta.pool)
+        .map_err(|e| open_error(e, "while opening the meta source"))
+        .unwrap()
+        .on_corrupt(config.meta.on_corrupt_entry);
+    if config.meta.events.enabled {
+        meta_src = meta_src.record_events(config.meta.events.max_events);
+    }
+    let journal = match &config.data.journal {
+        Some(dir) => Some(recover(dir, &data_src, &meta_src).await),
+        None => None,
+    };
+
+    let config_file = ConfigFile {
+        path: config_path,
+        args,
+        loaded,
+    };
+    serve(config, Some(config_file), data_src, meta_src, journal).await
+}
+
+/// 使用已经打开的后端启动服务，`config_file` 为 [`None`] 时不会重新加载密钥和配置
+pub async fn serve(
+    config: AppConfig,
+    config_file: Option<ConfigFile>,
+    data_src: DataSource,
+    meta_src: MetaSource,
+    journal: Option<Journal>,
+) {
+    let revocations: Arc<dyn RevocationStore> = match &config.auth.revocation_list {
+        Some(path) => {
+            Arc::new(FileRevocationStore::open(path).expect("Failed to open revocation list"))
+        }
+        None => Arc::new(MemoryRevocationStore::new()),
+    };
+    // 意图日志已经恢复完毕，缓存从空开始，只有这个进程中的写入会使它失效
+    let data_src = match config.data.cache.enabled() {
+        true => data_src.cached(
+            usize::try_from(config.data.cache.max_memory).unwrap_or(usize::MAX),
+            usize::try_from(config.data.cache.max_object_size).unwrap_or(usize::MAX),
+        ),
+        false => data_src,
+    };
+    // 所有的写入都经过这两个后端，保留在这里统一检查，参见 `crab_vault::engine::retention`
+    let meta_src = Arc::new(meta_src);
+    let data_src = data_src.guard_retention(meta_src.clone());
+    let path_rules = PathRuleStore::open(
+        config.auth.path_rules,
+        config.auth.glob_limits,
+        config.auth.path_rules_file.as_deref(),
+    )
+    .map_err(|e| e.exit_now())
+    .unwrap();
+    let state = ApiState::new(
+        data_src,
+        meta_src,
+        config.encryption,
+        revocations.clone(),
+        path_rules,
+        config.server.buffering.clone(),
+        config.data.compression.clone(),
+        config.data.sniff,
+        config.data.trash.clone(),
+        journal,
+    );
 
-    let data_src = DataSource::new(&config.data.source).expect("Failed to create data storage");
-    let meta_src = MetaSource::new(&config.meta.source).expect("Failed to create meta storage");
-    let state = ApiState::new(data_src, meta_src);
+    let limits = RequestLimits {
+        max_body_size: config.server.max_body_size,
+        read_timeout: config.server.read_timeout(),
+        max_buffered_body_size: config.server.max_buffered_body_size(),
+    };
+    let write_timeout = config.server.write_timeout();
+    let rate_limiter = Arc::new(RateLimiter::new(&config.server.rate_limit));
 
     let tracing_layer = TraceLayer::new_for_http()
         .make_span_with(|req: &Request| {
             let method = req.method().to_string();
-            let uri = req.uri().to_string();
-            let req_id = BASE64_STANDARD.encode(uuid::Uuid::new_v4()); // 使用 base64 编码的 uuid 作为请求 req_id
-            tracing::info_span!("[request]", req_id, method, uri)
+            let uri = redact_presigned_token(req.uri());
+            let client_ip = req
+                .extensions()
+                .get::<ClientIp>()
+                .map(|ClientIp(ip)| ip.to_string())
+                .unwrap_or_default();
+            let req_id = req
+                .extensions()
+                .get::<RequestId>()
+                .map(|RequestId(id)| id.clone())
+                .unwrap_or_default();
+            // 不合法的 `traceparent` 按照 W3C 的规定忽略，这个请求开始一条新的调用链
+            let parent = req
+                .headers()
+                .get(TRACEPARENT)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.parse::<TraceParent>().ok());
+            let span = tracing::info_span!(
+                "[request]",
+                req_id,
+                client_ip,
+                method,
+                uri,
+                trace_id = parent.as_ref().map(TraceParent::trace_id_hex),
+                bucket = tracing::field::Empty,
+                object = tracing::field::Empty,
+                jti = tracing::field::Empty,
+                glob_match_us = tracing::field::Empty,
+                hash_us = tracing::field::Empty
+            );
+            #[cfg(feature = "otlp")]
+            if let Some(parent) = &parent {
+                crab_vault::logger::otlp::set_parent(&span, parent);
+            }
+            span
         })
         .on_failure(())
         .on_request(DefaultOnRequest::new().level(tracing::Level::INFO))
         .on_response(DefaultOnResponse::new().level(tracing::Level::INFO));
 
-    let normalize_path_layer = NormalizePathLayer::trim_trailing_slash();
+    let client_ip_layer = ClientIpLayer::new(TrustedProxies::new(config.server.trusted_proxies));
 
-    let cors_layer = CorsLayer::new()
-        .allow_methods(cors::Any)
-        .allow_headers(cors::Any)
-        .allow_origin(cors::Any)
-        .allow_credentials(false)
-        .max_age(Duration::from_secs(3600 * 24));
+    let (access_log, access_log_writer) = AccessLog::spawn(
+        &config.access_log,
+        FromRef::from_ref(&state),
+        FromRef::from_ref(&state),
+    )
+    .map_err(|e| {
+        FatalError::new(ErrorKind::Io, e.to_string(), Some("while opening the access log".into()))
+            .exit_now()
+    })
+    .unwrap()
+    .unzip();
+
+    let cors = Arc::new(Live::new(
+        CorsRules::new(&config.server.cors).expect("`server.cors` is validated when loading"),
+    ));
+
+    let keys = Arc::new(KeyManager::new(
+        config
+            .auth
+            .jwt_decoder_config
+            .decoder
+            .clone()
+            .revocation_store(revocations.clone()),
+    ));
+    if let Some(file) = &config_file {
+        KeyReloader::new(
+            keys.clone(),
+            file.path.clone(),
+            config.auth.key_reload,
+            revocations,
+            config.auth.jwt_decoder_config,
+        )
+        .spawn();
+    }
+
+    let effective = Arc::new(Live::new(config.redacted.as_ref().clone()));
+    let reloader = config_file.map(|file| {
+        let path = PathBuf::from(&file.path);
+        let reloader = Arc::new(Mutex::new(ConfigReloader::new(
+            file,
+            effective.clone(),
+            FromRef::from_ref(&state),
+            rate_limiter.clone(),
+            cors.clone(),
+        )));
+        if config.server.watch_config
+            && let Err(e) = reload::spawn_watcher(reloader.clone(), &path)
+        {
+            tracing::warn!("Cannot watch the configuration file, use `POST /admin/reload` instead. Details: {e}");
+        }
+        reloader
+    });
+
+    let admin = api::build_admin_router(
+        FromRef::from_ref(&state),
+        keys.clone(),
+        FromRef::from_ref(&state),
+        config.auth.glob_limits,
+    );
+    let replication = Replication::spawn(
+        config.replication,
+        FromRef::from_ref(&state),
+        FromRef::from_ref(&state),
+        FromRef::from_ref(&state),
+    )
+    .map_err(|e| e.exit_now())
+    .unwrap();
+    let admin_state = AdminState::new(
+        FromRef::from_ref(&state),
+        FromRef::from_ref(&state),
+        effective,
+        reloader,
+        Arc::new(replication),
+    );
+
+    // 收到停止信号之后不再接受新的连接，正在上传的 object 仍然可以写完
+    let shutdown = Shutdown::listen();
+    let timeout = Duration::from_secs(config.server.shutdown_timeout);
+
+    GcTask::new(
+        FromRef::from_ref(&state),
+        FromRef::from_ref(&state),
+        FromRef::from_ref(&state),
+        config.data.gc,
+    )
+    .spawn();
+
+    LifecycleTask::new(
+        FromRef::from_ref(&state),
+        FromRef::from_ref(&state),
+        FromRef::from_ref(&state),
+        config.data.lifecycle,
+    )
+    .spawn();
+
+    InventoryTask::new(state.clone(), config.data.inventory).spawn();
+
+    TrashTask::new(
+        FromRef::from_ref(&state),
+        FromRef::from_ref(&state),
+        config.data.trash,
+    )
+    .spawn();
 
     let app = api::build_router(
-        config.auth.jwt_decoder_config.decoder,
-        config.auth.path_rules,
+        FromRef::from_ref(&state),
+        keys,
+        FromRef::from_ref(&state),
+        config.auth.glob_limits,
+        config.server.versioning,
+        limits,
+        cors,
+        rate_limiter,
+        config.data.sniff,
+        config.server.compression,
     )
-    .await
-    .layer(cors_layer)
-    .layer(tracing_layer)
-    .layer(normalize_path_layer)
-    .with_state(state);
+    .await;
+
+    let app = match config.server.admin_port {
+        None => app.merge(admin.with_state(admin_state)),
+        Some(port) => {
+            let admin = admin
+                .with_state(admin_state)
+                .layer(tracing_layer.clone())
+                .layer(AccessLogLayer::new(access_log.clone()))
+                .layer(RequestIdLayer)
+                .layer(client_ip_layer.clone());
+            spawn_admin(port, admin, shutdown.clone()).await;
+            app
+        }
+    };
+
+    let app = app
+        .layer(tracing_layer)
+        .layer(AccessLogLayer::new(access_log))
+        .layer(RequestIdLayer)
+        .layer(client_ip_layer)
+        .with_state(state);
+
+    // hyper 只在响应中没有 `Date` 头时才会用系统时间补上
+    let app = match config.server.test_mode {
+        Some(_) => app.layer(SetResponseHeaderLayer::overriding(DATE, |_: &Response| {
+            HeaderValue::from_str(&clock::now().format("%a, %d %b %Y %H:%M:%S GMT").to_string())
+                .ok()
+        })),
+        None => app,
+    };
+
+    // Router 的 layer 在路由之后才执行，路径必须在路由之前规范化
+    let app = TrailingSlashLayer.layer(app);
 
     let listener = tokio::net::TcpListener::bind((Ipv4Addr::UNSPECIFIED, config.server.port))
         .await
         .unwrap();
+    let service = ServiceExt::<Request>::into_make_service_with_connect_info::<SocketAddr>(app);
+
+    // 经过 `tap_io` 之后 axum 才能从连接中取出客户端地址
+    let result = match config.server.tls {
+        None => {
+            tracing::info!(
+                "Server running on http://{}",
+                listener.local_addr().unwrap()
+            );
+            let listener = WriteTimeoutListener::new(listener, write_timeout).tap_io(|io| {
+                let _ = io.get_ref().set_nodelay(true);
+            });
+            let server =
+                axum::serve(listener, service).with_graceful_shutdown(shutdown.clone().requested());
+            shutdown.drain(server, timeout).await
+        }
+        Some(tls) => {
+            let certs = CertStore::open(&tls).map_err(|e| e.exit_now()).unwrap();
+            CertReloader::new(certs.clone(), &tls).spawn();
+
+            let local_addr = listener.local_addr().unwrap();
+            if let Some(port) = tls.redirect_http_port {
+                tls::spawn_redirect(port, local_addr.port()).await;
+            }
+
+            let listener = TlsListener::new(listener, certs.server_config()).unwrap();
+            let listener = WriteTimeoutListener::new(listener, write_timeout).tap_io(|io| {
+                let _ = io.get_ref().get_ref().0.set_nodelay(true);
+            });
+
+            tracing::info!("Server running on https://{local_addr}");
+            let server =
+                axum::serve(listener, service).with_graceful_shutdown(shutdown.clone().requested());
+            shutdown.drain(server, timeout).await
+        }
+    };
+
+    if let Err(e) = result {
+        tracing::error!("Server stopped unexpectedly: {e}");
+    }
+    if let Some(writer) = access_log_writer {
+        writer.finish().await;
+    }
+    logger::flush();
+}
+
+/// 检查只在启动服务时才会读取的文件，也就是路径规则文件和 TLS 证书，参见 `crab-vault config validate`
+pub fn check(config: &StaticAppConfig) -> FatalResult<()> {
+    let mut errors = MultiFatalError::new();
+
+    // 复杂度限制本身的错误已经由 `into_runtime` 报告
+    if let Some(file) = &config.auth.path_rules_file
+        && let Ok(limits) = config.auth.glob_limits.clone().into_runtime()
+        && let Err(mut e) = PathRuleStore::open(vec![], limits, Some(file))
+    {
+        errors.append(&mut e);
+    }
+
+    if let Some(tls) = &config.server.tls
+        && let Err(e) = CertStore::open(tls)
+    {
+        errors.push(e);
+    }
+
+    match errors.is_empty() {
+        true => Ok(()),
+        false => Err(errors),
+    }
+}
+
+/// 在 `port` 上单独提供管理接口，只使用 HTTP，收到停止信号时与主服务一同停止接受新的连接
+async fn spawn_admin(port: u16, admin: Router, shutdown: Shutdown) {
+    let listener = tokio::net::TcpListener::bind((Ipv4Addr::UNSPECIFIED, port))
+        .await
+        .map_err(|e| {
+            FatalError::from(e)
+                .when(format!("while binding the admin port {port}"))
+                .exit_now()
+        })
+        .unwrap();
 
     tracing::info!(
-        "Server running on http://{}",
+        "Admin endpoints running on http://{}",
         listener.local_addr().unwrap()
     );
 
-    axum::serve(listener, app.into_make_service())
+    let service = admin.into_make_service_with_connect_info::<SocketAddr>();
+    tokio::spawn(async move {
+        if let Err(e) = axum::serve(listener, service)
+            .with_graceful_shutdown(shutdown.requested())
+            .await
+        {
+            tracing::error!("Admin server stopped unexpectedly: {e}");
+        }
+    });
+}
+
+fn open_error(e: EngineError, when: &str) -> ! {
+    FatalError::new(ErrorKind::Io, e.to_string(), Some(when.into())).exit_now()
+}
+
+/// 打开意图日志，在开始处理请求之前完成上次没有完成的修改
+async fn recover(dir: &Path, data_src: &DataSource, meta_src: &MetaSource) -> Journal {
+    let journal = Journal::open(dir)
         .await
+        .map_err(|e| open_error(e, "while opening the journal"))
         .unwrap();
+    let reports = journal::replay(&journal, data_src, meta_src, true)
+        .await
+        .map_err(|e| open_error(e, "while replaying the journal"))
+        .unwrap();
+
+    for report in reports.iter().filter(|v| v.repaired) {
+        tracing::warn!(
+            "Recovered /{}/{} from the journal: {}",
+            report.bucket,
+            report.object,
+            report.finding.as_str()
+        );
+    }
+
+    journal
 }
@@ -0,0 +1,89 @@
+use std::{future::IntoFuture, io, time::Duration};
+
+use tokio::sync::watch;
+
+/// ## 进程收到的停止信号
+///
+/// 第一次收到 SIGINT 或者 SIGTERM 时开始停止：不再接受新的连接，等待正在处理的请求完成。
+/// 等待超过时限，或者再次收到信号时直接退出
+#[derive(Clone)]
+pub struct Shutdown {
+    requested: watch::Receiver<bool>,
+}
+
+impl Shutdown {
+    /// 在后台开始监听停止信号
+    pub fn listen() -> Self {
+        let (tx, requested) = watch::channel(false);
+
+        tokio::spawn(async move {
+            let signal = signal().await;
+            tracing::info!("Received {signal}, shutting down");
+            let _ = tx.send(true);
+        });
+
+        Self { requested }
+    }
+
+    /// 收到停止信号之后完成
+    pub async fn requested(mut self) {
+        // 发送端只会在发送之后退出
+        let _ = self.requested.wait_for(|v| *v).await;
+    }
+
+    /// ## 运行 `server` 直到它在收到停止信号之后处理完所有请求
+    ///
+    /// `server` 应当在 [`requested`](Self::requested) 完成时停止接受新的连接，
+    /// 超过 `timeout` 之后仍未完成的请求会被中断
+    pub async fn drain<F>(self, server: F, timeout: Duration) -> io::Result<()>
+    where
+        F: IntoFuture<Output = io::Result<()>>,
+    {
+        let deadline = async {
+            self.requested().await;
+            tokio::select! {
+                _ = tokio::time::sleep(timeout) => {
+                    tracing::warn!(
+                        "In-flight requests did not finish within {}s, aborting them",
+                        timeout.as_secs()
+                    );
+                }
+                signal = signal() => {
+                    tracing::warn!("Received {signal} again, aborting in-flight requests");
+                }
+            }
+        };
+
+        tokio::select! {
+            result = server.into_future() => {
+                tracing::info!("All in-flight requests finished");
+                result
+            }
+            _ = deadline => Ok(()),
+        }
+    }
+}
+
+/// 等待 SIGINT 或者 SIGTERM，返回信号的名称
+async fn signal() -> &'static str {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("failed to listen for SIGINT")
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("failed to listen for SIGTERM")
+            .recv()
+            .await
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<Option<()>>();
+
+    tokio::select! {
+        _ = ctrl_c => "SIGINT",
+        _ = terminate => "SIGTERM",
+    }
+}
@@ -0,0 +1,290 @@
+use std::{
+    collections::HashMap,
+    fmt, io,
+    net::{Ipv4Addr, SocketAddr},
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    time::{Duration, SystemTime},
+};
+
+use axum::{
+    Router,
+    extract::State,
+    http::{HeaderMap, StatusCode, Uri, header::HOST, uri::Authority},
+    response::{IntoResponse, Redirect, Response},
+    serve::Listener,
+};
+use clap::error::ErrorKind;
+use rustls::{
+    ServerConfig,
+    crypto::{CryptoProvider, aws_lc_rs},
+    pki_types::{CertificateDer, PrivateKeyDer, pem::PemObject},
+    server::{ClientHello, ResolvesServerCert},
+    sign::CertifiedKey,
+};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::mpsc,
+};
+use tokio_rustls::{TlsAcceptor, server::TlsStream};
+
+use crate::{app_config::server::TlsConfig, error::fatal::FatalError};
+
+/// 单个连接完成 TLS 握手的最长时间，超时的连接直接关闭
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 等待交给 hyper 的已经完成握手的连接数
+const ACCEPT_BACKLOG: usize = 64;
+
+/// ## 运行时可以整体替换的证书和私钥
+///
+/// 每次握手时取出当前的证书，替换不会影响已经建立的连接
+pub struct CertStore {
+    current: RwLock<Arc<CertifiedKey>>,
+}
+
+/// 定期检查证书和私钥文件，发生变化时重新加载，参见 [`KeyReloader`](super::key_manager::KeyReloader)
+pub struct CertReloader {
+    store: Arc<CertStore>,
+    cert_path: PathBuf,
+    key_path: PathBuf,
+    interval: Option<Duration>,
+
+    /// 需要检查的文件以及上一次看到的修改时间
+    watched: HashMap<PathBuf, Option<SystemTime>>,
+}
+
+/// ## 在 TCP 之上完成 TLS 握手的 [`Listener`]
+///
+/// 握手在单独的任务中进行，慢速的客户端不会阻塞其他连接
+pub struct TlsListener {
+    incoming: mpsc::Receiver<(TlsStream<TcpStream>, SocketAddr)>,
+    local_addr: SocketAddr,
+}
+
+impl CertStore {
+    pub fn open(config: &TlsConfig) -> Result<Arc<Self>, FatalError> {
+        let key = load_certified_key(&config.cert_path, &config.key_path)?;
+
+        Ok(Arc::new(Self {
+            current: RwLock::new(Arc::new(key)),
+        }))
+    }
+
+    /// 使用这里的证书的 rustls 配置
+    pub fn server_config(self: &Arc<Self>) -> Arc<ServerConfig> {
+        let mut config = ServerConfig::builder_with_provider(provider())
+            .with_safe_default_protocol_versions()
+            .expect("the default provider supports the default protocol versions")
+            .with_no_client_auth()
+            .with_cert_resolver(self.clone());
+        // axum 只启用了 HTTP/1
+        config.alpn_protocols = vec![b"http/1.1".to_vec()];
+        Arc::new(config)
+    }
+
+    fn current(&self) -> Arc<CertifiedKey> {
+        self.current
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    fn replace(&self, key: CertifiedKey) {
+        *self.current.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(key);
+    }
+}
+
+impl fmt::Debug for CertStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CertStore").finish_non_exhaustive()
+    }
+}
+
+impl ResolvesServerCert for CertStore {
+    fn resolve(&self, _client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        Some(self.current())
+    }
+}
+
+impl CertReloader {
+    pub fn new(store: Arc<CertStore>, config: &TlsConfig) -> Self {
+        let mut reloader = Self {
+            store,
+            cert_path: config.cert_path.clone(),
+            key_path: config.key_path.clone(),
+            interval: (config.reload_interval > 0)
+                .then(|| Duration::from_secs(config.reload_interval)),
+            watched: HashMap::new(),
+        };
+        reloader.watched = reloader.modified_times();
+        reloader
+    }
+
+    /// 在后台按照配置的间隔检查，没有配置间隔时什么都不做
+    pub fn spawn(mut self) {
+        let Some(interval) = self.interval else {
+            return;
+        };
+
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.tick().await;
+
+            loop {
+                ticker.tick().await;
+                self.tick();
+            }
+        });
+    }
+
+    fn tick(&mut self) {
+        let watched = self.modified_times();
+        if watched == self.watched {
+            return;
+        }
+        // 证书和私钥通常不是同时写入的，加载失败时等到下一次变化再试，期间继续使用旧的证书
+        self.watched = watched;
+
+        match load_certified_key(&self.cert_path, &self.key_path) {
+            Ok(key) => {
+                self.store.replace(key);
+                tracing::info!("tls certificate reloaded");
+            }
+            Err(e) => tracing::warn!(
+                error = %e.into_message(),
+                "failed to reload tls certificate, keep using the old one"
+            ),
+        }
+    }
+
+    fn modified_times(&self) -> HashMap<PathBuf, Option<SystemTime>> {
+        [&self.cert_path, &self.key_path]
+            .into_iter()
+            .map(|path| {
+                let modified = std::fs::metadata(path).and_then(|v| v.modified()).ok();
+                (path.clone(), modified)
+            })
+            .collect()
+    }
+}
+
+impl TlsListener {
+    /// 在 `listener` 上接受连接，并使用 `config` 完成握手
+    pub fn new(listener: TcpListener, config: Arc<ServerConfig>) -> io::Result<Self> {
+        let local_addr = listener.local_addr()?;
+        let acceptor = TlsAcceptor::from(config);
+        let (tx, incoming) = mpsc::channel(ACCEPT_BACKLOG);
+
+        tokio::spawn(async move {
+            loop {
+                let (stream, addr) = match listener.accept().await {
+                    Ok(v) => v,
+                    Err(e) => {
+                        tracing::warn!(error = %e, "failed to accept a tcp connection");
+                        tokio::time::sleep(Duration::from_secs(1)).await;
+                        continue;
+                    }
+                };
+
+                let (acceptor, tx) = (acceptor.clone(), tx.clone());
+                tokio::spawn(async move {
+                    match tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+                        Ok(Ok(stream)) => {
+                            let _ = tx.send((stream, addr)).await;
+                        }
+                        Ok(Err(e)) => tracing::debug!(%addr, error = %e, "tls handshake failed"),
+                        Err(_) => tracing::debug!(%addr, "tls handshake timed out"),
+                    }
+                });
+            }
+        });
+
+        Ok(Self {
+            incoming,
+            local_addr,
+        })
+    }
+}
+
+impl Listener for TlsListener {
+    type Io = TlsStream<TcpStream>;
+    type Addr = SocketAddr;
+
+    async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+        match self.incoming.recv().await {
+            Some(v) => v,
+            // 接受连接的任务只会因为 panic 而退出
+            None => std::future::pending().await,
+        }
+    }
+
+    fn local_addr(&self) -> io::Result<Self::Addr> {
+        Ok(self.local_addr)
+    }
+}
+
+/// 在 `port` 上监听 HTTP，把所有请求永久重定向到 `https_port` 上的同一个路径
+pub async fn spawn_redirect(port: u16, https_port: u16) {
+    let listener = TcpListener::bind((Ipv4Addr::UNSPECIFIED, port))
+        .await
+        .map_err(|e| {
+            FatalError::from(e)
+                .when(format!("while binding the http redirect port {port}"))
+                .exit_now()
+        })
+        .unwrap();
+
+    tracing::info!(
+        "Redirecting http://{} to https",
+        listener.local_addr().unwrap()
+    );
+
+    let app = Router::new().fallback(redirect).with_state(https_port);
+    tokio::spawn(async move { axum::serve(listener, app).await });
+}
+
+async fn redirect(State(https_port): State<u16>, headers: HeaderMap, uri: Uri) -> Response {
+    let Some(host) = headers
+        .get(HOST)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<Authority>().ok())
+    else {
+        return (StatusCode::BAD_REQUEST, "missing or invalid Host header").into_response();
+    };
+
+    let path = uri.path_and_query().map(|v| v.as_str()).unwrap_or("/");
+    let location = match https_port {
+        443 => format!("https://{}{}", host.host(), path),
+        port => format!("https://{}:{}{}", host.host(), port, path),
+    };
+
+    Redirect::permanent(&location).into_response()
+}
+
+fn provider() -> Arc<CryptoProvider> {
+    Arc::new(aws_lc_rs::default_provider())
+}
+
+/// 读取 PEM 格式的证书链和私钥，并检查两者是否匹配
+fn load_certified_key(cert_path: &Path, key_path: &Path) -> Result<CertifiedKey, FatalError> {
+    let invalid = |message: String, path: &Path| {
+        FatalError::new(
+            ErrorKind::InvalidValue,
+            message,
+            Some(format!("while loading `{}`", path.display())),
+        )
+    };
+
+    let certs = CertificateDer::pem_file_iter(cert_path)
+        .and_then(|v| v.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| invalid(e.to_string(), cert_path))?;
+    if certs.is_empty() {
+        return Err(invalid("no certificate found".into(), cert_path));
+    }
+
+    let key =
+        PrivateKeyDer::from_pem_file(key_path).map_err(|e| invalid(e.to_string(), key_path))?;
+
+    CertifiedKey::from_der(certs, key, &provider()).map_err(|e| invalid(e.to_string(), key_path))
+}
@@ -0,0 +1,73 @@
+use std::{sync::Arc, time::Duration};
+
+use crab_vault::engine::{DataSource, MetaSource, clock, error::EngineResult, trash};
+
+use crate::app_config::data::TrashConfig;
+
+/// 在后台定期清除回收站中超过了保留期的条目，参见 `DELETE /{bucket}/{object}`
+///
+/// 条目已经不在原来的 bucket 中，所以不需要记录在意图日志中，失败的条目下次扫描时再删除
+pub struct TrashTask {
+    data_src: Arc<DataSource>,
+    meta_src: Arc<MetaSource>,
+    config: TrashConfig,
+}
+
+impl TrashTask {
+    pub fn new(data_src: Arc<DataSource>, meta_src: Arc<MetaSource>, config: TrashConfig) -> Self {
+        Self {
+            data_src,
+            meta_src,
+            config,
+        }
+    }
+
+    /// 按照配置的间隔扫描，没有开启回收站或者间隔为 `0` 时什么都不做
+    pub fn spawn(self) {
+        if !self.config.enabled() || self.config.interval == 0 {
+            return;
+        }
+
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(Duration::from_secs(self.config.interval));
+
+            loop {
+                ticker.tick().await;
+                if let Err(e) = self.tick().await {
+                    tracing::warn!(error = %e, "trash purge failed");
+                }
+            }
+        });
+    }
+
+    async fn tick(&self) -> EngineResult<()> {
+        // 保留期长到无法表示时没有条目会过期
+        let Some(before) = i64::try_from(self.config.retention)
+            .ok()
+            .and_then(chrono::Duration::try_seconds)
+            .and_then(|v| clock::now().checked_sub_signed(v))
+        else {
+            return Ok(());
+        };
+        let expired = trash::expired(self.meta_src.as_ref(), before).await?;
+
+        // 一个条目失败时继续处理其他条目
+        for (bucket, entry) in expired {
+            let object = &entry.object_name;
+            match trash::remove(
+                self.data_src.as_ref(),
+                self.meta_src.as_ref(),
+                &bucket,
+                &entry,
+            )
+            .await
+            {
+                Ok(()) => tracing::info!(bucket, object, id = entry.id, "trash entry purged"),
+                Err(e) => {
+                    tracing::warn!(bucket, object, id = entry.id, error = %e, "failed to purge trash entry")
+                }
+            }
+        }
+        Ok(())
+    }
+}
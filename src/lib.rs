@@ -1,4 +1,5 @@
 pub extern crate crab_vault_auth as auth;
+pub extern crate crab_vault_client as client;
 pub extern crate crab_vault_utils as utils;
 pub extern crate crab_vault_engine as engine;
 pub extern crate crab_vault_logger as logger;
\ No newline at end of file
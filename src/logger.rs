@@ -1,36 +1,297 @@
-use crab_vault::logger::{json::JsonLogger, pretty::PrettyLogger};
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+use std::sync::{Mutex, OnceLock};
 
-use crate::app_config::logger::LoggerConfig;
+#[cfg(feature = "otlp")]
+use crab_vault::logger::otlp::OtlpExporter;
+use crab_vault::logger::{
+    LogLevel,
+    filter::TargetFilter,
+    json::{JsonLogFlusher, JsonLogger},
+    pretty::PrettyLogger,
+    scrub::Scrubber,
+    syslog::SyslogLogger,
+    writer::NonBlocking,
+};
+use tracing_subscriber::{Layer, Registry, layer::SubscriberExt, reload, util::SubscriberInitExt};
+
+use crate::app_config::logger::{LogOutput, LoggerConfig};
+
+/// 配置了 `dump_path` 时日志文件的落盘句柄，subscriber 是全局的，所以它也是
+static JSON_LOG: OnceLock<JsonLogFlusher> = OnceLock::new();
+
+/// 配置了 `otlp_endpoint` 时的 span 导出器，退出之前需要把剩下的 span 发送出去
+#[cfg(feature = "otlp")]
+static OTLP: OnceLock<OtlpExporter> = OnceLock::new();
+
+/// 记录在 span 上的 object 名称同样需要脱敏，但 span 上的字段在各个日志层中是分开处理的
+static SCRUBBER: OnceLock<Scrubber> = OnceLock::new();
+
+/// 开启了 `logger.async` 时控制台日志的后台线程，退出之前需要等它写完
+static CONSOLE_OUTPUT: OnceLock<NonBlocking> = OnceLock::new();
+
+/// 所有输出的日志层的句柄，重新加载配置时通过它们修改 `logger.level` 和 `logger.filter`
+static OUTPUTS: OnceLock<Outputs> = OnceLock::new();
+
+type BoxedLayer = Box<dyn Layer<Registry> + Send + Sync>;
+type ReloadableLayer = (BoxedLayer, Box<dyn ReloadHandle>);
+
+/// 各个输出共用的最低级别和按照模块设置的级别，修改时逐个重新加载每个输出
+struct Outputs {
+    current: Mutex<(LogLevel, TargetFilter)>,
+    handles: Vec<Box<dyn ReloadHandle>>,
+}
+
+/// 一个输出的日志层的 [`reload::Handle`]
+trait ReloadHandle: Send + Sync {
+    fn set_level(&self, level: LogLevel) -> Result<(), reload::Error>;
+
+    fn set_filter(&self, filter: TargetFilter) -> Result<(), reload::Error>;
+}
 
 pub fn init(config: LoggerConfig) {
-    let logger = tracing_subscriber::registry().with(
-        PrettyLogger::new(config.level)
-            .with_ansi(config.with_ansi)
-            .with_file(config.with_file)
-            .with_target(config.with_target)
-            .with_thread(config.with_thread),
-    );
-
-    if config.dump_path.is_some() {
-        let json = JsonLogger::new(config.dump_path.clone().unwrap(), config.dump_level);
-
-        match json {
-            Ok(json) => {
-                logger
-                    .with(
-                        json.with_file(config.with_file)
-                            .with_target(config.with_target)
-                            .with_thread(config.with_thread),
-                    )
-                    .init();
+    let _ = SCRUBBER.set(config.scrubber.clone());
+
+    // 这些日志层创建失败的原因要等到 subscriber 初始化之后才能输出
+    let mut errors = vec![];
+    let mut layers = vec![];
+    let mut handles = vec![];
+    match otlp_layer(&config) {
+        Ok(layer) => layers.extend(layer),
+        Err(e) => errors.push(format!(
+            "Cannot export spans to the otlp endpoint! Details: {e}"
+        )),
+    }
+    for output in &config.outputs {
+        match output_layer(*output, &config) {
+            Ok(Some((layer, handle))) => {
+                layers.push(layer);
+                handles.push(handle);
             }
-            Err(e) => {
-                logger.init();
-                tracing::error!("Cannot open the logger file! Details: {}", e);
+            Ok(None) => {}
+            Err(e) => errors.push(e),
+        }
+    }
+    if let Some(dump_path) = &config.dump_path {
+        match JsonLogger::new(dump_path, config.dump_level) {
+            Ok(mut json) => {
+                if let Some(capacity) = config.async_buffer {
+                    json = json.with_async_buffer(capacity);
+                }
+                let _ = JSON_LOG.set(json.flusher());
+                let (layer, handle) = reloadable(
+                    json.with_filter(config.filter.clone())
+                        .with_file(config.with_file)
+                        .with_target(config.with_target)
+                        .with_thread(config.with_thread)
+                        .with_scrubber(config.scrubber.clone()),
+                );
+                layers.push(layer);
+                handles.push(handle);
             }
+            Err(e) => errors.push(format!("Cannot open the logger file! Details: {e}")),
         }
-    } else {
-        logger.init();
+    }
+
+    let _ = OUTPUTS.set(Outputs {
+        current: Mutex::new((config.level, config.filter)),
+        handles,
+    });
+
+    // 空的 `Vec` 对所有的 callsite 都返回 `Interest::never`，会让其他日志层也收不到事件
+    let layers = (!layers.is_empty()).then_some(layers);
+    tracing_subscriber::registry().with(layers).init();
+
+    for e in errors {
+        tracing::error!("{e}");
+    }
+}
+
+/// 把 `layer` 包装成可以重新加载的日志层，同时返回它的句柄
+fn reloadable<L>(layer: L) -> ReloadableLayer
+where
+    L: Layer<Registry> + Send + Sync + 'static,
+    reload::Handle<L, Registry>: ReloadHandle,
+{
+    let (layer, handle) = reload::Layer::new(layer);
+    (layer.boxed(), Box::new(handle))
+}
+
+/// `logger.outputs` 中的一个输出，它们都是可以重新加载的
+fn output_layer(
+    output: LogOutput,
+    config: &LoggerConfig,
+) -> Result<Option<ReloadableLayer>, String> {
+    let syslog = &config.syslog;
+    match output {
+        LogOutput::Pretty => {
+            let mut console = PrettyLogger::new(config.level)
+                .with_filter(config.filter.clone())
+                .with_ansi(config.with_ansi)
+                .with_file(config.with_file)
+                .with_target(config.with_target)
+                .with_thread(config.with_thread)
+                .with_theme(config.theme.clone())
+                .with_scrubber(config.scrubber.clone());
+            if let Some(capacity) = config.async_buffer {
+                let output = NonBlocking::new(std::io::stdout(), capacity);
+                let _ = CONSOLE_OUTPUT.set(output.clone());
+                console = console.with_non_blocking(output);
+            }
+            Ok(Some(reloadable(console)))
+        }
+        LogOutput::Syslog => {
+            let layer = SyslogLogger::connect(&syslog.address, config.level)
+                .map_err(|e| {
+                    format!(
+                        "Cannot connect to syslog at `{}`! Details: {e}",
+                        syslog.address
+                    )
+                })?
+                .with_facility(syslog.facility)
+                .with_app_name(&syslog.app_name)
+                .with_filter(config.filter.clone())
+                .with_scrubber(config.scrubber.clone());
+            Ok(Some(reloadable(layer)))
+        }
+        #[cfg(unix)]
+        LogOutput::Journald => {
+            use crab_vault::logger::journald::JournaldLogger;
+
+            let layer = JournaldLogger::connect(config.level)
+                .map_err(|e| format!("Cannot connect to journald! Details: {e}"))?
+                .with_identifier(&syslog.app_name)
+                .with_filter(config.filter.clone())
+                .with_scrubber(config.scrubber.clone());
+            Ok(Some(reloadable(layer)))
+        }
+        // 加载配置时已经拒绝了
+        #[cfg(not(unix))]
+        LogOutput::Journald => Ok(None),
+    }
+}
+
+/// 导出 span 的日志层，创建导出器失败的原因要等到 subscriber 初始化之后才能输出
+#[cfg(feature = "otlp")]
+fn otlp_layer(config: &LoggerConfig) -> Result<Option<BoxedLayer>, String> {
+    let Some(endpoint) = &config.otlp_endpoint else {
+        return Ok(None);
+    };
+
+    let exporter = OtlpExporter::new(endpoint, config.level).map_err(|e| e.to_string())?;
+    let layer = exporter.layer().boxed();
+    let _ = OTLP.set(exporter);
+    Ok(Some(layer))
+}
+
+/// 没有启用 `otlp` feature 时 `otlp_endpoint` 在加载配置时就被拒绝了
+#[cfg(not(feature = "otlp"))]
+fn otlp_layer(_: &LoggerConfig) -> Result<Option<BoxedLayer>, String> {
+    Ok(None)
+}
+
+/// 修改 `logger.outputs` 中所有输出的最低级别，日志文件仍然使用 `logger.dump_level`，导出的 span 不受影响
+pub fn set_level(level: LogLevel) -> Result<(), String> {
+    let outputs = OUTPUTS.get().ok_or("the logger is not initialized")?;
+    let mut current = outputs.current.lock().unwrap_or_else(|e| e.into_inner());
+
+    for handle in &outputs.handles {
+        handle.set_level(level).map_err(|e| e.to_string())?;
+    }
+    current.0 = level;
+    Ok(())
+}
+
+/// 替换所有输出按照模块设置的级别，包括日志文件，导出的 span 不受影响
+pub fn set_filter(filter: TargetFilter) -> Result<(), String> {
+    let outputs = OUTPUTS.get().ok_or("the logger is not initialized")?;
+    let mut current = outputs.current.lock().unwrap_or_else(|e| e.into_inner());
+
+    for handle in &outputs.handles {
+        handle
+            .set_filter(filter.clone())
+            .map_err(|e| e.to_string())?;
+    }
+    current.1 = filter;
+    Ok(())
+}
+
+/// 各个输出正在使用的最低级别和按照模块设置的级别
+pub fn current_filter() -> Option<(LogLevel, TargetFilter)> {
+    let outputs = OUTPUTS.get()?;
+    let current = outputs.current.lock().unwrap_or_else(|e| e.into_inner());
+    Some(current.clone())
+}
+
+impl ReloadHandle for reload::Handle<PrettyLogger, Registry> {
+    fn set_level(&self, level: LogLevel) -> Result<(), reload::Error> {
+        self.modify(|v| v.set_level(level))
+    }
+
+    fn set_filter(&self, filter: TargetFilter) -> Result<(), reload::Error> {
+        self.modify(|v| v.set_filter(filter))
+    }
+}
+
+impl ReloadHandle for reload::Handle<SyslogLogger, Registry> {
+    fn set_level(&self, level: LogLevel) -> Result<(), reload::Error> {
+        self.modify(|v| v.set_level(level))
+    }
+
+    fn set_filter(&self, filter: TargetFilter) -> Result<(), reload::Error> {
+        self.modify(|v| v.set_filter(filter))
+    }
+}
+
+#[cfg(unix)]
+impl ReloadHandle for reload::Handle<crab_vault::logger::journald::JournaldLogger, Registry> {
+    fn set_level(&self, level: LogLevel) -> Result<(), reload::Error> {
+        self.modify(|v| v.set_level(level))
+    }
+
+    fn set_filter(&self, filter: TargetFilter) -> Result<(), reload::Error> {
+        self.modify(|v| v.set_filter(filter))
+    }
+}
+
+/// 日志文件的最低级别是单独的 `logger.dump_level`，只替换按照模块设置的级别
+impl ReloadHandle for reload::Handle<JsonLogger, Registry> {
+    fn set_level(&self, _: LogLevel) -> Result<(), reload::Error> {
+        Ok(())
+    }
+
+    fn set_filter(&self, filter: TargetFilter) -> Result<(), reload::Error> {
+        self.modify(|v| v.set_filter(filter))
+    }
+}
+
+/// 在当前的请求 span 上记录访问的 bucket 和 object，object 按照 `logger.scrub` 遮盖
+pub fn record_target(bucket: &str, object: Option<&str>) {
+    let span = tracing::Span::current();
+    span.record("bucket", bucket);
+
+    if let Some(object) = object {
+        let masked = SCRUBBER.get().and_then(|v| v.scrub_object(bucket, object));
+        span.record("object", masked.as_deref().unwrap_or(object));
+    }
+}
+
+/// 写出缓冲的控制台日志，把日志文件落盘，发送还没有导出的 span，进程退出之前调用
+pub fn flush() {
+    if let Some(console) = CONSOLE_OUTPUT.get()
+        && let Err(e) = console.flush()
+    {
+        eprintln!("Cannot flush the console logs! Details: {e}");
+    }
+
+    if let Some(json) = JSON_LOG.get()
+        && let Err(e) = json.flush()
+    {
+        eprintln!("Cannot flush the logger file! Details: {e}");
+    }
+
+    #[cfg(feature = "otlp")]
+    if let Some(otlp) = OTLP.get()
+        && let Err(e) = otlp.shutdown()
+    {
+        eprintln!("Cannot export the remaining spans! Details: {e}");
     }
 }